@@ -0,0 +1,100 @@
+//! Benchmark of the ingestion of the votes by the builder, for a race with many candidates. It
+//! compares [Builder::add_vote] with a reference that scans the list of the candidates for each
+//! choice, and checks that both give the same tabulation.
+//!
+//! ```text
+//! cargo run --release --example builder_ingestion
+//! ```
+
+use std::time::Instant;
+
+use ranked_voting::{run_election, Ballot, BallotChoice, Builder, VoteRules};
+
+const NUM_CANDIDATES: usize = 200;
+const NUM_BALLOTS: usize = 100_000;
+const NUM_RANKS: usize = 6;
+
+// A small deterministic generator, so that every run ingests the same votes.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % bound
+    }
+}
+
+// The classification of a choice by a scan of the candidates.
+fn linear_choice(names: &[String], choice: &[String]) -> BallotChoice {
+    match choice {
+        [] => BallotChoice::Undervote,
+        [s] if s.trim().is_empty() => BallotChoice::Blank,
+        [s] if names.iter().any(|n| n == s.trim()) => BallotChoice::Candidate(s.trim().to_string()),
+        [_] => BallotChoice::UndeclaredWriteIn,
+        _ => BallotChoice::Overvote,
+    }
+}
+
+fn main() {
+    let names: Vec<String> = (0..NUM_CANDIDATES).map(|i| format!("C{:03}", i)).collect();
+    let mut rng = Lcg(42);
+    let votes: Vec<Vec<Vec<String>>> = (0..NUM_BALLOTS)
+        .map(|_| {
+            (0..NUM_RANKS)
+                .map(|_| match rng.next(20) {
+                    0 => vec![format!("W{}", rng.next(50))],
+                    1 => vec!["".to_string()],
+                    // The candidates with a low index are more popular.
+                    _ => {
+                        let bound = rng.next(NUM_CANDIDATES) + 1;
+                        vec![names[rng.next(bound)].clone()]
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let empty = || {
+        Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&names)
+            .unwrap()
+    };
+
+    let mut linear = empty();
+    let start = Instant::now();
+    for choices in votes.iter() {
+        let ballot = Ballot::new(
+            choices.iter().map(|c| linear_choice(&names, c)).collect(),
+            1,
+        );
+        linear.add_ballot(ballot).unwrap();
+    }
+    let linear_elapsed = start.elapsed();
+
+    let mut hashed = empty();
+    let start = Instant::now();
+    for choices in votes.iter() {
+        hashed.add_vote(choices, 1).unwrap();
+    }
+    let hashed_elapsed = start.elapsed();
+
+    // The tallies are compared as flat rows, which do not depend on the order of the tally.
+    let result = run_election(&hashed).unwrap();
+    let linear_result = run_election(&linear).unwrap();
+    assert_eq!(result.winners, linear_result.winners);
+    assert_eq!(result.to_flat_rows(), linear_result.to_flat_rows());
+    println!(
+        "{} candidates, {} ballots, {} ranks, {} rounds, winner {:?}",
+        NUM_CANDIDATES,
+        NUM_BALLOTS,
+        NUM_RANKS,
+        result.round_stats.len(),
+        result.winners
+    );
+    println!("linear scan: {:?}", linear_elapsed);
+    println!("add_vote: {:?}", hashed_elapsed);
+}
@@ -0,0 +1,53 @@
+//! The Coombs method as a custom elimination strategy: each round eliminates the candidate
+//! ranked last by the most ballots, instead of the candidate with the fewest first choices. On
+//! the ballots below, instant-runoff elects C while Coombs elects B, the compromise candidate.
+//!
+//! ```text
+//! cargo run --example coombs
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ranked_voting::{
+    run_election1, CandidateId, EliminationStrategy, RoundContext, VoteRules, VotingErrors,
+};
+
+#[derive(Debug)]
+struct Coombs;
+
+impl EliminationStrategy for Coombs {
+    fn select(&self, ctx: &RoundContext) -> Result<Vec<CandidateId>, VotingErrors> {
+        let mut last_places: HashMap<CandidateId, u64> = HashMap::new();
+        for (ranking, count) in ctx.rankings() {
+            if let Some(last) = ranking.last() {
+                *last_places.entry(*last).or_insert(0) += count;
+            }
+        }
+        // The first candidate in the order of the candidates breaks the ties.
+        let mut selected: Option<(CandidateId, u64)> = None;
+        for (_, cid) in ctx.candidates() {
+            let count = last_places.get(cid).cloned().unwrap_or(0);
+            if selected.is_none_or(|(_, max)| count > max) {
+                selected = Some((*cid, count));
+            }
+        }
+        Ok(selected.map(|(cid, _)| cid).into_iter().collect())
+    }
+}
+
+fn main() {
+    let mut ballots: Vec<Vec<&str>> = Vec::new();
+    ballots.extend(std::iter::repeat_n(vec!["A", "B", "C"], 4));
+    ballots.extend(std::iter::repeat_n(vec!["C", "B", "A"], 3));
+    ballots.extend(std::iter::repeat_n(vec!["B", "C", "A"], 2));
+
+    let irv = run_election1(&ballots, &VoteRules::default()).unwrap();
+    let mut coombs_rules = VoteRules::default();
+    coombs_rules.elimination_strategy = Some(Arc::new(Coombs));
+    let coombs = run_election1(&ballots, &coombs_rules).unwrap();
+    println!("instant-runoff: {:?}", irv.winners);
+    println!("coombs: {:?}", coombs.winners);
+    assert_eq!(irv.winners, Some(vec!["C".to_string()]));
+    assert_eq!(coombs.winners, Some(vec!["B".to_string()]));
+}
@@ -0,0 +1,92 @@
+//! Micro-benchmark of the tabulation on long ballots: many candidates, many ranks and many
+//! rounds. It reports the time and the memory allocations of the tabulation.
+//!
+//! ```text
+//! cargo run --release --example long_ballots
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use ranked_voting::{run_election, Builder, VoteRules};
+
+// Counts the allocations, to compare the representations of the ballots.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const NUM_CANDIDATES: usize = 40;
+const NUM_BALLOTS: usize = 20_000;
+const NUM_RANKS: usize = 30;
+// Blank ranks at the end of each ballot, as in the exports of the voting machines.
+const NUM_TRAILING_BLANKS: usize = 10;
+
+// A small deterministic generator, so that every run tabulates the same election.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % bound
+    }
+}
+
+fn main() {
+    let names: Vec<String> = (0..NUM_CANDIDATES).map(|i| format!("C{:02}", i)).collect();
+    let mut builder = Builder::new(&VoteRules::default())
+        .unwrap()
+        .candidates(&names)
+        .unwrap();
+    let mut rng = Lcg(42);
+    for _ in 0..NUM_BALLOTS {
+        // The candidates with a low index are more popular.
+        let mut remaining: Vec<usize> = (0..NUM_CANDIDATES).collect();
+        let mut choices: Vec<Vec<String>> = Vec::new();
+        for _ in 0..NUM_RANKS {
+            let bound = rng.next(remaining.len()) + 1;
+            let idx = rng.next(bound);
+            choices.push(vec![names[remaining.remove(idx)].clone()]);
+        }
+        choices.extend((0..NUM_TRAILING_BLANKS).map(|_| vec!["".to_string()]));
+        builder.add_vote(&choices, 1).unwrap();
+    }
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let result = run_election(&builder).unwrap();
+    let elapsed = start.elapsed();
+    println!(
+        "{} ballots, {} ranks, {} rounds, winner {:?}",
+        NUM_BALLOTS,
+        NUM_RANKS + NUM_TRAILING_BLANKS,
+        result.round_stats.len(),
+        result.winners
+    );
+    println!("time: {:?}", elapsed);
+    println!(
+        "allocations: {} ({} MB)",
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        (ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes) / 1_000_000
+    );
+}
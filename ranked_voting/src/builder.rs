@@ -1,12 +1,17 @@
-pub use crate::config::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::*;
+use crate::{classify_choice, CandidateName, ChoiceLabels};
 
 /// A builder for adding votes.
 ///
 /// Using the builder should be considered for performance code.
 ///
 /// ```
-/// pub use ranked_voting::Builder;
-/// pub use ranked_voting::VoteRules;
+/// use ranked_voting::Builder;
+/// use ranked_voting::VoteRules;
 /// # use ranked_voting::VotingErrors;
 ///
 /// let mut builder = Builder::new(&VoteRules::default())?
@@ -17,10 +22,51 @@ pub use crate::config::*;
 ///
 /// # Ok::<(), VotingErrors>(())
 /// ```
+///
+/// # Thread safety
+///
+/// A builder is `Send` and `Sync`: it can be moved to another thread, and shared to run the
+/// tabulation from several threads. Adding votes needs a mutable reference. To add votes from
+/// several threads, for example from parallel parsers, use [Builder::into_concurrent] instead of
+/// a `Mutex` around the builder.
 pub struct Builder {
     pub(crate) _rules: VoteRules,
     pub(crate) _candidates: Option<Vec<Candidate>>,
+    // The names of the declared candidates, to classify the choices of each vote without
+    // scanning the candidates.
+    pub(crate) _candidate_names: HashSet<CandidateName>,
     pub(crate) _votes: Vec<Ballot>,
+    // The labels of the special choices, for Builder::add_vote.
+    pub(crate) _labels: ChoiceLabels,
+    // The number of invalid ballots that Builder::extend_from accepts before failing.
+    pub(crate) _error_budget: u64,
+    // Stops the tabulation between two rounds when it is set.
+    pub(crate) _cancel: Option<Arc<AtomicBool>>,
+    // The number of candidates listed in the log of each round (all of them if None).
+    pub(crate) _log_top: Option<usize>,
+    // The share of the ballots whose reading is logged (see Builder::debug_sample_rate).
+    pub(crate) _debug_sample_rate: SampleRate,
+}
+
+/// What happened to the items given to [Builder::extend_from].
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct IngestReport {
+    /// The number of items that were turned into ballots.
+    pub accepted: u64,
+    /// The number of distinct ballots added to the builder. The identical ballots are added
+    /// once, with the sum of their counts.
+    pub aggregated: u64,
+    /// The number of items that failed, for each error, in the order of their first
+    /// occurrence.
+    pub errors: Vec<(VotingErrors, u64)>,
+}
+
+impl IngestReport {
+    /// The number of items that failed.
+    pub fn num_errors(&self) -> u64 {
+        self.errors.iter().map(|(_, n)| n).sum()
+    }
 }
 
 impl Builder {
@@ -28,25 +74,142 @@ impl Builder {
         Ok(Builder {
             _rules: rules.clone(),
             _candidates: None,
+            _candidate_names: HashSet::new(),
             _votes: Vec::new(),
+            _labels: ChoiceLabels::default(),
+            _error_budget: 0,
+            _cancel: None,
+            _log_top: None,
+            _debug_sample_rate: SampleRate::NONE,
         })
     }
 
+    /// The number of items that [Builder::extend_from] may reject before failing (0 by
+    /// default: the first error fails).
+    pub fn error_budget(self, budget: u64) -> Builder {
+        Builder {
+            _error_budget: budget,
+            ..self
+        }
+    }
+
+    /// The labels of the special choices of [Builder::add_vote] (none by default).
+    pub fn labels(self, labels: ChoiceLabels) -> Builder {
+        Builder {
+            _labels: labels,
+            ..self
+        }
+    }
+
+    /// A flag that cancels the tabulation when it is set, for example from a signal handler.
+    /// The flag is checked once each round is complete: the tabulation then fails with
+    /// [VotingErrors::Interrupted], after at least one round.
+    pub fn cancellation(self, flag: Arc<AtomicBool>) -> Builder {
+        Builder {
+            _cancel: Some(flag),
+            ..self
+        }
+    }
+
+    /// Lists at most `top` candidates by votes in the log of each round, plus the candidates
+    /// elected or eliminated in the round. The other candidates are summarized in one line. All
+    /// the candidates are listed by default. The results are not affected.
+    pub fn log_top(self, top: usize) -> Builder {
+        Builder {
+            _log_top: Some(top),
+            ..self
+        }
+    }
+
+    /// Logs the reading of a share of the ballots at the debug level: their choices, and
+    /// whether they count for a candidate in the first round or why they are inactive. The
+    /// ballots are chosen by their [Ballot::id], or by their position in the list of ballots
+    /// (`#1`, `#2`...) if they have none, so that two runs on the same ballots log the same ones.
+    /// No ballot is logged by default ([SampleRate::NONE]).
+    ///
+    /// A debugging aid of timrcv (`--debug-sample-rate`), outside of the stable API.
+    #[doc(hidden)]
+    pub fn debug_sample_rate(self, rate: SampleRate) -> Builder {
+        Builder {
+            _debug_sample_rate: rate,
+            ..self
+        }
+    }
+
+    /// Declares the candidates. The names are normalized with
+    /// [VoteRules::name_normalization].
     pub fn candidates(self, cands: &[String]) -> Result<Builder, VotingErrors> {
+        let names: Vec<CandidateName> = cands
+            .iter()
+            .map(|name| CandidateName::new(name, self._rules.name_normalization))
+            .collect();
         Ok(Builder {
-            _rules: self._rules,
             _candidates: Some(
-                cands
-                    .to_vec()
+                names
                     .iter()
                     .map(|name| Candidate {
                         name: name.clone(),
                         code: None,
                         excluded: false,
+                        inferred: false,
                     })
                     .collect(),
             ),
+            _candidate_names: names.into_iter().collect(),
             _votes: Vec::new(),
+            ..self
+        })
+    }
+
+    /// Excludes some of the declared candidates: they are never continuing, and the ballots
+    /// that rank them go to their next ranked candidate from the first round. The names that are
+    /// not declared are rejected with [VotingErrors::UnknownCandidate].
+    ///
+    /// ```
+    /// use ranked_voting::*;
+    ///
+    /// let names: Vec<String> = ["Anna", "Bob", "Clara"].iter().map(|s| s.to_string()).collect();
+    /// let mut builder = Builder::new(&VoteRules::default())?
+    ///     .candidates(&names)?
+    ///     .exclude(&["Clara".to_string()])?;
+    /// builder.add_vote_simple(&["Clara".to_string()])?;
+    /// builder.add_vote(&[vec!["Clara".to_string()], vec!["Bob".to_string()]], 2)?;
+    /// builder.add_vote_simple(&["Anna".to_string()])?;
+    /// let result = run_election(&builder)?;
+    /// assert_eq!(result.excluded, vec![("Clara".to_string(), 3)]);
+    /// assert_eq!(result.round_stats[0].tally, vec![("Bob".to_string(), 2), ("Anna".to_string(), 1)]);
+    /// # Ok::<(), VotingErrors>(())
+    /// ```
+    pub fn exclude(self, names: &[String]) -> Result<Builder, VotingErrors> {
+        let mut candidates = self._candidates.clone().unwrap_or_default();
+        for name in names {
+            let name = CandidateName::new(name, self._rules.name_normalization);
+            match candidates.iter_mut().find(|c| c.name == name) {
+                Some(c) => c.excluded = true,
+                None => return Err(VotingErrors::UnknownCandidate(name.to_string())),
+            }
+        }
+        Ok(Builder {
+            _candidates: Some(candidates),
+            ..self
+        })
+    }
+
+    /// Marks some of the declared candidates as inferred: their names were found in the ballots
+    /// instead of being declared, and [CandidateInfo::inferred] is set for them. The names that
+    /// are not declared are rejected with [VotingErrors::UnknownCandidate].
+    pub fn inferred(self, names: &[String]) -> Result<Builder, VotingErrors> {
+        let mut candidates = self._candidates.clone().unwrap_or_default();
+        for name in names {
+            let name = CandidateName::new(name, self._rules.name_normalization);
+            match candidates.iter_mut().find(|c| c.name == name) {
+                Some(c) => c.inferred = true,
+                None => return Err(VotingErrors::UnknownCandidate(name.to_string())),
+            }
+        }
+        Ok(Builder {
+            _candidates: Some(candidates),
+            ..self
         })
     }
 
@@ -61,36 +224,284 @@ impl Builder {
     /// Adds a vote, with a potential weight attached to it.
     ///
     /// candidates: the list of choices made by the voter, in order. Choices do not need to be unique,
-    /// or distinct or non-empty.
+    /// or distinct or non-empty. Each group of names is one rank, classified with
+    /// [classify_choice] and the labels of [Builder::labels], as the readers of the command line
+    /// do. A choice that matches a declared candidate (see [VoteRules::name_normalization]) is
+    /// stored with the name of the declaration.
     pub fn add_vote(&mut self, candidates: &[Vec<String>], count: u32) -> Result<(), VotingErrors> {
-        let mut choices: Vec<BallotChoice> = Vec::new();
-        for c in candidates {
-            let cand = match c.as_slice() {
-                [] => BallotChoice::Undervote,
-                [s] if s.is_empty() => BallotChoice::Blank,
-                [s] => {
-                    if let Some(valid_candidates) = self._candidates.as_deref() {
-                        if valid_candidates.iter().any(|cd| cd.name == *s) {
-                            BallotChoice::Candidate(s.clone())
-                        } else {
-                            BallotChoice::UndeclaredWriteIn
+        let choices = self.classify(candidates);
+        self.add_ballot(Ballot {
+            count: count as u64,
+            candidates: choices,
+            challenged: false,
+            id: None,
+        })
+    }
+
+    // The choices of the ranks of a vote, as in Builder::add_vote.
+    fn classify(&self, candidates: &[Vec<String>]) -> Vec<BallotChoice> {
+        let names = self._candidates.as_ref().map(|_| &self._candidate_names);
+        candidates
+            .iter()
+            .map(|group| {
+                classify_choice(group, names, self._rules.name_normalization, &self._labels)
+            })
+            .collect()
+    }
+
+    /// Adds a ballot, which is stored as is, without copying it.
+    pub fn add_ballot(&mut self, vote: Ballot) -> Result<(), VotingErrors> {
+        self._votes.push(vote);
+        Ok(())
+    }
+
+    /// Adds a copy of a ballot.
+    #[deprecated(note = "use add_ballot, which takes the ballot without copying it")]
+    pub fn add_vote_2(&mut self, vote: &Ballot) -> Result<(), VotingErrors> {
+        self.add_ballot(vote.clone())
+    }
+
+    /// The ballots of the builder, in the order they were added, without copying them.
+    ///
+    /// The ballots given to [Builder::add_ballot] can be taken back after the tabulation, which
+    /// only borrows the builder.
+    pub fn into_ballots(self) -> Vec<Ballot> {
+        self._votes
+    }
+
+    /// Adds the ballots of an iterator, converting each item with the function `f`.
+    ///
+    /// The items are processed one at a time, so that the input does not need to be in memory.
+    /// The identical ballots of the iterator are aggregated into one ballot, with the sum of
+    /// their counts. An item for which `f` fails is counted in the report and skipped, until
+    /// there are more failures than the error budget (see [Builder::error_budget]): the
+    /// error is then returned. The ballots added until then are kept.
+    ///
+    /// Ingesting JSON Lines with serde:
+    ///
+    /// ```
+    /// use ranked_voting::*;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Record {
+    ///     ranks: Vec<String>,
+    /// }
+    ///
+    /// let lines = r#"
+    /// {"ranks": ["Anna", "Bob"]}
+    /// {"ranks": ["Bob"]}
+    /// {"ranks": ["Anna", "Bob"]}
+    /// {"rank": ["Bob"]}
+    /// "#;
+    ///
+    /// let mut builder = Builder::new(&VoteRules::default())?
+    ///     .candidates(&["Anna".to_string(), "Bob".to_string()])?
+    ///     .error_budget(10);
+    /// let records = serde_json::Deserializer::from_str(lines).into_iter::<Record>();
+    /// let report = builder.extend_from(records, |record| {
+    ///     let record = record.map_err(|e| VotingErrors::InvalidBallot(e.to_string()))?;
+    ///     Ok(Ballot::new(
+    ///         record.ranks.into_iter().map(BallotChoice::Candidate).collect(),
+    ///         1,
+    ///     ))
+    /// })?;
+    /// assert_eq!(report.accepted, 3);
+    /// assert_eq!(report.aggregated, 2);
+    /// assert_eq!(report.num_errors(), 1);
+    ///
+    /// let result = run_election(&builder)?;
+    /// assert_eq!(result.winners, Some(vec!["Anna".to_string()]));
+    /// # Ok::<(), VotingErrors>(())
+    /// ```
+    ///
+    /// With the default error budget, the first invalid item fails:
+    ///
+    /// ```
+    /// use ranked_voting::*;
+    ///
+    /// let mut builder = Builder::new(&VoteRules::default())?;
+    /// let lines = "[\"Anna\"]\n[\"Bob\"\n";
+    /// let items = serde_json::Deserializer::from_str(lines).into_iter::<Vec<String>>();
+    /// let res = builder.extend_from(items, |item| {
+    ///     let names = item.map_err(|e| VotingErrors::InvalidBallot(e.to_string()))?;
+    ///     Ok(Ballot::new(
+    ///         names.into_iter().map(BallotChoice::Candidate).collect(),
+    ///         1,
+    ///     ))
+    /// });
+    /// assert!(matches!(res, Err(VotingErrors::InvalidBallot(_))));
+    /// # Ok::<(), VotingErrors>(())
+    /// ```
+    pub fn extend_from<I, T, F>(&mut self, iter: I, mut f: F) -> Result<IngestReport, VotingErrors>
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(T) -> Result<Ballot, VotingErrors>,
+    {
+        let mut report = IngestReport::default();
+        // The position in self._votes of each distinct ballot added by this call.
+        let mut positions: HashMap<(Vec<BallotChoice>, bool), usize> = HashMap::new();
+        for item in iter {
+            match f(item) {
+                Ok(ballot) => {
+                    report.accepted += 1;
+                    let key = (ballot.candidates, ballot.challenged);
+                    match positions.get(&key) {
+                        Some(&pos) => self._votes[pos].count += ballot.count,
+                        None => {
+                            self._votes.push(Ballot {
+                                candidates: key.0.clone(),
+                                count: ballot.count,
+                                challenged: key.1,
+                                id: None,
+                            });
+                            positions.insert(key, self._votes.len() - 1);
+                            report.aggregated += 1;
                         }
-                    } else {
-                        BallotChoice::Candidate(s.clone())
                     }
                 }
-                _ => BallotChoice::Overvote,
-            };
-            choices.push(cand);
+                Err(e) => {
+                    match report.errors.iter_mut().find(|(e2, _)| *e2 == e) {
+                        Some((_, n)) => *n += 1,
+                        None => report.errors.push((e.clone(), 1)),
+                    }
+                    if report.num_errors() > self._error_budget {
+                        return Err(e);
+                    }
+                }
+            }
         }
-        self.add_vote_2(&Ballot {
+        Ok(report)
+    }
+
+    /// A handle to add the votes from several threads. Each thread takes its own
+    /// [BuilderShard] with [ConcurrentBuilder::shard], which classifies and aggregates its votes
+    /// without any lock. [ConcurrentBuilder::finish] then adds the ballots of all the shards to
+    /// the builder, in the order in which the shards were taken.
+    ///
+    /// ```
+    /// use ranked_voting::*;
+    ///
+    /// let names: Vec<String> = ["Anna", "Bob"].iter().map(|s| s.to_string()).collect();
+    /// let builder = Builder::new(&VoteRules::default())?.candidates(&names)?;
+    /// let concurrent = builder.into_concurrent();
+    /// std::thread::scope(|s| {
+    ///     for part in 0..4 {
+    ///         let concurrent = &concurrent;
+    ///         s.spawn(move || {
+    ///             let mut shard = concurrent.shard();
+    ///             shard.add_vote_simple(&["Anna".to_string()]);
+    ///             if part == 0 {
+    ///                 shard.add_vote_simple(&["Bob".to_string()]);
+    ///             }
+    ///         });
+    ///     }
+    /// });
+    /// let builder = concurrent.finish();
+    /// let result = run_election(&builder)?;
+    /// assert_eq!(result.round_stats[0].tally, vec![("Anna".to_string(), 4), ("Bob".to_string(), 1)]);
+    /// # Ok::<(), VotingErrors>(())
+    /// ```
+    pub fn into_concurrent(self) -> ConcurrentBuilder {
+        ConcurrentBuilder {
+            builder: self,
+            shards: Mutex::new(Vec::new()),
+            next_shard: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// A builder that receives the votes from several threads (see [Builder::into_concurrent]).
+///
+/// It is `Sync`: the threads share a reference to it, and each one adds its votes to its own
+/// [BuilderShard].
+pub struct ConcurrentBuilder {
+    builder: Builder,
+    // The ballots of the dropped shards, with the order in which the shards were taken.
+    shards: Mutex<Vec<(usize, Vec<Ballot>)>>,
+    next_shard: AtomicUsize,
+}
+
+impl ConcurrentBuilder {
+    /// A new shard, for the votes of one thread.
+    pub fn shard(&self) -> BuilderShard<'_> {
+        BuilderShard {
+            parent: self,
+            index: self.next_shard.fetch_add(1, Ordering::Relaxed),
+            votes: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// The builder with the ballots of all the shards. The shards must have been dropped: the
+    /// borrow checker ensures it for the shards taken in scoped threads.
+    pub fn finish(self) -> Builder {
+        let mut shards = self
+            .shards
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        shards.sort_by_key(|(index, _)| *index);
+        let mut builder = self.builder;
+        for (_, votes) in shards {
+            builder._votes.extend(votes);
+        }
+        builder
+    }
+}
+
+/// The votes of one thread for a [ConcurrentBuilder]. The identical ballots of the shard are
+/// aggregated into one ballot, with the sum of their counts. The ballots are given to the
+/// concurrent builder when the shard is dropped.
+pub struct BuilderShard<'a> {
+    parent: &'a ConcurrentBuilder,
+    index: usize,
+    votes: Vec<Ballot>,
+    // The position in votes of each distinct ballot.
+    positions: HashMap<(Vec<BallotChoice>, bool), usize>,
+}
+
+impl BuilderShard<'_> {
+    /// Adds a vote, as [Builder::add_vote_simple].
+    pub fn add_vote_simple(&mut self, candidates: &[String]) {
+        self.add_vote(&[candidates.to_vec()], 1)
+    }
+
+    /// Adds a vote, classified as in [Builder::add_vote].
+    pub fn add_vote(&mut self, candidates: &[Vec<String>], count: u32) {
+        let choices = self.parent.builder.classify(candidates);
+        self.add_ballot(Ballot {
             count: count as u64,
             candidates: choices,
+            challenged: false,
+            id: None,
         })
     }
 
-    pub fn add_vote_2(&mut self, vote: &Ballot) -> Result<(), VotingErrors> {
-        self._votes.push(vote.clone());
-        Ok(())
+    /// Adds a ballot, as [Builder::add_ballot].
+    pub fn add_ballot(&mut self, vote: Ballot) {
+        let key = (vote.candidates, vote.challenged);
+        match self.positions.get(&key) {
+            Some(&pos) => self.votes[pos].count += vote.count,
+            None => {
+                self.votes.push(Ballot {
+                    candidates: key.0.clone(),
+                    count: vote.count,
+                    challenged: key.1,
+                    id: None,
+                });
+                self.positions.insert(key, self.votes.len() - 1);
+            }
+        }
+    }
+}
+
+impl Drop for BuilderShard<'_> {
+    fn drop(&mut self) {
+        let votes = std::mem::take(&mut self.votes);
+        self.parent
+            .shards
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((self.index, votes));
     }
 }
@@ -0,0 +1,153 @@
+/*!
+The classification of the choices of a ballot.
+
+The same function, [classify_choice], turns the content of a rank into a [BallotChoice] for
+[crate::Builder::add_vote] and for the readers of the command line, so that a ballot tabulates in
+the same way whatever the path it takes.
+*/
+
+use std::collections::HashSet;
+
+use crate::{BallotChoice, CandidateName, NameNormalization};
+
+/// The label of the undeclared write-ins.
+pub const UNDECLARED_WRITE_IN_LABEL: &str = "UWI";
+
+/// The labels that mark the special choices in the ballots. None of them is set by default.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct ChoiceLabels {
+    /// An entry with this label is an undervote.
+    pub undervote_label: Option<String>,
+    /// An entry with this label is an overvote.
+    pub overvote_label: Option<String>,
+    /// The separator of the candidates of an entry that marks an overvote, such as `|` in
+    /// `Anna|Bob`.
+    pub overvote_delimiter: Option<String>,
+    /// A blank entry is an undeclared write-in instead of a blank.
+    pub blank_as_undeclared_write_in: bool,
+}
+
+/// The declared candidate of an entry, in the form of its declaration.
+fn declared(
+    names: &HashSet<CandidateName>,
+    normalization: NameNormalization,
+    entry: &str,
+) -> Option<String> {
+    names
+        .get(&CandidateName::new(entry, normalization))
+        .map(|name| name.as_str().to_string())
+}
+
+/// Classifies the content of one rank of a ballot. `candidates` are the declared candidates, or
+/// None if every name is a candidate.
+///
+/// The content of a rank is a group of entries:
+///
+/// - an empty group is an [BallotChoice::Undervote];
+/// - the whitespace around the entries is ignored, the blank entries of a group of several entries
+///   are dropped, and the entries that have the same matching key (see [NameNormalization]) count
+///   once. A group with a single entry left is that entry, and a group with only blank entries is a
+///   single blank;
+/// - a group with several distinct entries left is an [BallotChoice::Overvote].
+///
+/// A single entry is, in this order:
+///
+/// - a declared candidate, with the name of the declaration;
+/// - `UWI`: an [BallotChoice::UndeclaredWriteIn];
+/// - the undervote or the overvote label of [ChoiceLabels];
+/// - a blank: [BallotChoice::Blank], or an undeclared write-in with
+///   [ChoiceLabels::blank_as_undeclared_write_in];
+/// - an entry with the overvote delimiter whose fragments are all declared candidates: the
+///   candidate if there is only one of them, an overvote otherwise;
+/// - any other name: an undeclared write-in, or the candidate of that name if no candidates are
+///   declared.
+///
+/// ```
+/// use ranked_voting::*;
+/// use std::collections::HashSet;
+///
+/// let names: HashSet<CandidateName> = ["Anna", "Bob"]
+///     .iter()
+///     .map(|n| CandidateName::new(n, NameNormalization::Trim))
+///     .collect();
+/// let labels = ChoiceLabels::default();
+/// let classify = |group: &[&str]| {
+///     let group: Vec<String> = group.iter().map(|s| s.to_string()).collect();
+///     classify_choice(&group, Some(&names), NameNormalization::Trim, &labels)
+/// };
+/// assert_eq!(classify(&[" Anna"]), BallotChoice::Candidate("Anna".to_string()));
+/// assert_eq!(classify(&["Anna", "Anna ", ""]), BallotChoice::Candidate("Anna".to_string()));
+/// assert_eq!(classify(&["Anna", "Bob"]), BallotChoice::Overvote);
+/// assert_eq!(classify(&["Clara"]), BallotChoice::UndeclaredWriteIn);
+/// assert_eq!(classify(&[" "]), BallotChoice::Blank);
+/// assert_eq!(classify(&[]), BallotChoice::Undervote);
+/// ```
+pub fn classify_choice(
+    group: &[String],
+    candidates: Option<&HashSet<CandidateName>>,
+    normalization: NameNormalization,
+    labels: &ChoiceLabels,
+) -> BallotChoice {
+    if group.is_empty() {
+        return BallotChoice::Undervote;
+    }
+    let mut keys: HashSet<String> = HashSet::new();
+    let entries: Vec<&str> = group
+        .iter()
+        .map(|e| e.trim())
+        .filter(|e| !e.is_empty())
+        .filter(|e| keys.insert(normalization.key(e).into_owned()))
+        .collect();
+    match entries.as_slice() {
+        [] => classify_entry("", candidates, normalization, labels),
+        [e] => classify_entry(e, candidates, normalization, labels),
+        _ => BallotChoice::Overvote,
+    }
+}
+
+fn classify_entry(
+    entry: &str,
+    candidates: Option<&HashSet<CandidateName>>,
+    normalization: NameNormalization,
+    labels: &ChoiceLabels,
+) -> BallotChoice {
+    if let Some(name) = candidates.and_then(|names| declared(names, normalization, entry)) {
+        return BallotChoice::Candidate(name);
+    }
+    match entry {
+        UNDECLARED_WRITE_IN_LABEL => BallotChoice::UndeclaredWriteIn,
+        e if labels.undervote_label.as_deref() == Some(e) => BallotChoice::Undervote,
+        e if labels.overvote_label.as_deref() == Some(e) => BallotChoice::Overvote,
+        "" if labels.blank_as_undeclared_write_in => BallotChoice::UndeclaredWriteIn,
+        "" => BallotChoice::Blank,
+        e => match (candidates, labels.overvote_delimiter.as_deref()) {
+            (None, _) => BallotChoice::Candidate(e.to_string()),
+            (Some(names), Some(delim)) if e.contains(delim) => {
+                delimited(e, delim, names, normalization).unwrap_or(BallotChoice::UndeclaredWriteIn)
+            }
+            _ => BallotChoice::UndeclaredWriteIn,
+        },
+    }
+}
+
+// Interprets an entry that contains the overvote delimiter. Returns None if the entry is not a
+// list of declared candidates.
+fn delimited(
+    entry: &str,
+    delim: &str,
+    names: &HashSet<CandidateName>,
+    normalization: NameNormalization,
+) -> Option<BallotChoice> {
+    let mut fragments: Vec<String> = entry
+        .split(delim)
+        .filter(|f| !f.trim().is_empty())
+        .map(|f| declared(names, normalization, f))
+        .collect::<Option<Vec<String>>>()?;
+    fragments.sort();
+    fragments.dedup();
+    match fragments.as_slice() {
+        [f] => Some(BallotChoice::Candidate(f.clone())),
+        // An entry with only the delimiter marks an overvote without the names.
+        _ => Some(BallotChoice::Overvote),
+    }
+}
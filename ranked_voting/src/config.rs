@@ -1,8 +1,12 @@
 // ********* Input data structures ***********
 
+use std::cmp::Ordering;
 use std::default::Default;
 use std::error::Error;
 use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::{CandidateName, EliminationStrategy, NameNormalization};
 
 /// All the possible states corresponding to a choice in a ballot.
 ///
@@ -28,8 +32,10 @@ pub enum BallotChoice {
 /// A ballot submitted by a voter.
 ///
 /// This is a low-level interface that is meant to express all the situations
-/// found in practice.
+/// found in practice. A ballot is created with [Ballot::new], and marked with
+/// [Ballot::challenged] or [Ballot::with_id]: new fields may be added.
 #[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
 pub struct Ballot {
     /// Ranked candidates in the ballot. The order of the candidates in
     /// the list indicates the rank of the choices made by the voter.
@@ -37,8 +43,43 @@ pub struct Ballot {
     /// A count associated to a ballot (typically 1). Ballots with
     /// a count of zero are immediately exhausted.
     pub count: u64,
+    /// True if the ballot is challenged or provisional, pending adjudication.
+    /// The tabulation does not look at this flag: it is up to the caller to
+    /// decide whether such ballots are included.
+    pub challenged: bool,
+    /// The id of the ballot in its source, only used to choose the ballots that are logged (see
+    /// `Builder::debug_sample_rate`). The ballots without an id are chosen by their position.
+    pub id: Option<String>,
 }
 
+impl Ballot {
+    /// A ballot with the given choices and count, which is not challenged and has no id.
+    pub fn new(candidates: Vec<BallotChoice>, count: u64) -> Ballot {
+        Ballot {
+            candidates,
+            count,
+            challenged: false,
+            id: None,
+        }
+    }
+
+    /// The same ballot, marked as challenged or provisional.
+    pub fn challenged(mut self) -> Ballot {
+        self.challenged = true;
+        self
+    }
+
+    /// The same ballot, with the id of its source.
+    pub fn with_id(mut self, id: impl Into<String>) -> Ballot {
+        self.id = Some(id.into());
+        self
+    }
+}
+
+/// The former name of [Ballot].
+#[deprecated(note = "use Ballot")]
+pub type Vote = Ballot;
+
 // ******** Output data structures *********
 
 /// Statistics for the elimination of the candidates.
@@ -50,18 +91,55 @@ pub struct EliminationStats {
     pub name: String,
     /// Transfers of the votes to other candidates.
     /// Includes the names of the candidates and the count of votes
-    /// associated to this transfer.
+    /// associated to this transfer. The transfers are sorted by descending count, then by
+    /// name (see [EliminationStats::sort_transfers]).
     pub transfers: Vec<(String, u64)>,
     /// The number of votes that were associated to this candidate and that
     /// do not have a transfer.
     pub exhausted: u64,
 }
 
+impl EliminationStats {
+    /// Sorts the transfers in the order of [EliminationStats::transfers]: by descending count,
+    /// then by name. A helper of timrcv, outside of the stable API.
+    #[doc(hidden)]
+    pub fn sort_transfers(&mut self) {
+        self.transfers
+            .sort_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then_with(|| n1.cmp(n2)));
+    }
+}
+
+/// The number of a round.
+///
+/// Rounds are numbered consecutively from 1. When the ballots contain undeclared write-ins
+/// in first position, the round that removes them is round 1.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, Ord, PartialOrd)]
+pub struct RoundId(pub u32);
+
+impl RoundId {
+    /// The first round.
+    pub const FIRST: RoundId = RoundId(1);
+
+    /// The round at the given position (starting with 0) in a list of rounds.
+    pub(crate) fn from_index(idx: usize) -> RoundId {
+        RoundId(idx as u32 + 1)
+    }
+}
+
+impl Display for RoundId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// Statistics for one round
+///
+/// New statistics may be added: a round is created with [RoundStats::new].
 #[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
 pub struct RoundStats {
-    /// The id of the round (starting with 0)
-    pub round: u32,
+    /// The id of the round (starting with 1)
+    pub round: RoundId,
     /// The tally for each candidate.
     pub tally: Vec<(String, u64)>,
     /// The list of candidates that are elected in this round.
@@ -69,10 +147,139 @@ pub struct RoundStats {
     /// The list of candidates that are eliminated, along with
     /// transfer information.
     pub tally_result_eliminated: Vec<EliminationStats>,
+    /// True if this round was run after the winner was declared (see
+    /// [VoteRules::continue_to_completion]). Such a round does not elect anyone.
+    pub informational: bool,
+    /// The ballots that do not count for any candidate in this round, since the start of the
+    /// tabulation. The ballots exhausted by the eliminations of a round are inactive from the
+    /// next round.
+    pub inactive_ballots: InactiveBallots,
+    /// The special events of the round, in the order they happened.
+    pub notes: Vec<RoundNote>,
+    /// The winning threshold of the round. It is 0 when the round cannot elect anyone (see
+    /// [UwiRound1Reporting]).
+    pub threshold: u64,
 }
 
-/// The result, in case of a successful election.
+impl RoundStats {
+    /// A round with the given tally, without results, notes or inactive ballots, and a
+    /// threshold of 0.
+    pub fn new(round: RoundId, tally: Vec<(String, u64)>) -> RoundStats {
+        RoundStats {
+            round,
+            tally,
+            tally_results_elected: Vec::new(),
+            tally_result_eliminated: Vec::new(),
+            informational: false,
+            inactive_ballots: InactiveBallots::default(),
+            notes: Vec::new(),
+            threshold: 0,
+        }
+    }
+}
+
+/// A special event of a round, that the tally and the transfers do not tell by themselves.
 #[derive(Eq, PartialEq, Debug, Clone)]
+pub enum RoundNote {
+    /// Several candidates had the fewest votes, and the tiebreak (see [TieBreakMode]) chose the
+    /// one to eliminate. The candidates are in the order of the tiebreak.
+    Tiebreak {
+        candidates: Vec<String>,
+        eliminated: String,
+    },
+    /// Several candidates were eliminated together (see [EliminationAlgorithm::Batch]).
+    BatchElimination { count: usize },
+    /// The elimination strategy of the rules (see [VoteRules::elimination_strategy]) chose this
+    /// candidate.
+    Forced { candidate: String },
+    /// The round transfers the undeclared write-ins in first position.
+    UwiElimination,
+    /// The round was run after the winner was declared (see [RoundStats::informational]).
+    Informational,
+    /// The threshold of the round follows [UwiRound1Reporting]. A threshold of 0 elects nobody.
+    ThresholdPolicy {
+        reporting: UwiRound1Reporting,
+        threshold: u64,
+    },
+}
+
+/// The inactive ballots, by the reason why they stopped counting.
+///
+/// The reasons are the categories of the "Inactive Ballots" block of the RCTab summaries. The
+/// ballots without any mark are not inactive ballots: they are never counted.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct InactiveBallots {
+    /// The ballots that do not rank any continuing candidate.
+    pub exhausted_choices: u64,
+    /// The ballots that reached an overvote (with [OverVoteRule::ExhaustImmediately]).
+    pub overvotes: u64,
+    /// The ballots that skipped too many rankings (see [VoteRules::max_skipped_rank_allowed]).
+    pub skipped_rankings: u64,
+    /// The ballots that ranked a candidate again (with [DuplicateCandidateMode::Exhaust]).
+    pub repeated_rankings: u64,
+}
+
+impl InactiveBallots {
+    pub fn total(&self) -> u64 {
+        self.exhausted_choices + self.overvotes + self.skipped_rankings + self.repeated_rankings
+    }
+}
+
+/// Statistics about one rank position of the ballots, for research on ballot design.
+///
+/// A mark is anything that is not a blank or an undervote: a candidate, a write-in or an overvote.
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub struct RankPositionStats {
+    /// The rank position (starting with 1)
+    pub position: u32,
+    /// The number of votes whose last mark is at this position.
+    pub last_mark: u64,
+    /// The number of votes that skipped this position and marked a later one.
+    pub skip_then_return: u64,
+}
+
+/// Statistics about the rank positions of all the ballots, before applying the rules.
+/// The counts are weighted by the count of each ballot.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct RankStatistics {
+    /// The statistics for each position, up to the longest ballot.
+    pub positions: Vec<RankPositionStats>,
+    /// The number of votes.
+    pub total_votes: u64,
+    /// The number of votes that skipped at least one position and marked a later one.
+    pub skip_then_return_votes: u64,
+}
+
+impl RankStatistics {
+    /// The percentage of the votes that skipped a position and marked a later one.
+    pub fn skip_then_return_percent(&self) -> f64 {
+        if self.total_votes == 0 {
+            0.0
+        } else {
+            100.0 * (self.skip_then_return_votes as f64) / (self.total_votes as f64)
+        }
+    }
+}
+
+/// The votes of a candidate in one round, by the rank at which the ballots rank the candidate
+/// (see [VoteRules::track_rank_origin]).
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct RankOrigin {
+    pub round: RoundId,
+    pub candidate: String,
+    /// The votes by rank (starting with 1), by increasing rank. The ranks without votes are not
+    /// listed. The ranks are the positions in the ballots given to the tabulation, including the
+    /// skipped ones.
+    pub votes_by_rank: Vec<(u32, u64)>,
+}
+
+/// The result, in case of a successful election.
+///
+/// New statistics may be added: the default result has no round and no winner.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct VotingResult {
     /// The winner(s) of this election, if any.
     pub winners: Option<Vec<String>>,
@@ -80,6 +287,243 @@ pub struct VotingResult {
     pub threshold: u64,
     /// The statistics for each round.
     pub round_stats: Vec<RoundStats>,
+    /// The statistics for each rank position of the ballots.
+    pub rank_stats: RankStatistics,
+    /// True if the "None of the above" candidate (see [VoteRules::nota_candidate]) won the
+    /// tabulation. In this case, `winners` is None and nobody is elected.
+    pub nota_prevailed: bool,
+    /// The candidates of the tabulation, in the order of registration.
+    pub candidates: Vec<CandidateInfo>,
+    /// The eliminated candidates, in the order of elimination, then the winners (see
+    /// [EliminationEntry]). The candidates eliminated in the same round (batch elimination) are
+    /// sorted by ascending tally, then by name. The undeclared write-ins, the other candidates of
+    /// the round that elects the winners and the informational rounds (see
+    /// [VoteRules::continue_to_completion]) are not included. When "None of the above" prevails,
+    /// there is no winner at the end.
+    pub elimination_order: Vec<EliminationEntry>,
+    /// The number of votes that rank each winner, at any position, in the order of the winners.
+    /// All the ballots given to the tabulation are counted, with their rankings before the
+    /// truncation by [VoteRules::max_rankings_allowed]. It is empty without winner.
+    pub winner_ranked_on: Vec<(String, u64)>,
+    /// The first round in which each winner has at least the threshold of the round, in the
+    /// order of the winners. It is the "mathematical win": it may come before the round that
+    /// declares the winner, for example when a tiebreak is resolved in the same round, and the
+    /// informational rounds (see [VoteRules::continue_to_completion]) come after it. It is
+    /// empty without winner.
+    pub first_majority_round: Vec<(String, Option<RoundId>)>,
+    /// The excluded candidates (see [crate::Builder::exclude]), in the order of registration,
+    /// with the votes initially cast for them: the ballots whose first ranked candidate is
+    /// excluded. The excluded candidates are never in the tallies. Their ballots count for the
+    /// next ranked candidate from the first round, which reports them as transfers of an
+    /// elimination of the excluded candidate.
+    pub excluded: Vec<(String, u64)>,
+    /// With [VoteRules::track_rank_origin], the votes of the candidates of each round by the rank
+    /// they come from, by round, then in the order of the tally. None otherwise.
+    pub rank_origin: Option<Vec<RankOrigin>>,
+    /// With [OverVoteScope::WholeBallot], the votes of the ballots that are inactive from the
+    /// first round because of an overvote, and that would count in the first round under
+    /// [OverVoteScope::UpToNextContinuing]. They are counted with the overvotes of the inactive
+    /// ballots of the first round. 0 otherwise.
+    pub whole_ballot_overvotes: u64,
+}
+
+impl VotingResult {
+    /// The share of the first round tally of each candidate, in percent with one decimal
+    /// (for example `"34.2"`), in the order of the tally.
+    ///
+    /// The total is the total of the first round tally, which is also used for the threshold:
+    /// it counts the valid ballots, including the undeclared write-ins, and not the ballots
+    /// already exhausted by the validation.
+    pub fn first_choice_percent(&self) -> Vec<(String, String)> {
+        self.first_choice_percent_rounded(PercentRounding::default())
+    }
+
+    /// The same as [VotingResult::first_choice_percent], with the given rounding of the last
+    /// decimal.
+    pub fn first_choice_percent_rounded(&self, rounding: PercentRounding) -> Vec<(String, String)> {
+        let tally = match self.round_stats.first() {
+            Some(rs) => &rs.tally,
+            None => return vec![],
+        };
+        let total: u64 = tally.iter().map(|(_, count)| count).sum();
+        tally
+            .iter()
+            .map(|(name, count)| (name.clone(), rounding.format(*count, total, 1)))
+            .collect()
+    }
+
+    /// The rounds as a flat table, with one row per candidate and per round, followed by one
+    /// row per transfer of the votes of the candidate when it is eliminated in this round.
+    ///
+    /// In each round, the candidates are sorted by descending tally, then by name. The transfers
+    /// are sorted by the name of the receiving candidate, and the exhausted votes of an
+    /// eliminated candidate are the last transfer, to [FLAT_ROW_EXHAUSTED]. A candidate that is
+    /// not in the tally of the next round is eliminated, even without any transfer. In the round
+    /// that elects the winners, the other candidates are continuing.
+    pub fn to_flat_rows(&self) -> Vec<FlatRow> {
+        self.to_flat_rows_by(|n1, n2| n1.cmp(n2))
+    }
+
+    /// The rows of [VotingResult::to_flat_rows], with the names compared by `cmp_names` instead
+    /// of the order of their bytes (for example, the collation of a locale).
+    pub fn to_flat_rows_by(&self, cmp_names: impl Fn(&str, &str) -> Ordering) -> Vec<FlatRow> {
+        let mut rows = Vec::new();
+        for (idx, rs) in self.round_stats.iter().enumerate() {
+            let next_tally = self.round_stats.get(idx + 1).map(|next| &next.tally);
+            let mut tally = rs.tally.clone();
+            tally.sort_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then_with(|| cmp_names(n1, n2)));
+            // As in the summaries, the round that elects the winners does not eliminate anyone.
+            let electing = !rs.tally_results_elected.is_empty();
+            for (name, votes) in tally.iter() {
+                let elimination = rs
+                    .tally_result_eliminated
+                    .iter()
+                    .find(|es| !electing && &es.name == name);
+                let absent_next =
+                    !electing && next_tally.is_some_and(|t| t.iter().all(|(n, _)| n != name));
+                let status = if rs.tally_results_elected.contains(name) {
+                    "elected"
+                } else if elimination.is_some() || absent_next {
+                    "eliminated"
+                } else {
+                    "continuing"
+                };
+                let row = |transfer_to: Option<String>, transfer_votes: Option<u64>| FlatRow {
+                    round: rs.round.0,
+                    candidate: name.clone(),
+                    votes: *votes,
+                    status: status.to_string(),
+                    transfer_to,
+                    transfer_votes,
+                };
+                rows.push(row(None, None));
+                if let Some(es) = elimination {
+                    let mut transfers = es.transfers.clone();
+                    transfers
+                        .sort_by(|(n1, c1), (n2, c2)| cmp_names(n1, n2).then_with(|| c1.cmp(c2)));
+                    for (to, count) in transfers {
+                        rows.push(row(Some(to), Some(count)));
+                    }
+                    if es.exhausted > 0 {
+                        rows.push(row(
+                            Some(FLAT_ROW_EXHAUSTED.to_string()),
+                            Some(es.exhausted),
+                        ));
+                    }
+                }
+            }
+        }
+        rows
+    }
+}
+
+/// The `transfer_to` of the [FlatRow] that holds the exhausted votes of an eliminated
+/// candidate.
+pub const FLAT_ROW_EXHAUSTED: &str = "exhausted";
+
+/// A row of the flat table of the rounds (see [VotingResult::to_flat_rows]).
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub struct FlatRow {
+    /// The id of the round (starting with 1).
+    pub round: u32,
+    pub candidate: String,
+    /// The tally of the candidate in this round.
+    pub votes: u64,
+    /// One of `elected`, `eliminated` or `continuing`.
+    pub status: String,
+    /// For a transfer row, the candidate that receives the votes, or [FLAT_ROW_EXHAUSTED].
+    pub transfer_to: Option<String>,
+    /// For a transfer row, the number of votes that are transferred.
+    pub transfer_votes: Option<u64>,
+}
+
+/// How a percentage is rounded to its last decimal, to match the conventions of the official
+/// reports.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum PercentRounding {
+    /// The halves are rounded up: 12.25 becomes 12.3.
+    #[default]
+    HalfUp,
+    /// The decimals beyond the last one are dropped: 12.29 becomes 12.2.
+    Truncate,
+    /// The halves are rounded to the even decimal: 12.25 becomes 12.2, 12.35 becomes 12.4.
+    HalfEven,
+}
+
+impl PercentRounding {
+    /// The share of a count in a total, in percent with the given number of decimals. The
+    /// computation is exact: a share that is exactly half-way is always seen as such. The share
+    /// of an empty total is 0.
+    ///
+    /// ```
+    /// use ranked_voting::PercentRounding;
+    ///
+    /// assert_eq!(PercentRounding::HalfUp.format(1, 8, 1), "12.5");
+    /// assert_eq!(PercentRounding::HalfUp.format(1, 16, 1), "6.3");
+    /// assert_eq!(PercentRounding::HalfEven.format(1, 16, 1), "6.2");
+    /// assert_eq!(PercentRounding::Truncate.format(2, 3, 2), "66.66");
+    /// ```
+    pub fn format(&self, count: u64, total: u64, decimals: u32) -> String {
+        let scale = 10u128.pow(decimals);
+        let units = if total == 0 {
+            0
+        } else {
+            let scaled = (count as u128) * 100 * scale;
+            let (q, r) = (scaled / total as u128, scaled % total as u128);
+            let twice_r = 2 * r;
+            let round_up = match self {
+                PercentRounding::Truncate => false,
+                PercentRounding::HalfUp => twice_r >= total as u128,
+                PercentRounding::HalfEven => {
+                    twice_r > total as u128 || (twice_r == total as u128 && q % 2 == 1)
+                }
+            };
+            q + round_up as u128
+        };
+        if decimals == 0 {
+            units.to_string()
+        } else {
+            format!(
+                "{}.{:0width$}",
+                units / scale,
+                units % scale,
+                width = decimals as usize
+            )
+        }
+    }
+}
+
+// The share of a count in a total, in percent with one decimal.
+pub(crate) fn format_percent(count: u64, total: u64) -> String {
+    PercentRounding::default().format(count, total, 1)
+}
+
+/// An entry of the order of finish of the candidates (see [VotingResult::elimination_order]).
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct EliminationEntry {
+    pub name: String,
+    /// The round of the elimination, or the round that elects a winner.
+    pub round: RoundId,
+    /// The tally of the candidate in this round, before the transfer of its votes.
+    pub votes: u64,
+    /// The share of the tally of the round, in percent with one decimal (for example
+    /// `"16.7"`).
+    pub share_percent: String,
+    /// True for the winners, which are the last entries.
+    pub elected: bool,
+}
+
+/// A candidate of the tabulation.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct CandidateInfo {
+    pub name: String,
+    pub code: Option<String>,
+    pub excluded: bool,
+    /// True if the candidates were not declared (see [crate::Builder::candidates]) and this
+    /// candidate was found in the ballots instead, or if it was marked with
+    /// [crate::Builder::inferred].
+    pub inferred: bool,
 }
 
 /// Errors that prevent the algorithm from completing successfully.
@@ -92,6 +536,14 @@ pub enum VotingErrors {
     // TODO: explain when it may happen
     NoConvergence,
     NoCandidateToEliminate,
+    /// A ballot given to the builder cannot be read. The message describes the ballot.
+    InvalidBallot(String),
+    /// The cancellation flag of the builder was set (see [crate::Builder::cancellation]). The
+    /// rounds completed before the cancellation were passed to the observer.
+    Interrupted,
+    /// A name given to the builder is not a declared candidate (see
+    /// [crate::Builder::exclude]).
+    UnknownCandidate(String),
 }
 
 impl Error for VotingErrors {}
@@ -113,13 +565,14 @@ impl Display for VotingErrors {
 pub enum TieBreakMode {
     /// Uses the order in which the candidates have been declared.
     /// The first candidate in the list will have priority over all other candidates.
+    /// When the candidates are not declared, they are sorted by name.
     UseCandidateOrder,
     /// Use a random order. The input argument is the seed to initialize the
     /// order.
     ///
     /// Note: the random mode is implemented differently than the 'rcv' program. It uses a cryptographic hash on the candidate
-    /// names instead of relying on the java primitives.
-    Random(u32),
+    /// names instead of relying on the java primitives. See [crate::tiebreak::permutation] for the order of the candidates.
+    Random(u64),
 }
 
 /// How to deal with overvotes.
@@ -141,20 +594,61 @@ pub enum OverVoteRule {
     AlwaysSkipToNextRank,
 }
 
+/// How much of the ballots is read for the overvotes under [OverVoteRule::ExhaustImmediately].
+///
+/// As an example, the ballot `[A, B, OVERVOTE]`:
+/// - under `UpToNextContinuing`, counts for A, then for B if A is eliminated. The overvote
+///   after the last continuing candidate is never read, as in the reference implementation.
+/// - under `WholeBallot`, is inactive from the first round: an overvote anywhere on the ballot
+///   exhausts it, as in some older rules.
+///
+/// The scope has no effect with [OverVoteRule::AlwaysSkipToNextRank]. The rankings after
+/// [VoteRules::max_rankings_allowed] are not read under both scopes.
+///
+/// Default: `UpToNextContinuing`.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum OverVoteScope {
+    /// The rankings are read up to the next continuing candidate of the ballot.
+    UpToNextContinuing,
+    /// All the rankings are read once, before the first round.
+    WholeBallot,
+}
+
 /// Strategy on how to deal with duplicated names.
 ///
 /// Consider the ballot `[A, B, B, C]`. After candidate `A` is eliminated:
 /// - with Exhaust, this ballot would be entirely discarded
 /// - with SkipDuplicate, this ballot would be equivalent to reducing `B` to only
-/// a single instance: `[B, C]`.
+///   a single instance: `[B, C]`.
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub enum DuplicateCandidateMode {
     Exhaust,
     SkipDuplicate,
 }
 
+/// How the first round is computed when some ballots start with undeclared write-ins.
+///
+/// These ballots are always transferred to their next valid choice after the first round.
+/// The modes differ in the threshold of the first round, and hence in whether a candidate can
+/// already be elected in the first round.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum UwiRound1Reporting {
+    /// The first round only transfers the undeclared write-ins. Its threshold is 0, and no
+    /// candidate can be elected before the second round.
+    Current,
+    /// Like the reference implementation: the threshold of the first round counts the votes
+    /// of the undeclared write-ins, and a candidate that reaches it is elected in the first
+    /// round. The transfers of the undeclared write-ins are in the same round.
+    Reference,
+}
+
 /// The sort of election to run.
 /// For now, only elections with a single winner are implemented.
+///
+/// With a single candidate (as many candidates as winners), the candidate has all the active
+/// votes and is elected in the first round. The multi-winner modes will refuse a number of winners above
+/// the number of candidates when the rules are validated, instead of electing all the candidates:
+/// `timrcv` already refuses any `numberOfWinners` other than 1.
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum WinnerElectionMode {
     SingelWinnerMajority, // TODO add the other modes
@@ -163,11 +657,11 @@ pub enum WinnerElectionMode {
 /// The elimination algorithm to apply.
 ///
 /// - Single eliminates one candidate at a time. This is the easiest to
-/// understand, but it may add many more rounds when there a lot of
-/// candidates with a comparatively very low number of votes.
+///   understand, but it may add many more rounds when there a lot of
+///   candidates with a comparatively very low number of votes.
 ///
 /// - Batch eliminates candidates more rapidly.
-/// TODO document algorithm.
+///   TODO document algorithm.
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub enum EliminationAlgorithm {
     Batch,
@@ -180,7 +674,7 @@ pub enum EliminationAlgorithm {
 /// - Unlimited would read this ballot as `[A]`
 /// - ExhaustOnFirstOccurence would discard this ballot
 /// - `MaxAllowed(3)` would read this ballot as `[A]`, but `MaxAllowed(2)` or below would
-/// exhaust the ballot.
+///   exhaust the ballot.
 ///
 /// The rule is only applied to sequences of blank or undervotes. For instance,
 /// `MaxAllowed(2)` would:
@@ -197,13 +691,17 @@ pub enum MaxSkippedRank {
 
 /// The rules that control the voting process.
 ///
-/// The easiest way to use them is to use a default instance of the rules and modify them.
+/// The easiest way to use them is to use a default instance of the rules and modify them:
+/// new rules may be added, with a default that keeps the current behaviour.
 #[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
 pub struct VoteRules {
     /// Tie break mode (see documentation)
     pub tiebreak_mode: TieBreakMode,
     /// Overvoting control (see documentation)
     pub overvote_rule: OverVoteRule,
+    /// The rankings read for the overvotes (see documentation)
+    pub overvote_scope: OverVoteScope,
     /// Winner selection (see documentation)
     pub winner_election_mode: WinnerElectionMode,
     // // TODO: remove
@@ -216,13 +714,147 @@ pub struct VoteRules {
     // pub minimum_vote_threshold: Option<u32>,
     /// Control of skipped rankings (blank or undervote)
     pub max_skipped_rank_allowed: MaxSkippedRank,
+    /// If false, only the explicit undervotes ([BallotChoice::Undervote]) count as skipped
+    /// rankings for [VoteRules::max_skipped_rank_allowed]. The blanks ([BallotChoice::Blank]),
+    /// for example from truncated data, are then ignored when reading the ballots.
+    ///
+    /// Default: true
+    pub blank_counts_as_skipped: bool,
     /// The maximum number of rankings (choices) allowed for each ballot.
     ///
-    /// If a ballot has more choices than this number, it is immediately discarded.
+    /// Only the first choices of each ballot are read: the choices after this number are
+    /// ignored. With `Some(1)`, the election is a plurality with several rounds: the ballots
+    /// of the eliminated candidates are exhausted, and never transferred.
+    ///
+    /// Default: None (no limit)
     pub max_rankings_allowed: Option<u32>,
     pub elimination_algorithm: EliminationAlgorithm,
     /// Duplicate candidate control (see documentation)
     pub duplicate_candidate_mode: DuplicateCandidateMode,
+    /// With [DuplicateCandidateMode::Exhaust], the largest number of rankings kept from a
+    /// ballot before the first round. The rankings after the first repeated candidate are
+    /// already dropped, since the ballot is exhausted there: this cap bounds the blanks, the
+    /// undervotes and the write-ins before it. A ballot that reaches the cap is exhausted like
+    /// a ballot without a next choice. It has no effect with
+    /// [DuplicateCandidateMode::SkipDuplicate].
+    ///
+    /// Default: None (no cap)
+    pub exhaust_rankings_cap: Option<u32>,
+    /// If true, the tabulation continues after the winner is declared, until
+    /// all the other candidates are eliminated. The extra rounds are marked
+    /// as informational and do not change the winner or the threshold.
+    /// An error in one of them stops them with a warning in the log, and the
+    /// rounds before it are kept.
+    ///
+    /// Default: false
+    pub continue_to_completion: bool,
+    /// If true, the random tiebreak mode uses the order of the earlier versions of this crate
+    /// ([crate::tiebreak::legacy_permutation]) instead of the keyed hash. The same seed gives
+    /// different orders in both modes, so this is only useful to reproduce older tabulations.
+    ///
+    /// Default: false
+    pub legacy_random_tiebreak: bool,
+    /// The name of a "None of the above" candidate. It is tabulated like any other candidate,
+    /// but if it wins, no candidate is elected and [VotingResult::nota_prevailed] is set.
+    ///
+    /// Default: None
+    pub nota_candidate: Option<String>,
+    /// If true, the "None of the above" candidate is never part of a batch elimination: it
+    /// can only be eliminated on its own, as the candidate with the fewest votes.
+    ///
+    /// Default: false
+    pub protect_nota: bool,
+    /// The computation of the first round when some ballots start with undeclared write-ins.
+    ///
+    /// Default: [UwiRound1Reporting::Current]
+    pub uwi_round1_reporting: UwiRound1Reporting,
+    /// How the names of the ballots are matched with the names of the candidates, and with
+    /// [VoteRules::nota_candidate].
+    ///
+    /// Default: [NameNormalization::Trim]
+    pub name_normalization: NameNormalization,
+    /// A custom selection of the candidates eliminated in each round, instead of the single or
+    /// batch elimination of [VoteRules::elimination_algorithm]. It is only set by programs that
+    /// use the library, and is not part of the configuration files.
+    ///
+    /// Default: None
+    pub elimination_strategy: Option<Arc<dyn EliminationStrategy>>,
+    /// If true, the votes of the candidates in each round are counted by the rank at which
+    /// their ballots rank them: for example, how many votes of the winner in the final round
+    /// were first choices, second choices, ... (see [VotingResult::rank_origin]). The ballots
+    /// take the same memory either way.
+    ///
+    /// Default: false
+    pub track_rank_origin: bool,
+}
+
+/// A share of the ballots, chosen by the SHA-256 digest of a key (for example the id of the
+/// ballot): the same keys are always in the sample.
+///
+/// ```
+/// use ranked_voting::SampleRate;
+///
+/// let rate: SampleRate = "0.25".parse().unwrap();
+/// assert_eq!(rate.rate(), 0.25);
+/// assert_eq!(rate.contains("ballot-1"), rate.contains("ballot-1"));
+/// assert!(!SampleRate::NONE.contains("ballot-1"));
+/// assert!("1.5".parse::<SampleRate>().is_err());
+/// ```
+///
+/// A debugging aid of timrcv, outside of the stable API.
+#[doc(hidden)]
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct SampleRate {
+    parts_per_million: u32,
+}
+
+impl SampleRate {
+    const PARTS: u32 = 1_000_000;
+
+    /// No ballot.
+    pub const NONE: SampleRate = SampleRate {
+        parts_per_million: 0,
+    };
+
+    /// The share of the ballots, between 0 and 1 (None otherwise). The precision is one ballot
+    /// per million.
+    pub fn new(rate: f64) -> Option<SampleRate> {
+        if !(0.0..=1.0).contains(&rate) {
+            return None;
+        }
+        Some(SampleRate {
+            parts_per_million: (rate * SampleRate::PARTS as f64).round() as u32,
+        })
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.parts_per_million as f64 / SampleRate::PARTS as f64
+    }
+
+    /// True if the ballot with this key is in the sample.
+    pub fn contains(&self, key: &str) -> bool {
+        match self.parts_per_million {
+            0 => false,
+            SampleRate::PARTS => true,
+            parts => {
+                let digest = sha256::digest(key);
+                let value = u64::from_str_radix(&digest[..16], 16).unwrap_or(0);
+                value % (SampleRate::PARTS as u64) < parts as u64
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SampleRate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SampleRate, String> {
+        s.trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(SampleRate::new)
+            .ok_or_else(|| format!("expected a rate between 0 and 1, got {:?}", s))
+    }
 }
 
 impl Default for VoteRules {
@@ -235,19 +867,32 @@ impl VoteRules {
     const DEFAULT_RULES: VoteRules = VoteRules {
         tiebreak_mode: TieBreakMode::UseCandidateOrder,
         overvote_rule: OverVoteRule::AlwaysSkipToNextRank,
+        overvote_scope: OverVoteScope::UpToNextContinuing,
         winner_election_mode: WinnerElectionMode::SingelWinnerMajority,
         max_skipped_rank_allowed: MaxSkippedRank::Unlimited,
+        blank_counts_as_skipped: true,
         // number_of_winners: 1,
         // minimum_vote_threshold: None,
         max_rankings_allowed: None,
         elimination_algorithm: EliminationAlgorithm::Single,
         duplicate_candidate_mode: DuplicateCandidateMode::SkipDuplicate,
+        exhaust_rankings_cap: None,
+        continue_to_completion: false,
+        legacy_random_tiebreak: false,
+        nota_candidate: None,
+        protect_nota: false,
+        uwi_round1_reporting: UwiRound1Reporting::Current,
+        name_normalization: NameNormalization::Trim,
+        elimination_strategy: None,
+        track_rank_origin: false,
     };
 }
 
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub(crate) struct Candidate {
-    pub name: String,
+    pub name: CandidateName,
     pub code: Option<String>,
     pub excluded: bool,
+    /// True if the candidate was not declared but found in the ballots.
+    pub inferred: bool,
 }
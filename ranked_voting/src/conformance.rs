@@ -0,0 +1,422 @@
+/*!
+Conformance cases for the corner cases of the rules.
+
+Each case is a small election whose round-by-round outcome was worked out by hand from the
+documented behavior of RCTab, the reference implementation. A ballot is read rank by rank from
+the first one, and the first rule that fails at a rank exhausts it: first the skipped rankings
+before the rank, then a candidate ranked again, then an overvote. The undeclared write-ins are a
+single candidate, eliminated in the first round.
+
+The ballots are written with one token per rank: a candidate name, `-` for a blank, `_` for an
+undervote, `*` for an overvote and `?` for an undeclared write-in.
+
+When this implementation deviates on purpose, the case names the option that restores the
+reference behavior, and is run with both settings.
+*/
+
+use crate::*;
+
+// The expected tally of a round, and its inactive ballots.
+struct Round {
+    tally: &'static [(&'static str, u64)],
+    // The inactive ballots: overvotes, skipped rankings, exhausted choices, repeated rankings.
+    inactive: [u64; 4],
+}
+
+struct Case {
+    name: String,
+    rules: VoteRules,
+    candidates: &'static [&'static str],
+    ballots: &'static [(&'static str, u64)],
+    rounds: Vec<Round>,
+    winner: &'static str,
+}
+
+fn round(tally: &'static [(&'static str, u64)], inactive: [u64; 4]) -> Round {
+    Round { tally, inactive }
+}
+
+fn parse_ballot(text: &str, count: u64) -> Ballot {
+    let candidates = text
+        .split_whitespace()
+        .map(|token| match token {
+            "-" => BallotChoice::Blank,
+            "_" => BallotChoice::Undervote,
+            "*" => BallotChoice::Overvote,
+            "?" => BallotChoice::UndeclaredWriteIn,
+            name => BallotChoice::Candidate(name.to_string()),
+        })
+        .collect();
+    Ballot {
+        candidates,
+        count,
+        challenged: false,
+        id: None,
+    }
+}
+
+fn check(case: &Case) {
+    let names: Vec<String> = case.candidates.iter().map(|c| c.to_string()).collect();
+    let mut builder = Builder::new(&case.rules)
+        .unwrap()
+        .candidates(&names)
+        .unwrap();
+    for (text, count) in case.ballots.iter() {
+        builder.add_ballot(parse_ballot(text, *count)).unwrap();
+    }
+    let result = run_election(&builder).unwrap();
+    let rounds: Vec<&RoundStats> = result
+        .round_stats
+        .iter()
+        .filter(|rs| !rs.informational)
+        .collect();
+    assert_eq!(
+        rounds.len(),
+        case.rounds.len(),
+        "{}: number of rounds: {:?}",
+        case.name,
+        rounds
+    );
+    for (rs, expected) in rounds.iter().zip(case.rounds.iter()) {
+        let mut tally: Vec<(&str, u64)> = rs
+            .tally
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        tally.sort();
+        let mut expected_tally = expected.tally.to_vec();
+        expected_tally.sort();
+        assert_eq!(tally, expected_tally, "{}: round {}", case.name, rs.round);
+        let ib = &rs.inactive_ballots;
+        assert_eq!(
+            [
+                ib.overvotes,
+                ib.skipped_rankings,
+                ib.exhausted_choices,
+                ib.repeated_rankings
+            ],
+            expected.inactive,
+            "{}: inactive ballots of round {}",
+            case.name,
+            rs.round
+        );
+    }
+    assert_eq!(
+        result.winners,
+        Some(vec![case.winner.to_string()]),
+        "{}: winner",
+        case.name
+    );
+}
+
+// The same ballots under each overvote rule and each limit of skipped rankings. C is
+// eliminated in the first round, and its ballots cross an overvote, one blank or two blanks.
+fn overvote_and_skipped_rank_cases() -> Vec<Case> {
+    const BALLOTS: &[(&str, u64)] = &[
+        ("A", 7),
+        ("B", 5),
+        ("C", 1),
+        ("C * B", 1),
+        ("C - B", 1),
+        ("C - - B", 1),
+        ("* A", 1),
+        ("- - A", 1),
+    ];
+    use MaxSkippedRank::*;
+    use OverVoteRule::*;
+    // (overvote rule, skipped rankings, first round, second round)
+    let table: Vec<(OverVoteRule, MaxSkippedRank, Round, Round)> = vec![
+        (
+            ExhaustImmediately,
+            Unlimited,
+            round(&[("A", 8), ("B", 5), ("C", 4)], [1, 0, 0, 0]),
+            round(&[("A", 8), ("B", 7)], [2, 0, 1, 0]),
+        ),
+        (
+            ExhaustImmediately,
+            ExhaustOnFirstOccurence,
+            round(&[("A", 7), ("B", 5), ("C", 4)], [1, 1, 0, 0]),
+            round(&[("A", 7), ("B", 5)], [2, 3, 1, 0]),
+        ),
+        (
+            ExhaustImmediately,
+            MaxAllowed(1),
+            round(&[("A", 7), ("B", 5), ("C", 4)], [1, 1, 0, 0]),
+            round(&[("A", 7), ("B", 6)], [2, 2, 1, 0]),
+        ),
+        (
+            AlwaysSkipToNextRank,
+            Unlimited,
+            round(&[("A", 9), ("B", 5), ("C", 4)], [0, 0, 0, 0]),
+            round(&[("A", 9), ("B", 8)], [0, 0, 1, 0]),
+        ),
+        (
+            AlwaysSkipToNextRank,
+            ExhaustOnFirstOccurence,
+            round(&[("A", 8), ("B", 5), ("C", 4)], [0, 1, 0, 0]),
+            round(&[("A", 8), ("B", 6)], [0, 3, 1, 0]),
+        ),
+        (
+            AlwaysSkipToNextRank,
+            MaxAllowed(1),
+            round(&[("A", 8), ("B", 5), ("C", 4)], [0, 1, 0, 0]),
+            round(&[("A", 8), ("B", 7)], [0, 2, 1, 0]),
+        ),
+    ];
+    table
+        .into_iter()
+        .map(|(overvote_rule, max_skipped, first, second)| Case {
+            name: format!("overvote {:?}, skipped {:?}", overvote_rule, max_skipped),
+            rules: VoteRules {
+                overvote_rule,
+                max_skipped_rank_allowed: max_skipped,
+                ..VoteRules::default()
+            },
+            candidates: &["A", "B", "C"],
+            ballots: BALLOTS,
+            rounds: vec![first, second],
+            winner: "A",
+        })
+        .collect()
+}
+
+// The rules that fail at the same rank, or at successive ranks: the first failing rule in the
+// order of the ranks is the reason.
+fn reason_order_cases() -> Vec<Case> {
+    vec![Case {
+        name: "reason order".to_string(),
+        rules: VoteRules {
+            overvote_rule: OverVoteRule::ExhaustImmediately,
+            max_skipped_rank_allowed: MaxSkippedRank::ExhaustOnFirstOccurence,
+            duplicate_candidate_mode: DuplicateCandidateMode::Exhaust,
+            ..VoteRules::default()
+        },
+        candidates: &["A", "B", "C"],
+        ballots: &[
+            ("A", 5),
+            ("B", 4),
+            // The skipped ranking comes before the overvote of the next rank.
+            ("C - * B", 1),
+            // The overvote comes before C is ranked again.
+            ("C * C B", 1),
+            ("C", 1),
+        ],
+        rounds: vec![
+            round(&[("A", 5), ("B", 4), ("C", 3)], [0, 0, 0, 0]),
+            round(&[("A", 5), ("B", 4)], [1, 1, 1, 0]),
+        ],
+        winner: "A",
+    }]
+}
+
+// A candidate ranked twice next to the head of the ballot. A head that is still continuing is
+// not a duplicate of itself: `B B` counts for B in all the rounds.
+fn duplicate_cases() -> Vec<Case> {
+    const BALLOTS: &[(&str, u64)] = &[("A", 7), ("B", 5), ("B B", 1), ("C C B", 1), ("C B", 2)];
+    let table = vec![
+        (
+            DuplicateCandidateMode::Exhaust,
+            round(&[("A", 7), ("B", 8)], [0, 0, 0, 1]),
+        ),
+        (
+            DuplicateCandidateMode::SkipDuplicate,
+            round(&[("A", 7), ("B", 9)], [0, 0, 0, 0]),
+        ),
+    ];
+    table
+        .into_iter()
+        .map(|(mode, second)| Case {
+            name: format!("duplicate next to the head, {:?}", mode),
+            rules: VoteRules {
+                duplicate_candidate_mode: mode,
+                ..VoteRules::default()
+            },
+            candidates: &["A", "B", "C"],
+            ballots: BALLOTS,
+            rounds: vec![round(&[("A", 7), ("B", 6), ("C", 3)], [0, 0, 0, 0]), second],
+            winner: "B",
+        })
+        .collect()
+}
+
+// The undeclared write-ins, followed by blanks. The first round has the write-ins, which are
+// transferred to the next rank that respects the rules. With the default
+// `UwiRound1Reporting::Current`, nobody is elected in the first round: the reference behavior
+// is restored by `UwiRound1Reporting::Reference`, which counts the write-ins in the threshold of
+// the first round.
+fn undeclared_write_in_cases() -> Vec<Case> {
+    const BALLOTS: &[(&str, u64)] = &[("A", 6), ("B", 2), ("? - B", 1), ("? - - A", 1), ("?", 1)];
+    let rules = |reporting| VoteRules {
+        max_skipped_rank_allowed: MaxSkippedRank::MaxAllowed(1),
+        uwi_round1_reporting: reporting,
+        ..VoteRules::default()
+    };
+    const FIRST: &[(&str, u64)] = &[("A", 6), ("B", 2), ("Undeclared Write-ins", 3)];
+    vec![
+        Case {
+            name: "undeclared write-ins and blanks, current".to_string(),
+            rules: rules(UwiRound1Reporting::Current),
+            candidates: &["A", "B"],
+            ballots: BALLOTS,
+            rounds: vec![
+                round(FIRST, [0, 0, 0, 0]),
+                round(&[("A", 6), ("B", 3)], [0, 1, 1, 0]),
+            ],
+            winner: "A",
+        },
+        Case {
+            name: "undeclared write-ins and blanks, reference".to_string(),
+            rules: rules(UwiRound1Reporting::Reference),
+            candidates: &["A", "B"],
+            ballots: BALLOTS,
+            rounds: vec![round(FIRST, [0, 0, 0, 0])],
+            winner: "A",
+        },
+        // All the write-ins are the same candidate: ranking a write-in again is a duplicate.
+        Case {
+            name: "undeclared write-in ranked twice".to_string(),
+            rules: VoteRules {
+                duplicate_candidate_mode: DuplicateCandidateMode::Exhaust,
+                ..VoteRules::default()
+            },
+            candidates: &["A", "B", "C"],
+            ballots: &[("A", 6), ("B", 4), ("C", 2), ("? C ? B", 1)],
+            rounds: vec![
+                round(
+                    &[("A", 6), ("B", 4), ("C", 2), ("Undeclared Write-ins", 1)],
+                    [0, 0, 0, 0],
+                ),
+                round(&[("A", 6), ("B", 4), ("C", 3)], [0, 0, 0, 0]),
+                round(&[("A", 6), ("B", 4)], [0, 0, 2, 1]),
+            ],
+            winner: "A",
+        },
+    ]
+}
+
+// The blanks count as skipped rankings in the reference implementation. With
+// `blank_counts_as_skipped: false`, only the undervotes do.
+fn blank_cases() -> Vec<Case> {
+    let rules = |blank_counts_as_skipped| VoteRules {
+        max_skipped_rank_allowed: MaxSkippedRank::ExhaustOnFirstOccurence,
+        blank_counts_as_skipped,
+        ..VoteRules::default()
+    };
+    const BALLOTS: &[(&str, u64)] = &[("A", 7), ("B", 4), ("C - B", 2), ("C _ B", 1)];
+    const FIRST: &[(&str, u64)] = &[("A", 7), ("B", 4), ("C", 3)];
+    vec![
+        Case {
+            name: "blanks count as skipped".to_string(),
+            rules: rules(true),
+            candidates: &["A", "B", "C"],
+            ballots: BALLOTS,
+            rounds: vec![
+                round(FIRST, [0, 0, 0, 0]),
+                round(&[("A", 7), ("B", 4)], [0, 3, 0, 0]),
+            ],
+            winner: "A",
+        },
+        Case {
+            name: "blanks ignored".to_string(),
+            rules: rules(false),
+            candidates: &["A", "B", "C"],
+            ballots: BALLOTS,
+            rounds: vec![
+                round(FIRST, [0, 0, 0, 0]),
+                round(&[("A", 7), ("B", 6)], [0, 1, 0, 0]),
+            ],
+            winner: "A",
+        },
+    ]
+}
+
+// The batch elimination removes the largest group of the lowest candidates whose total is
+// strictly lower than the votes of the next candidate.
+fn batch_cases() -> Vec<Case> {
+    let rules = |elimination_algorithm| VoteRules {
+        elimination_algorithm,
+        ..VoteRules::default()
+    };
+    vec![
+        // C, D and E (6 votes) are below B (7 votes): they are eliminated together, and the
+        // ballot of E is exhausted since C is eliminated in the same round.
+        Case {
+            name: "batch below the next candidate".to_string(),
+            rules: rules(EliminationAlgorithm::Batch),
+            candidates: &["A", "B", "C", "D", "E"],
+            ballots: &[("A", 9), ("B", 7), ("C B", 3), ("D A", 2), ("E C", 1)],
+            rounds: vec![
+                round(
+                    &[("A", 9), ("B", 7), ("C", 3), ("D", 2), ("E", 1)],
+                    [0, 0, 0, 0],
+                ),
+                round(&[("A", 11), ("B", 10)], [0, 0, 1, 0]),
+            ],
+            winner: "A",
+        },
+        // The same ballots, one candidate at a time.
+        Case {
+            name: "single elimination".to_string(),
+            rules: rules(EliminationAlgorithm::Single),
+            candidates: &["A", "B", "C", "D", "E"],
+            ballots: &[("A", 9), ("B", 7), ("C B", 3), ("D A", 2), ("E C", 1)],
+            rounds: vec![
+                round(
+                    &[("A", 9), ("B", 7), ("C", 3), ("D", 2), ("E", 1)],
+                    [0, 0, 0, 0],
+                ),
+                round(&[("A", 9), ("B", 7), ("C", 4), ("D", 2)], [0, 0, 0, 0]),
+                round(&[("A", 11), ("B", 7), ("C", 4)], [0, 0, 0, 0]),
+                round(&[("A", 11), ("B", 10)], [0, 0, 1, 0]),
+            ],
+            winner: "A",
+        },
+        // D and E (3 votes) are below C (4 votes), but C, D and E (7 votes) are not below B
+        // (7 votes): the batch stops below C.
+        Case {
+            name: "batch boundary on an equal total".to_string(),
+            rules: rules(EliminationAlgorithm::Batch),
+            candidates: &["A", "B", "C", "D", "E"],
+            ballots: &[("A", 10), ("B", 7), ("C B", 4), ("D A", 2), ("E C", 1)],
+            rounds: vec![
+                round(
+                    &[("A", 10), ("B", 7), ("C", 4), ("D", 2), ("E", 1)],
+                    [0, 0, 0, 0],
+                ),
+                round(&[("A", 12), ("B", 7), ("C", 5)], [0, 0, 0, 0]),
+                round(&[("A", 12), ("B", 11)], [0, 0, 1, 0]),
+            ],
+            winner: "A",
+        },
+    ]
+}
+
+#[test]
+fn overvote_and_skipped_rankings() {
+    overvote_and_skipped_rank_cases().iter().for_each(check);
+}
+
+#[test]
+fn reason_order() {
+    reason_order_cases().iter().for_each(check);
+}
+
+#[test]
+fn duplicates() {
+    duplicate_cases().iter().for_each(check);
+}
+
+#[test]
+fn undeclared_write_ins() {
+    undeclared_write_in_cases().iter().for_each(check);
+}
+
+#[test]
+fn blanks() {
+    blank_cases().iter().for_each(check);
+}
+
+#[test]
+fn batch_elimination() {
+    batch_cases().iter().for_each(check);
+}
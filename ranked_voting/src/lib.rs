@@ -8,13 +8,13 @@ This library can be used in multiple flavours:
 - as a simple library for most cases (see the [run_election1] function)
 
 - as a command-line utility that provides fast and easy election results that can then
-be displayed or exported. The section [timrcv](#timrcv) provides a manual.
+  be displayed or exported. The section [timrcv](#timrcv) provides a manual.
 
 - as a more complex library that can handle all the diversity of implementations. It provides
-for example multiple ways to deal with blank or absentee ballots, undeclared candidates, etc.
-If you are attempting to replicate the results of a specific elections, you should
-carefully check the voting rules and use the configuration accordingly. If you are doing so,
-you should check [run_election] and [VoteRules]
+  for example multiple ways to deal with blank or absentee ballots, undeclared candidates, etc.
+  If you are attempting to replicate the results of a specific elections, you should
+  carefully check the voting rules and use the configuration accordingly. If you are doing so,
+  you should check [run_election] and [VoteRules]
 
 # timrcv
 
@@ -65,10 +65,10 @@ Output:
 [ INFO  ranked_voting] Candidate: 3: C
 [ INFO  ranked_voting] Candidate: 4: D
 [ INFO  ranked_voting] Round 1 (winning threshold: 4)
-[ INFO  ranked_voting]       2 B -> running
-[ INFO  ranked_voting]       2 A -> running
-[ INFO  ranked_voting]       1 C -> running
-[ INFO  ranked_voting]       1 D -> eliminated:1 -> B,
+[ INFO  ranked_voting]       2  33.3% B -> running
+[ INFO  ranked_voting]       2  33.3% A -> running
+[ INFO  ranked_voting]       1  16.7% C -> running
+[ INFO  ranked_voting]       1  16.7% D -> eliminated:1 -> B,
 [ INFO  ranked_voting] Round 2 (winning threshold: 4)
 [ INFO  ranked_voting]       3 B -> running
 [ INFO  ranked_voting]       2 A -> running
@@ -78,6 +78,7 @@ Output:
 [ INFO  ranked_voting]       3 B -> eliminated:3 -> A,
 [ INFO  ranked_voting] Round 4 (winning threshold: 4)
 [ INFO  ranked_voting]       6 A -> elected
+[ INFO  ranked_voting] A won with 6 of the 6 active ballots in the final round, and was ranked on 6 of the 6 ballots (100.0%)
 ```
 
 `timrcv` supports many options (input and output formats, validation of the candidates, configuration of the tabulating process, ...).
@@ -89,32 +90,44 @@ Output:
  */
 
 mod builder;
+mod choice;
+pub use choice::{classify_choice, ChoiceLabels, UNDECLARED_WRITE_IN_LABEL};
 mod config;
-pub use builder::Builder;
+#[cfg(test)]
+mod conformance;
+pub use builder::{Builder, BuilderShard, ConcurrentBuilder, IngestReport};
 pub mod manual;
+pub mod metrics;
+mod name;
+pub use name::{nfc, CandidateName, NameNormalization};
 pub mod quick_start;
-use log::{debug, info};
+mod strategy;
+pub use strategy::{EliminationStrategy, RoundContext};
+pub mod tiebreak;
+pub mod verify;
+use log::{debug, info, warn};
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     hash::Hash,
     ops::{Add, AddAssign},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 pub use crate::config::*;
 
-// **** Private structures ****
-
-type RoundId = u32;
-
+/// The identifier of a candidate during a tabulation (see [RoundContext]).
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, Ord, PartialOrd)]
-struct CandidateId(u32);
+pub struct CandidateId(u32);
+
+// **** Private structures ****
 
 // A position in a ballot may not be filled with a candidate name, and this may still be acceptable.
 // It simply means that this ballot will not be account for this turn.
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, Ord, PartialOrd)]
 enum Choice {
-    BlankOrUndervote,
+    Blank,
+    Undervote,
     Overvote,
     Undeclared,
     Filled(CandidateId),
@@ -124,41 +137,113 @@ enum Choice {
 #[derive(Eq, PartialEq, Debug, Clone, Hash)]
 struct RankedChoice {
     first_valid: CandidateId,
-    rest: Vec<Choice>,
+    // The position of the first of the choices in the ballot, as they are given to the
+    // tabulation: the choices before it cannot give a candidate. It fills the space left after
+    // first_valid, so that the ballots take the same memory with it.
+    offset: u32,
+    // The choices of the ballot, starting with the first head. The blanks and the undervotes
+    // after the last mark are dropped: they cannot give a next candidate. The marks after the
+    // last valid candidate are kept, since they decide why the ballot is exhausted.
+    choices: Box<[Choice]>,
+    // The position of first_valid in the choices. The ballot moves forward by advancing this
+    // position, without copying the choices.
+    head: usize,
 }
 
 impl RankedChoice {
+    /// A ballot whose head is the first choice, which must be a valid candidate.
+    fn new(choices: &[Choice]) -> RankedChoice {
+        RankedChoice::with_head(choices, 0)
+    }
+
+    /// A ballot whose head is the choice at `head`, which must be a valid candidate. The
+    /// choices before the head are already passed: they only count for the duplicates.
+    fn with_head(choices: &[Choice], head: usize) -> RankedChoice {
+        let first_valid = match choices.get(head) {
+            Some(Choice::Filled(cid)) => *cid,
+            x => panic!("RankedChoice::new: the head is not a candidate: {:?}", x),
+        };
+        let end = choices
+            .iter()
+            .rposition(is_mark)
+            .map_or(head + 1, |idx| idx + 1);
+        RankedChoice {
+            first_valid,
+            offset: 0,
+            choices: choices[..end].into(),
+            head,
+        }
+    }
+
+    /// The same ballot, whose choices start at the position `offset` of the ballot.
+    fn at_offset(self, offset: usize) -> RankedChoice {
+        RankedChoice {
+            offset: offset as u32,
+            ..self
+        }
+    }
+
+    /// The rank of first_valid in the ballot, starting with 1.
+    fn rank(&self) -> u32 {
+        self.offset + self.head as u32 + 1
+    }
+
+    /// The choices before first_valid that the ballot already moved past. They are needed to
+    /// detect duplicates the same way as the reference implementation.
+    fn passed(&self) -> &[Choice] {
+        &self.choices[..self.head]
+    }
+
     /// Removes all the eliminated candidates from the list of choices.
-    /// Takes into account the policy for duplicated candidates. If the head candidates appears multiple
-    /// time under the exhaust policy, this ballot will be exhausted.
+    /// Takes into account the policy for duplicated candidates. Under the exhaust policy, the
+    /// ballot is exhausted when moving past the head reaches a candidate that was already ranked
+    /// before. A head that is still continuing is never checked against itself: `[A, A, B]`
+    /// counts for A until A is eliminated.
+    /// Returns the reason why the ballot is exhausted if there is no next candidate.
     fn filtered_candidate(
-        &self,
+        mut self,
         still_valid: &HashSet<CandidateId>,
         duplicate_policy: DuplicateCandidateMode,
         overvote: OverVoteRule,
         skipped_ranks: MaxSkippedRank,
-    ) -> Option<RankedChoice> {
+        blank_counts_as_skipped: bool,
+    ) -> Result<RankedChoice, ExhaustReason> {
         // If the top candidate did not get eliminated, keep the current ranked choice.
         if still_valid.contains(&self.first_valid) {
-            return Some(self.clone());
+            return Ok(self);
         }
 
         // Run the choice pruning procedure.
-        // Add again the first choice since it may have an impact on the elimination rules.
-        let mut all_choices = vec![Choice::Filled(self.first_valid)];
-        all_choices.extend(self.rest.clone());
-
-        if let Some((first_valid, rest)) = advance_voting(
-            &all_choices,
+        // Start again from the first choice since it may have an impact on the elimination rules.
+        let all_choices = &self.choices[self.head..];
+        let (first_valid, idx) = match advance_voting(
+            all_choices,
+            self.passed(),
             still_valid,
             duplicate_policy,
             overvote,
             skipped_ranks,
+            blank_counts_as_skipped,
         ) {
-            Some(RankedChoice { first_valid, rest })
-        } else {
-            None
-        }
+            Some(x) => x,
+            // Only the exhausted ballots look for the reason.
+            None => {
+                return Err(exhaust_reason(
+                    all_choices,
+                    self.passed(),
+                    still_valid,
+                    false,
+                    duplicate_policy,
+                    overvote,
+                    skipped_ranks,
+                    blank_counts_as_skipped,
+                ))
+            }
+        };
+        // Everything between the old head (included) and the new head is now in passed().
+        self.first_valid = first_valid;
+        self.head += idx;
+        Ok(self)
     }
 }
 
@@ -194,16 +279,6 @@ struct VoteInternal {
     count: VoteCount,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Hash)]
-struct VoteSignature {
-    // Guaranteed to never be empty at construction.
-    ranks: Vec<CandidateId>,
-    // Guaranteed to never be zero at construction
-    count: VoteCount,
-}
-
-impl VoteSignature {}
-
 #[derive(Eq, PartialEq, Debug, Clone)]
 enum RoundCandidateStatusInternal {
     StillRunning,
@@ -218,6 +293,30 @@ enum RoundCandidateStatusInternal {
 struct RoundStatistics {
     candidate_stats: Vec<(CandidateId, VoteCount, RoundCandidateStatusInternal)>,
     uwi_elimination_stats: Option<(Vec<(CandidateId, VoteCount)>, VoteCount)>,
+    // The ballots inactive at the start of the round.
+    inactive: InactiveBallots,
+    // The ballots of the excluded candidates, only in the first round.
+    excluded: Vec<ExcludedFirstChoices>,
+    // The votes of each candidate by the rank they come from (VoteRules::track_rank_origin).
+    rank_origin: Option<BTreeMap<CandidateId, BTreeMap<u32, VoteCount>>>,
+    // The special events of the round.
+    notes: Vec<RoundNote>,
+    // The threshold of the round.
+    threshold: VoteCount,
+}
+
+/// The name under which the undeclared write-ins are reported in the tallies of the rounds.
+pub const UNDECLARED_WRITE_INS: &str = "Undeclared Write-ins";
+
+// The ballots whose first ranked candidate is excluded, and the candidates they count for in
+// the first round.
+#[derive(Eq, PartialEq, Debug, Clone)]
+struct ExcludedFirstChoices {
+    name: String,
+    votes: VoteCount,
+    // By name, with the undeclared write-ins.
+    transfers: BTreeMap<String, VoteCount>,
+    exhausted: VoteCount,
 }
 
 #[derive(Eq, PartialEq, Debug, Clone)]
@@ -226,6 +325,34 @@ struct RoundResult {
     stats: RoundStatistics,
     // Winning vote threshold
     vote_threshold: VoteCount,
+    // The ballots exhausted by the eliminations of this round.
+    exhausted: InactiveBallots,
+}
+
+// Why a ballot stopped counting.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+enum ExhaustReason {
+    ExhaustedChoices,
+    Overvote,
+    SkippedRankings,
+    RepeatedRankings,
+}
+
+fn add_inactive(inactive: &mut InactiveBallots, reason: ExhaustReason, count: VoteCount) {
+    let counter = match reason {
+        ExhaustReason::ExhaustedChoices => &mut inactive.exhausted_choices,
+        ExhaustReason::Overvote => &mut inactive.overvotes,
+        ExhaustReason::SkippedRankings => &mut inactive.skipped_rankings,
+        ExhaustReason::RepeatedRankings => &mut inactive.repeated_rankings,
+    };
+    *counter += count.0;
+}
+
+fn add_all_inactive(inactive: &mut InactiveBallots, other: &InactiveBallots) {
+    inactive.exhausted_choices += other.exhausted_choices;
+    inactive.overvotes += other.overvotes;
+    inactive.skipped_rankings += other.skipped_rankings;
+    inactive.repeated_rankings += other.repeated_rankings;
 }
 
 /// Runs an election using the instant-runoff voting algorithm.
@@ -257,7 +384,35 @@ struct RoundResult {
 /// # Ok::<(), VotingErrors>(())
 /// ```
 pub fn run_election(builder: &builder::Builder) -> Result<VotingResult, VotingErrors> {
-    run_voting_stats(&builder._votes, &builder._rules, &builder._candidates)
+    run_voting_stats(
+        &builder._votes,
+        &builder._rules,
+        &builder._candidates,
+        builder._cancel.as_deref(),
+        builder._log_top,
+        builder._debug_sample_rate,
+        &mut |_| {},
+    )
+}
+
+/// Runs an election like [`run_election`], and calls `observer` with the statistics of each
+/// round as soon as the round is tabulated.
+///
+/// The rounds are passed in order, including the informational rounds (which are passed after
+/// the winner is found). They are the same as the `round_stats` of the result.
+pub fn run_election_observed(
+    builder: &builder::Builder,
+    observer: &mut dyn FnMut(&RoundStats),
+) -> Result<VotingResult, VotingErrors> {
+    run_voting_stats(
+        &builder._votes,
+        &builder._rules,
+        &builder._candidates,
+        builder._cancel.as_deref(),
+        builder._log_top,
+        builder._debug_sample_rate,
+        observer,
+    )
 }
 
 /// Runs an election (simple interface) using the instant-runoff voting algorithm.
@@ -292,14 +447,9 @@ pub fn run_election1(
     let mut builder = Builder::new(rules)?;
 
     {
-        // Take everyone from the election as a valid candidate.
-        let mut cand_set: HashSet<String> = HashSet::new();
-        for ballot in votes.iter() {
-            for choice in ballot.iter() {
-                cand_set.insert(choice.to_string());
-            }
-        }
-        let cand_vec: Vec<String> = cand_set.iter().cloned().collect();
+        // Take everyone from the election as a valid candidate, sorted by name.
+        let cand_set: BTreeSet<&str> = votes.iter().flatten().copied().collect();
+        let cand_vec: Vec<String> = cand_set.into_iter().map(|c| c.to_string()).collect();
         builder = builder.candidates(&cand_vec)?;
     }
     for choices in votes.iter() {
@@ -309,24 +459,36 @@ pub fn run_election1(
     run_election(&builder)
 }
 
-fn candidates_from_ballots(ballots: &[Ballot]) -> Vec<config::Candidate> {
-    // Take everyone from the election as a valid candidate.
-    let mut cand_set: HashSet<String> = HashSet::new();
+// Takes everyone from the election as a valid candidate, sorted by name. This order is the
+// candidate order for the tiebreaks. The names that only differ by their normalization are the
+// same candidate, with the smallest of its forms. Neither depends on the order of the ballots.
+fn candidates_from_ballots(
+    ballots: &[Ballot],
+    normalization: NameNormalization,
+) -> Vec<config::Candidate> {
+    let mut forms: HashMap<String, String> = HashMap::new();
     for ballot in ballots.iter() {
         for choice in ballot.candidates.iter() {
             if let BallotChoice::Candidate(name) = choice {
-                cand_set.insert(name.clone());
+                let name = CandidateName::new(name, normalization);
+                let form = forms
+                    .entry(name.key().to_string())
+                    .or_insert_with(|| name.as_str().to_string());
+                if name.as_str() < form.as_str() {
+                    *form = name.as_str().to_string();
+                }
             }
         }
     }
-    let mut cand_vec: Vec<String> = cand_set.iter().cloned().collect();
+    let mut cand_vec: Vec<String> = forms.into_values().collect();
     cand_vec.sort();
     cand_vec
-        .iter()
-        .map(|n| config::Candidate {
-            name: n.clone(),
+        .into_iter()
+        .map(|name| config::Candidate {
+            name: CandidateName::new(&name, normalization),
             code: None,
             excluded: false,
+            inferred: true,
         })
         .collect()
 }
@@ -337,16 +499,22 @@ fn candidates_from_ballots(ballots: &[Ballot]) -> Vec<config::Candidate> {
 /// * `coll` the collection of votes to process
 /// * `rules` the rules that govern this election
 /// * `candidates` the registered candidates for this election. If not provided, the
-/// candidates will be inferred from the votes.
+///   candidates will be inferred from the votes.
+/// * `cancel` stops the tabulation after the current round when it is set.
+/// * `observer` called with the statistics of each round, once it is tabulated.
 fn run_voting_stats(
-    coll: &Vec<Ballot>,
+    coll: &[Ballot],
     rules: &config::VoteRules,
     candidates_o: &Option<Vec<config::Candidate>>,
+    cancel: Option<&AtomicBool>,
+    log_top: Option<usize>,
+    sample_rate: SampleRate,
+    observer: &mut dyn FnMut(&RoundStats),
 ) -> Result<VotingResult, VotingErrors> {
     info!("run_voting_stats: Processing {:?} votes", coll.len());
     let candidates = candidates_o
         .to_owned()
-        .unwrap_or_else(|| candidates_from_ballots(coll));
+        .unwrap_or_else(|| candidates_from_ballots(coll, rules.name_normalization));
 
     debug!(
         "run_voting_stats: candidates: {:?}, rules: {:?}",
@@ -354,7 +522,7 @@ fn run_voting_stats(
         candidates,
     );
 
-    let cr: CheckResult = checks(coll, &candidates, rules)?;
+    let mut cr: CheckResult = checks(coll, &candidates, rules, sample_rate)?;
     let checked_votes = cr.votes;
     debug!(
         "run_voting_stats: Checked votes: {:?}, detected UWIs {:?}",
@@ -362,19 +530,22 @@ fn run_voting_stats(
         cr.count_exhausted_uwi_first_round
     );
     let all_candidates: Vec<(String, CandidateId)> = cr.candidates;
-    {
-        info!("Processing {:?} aggregated votes", checked_votes.len());
-        let mut sorted_candidates: Vec<&(String, CandidateId)> = all_candidates.iter().collect();
-        sorted_candidates.sort_by_key(|p| p.1);
-        for p in sorted_candidates.iter() {
-            info!("Candidate: {}: {}", p.1 .0, p.0);
-        }
-    }
+    let candidate_infos = candidate_infos(&candidates, &all_candidates);
+    info!("Processing {:?} aggregated votes", checked_votes.len());
+    log_candidates(&candidate_infos);
 
     let mut initial_count: VoteCount = VoteCount::EMPTY;
     for v in checked_votes.iter() {
         initial_count += v.count;
     }
+    // All the ballots were discarded by the rules (for example the skipped rankings of ballots
+    // truncated by max_rankings_allowed): nobody can be elected with no vote.
+    if initial_count == VoteCount::EMPTY
+        && cr.uwi_first_votes.is_empty()
+        && cr.count_exhausted_uwi_first_round == VoteCount::EMPTY
+    {
+        return Err(VotingErrors::EmptyElection);
+    }
 
     // We are done, stop here.
     let candidates_by_id: HashMap<CandidateId, String> = all_candidates
@@ -386,10 +557,20 @@ fn run_voting_stats(
     let mut cur_sorted_candidates: Vec<(String, CandidateId)> = all_candidates.clone();
     let mut cur_votes: Vec<VoteInternal> = checked_votes;
     let mut cur_stats: Vec<RoundStatistics> = Vec::new();
+    // The ballots inactive at the start of the current round.
+    let mut inactive = cr.inactive_first_round.clone();
 
     // TODO: better management of the number of iterations
     while cur_stats.iter().len() < 10000 {
-        let round_id = (cur_stats.iter().len() + 1) as u32;
+        // The first round is always tabulated, so that an interrupted run has some result.
+        if !cur_stats.is_empty() && cancel.map(|c| c.load(Ordering::Relaxed)).unwrap_or(false) {
+            info!(
+                "run_voting_stats: cancelled after {} rounds",
+                cur_stats.len()
+            );
+            return Err(VotingErrors::Interrupted);
+        }
+        let round_id = RoundId::from_index(cur_stats.len());
         debug!(
             "run_voting_stats: Round id: {:?} cur_candidates: {:?}",
             round_id, cur_sorted_candidates
@@ -397,18 +578,37 @@ fn run_voting_stats(
         let has_initial_uwis = cur_stats.is_empty()
             && (!cr.uwi_first_votes.is_empty()
                 || cr.count_exhausted_uwi_first_round > VoteCount::EMPTY);
-        let round_res: RoundResult = if has_initial_uwis {
+        let rank_origin = rules
+            .track_rank_origin
+            .then(|| compute_rank_origin(&cur_votes, &cur_sorted_candidates));
+        let mut round_res: RoundResult = if has_initial_uwis {
             // First round and we have some undeclared write ins.
             // Apply a special path to get rid of them.
             run_first_round_uwi(
-                &cur_votes,
-                &cr.uwi_first_votes,
+                std::mem::take(&mut cur_votes),
+                std::mem::take(&mut cr.uwi_first_votes),
                 cr.count_exhausted_uwi_first_round,
+                rules,
                 &cur_sorted_candidates,
             )?
         } else {
-            run_one_round(&cur_votes, rules, &cur_sorted_candidates, round_id)?
+            run_one_round(
+                std::mem::take(&mut cur_votes),
+                rules,
+                &cur_sorted_candidates,
+                round_id,
+                &cur_stats,
+            )?
         };
+        round_res.stats.inactive = inactive.clone();
+        add_all_inactive(&mut inactive, &round_res.exhausted);
+        if has_initial_uwis {
+            add_all_inactive(&mut inactive, &cr.uwi_exhausted_reasons);
+        }
+        if cur_stats.is_empty() {
+            round_res.stats.excluded = cr.excluded.clone();
+        }
+        round_res.stats.rank_origin = rank_origin;
         let round_stats = round_res.stats.clone();
         debug!(
             "run_voting_stats: Round id: {:?} stats: {:?}",
@@ -419,23 +619,19 @@ fn run_voting_stats(
             &round_stats,
             &all_candidates,
             round_res.vote_threshold,
+            log_top,
         );
 
+        observer(&round_result_to_stat(
+            &round_res.stats,
+            round_id,
+            &candidates_by_id,
+        )?);
         cur_votes = round_res.votes;
         cur_stats.push(round_res.stats);
         let stats = round_stats.candidate_stats;
 
-        // Survivors are described in candidate order.
-        let mut survivors: Vec<(String, CandidateId)> = Vec::new();
-        for (s, cid) in cur_sorted_candidates.iter() {
-            // Has this candidate been marked as eliminated? Skip it
-            let is_eliminated = stats.iter().any(|(cid2, _, s)| {
-                matches!(s, RoundCandidateStatusInternal::Eliminated(_, _) if *cid == *cid2)
-            });
-            if !is_eliminated {
-                survivors.push((s.clone(), *cid));
-            }
-        }
+        let survivors = surviving_candidates(&cur_sorted_candidates, &stats);
         // Invariant: the number of candidates decreased or all the candidates are winners
         let all_survivors_winners = stats
             .iter()
@@ -462,35 +658,359 @@ fn run_voting_stats(
             })
             .collect();
         if !winners.is_empty() {
-            let stats = round_results_to_stats(&cur_stats, &candidates_by_id)?;
+            let num_decisive_rounds = cur_stats.len();
+            let nota_prevailed = rules
+                .nota_candidate
+                .as_ref()
+                .map(|nota| {
+                    let nota = CandidateName::new(nota, rules.name_normalization);
+                    winners.iter().any(|cid| {
+                        CandidateName::new(&candidates_by_id[cid], rules.name_normalization) == nota
+                    })
+                })
+                .unwrap_or(false);
+            let elimination_order = elimination_order(
+                &cur_stats,
+                if nota_prevailed { &[] } else { &winners },
+                &candidates_by_id,
+            );
+            if rules.continue_to_completion {
+                run_informational_rounds(
+                    &mut cur_stats,
+                    cur_votes,
+                    cur_sorted_candidates,
+                    inactive,
+                    rules,
+                    &all_candidates,
+                    log_top,
+                );
+            }
+            let mut stats = round_results_to_stats(&cur_stats, &candidates_by_id)?;
+            for rs in stats.iter_mut().skip(num_decisive_rounds) {
+                rs.informational = true;
+                rs.notes.push(RoundNote::Informational);
+                observer(rs);
+            }
             let mut winner_names: Vec<String> = Vec::new();
-            for cid in winners {
-                winner_names.push(candidates_by_id.get(&cid).unwrap().clone());
+            for cid in winners.iter() {
+                winner_names.push(candidates_by_id.get(cid).unwrap().clone());
+            }
+            if nota_prevailed {
+                info!("run_voting_stats: None of the above prevailed, no candidate is elected");
+            }
+            let winner_ranked_on: Vec<(String, u64)> = if nota_prevailed {
+                vec![]
+            } else {
+                winners
+                    .iter()
+                    .map(|cid| {
+                        let count = cr.ranked_on.get(cid).cloned().unwrap_or(VoteCount::EMPTY);
+                        (candidates_by_id.get(cid).unwrap().clone(), count.0)
+                    })
+                    .collect()
+            };
+            let first_majority_round: Vec<(String, Option<RoundId>)> = if nota_prevailed {
+                vec![]
+            } else {
+                winners
+                    .iter()
+                    .map(|cid| {
+                        (
+                            candidates_by_id.get(cid).unwrap().clone(),
+                            first_majority_round(&cur_stats, *cid),
+                        )
+                    })
+                    .collect()
+            };
+            let final_tally = &stats[num_decisive_rounds - 1].tally;
+            let active: u64 = final_tally.iter().map(|(_, count)| count).sum();
+            for (name, ranked_on) in winner_ranked_on.iter() {
+                let votes = final_tally
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, count)| *count)
+                    .unwrap_or(0);
+                info!(
+                    "{} won with {} of the {} active ballots in the final round, and was ranked on {} of the {} ballots ({}%)",
+                    name,
+                    votes,
+                    active,
+                    ranked_on,
+                    cr.rank_stats.total_votes,
+                    format_percent(*ranked_on, cr.rank_stats.total_votes)
+                );
             }
             return Ok(VotingResult {
                 threshold: round_res.vote_threshold.0,
-                winners: Some(winner_names),
+                winners: if nota_prevailed {
+                    None
+                } else {
+                    Some(winner_names)
+                },
                 round_stats: stats,
+                rank_stats: cr.rank_stats,
+                nota_prevailed,
+                candidates: candidate_infos,
+                elimination_order,
+                winner_ranked_on,
+                first_majority_round,
+                excluded: cr
+                    .excluded
+                    .iter()
+                    .map(|e| (e.name.clone(), e.votes.0))
+                    .collect(),
+                rank_origin: rules
+                    .track_rank_origin
+                    .then(|| rank_origins(&cur_stats, &candidates_by_id)),
+                whole_ballot_overvotes: cr.whole_ballot_overvotes.0,
             });
         }
     }
     Err(VotingErrors::NoConvergence)
 }
 
+// The eliminated candidates of each round, sorted by ascending tally and then by name, then the
+// winners with their tally in the last round. Like in the summaries, nobody is eliminated in
+// the round that elects the winners.
+fn elimination_order(
+    stats: &[RoundStatistics],
+    winners: &[CandidateId],
+    candidates_by_id: &HashMap<CandidateId, String>,
+) -> Vec<EliminationEntry> {
+    let entry = |idx: usize, rs: &RoundStatistics, name: &String, count: VoteCount| {
+        let total: u64 = rs
+            .candidate_stats
+            .iter()
+            .filter(|(cid, _, _)| candidates_by_id.contains_key(cid))
+            .map(|(_, c, _)| c.0)
+            .sum();
+        EliminationEntry {
+            name: name.clone(),
+            round: RoundId::from_index(idx),
+            votes: count.0,
+            share_percent: format_percent(count.0, total),
+            elected: false,
+        }
+    };
+    let mut res: Vec<EliminationEntry> = Vec::new();
+    let (last, eliminating) = match stats.split_last() {
+        Some(p) => p,
+        None => return res,
+    };
+    for (idx, rs) in eliminating.iter().enumerate() {
+        let mut eliminated: Vec<(VoteCount, &String)> = rs
+            .candidate_stats
+            .iter()
+            .filter(|(_, _, s)| matches!(s, RoundCandidateStatusInternal::Eliminated(_, _)))
+            .filter_map(|(cid, count, _)| candidates_by_id.get(cid).map(|n| (*count, n)))
+            .collect();
+        eliminated.sort();
+        res.extend(
+            eliminated
+                .into_iter()
+                .map(|(count, name)| entry(idx, rs, name, count)),
+        );
+    }
+    for cid in winners.iter() {
+        let count = last
+            .candidate_stats
+            .iter()
+            .find(|(c, _, _)| c == cid)
+            .map(|(_, count, _)| *count)
+            .unwrap_or(VoteCount::EMPTY);
+        if let Some(name) = candidates_by_id.get(cid) {
+            res.push(EliminationEntry {
+                elected: true,
+                ..entry(eliminating.len(), last, name, count)
+            });
+        }
+    }
+    res
+}
+
+// The registered candidates that take part in the tabulation, in the order of their ids.
+fn candidate_infos(
+    reg_candidates: &[config::Candidate],
+    all_candidates: &[(String, CandidateId)],
+) -> Vec<CandidateInfo> {
+    let mut sorted_candidates: Vec<&(String, CandidateId)> = all_candidates.iter().collect();
+    sorted_candidates.sort_by_key(|p| p.1);
+    sorted_candidates
+        .iter()
+        .filter_map(|(name, _)| reg_candidates.iter().find(|c| c.name.as_str() == name))
+        .map(|c| CandidateInfo {
+            name: c.name.to_string(),
+            code: c.code.clone(),
+            excluded: c.excluded,
+            inferred: c.inferred,
+        })
+        .collect()
+}
+
+fn log_candidates(candidates: &[CandidateInfo]) {
+    for (idx, c) in candidates.iter().enumerate() {
+        let origin = if c.inferred {
+            " (inferred from the ballots)"
+        } else {
+            ""
+        };
+        info!("Candidate: {}: {}{}", idx + 1, c.name, origin);
+    }
+}
+
+// Survivors are described in candidate order.
+fn surviving_candidates(
+    cur_sorted_candidates: &[(String, CandidateId)],
+    stats: &[(CandidateId, VoteCount, RoundCandidateStatusInternal)],
+) -> Vec<(String, CandidateId)> {
+    let mut survivors: Vec<(String, CandidateId)> = Vec::new();
+    for (s, cid) in cur_sorted_candidates.iter() {
+        // Has this candidate been marked as eliminated? Skip it
+        let is_eliminated = stats.iter().any(|(cid2, _, s)| {
+            matches!(s, RoundCandidateStatusInternal::Eliminated(_, _) if *cid == *cid2)
+        });
+        if !is_eliminated {
+            survivors.push((s.clone(), *cid));
+        }
+    }
+    survivors
+}
+
+// The first round in which the candidate has at least the threshold of the round, including
+// the informational rounds. A threshold of 0 (see UwiRound1Reporting::Current) elects nobody.
+fn first_majority_round(stats: &[RoundStatistics], cid: CandidateId) -> Option<RoundId> {
+    stats
+        .iter()
+        .position(|rs| {
+            rs.threshold > VoteCount::EMPTY
+                && rs
+                    .candidate_stats
+                    .iter()
+                    .any(|(cid2, count, _)| *cid2 == cid && *count >= rs.threshold)
+        })
+        .map(RoundId::from_index)
+}
+
+// Keeps eliminating candidates after the winner has been found, until only one
+// candidate is left. The last round shows the tally of this single candidate.
+// These rounds do not elect anyone. The winners are already known: an error in one of these
+// rounds only stops them, with a warning, and the rounds before it are kept.
+fn run_informational_rounds(
+    cur_stats: &mut Vec<RoundStatistics>,
+    votes: Vec<VoteInternal>,
+    candidates: Vec<(String, CandidateId)>,
+    inactive: InactiveBallots,
+    rules: &config::VoteRules,
+    all_candidates: &[(String, CandidateId)],
+    log_top: Option<usize>,
+) {
+    let mut inactive = inactive;
+    let mut cur_votes = votes;
+    let mut cur_sorted_candidates = candidates;
+    while !cur_sorted_candidates.is_empty() {
+        let round_id = RoundId::from_index(cur_stats.len());
+        let is_last = cur_sorted_candidates.len() == 1;
+        let rank_origin = rules
+            .track_rank_origin
+            .then(|| compute_rank_origin(&cur_votes, &cur_sorted_candidates));
+        let mut round_res = match run_one_round(
+            std::mem::take(&mut cur_votes),
+            rules,
+            &cur_sorted_candidates,
+            round_id,
+            cur_stats,
+        ) {
+            Ok(round_res) => round_res,
+            Err(e) => {
+                warn!(
+                    "run_informational_rounds: the informational rounds stop before round {}: {:?}",
+                    round_id, e
+                );
+                break;
+            }
+        };
+        for (_, _, status) in round_res.stats.candidate_stats.iter_mut() {
+            if *status == RoundCandidateStatusInternal::Elected {
+                *status = RoundCandidateStatusInternal::StillRunning;
+            }
+        }
+        round_res.stats.inactive = inactive.clone();
+        round_res.stats.rank_origin = rank_origin;
+        add_all_inactive(&mut inactive, &round_res.exhausted);
+        info!("Informational round:");
+        print_round_stats(
+            round_id,
+            &round_res.stats,
+            all_candidates,
+            round_res.vote_threshold,
+            log_top,
+        );
+        cur_sorted_candidates =
+            surviving_candidates(&cur_sorted_candidates, &round_res.stats.candidate_stats);
+        cur_votes = round_res.votes;
+        cur_stats.push(round_res.stats);
+        if is_last {
+            break;
+        }
+    }
+}
+
 fn print_round_stats(
     round_id: RoundId,
     stats: &RoundStatistics,
     candidate_names: &[(String, CandidateId)],
     vote_threshold: VoteCount,
+    log_top: Option<usize>,
 ) {
-    info!(
+    for line in round_stats_lines(round_id, stats, candidate_names, vote_threshold, log_top) {
+        info!("{}", line);
+    }
+}
+
+// The lines of the log of a round. With `log_top`, only the candidates with the most votes and
+// the candidates elected or eliminated in the round are listed, and the others are summarized
+// in one line.
+fn round_stats_lines(
+    round_id: RoundId,
+    stats: &RoundStatistics,
+    candidate_names: &[(String, CandidateId)],
+    vote_threshold: VoteCount,
+    log_top: Option<usize>,
+) -> Vec<String> {
+    let mut lines: Vec<String> = vec![format!(
         "Round {} (winning threshold: {})",
         round_id, vote_threshold.0
-    );
+    )];
     let mut sorted_candidates = stats.candidate_stats.clone();
     sorted_candidates.sort_by_key(|(_, count, _)| -(count.0 as i64));
     let fetch_name = |cid: &CandidateId| candidate_names.iter().find(|(_, cid2)| cid2 == cid);
-    for (cid, count, cstatus) in sorted_candidates.iter() {
+    // The first round also shows the share of each candidate, over the same total as the
+    // summary (including the undeclared write-ins).
+    let first_round_total: Option<VoteCount> = (round_id == RoundId::FIRST).then(|| {
+        let uwi_count = match &stats.uwi_elimination_stats {
+            Some((transfers, exhausted)) => {
+                transfers.iter().map(|(_, vc)| *vc).sum::<VoteCount>() + *exhausted
+            }
+            None => VoteCount::EMPTY,
+        };
+        stats
+            .candidate_stats
+            .iter()
+            .map(|(_, vc, _)| *vc)
+            .sum::<VoteCount>()
+            + uwi_count
+    });
+    // The number and the largest count of the candidates that are not listed.
+    let mut num_others: usize = 0;
+    let mut others_max = VoteCount::EMPTY;
+    for (idx, (cid, count, cstatus)) in sorted_candidates.iter().enumerate() {
+        if log_top.is_some_and(|top| idx >= top)
+            && *cstatus == RoundCandidateStatusInternal::StillRunning
+        {
+            num_others += 1;
+            others_max = others_max.max(*count);
+            continue;
+        }
         if let Some((name, _)) = fetch_name(cid) {
             let status = match cstatus {
                 RoundCandidateStatusInternal::Elected => "elected".to_string(),
@@ -508,9 +1028,24 @@ fn print_round_stats(
                     s
                 }
             };
-            info!("{:7} {} -> {}", count.0, name, status);
+            lines.push(match first_round_total {
+                Some(total) => format!(
+                    "{:7} {:>6} {} -> {}",
+                    count.0,
+                    format!("{}%", format_percent(count.0, total.0)),
+                    name,
+                    status
+                ),
+                None => format!("{:7} {} -> {}", count.0, name, status),
+            });
         }
     }
+    if num_others > 0 {
+        lines.push(format!(
+            "        \u{2026} and {} others with \u{2264} {} votes",
+            num_others, others_max.0
+        ));
+    }
     if let Some((transfers, exhausted)) = stats.uwi_elimination_stats.clone() {
         let mut s = String::from("undeclared candidates: ");
         if exhausted > VoteCount::EMPTY {
@@ -521,12 +1056,26 @@ fn print_round_stats(
                 s.push_str(format!("{} -> {}, ", vc.0, tname).as_str());
             }
         }
-        info!("        {}", s);
+        lines.push(format!("        {}", s));
+    }
+    for e in stats.excluded.iter().filter(|e| e.votes > VoteCount::EMPTY) {
+        let mut s = format!("excluded {}: {} votes, ", e.name, e.votes.0);
+        if e.exhausted > VoteCount::EMPTY {
+            s.push_str(format!("{} exhausted, ", e.exhausted.0).as_str());
+        }
+        for (tname, vc) in e.transfers.iter() {
+            s.push_str(format!("{} -> {}, ", vc.0, tname).as_str());
+        }
+        lines.push(format!("        {}", s));
     }
+    lines
 }
 
 fn get_threshold(tally: &HashMap<CandidateId, VoteCount>) -> VoteCount {
-    let total_count: VoteCount = tally.values().cloned().sum();
+    threshold_of_total(tally.values().cloned().sum())
+}
+
+fn threshold_of_total(total_count: VoteCount) -> VoteCount {
     if total_count == VoteCount::EMPTY {
         VoteCount::EMPTY
     } else {
@@ -541,7 +1090,7 @@ fn round_results_to_stats(
 ) -> Result<Vec<RoundStats>, VotingErrors> {
     let mut res: Vec<RoundStats> = Vec::new();
     for (idx, r) in results.iter().enumerate() {
-        let round_id: RoundId = idx as u32 + 1;
+        let round_id = RoundId::from_index(idx);
         res.push(round_result_to_stat(r, round_id, candidates_by_id)?);
     }
     Ok(res)
@@ -557,12 +1106,26 @@ fn round_result_to_stat(
         tally: Vec::new(),
         tally_results_elected: Vec::new(),
         tally_result_eliminated: Vec::new(),
+        informational: false,
+        inactive_ballots: stats.inactive.clone(),
+        notes: stats.notes.clone(),
+        threshold: stats.threshold.0,
     };
 
-    for (cid, c, status) in stats.candidate_stats.iter() {
-        let name: &String = candidates_by_id
-            .get(cid)
-            .ok_or(VotingErrors::EmptyElection)?; // TODO: wrong error
+    // The candidates by descending votes, then by name: the order of the tally does not depend
+    // on the order of the ballots.
+    let mut candidate_stats: Vec<(&String, &VoteCount, &RoundCandidateStatusInternal)> = stats
+        .candidate_stats
+        .iter()
+        .map(|(cid, c, status)| {
+            candidates_by_id
+                .get(cid)
+                .map(|name| (name, c, status))
+                .ok_or(VotingErrors::EmptyElection) // TODO: wrong error
+        })
+        .collect::<Result<_, _>>()?;
+    candidate_stats.sort_by(|(n1, c1, _), (n2, c2, _)| c2.0.cmp(&c1.0).then(n1.cmp(n2)));
+    for (name, c, status) in candidate_stats {
         rs.tally.push((name.clone(), c.0));
         match status {
             RoundCandidateStatusInternal::StillRunning => {
@@ -581,11 +1144,13 @@ fn round_result_to_stat(
                         .ok_or(VotingErrors::EmptyElection)?; // TODO: wrong error
                     pub_transfers.push((t_name.clone(), t_count.0));
                 }
-                rs.tally_result_eliminated.push(config::EliminationStats {
+                let mut es = config::EliminationStats {
                     name: name.clone(),
                     transfers: pub_transfers,
                     exhausted: exhausts.0,
-                });
+                };
+                es.sort_transfers();
+                rs.tally_result_eliminated.push(es);
             }
             RoundCandidateStatusInternal::Eliminated(_, _) => {
                 // Do not print a candidate if its corresponding stats are going to be empty.
@@ -593,7 +1158,22 @@ fn round_result_to_stat(
         }
     }
 
-    let uwi = "Undeclared Write-ins".to_string();
+    // Like the eliminated candidates, an excluded candidate without votes is not reported.
+    for e in stats.excluded.iter().filter(|e| e.votes > VoteCount::EMPTY) {
+        let mut es = EliminationStats {
+            name: e.name.clone(),
+            transfers: e
+                .transfers
+                .iter()
+                .map(|(n, vc)| (n.clone(), vc.0))
+                .collect(),
+            exhausted: e.exhausted.0,
+        };
+        es.sort_transfers();
+        rs.tally_result_eliminated.push(es);
+    }
+
+    let uwi = UNDECLARED_WRITE_INS.to_string();
 
     if let Some((uwi_transfers, uwi_exhauster)) = stats.uwi_elimination_stats.clone() {
         let uwi_tally: VoteCount =
@@ -609,11 +1189,13 @@ fn round_result_to_stat(
             pub_transfers.push((t_name.clone(), t_count.0));
         }
 
-        rs.tally_result_eliminated.push(EliminationStats {
+        let mut es = EliminationStats {
             name: uwi,
             transfers: pub_transfers,
             exhausted: uwi_exhauster.0,
-        });
+        };
+        es.sort_transfers();
+        rs.tally_result_eliminated.push(es);
     }
 
     rs.tally_result_eliminated.sort_by_key(|es| es.name.clone());
@@ -622,12 +1204,13 @@ fn round_result_to_stat(
 }
 
 fn run_first_round_uwi(
-    votes: &[VoteInternal],
-    uwi_first_votes: &[VoteInternal],
+    votes: Vec<VoteInternal>,
+    uwi_first_votes: Vec<VoteInternal>,
     uwi_first_exhausted: VoteCount,
+    rules: &config::VoteRules,
     candidate_names: &[(String, CandidateId)],
 ) -> Result<RoundResult, VotingErrors> {
-    let tally = compute_tally(votes, candidate_names);
+    let tally = compute_tally(&votes, candidate_names);
     let mut elimination_stats: HashMap<CandidateId, VoteCount> = HashMap::new();
     for v in uwi_first_votes.iter() {
         let e = elimination_stats
@@ -636,10 +1219,29 @@ fn run_first_round_uwi(
         *e += v.count;
     }
 
+    // In the reference mode, the undeclared write-ins count in the threshold as if they were a
+    // candidate, and the candidates that reach it are elected in this round.
+    let vote_threshold = match rules.uwi_round1_reporting {
+        UwiRound1Reporting::Current => VoteCount::EMPTY,
+        UwiRound1Reporting::Reference => {
+            let uwi_count: VoteCount =
+                uwi_first_votes.iter().map(|v| v.count).sum::<VoteCount>() + uwi_first_exhausted;
+            threshold_of_total(tally.values().cloned().sum::<VoteCount>() + uwi_count)
+        }
+    };
+    debug!("run_first_round_uwi: vote_threshold: {:?}", vote_threshold);
+    let status = |count: VoteCount| {
+        if vote_threshold > VoteCount::EMPTY && count >= vote_threshold {
+            RoundCandidateStatusInternal::Elected
+        } else {
+            RoundCandidateStatusInternal::StillRunning
+        }
+    };
+
     let full_stats = RoundStatistics {
         candidate_stats: tally
             .iter()
-            .map(|(cid, vc)| (*cid, *vc, RoundCandidateStatusInternal::StillRunning))
+            .map(|(cid, vc)| (*cid, *vc, status(*vc)))
             .collect(),
         uwi_elimination_stats: Some((
             elimination_stats
@@ -648,18 +1250,80 @@ fn run_first_round_uwi(
                 .collect(),
             uwi_first_exhausted,
         )),
+        inactive: InactiveBallots::default(),
+        excluded: Vec::new(),
+        rank_origin: None,
+        notes: vec![
+            RoundNote::UwiElimination,
+            RoundNote::ThresholdPolicy {
+                reporting: rules.uwi_round1_reporting,
+                threshold: vote_threshold.0,
+            },
+        ],
+        threshold: vote_threshold,
     };
 
-    let mut all_votes = votes.to_vec();
-    all_votes.extend(uwi_first_votes.to_vec());
+    let mut all_votes = votes;
+    all_votes.extend(uwi_first_votes);
 
     Ok(RoundResult {
         votes: all_votes,
         stats: full_stats,
-        vote_threshold: VoteCount::EMPTY,
+        vote_threshold,
+        exhausted: InactiveBallots::default(),
     })
 }
 
+// The votes of each candidate by the rank of the ballots they come from, for the same votes as
+// compute_tally.
+fn compute_rank_origin(
+    votes: &[VoteInternal],
+    candidate_names: &[(String, CandidateId)],
+) -> BTreeMap<CandidateId, BTreeMap<u32, VoteCount>> {
+    let mut res: BTreeMap<CandidateId, BTreeMap<u32, VoteCount>> = candidate_names
+        .iter()
+        .map(|(_, cid)| (*cid, BTreeMap::new()))
+        .collect();
+    for v in votes.iter() {
+        if let Some(by_rank) = res.get_mut(&v.candidates.first_valid) {
+            *by_rank
+                .entry(v.candidates.rank())
+                .or_insert(VoteCount::EMPTY) += v.count;
+        }
+    }
+    res
+}
+
+// The rank origins of all the rounds, with the candidates of each round by descending votes,
+// then by name, like the tally.
+fn rank_origins(
+    stats: &[RoundStatistics],
+    candidates_by_id: &HashMap<CandidateId, String>,
+) -> Vec<RankOrigin> {
+    let mut res: Vec<RankOrigin> = Vec::new();
+    for (idx, rs) in stats.iter().enumerate() {
+        let mut round: Vec<(u64, RankOrigin)> = rs
+            .rank_origin
+            .iter()
+            .flatten()
+            .filter_map(|(cid, by_rank)| {
+                let total: VoteCount = by_rank.values().cloned().sum();
+                candidates_by_id.get(cid).map(|name| {
+                    let origin = RankOrigin {
+                        round: RoundId::from_index(idx),
+                        candidate: name.clone(),
+                        votes_by_rank: by_rank.iter().map(|(r, vc)| (*r, vc.0)).collect(),
+                    };
+                    (total.0, origin)
+                })
+            })
+            .collect();
+        round.sort_by(|(c1, o1), (c2, o2)| c2.cmp(c1).then(o1.candidate.cmp(&o2.candidate)));
+        res.extend(round.into_iter().map(|(_, origin)| origin));
+    }
+    res
+}
+
 fn compute_tally(
     votes: &[VoteInternal],
     candidate_names: &[(String, CandidateId)],
@@ -678,14 +1342,15 @@ fn compute_tally(
 
 /// Returns the removed candidates, and the remaining votes
 fn run_one_round(
-    votes: &[VoteInternal],
+    votes: Vec<VoteInternal>,
     rules: &config::VoteRules,
     candidate_names: &[(String, CandidateId)],
-    num_round: u32,
+    num_round: RoundId,
+    prior_rounds: &[RoundStatistics],
 ) -> Result<RoundResult, VotingErrors> {
     // Initialize the tally with the current candidate names to capture all the candidates who do
     // not even have a vote.
-    let tally = compute_tally(votes, candidate_names);
+    let tally = compute_tally(&votes, candidate_names);
     debug!("tally: {:?}", tally);
 
     let vote_threshold = get_threshold(&tally);
@@ -704,18 +1369,68 @@ fn run_one_round(
                 .map(|(cid, count)| (*cid, *count, RoundCandidateStatusInternal::Elected))
                 .collect(),
             uwi_elimination_stats: Some((vec![], VoteCount::EMPTY)),
+            inactive: InactiveBallots::default(),
+            excluded: Vec::new(),
+            rank_origin: None,
+            notes: Vec::new(),
+            threshold: vote_threshold,
         };
         return Ok(RoundResult {
             votes: votes.to_vec(),
             stats,
             vote_threshold,
+            exhausted: InactiveBallots::default(),
         });
     }
 
     // Find the candidates to eliminate
-    let p = find_eliminated_candidates(&tally, rules, candidate_names, num_round)?;
+    let p = match rules.elimination_strategy.as_ref() {
+        Some(strategy) => {
+            let ordered_tally: Vec<(CandidateId, u64)> = candidate_names
+                .iter()
+                .map(|(_, cid)| (*cid, tally.get(cid).map_or(0, |vc| vc.0)))
+                .collect();
+            let ctx = RoundContext::new(
+                num_round,
+                candidate_names,
+                ordered_tally,
+                prior_rounds,
+                &votes,
+            );
+            let mut selected = strategy.select(&ctx)?;
+            selected.sort();
+            selected.dedup();
+            if selected.iter().any(|cid| !tally.contains_key(cid)) {
+                return Err(VotingErrors::NoCandidateToEliminate);
+            }
+            (selected, TiebreakSituation::Clean)
+        }
+        None => find_eliminated_candidates(&tally, rules, candidate_names, num_round)?,
+    };
     let resolved_tiebreak: TiebreakSituation = p.1;
     let eliminated_candidates: HashSet<CandidateId> = p.0.iter().cloned().collect();
+    let name_of = |cid: &CandidateId| {
+        candidate_names
+            .iter()
+            .find(|(_, cid2)| cid == cid2)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_default()
+    };
+    let mut notes: Vec<RoundNote> = Vec::new();
+    if rules.elimination_strategy.is_some() {
+        notes.extend(p.0.iter().map(|cid| RoundNote::Forced {
+            candidate: name_of(cid),
+        }));
+    } else if let TiebreakSituation::TiebreakOccured(tied) = &resolved_tiebreak {
+        if let Some(cid) = p.0.first() {
+            notes.push(RoundNote::Tiebreak {
+                candidates: tied.iter().map(name_of).collect(),
+                eliminated: name_of(cid),
+            });
+        }
+    } else if p.0.len() > 1 {
+        notes.push(RoundNote::BatchElimination { count: p.0.len() });
+    }
 
     // TODO strategy to pick the winning candidates
 
@@ -741,29 +1456,32 @@ fn run_one_round(
         })
         .collect();
 
+    let mut exhausted = InactiveBallots::default();
     // Filter the rest of the votes to simply keep the votes that still matter
     let rem_votes: Vec<VoteInternal> = votes
-        .iter()
+        .into_iter()
         .filter_map(|va| {
             // Remove the choices that are not valid anymore and collect statistics.
+            let old_first = va.candidates.first_valid;
             let new_rank = va.candidates.filtered_candidate(
                 &remaining_candidates,
                 rules.duplicate_candidate_mode,
                 rules.overvote_rule,
                 rules.max_skipped_rank_allowed,
+                rules.blank_counts_as_skipped,
             );
-            let old_first = va.candidates.first_valid;
-            let new_first = new_rank.clone().map(|nr| nr.first_valid);
+            let new_first = new_rank.as_ref().map(|nr| nr.first_valid);
 
             match new_first {
-                None => {
+                Err(reason) => {
                     // Ballot is now exhausted. Record the exhausted vote.
                     let e = elimination_stats
                         .entry(old_first)
                         .or_insert((HashMap::new(), VoteCount::EMPTY));
                     e.1 += va.count;
+                    add_inactive(&mut exhausted, *reason, va.count);
                 }
-                Some(new_first_cid) if new_first_cid != old_first => {
+                Ok(new_first_cid) if new_first_cid != old_first => {
                     // The ballot has been transfered. Record the transfer.
                     let e = elimination_stats
                         .entry(old_first)
@@ -776,7 +1494,7 @@ fn run_one_round(
                 }
             }
 
-            new_rank.map(|rc| VoteInternal {
+            new_rank.ok().map(|rc| VoteInternal {
                 candidates: rc,
                 count: va.count,
             })
@@ -837,8 +1555,14 @@ fn run_one_round(
         stats: RoundStatistics {
             candidate_stats,
             uwi_elimination_stats: None,
+            inactive: InactiveBallots::default(),
+            excluded: Vec::new(),
+            rank_origin: None,
+            notes,
+            threshold: vote_threshold,
         },
         vote_threshold,
+        exhausted,
     })
 }
 
@@ -846,17 +1570,37 @@ fn find_eliminated_candidates(
     tally: &HashMap<CandidateId, VoteCount>,
     rules: &config::VoteRules,
     candidate_names: &[(String, CandidateId)],
-    num_round: u32,
+    num_round: RoundId,
 ) -> Result<(Vec<CandidateId>, TiebreakSituation), VotingErrors> {
     // Try to eliminate candidates in batch
     if rules.elimination_algorithm == EliminationAlgorithm::Batch {
-        if let Some(v) = find_eliminated_candidates_batch(tally) {
-            return Ok((v, TiebreakSituation::Clean));
+        if let Some(mut v) = find_eliminated_candidates_batch(tally) {
+            // A protected "None of the above" candidate is only eliminated on its own. The
+            // candidates of the batch with more votes than it are kept as well, so that no
+            // candidate is eliminated before a weaker one.
+            if rules.protect_nota {
+                if let Some(nota) = rules.nota_candidate.as_ref() {
+                    let nota = CandidateName::new(nota, rules.name_normalization);
+                    let nota_cid = v.iter().copied().find(|cid| {
+                        candidate_names.iter().any(|(name, cid2)| {
+                            cid == cid2
+                                && CandidateName::new(name, rules.name_normalization) == nota
+                        })
+                    });
+                    if let Some(nota_cid) = nota_cid {
+                        let nota_count = tally[&nota_cid];
+                        v.retain(|cid| *cid != nota_cid && tally[cid] <= nota_count);
+                    }
+                }
+            }
+            if !v.is_empty() {
+                return Ok((v, TiebreakSituation::Clean));
+            }
         }
     }
 
     if let Some((v, tb)) =
-        find_eliminated_candidates_single(tally, rules.tiebreak_mode, candidate_names, num_round)
+        find_eliminated_candidates_single(tally, rules, candidate_names, num_round)
     {
         return Ok((v, tb));
     }
@@ -865,13 +1609,18 @@ fn find_eliminated_candidates(
     Err(VotingErrors::EmptyElection)
 }
 
+// The batch is the largest group of the lowest candidates whose total is strictly lower than
+// the count of the next candidate. A candidate whose count is equal to the total of the
+// candidates below it is not in the batch: the batch stops below it. The candidates with equal
+// counts are therefore always on the same side of the boundary.
 fn find_eliminated_candidates_batch(
     tally: &HashMap<CandidateId, VoteCount>,
 ) -> Option<Vec<CandidateId>> {
-    // Sort the candidates in increasing tally.
+    // Sort the candidates in increasing tally. The id breaks the ties, so that the order does
+    // not depend on the iteration order of the tally.
     let mut sorted_tally: Vec<(CandidateId, VoteCount)> =
         tally.iter().map(|(&cid, &vc)| (cid, vc)).collect();
-    sorted_tally.sort_by_key(|(_, vc)| *vc);
+    sorted_tally.sort_by_key(|(cid, vc)| (*vc, *cid));
 
     // the vote count for this candidate and the cumulative count (excluding the current one)
     let mut sorted_tally_cum: Vec<(CandidateId, VoteCount, VoteCount)> = Vec::new();
@@ -891,8 +1640,7 @@ fn find_eliminated_candidates_batch(
     let large_gap_idx = sorted_tally_cum
         .iter()
         .enumerate()
-        .filter(|(_, (_, cur_vc, previous_cum_count))| previous_cum_count < cur_vc)
-        .last();
+        .rfind(|(_, (_, cur_vc, previous_cum_count))| previous_cum_count < cur_vc);
 
     // The idx == 0 element is not relevant because the previous cumulative count was zero.
     if let Some((idx, _)) = large_gap_idx {
@@ -910,18 +1658,19 @@ fn find_eliminated_candidates_batch(
 }
 
 // Flag to indicate if a tiebreak happened.
-#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
 enum TiebreakSituation {
-    Clean,           // Did not happen
-    TiebreakOccured, // Happened and had to be resolved.
+    Clean, // Did not happen
+    // Happened and had to be resolved, between these candidates in the order of the tiebreak.
+    TiebreakOccured(Vec<CandidateId>),
 }
 
 // Elimination method for single candidates.
 fn find_eliminated_candidates_single(
     tally: &HashMap<CandidateId, VoteCount>,
-    tiebreak: TieBreakMode,
+    rules: &config::VoteRules,
     candidate_names: &[(String, CandidateId)],
-    num_round: u32,
+    num_round: RoundId,
 ) -> Option<(Vec<CandidateId>, TiebreakSituation)> {
     // TODO should be a programming error
     if tally.is_empty() {
@@ -958,7 +1707,7 @@ fn find_eliminated_candidates_single(
     }
 
     // Look at the tiebreak mode:
-    let mut sorted_candidates: Vec<CandidateId> = match tiebreak {
+    let mut sorted_candidates: Vec<CandidateId> = match rules.tiebreak_mode {
         TieBreakMode::UseCandidateOrder => {
             let candidate_order: HashMap<CandidateId, usize> = candidate_names
                 .iter()
@@ -989,7 +1738,12 @@ fn find_eliminated_candidates_single(
                     m.unwrap()
                 })
                 .collect();
-            let res = candidate_permutation_crypto(&cand_with_names, seed, num_round);
+            let res = candidate_permutation_crypto(
+                &cand_with_names,
+                seed,
+                num_round,
+                rules.legacy_random_tiebreak,
+            );
             debug!(
                 "find_eliminated_candidates_single: sorted candidates in elimination queue using tiebreak mode random: {:?}",
                 res
@@ -1010,7 +1764,58 @@ fn find_eliminated_candidates_single(
         let last = sc.last().unwrap();
         sorted_candidates.retain(|cid| cid != last);
     }
-    Some((sorted_candidates, TiebreakSituation::TiebreakOccured))
+    Some((sorted_candidates, TiebreakSituation::TiebreakOccured(sc)))
+}
+
+// A choice that is not a skipped rank.
+fn is_mark(choice: &Choice) -> bool {
+    !matches!(choice, Choice::Blank | Choice::Undervote)
+}
+
+// Why a ballot has no next candidate, once advance_voting failed. The rules are checked up to
+// the next continuing candidate (or undeclared write-in in the first round if
+// `undeclared_continues`), or up to the last mark if there is none: the skipped ranks at the
+// end of a ballot do not exhaust it by themselves.
+#[allow(clippy::too_many_arguments)]
+fn exhaust_reason(
+    choices: &[Choice],
+    passed: &[Choice],
+    still_valid: &HashSet<CandidateId>,
+    undeclared_continues: bool,
+    duplicate_policy: DuplicateCandidateMode,
+    overvote: OverVoteRule,
+    skipped_ranks: MaxSkippedRank,
+    blank_counts_as_skipped: bool,
+) -> ExhaustReason {
+    let next = choices
+        .iter()
+        .enumerate()
+        .find_map(|(idx, choice)| match choice {
+            Choice::Filled(cid) if still_valid.contains(cid) => Some((idx, Some(*cid))),
+            Choice::Undeclared if undeclared_continues => Some((idx, None)),
+            _ => None,
+        });
+    let (slice, next_candidate) = match next {
+        Some((idx, next_candidate)) => (&choices[..idx], next_candidate),
+        None => {
+            let end = choices.iter().rposition(is_mark).map_or(0, |idx| idx + 1);
+            (&choices[..end], None)
+        }
+    };
+    match check_advance_rules(
+        slice,
+        next_candidate,
+        passed,
+        duplicate_policy,
+        overvote,
+        skipped_ranks,
+        blank_counts_as_skipped,
+    ) {
+        Some(AdvanceRuleCheck::DuplicateCandidates) => ExhaustReason::RepeatedRankings,
+        Some(AdvanceRuleCheck::FailOvervote) => ExhaustReason::Overvote,
+        Some(AdvanceRuleCheck::FailSkippedRank) => ExhaustReason::SkippedRankings,
+        None => ExhaustReason::ExhaustedChoices,
+    }
 }
 
 // All the failure modes when trying to read the next element in a ballot
@@ -1021,95 +1826,97 @@ enum AdvanceRuleCheck {
     FailSkippedRank,
 }
 
-// True if the rules are respected
+// A choice that ranks a candidate, for the duplicates. All the undeclared write-ins are the
+// same candidate, as in the reference implementation.
+fn ranks_candidate(choice: &Choice) -> bool {
+    matches!(choice, Choice::Filled(_) | Choice::Undeclared)
+}
+
+// None if the rules are respected.
+// The rules are checked rank by rank, in the order of the reference implementation: at each
+// rank, the skipped rankings before it, then a candidate ranked again, then an overvote. The
+// first rule that fails is the reason why the ballot is exhausted. The next candidate (the first
+// choice after the initial slice) also counts as a duplicate if it was already seen, since the
+// ballot would move to it.
 fn check_advance_rules(
     initial_slice: &[Choice],
+    next_candidate: Option<CandidateId>,
+    passed: &[Choice],
     duplicate_policy: DuplicateCandidateMode,
     overvote: OverVoteRule,
     skipped_ranks: MaxSkippedRank,
+    blank_counts_as_skipped: bool,
 ) -> Option<AdvanceRuleCheck> {
-    if duplicate_policy == DuplicateCandidateMode::Exhaust {
-        let mut seen_cids: HashSet<CandidateId> = HashSet::new();
-        for choice in initial_slice.iter() {
-            match *choice {
-                Choice::Filled(cid) if seen_cids.contains(&cid) => {
+    let max_skipped: Option<usize> = match skipped_ranks {
+        MaxSkippedRank::Unlimited => None,
+        MaxSkippedRank::ExhaustOnFirstOccurence => Some(0),
+        MaxSkippedRank::MaxAllowed(range_len) => Some(range_len as usize),
+    };
+    // The ballots are short: comparing with all the previous choices does not allocate, unlike
+    // a set of the seen candidates.
+    let is_repeated = |choice: &Choice, before: &[Choice]| {
+        duplicate_policy == DuplicateCandidateMode::Exhaust
+            && passed.iter().chain(before).any(|seen| seen == choice)
+    };
+    let mut num_skipped: usize = 0;
+    for (idx, choice) in initial_slice.iter().enumerate() {
+        // When the blanks do not count as skipped ranks, they are ignored: they neither count
+        // nor interrupt a sequence of undervotes.
+        let is_skipped = match choice {
+            Choice::Undervote => Some(true),
+            Choice::Blank => blank_counts_as_skipped.then_some(true),
+            _ => Some(false),
+        };
+        match is_skipped {
+            Some(true) => {
+                num_skipped += 1;
+                if max_skipped.is_some_and(|max| num_skipped > max) {
+                    debug!(
+                        "advance_voting: exhaust on skipped rankings: {:?}",
+                        initial_slice
+                    );
+                    return Some(AdvanceRuleCheck::FailSkippedRank);
+                }
+            }
+            None => {}
+            Some(false) => {
+                num_skipped = 0;
+                if ranks_candidate(choice) && is_repeated(choice, &initial_slice[..idx]) {
                     return Some(AdvanceRuleCheck::DuplicateCandidates);
                 }
-                Choice::Filled(cid) => {
-                    seen_cids.insert(cid);
+                if *choice == Choice::Overvote && overvote == OverVoteRule::ExhaustImmediately {
+                    debug!(
+                        "advance_voting: has initial overvote and exhausting {:?}",
+                        initial_slice
+                    );
+                    return Some(AdvanceRuleCheck::FailOvervote);
                 }
-                _ => {}
             }
         }
     }
-
-    // Overvote rule
-    let has_initial_overvote = initial_slice.iter().any(|c| *c == Choice::Overvote);
-    if has_initial_overvote && overvote == OverVoteRule::ExhaustImmediately {
-        debug!(
-            "advance_voting: has initial overvote and exhausting {:?}",
-            initial_slice
-        );
-        return Some(AdvanceRuleCheck::FailOvervote);
+    match next_candidate {
+        Some(cid) if is_repeated(&Choice::Filled(cid), initial_slice) => {
+            Some(AdvanceRuleCheck::DuplicateCandidates)
+        }
+        _ => None,
     }
-
-    // Skipped rank rule
-    if skipped_ranks == MaxSkippedRank::ExhaustOnFirstOccurence {
-        let has_skippable_elements = initial_slice
-            .iter()
-            .any(|choice| matches!(choice, Choice::BlankOrUndervote));
-        if has_skippable_elements {
-            debug!(
-                "advance_voting:exhaust on first blank occurence: {:?}",
-                initial_slice
-            );
-            return Some(AdvanceRuleCheck::FailSkippedRank);
-        }
-    }
-
-    if let MaxSkippedRank::MaxAllowed(range_len) = skipped_ranks {
-        let mut start_skipped_block: Option<usize> = None;
-        let rl = range_len as usize;
-        for (idx, choice) in initial_slice.iter().enumerate() {
-            match (choice, start_skipped_block) {
-                // We went beyond the threshold
-                (Choice::BlankOrUndervote, Some(start_idx)) if idx >= start_idx + rl => {
-                    debug!(
-                        "advance_voting:exhaust on multiple occurence: {:?}",
-                        initial_slice
-                    );
-                    return Some(AdvanceRuleCheck::FailSkippedRank);
-                }
-                // We are starting a new block
-                (Choice::BlankOrUndervote, None) => {
-                    start_skipped_block = Some(idx);
-                }
-                // We are exiting a block or encountering a new element. Reset.
-                _ => {
-                    start_skipped_block = None;
-                }
-            }
-        }
-    }
-
-    None
-}
+}
 
 // The algorithm is lazy. It will only apply the rules up to finding the next candidate.
-// Returned bool indicades if UWI's were encountered in the move to the first valid candidate.
-// TODO: this function slightly deviates for the reference implementation in the following case:
-// (blanks 1) Undeclared (blanks 2) Filled(_) ...
-// The reference implementation will only validate up to Undeclared and this function will validate
-// up to Filled.
-// In practice, this will cause the current implementation to immediately discard a ballot, while the
-// reference implementation first assigns the ballot to UWI and then exhausts it.
+// Returns the next candidate and its position.
+// In the first round, a ballot that starts with an undeclared write-in is only validated up to
+// the write-in (see advance_voting_initial), as in the reference implementation: the ballot
+// counts for the write-ins in the first round, and is exhausted afterwards if the choices after
+// the write-in break the rules (see the conformance cases).
 fn advance_voting(
     choices: &[Choice],
+    passed: &[Choice],
     still_valid: &HashSet<CandidateId>,
     duplicate_policy: DuplicateCandidateMode,
     overvote: OverVoteRule,
     skipped_ranks: MaxSkippedRank,
-) -> Option<(CandidateId, Vec<Choice>)> {
+    blank_counts_as_skipped: bool,
+) -> Option<(CandidateId, usize)> {
     // Find a potential candidate.
     let first_candidate = choices
         .iter()
@@ -1123,25 +1930,36 @@ fn advance_voting(
         // overvote or multiple blanks occured.
         let initial_slice = &choices[..idx];
 
-        if check_advance_rules(initial_slice, duplicate_policy, overvote, skipped_ranks).is_some() {
+        if check_advance_rules(
+            initial_slice,
+            Some(*cid),
+            passed,
+            duplicate_policy,
+            overvote,
+            skipped_ranks,
+            blank_counts_as_skipped,
+        )
+        .is_some()
+        {
             return None;
         }
 
-        let final_slice = &choices[idx + 1..];
-        Some((*cid, final_slice.to_vec()))
+        Some((*cid, idx))
     } else {
         None
     }
 }
 
-// For the 1st round, the initial choice may also be undeclared.
+// For the 1st round, the initial choice may also be undeclared. Returns the position of this
+// choice.
 fn advance_voting_initial(
     choices: &[Choice],
     still_valid: &HashSet<CandidateId>,
     duplicate_policy: DuplicateCandidateMode,
     overvote: OverVoteRule,
     skipped_ranks: MaxSkippedRank,
-) -> Option<Vec<Choice>> {
+    blank_counts_as_skipped: bool,
+) -> Option<usize> {
     // Find a potential candidate.
     let first_candidate: Option<usize> =
         choices
@@ -1157,13 +1975,21 @@ fn advance_voting_initial(
         // overvote or multiple blanks occured.
         let initial_slice = &choices[..idx];
 
-        if check_advance_rules(initial_slice, duplicate_policy, overvote, skipped_ranks).is_some() {
+        if check_advance_rules(
+            initial_slice,
+            None,
+            &[],
+            duplicate_policy,
+            overvote,
+            skipped_ranks,
+            blank_counts_as_skipped,
+        )
+        .is_some()
+        {
             return None;
         }
 
-        // This final slice includes the pivot element.
-        let final_slice = &choices[idx..];
-        Some(final_slice.to_vec())
+        Some(idx)
     } else {
         None
     }
@@ -1175,6 +2001,87 @@ struct CheckResult {
     candidates: Vec<(String, CandidateId)>,
     uwi_first_votes: Vec<VoteInternal>,
     count_exhausted_uwi_first_round: VoteCount,
+    uwi_exhausted_reasons: InactiveBallots,
+    inactive_first_round: InactiveBallots,
+    rank_stats: RankStatistics,
+    // The votes that rank each candidate at any position, before the truncation of the rankings.
+    ranked_on: HashMap<CandidateId, VoteCount>,
+    // The first choices of the excluded candidates, in the order of registration.
+    excluded: Vec<ExcludedFirstChoices>,
+    // The votes inactive from the first round with OverVoteScope::WholeBallot only.
+    whole_ballot_overvotes: VoteCount,
+}
+
+// Adds the marks of one ballot to the statistics per rank position.
+fn add_rank_stats(stats: &mut RankStatistics, choices: &[Choice], count: VoteCount) {
+    stats.total_votes += count.0;
+    let marked: Vec<bool> = choices.iter().map(is_mark).collect();
+    let last_mark = match marked.iter().rposition(|m| *m) {
+        Some(idx) => idx,
+        None => return,
+    };
+    while stats.positions.len() <= last_mark {
+        stats.positions.push(RankPositionStats {
+            position: (stats.positions.len() + 1) as u32,
+            last_mark: 0,
+            skip_then_return: 0,
+        });
+    }
+    stats.positions[last_mark].last_mark += count.0;
+    let skipped: Vec<usize> = (0..last_mark).filter(|idx| !marked[*idx]).collect();
+    for idx in skipped.iter() {
+        stats.positions[*idx].skip_then_return += count.0;
+    }
+    if !skipped.is_empty() {
+        stats.skip_then_return_votes += count.0;
+    }
+}
+
+// Removes the rankings of a ballot that the tabulation can never read, so that a ballot that
+// ranks the same candidates many times is not scanned again at each round:
+// - with DuplicateCandidateMode::SkipDuplicate, the later rankings of a candidate are skipped
+//   like the rankings of an eliminated candidate: they only stop a sequence of skipped
+//   rankings. They are dropped, unless they follow a skipped ranking. The positions of the
+//   rankings are needed by VoteRules::track_rank_origin: the ballot is then kept as is.
+// - with DuplicateCandidateMode::Exhaust, the ballot is exhausted when it reaches a candidate
+//   ranked again: the rankings after it are dropped, and the ballot is cut to
+//   VoteRules::exhaust_rankings_cap.
+// The overvotes of the whole ballot (OverVoteScope::WholeBallot) are looked for before.
+fn drop_unreadable_rankings(choices: &mut Vec<Choice>, rules: &config::VoteRules) {
+    match rules.duplicate_candidate_mode {
+        DuplicateCandidateMode::SkipDuplicate if !rules.track_rank_origin => {
+            let mut seen: HashSet<CandidateId> = HashSet::new();
+            // True if the previous choice that counts for the skipped rankings is not a skipped
+            // ranking: a ranking of a candidate after it changes nothing.
+            let mut after_mark = false;
+            choices.retain(|choice| {
+                let keep = match choice {
+                    Choice::Filled(cid) => seen.insert(*cid) || !after_mark,
+                    _ => true,
+                };
+                match choice {
+                    Choice::Undervote => after_mark = false,
+                    Choice::Blank if rules.blank_counts_as_skipped => after_mark = false,
+                    Choice::Blank => {}
+                    _ => after_mark = true,
+                }
+                keep
+            });
+        }
+        DuplicateCandidateMode::SkipDuplicate => {}
+        DuplicateCandidateMode::Exhaust => {
+            let repeated = choices
+                .iter()
+                .enumerate()
+                .position(|(idx, c)| ranks_candidate(c) && choices[..idx].contains(c));
+            if let Some(idx) = repeated {
+                choices.truncate(idx + 1);
+            }
+            if let Some(cap) = rules.exhaust_rankings_cap {
+                choices.truncate(cap as usize);
+            }
+        }
+    }
 }
 
 // Candidates are returned in the same order.
@@ -1182,25 +2089,38 @@ fn checks(
     coll: &[Ballot],
     reg_candidates: &[config::Candidate],
     rules: &config::VoteRules,
+    sample_rate: SampleRate,
 ) -> Result<CheckResult, VotingErrors> {
     debug!("checks: coll size: {:?}", coll.len());
-    let blacklisted_candidates: HashSet<String> = reg_candidates
+    // The names are looked up for every choice of every ballot: the maps are built once, and
+    // borrow the matching keys of the names instead of copying them.
+    let normalization = rules.name_normalization;
+    let blacklisted_candidates: HashSet<&str> = reg_candidates
         .iter()
-        .filter_map(|c| {
-            if c.excluded {
-                Some(c.name.clone())
-            } else {
-                None
-            }
-        })
+        .filter(|c| c.excluded)
+        .map(|c| c.name.key())
         .collect();
-    let candidates: HashMap<String, CandidateId> = reg_candidates
+    let candidates: HashMap<&str, CandidateId> = reg_candidates
         .iter()
         .enumerate()
-        .map(|(idx, c)| (c.name.clone(), CandidateId((idx + 1) as u32)))
+        .map(|(idx, c)| (c.name.key(), CandidateId((idx + 1) as u32)))
         .collect();
 
-    let valid_cids: HashSet<CandidateId> = candidates.values().cloned().collect();
+    // The excluded candidates are never continuing: their rankings are skipped.
+    let valid_cids: HashSet<CandidateId> = candidates
+        .iter()
+        .filter(|(key, _)| !blacklisted_candidates.contains(*key))
+        .map(|(_, cid)| *cid)
+        .collect();
+    let names_by_cid: HashMap<CandidateId, &str> = reg_candidates
+        .iter()
+        .filter_map(|c| {
+            candidates
+                .get(c.name.key())
+                .map(|cid| (*cid, c.name.as_str()))
+        })
+        .collect();
+    let mut excluded: HashMap<CandidateId, ExcludedFirstChoices> = HashMap::new();
 
     // The votes that are validated and that have a candidate from the first round
     let mut validated_votes: Vec<VoteInternal> = vec![];
@@ -1208,24 +2128,40 @@ fn checks(
     let mut uwi_validated_votes: Vec<VoteInternal> = vec![];
     // The count of votes that are immediately exhausted with a UWI in the first round.
     let mut uwi_exhausted_first_round: VoteCount = VoteCount::EMPTY;
-
-    for v in coll.iter() {
+    // The reasons why the votes above are exhausted at the end of the first round.
+    let mut uwi_exhausted_reasons = InactiveBallots::default();
+    // The votes that are discarded before the first round, except the votes without any mark.
+    let mut inactive_first_round = InactiveBallots::default();
+    let mut rank_stats = RankStatistics::default();
+    let mut ranked_on: HashMap<CandidateId, VoteCount> = HashMap::new();
+    let mut whole_ballot_overvotes = VoteCount::EMPTY;
+
+    for (idx, v) in coll.iter().enumerate() {
+        // The decision for the ballot, only for the ballots logged (see
+        // Builder::debug_sample_rate).
+        let position: String;
+        let key: &str = match v.id.as_ref() {
+            Some(id) => id,
+            None => {
+                position = format!("#{}", idx + 1);
+                &position
+            }
+        };
+        let sampled = sample_rate.contains(key);
+        let mut decision: Option<String> = None;
         let mut choices: Vec<Choice> = vec![];
         for c in v.candidates.iter() {
             let choice: Choice = match c {
-                BallotChoice::Candidate(name) if blacklisted_candidates.contains(name) => {
-                    unimplemented!("blacklisted not implemented");
-                }
                 BallotChoice::Candidate(name) => {
-                    if let Some(cid) = candidates.get(name) {
+                    if let Some(cid) = candidates.get(normalization.key(name).as_ref()) {
                         Choice::Filled(*cid)
                     } else {
                         // Undeclared candidate
                         Choice::Undeclared
                     }
                 }
-                BallotChoice::Blank => Choice::BlankOrUndervote,
-                BallotChoice::Undervote => Choice::BlankOrUndervote,
+                BallotChoice::Blank => Choice::Blank,
+                BallotChoice::Undervote => Choice::Undervote,
                 BallotChoice::Overvote => Choice::Overvote,
                 BallotChoice::UndeclaredWriteIn => Choice::Undeclared,
             };
@@ -1233,43 +2169,124 @@ fn checks(
         }
 
         let count = VoteCount(v.count);
+        add_rank_stats(&mut rank_stats, &choices, count);
+        let ranked: HashSet<CandidateId> = choices
+            .iter()
+            .filter_map(|c| match c {
+                Choice::Filled(cid) => Some(*cid),
+                _ => None,
+            })
+            .collect();
+        for cid in ranked {
+            *ranked_on.entry(cid).or_insert(VoteCount::EMPTY) += count;
+        }
+        // The ballot is truncated before the first choice is looked for: a ballot whose first
+        // rankings are skipped may have no choice left.
+        if let Some(max_rankings) = rules.max_rankings_allowed {
+            choices.truncate(max_rankings as usize);
+        }
+        // With the whole ballot scope, an overvote at any read ranking makes the ballot inactive
+        // before the first round, including the rankings after a repeated candidate.
+        let whole_ballot_overvote = rules.overvote_scope == OverVoteScope::WholeBallot
+            && rules.overvote_rule == OverVoteRule::ExhaustImmediately
+            && choices.contains(&Choice::Overvote);
+        drop_unreadable_rankings(&mut choices, rules);
         // The first choice is a valid one. A ballot can be constructed out of it.
 
-        let initial_advance_opt = advance_voting_initial(
+        let mut initial_advance_opt = advance_voting_initial(
             &choices,
             &valid_cids,
             rules.duplicate_candidate_mode,
             rules.overvote_rule,
             rules.max_skipped_rank_allowed,
+            rules.blank_counts_as_skipped,
         );
+        if whole_ballot_overvote && initial_advance_opt.take().is_some() {
+            whole_ballot_overvotes += count;
+        }
+
+        // A ballot whose first ranked candidate is excluded is reported with this candidate,
+        // with the candidate it counts for in the first round.
+        let first_ranked: Option<CandidateId> = choices
+            .iter()
+            .find_map(|c| match c {
+                Choice::Filled(cid) => Some(Some(*cid)),
+                Choice::Undeclared => Some(None),
+                _ => None,
+            })
+            .flatten();
+        if let Some(cid) = first_ranked.filter(|cid| !valid_cids.contains(cid)) {
+            let e = excluded.entry(cid).or_insert_with(|| ExcludedFirstChoices {
+                name: names_by_cid[&cid].to_string(),
+                votes: VoteCount::EMPTY,
+                transfers: BTreeMap::new(),
+                exhausted: VoteCount::EMPTY,
+            });
+            e.votes += count;
+            let next = initial_advance_opt.map(|idx| match choices[idx] {
+                Choice::Filled(next_cid) => names_by_cid[&next_cid],
+                _ => UNDECLARED_WRITE_INS,
+            });
+            match next {
+                Some(name) => {
+                    *e.transfers
+                        .entry(name.to_string())
+                        .or_insert(VoteCount::EMPTY) += count
+                }
+                None => e.exhausted += count,
+            }
+        }
 
-        if let Some(initial_advance) = initial_advance_opt {
+        if let Some(initial_idx) = initial_advance_opt {
+            // This slice starts with the pivot element.
+            let initial_advance = &choices[initial_idx..];
             // Check the head of the ballot.
             if let Some(Choice::Filled(cid)) = initial_advance.first() {
-                let candidates = RankedChoice {
-                    first_valid: *cid,
-                    rest: initial_advance[1..].to_vec(),
-                };
+                if sampled {
+                    decision = Some(format!("counts for {}", names_by_cid[cid]));
+                }
+                let candidates = RankedChoice::new(initial_advance).at_offset(initial_idx);
                 validated_votes.push(VoteInternal { candidates, count });
             } else if let Some(Choice::Undeclared) = initial_advance.first() {
                 // Valid and first choice is undeclared. See if the rest is a valid vote.
-                if let Some((first_cid, rest)) = advance_voting(
-                    &initial_advance,
+                if let Some((_, idx)) = advance_voting(
+                    initial_advance,
+                    &[],
                     &valid_cids,
                     rules.duplicate_candidate_mode,
                     rules.overvote_rule,
                     rules.max_skipped_rank_allowed,
+                    rules.blank_counts_as_skipped,
                 ) {
-                    // The vote is still valid by advancing, we keep it
-                    let candidates = RankedChoice {
-                        first_valid: first_cid,
-                        rest,
-                    };
+                    // The vote is still valid by advancing, we keep it. The undeclared write-in
+                    // stays in the passed choices, since ranking it again is a duplicate.
+                    if sampled {
+                        decision = Some(format!("counts for {}", UNDECLARED_WRITE_INS));
+                    }
+                    let candidates =
+                        RankedChoice::with_head(initial_advance, idx).at_offset(initial_idx);
                     uwi_validated_votes.push(VoteInternal { candidates, count });
                 } else {
                     // The vote was valid up to undeclared but not valid anymore after it.
                     // Exhaust immediately.
                     uwi_exhausted_first_round += count;
+                    let reason = exhaust_reason(
+                        initial_advance,
+                        &[],
+                        &valid_cids,
+                        false,
+                        rules.duplicate_candidate_mode,
+                        rules.overvote_rule,
+                        rules.max_skipped_rank_allowed,
+                        rules.blank_counts_as_skipped,
+                    );
+                    if sampled {
+                        decision = Some(format!(
+                            "exhausted after {} ({:?})",
+                            UNDECLARED_WRITE_INS, reason
+                        ));
+                    }
+                    add_inactive(&mut uwi_exhausted_reasons, reason, count);
                 }
             } else {
                 panic!(
@@ -1277,9 +2294,44 @@ fn checks(
                     choices, initial_advance
                 );
             }
-        } else {
+        } else if whole_ballot_overvote {
+            if sampled {
+                decision =
+                    Some("inactive before the first round (Overvote, whole ballot)".to_string());
+            }
+            add_inactive(&mut inactive_first_round, ExhaustReason::Overvote, count);
+        } else if choices.iter().any(is_mark) {
             // Vote is being discarded, nothing to read in it with the given rules.
+            let reason = exhaust_reason(
+                &choices,
+                &[],
+                &valid_cids,
+                true,
+                rules.duplicate_candidate_mode,
+                rules.overvote_rule,
+                rules.max_skipped_rank_allowed,
+                rules.blank_counts_as_skipped,
+            );
+            if sampled {
+                decision = Some(format!("inactive before the first round ({:?})", reason));
+            }
+            add_inactive(&mut inactive_first_round, reason, count);
+        } else if sampled {
+            decision = Some("no mark".to_string());
         }
+        if let Some(decision) = decision {
+            debug!(
+                "checks: ballot {} (count {}): {:?} -> {:?}: {}",
+                key, v.count, v.candidates, choices, decision
+            );
+        }
+    }
+
+    if whole_ballot_overvotes > VoteCount::EMPTY {
+        info!(
+            "{} votes with an overvote after their first continuing candidate are inactive from the first round (whole ballot overvote scope)",
+            whole_ballot_overvotes.0
+        );
     }
 
     debug!(
@@ -1290,7 +2342,12 @@ fn checks(
 
     let ordered_candidates: Vec<(String, CandidateId)> = reg_candidates
         .iter()
-        .filter_map(|c| candidates.get(&c.name).map(|cid| (c.name.clone(), *cid)))
+        .filter(|c| !c.excluded)
+        .filter_map(|c| {
+            candidates
+                .get(c.name.key())
+                .map(|cid| (c.name.to_string(), *cid))
+        })
         .collect();
 
     debug!("checks: ordered_candidates {:?}", ordered_candidates);
@@ -1299,20 +2356,2153 @@ fn checks(
         uwi_first_votes: uwi_validated_votes,
         candidates: ordered_candidates,
         count_exhausted_uwi_first_round: uwi_exhausted_first_round,
+        uwi_exhausted_reasons,
+        inactive_first_round,
+        rank_stats,
+        ranked_on,
+        whole_ballot_overvotes,
+        excluded: reg_candidates
+            .iter()
+            .filter(|c| c.excluded)
+            .filter_map(|c| {
+                let cid = candidates.get(c.name.key())?;
+                Some(
+                    excluded
+                        .remove(cid)
+                        .unwrap_or_else(|| ExcludedFirstChoices {
+                            name: c.name.to_string(),
+                            votes: VoteCount::EMPTY,
+                            transfers: BTreeMap::new(),
+                            exhausted: VoteCount::EMPTY,
+                        }),
+                )
+            })
+            .collect(),
     })
 }
 
 /// Generates a "random" permutation of the candidates. Random in this context means hard to guess in advance.
-/// This uses a cryptographic algorithm that is resilient to collisions.
+/// This uses a cryptographic algorithm that is resilient to collisions (see the `tiebreak` module).
 fn candidate_permutation_crypto(
     candidates: &[(CandidateId, String)],
-    seed: u32,
-    num_round: u32,
+    seed: u64,
+    num_round: RoundId,
+    legacy: bool,
 ) -> Vec<CandidateId> {
-    let mut data: Vec<(CandidateId, String)> = candidates
+    let names: Vec<String> = candidates.iter().map(|(_, name)| name.clone()).collect();
+    let sorted_names = if legacy {
+        tiebreak::legacy_permutation(seed, num_round.0, &names)
+    } else {
+        tiebreak::permutation(seed, num_round.0, &names)
+    };
+    sorted_names
         .iter()
-        .map(|(cid, name)| (*cid, format!("{:08}{:08}{}", seed, num_round, name)))
-        .collect();
-    data.sort_by_key(|p| p.1.clone());
-    data.iter().map(|p| p.0).collect()
+        .filter_map(|name| {
+            candidates
+                .iter()
+                .find(|(_, n)| n == name)
+                .map(|(cid, _)| *cid)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continue_to_completion() {
+        let ballots: Vec<Vec<&str>> = vec![
+            vec!["A", "B"],
+            vec!["A", "B"],
+            vec!["A", "B"],
+            vec!["A", "B"],
+            vec!["B", "C"],
+            vec!["B", "C"],
+            vec!["C", "B"],
+        ];
+        let plain = run_election1(&ballots, &VoteRules::default()).unwrap();
+        let rules = VoteRules {
+            continue_to_completion: true,
+            ..VoteRules::default()
+        };
+        let res = run_election1(&ballots, &rules).unwrap();
+
+        assert_eq!(plain.round_stats.len(), 1);
+        assert_eq!(res.winners, plain.winners);
+        assert_eq!(res.threshold, plain.threshold);
+        assert_eq!(
+            res.round_stats[0].tally_results_elected,
+            plain.round_stats[0].tally_results_elected
+        );
+
+        let informational: Vec<bool> = res.round_stats.iter().map(|rs| rs.informational).collect();
+        assert_eq!(informational, vec![false, true, true]);
+        let rounds: Vec<u32> = res.round_stats.iter().map(|rs| rs.round.0).collect();
+        assert_eq!(rounds, vec![1, 2, 3]);
+        assert_eq!(res.round_stats[2].tally, vec![("A".to_string(), 4)]);
+        assert!(res.round_stats[1..]
+            .iter()
+            .all(|rs| rs.tally_results_elected.is_empty()));
+    }
+
+    #[test]
+    fn first_majority_round() {
+        // A has the majority from the first round. The informational rounds continue until the
+        // third round.
+        let ballots: Vec<Vec<&str>> = vec![
+            vec!["A", "B"],
+            vec!["A", "B"],
+            vec!["A", "B"],
+            vec!["A", "B"],
+            vec!["B", "C"],
+            vec!["B", "C"],
+            vec!["C", "B"],
+        ];
+        let rules = VoteRules {
+            continue_to_completion: true,
+            ..VoteRules::default()
+        };
+        let res = run_election1(&ballots, &rules).unwrap();
+        assert_eq!(res.round_stats.len(), 3);
+        assert_eq!(
+            res.first_majority_round,
+            vec![("A".to_string(), Some(RoundId(1)))]
+        );
+
+        // A has the majority in the first round, which resolves a tiebreak between B and C: A
+        // is only declared in the second round.
+        let ballots = [vec!["A"], vec!["A"], vec!["A"], vec!["B"], vec!["C"]];
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        assert_eq!(res.round_stats.len(), 2);
+        assert!(res.round_stats[0].tally_results_elected.is_empty());
+        assert_eq!(res.round_stats[1].tally_results_elected, vec!["A"]);
+        assert_eq!(
+            res.first_majority_round,
+            vec![("A".to_string(), Some(RoundId(1)))]
+        );
+    }
+
+    #[test]
+    fn debug_sample_rate() {
+        let keys: Vec<String> = (1..=10000).map(|idx| format!("#{}", idx)).collect();
+        let sample = |rate: SampleRate| -> Vec<&String> {
+            keys.iter().filter(|k| rate.contains(k)).collect()
+        };
+        // The same ballots are chosen by two runs.
+        let rate: SampleRate = "0.01".parse().unwrap();
+        let first = sample(rate);
+        assert_eq!(first, sample(SampleRate::new(0.01).unwrap()));
+        assert!(first.len() > 50 && first.len() < 150, "{}", first.len());
+        // A larger sample contains the smaller one.
+        let larger = sample(SampleRate::new(0.1).unwrap());
+        assert!(first.iter().all(|k| larger.contains(k)));
+        assert!(sample(SampleRate::NONE).is_empty());
+        assert!(sample("0".parse().unwrap()).is_empty());
+        assert_eq!(sample(SampleRate::new(1.0).unwrap()).len(), keys.len());
+        assert_eq!(SampleRate::new(-0.1), None);
+
+        // The tabulation is the same with the logs.
+        let ballots = [vec!["A", "B"], vec!["B"], vec!["C", "A"], vec![]];
+        let result = |rate: SampleRate| {
+            let mut builder = Builder::new(&VoteRules::default())
+                .unwrap()
+                .debug_sample_rate(rate);
+            for names in ballots.iter() {
+                let choices = names
+                    .iter()
+                    .map(|n| BallotChoice::Candidate(n.to_string()))
+                    .collect();
+                builder.add_ballot(Ballot::new(choices, 1)).unwrap();
+            }
+            run_election(&builder).unwrap()
+        };
+        assert_eq!(
+            result(SampleRate::new(1.0).unwrap()),
+            result(SampleRate::NONE)
+        );
+    }
+
+    #[test]
+    fn repeated_rankings_are_dropped() {
+        // The ballots of the election, by rows of names. An empty name is an undervote.
+        let builder = |rules: &VoteRules, ballots: &[(Vec<&str>, u32)]| {
+            let mut builder = Builder::new(rules)
+                .unwrap()
+                .candidates(&["A".to_string(), "B".to_string(), "C".to_string()])
+                .unwrap();
+            for (names, count) in ballots.iter() {
+                let groups: Vec<Vec<String>> = names
+                    .iter()
+                    .map(|n| match *n {
+                        "" => vec![],
+                        n => vec![n.to_string()],
+                    })
+                    .collect();
+                builder.add_vote(&groups, *count).unwrap();
+            }
+            builder
+        };
+        // The longest internal ballot.
+        let max_len = |b: &Builder| {
+            let cr = checks(
+                &b._votes,
+                b._candidates.as_ref().unwrap(),
+                &b._rules,
+                SampleRate::NONE,
+            )
+            .unwrap();
+            cr.votes
+                .iter()
+                .map(|v| v.candidates.choices.len())
+                .max()
+                .unwrap()
+        };
+        let adversarial: Vec<&str> = ["A", "B", "C"].repeat(50);
+        let others = vec![(vec!["B", "C"], 3), (vec!["C", "B"], 2)];
+        for (mode, canonical) in [
+            (DuplicateCandidateMode::SkipDuplicate, vec!["A", "B", "C"]),
+            (DuplicateCandidateMode::Exhaust, vec!["A", "B", "C", "A"]),
+        ] {
+            let rules = VoteRules {
+                duplicate_candidate_mode: mode,
+                ..VoteRules::default()
+            };
+            let mut ballots = others.clone();
+            ballots.push((adversarial.clone(), 4));
+            let b = builder(&rules, &ballots);
+            assert_eq!(max_len(&b), canonical.len(), "{:?}", mode);
+            let mut expected = others.clone();
+            expected.push((canonical, 4));
+            // The rank statistics describe the ballots as cast, and are not compared.
+            let res = run_election(&b).unwrap();
+            let expected_res = run_election(&builder(&rules, &expected)).unwrap();
+            assert_eq!(res.winners, expected_res.winners, "{:?}", mode);
+            assert_eq!(res.round_stats, expected_res.round_stats, "{:?}", mode);
+            assert_eq!(res.rank_stats.positions.len(), adversarial.len());
+        }
+
+        // The positions of the rankings are kept for the rank origins.
+        let rules = VoteRules {
+            track_rank_origin: true,
+            ..VoteRules::default()
+        };
+        let mut ballots = others.clone();
+        ballots.push((adversarial.clone(), 4));
+        assert_eq!(max_len(&builder(&rules, &ballots)), adversarial.len());
+
+        // A ranking repeated after an undervote stops the sequence of skipped rankings: the
+        // ballot goes to C after A, with at most one skipped ranking.
+        let rules = VoteRules {
+            max_skipped_rank_allowed: MaxSkippedRank::MaxAllowed(1),
+            ..VoteRules::default()
+        };
+        let ballots = vec![
+            (vec!["A", "", "A", "", "C"], 2),
+            (vec!["B"], 4),
+            (vec!["C"], 3),
+        ];
+        let b = builder(&rules, &ballots);
+        assert_eq!(max_len(&b), 5);
+        let res = run_election(&b).unwrap();
+        assert_eq!(res.winners, Some(vec!["C".to_string()]));
+
+        // With the whole ballot scope, the overvote after a repeated candidate still makes the
+        // ballot inactive: B wins under both duplicate modes.
+        for mode in [
+            DuplicateCandidateMode::SkipDuplicate,
+            DuplicateCandidateMode::Exhaust,
+        ] {
+            let rules = VoteRules {
+                duplicate_candidate_mode: mode,
+                overvote_rule: OverVoteRule::ExhaustImmediately,
+                overvote_scope: OverVoteScope::WholeBallot,
+                ..VoteRules::default()
+            };
+            let mut b = Builder::new(&rules)
+                .unwrap()
+                .candidates(&["A".to_string(), "B".to_string(), "C".to_string()])
+                .unwrap();
+            let overvoted: Vec<Vec<String>> = vec![
+                vec!["A".to_string()],
+                vec!["B".to_string()],
+                vec!["A".to_string()],
+                vec!["B".to_string(), "C".to_string()],
+            ];
+            b.add_vote(&overvoted, 2).unwrap();
+            b.add_vote(&[vec!["B".to_string()]], 2).unwrap();
+            b.add_vote(&[vec!["A".to_string()]], 1).unwrap();
+            let res = run_election(&b).unwrap();
+            assert_eq!(res.winners, Some(vec!["B".to_string()]), "{:?}", mode);
+            assert_eq!(res.whole_ballot_overvotes, 2, "{:?}", mode);
+        }
+
+        // The cap of the rankings with the duplicates exhausted bounds the undervotes: the
+        // ranking of B after them is not read, and the undervotes at the end are not kept.
+        let mut undervotes: Vec<&str> = vec!["A"];
+        undervotes.extend(vec![""; 100]);
+        undervotes.push("B");
+        let ballots = vec![(undervotes, 1), (vec!["B"], 1), (vec!["C"], 1)];
+        let rules = VoteRules {
+            duplicate_candidate_mode: DuplicateCandidateMode::Exhaust,
+            ..VoteRules::default()
+        };
+        assert_eq!(max_len(&builder(&rules, &ballots)), 102);
+        let rules = VoteRules {
+            exhaust_rankings_cap: Some(10),
+            ..rules
+        };
+        assert_eq!(max_len(&builder(&rules, &ballots)), 1);
+    }
+
+    #[test]
+    fn overvote_scope() {
+        // The ballots of B have an overvote in the final rank position, after B.
+        let run = |scope: OverVoteScope| {
+            let rules = VoteRules {
+                overvote_rule: OverVoteRule::ExhaustImmediately,
+                overvote_scope: scope,
+                ..VoteRules::default()
+            };
+            let mut builder = Builder::new(&rules).unwrap();
+            builder = builder
+                .candidates(&["A".to_string(), "B".to_string(), "C".to_string()])
+                .unwrap();
+            let ballots: Vec<(Vec<Vec<&str>>, u32)> = vec![
+                (vec![vec!["A"]], 4),
+                (vec![vec!["B"], vec!["A", "C"]], 3),
+                (vec![vec!["C"], vec!["B"]], 2),
+            ];
+            for (choices, count) in ballots {
+                let choices: Vec<Vec<String>> = choices
+                    .iter()
+                    .map(|g| g.iter().map(|c| c.to_string()).collect())
+                    .collect();
+                builder.add_vote(&choices, count).unwrap();
+            }
+            run_election(&builder).unwrap()
+        };
+        // B wins with the votes of C: the overvote is never read.
+        let res = run(OverVoteScope::UpToNextContinuing);
+        assert_eq!(res.winners, Some(vec!["B".to_string()]));
+        assert_eq!(res.round_stats[0].inactive_ballots.overvotes, 0);
+        assert_eq!(res.whole_ballot_overvotes, 0);
+        // The ballots of B are inactive from the first round, and A has the majority.
+        let res = run(OverVoteScope::WholeBallot);
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+        assert_eq!(res.round_stats.len(), 1);
+        assert_eq!(res.round_stats[0].inactive_ballots.overvotes, 3);
+        assert_eq!(res.whole_ballot_overvotes, 3);
+        // The scope has no effect when the overvotes are skipped.
+        let rules = VoteRules {
+            overvote_scope: OverVoteScope::WholeBallot,
+            ..VoteRules::default()
+        };
+        let ballots = vec![vec!["A"], vec!["A"], vec!["B", "A"], vec!["C", "B"]];
+        assert_eq!(
+            run_election1(&ballots, &rules).unwrap(),
+            run_election1(&ballots, &VoteRules::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn competitiveness() {
+        // B is second in the first round, and wins with the votes of C. The ballot of D is
+        // exhausted by its elimination.
+        let mut ballots: Vec<Vec<&str>> = Vec::new();
+        ballots.extend(std::iter::repeat_n(vec!["A"], 4));
+        ballots.extend(std::iter::repeat_n(vec!["B", "C"], 3));
+        ballots.extend(std::iter::repeat_n(vec!["C", "B"], 2));
+        ballots.push(vec!["D"]);
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        assert_eq!(res.winners, Some(vec!["B".to_string()]));
+        let report = metrics::competitiveness(&res);
+        assert_eq!(report.rounds, 3);
+        // 1 / (0.4^2 + 0.3^2 + 0.2^2 + 0.1^2)
+        let effective = report.effective_candidates.unwrap();
+        assert!((effective - 1.0 / 0.3).abs() < 1e-9, "{}", effective);
+        assert_eq!(
+            (report.first_round_votes, report.final_round_votes),
+            (10, 9)
+        );
+        assert_eq!(report.final_round_margin, Some(1));
+        let margin = report.final_round_margin_percent().unwrap();
+        assert!((margin - 100.0 / 9.0).abs() < 1e-9, "{}", margin);
+        assert_eq!(report.come_from_behind, Some(true));
+        assert_eq!(report.exhausted_ballots, 1);
+        assert_eq!(report.exhausted_percent(), Some(10.0));
+
+        // The informational rounds do not count, and the winner leads from the first round.
+        let rules = VoteRules {
+            continue_to_completion: true,
+            ..VoteRules::default()
+        };
+        let mut ballots: Vec<Vec<&str>> = Vec::new();
+        ballots.extend(std::iter::repeat_n(vec!["A", "B"], 4));
+        ballots.extend(std::iter::repeat_n(vec!["B", "C"], 2));
+        ballots.push(vec!["C", "B"]);
+        let res = run_election1(&ballots, &rules).unwrap();
+        assert_eq!(res.round_stats.len(), 3);
+        let report = metrics::competitiveness(&res);
+        assert_eq!(report.rounds, 1);
+        assert_eq!(report.come_from_behind, Some(false));
+        assert_eq!(report.final_round_margin, Some(2));
+        assert_eq!(report.exhausted_ballots, 0);
+    }
+
+    #[test]
+    fn max_rankings_one() {
+        let a: &[&str] = &["A", "B", "C"];
+        let b: &[&str] = &["B", "C", "A"];
+        let c: &[&str] = &["C", "B", "A"];
+        let blank_a: &[&str] = &["", "A"];
+        // The ballots, batch elimination, the winner and the number of rounds. With the full
+        // rankings, the winner would be another candidate, or would be elected later.
+        type Case<'a> = (Vec<(&'a [&'a str], u32)>, bool, &'a str, usize);
+        let cases: Vec<Case> = vec![
+            (vec![(a, 5), (b, 3), (c, 2)], false, "A", 2),
+            (vec![(a, 3), (b, 4), (c, 5)], false, "C", 2),
+            (vec![(a, 4), (b, 3), (c, 2)], true, "A", 2),
+            (vec![(a, 6), (b, 2), (c, 1)], false, "A", 1),
+            (vec![(blank_a, 3), (b, 2), (c, 1)], false, "B", 1),
+        ];
+        for (ballots, batch, winner, num_rounds) in cases.iter() {
+            let rules = VoteRules {
+                max_rankings_allowed: Some(1),
+                elimination_algorithm: if *batch {
+                    EliminationAlgorithm::Batch
+                } else {
+                    EliminationAlgorithm::Single
+                },
+                ..VoteRules::default()
+            };
+            let mut builder = Builder::new(&rules)
+                .unwrap()
+                .candidates(&["A".to_string(), "B".to_string(), "C".to_string()])
+                .unwrap();
+            for (ballot, count) in ballots.iter() {
+                let choices: Vec<Vec<String>> =
+                    ballot.iter().map(|c| vec![c.to_string()]).collect();
+                builder.add_vote(&choices, *count).unwrap();
+            }
+            let res = run_election(&builder).unwrap();
+            assert_eq!(res.winners, Some(vec![winner.to_string()]), "{:?}", ballots);
+            assert_eq!(res.round_stats.len(), *num_rounds, "{:?}", ballots);
+            // The eliminated candidates never transfer their votes.
+            for rs in res.round_stats.iter() {
+                for es in rs.tally_result_eliminated.iter() {
+                    assert!(es.transfers.is_empty(), "{:?}", ballots);
+                }
+            }
+            // The threshold is a majority of the votes of the last round.
+            let last = res.round_stats.last().unwrap();
+            let total: u64 = last.tally.iter().map(|(_, c)| c).sum();
+            assert_eq!(res.threshold, total / 2 + 1, "{:?}", ballots);
+            let winner_votes = last.tally.iter().find(|(n, _)| n == winner).unwrap().1;
+            assert!(winner_votes >= res.threshold, "{:?}", ballots);
+        }
+
+        // Nothing is left once the skipped first rankings are truncated.
+        let rules = VoteRules {
+            max_rankings_allowed: Some(1),
+            ..VoteRules::default()
+        };
+        let mut builder = Builder::new(&rules).unwrap();
+        builder
+            .add_vote(&[vec!["".to_string()], vec!["A".to_string()]], 3)
+            .unwrap();
+        assert!(matches!(
+            run_election(&builder),
+            Err(VotingErrors::EmptyElection)
+        ));
+    }
+
+    #[test]
+    fn inactive_ballots_by_reason() {
+        let rules = VoteRules {
+            overvote_rule: OverVoteRule::ExhaustImmediately,
+            max_skipped_rank_allowed: MaxSkippedRank::MaxAllowed(1),
+            duplicate_candidate_mode: DuplicateCandidateMode::Exhaust,
+            ..VoteRules::default()
+        };
+        let mut builder = Builder::new(&rules)
+            .unwrap()
+            .candidates(&["A".to_string(), "B".to_string(), "C".to_string()])
+            .unwrap();
+        let ballots: Vec<(Vec<Vec<&str>>, u32)> = vec![
+            (vec![vec!["A"]], 6),
+            (vec![vec!["B"], vec!["C"]], 5),
+            (vec![vec!["C"], vec!["A", "B"], vec!["A"]], 1),
+            (vec![vec!["C"], vec![""], vec![""], vec!["A"]], 1),
+            (vec![vec!["C"], vec!["C"], vec!["A"]], 1),
+            (vec![vec!["C"]], 1),
+            // Discarded before the first round.
+            (vec![vec!["A", "B"], vec!["A"]], 2),
+            // Never counted.
+            (vec![vec![""], vec![""]], 3),
+        ];
+        for (choices, count) in ballots.iter() {
+            let choices: Vec<Vec<String>> = choices
+                .iter()
+                .map(|cs| cs.iter().map(|c| c.to_string()).collect())
+                .collect();
+            builder.add_vote(&choices, *count).unwrap();
+        }
+        let res = run_election(&builder).unwrap();
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+        let inactive: Vec<&InactiveBallots> = res
+            .round_stats
+            .iter()
+            .map(|rs| &rs.inactive_ballots)
+            .collect();
+        assert_eq!(
+            inactive,
+            vec![
+                &InactiveBallots {
+                    overvotes: 2,
+                    ..InactiveBallots::default()
+                },
+                &InactiveBallots {
+                    exhausted_choices: 1,
+                    overvotes: 3,
+                    skipped_rankings: 1,
+                    repeated_rankings: 1,
+                },
+            ]
+        );
+        assert_eq!(res.round_stats[0].tally_result_eliminated[0].exhausted, 4);
+        assert_eq!(inactive[1].total(), 6);
+    }
+
+    #[test]
+    fn observer_sees_each_round() {
+        let rules = VoteRules {
+            continue_to_completion: true,
+            ..VoteRules::default()
+        };
+        let mut builder = Builder::new(&rules).unwrap();
+        for (ballot, count) in [
+            (vec!["A", "B"], 3),
+            (vec!["B", "C"], 2),
+            (vec!["C", "B"], 2),
+        ] {
+            let choices: Vec<Vec<String>> = ballot.iter().map(|c| vec![c.to_string()]).collect();
+            builder.add_vote(&choices, count).unwrap();
+        }
+        let mut observed: Vec<RoundStats> = Vec::new();
+        let res = run_election_observed(&builder, &mut |rs| observed.push(rs.clone())).unwrap();
+        assert_eq!(res.winners, Some(vec!["B".to_string()]));
+        assert_eq!(observed.len(), 3);
+        assert_eq!(observed, res.round_stats);
+    }
+
+    #[test]
+    fn excluded_candidates() {
+        let names: Vec<String> = ["A", "B", "C", "X"].iter().map(|s| s.to_string()).collect();
+        let mut builder = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&names)
+            .unwrap()
+            .exclude(&["X".to_string()])
+            .unwrap();
+        let ballots: Vec<(Vec<&str>, u32)> = vec![
+            (vec!["A"], 3),
+            (vec!["B"], 3),
+            (vec!["C", "X", "B"], 1),
+            (vec!["X", "C", "B"], 1),
+            (vec!["X", "UWI", "A"], 1),
+        ];
+        for (choices, count) in ballots {
+            let groups: Vec<Vec<String>> = choices.iter().map(|c| vec![c.to_string()]).collect();
+            builder.add_vote(&groups, count).unwrap();
+        }
+        let res = run_election(&builder).unwrap();
+        assert_eq!(res.excluded, vec![("X".to_string(), 2)]);
+        assert!(res.candidates.iter().all(|c| c.name != "X"));
+        assert!(res
+            .round_stats
+            .iter()
+            .all(|rs| rs.tally.iter().all(|(n, _)| n != "X")));
+        // The pseudo-elimination of the first round, with the write-in as a destination.
+        let first = &res.round_stats[0];
+        assert!(first.tally_result_eliminated.contains(&EliminationStats {
+            name: "X".to_string(),
+            transfers: vec![("C".to_string(), 1), (UNDECLARED_WRITE_INS.to_string(), 1)],
+            exhausted: 0,
+        }));
+        // The ranking of the excluded candidate is skipped after the first round as well.
+        assert_eq!(res.winners, Some(vec!["B".to_string()]));
+
+        let err = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&names)
+            .unwrap()
+            .exclude(&["Y".to_string()]);
+        assert!(matches!(err, Err(VotingErrors::UnknownCandidate(_))));
+    }
+
+    #[test]
+    fn rank_origin_of_the_votes() {
+        let run = |track_rank_origin: bool| -> VotingResult {
+            let rules = VoteRules {
+                track_rank_origin,
+                ..VoteRules::default()
+            };
+            let mut builder = Builder::new(&rules).unwrap();
+            let ballots: Vec<(Vec<&str>, u32)> = vec![
+                (vec!["A"], 4),
+                (vec!["B"], 3),
+                (vec!["C", "B"], 2),
+                (vec!["D", "", "C"], 1),
+                (vec!["", "B"], 1),
+            ];
+            for (choices, count) in ballots {
+                // An empty name is a skipped rank.
+                let groups: Vec<Vec<String>> = choices
+                    .iter()
+                    .map(|c| match *c {
+                        "" => vec![],
+                        c => vec![c.to_string()],
+                    })
+                    .collect();
+                builder.add_vote(&groups, count).unwrap();
+            }
+            run_election(&builder).unwrap()
+        };
+        assert_eq!(run(false).rank_origin, None);
+
+        let res = run(true);
+        assert_eq!(res.winners, Some(vec!["B".to_string()]));
+        assert_eq!(res.round_stats.len(), 3);
+        let origin = |round: u32, name: &str| -> Vec<(u32, u64)> {
+            res.rank_origin
+                .as_ref()
+                .unwrap()
+                .iter()
+                .find(|o| o.round == RoundId(round) && o.candidate == name)
+                .unwrap()
+                .votes_by_rank
+                .clone()
+        };
+        // The ballot that skips its first rank counts as a second choice.
+        assert_eq!(origin(1, "B"), vec![(1, 3), (2, 1)]);
+        assert_eq!(origin(1, "D"), vec![(1, 1)]);
+        // D is eliminated: its ballot goes to C, ranked third.
+        assert_eq!(origin(2, "C"), vec![(1, 2), (3, 1)]);
+        // C is eliminated: the final tally of the winner.
+        assert_eq!(origin(3, "B"), vec![(1, 3), (2, 3)]);
+        assert_eq!(origin(3, "A"), vec![(1, 4)]);
+        // The candidates of each round are in the order of the tally.
+        let last: Vec<&str> = res
+            .rank_origin
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|o| o.round == RoundId(3))
+            .map(|o| o.candidate.as_str())
+            .collect();
+        assert_eq!(last, vec!["B", "A"]);
+        // The tabulation is the same with or without the tracking.
+        assert_eq!(res.round_stats, run(false).round_stats);
+    }
+
+    #[test]
+    fn log_top_candidates() {
+        let names: Vec<(String, CandidateId)> = (0..50)
+            .map(|i| (format!("C{:02}", i), CandidateId(i)))
+            .collect();
+        let stats = RoundStatistics {
+            candidate_stats: (0..50)
+                .map(|i| {
+                    let status = if i == 30 {
+                        RoundCandidateStatusInternal::Eliminated(
+                            vec![(CandidateId(0), VoteCount(70))],
+                            VoteCount::EMPTY,
+                        )
+                    } else {
+                        RoundCandidateStatusInternal::StillRunning
+                    };
+                    (CandidateId(i), VoteCount(100 - i as u64), status)
+                })
+                .collect(),
+            uwi_elimination_stats: None,
+            inactive: InactiveBallots::default(),
+            excluded: Vec::new(),
+            rank_origin: None,
+            notes: Vec::new(),
+            threshold: VoteCount(2000),
+        };
+        let round = RoundId::from_index(1);
+        let lines = round_stats_lines(round, &stats, &names, VoteCount(2000), Some(5));
+        // The header, the top 5, the eliminated candidate and the summary of the others.
+        assert_eq!(lines.len(), 8, "{:#?}", lines);
+        assert_eq!(lines[1], "    100 C00 -> running");
+        assert_eq!(lines[6], "     70 C30 -> eliminated:70 -> C00, ");
+        assert_eq!(
+            lines[7],
+            "        \u{2026} and 44 others with \u{2264} 95 votes"
+        );
+
+        let lines = round_stats_lines(round, &stats, &names, VoteCount(2000), None);
+        assert_eq!(lines.len(), 51);
+        // Nothing is summarized when the top covers all the candidates.
+        let lines = round_stats_lines(round, &stats, &names, VoteCount(2000), Some(50));
+        assert_eq!(lines.len(), 51);
+    }
+
+    #[test]
+    fn transfers_order() {
+        let mut ballots: Vec<Vec<&str>> = Vec::new();
+        for name in ["A", "B", "C", "D"] {
+            ballots.extend(std::iter::repeat_n(vec![name], 10));
+        }
+        for (name, n) in [("D", 1), ("C", 2), ("A", 3), ("B", 2)] {
+            ballots.extend(std::iter::repeat_n(vec!["E", name], n));
+        }
+        // The transfers come from a hash map: the order must not depend on its seed.
+        for _ in 0..20 {
+            let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+            let es = &res.round_stats[0].tally_result_eliminated[0];
+            assert_eq!(es.name, "E");
+            assert_eq!(
+                es.transfers,
+                vec![
+                    ("A".to_string(), 3),
+                    ("B".to_string(), 2),
+                    ("C".to_string(), 2),
+                    ("D".to_string(), 1),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn add_vote_groups() {
+        let groups = |gs: &[&[&str]]| -> Vec<Vec<String>> {
+            gs.iter()
+                .map(|g| g.iter().map(|s| s.to_string()).collect())
+                .collect()
+        };
+        let mut builder = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&["A".to_string(), "B".to_string()])
+            .unwrap()
+            .labels(ChoiceLabels {
+                overvote_label: Some("OVER".to_string()),
+                ..ChoiceLabels::default()
+            });
+        builder
+            .add_vote(&groups(&[&["A", "A ", ""], &["B", "A"], &["OVER"], &[]]), 1)
+            .unwrap();
+        assert_eq!(
+            builder._votes[0].candidates,
+            vec![
+                BallotChoice::Candidate("A".to_string()),
+                BallotChoice::Overvote,
+                BallotChoice::Overvote,
+                BallotChoice::Undervote,
+            ]
+        );
+
+        // Without declared candidates, the names are candidates but the labels still apply.
+        let mut builder = Builder::new(&VoteRules::default()).unwrap();
+        builder
+            .add_vote(&groups(&[&["UWI"], &[" C"], &["", " "]]), 1)
+            .unwrap();
+        assert_eq!(
+            builder._votes[0].candidates,
+            vec![
+                BallotChoice::UndeclaredWriteIn,
+                BallotChoice::Candidate("C".to_string()),
+                BallotChoice::Blank,
+            ]
+        );
+    }
+
+    #[test]
+    fn cancellation_stops_between_rounds() {
+        let flag = std::sync::Arc::new(AtomicBool::new(false));
+        let mut builder = Builder::new(&VoteRules::default())
+            .unwrap()
+            .cancellation(flag.clone());
+        for (ballot, count) in [
+            (vec!["A", "B"], 3),
+            (vec!["B", "C"], 2),
+            (vec!["C", "B"], 2),
+        ] {
+            let choices: Vec<Vec<String>> = ballot.iter().map(|c| vec![c.to_string()]).collect();
+            builder.add_vote(&choices, count).unwrap();
+        }
+        // Cancelled in the middle of the tabulation: only the first round is done.
+        let mut observed: Vec<RoundStats> = Vec::new();
+        let res = run_election_observed(&builder, &mut |rs| {
+            observed.push(rs.clone());
+            flag.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(res, Err(VotingErrors::Interrupted));
+        assert_eq!(observed.len(), 1);
+        assert_eq!(observed[0].round, RoundId(1));
+        // Without the flag, the election completes.
+        flag.store(false, Ordering::Relaxed);
+        let res = run_election(&builder).unwrap();
+        assert_eq!(res.winners, Some(vec!["B".to_string()]));
+    }
+
+    // Returns the head of the ballot after each elimination, or None once it is exhausted.
+    fn heads_after_eliminations(
+        ballot: &[u32],
+        eliminations: &[u32],
+        mode: DuplicateCandidateMode,
+    ) -> Vec<Option<u32>> {
+        let mut still_valid: HashSet<CandidateId> = (0..3).map(CandidateId).collect();
+        let choices: Vec<Choice> = ballot
+            .iter()
+            .map(|cid| Choice::Filled(CandidateId(*cid)))
+            .collect();
+        let mut current = Some(RankedChoice::new(&choices));
+        let mut heads = vec![];
+        for eliminated in eliminations {
+            still_valid.remove(&CandidateId(*eliminated));
+            current = current.and_then(|rc| {
+                rc.filtered_candidate(
+                    &still_valid,
+                    mode,
+                    OverVoteRule::ExhaustImmediately,
+                    MaxSkippedRank::Unlimited,
+                    true,
+                )
+                .ok()
+            });
+            heads.push(current.as_ref().map(|rc| rc.first_valid.0));
+        }
+        heads
+    }
+
+    const A: u32 = 0;
+    const B: u32 = 1;
+    const C: u32 = 2;
+
+    #[test]
+    fn ranked_choice_drops_tail() {
+        let still_valid: HashSet<CandidateId> = [A, B, C].iter().map(|c| CandidateId(*c)).collect();
+        let choices = [
+            Choice::Filled(CandidateId(A)),
+            Choice::Blank,
+            Choice::Filled(CandidateId(B)),
+            Choice::Overvote,
+            Choice::Blank,
+        ];
+        let rc = RankedChoice::new(&choices);
+        // The overvote is kept: it is the reason why the ballot is exhausted.
+        assert_eq!(rc.choices.len(), 4);
+
+        // The head moves forward in the same choices.
+        let mut remaining = still_valid.clone();
+        remaining.remove(&CandidateId(A));
+        let rc = rc
+            .filtered_candidate(
+                &remaining,
+                DuplicateCandidateMode::Exhaust,
+                OverVoteRule::ExhaustImmediately,
+                MaxSkippedRank::Unlimited,
+                true,
+            )
+            .unwrap();
+        assert_eq!((rc.first_valid, rc.head), (CandidateId(B), 2));
+        assert_eq!(rc.passed(), &choices[..2]);
+        remaining.remove(&CandidateId(B));
+        assert_eq!(
+            rc.filtered_candidate(
+                &remaining,
+                DuplicateCandidateMode::Exhaust,
+                OverVoteRule::ExhaustImmediately,
+                MaxSkippedRank::Unlimited,
+                true,
+            ),
+            Err(ExhaustReason::Overvote)
+        );
+    }
+
+    #[test]
+    fn same_candidate_everywhere() {
+        for mode in [
+            DuplicateCandidateMode::Exhaust,
+            DuplicateCandidateMode::SkipDuplicate,
+        ] {
+            assert_eq!(
+                heads_after_eliminations(&[A, A, A], &[B, C], mode),
+                vec![Some(A), Some(A)]
+            );
+            assert_eq!(
+                heads_after_eliminations(&[A, A, A], &[C, A], mode),
+                vec![Some(A), None]
+            );
+            assert_eq!(heads_after_eliminations(&[A, A, A], &[A], mode), vec![None]);
+        }
+    }
+
+    #[test]
+    fn duplicate_head_exhaust() {
+        let mode = DuplicateCandidateMode::Exhaust;
+        assert_eq!(
+            heads_after_eliminations(&[A, A, B], &[C, B], mode),
+            vec![Some(A), Some(A)]
+        );
+        assert_eq!(
+            heads_after_eliminations(&[A, A, B], &[B, C], mode),
+            vec![Some(A), Some(A)]
+        );
+        // Moving past A reaches the second A first.
+        assert_eq!(
+            heads_after_eliminations(&[A, A, B], &[C, A], mode),
+            vec![Some(A), None]
+        );
+        // The duplicate A comes after B: it is only reached once B is gone.
+        assert_eq!(
+            heads_after_eliminations(&[A, B, A, C], &[A, B], mode),
+            vec![Some(B), None]
+        );
+    }
+
+    #[test]
+    fn duplicate_head_skip() {
+        let mode = DuplicateCandidateMode::SkipDuplicate;
+        assert_eq!(
+            heads_after_eliminations(&[A, A, B], &[C, B], mode),
+            vec![Some(A), Some(A)]
+        );
+        assert_eq!(
+            heads_after_eliminations(&[A, A, B], &[C, A], mode),
+            vec![Some(A), Some(B)]
+        );
+        assert_eq!(
+            heads_after_eliminations(&[A, A, B], &[A, B], mode),
+            vec![Some(B), None]
+        );
+        assert_eq!(
+            heads_after_eliminations(&[A, B, A, C], &[A, B], mode),
+            vec![Some(B), Some(C)]
+        );
+    }
+
+    fn names(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("Candidate {}", i)).collect()
+    }
+
+    #[test]
+    fn tiebreak_permutation_is_deterministic_bijection() {
+        for n in 0..6 {
+            let mut expected = names(n);
+            expected.sort();
+            for seed in [0, 1, 42, u64::MAX] {
+                for round in 1..5 {
+                    let p = tiebreak::permutation(seed, round, &names(n));
+                    assert_eq!(p, tiebreak::permutation(seed, round, &names(n)));
+                    let mut sorted = p.clone();
+                    sorted.sort();
+                    assert_eq!(sorted, expected);
+                }
+            }
+        }
+        assert!(tiebreak::permutation(3, 1, &[]).is_empty());
+    }
+
+    #[test]
+    fn tiebreak_permutation_depends_on_seed_and_round() {
+        let cands = names(8);
+        let reference = tiebreak::permutation(1, 1, &cands);
+        for seed in 2..20 {
+            assert_ne!(tiebreak::permutation(seed, 1, &cands), reference);
+        }
+        for round in 2..20 {
+            assert_ne!(tiebreak::permutation(1, round, &cands), reference);
+        }
+        // The legacy order is the same for every seed.
+        let mut sorted = cands.clone();
+        sorted.sort();
+        for seed in 0..20 {
+            assert_eq!(tiebreak::legacy_permutation(seed, 1, &cands), sorted);
+        }
+    }
+
+    #[test]
+    fn random_tiebreak_uses_permutation() {
+        let candidates: Vec<(CandidateId, String)> = names(5)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, name)| (CandidateId(idx as u32), name))
+            .collect();
+        let all_names: Vec<String> = candidates.iter().map(|(_, n)| n.clone()).collect();
+        for legacy in [false, true] {
+            let ids = candidate_permutation_crypto(&candidates, 7, RoundId(2), legacy);
+            let expected = if legacy {
+                tiebreak::legacy_permutation(7, 2, &all_names)
+            } else {
+                tiebreak::permutation(7, 2, &all_names)
+            };
+            let id_names: Vec<String> = ids
+                .iter()
+                .map(|cid| candidates[cid.0 as usize].1.clone())
+                .collect();
+            assert_eq!(id_names, expected);
+        }
+    }
+
+    #[test]
+    fn rank_stats_skip_then_return() {
+        let f = Choice::Filled(CandidateId(1));
+        let b = Choice::Blank;
+        let ballots: Vec<(Vec<Choice>, u64)> = vec![
+            (vec![f, f, f, f], 1),
+            (vec![b, f], 2),
+            (vec![f, b, f], 1),
+            (vec![f, f, b, Choice::Overvote], 3),
+            (vec![f, b, b], 1),
+            (vec![b, b], 1),
+        ];
+        let mut stats = RankStatistics::default();
+        for (choices, count) in ballots.iter() {
+            add_rank_stats(&mut stats, choices, VoteCount(*count));
+        }
+        let last_marks: Vec<u64> = stats.positions.iter().map(|p| p.last_mark).collect();
+        let skips: Vec<u64> = stats.positions.iter().map(|p| p.skip_then_return).collect();
+        assert_eq!(last_marks, vec![1, 2, 1, 4]);
+        assert_eq!(skips, vec![2, 1, 3, 0]);
+        assert_eq!(stats.positions[3].position, 4);
+        assert_eq!(stats.total_votes, 9);
+        assert_eq!(stats.skip_then_return_votes, 6);
+        assert!((stats.skip_then_return_percent() - 66.666).abs() < 0.01);
+    }
+
+    fn nota_rules(protect_nota: bool) -> VoteRules {
+        VoteRules {
+            nota_candidate: Some("N".to_string()),
+            protect_nota,
+            ..VoteRules::default()
+        }
+    }
+
+    #[test]
+    fn nota_prevails() {
+        let ballots = vec![vec!["N"], vec!["N"], vec!["N", "A"], vec!["A"], vec!["B"]];
+        let res = run_election1(&ballots, &nota_rules(false)).unwrap();
+        assert!(res.nota_prevailed);
+        assert_eq!(res.winners, None);
+        let last_round = res.round_stats.last().unwrap();
+        assert_eq!(last_round.tally_results_elected, vec!["N"]);
+    }
+
+    #[test]
+    fn nota_eliminated() {
+        let ballots = vec![
+            vec!["A"],
+            vec!["A"],
+            vec!["A"],
+            vec!["B"],
+            vec!["B"],
+            vec!["N", "A"],
+        ];
+        let res = run_election1(&ballots, &nota_rules(false)).unwrap();
+        assert!(!res.nota_prevailed);
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+        assert_eq!(res.round_stats[0].tally_result_eliminated[0].name, "N");
+    }
+
+    #[test]
+    fn nota_protected_from_batch_elimination() {
+        let mut ballots = vec![vec!["A"]; 5];
+        ballots.extend(vec![vec!["B"]; 3]);
+        ballots.push(vec!["C"]);
+        ballots.push(vec!["N"]);
+        let eliminated_first = |protect_nota: bool| -> Vec<String> {
+            let rules = VoteRules {
+                elimination_algorithm: EliminationAlgorithm::Batch,
+                ..nota_rules(protect_nota)
+            };
+            let res = run_election1(&ballots, &rules).unwrap();
+            let mut names: Vec<String> = res.round_stats[0]
+                .tally_result_eliminated
+                .iter()
+                .map(|es| es.name.clone())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(eliminated_first(false), vec!["C", "N"]);
+        assert_eq!(eliminated_first(true), vec!["C"]);
+    }
+
+    #[test]
+    fn nota_protected_batch_keeps_stronger_candidates() {
+        // The batch is C (1), N (2) and D (4): D has more votes than N, and is not eliminated
+        // before N.
+        let mut ballots = vec![vec!["A"]; 12];
+        ballots.extend(vec![vec!["B"]; 10]);
+        ballots.extend(vec![vec!["D"]; 4]);
+        ballots.extend(vec![vec!["N"]; 2]);
+        ballots.push(vec!["C"]);
+        let rules = VoteRules {
+            elimination_algorithm: EliminationAlgorithm::Batch,
+            ..nota_rules(true)
+        };
+        let res = run_election1(&ballots, &rules).unwrap();
+        let names: Vec<&str> = res.round_stats[0]
+            .tally_result_eliminated
+            .iter()
+            .map(|es| es.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["C"]);
+        let rules = VoteRules {
+            elimination_algorithm: EliminationAlgorithm::Batch,
+            ..nota_rules(false)
+        };
+        let res = run_election1(&ballots, &rules).unwrap();
+        assert_eq!(res.round_stats[0].tally_result_eliminated.len(), 3);
+    }
+
+    fn verification_builder() -> Builder {
+        let mut builder = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&["A".to_string(), "B".to_string(), "C".to_string()])
+            .unwrap();
+        let ballots = [
+            vec!["A", "B"],
+            vec!["A", "C"],
+            vec!["B", "A"],
+            vec!["B", "C"],
+            vec!["C", "B"],
+        ];
+        for ballot in ballots.iter() {
+            let choices: Vec<Vec<String>> = ballot.iter().map(|c| vec![c.to_string()]).collect();
+            builder.add_vote(&choices, 1).unwrap();
+        }
+        builder
+    }
+
+    fn verify_round_sequence(
+        builder: &Builder,
+        official: &[RoundStats],
+    ) -> Result<verify::VerificationReport, VotingErrors> {
+        let result = run_election(builder)?;
+        Ok(verify::compare_rounds(&result.round_stats, official))
+    }
+
+    #[test]
+    fn verify_matching_rounds() {
+        let builder = verification_builder();
+        let official = run_election(&builder).unwrap().round_stats;
+        assert!(official.len() > 1);
+        let report = verify_round_sequence(&builder, &official).unwrap();
+        assert!(report.is_match());
+        assert_eq!(report.matching_rounds, official.len());
+
+        // Partial records: only the first round is known.
+        let report = verify_round_sequence(&builder, &official[..1]).unwrap();
+        assert!(report.is_match());
+        assert_eq!(report.matching_rounds, 1);
+        assert_eq!(report.computed_rounds, official.len());
+    }
+
+    #[test]
+    fn verify_altered_round() {
+        let builder = verification_builder();
+        let mut official = run_election(&builder).unwrap().round_stats;
+        for (name, count) in official[1].tally.iter_mut() {
+            if name == "A" {
+                *count += 2;
+            }
+        }
+        let report = verify_round_sequence(&builder, &official).unwrap();
+        assert!(!report.is_match());
+        assert_eq!(report.matching_rounds, 1);
+        let divergence = report.divergence.unwrap();
+        assert_eq!(divergence.round, RoundId(2));
+        assert!(!divergence.missing_round);
+        assert_eq!(divergence.deltas.len(), 1);
+        assert_eq!(divergence.deltas[0].name, "A");
+        assert_eq!(divergence.deltas[0].delta(), -2);
+        // The difference of any two tallies is exact.
+        let mut delta = divergence.deltas[0].clone();
+        (delta.official, delta.computed) = (u64::MAX, 0);
+        assert_eq!(delta.delta(), -(u64::MAX as i128));
+
+        // A round that the tabulation never reaches.
+        let mut extra = official[1].clone();
+        extra.round = RoundId::from_index(official.len());
+        let report = verify_round_sequence(&builder, &[extra]).unwrap();
+        assert!(report.divergence.unwrap().missing_round);
+    }
+
+    #[test]
+    fn whitespace_choices_are_blank() {
+        let mut builder = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&["A".to_string(), "B".to_string()])
+            .unwrap();
+        let choices: Vec<Vec<String>> = [" ", "\t", " A "]
+            .iter()
+            .map(|c| vec![c.to_string()])
+            .collect();
+        builder.add_vote(&choices, 1).unwrap();
+        assert_eq!(
+            builder._votes[0].candidates,
+            vec![
+                BallotChoice::Blank,
+                BallotChoice::Blank,
+                BallotChoice::Candidate("A".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn extend_from_aggregates_and_counts_errors() {
+        let mut builder = Builder::new(&VoteRules::default()).unwrap().error_budget(2);
+        let items: Vec<(&str, bool)> = vec![("A", false), ("", false), ("A", true), ("A", false)];
+        let report = builder
+            .extend_from(items, |(name, challenged)| {
+                if name.is_empty() {
+                    return Err(VotingErrors::InvalidBallot("no name".to_string()));
+                }
+                Ok(Ballot {
+                    candidates: vec![BallotChoice::Candidate(name.to_string())],
+                    count: 2,
+                    challenged,
+                    id: None,
+                })
+            })
+            .unwrap();
+        assert_eq!(report.accepted, 3);
+        assert_eq!(report.aggregated, 2);
+        assert_eq!(
+            report.errors,
+            vec![(VotingErrors::InvalidBallot("no name".to_string()), 1)]
+        );
+        // The challenged ballots are not merged with the others.
+        let counts: Vec<(u64, bool)> = builder
+            ._votes
+            .iter()
+            .map(|b| (b.count, b.challenged))
+            .collect();
+        assert_eq!(counts, vec![(4, false), (2, true)]);
+
+        // Beyond the budget, the error is returned.
+        let res = builder.extend_from(vec![1, 2, 3], |_| Err(VotingErrors::EmptyElection));
+        assert_eq!(res, Err(VotingErrors::EmptyElection));
+    }
+
+    #[test]
+    fn concurrent_builder_matches_single_thread() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Builder>();
+        assert_send_sync::<ConcurrentBuilder>();
+
+        let names: Vec<String> = ["A", "B", "C", "D", "E"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        // Some votes with repeated, unknown and blank choices, and overvotes.
+        let pool = ["A", "B", "C", "D", "E", "Z", ""];
+        let votes: Vec<(Vec<Vec<String>>, u32)> = (0..2000usize)
+            .map(|i| {
+                let ranks: Vec<Vec<String>> = (0..(i % 4 + 1))
+                    .map(|r| {
+                        let mut group =
+                            vec![pool[(i * 7 + r * 3 + i / 5) % pool.len()].to_string()];
+                        if (i + r) % 29 == 0 {
+                            group.push(pool[(i + r) % 5].to_string());
+                        }
+                        group
+                    })
+                    .collect();
+                (ranks, (i % 3 + 1) as u32)
+            })
+            .collect();
+        let new_builder = || {
+            Builder::new(&VoteRules::default())
+                .unwrap()
+                .candidates(&names)
+                .unwrap()
+        };
+
+        let mut single = new_builder();
+        for (ranks, count) in votes.iter() {
+            single.add_vote(ranks, *count).unwrap();
+        }
+
+        let concurrent = new_builder().into_concurrent();
+        std::thread::scope(|s| {
+            for chunk in votes.chunks(votes.len() / 4) {
+                let concurrent = &concurrent;
+                s.spawn(move || {
+                    let mut shard = concurrent.shard();
+                    for (ranks, count) in chunk.iter() {
+                        shard.add_vote(ranks, *count);
+                    }
+                });
+            }
+        });
+        let merged = concurrent.finish();
+        // The identical ballots of each shard are aggregated.
+        assert!(merged._votes.len() < single._votes.len());
+        let total = |b: &Builder| b._votes.iter().map(|v| v.count).sum::<u64>();
+        assert_eq!(total(&merged), total(&single));
+
+        let expected = run_election(&single).unwrap();
+        let result = run_election(&merged).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn builder_classifies_choices() {
+        let choices: Vec<Vec<String>> = [vec!["B"], vec!["Z"], vec![], vec!["A", "B"]]
+            .iter()
+            .map(|c| c.iter().map(|n| n.to_string()).collect())
+            .collect();
+        let mut declared = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&["A".to_string(), "B".to_string()])
+            .unwrap();
+        declared.add_vote(&choices, 1).unwrap();
+        assert_eq!(
+            declared._votes[0].candidates,
+            vec![
+                BallotChoice::Candidate("B".to_string()),
+                BallotChoice::UndeclaredWriteIn,
+                BallotChoice::Undervote,
+                BallotChoice::Overvote,
+            ]
+        );
+        // Without declared candidates, every name is a candidate.
+        let mut inferred = Builder::new(&VoteRules::default()).unwrap();
+        inferred.add_vote(&choices, 1).unwrap();
+        assert_eq!(
+            inferred._votes[0].candidates[1],
+            BallotChoice::Candidate("Z".to_string())
+        );
+    }
+
+    #[test]
+    fn candidates_declared_and_inferred() {
+        let names = |res: &VotingResult| -> Vec<(String, bool)> {
+            res.candidates
+                .iter()
+                .map(|c| (c.name.clone(), c.inferred))
+                .collect()
+        };
+
+        // Declared candidates keep their order, and a name that is not declared is a write-in.
+        let mut builder = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&["B".to_string(), "A".to_string()])
+            .unwrap();
+        builder.add_vote_simple(&["A".to_string()]).unwrap();
+        builder
+            .add_vote(&[vec!["X".to_string()], vec!["A".to_string()]], 1)
+            .unwrap();
+        builder.add_vote_simple(&["B".to_string()]).unwrap();
+        let res = run_election(&builder).unwrap();
+        assert_eq!(
+            names(&res),
+            vec![("B".to_string(), false), ("A".to_string(), false)]
+        );
+
+        // Without declared candidates, all the names of the ballots are candidates, sorted by
+        // name.
+        let mut builder = Builder::new(&VoteRules::default()).unwrap();
+        builder
+            .add_vote(&[vec!["X".to_string()], vec!["A".to_string()]], 1)
+            .unwrap();
+        builder.add_vote_simple(&["A".to_string()]).unwrap();
+        let res = run_election(&builder).unwrap();
+        assert_eq!(
+            names(&res),
+            vec![("A".to_string(), true), ("X".to_string(), true)]
+        );
+        assert!(res
+            .candidates
+            .iter()
+            .all(|c| !c.excluded && c.code.is_none()));
+
+        // The declared candidates that were found in the ballots keep their origin.
+        let mut builder = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&["B".to_string(), "A".to_string()])
+            .unwrap()
+            .inferred(&["A".to_string()])
+            .unwrap();
+        builder.add_vote_simple(&["A".to_string()]).unwrap();
+        let res = run_election(&builder).unwrap();
+        assert_eq!(
+            names(&res),
+            vec![("B".to_string(), false), ("A".to_string(), true)]
+        );
+        assert!(matches!(
+            Builder::new(&VoteRules::default())
+                .unwrap()
+                .candidates(&["A".to_string()])
+                .unwrap()
+                .inferred(&["Z".to_string()]),
+            Err(VotingErrors::UnknownCandidate(_))
+        ));
+    }
+
+    #[test]
+    fn inferred_candidate_order_breaks_ties() {
+        // A and B are tied: the last candidate in the candidate order is eliminated.
+        let votes = vec![vec!["B"], vec!["A"]];
+        let winners = |res: VotingResult| res.winners.unwrap();
+        let res = run_election1(&votes, &VoteRules::default()).unwrap();
+        let names: Vec<&str> = res.candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["A", "B"]);
+        assert_eq!(winners(res), vec!["A".to_string()]);
+
+        let with_candidates = |cands: Option<&[&str]>| -> VotingResult {
+            let mut builder = Builder::new(&VoteRules::default()).unwrap();
+            if let Some(cs) = cands {
+                let cs: Vec<String> = cs.iter().map(|c| c.to_string()).collect();
+                builder = builder.candidates(&cs).unwrap();
+            }
+            for v in votes.iter() {
+                builder.add_vote(&[vec![v[0].to_string()]], 1).unwrap();
+            }
+            run_election(&builder).unwrap()
+        };
+        // The inferred order is the order of the declaration that lists the candidates sorted
+        // by name, whatever the order of the ballots.
+        assert_eq!(winners(with_candidates(None)), vec!["A".to_string()]);
+        assert_eq!(
+            winners(with_candidates(Some(&["B", "A"]))),
+            vec!["B".to_string()]
+        );
+        assert_eq!(
+            winners(with_candidates(Some(&["A", "B"]))),
+            vec!["A".to_string()]
+        );
+    }
+
+    #[test]
+    fn blank_counts_as_skipped() {
+        let (b, u, o) = (Choice::Blank, Choice::Undervote, Choice::Overvote);
+        let fails = |slice: &[Choice], blank_counts_as_skipped: bool| {
+            check_advance_rules(
+                slice,
+                None,
+                &[],
+                DuplicateCandidateMode::SkipDuplicate,
+                OverVoteRule::AlwaysSkipToNextRank,
+                MaxSkippedRank::MaxAllowed(1),
+                blank_counts_as_skipped,
+            )
+            .is_some()
+        };
+        let cases: Vec<(Vec<Choice>, bool, bool)> = vec![
+            (vec![b], false, false),
+            (vec![u], false, false),
+            (vec![b, b], true, false),
+            (vec![u, b], true, false),
+            (vec![b, u, b], true, false),
+            // The blanks do not interrupt a sequence of undervotes.
+            (vec![u, b, u], true, true),
+            (vec![u, o, u], false, false),
+            (vec![u, u], true, true),
+        ];
+        for (slice, with_blanks, without_blanks) in cases.iter() {
+            assert_eq!(fails(slice, true), *with_blanks, "{:?}", slice);
+            assert_eq!(fails(slice, false), *without_blanks, "{:?}", slice);
+        }
+
+        // The two blanks exhaust the ballots of C, unless only the undervotes count.
+        let run = |blank_counts_as_skipped: bool| -> VotingResult {
+            let rules = VoteRules {
+                max_skipped_rank_allowed: MaxSkippedRank::MaxAllowed(1),
+                blank_counts_as_skipped,
+                ..VoteRules::default()
+            };
+            let mut builder = Builder::new(&rules).unwrap();
+            let candidate = |name: &str| BallotChoice::Candidate(name.to_string());
+            let ballots = [
+                (vec![candidate("A")], 3),
+                (vec![candidate("B")], 4),
+                (
+                    vec![
+                        candidate("C"),
+                        BallotChoice::Blank,
+                        BallotChoice::Blank,
+                        candidate("A"),
+                    ],
+                    2,
+                ),
+            ];
+            for (candidates, count) in ballots {
+                builder
+                    .add_ballot(Ballot {
+                        candidates,
+                        count,
+                        challenged: false,
+                        id: None,
+                    })
+                    .unwrap();
+            }
+            run_election(&builder).unwrap()
+        };
+        assert_eq!(run(true).winners, Some(vec!["B".to_string()]));
+        assert_eq!(run(false).winners, Some(vec!["A".to_string()]));
+    }
+
+    #[test]
+    fn uwi_round1_reporting() {
+        let run = |mode: UwiRound1Reporting| -> VotingResult {
+            let rules = VoteRules {
+                uwi_round1_reporting: mode,
+                ..VoteRules::default()
+            };
+            let mut builder = Builder::new(&rules)
+                .unwrap()
+                .candidates(&["A".to_string(), "B".to_string(), "C".to_string()])
+                .unwrap();
+            builder.add_vote(&[vec!["A".to_string()]], 6).unwrap();
+            builder.add_vote(&[vec!["B".to_string()]], 3).unwrap();
+            builder
+                .add_vote(&[vec!["X".to_string()], vec!["B".to_string()]], 1)
+                .unwrap();
+            builder.add_vote(&[vec!["C".to_string()]], 1).unwrap();
+            run_election(&builder).unwrap()
+        };
+
+        // The first round only transfers the write-ins, A is elected in the second round.
+        let current = run(UwiRound1Reporting::Current);
+        assert_eq!(current.round_stats.len(), 2);
+        assert!(current.round_stats[0].tally_results_elected.is_empty());
+        assert_eq!(current.round_stats[1].tally_results_elected, vec!["A"]);
+
+        // The write-in counts in the threshold of the first round (6 of 11), which A reaches.
+        let reference = run(UwiRound1Reporting::Reference);
+        assert_eq!(reference.round_stats.len(), 1);
+        assert_eq!(reference.threshold, 6);
+        let round1 = &reference.round_stats[0];
+        assert_eq!(round1.tally_results_elected, vec!["A"]);
+        assert!(round1
+            .tally
+            .contains(&("Undeclared Write-ins".to_string(), 1)));
+        assert_eq!(
+            round1.tally_result_eliminated[0].name,
+            "Undeclared Write-ins"
+        );
+        assert_eq!(
+            round1.tally_result_eliminated[0].transfers,
+            vec![("B".to_string(), 1)]
+        );
+        assert_eq!(reference.winners, current.winners);
+        assert_eq!(
+            round1.notes,
+            vec![
+                RoundNote::UwiElimination,
+                RoundNote::ThresholdPolicy {
+                    reporting: UwiRound1Reporting::Reference,
+                    threshold: 6,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn first_choice_percent() {
+        // The example of the documentation of the crate.
+        let ballots: Vec<Vec<&str>> = vec![
+            vec!["A", "B", "D"],
+            vec!["A", "C", "B"],
+            vec!["B", "A", "D", "C"],
+            vec!["B", "C", "A", "D"],
+            vec!["C", "A", "B", "D"],
+            vec!["D", "B", "A", "C"],
+        ];
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        let mut percents = res.first_choice_percent();
+        percents.sort();
+        let expected: Vec<(String, String)> =
+            [("A", "33.3"), ("B", "33.3"), ("C", "16.7"), ("D", "16.7")]
+                .iter()
+                .map(|(n, p)| (n.to_string(), p.to_string()))
+                .collect();
+        assert_eq!(percents, expected);
+        assert_eq!(format_percent(0, 0), "0.0");
+        assert_eq!(format_percent(171, 500), "34.2");
+    }
+
+    #[test]
+    fn custom_elimination_strategy() {
+        use std::sync::{Arc, Mutex};
+
+        // Eliminates the last running candidate in the order of the candidates, and records
+        // what it was given.
+        #[derive(Debug, Default)]
+        struct LastDeclared(Mutex<Vec<(u32, usize, Vec<u64>)>>);
+        impl EliminationStrategy for LastDeclared {
+            fn select(&self, ctx: &RoundContext) -> Result<Vec<CandidateId>, VotingErrors> {
+                let tally: Vec<u64> = ctx.tally().iter().map(|(_, count)| *count).collect();
+                let seen = (ctx.round().0, ctx.prior_rounds().len(), tally);
+                self.0.lock().unwrap().push(seen);
+                Ok(ctx
+                    .candidates()
+                    .last()
+                    .map(|(_, cid)| *cid)
+                    .into_iter()
+                    .collect())
+            }
+        }
+
+        // With the default rules, C is eliminated and A wins with its votes.
+        let ballots = vec![vec!["A"], vec!["A"], vec!["B"], vec!["B"], vec!["C", "A"]];
+        let strategy = Arc::new(LastDeclared::default());
+        let rules = VoteRules {
+            elimination_strategy: Some(strategy.clone()),
+            ..VoteRules::default()
+        };
+        assert_eq!(rules, rules.clone());
+        let res = run_election1(&ballots, &rules).unwrap();
+        // C then B are eliminated, whatever their votes.
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+        assert_eq!(
+            *strategy.0.lock().unwrap(),
+            vec![(1, 0, vec![2, 2, 1]), (2, 1, vec![3, 2])]
+        );
+
+        // A strategy that selects nobody stops the tabulation.
+        #[derive(Debug)]
+        struct Nobody;
+        impl EliminationStrategy for Nobody {
+            fn select(&self, _: &RoundContext) -> Result<Vec<CandidateId>, VotingErrors> {
+                Ok(vec![])
+            }
+        }
+        let rules = VoteRules {
+            elimination_strategy: Some(Arc::new(Nobody)),
+            ..VoteRules::default()
+        };
+        assert_eq!(
+            run_election1(&ballots, &rules),
+            Err(VotingErrors::NoCandidateToEliminate)
+        );
+
+        // After the winner, selecting nobody only stops the informational rounds: the result is
+        // the one of the decisive rounds.
+        #[derive(Debug)]
+        struct FirstRoundOnly;
+        impl EliminationStrategy for FirstRoundOnly {
+            fn select(&self, ctx: &RoundContext) -> Result<Vec<CandidateId>, VotingErrors> {
+                match ctx.round().0 {
+                    1 => Ok(ctx
+                        .candidates()
+                        .last()
+                        .map(|(_, cid)| *cid)
+                        .into_iter()
+                        .collect()),
+                    _ => Ok(vec![]),
+                }
+            }
+        }
+        let ballots = vec![vec!["A"], vec!["A"], vec!["A"], vec!["B"], vec!["C"]];
+        let rules = VoteRules {
+            elimination_strategy: Some(Arc::new(FirstRoundOnly)),
+            ..VoteRules::default()
+        };
+        let plain = run_election1(&ballots, &rules).unwrap();
+        let rules = VoteRules {
+            continue_to_completion: true,
+            ..rules
+        };
+        let res = run_election1(&ballots, &rules).unwrap();
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+        assert_eq!(res.round_stats, plain.round_stats);
+    }
+
+    #[test]
+    fn winner_ranked_on() {
+        // The example of the documentation of the crate: A is ranked on all the ballots.
+        let mut ballots: Vec<Vec<&str>> = vec![
+            vec!["A", "B", "D"],
+            vec!["A", "C", "B"],
+            vec!["B", "A", "D", "C"],
+            vec!["B", "C", "A", "D"],
+            vec!["C", "A", "B", "D"],
+            vec!["D", "B", "A", "C"],
+        ];
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+        assert_eq!(res.winner_ranked_on, vec![("A".to_string(), 6)]);
+
+        // Without A on the last ballot, A still wins and is ranked on 5 of the 6 ballots.
+        ballots[5] = vec!["D", "B", "C"];
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+        assert_eq!(res.winner_ranked_on, vec![("A".to_string(), 5)]);
+
+        // The rankings are counted before the truncation by max_rankings_allowed.
+        let rules = VoteRules {
+            max_rankings_allowed: Some(1),
+            ..VoteRules::default()
+        };
+        let res = run_election1(&[vec!["A", "B"], vec!["A"], vec!["B", "A"]], &rules).unwrap();
+        assert_eq!(res.winner_ranked_on, vec![("A".to_string(), 3)]);
+    }
+
+    #[test]
+    fn percent_rounding_boundaries() {
+        use PercentRounding::*;
+        // (count, total, decimals, half up, truncate, half even)
+        let cases: [(u64, u64, u32, &str, &str, &str); 9] = [
+            // Exactly half-way at the second decimal: 0.05% and 0.15%.
+            (1, 2000, 1, "0.1", "0.0", "0.0"),
+            (3, 2000, 1, "0.2", "0.1", "0.2"),
+            // 12.25% and 12.35%, which are not exact as floating point numbers.
+            (49, 400, 1, "12.3", "12.2", "12.2"),
+            (247, 2000, 1, "12.4", "12.3", "12.4"),
+            // Just below and just above half-way.
+            (2449, 20000 - 1, 1, "12.2", "12.2", "12.2"),
+            (2451, 20000, 1, "12.3", "12.2", "12.3"),
+            // 33.335% with two decimals, 66.666...%, and 100%.
+            (6667, 20000, 2, "33.34", "33.33", "33.34"),
+            (2, 3, 2, "66.67", "66.66", "66.67"),
+            (5, 5, 1, "100.0", "100.0", "100.0"),
+        ];
+        for (count, total, decimals, half_up, truncate, half_even) in cases {
+            assert_eq!(HalfUp.format(count, total, decimals), half_up);
+            assert_eq!(Truncate.format(count, total, decimals), truncate);
+            assert_eq!(HalfEven.format(count, total, decimals), half_even);
+        }
+        assert_eq!(HalfEven.format(1, 8, 0), "12");
+        assert_eq!(HalfEven.format(3, 8, 0), "38");
+        assert_eq!(Truncate.format(0, 0, 2), "0.00");
+    }
+
+    #[test]
+    fn elimination_order_with_batch() {
+        let rules = VoteRules {
+            elimination_algorithm: EliminationAlgorithm::Batch,
+            ..VoteRules::default()
+        };
+        let mut builder = Builder::new(&rules).unwrap();
+        let mut add = |choices: &[&str], count: u32| {
+            let choices: Vec<Vec<String>> = choices.iter().map(|c| vec![c.to_string()]).collect();
+            builder.add_vote(&choices, count).unwrap();
+        };
+        add(&["A"], 8);
+        add(&["B"], 6);
+        add(&["E", "B"], 4);
+        add(&["C", "B"], 2);
+        add(&["D", "B"], 1);
+        let res = run_election(&builder).unwrap();
+        // Round 1: C and D are eliminated together, since they have fewer votes together (3)
+        // than E (4). Round 2: E. Round 3: B wins.
+        let order: Vec<(&str, u32, u64, bool)> = res
+            .elimination_order
+            .iter()
+            .map(|e| (e.name.as_str(), e.round.0, e.votes, e.elected))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("D", 1, 1, false),
+                ("C", 1, 2, false),
+                ("E", 2, 4, false),
+                ("B", 3, 13, true)
+            ]
+        );
+        assert_eq!(res.winners, Some(vec!["B".to_string()]));
+    }
+
+    #[test]
+    fn single_candidate() {
+        // As many candidates as winners: the candidate is elected in the first round, even
+        // with ballots that do not rank it.
+        let mut builder = Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&["A".to_string()])
+            .unwrap();
+        builder.add_vote(&[vec!["A".to_string()]], 3).unwrap();
+        builder.add_vote(&[vec![]], 2).unwrap();
+        let res = run_election(&builder).unwrap();
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+        assert_eq!(res.round_stats.len(), 1);
+        assert_eq!(res.threshold, 2);
+        assert_eq!(
+            res.round_stats[0].tally_results_elected,
+            vec!["A".to_string()]
+        );
+    }
+
+    #[test]
+    fn elimination_order_votes() {
+        // The example of the manual.
+        let mut builder = Builder::new(&VoteRules::default()).unwrap();
+        for ballot in [
+            "A,B,,D", "A,C,B,", "B,A,D,C", "B,C,A,D", "C,A,B,D", "D,B,A,C",
+        ] {
+            let choices: Vec<Vec<String>> = ballot
+                .split(',')
+                .map(|c| match c {
+                    "" => vec![],
+                    c => vec![c.to_string()],
+                })
+                .collect();
+            builder.add_vote(&choices, 1).unwrap();
+        }
+        let res = run_election(&builder).unwrap();
+        let order: Vec<(&str, u32, u64, &str, bool)> = res
+            .elimination_order
+            .iter()
+            .map(|e| {
+                let share = e.share_percent.as_str();
+                (e.name.as_str(), e.round.0, e.votes, share, e.elected)
+            })
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("D", 1, 1, "16.7", false),
+                ("C", 2, 1, "16.7", false),
+                ("B", 3, 3, "50.0", false),
+                ("A", 4, 6, "100.0", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn round_notes() {
+        let rules = VoteRules {
+            elimination_algorithm: EliminationAlgorithm::Batch,
+            ..VoteRules::default()
+        };
+        let names: Vec<String> = ["A", "B", "C", "D", "E"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let mut builder = Builder::new(&rules).unwrap().candidates(&names).unwrap();
+        let mut add = |choices: &[&str], count: u32| {
+            let choices: Vec<Vec<String>> = choices.iter().map(|c| vec![c.to_string()]).collect();
+            builder.add_vote(&choices, count).unwrap();
+        };
+        add(&["A"], 7);
+        add(&["B"], 5);
+        add(&["E", "A"], 5);
+        add(&["C"], 2);
+        add(&["D"], 1);
+        let res = run_election(&builder).unwrap();
+        // Round 1: C and D are eliminated together. Round 2: B and E are tied, and E is
+        // eliminated since it comes last in the order of the candidates. Round 3: A wins.
+        let notes: Vec<&[RoundNote]> = res.round_stats.iter().map(|rs| &rs.notes[..]).collect();
+        assert_eq!(
+            notes,
+            vec![
+                &[RoundNote::BatchElimination { count: 2 }][..],
+                &[RoundNote::Tiebreak {
+                    candidates: vec!["E".to_string(), "B".to_string()],
+                    eliminated: "E".to_string(),
+                }][..],
+                &[][..],
+            ]
+        );
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+    }
+
+    #[test]
+    fn flat_rows_doc_example() {
+        let row =
+            |round: u32, candidate: &str, votes: u64, status: &str, t: Option<(&str, u64)>| {
+                FlatRow {
+                    round,
+                    candidate: candidate.to_string(),
+                    votes,
+                    status: status.to_string(),
+                    transfer_to: t.map(|(to, _)| to.to_string()),
+                    transfer_votes: t.map(|(_, count)| count),
+                }
+            };
+        let run = |votes: &[(&[&str], u32)]| {
+            let mut builder = Builder::new(&VoteRules::default()).unwrap();
+            builder = builder
+                .candidates(&[
+                    "Alice".to_string(),
+                    "Bob".to_string(),
+                    "Charlie".to_string(),
+                ])
+                .unwrap();
+            for (choices, count) in votes {
+                let choices: Vec<Vec<String>> =
+                    choices.iter().map(|c| vec![c.to_string()]).collect();
+                builder.add_vote(&choices, *count).unwrap();
+            }
+            run_election(&builder).unwrap().to_flat_rows()
+        };
+
+        // The election of the example of run_election.
+        let rows = run(&[
+            (&["Alice", "Bob", "Charlie"], 1),
+            (&["Alice"], 1),
+            (&["Charlie", "Bob"], 1),
+        ]);
+        assert_eq!(
+            rows,
+            vec![
+                row(1, "Alice", 2, "elected", None),
+                row(1, "Charlie", 1, "continuing", None),
+                row(1, "Bob", 0, "continuing", None),
+            ]
+        );
+
+        // With a transfer and exhausted votes.
+        let rows = run(&[
+            (&["Alice"], 5),
+            (&["Bob"], 3),
+            (&["Charlie", "Bob"], 1),
+            (&["Charlie"], 1),
+        ]);
+        assert_eq!(
+            rows,
+            vec![
+                row(1, "Alice", 5, "continuing", None),
+                row(1, "Bob", 3, "continuing", None),
+                row(1, "Charlie", 2, "eliminated", None),
+                row(1, "Charlie", 2, "eliminated", Some(("Bob", 1))),
+                row(1, "Charlie", 2, "eliminated", Some(("exhausted", 1))),
+                row(2, "Alice", 5, "elected", None),
+                row(2, "Bob", 4, "continuing", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn batch_elimination_boundary() {
+        // (tally, expected batch), with the candidate ids starting at 1.
+        let cases: Vec<(Vec<u64>, Option<Vec<u32>>)> = vec![
+            // Equal lowest counts are batched together.
+            (vec![1, 1, 5], Some(vec![1, 2])),
+            (vec![5, 1, 1], Some(vec![2, 3])),
+            (vec![1, 1, 2, 10], Some(vec![1, 2, 3])),
+            (vec![2, 1, 1, 10], Some(vec![2, 3, 1])),
+            // A count equal to the total below it is a tie, not a gap: 1 + 2 = 3 and
+            // 1 + 2 + 3 = 6, so that only the first candidate is batched.
+            (vec![1, 2, 3, 6], Some(vec![1])),
+            (vec![6, 3, 2, 1], Some(vec![4])),
+            // Equal counts just above the boundary are both kept.
+            (vec![1, 3, 3, 7], Some(vec![1])),
+            // Equal counts at the top: everything below is batched.
+            (vec![1, 2, 4, 4], Some(vec![1, 2])),
+            // No gap: only a single elimination can proceed.
+            (vec![1, 1], None),
+            (vec![2, 2, 2], None),
+        ];
+        for (counts, expected) in cases.iter() {
+            // A new map at each run, with a new iteration order.
+            for _ in 0..20 {
+                let tally: HashMap<CandidateId, VoteCount> = counts
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, c)| (CandidateId(idx as u32 + 1), VoteCount(*c)))
+                    .collect();
+                let batch = find_eliminated_candidates_batch(&tally)
+                    .map(|cids| cids.iter().map(|cid| cid.0).collect::<Vec<u32>>());
+                assert_eq!(&batch, expected, "{:?}", counts);
+            }
+        }
+    }
+
+    #[test]
+    fn candidate_name_equality() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |name: &CandidateName| {
+            let mut hasher = DefaultHasher::new();
+            name.hash(&mut hasher);
+            hasher.finish()
+        };
+        let trim = |s: &str| CandidateName::new(s, NameNormalization::Trim);
+        let fold = |s: &str| CandidateName::new(s, NameNormalization::Fold);
+
+        // The whitespace around the names is always ignored, and the display form keeps the rest.
+        assert_eq!(trim(" Anna Smith\t"), trim("Anna Smith"));
+        assert_eq!(hash(&trim(" Anna Smith\t")), hash(&trim("Anna Smith")));
+        assert_eq!(trim(" Anna Smith\t").as_str(), "Anna Smith");
+        assert_ne!(trim("Anna  Smith"), trim("Anna Smith"));
+        assert_ne!(trim("anna smith"), trim("Anna Smith"));
+
+        // Folding also ignores the case and the runs of whitespace.
+        assert_eq!(fold("Anna  SMITH"), fold("anna smith"));
+        assert_eq!(hash(&fold("Anna  SMITH")), hash(&fold("anna smith")));
+        assert_eq!(fold("Anna  SMITH").as_str(), "Anna  SMITH");
+        assert_eq!(fold("Anna  SMITH").to_string(), "Anna  SMITH");
+        assert_eq!(fold("Anna  SMITH").key(), "anna smith");
+        assert_ne!(fold("Anna"), fold("Anne"));
+
+        let names: HashSet<CandidateName> =
+            ["Anna", " Anna", "Bob"].iter().map(|s| trim(s)).collect();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names.get(&trim("Anna ")).map(|n| n.as_str()), Some("Anna"));
+    }
+
+    #[test]
+    fn name_normalization_in_tabulation() {
+        // The ballots are stored with the names of the declaration.
+        let rules = VoteRules {
+            name_normalization: NameNormalization::Fold,
+            nota_candidate: Some("none of the above".to_string()),
+            ..VoteRules::default()
+        };
+        let mut builder = Builder::new(&rules)
+            .unwrap()
+            .candidates(&["Anna".to_string(), "None of the Above".to_string()])
+            .unwrap();
+        builder.add_vote_simple(&["ANNA".to_string()]).unwrap();
+        builder
+            .add_vote_simple(&["none of  the above".to_string()])
+            .unwrap();
+        builder
+            .add_vote_simple(&[" None of the above".to_string()])
+            .unwrap();
+        assert_eq!(
+            builder._votes[0].candidates,
+            vec![BallotChoice::Candidate("Anna".to_string())]
+        );
+        let result = run_election(&builder).unwrap();
+        assert_eq!(result.winners, None);
+        assert!(result.nota_prevailed);
+
+        // The ballots added directly are matched in the tabulation, and the inferred candidates
+        // are merged when their names only differ by the normalization.
+        let mut builder = Builder::new(&VoteRules::default()).unwrap();
+        let ballot = |name: &str| Ballot {
+            candidates: vec![BallotChoice::Candidate(name.to_string())],
+            count: 1,
+            challenged: false,
+            id: None,
+        };
+        builder.add_ballot(ballot("Bob ")).unwrap();
+        builder.add_ballot(ballot("Bob")).unwrap();
+        builder.add_ballot(ballot("Anna")).unwrap();
+        let result = run_election(&builder).unwrap();
+        let names: Vec<&str> = result.candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Anna", "Bob"]);
+        assert_eq!(result.winners, Some(vec!["Bob".to_string()]));
+    }
+
+    // Counts the allocations of the threads that enable the counting.
+    struct CountingAllocator;
+
+    thread_local! {
+        static COUNTING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+        static NUM_ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            if COUNTING.with(|c| c.get()) {
+                NUM_ALLOCATIONS.with(|n| n.set(n.get() + 1));
+            }
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn add_ballot_stores_once() {
+        let ballots: Vec<Ballot> = (0..1000)
+            .map(|idx| Ballot {
+                candidates: vec![
+                    BallotChoice::Candidate(["A", "B", "C"][idx % 3].to_string()),
+                    BallotChoice::Blank,
+                ],
+                count: 1,
+                challenged: false,
+                id: None,
+            })
+            .collect();
+        let buffers: Vec<*const BallotChoice> =
+            ballots.iter().map(|b| b.candidates.as_ptr()).collect();
+
+        // The ballots are moved into the builder: the choices are not copied.
+        let mut builder = Builder::new(&VoteRules::default()).unwrap();
+        for ballot in ballots.clone() {
+            #[allow(deprecated)]
+            builder.add_vote_2(&ballot).unwrap();
+        }
+        let copied = std::mem::take(&mut builder._votes);
+        NUM_ALLOCATIONS.with(|n| n.set(0));
+        COUNTING.with(|c| c.set(true));
+        builder._votes.reserve(ballots.len());
+        for ballot in ballots {
+            builder.add_ballot(ballot).unwrap();
+        }
+        COUNTING.with(|c| c.set(false));
+        assert_eq!(NUM_ALLOCATIONS.with(|n| n.get()), 1);
+        let stored: Vec<*const BallotChoice> = builder
+            ._votes
+            .iter()
+            .map(|b| b.candidates.as_ptr())
+            .collect();
+        assert_eq!(stored, buffers);
+        // The deprecated method adds the same ballots, as copies.
+        assert_eq!(copied, builder._votes);
+    }
+
+    #[test]
+    fn advance_voting_allocations() {
+        // A synthetic election: 1000 ballots that rank the 10 candidates in rotated orders, with
+        // blanks and duplicates.
+        let num_candidates = 10;
+        let ballots: Vec<RankedChoice> = (0..1000)
+            .map(|idx| {
+                let mut choices: Vec<Choice> = (0..num_candidates)
+                    .map(|r| Choice::Filled(CandidateId((idx + r) % num_candidates)))
+                    .collect();
+                if idx % 3 == 0 {
+                    choices.insert(2, Choice::Blank);
+                }
+                if idx % 7 == 0 {
+                    choices.insert(4, choices[1]);
+                }
+                RankedChoice::new(&choices)
+            })
+            .collect();
+        for mode in [
+            DuplicateCandidateMode::SkipDuplicate,
+            DuplicateCandidateMode::Exhaust,
+        ] {
+            let mut still_valid: HashSet<CandidateId> =
+                (0..num_candidates).map(CandidateId).collect();
+            let mut current: Vec<RankedChoice> = ballots.clone();
+            let (mut num_moves, mut num_allocations) = (0, 0);
+            for eliminated in 0..num_candidates - 1 {
+                still_valid.remove(&CandidateId(eliminated));
+                let mut next: Vec<RankedChoice> = Vec::with_capacity(current.len());
+                for rc in current.drain(..) {
+                    let head = rc.head;
+                    NUM_ALLOCATIONS.with(|n| n.set(0));
+                    COUNTING.with(|c| c.set(true));
+                    let res = rc.filtered_candidate(
+                        &still_valid,
+                        mode,
+                        OverVoteRule::AlwaysSkipToNextRank,
+                        MaxSkippedRank::Unlimited,
+                        true,
+                    );
+                    COUNTING.with(|c| c.set(false));
+                    num_allocations += NUM_ALLOCATIONS.with(|n| n.get());
+                    if let Ok(rc) = res {
+                        num_moves += usize::from(rc.head != head);
+                        next.push(rc);
+                    }
+                }
+                current = next;
+            }
+            assert!(num_moves > 1000, "{:?}: {}", mode, num_moves);
+            assert_eq!(num_allocations, 0, "{:?}", mode);
+        }
+    }
 }
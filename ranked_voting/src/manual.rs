@@ -5,20 +5,46 @@ This is the long-form manual for `ranked_voting` and `timrcv`.
 ## Input formats
 
 The following formats are supported:
-* `ess` ES&S company
-* `dominion` Dominion company
-* `cdf` NIST CDF
-* `csv`, `csv_likert` Comma Separated Values in various flavours
-* `msforms`, `msforms_likert`, `msforms_likert_transpose` Input from Microsoft Forms and Google Forms products.
+* `ess` ES&S cast vote records (Excel spreadsheet)
+* `cdf` NIST Common Data Format, JSON notation
+* `dominion` Dominion CVR export directory (CandidateManifest.json and CvrExport.json)
+* `msforms_ranking` Microsoft Forms export using the ranking widget (Excel spreadsheet)
+* `msforms_likert` Microsoft Forms or Google Forms export using the Likert widget (Excel spreadsheet)
+* `msforms_likert_transpose` Microsoft Forms or Google Forms export using the Likert widget, with choices as rows (Excel spreadsheet)
+* `csv` Comma separated values, one ballot per row
+* `csv_likert` Comma separated values, one column per candidate
+* `parquet` Apache Parquet file with one column per rank or a list column (requires the parquet feature)
+
+The same list is available in machine-readable form in the `providers.json` file at the
+root of the repository, from which this list and the help of `--input-type` are generated.
+Each provider has a small example under the `tests` directory.
 
 ### `ess`
 
 Votes recorded in the ES&S format (Excel spreadsheet).
 
+The votes start at `firstVoteColumnIndex`, and the leading columns of the workbook are skipped,
+except the precinct (`precinctColumnIndex`) and the ballot style (`styleColumnIndex`), which are
+read if they are set. Both must be before `firstVoteColumnIndex`. With `--emit-provenance` or the
+v2 schema, the source in the summary has the number of ballots by precinct (`precincts`) and by
+ballot style (`ballotStyles`).
+
 ### `dominion`
 
 Votes recorded in the format from the Dominion company.
 
+The `filePath` is the directory with the files `CandidateManifest.json` and `CvrExport.json`. The
+marks with a candidate id that is not in the manifest fail with `E004`, with all the missing ids
+and their number of marks. A manifest that was not fully downloaded may still be a valid file
+without some candidates: with `expectedCandidateCount` (number, optional) on the file source,
+a manifest with another number of candidates fails with `E002` before the ballots are read, with
+the names of the candidates found.
+
+The name of a candidate of the manifest is its `Description`. Some manifests have a short code in
+the `Description`, and the name in the `ExternalId`: when the `Description` or the `ExternalId`
+is the name of a declared candidate, or the `code` of a declared candidate, the marks are
+counted for this candidate.
+
 ### `cdf`
 
 Votes recorded in the Common Data Format from NIST.
@@ -26,8 +52,13 @@ Votes recorded in the Common Data Format from NIST.
 Notes:
 - only the JSON notation is currently supported (not the XML)
 - only one election is supported
+- with `contestId` (string, optional) on the file source, only the contest with this id (`@id`
+  of the `Contest`, `ContestId` of the `CVRContest`) is read. The selections of the other
+  contests are not parsed, so that a report with many contests is read with the memory of one
+  contest. An id that is not in the report fails with `E002` and the ids of its contests. Without
+  `contestId`, each contest of each ballot is read as a separate ballot.
 
-### `msforms`
+### `msforms_ranking`
 
 Results from Microsoft Forms when using the ranking widget.
 The input file is expected to be in Excel (.xlsx) format.
@@ -69,7 +100,7 @@ In this example, this vote would mark `candidate B` as the first choice and then
 
 In this case, both the names of the choices and of the candidates are mandatory. See the example `msforms_likert_transpose` for an example of a configuration file.
 
-### csv
+### `csv`
 
 Simple CSV reader. Each column (in order) is considered to be a choice. The name of the choice in the header is not significant.
 
@@ -81,8 +112,40 @@ id2,20,A,C,B,D
 
 The `id` and `count` columns are optional. Headers in the first row is optional.
 See the [Configuration section](#configuration) on controling the optional rows and columns.
+All the column indices (`idColumnIndex`, `countColumnIndex`, `firstVoteColumnIndex`, ...) start
+at 1, or can be given as Excel column letters (`A` is the first column, `AA` the 27th). A warning is printed
+at the first row that does not contain all the configured columns.
+
+The validated ballots of any election can be exported in this format with the `--export-simple-csv <path>` flag,
+for example to migrate data between tools. The exported file has an id column, a count column and one column per choice,
+with a `challenged` column before the choices if some ballots are challenged. The ids are the ids of the ballots in their
+sources (the readers name the rows of a file without an id column by their file and their line). The special choices are written as follows:
+- the undeclared write-ins as `UWI`;
+- the overvotes with the `overvoteLabel` of the file sources, or `__OVERVOTE__`;
+- the undervotes with the `undervoteLabel` of the file sources, or `__UNDERVOTE__`. The empty
+  cells only pad the rows, and the undervotes at the end of a ballot are kept;
+- the blanks as a single space.
 
-### csv_likert
+The labels of the file sources are only used if all the file sources have the same one. Next to
+the export, `timrcv` writes the configuration that reads it back with the same ballots:
+`ballots_config.json` for `ballots.csv`. It is the configuration of the election, with this file
+source, and without the weights (the counts are already weighted). A previous export to the
+same path is replaced with its configuration. Any other file with this name is never
+overwritten, nor is the configuration of the election itself: the program stops before the
+tabulation (`E006`). Its file source is:
+
+```text
+{
+  "filePath": "ballots.csv",
+  "provider": "csv",
+  "idColumnIndex": "1",
+  "countColumnIndex": "2",
+  "firstVoteColumnIndex": "3",
+  "overvoteLabel": "__OVERVOTE__"
+}
+```
+
+### `csv_likert`
 
 Simple CSV reader sorted by candidates. This format is also created by Qualtrics polls. The file is expected to look as follows:
 
@@ -94,6 +157,234 @@ id2,20,1,3,2,4
 
 The `id` and `count` columns are optional. The candidate names must all be a column and defined in the first row of the CSV file. The numbers below are the ranks of this candidate for each ballot (or empty if this candidate was not ranked).
 
+Such a file can also be read without a configuration, with the candidates of the header:
+`timrcv --input ballots.csv --likert`.
+
+Some tools add the question to the headers, for example Google Forms writes
+`Who do you vote for? [Alice]`. The `candidateHeaderPattern` option extracts the name of the
+candidate from each header, either with a template where `{}` stands for the name
+(`"Who do you vote for? [{}]"`) or with a regular expression with one capture group
+(`"\\[(.+)\\]$"`). It is also supported by the `msforms_likert` provider. When some candidates
+are not found, the error lists them along with the header columns that did not match any candidate.
+When two header columns give the same candidate, for example with two questions of the form, the
+error names both columns.
+
+With `choices`, the cells are the labels of the ranks instead of their numbers, as in the
+exports of the grids of Google Forms (`1st`, `2nd`, ...): the first label is the first rank.
+
+### `parquet`
+
+Ballots stored in the Apache Parquet format. This provider is only available when `timrcv` is
+built with the `parquet` feature (`cargo install timrcv --features parquet`).
+
+The choices are read from either:
+- several string columns named with a prefix and the rank, for example `rank_1`, `rank_2`, ...
+  with `"rankColumnPrefix": "rank_"` and `"rankColumnCount": 4`. Null or empty values are
+  skipped ranks.
+- a single column containing the list of the choices of each ballot, with
+  `"rankingsColumn": "choices"`.
+
+The optional `idColumnName` and `countColumnName` options give the names of the columns with
+the ids (strings or integers) and the counts (integers) of the ballots.
+
+```text
+{
+  "filePath": "ballots.parquet",
+  "provider": "parquet",
+  "idColumnName": "ballot_id",
+  "rankColumnPrefix": "rank_",
+  "rankColumnCount": 4
+}
+```
+
+## Inspecting the ballots
+
+The `patterns` command reads and validates the ballots like a tabulation, and prints the most
+common rankings with their counts and percentages:
+
+```text
+timrcv patterns --config config.json --top 20
+```
+
+Skipped ranks are displayed as `(skip)`, overvotes as `[B/C]` (or `(overvote)` when the names
+are not known) and write-ins as `(write-in)`, for example `A > (skip) > C`. The `--json` flag
+prints the same information in JSON format.
+
+## Verifying official rounds
+
+The `verify-rounds` command tabulates the election and compares each round with official
+round tallies, for example when only the published first round is available alongside the
+ballots:
+
+```text
+timrcv verify-rounds --config config.json --official rounds.json
+```
+
+The official rounds are a JSON list such as `[{"round": 1, "tally": {"A": 120, "B": 97}}]`.
+The counts may be numbers or strings, and a summary file is also accepted. Rounds are numbered
+from 1 and the official data may contain only some of the rounds. The command prints the first
+round that differs, with the official and computed tallies of each candidate that differs. The
+exit code is 2 when a round differs. The `--json` flag prints the report in JSON format. The
+same comparison is available to other programs with
+`snapshot::ValidatedElection::verify_round_sequence`, on the validated ballots of a snapshot.
+
+The challenged ballots are counted or left out as in the tabulation, with `--with-challenged
+include` (the default) or `--with-challenged exclude`. `--with-challenged both` is refused: the
+official rounds are compared with a single tabulation.
+
+## Creating test cases
+
+The `make-fixture` command turns the configuration of an election into a self-contained test
+case, in the layout of the `tests` directory:
+
+```text
+timrcv make-fixture --config config.json --out-dir tests/my_election --max-ballots 200
+```
+
+The directory then contains `my_election_config.json`, a sample of the ballots of each file
+source and `my_election_expected_summary.json`, the summary of the tabulation of the sample.
+The sample keeps at least one ballot for each candidate (even if this exceeds `--max-ballots`),
+and the other ballots are spread over the file. The `csv` and `csv_likert` files keep their
+format. The other inputs (Excel, Dominion, CDF, ...) are converted to the simple `csv` format,
+and their file source is replaced in the configuration.
+
+## Reusing the validated ballots
+
+Reading and validating large inputs can take longer than the tabulation itself. The
+`--save-validated <path>` flag writes the validated ballots to a compact binary snapshot, with
+the identical ballots merged, and `--load-validated <path>` tabulates a snapshot instead of the
+file sources of the configuration:
+
+```text
+timrcv --config config.json --save-validated ballots.snapshot
+timrcv --config config.json --load-validated ballots.snapshot --summary-schema v2
+```
+
+The snapshot starts with a version number and ends with a checksum: a snapshot of another
+version or a corrupted file is refused. The snapshot also records a fingerprint of the rules,
+and is refused if the rules of the configuration are different, unless `--allow-rules-mismatch`
+is passed. The fingerprint is computed on a canonical text of the rules, and does not change
+with `--debug-sample-rate`. The snapshots of version 2 had another fingerprint and are refused. The
+voter categories are not kept, so the `categories` section of the summary is omitted when
+loading a snapshot.
+
+The snapshots are also available to other programs through `snapshot::ValidatedElection`:
+`ValidatedElection::new` merges the identical ballots, `save(path)` writes the snapshot,
+`load(path)` reads it back, `check_rules` compares its fingerprint with the rules and
+`verify_round_sequence` compares its tabulation with official rounds (see `verify-rounds`).
+
+## Exporting to PrefLib
+
+The `--export-preflib <path>` flag writes the validated ballots in the PrefLib format of the
+orders with ties (`.toi`), to share a dataset with the researchers who use the PrefLib tools.
+The header lists the alternatives (the declared candidates, then `UWI` if some ballots have
+undeclared write-ins), and each line is an order with the number of ballots that cast it. With
+the candidates `A`, `B` and `C`, and the overvote delimiter `|`, these ballots (id, count, then
+the ranks):
+
+```text
+id1,3,A,B,C
+id2,2,A,B,
+id3,1,B,A|C,
+id4,1,,B,A
+id5,1,C,C,A
+id6,1,A,B,C
+id7,1,,,
+```
+
+are exported as:
+
+```text
+# FILE NAME: example.toi
+# TITLE: Example
+# DATA TYPE: toi
+# MODIFICATION TYPE: original
+# NUMBER ALTERNATIVES: 3
+# NUMBER VOTERS: 9
+# NUMBER UNIQUE ORDERS: 5
+# ALTERNATIVE NAME 1: A
+# ALTERNATIVE NAME 2: B
+# ALTERNATIVE NAME 3: C
+4: 1,2,3
+2: 1,2
+1: 2,1
+1: 2,{1,3}
+1: 3,1
+```
+
+An overvote is a tie between the candidates of its rank (`{1,3}`); an overvote label without
+the names of the candidates is omitted. The blanks, the undervotes and the repeated candidates
+are omitted, and the ballots without any candidate (`id7`) are not exported. The counts are
+weighted by the voter categories. The orders are sorted by decreasing count, and the data type
+is `soi` when no order has a tie. The rules of the tabulation (`overvoteRule`,
+`maxSkippedRanksAllowed`, ...) do not change the orders. The export needs the file sources: it
+cannot be used with `--load-validated`, since the snapshots do not keep the candidates of the
+overvotes. The example above has rare orders: it is only exported with `--privacy-k 1` (see
+below).
+
+## Privacy of the ballot exports
+
+In a small precinct, a ranking cast by a single voter may identify this voter. Before writing
+`--export-simple-csv` or `--export-preflib`, `timrcv` counts the votes of each ranking pattern
+(the rankings as written in the export, with the counts of the ballots), and refuses to write
+the export (`E009`) if a pattern has fewer votes than the threshold `--privacy-k` (5 by
+default). With `--privacy-suppress-rare`, the ballots of the rare patterns are left out of the
+export instead, and with `--privacy-override` they are written anyway. Both are reported with a
+warning `W021`. `--privacy-k 1` turns the check off. The snapshots of `--save-validated` are not
+checked: they keep all the ballots, to tabulate them again.
+
+## Warning and error codes
+
+Each warning and each category of errors has a stable code, which is printed at the start of
+the log line, for example `[W003] Unknown voter category "guest", using the default weight 1`.
+The codes are never renumbered or reused.
+
+| Code | Name | Meaning |
+|------|------|---------|
+| `W001` | `short-row` | a CSV row has fewer columns than the configuration refers to |
+| `W002` | `unsplittable-overvote` | a choice contains the overvote delimiter but is not a list of candidates |
+| `W003` | `unknown-category` | a voter category has no weight, the default weight is used |
+| `W004` | `fairvote-missing-columns` | some columns of the FairVote row are empty |
+| `W005` | `compressed-ranks` | some ballots had gaps in their ranks, which were compressed |
+| `W006` | `skipped-source` | a file source cannot be read and is skipped (`--skip-bad-sources`) |
+| `W007` | `ranks-in-cells` | a `csv` file seems to have the candidates in the header and the ranks in the cells |
+| `W008` | `extra-candidate-columns` | a `msforms_likert` file has columns of ranks for candidates that are not declared |
+| `W009` | `source-is-output` | a file source is the `summary.json` file of the output directory |
+| `W010` | `rule-override` | a rule of the configuration is overridden by `--rule` |
+| `W011` | `rank-out-of-range` | a likert input has ranks outside of 1 to `maxRank`, which are ignored |
+| `W012` | `duplicate-ranks` | a likert input has ballots that give the same rank to several candidates |
+| `W013` | `near-miss-candidate` | a name of the ballots, or a declared candidate, is close to another declared candidate |
+| `W014` | `too-many-candidates` | suspiciously many candidates are inferred from a source without declared candidates |
+| `W015` | `outside-time-window` | some ballots were submitted outside of the time window of their source, or their timestamp cannot be read |
+| `W016` | `empty-rows` | a CSV file has empty rows, usually at the end of a spreadsheet export, which are skipped |
+| `W017` | `multi-name-cells` | a likert transpose input has cells with several candidates for the same rank, which are overvotes |
+| `W018` | `duplicate-source` | a file source is listed twice with the same options, and is read once (`--allow-duplicate-sources`) |
+| `W019` | `same-file-sources` | two file sources are the same file with different options, and the file is read for each |
+| `W020` | `identical-ballots` | identical ballots of a source were dropped (`deduplicateIdenticalBallots`) |
+| `W021` | `rare-patterns` | a ballot export has ranking patterns below `--privacy-k`, which are left out or written anyway |
+| `W022` | `unknown-choices` | a likert input has cells that are not labels of the `choices`, which are ignored (`allowUnknownChoices`) |
+| `W023` | `unwritten-output` | an output that the result does not depend on cannot be written: the summary of a failed run, the partial rounds (`--out-partial`) or their removal |
+| `W024` | `tabulation-interrupted` | the tabulation was interrupted, and only the rounds done so far are written |
+| `W025` | `verification-mismatch` | a total of the summary does not reconcile (`--verify-output`), or the summary differs from the reference (`--reference-strict`); the run then fails |
+| `W026` | `reference-not-checked` | some file sources were skipped, so the reference summary is not checked |
+| `W027` | `collation-unavailable` | a collation is configured, but `timrcv` is built without the `collation` feature |
+| `W028` | `snapshot-rules-mismatch` | a snapshot of the validated ballots was written with other rules (`--allow-rules-mismatch`) |
+| `E000` | `other` | any other error |
+| `E001` | `invalid-configuration` | invalid configuration or arguments |
+| `E002` | `unreadable-input` | an input file cannot be opened or read |
+| `E003` | `malformed-ballots` | some ballots are malformed |
+| `E004` | `unknown-candidates` | the candidates of the input do not match the declared candidates |
+| `E005` | `tabulation-failed` | the tabulation failed |
+| `E006` | `output-failed` | an output file cannot be written |
+| `E007` | `verification-failed` | the tabulation differs from the reference summary or the official rounds, or the totals of the summary do not reconcile |
+| `E008` | `failed-on-warning` | a warning passed to `--fail-on` was raised |
+| `E009` | `privacy-threshold` | a ballot export would have ranking patterns below `--privacy-k` |
+
+The `--fail-on` flag takes a comma-separated list of warning codes or names, and turns these
+warnings into errors, for example `--fail-on W001,unknown-category`. The summary is still
+written. The v2 summary and the JSON report of `verify-rounds` list the warnings in a
+`warnings` section with their `code`, `name` and `message`.
+
 ## Configuration
 
 `timrcv` comes with sensible defaults but users may want to apply specific rules
@@ -109,18 +400,662 @@ The deviations from the specification of RCVTab are documented below.
 
 Deviations for FileSource:
  - added `count_column_index` (string or number, optional): the location of the column that
- indicates the counts. If not provided, every vote will be assigned a count of 1.
+   indicates the counts. If not provided, every vote will be assigned a count of 1.
 
  - added `excel_worksheet_name` (string, optional): for Excel-based inputs, the name of
- the worksheet in Excel.
+   the worksheet in Excel. If it is not provided, the workbook must have a single visible
+   worksheet with cells.
+
+ - added `excelWorksheetNames` (array of strings, optional) and `allWorksheets` (boolean, optional):
+   for the `msforms_*` providers, reads the ballots from several worksheets of the same workbook,
+   for example when Google Forms splits the responses into "Form Responses 1" and
+   "Form Responses 2". The id of a ballot is the cell of `idColumnIndex`, the id of the
+   submission, as with a single worksheet, so that a submission found in two worksheets keeps its id; the ballots with the
+   id of a ballot of an earlier worksheet are reported with a warning (`W020`), and
+   `deduplicateIdenticalBallots` drops the identical ones. Without `idColumnIndex`, the id is the
+   line number, prefixed with the name of the worksheet.
 
  - added `choices` (array of strings, optional): The list of labels for the choices. For example, if
    the list is `["First choice", "Second choice"]`, then seeing `First choice` will be
    intepreted as choice #1, and so on.
 
+ - added `challengedColumn` (string or number, optional) and `challengedLabel` (string, optional):
+   the column that marks a ballot as challenged or provisional, and the label in that column
+   that marks it. Without a label, any non-empty content marks the ballot. Only the `csv` and
+   `csv_likert` providers support it. By default, challenged ballots are counted. The
+   `--with-challenged exclude` flag leaves them out, and `--with-challenged both` runs both
+   tabulations. The summary then contains the `included` and `excluded` summaries, and a
+   `comparison` with the winners of each tabulation and the first round difference per candidate.
+
+ - added `jobs` (number, optional): for the `csv` provider, the number of threads used to read
+   the file. It can also be set with the `--jobs` flag. The file is split into ranges of lines that
+   are read in parallel, and the ballots are in the same order as with a single thread. A quoted
+   field may contain a newline: when a range starts inside a quoted field, the file is read again
+   with a single thread. The errors of the file are reported with their line number, as with a
+   single thread.
+
+ - changed `overvoteDelimiter`: a cell that contains the delimiter is first compared with the
+   names of the candidates, so that a name such as `Smith | Jones Coalition` is not split. Otherwise
+   the cell is an overvote if all the parts between the delimiters are candidates (or if it only
+   contains the delimiter), the candidate itself if all the parts are the same candidate, and a
+   write-in with a warning if some parts are not candidates.
+
+ - changed the blank choices: the whitespace around a choice is ignored for all the providers, so
+   a cell with only spaces or tabs is a blank choice and not a write-in.
+
+ - changed the small inputs: when none of the file sources has a ballot, the program stops with
+   the error `input file <path> contains no ballots` (`E002`). An empty source next to sources
+   with ballots is accepted. When the candidates are not declared, a file whose rows do not name
+   any candidate stops it with `no candidates found in input <path>` (`E004`). A single ballot with a single candidate elects that candidate with a threshold of 1.
+   For `msforms_ranking`, an empty ranking cell is a blank ballot instead of an error.
+
+ - added `categoryColumn` (string or number, optional): the column with the category of the voter,
+   used by the `weights` section. It is either a column index or the name of the column in the
+   header. Only the `csv`, `csv_likert`, `msforms_ranking` and `msforms_likert` providers support it.
+
+ - added `sanityChecks` (boolean, optional, true by default): when candidates are declared, checks
+   that at least half of the choices, and half of the first choices, are declared candidates. A
+   lower rate usually comes from a wrong `firstVoteColumnIndex`: the error lists the most common
+   other choices and suggests the columns that contain candidate names. The `--no-sanity-checks`
+   flag turns the checks off for all the sources.
+
+ - added `compressRanks` (boolean, optional, false by default): for the `csv_likert`,
+   `msforms_likert` and `msforms_likert_transpose` providers, renumbers the ranks of each ballot
+   to consecutive integers, keeping the ties. A voter who ranks candidates 1, 4 and 7 then has
+   no skipped ranks, which matters with `maxSkippedRanksAllowed`. A warning gives the number of
+   ballots that were compressed.
+
+ - added `numberLocale` (`plain`, `en` or `eu`, optional, `plain` by default): how the counts and
+   the ranks of the `csv` and `csv_likert` providers are written. The spaces around the numbers,
+   including the non-breaking spaces, are always ignored. With `en`, the thousands may be
+   separated by commas or spaces (`1,234`). With `eu`, they may be separated by dots or spaces
+   (`1.234`). Decimal numbers (`12,5` with `eu`) and misplaced separators are rejected with the
+   row and the column of the cell. The counts of the `ess` provider that are written as text
+   follow the same rules, and its negative counts are rejected.
+
+ - added `layout` (`choices-in-cells` or `ranks-in-cells`, optional, `choices-in-cells` by
+   default): for the `csv` provider, `ranks-in-cells` reads the file like the `csv_likert`
+   provider, with the candidates in the header and their ranks in the cells. If the candidates
+   are not declared, the names of the header from `firstVoteColumnIndex` are the candidates.
+   The header is the first row, so the ballots start at the second row if `firstVoteRowIndex` is
+   not set. The `--likert` flag sets this layout for all the sources. Without it, a warning
+   (`W007`) suggests this layout when the header has names and almost all the choices are ranks,
+   also when `firstVoteRowIndex` is not set and the header is read as the first ballot.
+
+ - added `strictColumns` (boolean, optional, false by default): for the `msforms_likert` provider,
+   the columns that are not declared candidates but only contain the labels of the `choices` (for
+   example a candidate added to the form later) are ignored with a warning (`W008`) that gives
+   their header and their number of responses. With `strictColumns`, or the `--strict-columns`
+   flag, they are an error instead.
+
+ - added `allowUnknownChoices` (boolean, optional, false by default): for the `msforms_likert`
+   provider and the `csv_likert` provider with `choices`, a cell that is not one of the labels
+   of the `choices` (for example a rank added to the form during the collection) fails with
+   `E003` and the unknown labels with their number of cells. With `allowUnknownChoices`, or the
+   `--allow-unknown-choices` flag, these cells are ignored with a warning (`W022`).
+
+ - added `continuationRows` (boolean, optional, false by default): for the `csv` provider, a
+   ballot may span several consecutive rows with the same id, as in the exports that limit the
+   number of rank columns per row. The choices of each continuation row are appended to the
+   ranks of the ballot. The count of a continuation row must be empty or equal to the count of
+   the ballot. This option requires `idColumnIndex`, and the file is read with a single thread.
+
+ - added `maxRank` (number, optional, the number of candidate columns by default): for the
+   `csv_likert` and `msforms_likert` providers, the largest valid rank. The ranks outside of 1 to
+   `maxRank` (such as `0`, or `99` used for "no opinion") are ignored, with a warning (`W011`)
+   that gives the number of times each value appeared. The ballots that give the same rank to
+   several candidates are kept, with an overvote at that rank, and counted in a warning (`W012`).
+
+ - added `rankColumnsByHeader` (boolean, optional, false by default) and `rankHeaderPattern`
+   (string, optional, `rank{}` by default): for the `csv` provider, the rank columns are the
+   columns whose header matches the pattern, where `{}` stands for the number of the rank. The
+   match ignores the case and the surrounding spaces. The choices are ordered by this number,
+   whatever the position of the columns, and the other columns are ignored. The numbers must be
+   1, 2, ... without duplicates or gaps. This option requires a header, with `firstVoteRowIndex`
+   of 2 or more, and `firstVoteColumnIndex` is not used.
+
+ - added `maxInferredCandidates` (number, optional, 100 by default) and `strictInference`
+   (boolean, optional, false by default): when the candidates are not declared and are inferred
+   from the ballots, more than `maxInferredCandidates` candidates, or more candidates than half
+   of the ballots (from 20 candidates), usually means that the choice columns point to a column
+   of free text. This is a warning (`W014`) with the ten most common names, or an error (`E004`)
+   with `strictInference` or the `--strict` flag. The check is turned off with `sanityChecks`.
+
+ - added `timestampColumn` (string or number, optional), `acceptFrom` and `acceptUntil` (strings,
+   optional): for the `csv`, `csv_likert` and `msforms_*` providers, only the ballots submitted
+   in the half-open window from `acceptFrom` (included) to `acceptUntil` (excluded) are counted.
+   The column is either a column index or the name of the column in the header. The bounds and
+   the cells are dates or times in the RFC 3339 format (`2024-11-05T20:00:00-05:00`), where a
+   date alone is midnight and a time without an offset is in UTC. The cells of the spreadsheets
+   may also be date cells. A warning (`W015`) gives the number of dropped ballots of each file,
+   with the earliest and the latest of their timestamps. The ballots whose timestamp cannot be
+   read are kept and counted in the warning, unless they are more than 5% of the rows: the
+   column is then most likely wrong, and this is an error (`E003`). The `csv` provider reads the
+   file with a single thread when the window is set.
+
+ - added `deduplicateIdenticalBallots` (boolean, optional, false by default) and
+   `respondentColumn` (string or number, optional): counts only once the identical ballots of the
+   file, for the forms that accept several submissions of the same person (Google Forms without
+   sign-in). Two ballots are identical if they have the same choices (trimmed, without the empty
+   ranks at the end), the same category, the same count and the same challenged flag, whatever
+   their id and their time of submission. With
+   `respondentColumn` (for the `csv`, `csv_likert`, `msforms_ranking` and `msforms_likert`
+   providers, by index or by name in the header), the ballots must also come from the same respondent, for example the same
+   email address. The first ballot is kept, and a warning (`W020`) gives the number of dropped
+   ballots with the most submitted ones.
+
+ - changed `precinctColumnIndex` (string or number, optional): read by the `ess` provider, which
+   ignored it before. The other providers still ignore it.
+
+ - added `styleColumnIndex` (string or number, optional): the column of the ballot style, for the
+   `ess` provider. See [ess](#ess).
+
+Deviations for the top-level configuration:
+ - added `weights` (object, optional): weights the ballots by voter category, for example
+   `"weights": {"column": "category", "values": {"board": 3, "member": 1}, "default": 1}`. The
+   `column` is the default `categoryColumn` of the file sources whose provider reads the
+   categories. The ballots of a source without this column get the `default` weight, with a
+   warning (`W003`), instead of failing. The count of each ballot is
+   multiplied by the weight of its category. Unknown categories and ballots without a category
+   get the `default` weight (1 if not set), with a warning for each unknown category. A weighted
+   count that does not fit in 64 bits is an error of the ballot (`E003`). The summary contains a
+   `categories` section with the number of ballots, the weight and the weighted votes of each
+   category, counted on the validated ballots.
+
+ - added checks on the paths: a configuration file larger than 10 MB is refused, as is a file
+   source that is the configuration file itself. A file source that is the `summary.json` file
+   of the `outputDirectory` is read with a warning (`W009`), since it is overwritten by the run.
+
+ - changed the handling of a list of candidates that are all `excluded`: the program stops
+   before the first round with an error (`E001`) and a table of the removed candidates, with the
+   option that removed each of them. Earlier, all the votes were undeclared write-ins and the
+   tabulation failed without explanation.
+
+Deviations for Rules:
+ - changed `numberOfWinners`: only `1` is accepted, with the `singleWinnerMajority` mode. Any
+   other number fails the validation of the rules, instead of being ignored, including a number
+   above the number of candidates.
+ - added `continueToCompletion` (boolean, optional): if true, the tabulation continues after the
+   winner is declared until all the other candidates are eliminated. These informational rounds
+   do not change the outcome and are only written in the `v2` summary. An error in one of them
+   (for example no candidate to eliminate) stops them with a warning, and keeps the result.
+ - changed the order of the `random` tiebreak mode: the tied candidates are ordered by the SHA-256
+   digest of the seed, the round and their name (see `ranked_voting::tiebreak::permutation`).
+   This order is different from the reference implementation and from the earlier versions of
+   `timrcv`, which sorted the names alphabetically whatever the seed.
+ - added `legacyRandomTiebreak` (boolean, optional): if true, the `random` tiebreak mode uses
+   the alphabetical order of the earlier versions of `timrcv`, to reproduce older tabulations.
+ - added `notaCandidate` (string, optional): the name of a "None of the above" candidate. It is
+   tabulated like the other candidates, but if it wins, nobody is elected and the status of the
+   summary is `notaPrevailed` (exit code 2). When the candidates are declared, the name must be
+   one of them (`E001`), so that a typo does not turn it into a regular candidate.
+ - added `protectNota` (boolean, optional): if true, the `notaCandidate` is never part of a batch
+   elimination and can only be eliminated on its own. The candidates of such a batch with more
+   votes than the `notaCandidate` are not eliminated either.
+ - added `exhaustRankingsCap` (number, optional): with `exhaustOnDuplicateCandidate`, the largest
+   number of rankings read from a ballot. A ballot that reaches it without a continuing
+   candidate is exhausted. It bounds the work of the ballots with many blanks or undervotes.
+ - added `uwiRound1Reporting` (`current` or `reference`, optional): the first round when some
+   ballots start with undeclared write-ins. With `current` (the default), the first round only
+   transfers the undeclared write-ins and no candidate is elected before the second round. With
+   `reference`, the votes of the undeclared write-ins count in the threshold of the first round,
+   and a candidate that reaches this threshold is elected in the first round, as in the
+   reference implementation.
+ - added `blankCountsAsSkipped` (boolean, optional, true by default): with `false`, only the
+   explicit undervotes (the `undervoteLabel` of the source, or a missing rank) count as skipped
+   ranks for `maxSkippedRanksAllowed`. The blank cells are then ignored: they do not count and do
+   not interrupt a sequence of undervotes.
+ - changed `maxRankingsAllowed`: the rankings after this number are ignored, before the first
+   choice of the ballot is looked for. The earlier versions of `timrcv` read this setting but did
+   not apply it. With `"1"`, the election is a plurality with several rounds: the ballots of the
+   eliminated candidates are exhausted, and the ballots that skip the first ranking have no
+   choice. If no ballot is left, the tabulation fails instead of electing a candidate with no
+   vote.
+ - added `overvoteScope` (`upToNextContinuing` or `wholeBallot`, optional): the rankings read for
+   the overvotes with `overvoteRule` `exhaustImmediately`. With `upToNextContinuing` (the
+   default), the ballot `A, B, overvote` counts for A, then for B: the overvote after the last
+   continuing candidate is never read, as in the reference implementation. With `wholeBallot`,
+   an overvote at any ranking makes the ballot inactive from the first round, as in some older
+   rules, and the number of votes that it removes from the first round is logged. `wholeBallot`
+   fails with the other overvote rules.
 
 Deviations for OutputSettings:
 - removed `generateCdfJson`: feature not supported
+- added `rctabCompatibility` (string, optional): with `"1.3"`, each round of the summary has an
+  `inactiveBallots` block, as in the summaries of RCTab 1.3: the number of ballots that are
+  inactive in this round because they did not rank any continuing candidate
+  (`exhaustedChoices`), reached an overvote (`overvotes`), ranked a candidate again
+  (`repeatedRankings`) or skipped too many rankings (`skippedRankings`). The counts include the
+  ballots discarded before the first round, and the ballots exhausted by the eliminations of the
+  previous rounds. The ballots without any mark are not counted.
+- added `collation` (string, optional): a BCP-47 language tag such as `"sv"` or `"es"`. The
+  candidates with equal votes in the rounds of the `--out-xlsx` workbook and of the
+  `--out-flat-csv` table, the transfers of the flat table and the ballot patterns with equal
+  counts are sorted with the collation of this locale instead of the order of the bytes of the
+  names (in Swedish, `Åsa` sorts after `Zelda`). This option requires the `collation` feature,
+  which is off by default: without it, the names are sorted by their bytes, with a warning. The
+  keys of the JSON summary are always sorted by their bytes.
+- added `percentRounding` (`halfUp`, `truncate` or `halfEven`, optional, `halfUp` by default):
+  how the percentages are rounded to their last decimal, to match the conventions of the
+  official reports. It applies to the `firstChoicePercent` and the `skipThenReturnPercent` of the
+  `v2` summary, the shares of the `--out-xlsx` workbook and the `exhausted_percent` of the
+  `--out-fairvote-row` row. The percentages are computed exactly, so that a share such as
+  12.25% is always rounded as a half.
+- added `aggregateBelow` (number, optional): the candidates who are not winners and whose tally
+  in their last round is below this number are merged into a single `Other (n names)` entry in
+  the outputs, for the privacy rules that require the write-ins with few votes to be reported
+  together. It applies to the tallies, the transfers, the eliminations and the candidates of the
+  summary, and to the `--out-xlsx`, `--out-flat-csv`, `--out-rctab-csv` and `--out-fairvote-row`
+  outputs. The tabulation is unchanged, and the `--reference` summary is compared with the full
+  results. The `--out-partial` rounds and the `--analysis` section are not aggregated.
+- added `undeclaredWriteInDisplayName` (string, optional): the name of the undeclared write-ins
+  in the outputs, instead of `Undeclared Write-ins`, for example `"Candidatos no declarados"`
+  for the official outputs in Spanish. It applies to the same outputs as `aggregateBelow`, after
+  the aggregation. The tabulation is unchanged, and the `--reference` summary is compared with
+  the full results, so that the references in English still match. The name of a candidate
+  (compared as the names of the ballots) and, with `aggregateBelow`, the `Other (n names)` label
+  are refused as an invalid configuration, before the ballots are read. With `aggregateBelow`, a
+  candidate named like this label is refused as well.
 - removed `tabulateByPrecinct`: feature not supported
+- added `summarySchema` (`v1` or `v2`, optional): the format of the JSON summary. `v1` (the default)
+  follows the reference implementation. `v2` adds information specific to `timrcv`, for example
+  the informational rounds flagged with `"informational": true`. It can also be set with
+  the `--summary-schema` flag.
+- added `rankStatistics` to the `v2` summary, for research on ballot design. For each rank position,
+  `lastMark` is the number of votes whose last mark (candidate, write-in or overvote) is at this
+  position, and `skipThenReturn` the number of votes that left this position blank but marked a
+  later one. `skipThenReturnPercent` is the percentage of the votes that did so at least once.
+- added `candidates` to the `v2` summary: the candidates of the tabulation with their `name`,
+  `code`, `excluded` flag and `inferred` flag. A candidate is inferred when no candidates were
+  declared and its name was found in the ballots (or in the header of a `ranks-in-cells` file).
+  In the library, `Builder::inferred` marks the candidates that were found in the ballots.
+- added `firstChoicePercent` to the `v2` summary: the share of each entry of the first round
+  tally, in percent with one decimal (`"33.3"`). The total is the total of the first round tally,
+  as for the threshold: the undeclared write-ins are included, and the ballots exhausted by the
+  validation are not. The log of the first round shows the same percentages.
+- added `eliminationOrder` to the `v2` summary: the eliminated candidates in the order of their
+  elimination, as `{"name": "D", "round": 1}` objects. The candidates of a batch elimination are
+  sorted by ascending tally, then by name. The undeclared write-ins are not included, and nobody
+  is eliminated in the round that elects the winner.
+- added `winnerRankedOn` to the `v2` summary: for each winner, the number of votes that rank
+  this winner at any position, for example `{"A": "6"}`. All the validated ballots are counted,
+  with their rankings before the truncation by `maxRankingsAllowed`. The log ends with the same
+  number for each winner, next to the active ballots of the final round. The library has it in
+  `VotingResult::winner_ranked_on`.
+- added the `--out-fairvote-row <path>` flag: appends one row of aggregate statistics for the
+  election to a CSV file, in the layout of the FairVote "RCV data" template (jurisdiction, date,
+  office, contest, number of candidates and rounds, winner, votes of the winner in the first and
+  final rounds, final round margin, first round votes and exhausted ballots). The header is written
+  when the file is created. The `state` column is always empty, and a warning lists the columns
+  without a value. The exhausted ballots are the ones that become inactive after the first
+  round, from the inactive ballots of each round (see `inactiveBallots`).
+- added the `--out-xlsx <path>` flag: writes the results to an Excel workbook. The `Summary`
+  sheet has the contest, the winners, the threshold and the inactive ballots of the final round
+  as the exhausted ballots, the `Candidates`
+  sheet has the code and the final status of each candidate (with the round of elimination), and
+  there is one `Round <n>` sheet per round with the votes, the percentage and the status of each
+  candidate, and the ballots inactive at the start of the round. The counts are numeric cells. The informational
+  rounds are not included. This output requires the `xlsx` feature, which is on by default.
+- added `status` and `reason` to the summary: `status` is `winnerDeclared`, `noWinner`,
+  `notaPrevailed` or `error`, and `reason` explains it. When the run fails after the output
+  paths are checked (reading the ballots, the tabulation, the other outputs, the comparison with
+  the reference...), a summary with the `error` status, the message of the error and no results
+  is still written, unless the summary was already written. The exit code of `timrcv` is 0 when a winner is declared, 2 when
+  there is no winner and 1 for errors. These fields are not compared with reference summaries
+  that do not contain them.
+- changed the checks of `--reference` and `--out`: the reference summary must exist and be valid
+  JSON, and the directory of the summary (from `--out` or `outputDirectory`) must exist. These
+  are checked before the ballots are read, so that a wrong path fails at once instead of after
+  a long tabulation. The reference is still compared with the summary at the end.
+- added the `--skip-bad-sources` flag: a file source that cannot be opened or parsed is skipped
+  with a warning (`W006`) instead of stopping the program, and the election is tabulated with
+  the other sources. The summary then has the `partial` status (exit code 3) and a
+  `failedSources` list with the `filePath`, the `provider` and the `error` of each skipped
+  source. The comparison with the `--reference` summary is not done for a partial tabulation.
+  The program still fails if no source can be read.
+- added the `--emit-provenance` flag: the `config` section of the summary has a `sources` list
+  with, for each file source that was read, the `file`, the `provider`, the number of ballots in
+  the file (`parsedBallots`), the number of ballots kept by the validation (`ballots`) and their
+  votes with the weights of the voter categories (`weight`). `truncatedBallots` is the number
+  of ballots with marks beyond `maxRankingsAllowed`, which are not counted; each of them is
+  logged with `--verbose`, with its id and the marks as they are in the file. The list is always
+  written with the `v2` schema. It is not written when the ballots are loaded from a snapshot.
+- added the `--out-partial <dir>` flag: each round is written to the directory as soon as it is
+  tabulated, as `round_001.json`, `round_002.json`, ... (in the shape of the rounds of the
+  summary), with a `partial_summary.json` file that has the `config` and all the rounds so far.
+  If the program stops during a long tabulation, the rounds done so far are kept. The files are
+  written under a temporary name and then renamed. They are removed once the tabulation is
+  complete and its summary is written, unless `--keep-partials` is passed. A partial file that
+  cannot be written or removed is a warning: it does not stop the tabulation.
+- added the `--out-flat-csv <path>` flag: writes the rounds to a CSV file as a flat table, for
+  loading into a dataframe. The columns are `round`, `candidate`, `votes`, `status` (`elected`,
+  `eliminated` or `continuing`), `transfer_to` and `transfer_votes`. There is one row per
+  candidate and per round, followed by one row per transfer when the candidate is eliminated
+  (the exhausted votes are a transfer to `exhausted`). The same table is available in the
+  library with `VotingResult::to_flat_rows`.
+- added the `--analysis head-to-head-winner` flag: compares each eliminated candidate with each
+  winner using the rankings of the tabulated ballots. A ballot prefers the candidate that it
+  ranks higher, or the only one of the two that it ranks. The candidates who would beat a winner
+  head-to-head are flagged in a table in the log, and the summary has an `analysis` section with
+  a `headToHeadWinner` list (`candidate`, `eliminatedRound`, `winner`, `candidateVotes`,
+  `winnerVotes` and `beatsWinner`). The candidates that lose in the final round are included.
+- added the `--rule key=value` flag, which can be repeated: sets a rule with its name in the
+  `rules` section of the configuration, for example `--rule overvoteRule=exhaustImmediately
+  --rule exhaustOnDuplicateCandidate=true`. Without `--config`, the rules are set on top of the
+  default rules. With `--config`, they override the rules of the configuration with a warning
+  (`W010`). An unknown name, or a value that is not valid for the rule, stops the program before
+  the ballots are read (`E001`).
+- added the `--out-metrics <path>` flag: writes a JSON file with a `sources` list that has, for
+  each file source, the name of the file (without its directory), the `provider`, the size of the
+  file in `bytes`, the number of `parsedBallots`, the time taken to parse and to validate the
+  ballots (`parseMillis` and `validationMillis`) and the number of `warnings` raised while
+  reading it. Each entry is also logged as one line of JSON (`source_metrics: {...}`) as soon as
+  the source is read, for following the progress of a run with many files.
+- added the `--ledger <path>` flag: after the run, appends one line of JSON to the ledger file,
+  which is created if needed. The entry has the `timestamp` (UTC), the `version` of timrcv, a
+  `rulesFingerprint`, the SHA-256 digest of each input (`inputs`), the number of `ballots` kept
+  by the validation and their `votes`, the `status`, the `winners` and the `output` path of the
+  summary. The previous lines are never rewritten. If the last entry has exactly the same inputs,
+  the run is refused before the tabulation (`E001`), unless `--allow-duplicate-ledger` is passed.
+- added the `--reference-strict` flag: before the comparison with the `--reference` summary, the
+  threshold, the winners (the elected entries of the last round) and the number of rounds are
+  compared one by one. Each difference is logged and listed in the error, for example
+  `threshold "3" instead of "4"` (`E007`), instead of a single text difference of the summaries.
+  Without the flag, these differences still fail the comparison, since the whole summary is
+  compared, including the threshold of the `config` section.
+- added a check of the near misses when the candidates are declared: a name of the ballots that
+  is not a declared candidate but is within two characters of one (for long enough names) is
+  likely a typo. It is counted as a write-in, with a warning (`W013`) that gives its number of
+  cells and the close candidate. Two declared candidates that are this close also get a warning.
+  The names that only differ by a trailing label in parentheses, as `Jane Doe (DEM)` and
+  `Jane Doe (GOP)` in fusion voting, are distinct lines of the ballot: they are only logged, and
+  the log suggests to declare the name if it is missing. A label that differs by one character,
+  as `Jane Doe (DM)`, is still a typo. The names are never changed: the labels in parentheses
+  are always part of the names. The names of a file source are not checked when its
+  `sanityChecks` is false.
+- added custom elimination strategies to the library: the `elimination_strategy` field of
+  `VoteRules` takes an implementation of the `EliminationStrategy` trait, which selects the
+  candidates to eliminate in each round from a `RoundContext` (the round, the running
+  candidates, their tally, the tallies of the previous rounds and the rankings of the ballots
+  that still count). The transfers and the threshold are unchanged. An empty selection, or one
+  with a candidate that is not running, stops the tabulation. The `coombs` example of the
+  library (`cargo run -p ranked_voting --example coombs`) eliminates the candidate ranked last
+  by the most ballots and elects a different winner than the default rules. The strategies are
+  not available from the command line or the configuration files.
+- changed the count column of the CSV files: an empty count cell (or a cell with only spaces)
+  counts the ballot once, instead of stopping the program. The number of ballots of each file
+  with an empty count is logged. A count that is not empty and not an integer is still an error
+  (`E003`). The counts of the Excel files follow the same rules.
+- added the `--strict` flag: a source without declared candidates fails when suspiciously many
+  candidates are inferred from its ballots (see `maxInferredCandidates`), instead of a warning
+  (`W014`).
+- added the `--out-rctab-csv <path>` flag: writes the results in the layout of the summary CSV
+  file of RCTab, to compare the outputs of both tools. The file has the contest information
+  (`Generated By` is timrcv), the contest summary, and a table with three columns for each round
+  (`Round n Votes`, `% of vote` and `transfer`): the `Eliminated` and `Elected` rows, one row for
+  each candidate, the `Active Ballots`, the `Current Round Threshold` (the threshold of each
+  round, in `RoundStats::threshold` in the library), and the inactive ballots
+  by overvotes, skipped rankings, exhausted choices, repeated rankings and in total. The
+  candidates who stay longer come first, by descending votes in their last round. The transfer
+  of a round is the change of the votes in the next round, and the cells of a candidate are
+  empty after its elimination. The ballots without any ranking are not part of the tabulation,
+  and the `Number of Undervotes (No Rankings)` row is left out.
+- added `Builder::add_ballot` to the library, which takes the ballot and stores it without
+  copying it. `Builder::add_vote_2`, which copies the ballot, is deprecated.
+  `Builder::into_ballots` gives the ballots back after the tabulation: the command line no
+  longer copies the ballots to tabulate them.
+- changed the matching of the candidate names: the names of the ballots, of the declared
+  candidates and of `notaCandidate` are normalized once, in the same way, and the whitespace
+  around them is always ignored. A declared name with surrounding spaces now matches the
+  ballots, and the ballots that match a declared candidate are counted under the name of the
+  declaration. In the library, the `name_normalization` field of `VoteRules` may also ignore
+  the case and the runs of whitespace inside the names (`NameNormalization::Fold`), and the
+  inferred candidates whose names only differ by the normalization are merged. The command line
+  always uses the default normalization.
+- changed Ctrl-C during a tabulation: the first Ctrl-C stops the tabulation once the current
+  round is complete, and the rounds completed so far are written to the summary with the
+  `interrupted` status. No other output is written, and the exit code is 130. A second Ctrl-C
+  exits at once. Outside of the tabulation (while the ballots are read, or in the other
+  commands), Ctrl-C stops the program as before. The summary files are now written to
+  `<path>.tmp` first and then renamed, so that a summary is never left half-written; the
+  temporary file is removed if the write fails. In the library, `Builder::cancellation` sets a flag that
+  stops the tabulation between two rounds with `VotingErrors::Interrupted`.
+- changed the reason of the exhausted ballots when several rules fail, to match the reference
+  implementation: the rules are checked rank by rank, and the first failure in the order of the
+  ranks is the reason (at the same rank: the skipped rankings before it, then a repeated
+  candidate, then an overvote). Before, a repeated candidate always came first, then an
+  overvote. The undeclared write-ins are now the same candidate for
+  `exhaustOnDuplicateCandidate`: a ballot that ranks a write-in twice is exhausted when it
+  reaches the second one, as in the reference implementation. The conformance cases of the
+  library test these corner cases round by round.
+- added the templates of the output file names: the paths of `--out`, `--out-flat-csv` and
+  `--out-rctab-csv` may contain the placeholders `{contest}`, `{office}`, `{jurisdiction}` and
+  `{date}` (from `outputSettings`), `{timestamp}` (the start of the run in UTC, as
+  `20241105T210300Z`) and `{winner}` (the winners joined with `_`, or `none`). The characters
+  that file systems refuse, including `/` and `\`, are replaced with `_` in the values. The
+  other braces, such as in `summary_{n}.json`, are part of the file name. The directory of a
+  templated path is checked before the ballots are read, with the values known then, unless it
+  depends on `{winner}`: it is then checked once the path is rendered.
+- changed `Builder::add_vote` of the library to classify the choices in the same way as the
+  command line, with the new `classify_choice` function: the blank and the repeated entries of
+  a group are ignored, so that `[Anna, Anna]` is Anna and not an overvote, `UWI` is always an
+  undeclared write-in, and the labels of `Builder::labels` (undervote, overvote, overvote
+  delimiter, blanks as write-ins) are applied. The command line now also ignores the blank and
+  the repeated entries of a group.
+- changed the order of the transfers of an elimination, in the library and in the summary: by
+  descending count, then by name. Before, the order changed from run to run.
+- added the `--audit-first-choices <csv>` flag: compares the first choices of the validated
+  ballots with an independent export of the totals, before the tabulation. The file has two
+  columns (the candidate and its number of first choices) and an optional header. The computed
+  first choices are the choices at the first rank, with the counts and the weights of the
+  ballots: a blank, an undervote or an overvote at the first rank is not a first choice, and
+  the undeclared write-ins are counted together as `Undeclared Write-ins`, before they are
+  eliminated. A candidate that is missing from the file has no votes in it. The differences are
+  printed as a table, and the program fails (`E007`) if one of them is larger than
+  `--audit-tolerance` (0 by default).
+- added the `--log-top <n>` flag: the log of each round lists the `n` candidates with the most
+  votes and the candidates elected or eliminated in the round, then a line such as
+  `… and 63 others with ≤ 12 votes`. All the candidates are listed by default, and the outputs
+  are not affected. In the library, this is `Builder::log_top`.
+- changed the handling of the excluded candidates (`"excluded": true` in the configuration):
+  they never appear in the tallies of the rounds or in the list of candidates of the result.
+  The summary lists them with their first choices in a top-level `excludedCandidates` array
+  (the `excluded` key is the summary without the challenged ballots), and the first round
+  reports their ballots as the transfers of an elimination, to the next valid choice of each
+  ballot (or `exhausted`). The array is not compared with a `--reference` summary that does not
+  have it. In the library, this is `Builder::exclude`, which
+  fails with `VotingErrors::UnknownCandidate` for a name that is not declared. Before, an
+  excluded candidate that was declared to the library stopped the tabulation.
+- changed the order of the candidates when they are not declared: they are sorted by name, in
+  the library and in the command line, instead of the order of their first appearance in the
+  ballots (the inferred candidates of the next file sources still come after the ones of the
+  first sources). This order breaks the ties with `useCandidateOrder`. The names that only
+  differ by the normalization take the smallest of their forms. The tallies of the rounds in the
+  library are sorted by descending votes, then by name. With these changes, the results do not
+  depend on the order of the ballots.
+- added the `--shuffle-ballots-seed <n>` flag, for testing: the ballots of each source are
+  shuffled with this seed right after they are read, and before they are validated. The results
+  must be the same with any seed.
+- added the `trackRankOrigin` rule (`VoteRules::track_rank_origin` in the library): the votes of
+  each candidate in each round are counted by the rank at which the ballots rank the candidate,
+  for example how many votes of the winner in the final round were first, second or third
+  choices. The ranks are the positions in the ballots, including the skipped ones. The counts
+  are in `VotingResult::rank_origin`, and in the `rankOrigin` section of the `v2` summary, by
+  round and then in the order of the tally. The tabulation is not changed, and the ballots take
+  the same memory without the rule.
+- changed the `csv` reader for the exports of spreadsheets: the empty cells at the end of a
+  ballot are dropped, after the continuation rows are appended, so that they do not count as
+  skipped ranks. The rows whose cells are all empty at the end of the file are skipped, with a
+  single warning `W016` (`empty-rows`) for the file. The other empty rows are blank ballots. A cell with spaces is not empty: a row such as ` ,` is still a
+  blank ballot.
+- documented the precedence of the options of the command line: the flags (`--input-type`,
+  `--choices`, `--excel-worksheet-name`, `--rule`...) override the configuration file for all
+  the file sources, and the configuration file overrides the defaults. All the commands
+  (tabulation, `patterns`, `verify-rounds`, `make-fixture`) resolve their configuration in the
+  same way.
+- added the `--verify-output` flag: the totals of the summary are checked before it is
+  written. In each round, the votes and the inactive ballots (the `inactiveBallots` block, or
+  the exhausted votes of the previous rounds) add up to the ballots of the first round; the
+  transfers of an eliminated candidate add up to its votes, and the next round is the current
+  one with the transfers applied; an elected candidate reaches the threshold, unless it has at
+  least half of the votes of the last two candidates. The transfers of an excluded candidate add
+  up to its first choices. The program fails (`E007`) with the list of the totals that do not
+  reconcile. The tests run these checks on all the local fixtures and on random elections.
+- added the `--overlay <file.json>` flag: a partial configuration that is merged over the
+  configuration, for example to exclude a candidate or to change a rule during a canvass
+  without editing the configuration. The objects are merged key by key, the other values
+  replace the ones of the configuration, and the candidates are matched by name (the new names
+  are added after the candidates of the configuration). The flag can be repeated: the overlays
+  apply in their order, then `--rule` and the other flags. The changes of each overlay are
+  printed, such as `candidates[B].excluded: (none) -> true`. An overlay that is not a JSON
+  object fails with `E001`.
+- added the `serve --stdio` command, for the programs that drive timrcv as a subprocess: each
+  line of the standard input is a JSON-RPC 2.0 request, and each response is a line of the
+  standard output. The methods are `loadConfig {"path": ...}`, `parseSources`, `tabulate`
+  (with optional rule overrides in `{"rules": {...}}`, as with `--rule`), `getRounds` (the
+  rounds of the last tabulation, as in the summary) and `shutdown`. The ballots are kept
+  between the requests, so that a change of the rules does not read the sources again. The
+  errors of timrcv are JSON-RPC errors with the code `-32000 - N` for `EN`, and the stable code
+  and name in `data`. The warnings of a request are in the `warnings` field of its result. A
+  notification (a request without `id`) is handled but gets no response, as in JSON-RPC 2.0.
+- fixed the `msforms_likert_transpose` reader for the cells with several candidates, as in the
+  exports of checkbox grids: the names are split on `;` (or the `overvoteDelimiter` of the
+  source) and are an overvote at the rank of the cell, with a warning `W017`
+  (`multi-name-cells`). Before, the whole cell was read as a single name, usually an undeclared
+  write-in. A trailing `;` after a single name is dropped.
+- added the notes of the rounds (`RoundStats::notes`): the events of a round that the tally
+  does not tell, such as a tiebreak (with the tied candidates in the order of the tiebreak and
+  the eliminated one), a batch elimination (with the number of candidates), a candidate chosen
+  by a custom elimination strategy, the transfer of the undeclared write-ins of the first round
+  and its threshold policy (`uwiRound1Reporting`), and an informational round. With the v2
+  schema, the rounds of the summary have them in a `notes` array of objects, whose `type` is
+  `tiebreak`, `batchElimination`, `forced`, `uwiElimination`, `thresholdPolicy` or
+  `informational`. The rounds without notes have no `notes` key.
+- added the first round in which each winner reaches the threshold of the round
+  (`VotingResult::first_majority_round`, and `firstMajorityRound` in the v2 summaries), as
+  reported for the "mathematical win". It can come before the round that declares the winner
+  (a winner is not declared in a round that resolves a tiebreak), and before the informational
+  rounds of `continueToCompletion`.
+- the declared candidates must have distinct names and distinct codes (the empty codes are
+  ignored): a configuration that declares a name or a code twice fails with `E001`. With the v2
+  schema, the rounds of the summary have a `tallyByCode` map with the votes of the candidates
+  that have a code, keyed by code, when some candidates have a code. The candidates without a
+  code are only in `tally`.
+- two file sources that are the same file (after resolving the relative paths and the symbolic
+  links) with the same options fail with `E001`, since each ballot of the file would be counted
+  twice. With `--allow-duplicate-sources`, the file is read once, with a warning `W018`
+  (`duplicate-source`). The same file with different options, for example two contests of the
+  same export, is read for each source with a warning `W019` (`same-file-sources`).
+- added the `render --from-summary <summary.json>` command, which writes the outputs of an
+  election from its summary when the ballots are not available: the FairVote row
+  (`--out-fairvote-row`), the xlsx report (`--out-xlsx`), the flat CSV (`--out-flat-csv`) and the
+  RCTab CSV (`--out-rctab-csv`). The summary can be a v1 or v2 summary of timrcv or a summary of
+  RCTab. The RCTab CSV and the xlsx report are marked as rendered from the summary, not
+  tabulated; the flat CSV and the FairVote row have no place for the mark. The statistics that
+  need the ballots (the rank positions) are empty, and the threshold of each round is the
+  majority of its tally. A file that is not a summary fails with
+  `E002`.
+- the names of the candidates are in the Unicode normalization form C (NFC): a name written
+  with combining accents (NFD), as in the files written on macOS, matches the same name with
+  precomposed accents, and is displayed with them. All the strings of the JSON summary are
+  written in NFC, and the strings of a reference summary are normalized to NFC before the
+  comparison (`--reference`), so that a reference written on macOS matches.
+- added the metrics of competitiveness (`metrics::competitiveness`): the number of rounds, the
+  effective number of candidates (the inverse of the Herfindahl index of the first choices), the
+  margin of the final round in percent of its votes, whether the winner came from behind (the
+  winner was not leading in the first round), and the exhausted ballots in percent of the votes
+  of the first round. The informational rounds do not count. With the v2 schema, the summary has
+  them in a `competitiveness` object, and they are printed on one line after the tabulation.
+- added the `--export-preflib <path>` flag, which writes the validated ballots in the PrefLib
+  format of the orders with ties (`.toi`). See [Exporting to PrefLib](#exporting-to-preflib).
+- added the `deduplicateIdenticalBallots` and `respondentColumn` options of the file sources,
+  which count the identical submissions of a form only once, with a warning `W020`
+  (`identical-ballots`).
+- added the `--debug-sample-rate <rate>` flag: with `--verbose`, only a share of the ballots
+  (for example `0.01`) is logged during the validation, with their cells and their choices, and
+  during the first round, with the candidate they count for or the reason why they are inactive.
+  The ballots are chosen by the digest of their id (of their position in their file if they
+  have none), the same in both stages, so that the cells, the choices and the decision of a
+  ballot are logged together, and two runs log the same ballots. The library has the same option
+  in `Builder::debug_sample_rate`, with the ids of `Ballot::id`.
+- added the `expectedCandidateCount` option of the `dominion` file sources, to catch the
+  truncated candidate manifests. The candidate ids of the marks that are not in the manifest are
+  all reported in one error (`E004`), instead of an error without details on the first one.
+- without `excel_worksheet_name`, the Excel readers (`msforms_*` and `ess`) choose the worksheet
+  of the workbook among its visible worksheets with cells: the hidden sheets (such as the
+  settings of a form) and the chart sheets are skipped. A workbook with several such worksheets
+  fails with `E002` and the list of its worksheets, instead of stopping the program. The `ess`
+  reader also uses `excel_worksheet_name` now, instead of always reading the first worksheet.
+- added the `overvoteScope` rule (`VoteRules::overvote_scope` in the library), to exhaust the
+  ballots with an overvote at any ranking from the first round, for the parity with the reports
+  of the jurisdictions that read the whole ballot.
+- the library has a snapshot of its public API (`tests/public_api.txt`, checked by
+  `tests/public_api.rs`): the signatures of the public functions and methods with the types of
+  their arguments, the public fields, the variants of the enums and the implemented traits. A
+  change of the public API must update the snapshot (`UPDATE_PUBLIC_API=1 cargo test`), and
+  follow the semantic versioning of the crate. `Vote` is a deprecated alias of `Ballot`, for the
+  code written against the first versions of the library. `nfc`, `SampleRate`,
+  `Builder::debug_sample_rate` and `EliminationStats::sort_transfers` are helpers of timrcv,
+  hidden from the documentation and outside of the stable API. `Ballot`, `VoteRules`,
+  `VotingResult`, `RoundStats` and the other statistics of the results are
+  `#[non_exhaustive]`: a new field is not a breaking change. A ballot is created with
+  `Ballot::new`, a round with `RoundStats::new`, and the rules and the results from their
+  default values.
+- `--export-simple-csv` and `--export-preflib` refuse to write the ranking patterns cast by
+  fewer than 5 votes (`E009`), which may identify the voters. The threshold is set with
+  `--privacy-k`, and the rare patterns are left out with `--privacy-suppress-rare` or written
+  anyway with `--privacy-override` (`W021`). See
+  [Privacy of the ballot exports](#privacy-of-the-ballot-exports).
+- the `ess` provider reads the precinct (`precinctColumnIndex`) and the ballot style (the new
+  `styleColumnIndex`) of the ballots, and the sources of the summary have their totals by
+  precinct and by ballot style. These columns must be before `firstVoteColumnIndex` (`E001`).
+- the rankings that the tabulation can never read are dropped before the first round: the
+  repeated candidates of a ballot with the duplicates skipped (unless `trackRankOrigin` is set),
+  and all the rankings after the first repetition when the duplicates exhaust the ballot. A ballot
+  that ranks the same candidates again and again over many columns is then tabulated with a
+  bounded number of rankings, and with the same results: the overvotes of `wholeBallot` are
+  looked for before the rankings are dropped. `maxRankingsAllowed` remains the limit of the
+  number of rankings read from a ballot, and `exhaustRankingsCap` bounds the blanks and the
+  undervotes with the duplicates exhausted.
+- each entry of `eliminationOrder` has the tally of the candidate in the round of its
+  elimination (`votes`) and its share of the tally of the round (`sharePercent`), and the
+  winners are the last entries, with their votes in the final round and `"elected": true`. In the
+  library, the entries of `VotingResult::elimination_order` are `EliminationEntry` values.
+- added the `undeclaredWriteInDisplayName` output setting, to rename the undeclared write-ins in
+  the outputs. The library exports their name in the tallies as `UNDECLARED_WRITE_INS`.
+- `numberOfWinners` is checked: a configuration that asks for several winners fails instead of
+  electing a single winner.
+- `--export-simple-csv` writes the overvotes and the undervotes with the labels of the file
+  sources (`__OVERVOTE__` and an empty cell by default) instead of `|` and an empty cell, keeps
+  the blanks and the challenged ballots, and writes the configuration that reads the export back
+  next to it (`ballots_config.json`). Only the configuration of a previous export to the same
+  path is overwritten.
+- the `cdf` provider reads only the contest of the `contestId` of the file source, and skips the
+  other contests of the report before building their ballots.
+- the commands of the README and of the quick start are tested with the built binary
+  (`tests/quick_start.rs`), and the second command of the quick start has its missing `\`
+  before `--out`.
+- the cells of the `msforms_likert` inputs that are not labels of the `choices` fail with the
+  unknown labels and their number of cells, instead of a generic error, unless
+  `allowUnknownChoices` is set. The `csv_likert` provider reads the labels of the `choices` when
+  they are declared.
+- added `Builder::into_concurrent`, to add the votes from several threads: each thread adds its
+  votes to its own `BuilderShard`, without any lock, and `ConcurrentBuilder::finish` merges the
+  shards into the builder. The thread safety of `Builder` (`Send` and `Sync`) is documented.
+- the `dominion` provider matches the `Description` and the `ExternalId` of the candidates of the
+  manifest with the names and the codes of the declared candidates, for the manifests that have
+  codes in the `Description`.
+- the paths of all the outputs (`--out`, `--out-xlsx`, `--out-flat-csv`, `--out-rctab-csv`,
+  `--export-simple-csv`, ...) are checked before the tabulation: a path that is a directory or
+  in a missing directory fails with the flag and a suggested fix. A file that cannot be written
+  for lack of permissions or of space fails with the flag, the absolute path and the owner.
+- added the impact of the rules, printed as a table after the tabulation: for `overvoteRule`,
+  `exhaustOnDuplicateCandidate`, `maxSkippedRanksAllowed` and `maxRankingsAllowed`, the votes of
+  the ballots that each rule exhausted, or whose ranks it skipped or truncated. When the
+  repeated rankings of a candidate are skipped, only the ones followed by another ranking are
+  counted: the others do not change how the ballot is read. When a rule affected more votes
+  than the margin of the final round, a note suggests a sensitivity analysis. The v2 summaries
+  have the same table in a `rulesImpact` object (`rules`, `finalRoundMargin` and
+  `exceedsMargin`), with the counts written as strings like the other counts of the summary.
 
  */
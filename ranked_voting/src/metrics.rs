@@ -0,0 +1,129 @@
+/*!
+Standard metrics of the competitiveness of an election.
+
+The metrics are computed from the rounds of a [crate::VotingResult], so that they can be compared
+between contests: the number of rounds, the effective number of candidates, the margin of the
+final round, whether the winner came from behind, and the exhausted ballots. The informational
+rounds (see [crate::VoteRules::continue_to_completion]) do not count: the final round is the one
+that elects the winners.
+*/
+
+use crate::config::{RoundStats, VotingResult};
+
+/// The metrics of [competitiveness].
+#[derive(PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub struct CompetitivenessReport {
+    /// The number of rounds, without the informational rounds.
+    pub rounds: usize,
+    /// The inverse of the Herfindahl index of the first choices: `1 / sum(share^2)` over the
+    /// candidates of the first round. It is the number of candidates of the same size that
+    /// would split the votes as evenly. None if the first round has no votes.
+    pub effective_candidates: Option<f64>,
+    /// The votes of the first round.
+    pub first_round_votes: u64,
+    /// The votes of the final round.
+    pub final_round_votes: u64,
+    /// The difference of votes between the first two candidates of the final round (all its
+    /// votes if a single candidate is left). None if the final round has no candidate.
+    pub final_round_margin: Option<u64>,
+    /// True if the winner had fewer first choices than the leader of the first round. None
+    /// unless there is a single winner.
+    pub come_from_behind: Option<bool>,
+    /// The votes of the first round that are not in the final round: the ballots exhausted
+    /// during the tabulation.
+    pub exhausted_ballots: u64,
+}
+
+fn percent(count: u64, total: u64) -> Option<f64> {
+    if total == 0 {
+        None
+    } else {
+        Some(count as f64 * 100.0 / total as f64)
+    }
+}
+
+impl CompetitivenessReport {
+    /// The margin of the final round, in percent of its votes.
+    pub fn final_round_margin_percent(&self) -> Option<f64> {
+        percent(self.final_round_margin?, self.final_round_votes)
+    }
+
+    /// The exhausted ballots, in percent of the votes of the first round.
+    pub fn exhausted_percent(&self) -> Option<f64> {
+        percent(self.exhausted_ballots, self.first_round_votes)
+    }
+}
+
+fn total(rs: Option<&RoundStats>) -> u64 {
+    rs.map(|rs| rs.tally.iter().map(|(_, c)| c).sum())
+        .unwrap_or(0)
+}
+
+/// The metrics of the competitiveness of the election.
+///
+/// ```
+/// use ranked_voting::{metrics, run_election1, VoteRules};
+///
+/// let ballots = vec![vec!["A"], vec!["A"], vec!["A"], vec!["B"], vec!["B"]];
+/// let result = run_election1(&ballots, &VoteRules::default()).unwrap();
+/// let report = metrics::competitiveness(&result);
+/// assert_eq!(report.rounds, 1);
+/// assert_eq!(report.come_from_behind, Some(false));
+/// assert_eq!(report.final_round_margin_percent(), Some(20.0));
+/// ```
+pub fn competitiveness(result: &VotingResult) -> CompetitivenessReport {
+    let rounds: Vec<&RoundStats> = result
+        .round_stats
+        .iter()
+        .filter(|rs| !rs.informational)
+        .collect();
+    let first_round = rounds.first().copied();
+    let final_round = rounds.last().copied();
+    let first_round_votes = total(first_round);
+    let final_round_votes = total(final_round);
+
+    let effective_candidates = first_round.filter(|_| first_round_votes > 0).map(|rs| {
+        let concentration: f64 = rs
+            .tally
+            .iter()
+            .map(|(_, c)| {
+                let share = *c as f64 / first_round_votes as f64;
+                share * share
+            })
+            .sum();
+        1.0 / concentration
+    });
+    let final_round_margin = final_round.and_then(|rs| {
+        let mut counts: Vec<u64> = rs.tally.iter().map(|(_, c)| *c).collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        match counts.as_slice() {
+            [first, second, ..] => Some(first - second),
+            [first] => Some(*first),
+            [] => None,
+        }
+    });
+    let come_from_behind = match (result.winners.as_deref(), first_round) {
+        (Some([winner]), Some(rs)) => {
+            let votes = |name: &str| {
+                rs.tally
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map_or(0, |(_, c)| *c)
+            };
+            let leader = rs.tally.iter().map(|(_, c)| *c).max().unwrap_or(0);
+            Some(votes(winner) < leader)
+        }
+        _ => None,
+    };
+
+    CompetitivenessReport {
+        rounds: rounds.len(),
+        effective_candidates,
+        first_round_votes,
+        final_round_votes,
+        final_round_margin,
+        come_from_behind,
+        exhausted_ballots: first_round_votes.saturating_sub(final_round_votes),
+    }
+}
@@ -0,0 +1,128 @@
+/*!
+The names of the candidates.
+
+A name is written in many places: in the declaration of the candidates, in each ballot, in the
+"None of the above" rule. A [CandidateName] normalizes a name once, when it is created, and two
+names are equal if their matching keys are equal, whatever the whitespace around them. The
+normalization is set by [crate::VoteRules::name_normalization].
+
+The names are always in the Unicode normalization form C (NFC), for the display and for the
+matching: a name written with combining accents, as in the files written on macOS (`e` followed
+by U+0301), matches and is displayed as the same name with precomposed accents (`é`).
+*/
+
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// How a name is turned into the key that matches the ballots with the candidates.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum NameNormalization {
+    /// The whitespace around the name is ignored.
+    #[default]
+    Trim,
+    /// The whitespace around the name is ignored, the runs of whitespace inside the name count
+    /// as a single space, and the case is ignored: `Anna  SMITH` matches `anna smith`.
+    Fold,
+}
+
+impl NameNormalization {
+    /// The matching key of a name. The key of the default normalization borrows the name when
+    /// it is already in NFC.
+    pub fn key<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            NameNormalization::Trim => nfc(name.trim()),
+            NameNormalization::Fold => Cow::Owned(
+                nfc(name)
+                    .split_whitespace()
+                    .map(|w| w.to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        }
+    }
+}
+
+/// The string in the Unicode normalization form C. The strings that are already in NFC, such as
+/// the ASCII strings, are borrowed.
+///
+/// ```
+/// use ranked_voting::nfc;
+///
+/// assert_eq!(nfc("Jose\u{301}"), "Jos\u{e9}");
+/// assert_eq!(nfc("José"), "José");
+/// ```
+///
+/// A helper of timrcv, outside of the stable API.
+#[doc(hidden)]
+pub fn nfc(s: &str) -> Cow<'_, str> {
+    if is_nfc(s) {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(s.nfc().collect())
+    }
+}
+
+/// The name of a candidate: the form that is displayed, and the key that matches it.
+///
+/// ```
+/// use ranked_voting::{CandidateName, NameNormalization};
+///
+/// let declared = CandidateName::new(" Anna Smith", NameNormalization::Fold);
+/// let on_ballot = CandidateName::new("anna  smith ", NameNormalization::Fold);
+/// assert_eq!(declared, on_ballot);
+/// assert_eq!(declared.as_str(), "Anna Smith");
+/// assert_eq!(on_ballot.key(), "anna smith");
+///
+/// // Written with a combining accent.
+/// let nfd = CandidateName::new("Jose\u{301}", NameNormalization::Trim);
+/// assert_eq!(nfd, CandidateName::new("José", NameNormalization::Trim));
+/// assert_eq!(nfd.as_str(), "José");
+/// ```
+#[derive(Debug, Clone)]
+pub struct CandidateName {
+    display: String,
+    key: String,
+}
+
+impl CandidateName {
+    /// Normalizes a name. The displayed form is the name without the whitespace around it.
+    pub fn new(name: &str, normalization: NameNormalization) -> CandidateName {
+        CandidateName {
+            display: nfc(name.trim()).into_owned(),
+            key: normalization.key(name).into_owned(),
+        }
+    }
+
+    /// The name as it is displayed.
+    pub fn as_str(&self) -> &str {
+        &self.display
+    }
+
+    /// The key that is compared to match the names.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl PartialEq for CandidateName {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for CandidateName {}
+
+impl Hash for CandidateName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state)
+    }
+}
+
+impl fmt::Display for CandidateName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display)
+    }
+}
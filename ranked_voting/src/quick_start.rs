@@ -34,7 +34,8 @@ This will lead you to the online spreadsheet that should look like the following
 
 Download the spreadsheet on our computer in the **Excel format** (xlsx).
 
-Run `timrcv` with the following command (the name of the file may differ for you).
+Run `timrcv` with the following command (the name of the file may differ for you). The file of
+this example is `tests/quick_start/test voting.xlsx` in the repository.
 
 
 ```bash
@@ -76,7 +77,7 @@ Going back to our example, we create an output summary:
 timrcv -i 'test voting.xlsx'  --input-type msforms_likert_transpose \
 --choices "Who do you want to vote for? [First choice]" \
 --choices "Who do you want to vote for? [Choice #2]" \
---choices "Who do you want to vote for? [Choice #3]"
+--choices "Who do you want to vote for? [Choice #3]" \
 --out my_election_results.json
 ```
 
@@ -91,8 +92,8 @@ You should obtain a visualization similar to this one:
 
 It is the end of this quick start. You can explore the following sections:
 - if you are trying to recreate a specific election outcome from official tabulated data,
- you should check the documentation of the `--config` flag. `timrcv` accepts many options in a
- JSON format to control exactly how an election can be run. See the [configuration section](../manual/index.html#configuration).
+  you should check the documentation of the `--config` flag. `timrcv` accepts many options in a
+  JSON format to control exactly how an election can be run. See the [configuration section](../manual/index.html#configuration).
  - if your input is in a different format, check the input documentation page.
 
 
@@ -0,0 +1,120 @@
+/*!
+Custom elimination strategies.
+
+By default, the candidates with the fewest votes are eliminated, one at a time or in batch (see
+[crate::EliminationAlgorithm]). A strategy set in [crate::VoteRules::elimination_strategy]
+selects the eliminated candidates of each round instead, for example to experiment with the
+Coombs method, which eliminates the candidate ranked last by the most ballots. The rest of the
+tabulation is unchanged: the votes of the eliminated candidates are transferred and a candidate
+with a majority wins.
+*/
+
+use std::collections::HashSet;
+use std::fmt::Debug;
+
+use crate::{CandidateId, Choice, RoundId, RoundStatistics, VoteInternal, VotingErrors};
+
+/// Selects the candidates to eliminate in a round.
+pub trait EliminationStrategy: Debug + Send + Sync {
+    /// The candidates to eliminate in the round of the context. The selection must not be empty
+    /// and must only have running candidates, otherwise the tabulation fails with
+    /// [VotingErrors::NoCandidateToEliminate].
+    fn select(&self, ctx: &RoundContext) -> Result<Vec<CandidateId>, VotingErrors>;
+}
+
+// The rules are compared by the identity of their strategy.
+impl PartialEq for dyn EliminationStrategy {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(
+            self as *const dyn EliminationStrategy as *const u8,
+            other as *const dyn EliminationStrategy as *const u8,
+        )
+    }
+}
+
+impl Eq for dyn EliminationStrategy {}
+
+/// The state of the tabulation when the candidates of a round are eliminated.
+pub struct RoundContext<'a> {
+    round: RoundId,
+    candidates: &'a [(String, CandidateId)],
+    tally: Vec<(CandidateId, u64)>,
+    prior_rounds: Vec<Vec<(CandidateId, u64)>>,
+    votes: &'a [VoteInternal],
+}
+
+impl<'a> RoundContext<'a> {
+    pub(crate) fn new(
+        round: RoundId,
+        candidates: &'a [(String, CandidateId)],
+        tally: Vec<(CandidateId, u64)>,
+        prior_rounds: &[RoundStatistics],
+        votes: &'a [VoteInternal],
+    ) -> RoundContext<'a> {
+        RoundContext {
+            round,
+            candidates,
+            tally,
+            prior_rounds: prior_rounds
+                .iter()
+                .map(|rs| {
+                    rs.candidate_stats
+                        .iter()
+                        .map(|(cid, count, _)| (*cid, count.0))
+                        .collect()
+                })
+                .collect(),
+            votes,
+        }
+    }
+
+    /// The current round.
+    pub fn round(&self) -> RoundId {
+        self.round
+    }
+
+    /// The running candidates with their names, in the order of the candidates.
+    pub fn candidates(&self) -> &[(String, CandidateId)] {
+        self.candidates
+    }
+
+    /// The name of a running candidate.
+    pub fn name(&self, cid: CandidateId) -> Option<&str> {
+        self.candidates
+            .iter()
+            .find(|(_, cid2)| *cid2 == cid)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The votes of each running candidate in this round, in the order of the candidates.
+    pub fn tally(&self) -> &[(CandidateId, u64)] {
+        &self.tally
+    }
+
+    /// The tallies of the previous rounds, starting with the first round.
+    pub fn prior_rounds(&self) -> &[Vec<(CandidateId, u64)>] {
+        &self.prior_rounds
+    }
+
+    /// The rankings of the ballots that still count: the running candidates in the order of
+    /// each ballot from its current choice, with the number of votes of the ballot. A candidate
+    /// ranked twice is only kept at its first rank. The rules on overvotes and skipped rankings
+    /// are not applied to the later choices.
+    pub fn rankings(&self) -> Vec<(Vec<CandidateId>, u64)> {
+        let running: HashSet<CandidateId> = self.candidates.iter().map(|(_, cid)| *cid).collect();
+        self.votes
+            .iter()
+            .map(|v| {
+                let mut ranking: Vec<CandidateId> = Vec::new();
+                for choice in v.candidates.choices[v.candidates.head..].iter() {
+                    if let Choice::Filled(cid) = choice {
+                        if running.contains(cid) && !ranking.contains(cid) {
+                            ranking.push(*cid);
+                        }
+                    }
+                }
+                (ranking, v.count.0)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,40 @@
+/*!
+Orderings of the candidates used by the random tiebreak mode.
+
+They are public so that auditors can independently check how a tie was resolved: given the
+seed, the round and the names of the tied candidates, the first candidate of the permutation
+is the one eliminated.
+*/
+
+/// The keyed order of the tied candidates for a round.
+///
+/// Each candidate is ordered by the SHA-256 digest of the seed, the round and its name. The
+/// order cannot be guessed from the names alone without knowing the seed. Two candidates can
+/// only end up in the same position if their names are identical.
+pub fn permutation(seed: u64, round: u32, names: &[String]) -> Vec<String> {
+    let mut keyed: Vec<(String, &String)> = names
+        .iter()
+        .map(|name| (sha256::digest(hash_input(seed, round, name)), name))
+        .collect();
+    keyed.sort();
+    keyed.into_iter().map(|(_, name)| name.clone()).collect()
+}
+
+/// The order used by the earlier versions of this crate: a plain lexicographic sort of the seed,
+/// the round and the name. It only depends on the names of the candidates, since the seed and
+/// the round are the same prefix for all of them.
+///
+/// It is still available to reproduce older tabulations with the `legacy_random_tiebreak`
+/// rule.
+pub fn legacy_permutation(seed: u64, round: u32, names: &[String]) -> Vec<String> {
+    let mut keyed: Vec<(String, &String)> = names
+        .iter()
+        .map(|name| (hash_input(seed, round, name), name))
+        .collect();
+    keyed.sort();
+    keyed.into_iter().map(|(_, name)| name.clone()).collect()
+}
+
+fn hash_input(seed: u64, round: u32, name: &str) -> String {
+    format!("{:020}{:08}{}", seed, round, name)
+}
@@ -0,0 +1,113 @@
+/*!
+Verification of official round data against a tabulation of the ballots.
+
+Auditors sometimes only have the official tallies of some rounds, for example the first round
+as published on election night. [compare_rounds] compares the rounds of a tabulation of the
+ballots with them, and reports the first round where the official tallies differ from the
+computed ones.
+*/
+
+use crate::config::{RoundId, RoundStats};
+
+/// The official and computed tallies of a candidate in a round.
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub struct CandidateDelta {
+    pub name: String,
+    /// The tally in the official round (0 if the candidate is not in this round).
+    pub official: u64,
+    /// The tally in the computed round (0 if the candidate is not in this round).
+    pub computed: u64,
+}
+
+impl CandidateDelta {
+    /// The difference between the computed and the official tallies. It does not overflow,
+    /// whatever the tallies.
+    pub fn delta(&self) -> i128 {
+        self.computed as i128 - self.official as i128
+    }
+}
+
+/// The first round where the official tallies differ from the computed tallies.
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub struct RoundDivergence {
+    pub round: RoundId,
+    /// True if the tabulation ended before this round.
+    pub missing_round: bool,
+    /// The candidates whose tallies differ, in the order of the official round.
+    pub deltas: Vec<CandidateDelta>,
+}
+
+/// The outcome of [compare_rounds].
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub struct VerificationReport {
+    /// The number of official rounds that match the tabulation.
+    pub matching_rounds: usize,
+    /// The number of rounds of the tabulation.
+    pub computed_rounds: usize,
+    /// The first divergence, if any. The rounds after it are not compared.
+    pub divergence: Option<RoundDivergence>,
+}
+
+impl VerificationReport {
+    /// True if all the official rounds match the tabulation.
+    pub fn is_match(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Compares the official rounds with the computed rounds, by round number.
+///
+/// The official rounds do not need to be complete: the computed rounds that are not in the
+/// official data are not compared. The candidates missing from a round have a tally of 0.
+pub fn compare_rounds(computed: &[RoundStats], official: &[RoundStats]) -> VerificationReport {
+    let mut matching_rounds = 0;
+    for off in official.iter() {
+        let comp = computed.iter().find(|rs| rs.round == off.round);
+        let deltas = tally_deltas(comp.map(|rs| rs.tally.as_slice()).unwrap_or(&[]), off);
+        if comp.is_none() || !deltas.is_empty() {
+            return VerificationReport {
+                matching_rounds,
+                computed_rounds: computed.len(),
+                divergence: Some(RoundDivergence {
+                    round: off.round,
+                    missing_round: comp.is_none(),
+                    deltas,
+                }),
+            };
+        }
+        matching_rounds += 1;
+    }
+    VerificationReport {
+        matching_rounds,
+        computed_rounds: computed.len(),
+        divergence: None,
+    }
+}
+
+fn tally_deltas(computed: &[(String, u64)], official: &RoundStats) -> Vec<CandidateDelta> {
+    let lookup = |tally: &[(String, u64)], name: &str| -> u64 {
+        tally
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, c)| *c)
+            .unwrap_or(0)
+    };
+    let mut names: Vec<&String> = official.tally.iter().map(|(n, _)| n).collect();
+    for (name, _) in computed.iter() {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| CandidateDelta {
+            name: name.clone(),
+            official: lookup(&official.tally, name),
+            computed: lookup(computed, name),
+        })
+        .filter(|d| d.official != d.computed)
+        .collect()
+}
@@ -0,0 +1,84 @@
+// The allocations of the tabulation, counted by a global allocator. The allocator is only
+// installed in this test binary, so that the other tests do not pay for it.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use ranked_voting::{Ballot, BallotChoice, Builder, VoteRules};
+
+// Counts the bytes allocated by each thread.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATED: Cell<u64> = const { Cell::new(0) };
+}
+
+fn count_allocation(size: usize) {
+    let _ = ALLOCATED.try_with(|a| a.set(a.get() + size as u64));
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+fn allocated_by(f: impl FnOnce()) -> u64 {
+    let start = ALLOCATED.with(|a| a.get());
+    f();
+    ALLOCATED.with(|a| a.get()) - start
+}
+
+#[test]
+fn tabulation_does_not_copy_ballots() {
+    // Long names: a copy of the ballots would allocate their names again.
+    let name = |k: u64| format!("{}{}", k, "x".repeat(200));
+    let ballots: Vec<Ballot> = (0..10_000)
+        .map(|k| {
+            Ballot::new(
+                vec![
+                    BallotChoice::Candidate(name(k % 3)),
+                    BallotChoice::Candidate(name((k + 1) % 3)),
+                    BallotChoice::Candidate(name((k + 2) % 3)),
+                ],
+                1,
+            )
+        })
+        .collect();
+    let ballots_bytes = allocated_by(|| {
+        let _ = ballots.clone();
+    });
+    // The declared candidates: the names of the ballots are only looked up.
+    let names: Vec<String> = (0..3).map(name).collect();
+    let mut builder = Builder::new(&VoteRules::default())
+        .unwrap()
+        .candidates(&names)
+        .unwrap();
+    for ballot in ballots {
+        builder.add_ballot(ballot).unwrap();
+    }
+    let tabulation_bytes = allocated_by(|| {
+        ranked_voting::run_election(&builder).unwrap();
+    });
+    assert!(
+        tabulation_bytes < ballots_bytes,
+        "{} bytes allocated by the tabulation, {} by a copy of the ballots",
+        tabulation_bytes,
+        ballots_bytes
+    );
+    // The ballots are given back after the tabulation.
+    assert_eq!(builder.into_ballots().len(), 10_000);
+}
@@ -0,0 +1,462 @@
+// The public API of the crate, compared with the snapshot of public_api.txt: the signatures of
+// the public functions and methods, the public fields of the structures, the variants of the
+// enums, the constants, the type aliases and the implemented traits. A change of the snapshot
+// is a change of the public API: it must be deliberate, and follow the semantic versioning of
+// the crate (see the changelog). Run the test with UPDATE_PUBLIC_API=1 to write the snapshot.
+//
+// The items are read from the sources, which follow rustfmt. The private modules are only
+// included through their `pub use` declarations.
+
+use std::collections::BTreeSet;
+
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/public_api.txt");
+
+// The sources of the modules, by name.
+const MODULES: &[(&str, &str)] = &[
+    ("builder", include_str!("../src/builder.rs")),
+    ("choice", include_str!("../src/choice.rs")),
+    ("config", include_str!("../src/config.rs")),
+    ("manual", include_str!("../src/manual.rs")),
+    ("metrics", include_str!("../src/metrics.rs")),
+    ("name", include_str!("../src/name.rs")),
+    ("quick_start", include_str!("../src/quick_start.rs")),
+    ("strategy", include_str!("../src/strategy.rs")),
+    ("tiebreak", include_str!("../src/tiebreak.rs")),
+    ("verify", include_str!("../src/verify.rs")),
+];
+
+// The attributes that are part of the API.
+const KEPT_ATTRIBUTES: [&str; 4] = ["derive(", "non_exhaustive", "doc(hidden)", "deprecated"];
+
+// Removes the comments and the contents of the literals, so that the only braces, brackets and
+// semicolons left are the ones of the code.
+fn strip(src: &str) -> String {
+    let chars: Vec<char> = src.chars().collect();
+    let mut res = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match (c, next) {
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                let mut depth = 0;
+                while i < chars.len() {
+                    if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                        depth += 1;
+                        i += 2;
+                    } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            ('r', Some('"' | '#')) if !chars[..i].last().is_some_and(|p| p.is_alphanumeric()) => {
+                let hashes = chars[i + 1..].iter().take_while(|c| **c == '#').count();
+                i += 2 + hashes;
+                let end: String = std::iter::once('"')
+                    .chain("#".repeat(hashes).chars())
+                    .collect();
+                while i < chars.len() && !chars[i..].iter().collect::<String>().starts_with(&end) {
+                    i += 1;
+                }
+                i += end.len();
+                res.push_str("\"\"");
+            }
+            ('"', _) => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += if chars[i] == '\\' { 2 } else { 1 };
+                }
+                i += 1;
+                res.push_str("\"\"");
+            }
+            // A character, as opposed to a lifetime.
+            ('\'', Some('\\')) => {
+                i += 2;
+                while i < chars.len() && chars[i] != '\'' {
+                    i += 1;
+                }
+                i += 1;
+                res.push_str("' '");
+            }
+            ('\'', Some(_)) if chars.get(i + 2) == Some(&'\'') => {
+                i += 3;
+                res.push_str("' '");
+            }
+            _ => {
+                res.push(c);
+                i += 1;
+            }
+        }
+    }
+    res
+}
+
+// Collapses the whitespace, as the items are written on one line.
+fn one_line(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace("< ", "<")
+        .replace(" )", ")")
+        .replace(",)", ")")
+        .replace(" >", ">")
+        .replace(",>", ">")
+        .replace(" ,", ",")
+        .replace(", }", " }")
+}
+
+// Splits a list at the separators outside of any brackets.
+fn split_top(s: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut part = String::new();
+    let mut prev = ' ';
+    for c in s.chars() {
+        match c {
+            '(' | '[' | '{' | '<' => depth += 1,
+            '>' if prev == '-' => {}
+            ')' | ']' | '}' | '>' => depth -= 1,
+            _ => {}
+        }
+        if c == separator && depth == 0 {
+            parts.push(std::mem::take(&mut part));
+        } else {
+            part.push(c);
+        }
+        prev = c;
+    }
+    parts.push(part);
+    parts
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+// The items of a stripped source: each one ends with a semicolon or with its closing brace.
+fn items(src: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut item = String::new();
+    let mut braces = 0;
+    let mut brackets = 0;
+    for c in src.chars() {
+        item.push(c);
+        match c {
+            '{' => braces += 1,
+            '[' => brackets += 1,
+            ']' => brackets -= 1,
+            '}' => {
+                braces -= 1;
+                if braces == 0 && brackets == 0 {
+                    res.push(std::mem::take(&mut item));
+                }
+            }
+            ';' if braces == 0 && brackets == 0 => res.push(std::mem::take(&mut item)),
+            _ => {}
+        }
+    }
+    res.into_iter()
+        .map(|i| i.trim().to_string())
+        .filter(|i| !i.is_empty())
+        .collect()
+}
+
+// Separates the attributes of an item, and keeps the ones of the API. None if the item is only
+// compiled in the tests.
+fn attributes(item: &str) -> Option<(Vec<String>, &str)> {
+    let mut rest = item.trim_start();
+    let mut kept = Vec::new();
+    while let Some(attr) = rest.strip_prefix("#!").or_else(|| rest.strip_prefix('#')) {
+        let mut depth = 0;
+        let end = attr
+            .char_indices()
+            .find(|(_, c)| {
+                match c {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|(i, _)| i)
+            .unwrap();
+        let content = one_line(&attr[1..end]);
+        if content == "cfg(test)" {
+            return None;
+        }
+        if KEPT_ATTRIBUTES.iter().any(|k| content.starts_with(k)) {
+            kept.push(format!("#[{}]", content));
+        }
+        rest = attr[end + 1..].trim_start();
+    }
+    Some((kept, rest))
+}
+
+// The text of an item before its body, and its body.
+fn header_body(item: &str) -> (String, Option<&str>) {
+    if item.starts_with("use ") || item.starts_with("pub use ") {
+        return (one_line(item.trim_end_matches(';')), None);
+    }
+    match item.find('{') {
+        Some(start) => (
+            one_line(&item[..start]),
+            Some(&item[start + 1..item.rfind('}').unwrap()]),
+        ),
+        None => (one_line(item.trim_end_matches(';')), None),
+    }
+}
+
+// The name of an item from its header, after the keyword.
+fn item_name(header: &str, keyword: &str) -> String {
+    let rest = &header[header.find(keyword).unwrap() + keyword.len()..];
+    rest.trim_start()
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap()
+        .to_string()
+}
+
+// The type of an implementation, and whether it implements a trait.
+fn impl_type(header: &str) -> (String, bool) {
+    let rest = header.strip_prefix("impl").unwrap();
+    // The generic parameters of the implementation.
+    let rest = match rest.strip_prefix('<') {
+        Some(generics) => {
+            let mut depth = 1;
+            let end = generics
+                .char_indices()
+                .find(|(_, c)| {
+                    match c {
+                        '<' => depth += 1,
+                        '>' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .unwrap()
+                .0;
+            &generics[end + 1..]
+        }
+        None => rest,
+    };
+    let (ty, is_trait) = match rest.split_once(" for ") {
+        Some((_, ty)) => (ty, true),
+        None => (rest, false),
+    };
+    let ty = ty.trim().split('<').next().unwrap();
+    (ty.rsplit("::").next().unwrap().to_string(), is_trait)
+}
+
+// A public item, with the name it is exported under.
+struct Entry {
+    name: String,
+    text: String,
+    // The type of an implementation, kept if the type is public.
+    impl_of: Option<String>,
+}
+
+// The public items of a module, with the names of its `pub use` declarations.
+fn module_entries(prefix: &str, src: &str) -> Vec<Entry> {
+    let mut res = Vec::new();
+    for item in items(&strip(src)) {
+        let (attrs, item) = match attributes(&item) {
+            Some(a) => a,
+            None => continue,
+        };
+        let (header, body) = header_body(item);
+        let text = attrs
+            .into_iter()
+            .chain(std::iter::once(header.clone()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        // The items of the modules start with their path.
+        let in_module = |text: &str| match prefix.strip_suffix("::") {
+            Some(module) => format!("{}: {}", module, text),
+            None => text.to_string(),
+        };
+        let entry = |name: String, text: String| Entry {
+            name: format!("{}{}", prefix, name),
+            text: in_module(&text),
+            impl_of: None,
+        };
+        if header.starts_with("impl") {
+            let (ty, is_trait) = impl_type(&header);
+            let mut lines = vec![text.clone()];
+            if !is_trait {
+                for sub in items(body.unwrap_or_default()) {
+                    if let Some((sub_attrs, sub)) = attributes(&sub) {
+                        let (sub_header, _) = header_body(sub);
+                        // The value of a constant is not part of its signature.
+                        let signature = match sub_header.split_once(" = ") {
+                            Some((signature, _)) if sub_header.starts_with("pub const ") => {
+                                signature.to_string()
+                            }
+                            _ => sub_header,
+                        };
+                        if signature.starts_with("pub ") {
+                            let sub_text = sub_attrs.join(" ") + " " + &signature;
+                            lines.push(one_line(&format!("{} {{ {} }}", text, sub_text)));
+                        }
+                    }
+                }
+            }
+            for line in lines {
+                res.push(Entry {
+                    name: String::new(),
+                    text: in_module(&line),
+                    impl_of: Some(ty.clone()),
+                });
+            }
+            continue;
+        }
+        let words: Vec<&str> = header.split_whitespace().collect();
+        match words.as_slice() {
+            ["pub", "use", ..] => {
+                let path = header["pub use ".len()..].trim_start_matches("crate::");
+                let (module, names) = path.split_once("::").unwrap();
+                let names: Option<BTreeSet<&str>> = match names {
+                    "*" => None,
+                    n => Some(
+                        n.trim_start_matches('{')
+                            .trim_end_matches('}')
+                            .split(',')
+                            .map(|n| n.trim())
+                            .filter(|n| !n.is_empty())
+                            .collect(),
+                    ),
+                };
+                let (_, src) = MODULES.iter().find(|(m, _)| *m == module).unwrap();
+                for e in module_entries(prefix, src) {
+                    let exported = |n: &str| {
+                        names
+                            .as_ref()
+                            .is_none_or(|names| names.contains(&n[prefix.len()..]))
+                    };
+                    if e.impl_of.is_some() || exported(&e.name) {
+                        res.push(e);
+                    }
+                }
+            }
+            ["pub", "mod", module] => {
+                res.push(entry(module.to_string(), text));
+                let (_, src) = MODULES.iter().find(|(m, _)| m == module).unwrap();
+                res.extend(module_entries(&format!("{}{}::", prefix, module), src));
+            }
+            ["pub", "struct", ..] => {
+                let name = item_name(&header, "struct");
+                let fields = split_top(body.unwrap_or_default(), ',');
+                let mut public: Vec<String> = Vec::new();
+                for field in fields.iter() {
+                    let (field_attrs, field) = attributes(field).unwrap();
+                    if field.starts_with("pub ") {
+                        public.push(
+                            field_attrs
+                                .into_iter()
+                                .chain([one_line(field)])
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                        );
+                    } else {
+                        public.push("..".to_string());
+                    }
+                }
+                public.dedup();
+                let text = match body {
+                    Some(_) => format!("{} {{ {} }}", text, public.join(", ")),
+                    None => text,
+                };
+                res.push(entry(name, text));
+            }
+            ["pub", "enum", ..] => {
+                let name = item_name(&header, "enum");
+                let variants: Vec<String> = split_top(body.unwrap(), ',')
+                    .iter()
+                    .map(|v| {
+                        let (variant_attrs, v) = attributes(v).unwrap();
+                        variant_attrs
+                            .into_iter()
+                            .chain([one_line(v)])
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect();
+                res.push(entry(
+                    name,
+                    format!("{} {{ {} }}", text, variants.join(", ")),
+                ));
+            }
+            ["pub", "trait", ..] => {
+                let name = item_name(&header, "trait");
+                let fns: Vec<String> = items(body.unwrap())
+                    .iter()
+                    .map(|f| header_body(attributes(f).unwrap().1).0)
+                    .collect();
+                res.push(entry(name, format!("{} {{ {} }}", text, fns.join("; "))));
+            }
+            ["pub", kind @ ("fn" | "const" | "static" | "type"), ..] => {
+                let name = item_name(&header, kind);
+                // The value of a constant is not part of its signature.
+                let text = match text.split_once(" = ") {
+                    Some((signature, _)) if *kind != "type" => signature.to_string(),
+                    _ => text,
+                };
+                res.push(entry(name, text));
+            }
+            [first, ..] if first.starts_with("pub") && *first != "pub(crate)" => {
+                panic!("unexpected public item: {}", header)
+            }
+            _ => {}
+        }
+    }
+    res
+}
+
+fn public_api() -> String {
+    let entries = module_entries("", include_str!("../src/lib.rs"));
+    let types: BTreeSet<&str> = entries
+        .iter()
+        .filter(|e| e.impl_of.is_none())
+        .map(|e| e.name.rsplit("::").next().unwrap())
+        .collect();
+    let lines: BTreeSet<&str> = entries
+        .iter()
+        .filter(|e| {
+            e.impl_of
+                .as_ref()
+                .is_none_or(|t| types.contains(t.as_str()))
+        })
+        .map(|e| e.text.as_str())
+        .collect();
+    lines.into_iter().map(|l| format!("{}\n", l)).collect()
+}
+
+#[test]
+fn public_api_snapshot() {
+    let api = public_api();
+    if std::env::var_os("UPDATE_PUBLIC_API").is_some() {
+        std::fs::write(SNAPSHOT, &api).unwrap();
+    }
+    let snapshot = std::fs::read_to_string(SNAPSHOT).unwrap_or_default();
+    let lines = |s: &str| s.lines().map(|l| l.to_string()).collect::<BTreeSet<_>>();
+    let (api_lines, snapshot_lines) = (lines(&api), lines(&snapshot));
+    let added: Vec<_> = api_lines.difference(&snapshot_lines).collect();
+    let removed: Vec<_> = snapshot_lines.difference(&api_lines).collect();
+    assert!(
+        added.is_empty() && removed.is_empty(),
+        "the public API changed (run with UPDATE_PUBLIC_API=1 if the change is deliberate)\nadded: {:#?}\nremoved: {:#?}",
+        added,
+        removed
+    );
+}
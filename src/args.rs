@@ -1,4 +1,19 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use ranked_voting::SampleRate;
+use std::sync::OnceLock;
+
+use crate::rcv::provider_list;
+
+// The long help of --input-type, with the list of the providers.
+fn input_type_help() -> &'static str {
+    static HELP: OnceLock<String> = OnceLock::new();
+    HELP.get_or_init(|| {
+        format!(
+            "(default csv) The type of the input. The input types are:\n{}",
+            provider_list()
+        )
+    })
+}
 
 /// This is a ranked voting tabulation program.
 #[derive(Parser, Debug, Clone)]
@@ -6,12 +21,21 @@ use clap::Parser;
 pub struct Args {
     /// (file path, optional) The file containing the election data. (Only JSON election descriptions are currently supported)
     /// For more information about the file format, read the documentation at
-    #[clap(short, long, value_parser)]
+    #[clap(short, long, value_parser, global = true)]
     pub config: Option<String>,
     /// (file path) A reference file containing the outcome of an election in JSON format. If provided, timrcv will
     /// check that the tabulated output matches the reference.
     #[clap(short, long, value_parser)]
     pub reference: Option<String>,
+    /// If passed as an argument, the comparison with --reference also checks the threshold, the winners of the
+    /// last round and the number of rounds one by one, and reports each difference.
+    #[clap(long, takes_value = false)]
+    pub reference_strict: bool,
+    /// If passed as an argument, the totals of the summary are checked before it is written: the votes of each
+    /// round and the inactive ballots add up to the ballots of the first round, the transfers of an eliminated
+    /// candidate add up to its votes, and the elected candidates reach the threshold.
+    #[clap(long, takes_value = false)]
+    pub verify_output: bool,
 
     /// (file path, 'stdout' or empty) If specified, the summary of the election will be written in JSON format to the given
     /// location. Setting this option overrides the path that may be specified with the --config option.
@@ -20,25 +44,292 @@ pub struct Args {
 
     /// (file path or empty) If specified, the summary of the election will be written in JSON format to the given
     /// location. Setting this option overrides what may be specified with the --data option.
-    #[clap(short, long, value_parser)]
+    #[clap(short, long, value_parser, global = true)]
     pub input: Option<String>,
 
     /// (default csv) The type of the input. See documentation for all the input types.
-    #[clap(long, value_parser)]
+    #[clap(long, value_parser, global = true, long_help = input_type_help())]
     pub input_type: Option<String>,
 
     /// (list of comma-separated values or not specified) If specified, the list of labels for the ranks. This is useful for
     /// Likert-like styles of inputs in which there is no natural order. It should correspond to the entries in the first row
     /// of the input.
-    #[clap(long, value_parser)]
+    #[clap(long, value_parser, global = true)]
     pub choices: Option<Vec<String>>,
 
     /// (default Form1) When using an Excel file, indicates the name of the worksheet to use.
-    #[clap(long, value_parser)]
+    #[clap(long, value_parser, global = true)]
     pub excel_worksheet_name: Option<String>,
 
+    /// (number, default 1) The number of threads used to read large CSV files in the simple format.
+    /// Files with a quoted field across two ranges of lines are read again with a single thread.
+    #[clap(long, value_parser, global = true)]
+    pub jobs: Option<usize>,
+
+    /// If passed as an argument, the checks that detect misconfigured sources (for example a wrong
+    /// firstVoteColumnIndex, when few choices are declared candidates) are turned off.
+    #[clap(long, takes_value = false, global = true)]
+    pub no_sanity_checks: bool,
+
+    /// If passed as an argument, the csv files have the names of the candidates in the header and the ranks
+    /// in the cells, as with the csv_likert provider (layout 'ranks-in-cells'). The candidates are read from
+    /// the header if they are not declared.
+    #[clap(long, takes_value = false, global = true)]
+    pub likert: bool,
+
+    /// If passed as an argument, the msforms_likert sources fail when the spreadsheet has columns of ranks for
+    /// candidates that are not in the configuration. By default, these columns are ignored with a warning.
+    #[clap(long, takes_value = false, global = true)]
+    pub strict_columns: bool,
+
+    /// If passed as an argument, the cells of the likert sources (msforms_likert, csv_likert with choices) that
+    /// are not labels of the choices are ignored with a warning, for example the ranks added to a form during
+    /// the collection. By default, these cells are an error.
+    #[clap(long, takes_value = false, global = true)]
+    pub allow_unknown_choices: bool,
+
+    /// If passed as an argument, a source without declared candidates fails when suspiciously many candidates
+    /// are inferred from its ballots (see the maxInferredCandidates option). By default, this is a warning.
+    #[clap(long, takes_value = false, global = true)]
+    pub strict: bool,
+
+    /// (list of comma-separated codes, optional) The warnings that stop the program with an error, for
+    /// example 'W001,W003'. The names of the warnings (such as 'short-row') are also accepted. The
+    /// summary is still written before failing.
+    #[clap(long, value_parser, global = true)]
+    pub fail_on: Option<String>,
+
+    /// (key=value, repeatable, optional) Sets a rule of the election, with the name of the rule in the
+    /// configuration, for example '--rule overvoteRule=exhaustImmediately --rule maxSkippedRanksAllowed=1'.
+    /// Without --config, the rules are set on top of the default rules. With --config, they override the
+    /// rules of the configuration, with a warning.
+    #[clap(long, value_parser, global = true)]
+    pub rule: Vec<String>,
+
+    /// (file path, repeatable, optional) A partial configuration in JSON that is merged over the configuration,
+    /// for example to exclude a candidate or to change a rule without editing the configuration. The candidates
+    /// are matched by name. Several overlays apply in their order, and --rule and the other flags apply after
+    /// them. The changes of each overlay are printed.
+    #[clap(long, value_parser, global = true)]
+    pub overlay: Vec<String>,
+
+    /// (file path, optional) If specified, the validated ballots will be written to the given location in the
+    /// simple CSV format (id, count, then one column per choice), with the configuration that reads this file
+    /// back next to it (ballots_config.json for ballots.csv). See the documentation of the csv format.
+    #[clap(long, value_parser)]
+    pub export_simple_csv: Option<String>,
+
+    /// (file path, optional) If specified, the validated ballots are written to the given location in the
+    /// PrefLib format of the orders with ties (.toi), with the same orders counted together. An overvote is a
+    /// tie between the candidates of its rank, and the blanks are omitted.
+    #[clap(long, value_parser)]
+    pub export_preflib: Option<String>,
+
+    /// (number, default 5) The privacy threshold of --export-simple-csv and --export-preflib: the ballots are
+    /// only written if each of their ranking patterns is cast by at least this number of votes, since a rare
+    /// pattern may identify its voter. 0 or 1 turns the check off.
+    #[clap(long, value_parser)]
+    pub privacy_k: Option<u64>,
+
+    /// If passed as an argument, the ranking patterns below the privacy threshold (--privacy-k) are left out of
+    /// --export-simple-csv and --export-preflib, with a warning.
+    #[clap(long, takes_value = false)]
+    pub privacy_suppress_rare: bool,
+
+    /// If passed as an argument, the ranking patterns below the privacy threshold (--privacy-k) are written
+    /// anyway in --export-simple-csv and --export-preflib, with a warning.
+    #[clap(long, takes_value = false)]
+    pub privacy_override: bool,
+
+    /// If passed as an argument, a file source that cannot be opened or parsed is skipped with a warning, and
+    /// the election is tabulated with the other sources. The summary then has the 'partial' status and lists the
+    /// failed sources. The comparison with --reference is not done in this case.
+    #[clap(long, takes_value = false)]
+    pub skip_bad_sources: bool,
+
+    /// If passed as an argument, the config section of the summary lists the number of ballots read from each
+    /// file source (parsed, kept after the validation, and weighted). This is always done with the v2 schema.
+    #[clap(long, takes_value = false)]
+    pub emit_provenance: bool,
+
+    /// (file path, optional) If specified, the validated ballots are written to the given location as a
+    /// binary snapshot, which can be tabulated again with --load-validated.
+    #[clap(long, value_parser)]
+    pub save_validated: Option<String>,
+
+    /// (file path, optional) If specified, the ballots are read from a snapshot written by --save-validated,
+    /// instead of the file sources of the configuration. The configuration must have the same rules.
+    #[clap(long, value_parser)]
+    pub load_validated: Option<String>,
+
+    /// If passed as an argument, a snapshot written with other rules can be loaded with --load-validated.
+    #[clap(long, takes_value = false)]
+    pub allow_rules_mismatch: bool,
+
+    /// (v1 or v2, default v1) The version of the JSON summary. The v1 format is the format of the reference
+    /// implementation. The v2 format includes additional information specific to timrcv.
+    #[clap(long, value_parser)]
+    pub summary_schema: Option<String>,
+
+    /// (include, exclude or both, default include) How to treat the ballots marked as challenged in the input
+    /// (see the challengedColumn option). With 'both', the summary contains the tabulations with and without the
+    /// challenged ballots, and a comparison of the two.
+    #[clap(long, value_parser)]
+    pub with_challenged: Option<String>,
+
+    /// (file path, optional) If specified, a row of aggregate statistics (jurisdiction, date, rounds,
+    /// winner, final round margin, exhausted ballots, ...) is appended to the given CSV file, in the
+    /// layout of the FairVote RCV data template. The file and its header are created if needed.
+    #[clap(long, value_parser, global = true)]
+    pub out_fairvote_row: Option<String>,
+
+    /// (file path, optional) If specified, the results are written to an Excel workbook: a summary sheet
+    /// (winners, threshold, exhausted ballots), a candidates sheet with their final status, and one sheet per
+    /// round with the tallies.
+    #[clap(long, value_parser, global = true)]
+    pub out_xlsx: Option<String>,
+
+    /// (file path, optional) If specified, the rounds are written to a CSV file as a flat table, with the
+    /// columns round, candidate, votes, status, transfer_to and transfer_votes: one row per candidate and per
+    /// round, and one row per transfer of the votes of an eliminated candidate.
+    #[clap(long, value_parser, global = true)]
+    pub out_flat_csv: Option<String>,
+
+    /// (file path, optional) If specified, the results are written to a CSV file in the layout of the summary
+    /// CSV file of RCTab: the contest information, the contest summary, and one row per candidate with the
+    /// votes, the share and the transfer of each round, followed by the active and inactive ballots.
+    #[clap(long, value_parser, global = true)]
+    pub out_rctab_csv: Option<String>,
+
+    /// (file path, optional) If specified, the metrics of the reading of each file source are written to the
+    /// given location in JSON format: the name of the file, the provider, its size, the number of ballots,
+    /// the time taken to parse and to validate them, and the number of warnings. Each entry is also logged
+    /// as one line as soon as the source is read.
+    #[clap(long, value_parser)]
+    pub out_metrics: Option<String>,
+
+    /// (file path, optional) If specified, a line of JSON is appended to this ledger after the run, with the
+    /// time, the version of timrcv, a fingerprint of the rules, the SHA-256 digests of the inputs, the number
+    /// of ballots, the winners and the path of the summary. The file is created if needed. A run with the same
+    /// inputs as the last entry of the ledger is refused, unless --allow-duplicate-ledger is passed.
+    #[clap(long, value_parser)]
+    pub ledger: Option<String>,
+
+    /// If passed as an argument, a run with the same inputs as the last entry of the --ledger is allowed.
+    #[clap(long, takes_value = false)]
+    pub allow_duplicate_ledger: bool,
+
+    /// If passed as an argument, a file source that is listed twice with the same options (the same file,
+    /// after resolving the relative paths and the symbolic links) is read once, with a warning. By default,
+    /// this is an error, since every ballot of the file would be counted twice.
+    #[clap(long, takes_value = false)]
+    pub allow_duplicate_sources: bool,
+
+    /// (file path, optional) If specified, the first choices of the validated ballots are compared with the
+    /// totals of this CSV file before the tabulation, for example an export of the voting machines. The file
+    /// has two columns, the candidate and its number of first choices, and an optional header. The
+    /// differences are printed, and the program fails if one of them is larger than --audit-tolerance.
+    #[clap(long, value_parser)]
+    pub audit_first_choices: Option<String>,
+
+    /// (number, default 0) The largest difference of first choices for a candidate that is accepted by
+    /// --audit-first-choices.
+    #[clap(long, value_parser, default_value_t = 0)]
+    pub audit_tolerance: u64,
+
+    /// (head-to-head-winner, optional) An analysis of the outcome, printed as a table and added to the
+    /// summary in the 'analysis' section. 'head-to-head-winner' compares each eliminated candidate with the
+    /// winners using the rankings of the ballots, and flags the candidates who would beat a winner.
+    #[clap(long, value_parser)]
+    pub analysis: Option<String>,
+
+    /// (directory path, optional) If specified, each round is written to this directory as soon as it is
+    /// tabulated (round_001.json, ...), with a partial_summary.json file that has all the rounds so far. The
+    /// files are removed once the tabulation is complete, unless --keep-partials is passed.
+    #[clap(long, value_parser)]
+    pub out_partial: Option<String>,
+
+    /// If passed as an argument, the files of --out-partial are kept after the tabulation.
+    #[clap(long, takes_value = false)]
+    pub keep_partials: bool,
+
     // Other arguments
     /// If passed as an argument, will turn on verbose logging to the standard output.
-    #[clap(long, takes_value = false)]
+    #[clap(long, takes_value = false, global = true)]
     pub verbose: bool,
+
+    /// (number, optional) The number of candidates listed in the log of each round: the candidates with the
+    /// most votes, plus the candidates elected or eliminated in the round. The other candidates are summarized
+    /// in one line. By default, all the candidates are listed. The outputs are not affected.
+    #[clap(long, value_parser, global = true)]
+    pub log_top: Option<usize>,
+
+    /// (number, optional) For testing: shuffles the ballots of each source with this seed, right after they are
+    /// read. The same seed always gives the same order. The results must not depend on the order of the
+    /// ballots, and this checks it.
+    #[clap(long, value_parser, global = true)]
+    pub shuffle_ballots_seed: Option<u64>,
+
+    /// (number between 0 and 1, optional) With --verbose, only logs the validation of this share of the
+    /// ballots, for example 0.01 for one ballot in a hundred: their cells, their choices, and whether they
+    /// count for a candidate in the first round or why they are inactive. The ballots are chosen by their
+    /// id, so that two runs log the same ballots. By default, all the ballots are logged.
+    #[clap(long, value_parser, global = true)]
+    pub debug_sample_rate: Option<SampleRate>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Prints the most common ranking patterns in the ballots, without tabulating the election.
+    /// The ballots are read with the same options (--config, --input, ...) as for a tabulation.
+    Patterns {
+        /// (default 20) The number of patterns to print.
+        #[clap(long, value_parser, default_value_t = 20)]
+        top: usize,
+        /// If passed as an argument, the patterns are printed in JSON format.
+        #[clap(long, takes_value = false)]
+        json: bool,
+    },
+    /// Tabulates the election and compares each round with official round tallies, for example
+    /// to audit the published results. Prints the first round that differs.
+    VerifyRounds {
+        /// (file path) The official rounds, as a JSON list of
+        /// {"round": 1, "tally": {"candidate": count, ...}} objects, or a summary file.
+        #[clap(long, value_parser)]
+        official: String,
+        /// If passed as an argument, the report is printed in JSON format.
+        #[clap(long, takes_value = false)]
+        json: bool,
+    },
+    /// Writes a self-contained test case from the election of --config: the configuration, a
+    /// sample of the ballots that keeps all the candidates, and the expected summary. The CSV
+    /// files keep their format, the other inputs are converted to the simple CSV format.
+    MakeFixture {
+        /// (directory) The directory of the test case. Its name is also the name of the test.
+        #[clap(long, value_parser)]
+        out_dir: String,
+        /// (default 200) The maximum number of ballots kept from each file source.
+        #[clap(long, value_parser, default_value_t = 200)]
+        max_ballots: usize,
+    },
+    /// Serves JSON-RPC requests, for the programs that drive timrcv as a subprocess. The session
+    /// keeps the ballots between the requests, so that a change of the rules only tabulates them
+    /// again. See the manual for the methods.
+    Serve {
+        /// Reads the requests from the standard input, one per line, and writes the responses to
+        /// the standard output. This is the only transport for now.
+        #[clap(long, takes_value = false)]
+        stdio: bool,
+    },
+    /// Writes the outputs (--out-flat-csv, --out-rctab-csv, --out-fairvote-row, --out-xlsx) from
+    /// the summary of an election instead of its ballots, when only the summary is available.
+    /// The outputs that have room for it are marked as rendered from the summary.
+    Render {
+        /// (file path) The summary, of timrcv or of RCTab.
+        #[clap(long, value_parser)]
+        from_summary: String,
+    },
 }
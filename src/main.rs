@@ -1,22 +1,54 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
 use clap::Parser;
 use env_logger::Env;
+use log::error;
 
 mod args;
 pub mod rcv;
-use crate::args::Args;
+use crate::args::{Args, Command};
+use crate::rcv::error_message;
+use crate::rcv::removed_candidates_table;
+use crate::rcv::resolve_config;
 use crate::rcv::run_election;
+use crate::rcv::run_make_fixture;
+use crate::rcv::run_patterns;
+use crate::rcv::run_render;
+use crate::rcv::run_serve;
+use crate::rcv::run_verify_rounds;
+use crate::rcv::RcvError;
 use crate::rcv::RcvResult;
+use crate::rcv::RunOptions;
+use crate::rcv::SummaryStatus;
 
 const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
 
-fn main() -> RcvResult<()> {
-    println!("This is timrcv version {}", VERSION.unwrap_or("unknown"));
-    println!("This software is not certificed. It may have some bugs. Do not use for official tabulation and certification of an election.");
-    println!("For official needs, consider using RCTab https://www.rcvresources.org/rctab");
+fn main() {
+    if let Err(e) = run() {
+        let code = e.code();
+        error!("[{}] {}: {}", code.code(), code.name(), error_message(&e));
+        if let RcvError::NoContinuingCandidates { removed } = &e {
+            error!("\n{}", removed_candidates_table(removed));
+        }
+        std::process::exit(SummaryStatus::Error.exit_code());
+    }
+}
 
+fn run() -> RcvResult<()> {
     let args = Args::parse();
+    // The standard output of the server only has the responses.
+    let serving = matches!(args.command, Some(Command::Serve { .. }));
+    if !serving {
+        println!("This is timrcv version {}", VERSION.unwrap_or("unknown"));
+        println!("This software is not certificed. It may have some bugs. Do not use for official tabulation and certification of an election.");
+        println!("For official needs, consider using RCTab https://www.rcvresources.org/rctab");
+    }
+
     let env = Env::new().default_filter_or({
-        if args.verbose {
+        if serving {
+            "warn"
+        } else if args.verbose {
             "debug"
         } else {
             "info"
@@ -26,13 +58,46 @@ fn main() -> RcvResult<()> {
 
     let args2 = args.clone();
 
-    run_election(
-        args.config,
+    match args.command {
+        Some(Command::Patterns { top, json }) => {
+            return run_patterns(args.config, args.input, top, json, Some(args2));
+        }
+        Some(Command::VerifyRounds { official, json }) => {
+            if !run_verify_rounds(args.config, args.input, official, json, Some(args2))? {
+                std::process::exit(2);
+            }
+            return Ok(());
+        }
+        Some(Command::MakeFixture {
+            out_dir,
+            max_ballots,
+        }) => {
+            return run_make_fixture(args.config, out_dir, max_ballots, Some(args2));
+        }
+        Some(Command::Render { from_summary }) => {
+            return run_render(from_summary, Some(args2));
+        }
+        Some(Command::Serve { stdio }) => {
+            return run_serve(stdio);
+        }
+        None => {}
+    }
+
+    let args_o = Some(args2);
+    let config = resolve_config(&args.config, &args.input, &args_o)?;
+    let mut options = RunOptions::from_args(&args_o)?;
+    // The flag of Ctrl-C, only handled during the tabulation.
+    options.interrupt = Some(Arc::new(AtomicBool::new(false)));
+    let status = run_election(
+        config,
+        &options,
+        &args.config,
         args.reference,
-        args.input,
         args.out,
         false,
-        Some(args2),
-    )
-    .map(|_| ())
+    )?;
+    if status != SummaryStatus::WinnerDeclared {
+        std::process::exit(status.exit_code());
+    }
+    Ok(())
 }
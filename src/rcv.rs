@@ -1,10 +1,14 @@
-use log::{debug, info, warn};
+use clap::Parser;
+use log::{debug, info, log_enabled, warn, Level};
 
 use ranked_voting::*;
 use snafu::{prelude::*, ErrorCompat, ResultExt, Snafu};
 
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 
 use calamine::{open_workbook, Reader, Xlsx};
 
@@ -12,36 +16,138 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serde_json::Map as JSMap;
 use serde_json::Value as JSValue;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use text_diff::print_diff;
 
+mod aggregate;
+pub mod analysis;
+mod audit;
+pub mod codes;
+pub mod collation;
 mod config_reader;
+mod dedup;
+pub mod fairvote;
+mod fixture;
+pub mod interrupt;
 pub mod io_cdf;
 pub mod io_common;
 pub mod io_csv;
 pub mod io_dominion;
 mod io_ess;
 mod io_msforms;
+#[cfg(feature = "parquet")]
+mod io_parquet;
+mod ledger;
+mod metrics;
+mod output_name;
+mod output_path;
+mod overlay;
+mod partial;
+pub mod patterns;
+mod preflib;
+mod privacy;
+mod rctab_csv;
+mod reconcile;
+mod render;
+mod rules_impact;
+mod run_options;
+mod sanity;
+mod serve;
+mod snapshot;
+mod summary;
+mod time_window;
+pub mod verify_rounds;
+#[cfg(feature = "xlsx")]
+mod xlsx;
 
 use crate::args::Args;
+use crate::rcv::codes::WarningCode;
+use crate::rcv::collation::NameOrder;
 use crate::rcv::config_reader::*;
+pub use crate::rcv::run_options::RunOptions;
+use crate::rcv::summary::Summary;
 
 #[derive(Debug, Snafu)]
 pub enum RcvError {
     // General
-    #[snafu(display(""))]
+    #[snafu(display("cannot read a file source (the paths are relative to {root_path})"))]
     OpeningFile {
         source: Box<RcvError>,
         root_path: String,
     },
-    #[snafu(display(""))]
+    #[snafu(display("no input: pass a configuration file (--config) or an input file (--input)"))]
     MissingInput {},
-    #[snafu(display(""))]
+    #[snafu(display("unknown input format {format:?}"))]
     UnknownFormat { format: String },
-    #[snafu(display(""))]
+    #[snafu(display("cannot parse line {lineno} at column {col}"))]
     LineParse { lineno: usize, col: usize },
-    #[snafu(display(""))]
-    MissingMandatoryCandidates {},
+    #[snafu(display("provider '{provider}' requires the candidates list because {reason}"))]
+    MissingMandatoryCandidates { provider: String, reason: String },
+    #[snafu(display(
+        "the configuration declares an empty list of candidates for provider '{provider}'"
+    ))]
+    EmptyCandidates { provider: String },
+    #[snafu(display(
+        "randomSeed must be a non-negative integer that fits in 64 bits, found {value}"
+    ))]
+    InvalidRandomSeed { value: String },
+    #[snafu(display("the random tiebreak mode requires the randomSeed option"))]
+    MissingRandomSeed {},
+    #[snafu(display("column indices start at 1 (or the letter A)"))]
+    ColumnIndexZero {},
+    #[snafu(display(
+        "invalid column {value:?}, the columns are numbers starting at 1 or letters from A to ZZZ"
+    ))]
+    InvalidColumnLetter { value: String },
+    #[snafu(display("provider '{provider}' does not support the challengedColumn option"))]
+    ChallengedNotSupported { provider: String },
+    #[snafu(display("provider '{provider}' does not support the weights by category"))]
+    CategoriesNotSupported { provider: String },
+    #[snafu(display(
+        "row {lineno}, column {col}: cannot read {content:?} as an integer: {reason}"
+    ))]
+    InvalidNumber {
+        lineno: usize,
+        col: usize,
+        content: String,
+        reason: String,
+    },
+    #[snafu(display(
+        "ballot {id}: the count {count} with the weight {weight} of its category is too large"
+    ))]
+    WeightOverflow { id: String, count: u64, weight: u64 },
+    #[snafu(display("the total count of {what} is too large"))]
+    CountOverflow { what: String },
+    #[snafu(display("the category column {column} is not in the header row"))]
+    CategoryColumnNotFound { column: String },
+    #[snafu(display("the timestamp column {column} is not in the header row"))]
+    TimestampColumnNotFound { column: String },
+    #[snafu(display("the respondent column {column} is not in the header row"))]
+    RespondentColumnNotFound { column: String },
+    #[snafu(display(
+        "{num_unparseable} of the {num_rows} timestamps of {path} cannot be read, such as {content:?} at row {lineno}. Check timestampColumn"
+    ))]
+    UnparseableTimestamps {
+        path: String,
+        num_unparseable: u64,
+        num_rows: u64,
+        lineno: usize,
+        content: String,
+    },
+    #[snafu(display("invalid time window: {reason}"))]
+    InvalidTimeWindow { reason: String },
+    #[snafu(display("provider '{provider}' does not support the timestampColumn option"))]
+    TimestampsNotSupported { provider: String },
+    #[snafu(display("provider '{provider}' does not support the respondentColumn option"))]
+    RespondentsNotSupported { provider: String },
+    #[snafu(display(
+        "provider '{provider}' does not support the expectedCandidateCount option, only the dominion provider has a candidate manifest"
+    ))]
+    ExpectedCandidateCountNotSupported { provider: String },
+    #[snafu(display(
+        "provider '{provider}' does not support the styleColumnIndex option, only the ess provider has ballot styles"
+    ))]
+    StylesNotSupported { provider: String },
 
     // Excel
     #[snafu(display("Error opening file {path}"))]
@@ -49,67 +155,443 @@ pub enum RcvError {
         source: calamine::XlsxError,
         path: String,
     },
-    #[snafu(display(""))]
+    #[snafu(display(
+        "the workbook has no worksheet with cells, or a row is shorter than its header"
+    ))]
     EmptyExcel {},
-    #[snafu(display(""))]
+    #[snafu(display(
+        "{option} is the column {column}, which is not before firstVoteColumnIndex {first}: the precinct and the ballot style must be before the votes"
+    ))]
+    MetadataColumnAfterVotes {
+        option: String,
+        column: usize,
+        first: usize,
+    },
+    #[snafu(display("Worksheet {worksheet_name} not found"))]
+    ExcelMissingWorksheet { worksheet_name: String },
+    #[snafu(display(
+        "cannot choose the worksheet of {path} among {worksheets}: select it with --excel-worksheet-name"
+    ))]
+    ExcelAmbiguousWorksheet { path: String, worksheets: String },
+    #[snafu(display("unexpected type of the cell {content:?} at line {lineno}"))]
     ExcelWrongCellType { lineno: u64, content: String },
-    #[snafu(display(""))]
+    #[snafu(display("the candidate {candidate_name:?} is not in the header of the worksheet"))]
     ExcelCannotFindCandidateInHeader { candidate_name: String },
+    #[snafu(display(
+        "candidates not found in the header: {missing}. Unmatched header columns: {unmatched}"
+    ))]
+    CandidatesNotInHeader { missing: String, unmatched: String },
+    #[snafu(display(
+        "the header columns {first} and {second} both give the candidate name '{name}'"
+    ))]
+    DuplicateHeaderName {
+        name: String,
+        first: String,
+        second: String,
+    },
+    #[snafu(display(
+        "the columns {columns} of {path} contain ranks but are not declared candidates. Declare the candidates or remove --strict-columns to ignore these columns"
+    ))]
+    ExtraCandidateColumns { path: String, columns: String },
+    #[snafu(display(
+        "only {percent}% of the {scope} are declared candidates with firstVoteColumnIndex {column}. The most common other choices are {unmatched}.{suggestion} Use --no-sanity-checks to tabulate anyway"
+    ))]
+    CandidateHitRate {
+        scope: String,
+        percent: String,
+        column: String,
+        unmatched: String,
+        suggestion: String,
+    },
+    #[snafu(display(
+        "{num_candidates} candidates were inferred from the {votes} ballots of {path}. The most common are {common}. Declare the candidates or fix the column indices (firstVoteColumnIndex), or remove --strict to tabulate anyway"
+    ))]
+    TooManyCandidates {
+        path: String,
+        num_candidates: usize,
+        votes: u64,
+        common: String,
+    },
+    #[snafu(display("invalid {option} '{pattern}': {reason}"))]
+    InvalidHeaderPattern {
+        option: String,
+        pattern: String,
+        reason: String,
+    },
+    #[snafu(display(
+        "rankColumnsByHeader requires a header: firstVoteRowIndex must be at least 2"
+    ))]
+    RankColumnsWithoutHeader {},
+    #[snafu(display("the rank columns of {path} {reason}"))]
+    RankHeaders { path: String, reason: String },
+
+    // Parquet
+    #[snafu(display(
+        "provider '{provider}' requires timrcv to be built with the '{feature}' feature"
+    ))]
+    FeatureNotEnabled { provider: String, feature: String },
+    #[snafu(display(
+        "option '{option}' requires timrcv to be built with the '{feature}' feature"
+    ))]
+    OptionNotEnabled { option: String, feature: String },
+    #[snafu(display("Error opening file {path}"))]
+    ParquetOpen {
+        source: std::io::Error,
+        path: String,
+    },
+    #[cfg(feature = "parquet")]
+    #[snafu(display("Error reading Parquet file {path}"))]
+    ParquetRead {
+        source: parquet::errors::ParquetError,
+        path: String,
+    },
+    #[cfg(feature = "parquet")]
+    #[snafu(display("Error reading the record batches of {path}"))]
+    ArrowRead {
+        source: arrow_schema::ArrowError,
+        path: String,
+    },
+    #[snafu(display(
+        "the parquet provider requires either rankingsColumn, or rankColumnPrefix and rankColumnCount"
+    ))]
+    ParquetRankColumns {},
+    #[snafu(display("column {column} not found"))]
+    ParquetMissingColumn { column: String },
+    #[snafu(display("column {column} has the unsupported type {data_type}"))]
+    ParquetWrongType { column: String, data_type: String },
 
     // CSV
-    #[snafu(display(""))]
+    #[snafu(display("cannot open the CSV file"))]
     CsvOpenError { source: csv::Error },
-    #[snafu(display(""))]
+    #[snafu(display("cannot read a line of the CSV file"))]
     CsvLineParse { source: csv::Error },
-    #[snafu(display(""))]
+    #[snafu(display("cannot read the line {lineno} of the CSV file"))]
+    CsvChunkLineParse { lineno: usize, source: csv::Error },
+    #[snafu(display(
+        "line {lineno} has {len} columns, while the lines before it have {expected} columns"
+    ))]
+    CsvRowLength {
+        lineno: usize,
+        len: usize,
+        expected: usize,
+    },
+    #[snafu(display("line {lineno} has fewer columns than the layout of the file source"))]
     CsvLineToShort { lineno: usize },
-    #[snafu(display(""))]
+    #[snafu(display("the CSV file is empty"))]
     CsvEmpty {},
+    #[snafu(display(
+        "input file{} {} contain{} no ballots",
+        if paths.len() > 1 { "s" } else { "" },
+        paths.join(", "),
+        if paths.len() > 1 { "" } else { "s" }
+    ))]
+    NoBallots { paths: Vec<String> },
+    #[snafu(display("the configuration has no cvrFileSources"))]
+    NoFileSources {},
+    #[snafu(display("continuationRows requires the idColumnIndex option"))]
+    ContinuationRowsWithoutId {},
+    #[snafu(display(
+        "row {lineno}: the continuation row has the count {count}, but the ballot has the count {expected}"
+    ))]
+    ContinuationCount {
+        lineno: usize,
+        count: u64,
+        expected: u64,
+    },
+    #[snafu(display("no candidates found in input {path}"))]
+    NoCandidatesInInput { path: String },
+    #[snafu(display("Error writing CSV file {path}"))]
+    CsvWrite { source: csv::Error, path: String },
+
+    // Snapshots
+    #[snafu(display("Error opening snapshot {path}"))]
+    SnapshotOpen {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("Error writing snapshot {path}"))]
+    SnapshotWrite {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("Error writing the metrics {path}"))]
+    MetricsWrite {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("Error writing the ledger {path}"))]
+    LedgerWrite {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("Error reading the ledger {path}"))]
+    LedgerRead {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("the last line of the ledger {path} is not valid JSON"))]
+    LedgerParse {
+        source: serde_json::Error,
+        path: String,
+    },
+    #[snafu(display(
+        "the last entry of the ledger {path} ({timestamp}) has the same inputs, use --allow-duplicate-ledger to run again"
+    ))]
+    LedgerDuplicate { path: String, timestamp: String },
+    #[snafu(display("Error writing the partial results {path}"))]
+    PartialWrite {
+        source: std::io::Error,
+        path: String,
+    },
+    #[cfg(feature = "xlsx")]
+    #[snafu(display("Error writing the workbook {path}"))]
+    XlsxWrite {
+        source: zip::result::ZipError,
+        path: String,
+    },
+    #[snafu(display("invalid snapshot {path}: {reason}"))]
+    InvalidSnapshot { path: String, reason: String },
+    #[snafu(display(
+        "the snapshot {path} was written with other rules, use --allow-rules-mismatch to load it anyway"
+    ))]
+    SnapshotRulesMismatch { path: String },
 
     // Format issues
-    #[snafu(display(""))]
+    #[snafu(display("the CDF report has no election, or a selection of an unknown candidate"))]
     CdfParsingJson {},
-    #[snafu(display(""))]
+    #[snafu(display(
+        "the CDF report {path} has no contest {contest_id}, its contests are: {contests}"
+    ))]
+    CdfMissingContest {
+        path: String,
+        contest_id: String,
+        contests: String,
+    },
+    #[snafu(display("the Dominion export is not valid JSON"))]
     DominionParsingJson {},
-    #[snafu(display(""))]
-    DominionMissingCandidateId { candidate_name: String },
-    #[snafu(display(""))]
+    #[snafu(display(
+        "the marks of {path} have candidate ids that are not in the candidate manifest: {ids}"
+    ))]
+    DominionMissingCandidateId { path: String, ids: String },
+    #[snafu(display(
+        "the candidate manifest of {path} has {found} candidates instead of the {expected} of expectedCandidateCount, it may be truncated. Candidates found: {names}"
+    ))]
+    CandidateCountMismatch {
+        path: String,
+        expected: usize,
+        found: usize,
+        names: String,
+    },
+    #[snafu(display("a candidate id of the Dominion export is not a number"))]
     DominionParsingCandidateId { source: std::num::ParseIntError },
-    #[snafu(display(""))]
+    #[snafu(display("cannot open the JSON file {path}"))]
     OpeningJson {
         source: std::io::Error,
         path: String,
     },
-    #[snafu(display(""))]
+    #[snafu(display("invalid JSON"))]
     ParsingJson { source: serde_json::Error },
 
-    #[snafu(display(""))]
+    #[snafu(display("the file source has no choices: set its choices, or pass --choices"))]
     MissingChoices {},
+    #[snafu(display(
+        "{path}: some cells are not among the {num_choices} choices of the configuration: {labels}. Add them to the choices, or pass --allow-unknown-choices to ignore them"
+    ))]
+    UnknownChoiceLabels {
+        path: String,
+        num_choices: usize,
+        labels: String,
+    },
 
-    #[snafu(display(""))]
+    #[snafu(display("expected a number, or a column letter for a column index"))]
     ParsingJsonNumber {},
-    #[snafu(display(""))]
+    #[snafu(display("cannot find the directory of the configuration file"))]
     MissingParentDir {},
 
     #[snafu(display("ID may not be less than 10, but it was {id}"))]
     InvalidId { id: u16 },
 
-    #[snafu(display(""))]
+    #[snafu(display("cannot open the configuration file"))]
     ConfigOpeningJson { source: std::io::Error },
+    #[snafu(display(
+        "the configuration file {path} has {size} bytes, the limit is {limit} bytes"
+    ))]
+    ConfigTooLarge { path: String, size: u64, limit: u64 },
+    #[snafu(display("invalid overlay {path}: {reason}"))]
+    InvalidOverlay { path: String, reason: String },
+    #[snafu(display("the file source {file_path} is the configuration file itself"))]
+    SourceIsConfig { file_path: String },
+    #[snafu(display(
+        "invalid collation '{tag}': expected a BCP-47 language tag such as 'sv' or 'es-MX'"
+    ))]
+    InvalidCollation { tag: String },
+    #[snafu(display(
+        "no candidate is left for the tabulation: the {} declared candidates are removed",
+        removed.len()
+    ))]
+    NoContinuingCandidates { removed: Vec<RemovedCandidate> },
+    #[snafu(display("the candidate {name:?} is declared twice"))]
+    DuplicateCandidateName { name: String },
+    #[snafu(display(
+        "notaCandidate: {name:?} is not a declared candidate (the candidates are {candidates})"
+    ))]
+    UnknownNotaCandidate { name: String, candidates: String },
+    #[snafu(display("{setting}: {name:?} {reason}"))]
+    OutputNameCollision {
+        setting: String,
+        name: String,
+        reason: String,
+    },
+    #[snafu(display("the candidates {first:?} and {second:?} have the same code {code:?}"))]
+    DuplicateCandidateCode {
+        code: String,
+        first: String,
+        second: String,
+    },
+    #[snafu(display(
+        "the file sources {first} and {second} are the same file with the same options: its ballots would be counted twice (--allow-duplicate-sources reads it once)"
+    ))]
+    DuplicateFileSource { first: String, second: String },
+
+    #[snafu(display("invalid official rounds in {path}: {reason}"))]
+    OfficialRounds { path: String, reason: String },
+    #[snafu(display("invalid summary {path}: {reason}"))]
+    InvalidSummary { path: String, reason: String },
+    #[snafu(display("unknown warning code '{code}' in --fail-on, the known codes are {known}"))]
+    UnknownWarningCode { code: String, known: String },
+    #[snafu(display("failing because of the warnings {codes} (--fail-on)"))]
+    FailedOnWarnings { codes: String },
 
     // Reference errors
-    #[snafu(display(""))]
+    #[snafu(display("cannot read the reference summary"))]
     ReferenceOpeningFile { source: Box<RcvError> },
+    #[snafu(display("cannot open the reference summary {path}"))]
+    ReferenceOpen {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("the reference summary {path} is not a file"))]
+    ReferenceNotAFile { path: String },
+    #[snafu(display("the reference summary {path} is not valid JSON"))]
+    ReferenceParse {
+        source: serde_json::Error,
+        path: String,
+    },
+    #[snafu(display("unknown numberLocale {value:?}, the locales are: plain, en, eu"))]
+    UnknownNumberLocale { value: String },
+    #[snafu(display("--rule {arg} is not of the form key=value"))]
+    RuleArgument { arg: String },
+    #[snafu(display("unknown rule {key} in --rule, the rules are: {known}"))]
+    UnknownRule { key: String, known: String },
+    #[snafu(display("invalid value {value:?} for the rule {key}: {reason}"))]
+    InvalidRuleValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+    #[snafu(display(
+        "the directory {parent} of {flag} {path} does not exist, create it first (mkdir -p {parent})"
+    ))]
+    OutputDirMissing {
+        flag: String,
+        path: String,
+        parent: String,
+    },
+    #[snafu(display("{flag} {path} is a directory, append a file name, for example {example}"))]
+    OutputIsDirectory {
+        flag: String,
+        path: String,
+        example: String,
+    },
+    #[snafu(display("difference detected between calculated summary and reference summary"))]
+    ReferenceMismatch {},
+    #[snafu(display("the summary differs from the reference summary: {}", mismatches.join(", ")))]
+    ReferenceStrictMismatch { mismatches: Vec<String> },
+    #[snafu(display("the totals of the summary do not reconcile: {}", mismatches.join("; ")))]
+    OutputUnreconciled { mismatches: Vec<String> },
+
+    // Audit errors
+    #[snafu(display("cannot read the audit file {path}"))]
+    AuditOpen { source: csv::Error, path: String },
+    #[snafu(display("invalid audit file {path}, line {lineno}: {reason}"))]
+    AuditParse {
+        path: String,
+        lineno: usize,
+        reason: String,
+    },
+    #[snafu(display(
+        "the first choices differ from the audit file {path} by more than {tolerance} votes: {}",
+        mismatches.join("; ")
+    ))]
+    AuditMismatch {
+        path: String,
+        tolerance: u64,
+        mismatches: Vec<String>,
+    },
+
+    #[snafu(display("cannot read the requests or write the responses of the server"))]
+    ServeIo { source: std::io::Error },
+    #[snafu(display("the server needs a transport: only --stdio is supported"))]
+    ServeTransport {},
+
+    #[snafu(display("cannot write {flag} {path} (owned by {owner}): {reason}"))]
+    OutputPermissionDenied {
+        flag: String,
+        path: String,
+        owner: String,
+        reason: String,
+    },
+    #[snafu(display("the disk is full, cannot write {flag} {path}: {reason}"))]
+    OutputDiskFull {
+        flag: String,
+        path: String,
+        reason: String,
+    },
+
+    #[snafu(display("cannot write the PrefLib export {path}"))]
+    PreflibWrite {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("cannot write the configuration of the export {path}"))]
+    ExportConfigWrite {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display(
+        "the configuration of the export {path} already exists, and it is the configuration of the election or it was not written by a previous export: remove it, or choose another --export-simple-csv path"
+    ))]
+    ExportConfigExists { path: String },
+    #[snafu(display(
+        "--export-preflib cannot be used with --load-validated: the snapshot does not have the candidates of the overvotes"
+    ))]
+    PreflibFromSnapshot {},
+    #[snafu(display(
+        "verify-rounds cannot be used with --with-challenged both: the official rounds are compared with a single tabulation, use include or exclude"
+    ))]
+    VerifyRoundsChallengedBoth {},
+    #[snafu(display(
+        "{output} would have {rare} ranking patterns cast by fewer than {k} votes (the smallest by {smallest}), which may identify the voters: pass --privacy-suppress-rare to leave them out, or --privacy-override to write them anyway"
+    ))]
+    PrivacyThreshold {
+        output: String,
+        rare: usize,
+        smallest: u64,
+        k: u64,
+    },
 
     // Summary errors
-    #[snafu(display(""))]
+    #[snafu(display("cannot write {path}"))]
     SummaryWrite {
         source: std::io::Error,
         path: String,
     },
 
-    #[snafu(display(""))]
+    #[snafu(display("{}", voting_error_text(source)))]
     RvVoting { source: VotingErrors },
+    // The rounds are the ones completed before the interruption.
+    #[snafu(display("the tabulation was interrupted after {} rounds", rounds.len()))]
+    Interrupted { rounds: Vec<RoundStats> },
 
     #[snafu(whatever, display("{message}"))]
     Whatever {
@@ -122,60 +604,236 @@ pub enum RcvError {
 pub type RcvResult<T> = Result<T, RcvError>;
 type BRcvResult<T> = Result<T, Box<RcvError>>;
 
-fn result_stats_to_json(rs: &VotingResult) -> Vec<JSValue> {
-    let mut l: Vec<JSValue> = Vec::new();
-    let num_rounds = rs.round_stats.len();
-    for (idx, _round_stat) in rs.round_stats.iter().enumerate() {
-        let round_stat = _round_stat.clone();
-        let mut tally: JSMap<String, JSValue> = JSMap::new();
-        for (name, count) in round_stat.tally {
-            tally.insert(name.clone(), json!(count.to_string()));
-        }
-
-        let mut tally_results: Vec<JSValue> = Vec::new();
-        for elim_stats in round_stat.tally_result_eliminated {
-            let mut transfers: JSMap<String, JSValue> = JSMap::new();
-            for (name, count) in elim_stats.transfers {
-                // No UWI to account for in transfers for now
-                // TODO: check that this is the case
-                transfers.insert(name, json!(count.to_string()));
-            }
-            if elim_stats.exhausted > 0 {
-                transfers.insert(
-                    "exhausted".to_string(),
-                    json!(elim_stats.exhausted.to_string()),
-                );
-            }
-            // The eliminated candidates are not output for the last round.
-            if idx < num_rounds - 1 {
-                tally_results.push(json!({
-                    "eliminated": elim_stats.name.clone(),
-                    "transfers": transfers
-                }));
-            }
-        }
-        for winner_name in round_stat.tally_results_elected {
-            tally_results.push(json!({
-                "elected": winner_name,
-                "transfers": {}
-            }));
-        }
-
-        let js = json!({"round": round_stat.round, "tally": tally, "tallyResults": tally_results});
-        l.push(js);
-    }
-    l
-}
-
 /// A ballot, as parsed by the readers
 /// This is before applying rules for undervote, blanks, etc.
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub struct ParsedBallot {
-    // TODO: add precinct
     // TODO: add filename?
     pub id: Option<String>,
     pub count: Option<u64>,
     pub choices: Vec<Vec<String>>,
+    pub challenged: bool,
+    /// The voter category, used to weight the ballot (see the weights configuration).
+    pub category: Option<String>,
+    /// The respondent of a form (respondentColumn), used to find the identical ballots.
+    pub respondent: Option<String>,
+    /// The precinct of the ballot (precinctColumnIndex), for the totals by precinct.
+    pub precinct: Option<String>,
+    /// The ballot style of the ballot (styleColumnIndex), for the totals by ballot style.
+    pub style: Option<String>,
+}
+
+/// Reads the ballots of a file: its path, the file source, and the candidates of the
+/// configuration (empty if they are not declared).
+pub type ReadBallots = fn(String, &FileSource, &[RcvCandidate]) -> BRcvResult<Vec<ParsedBallot>>;
+
+/// A reader of ballot data, as referred to by the `provider` field of a file source.
+#[derive(Debug, Clone, Copy)]
+pub struct Provider {
+    pub name: &'static str,
+    /// Reads the ballots, once the options of the file source are checked against the fields
+    /// below.
+    pub read: ReadBallots,
+    /// If set, the provider cannot work without the list of candidates from the
+    /// configuration. The value explains why.
+    pub candidates_required: Option<&'static str>,
+    /// True if the provider can read the challengedColumn option.
+    pub supports_challenged: bool,
+    /// True if the provider can read the category column used by the weights.
+    pub supports_categories: bool,
+    /// True if the provider can filter the ballots by their timestamp (timestampColumn).
+    pub supports_timestamps: bool,
+    /// True if the provider can read the respondent column (respondentColumn).
+    pub supports_respondents: bool,
+}
+
+/// All the providers understood by read_ranking_data.
+pub const PROVIDERS: &[Provider] = &[
+    Provider {
+        name: "ess",
+        read: |path, cfs, _| io_ess::read_excel_file(path, cfs),
+        candidates_required: None,
+        supports_challenged: false,
+        supports_categories: false,
+        supports_timestamps: false,
+        supports_respondents: false,
+    },
+    Provider {
+        name: "cdf",
+        read: |path, cfs, _| io_cdf::read_json(path, cfs),
+        candidates_required: None,
+        supports_challenged: false,
+        supports_categories: false,
+        supports_timestamps: false,
+        supports_respondents: false,
+    },
+    Provider {
+        name: "dominion",
+        read: |path, cfs, candidates| io_dominion::read_dominion(&path, cfs, candidates),
+        candidates_required: None,
+        supports_challenged: false,
+        supports_categories: false,
+        supports_timestamps: false,
+        supports_respondents: false,
+    },
+    Provider {
+        name: "msforms_ranking",
+        read: |path, cfs, _| io_msforms::read_msforms_ranking(path, cfs),
+        candidates_required: None,
+        supports_challenged: false,
+        supports_categories: true,
+        supports_timestamps: true,
+        supports_respondents: true,
+    },
+    Provider {
+        name: "msforms_likert",
+        read: |path, cfs, candidates| {
+            io_msforms::read_msforms_likert(path, cfs, &candidate_names(candidates))
+        },
+        candidates_required: Some("column headers are matched against candidate names"),
+        supports_challenged: false,
+        supports_categories: true,
+        supports_timestamps: true,
+        supports_respondents: true,
+    },
+    Provider {
+        name: "msforms_likert_transpose",
+        read: |path, cfs, _| io_msforms::read_msforms_likert_transpose(path, cfs),
+        candidates_required: None,
+        supports_challenged: false,
+        supports_categories: false,
+        supports_timestamps: true,
+        supports_respondents: false,
+    },
+    Provider {
+        name: "csv",
+        read: |path, cfs, candidates| {
+            let ballots = io_csv::read_csv_ranking(path.clone(), cfs)?;
+            io_csv::suggest_ranks_in_cells(&path, cfs, &ballots, &candidate_names(candidates))?;
+            Ok(ballots)
+        },
+        candidates_required: None,
+        supports_challenged: true,
+        supports_categories: true,
+        supports_timestamps: true,
+        supports_respondents: true,
+    },
+    Provider {
+        name: "csv_likert",
+        read: |path, cfs, candidates| {
+            io_csv::read_csv_likert(path, cfs, &candidate_names(candidates))
+        },
+        candidates_required: Some("column headers are matched against candidate names"),
+        supports_challenged: true,
+        supports_categories: true,
+        supports_timestamps: true,
+        supports_respondents: true,
+    },
+    Provider {
+        name: "parquet",
+        #[cfg(feature = "parquet")]
+        read: |path, cfs, _| io_parquet::read_parquet(path, cfs),
+        #[cfg(not(feature = "parquet"))]
+        read: |_, _, _| {
+            Err(Box::new(RcvError::FeatureNotEnabled {
+                provider: "parquet".to_string(),
+                feature: "parquet".to_string(),
+            }))
+        },
+        candidates_required: None,
+        supports_challenged: false,
+        supports_categories: false,
+        supports_timestamps: false,
+        supports_respondents: false,
+    },
+];
+
+/// Which ballots to tabulate when some of them are marked as challenged.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ChallengedMode {
+    /// All the ballots are counted. This is the default.
+    Include,
+    /// The challenged ballots are left out.
+    Exclude,
+    /// Two tabulations, with and without the challenged ballots, and a comparison.
+    Both,
+}
+
+impl ChallengedMode {
+    pub fn parse(mode: Option<&str>) -> RcvResult<ChallengedMode> {
+        match mode {
+            None | Some("include") => Ok(ChallengedMode::Include),
+            Some("exclude") => Ok(ChallengedMode::Exclude),
+            Some("both") => Ok(ChallengedMode::Both),
+            Some(x) => whatever!("unknown mode for challenged ballots: {}", x),
+        }
+    }
+}
+
+/// An entry of providers.json, the list of the providers for the users and the other tools.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderManifest {
+    pub name: String,
+    pub description: String,
+    /// Matches [Provider::candidates_required].
+    pub candidates_required: bool,
+}
+
+/// The entries of providers.json, in the order of the file. The file is checked against
+/// PROVIDERS by the tests.
+pub fn provider_manifest() -> Vec<ProviderManifest> {
+    serde_json::from_str(include_str!("../providers.json")).unwrap()
+}
+
+/// The list of the providers in the manual and in the help of --input-type.
+pub fn provider_list() -> String {
+    provider_manifest()
+        .iter()
+        .map(|p| format!("* `{}` {}\n", p.name, p.description))
+        .collect()
+}
+
+fn candidate_names(candidates: &[RcvCandidate]) -> Vec<String> {
+    candidates.iter().map(|c| c.name.clone()).collect()
+}
+
+pub fn find_provider(name: &str) -> RcvResult<&'static Provider> {
+    PROVIDERS
+        .iter()
+        .find(|p| p.name == name)
+        .context(UnknownFormatSnafu { format: name })
+}
+
+/// The number of ballots and the weighted votes of a voter category.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct CategoryTotal {
+    pub ballots: u64,
+    pub weight: u64,
+    pub votes: u64,
+}
+
+/// The totals for each voter category. The ballots without a category are under None.
+pub type CategoryTotals = BTreeMap<Option<String>, CategoryTotal>;
+
+/// The number of ballots read from a file source.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct SourceTotal {
+    pub file_path: String,
+    pub provider: String,
+    /// The ballots in the file, before the validation.
+    pub parsed: u64,
+    /// The ballots kept by the validation.
+    pub ballots: u64,
+    /// The votes of the kept ballots, with the weights of their categories.
+    pub weight: u64,
+    /// The ballots with marks beyond maxRankingsAllowed, before the validation.
+    pub truncated: u64,
+    /// The size of the file and the time taken to read it (--out-metrics).
+    pub metrics: metrics::SourceMetrics,
+    /// The ballots in the file by precinct and by ballot style, if the source has these columns.
+    pub precincts: BTreeMap<String, u64>,
+    pub styles: BTreeMap<String, u64>,
 }
 
 fn read_ranking_data(
@@ -183,242 +841,1243 @@ fn read_ranking_data(
     cfs: &FileSource,
     candidates_o: Option<&Vec<RcvCandidate>>,
     rules: &RcvRules,
-) -> RcvResult<(Vec<ranked_voting::Ballot>, Vec<RcvCandidate>)> {
+    weights: Option<&Weights>,
+    category_totals: &mut CategoryTotals,
+    orders: Option<&mut Vec<preflib::BallotOrder>>,
+) -> RcvResult<(Vec<ranked_voting::Ballot>, Vec<RcvCandidate>, SourceTotal)> {
+    let mut metrics = metrics::SourceMetrics::new(&root_path, cfs);
+    let warnings_before = codes::num_warnings();
+    let start = Instant::now();
+    let (mut parsed_ballots, validated_candidates) =
+        read_parsed_ballots(root_path, cfs, candidates_o)?;
+    metrics.parse_time = start.elapsed();
+    if cfs.deduplicate_identical_ballots == Some(true) {
+        parsed_ballots = dedup::deduplicate(&cfs.file_path, parsed_ballots);
+    }
+    // Without declared candidates, the rows must name at least one candidate. A source without
+    // ballots is only refused if all the sources are empty (see read_election_data).
+    ensure!(
+        candidates_o.is_some() || parsed_ballots.is_empty() || !validated_candidates.is_empty(),
+        NoCandidatesInInputSnafu {
+            path: &cfs.file_path
+        }
+    );
+    if candidates_o.is_some() && cfs.sanity_checks != Some(false) {
+        sanity::check_candidate_hit_rate(&parsed_ballots, &validated_candidates, cfs)?;
+        sanity::check_undeclared_near_misses(
+            &cfs.file_path,
+            &parsed_ballots,
+            &validated_candidates,
+            cfs,
+        );
+    }
+    if candidates_o.is_none() && cfs.sanity_checks != Some(false) {
+        sanity::check_inferred_candidates(
+            &cfs.file_path,
+            &parsed_ballots,
+            &validated_candidates,
+            cfs,
+        )?;
+    }
+    let start = Instant::now();
+    let validated = validate_ballots(&parsed_ballots, &validated_candidates, cfs, rules, weights)?;
+    metrics.validation_time = start.elapsed();
+    // The categories only count the ballots kept by the validation.
+    if let Some(w) = weights {
+        let kept: Vec<&ParsedBallot> = validated
+            .iter()
+            .map(|(idx, _)| &parsed_ballots[*idx])
+            .collect();
+        add_category_totals(category_totals, &kept, w)?;
+    }
+    let ballots: Vec<Ballot> = validated.into_iter().map(|(_, b)| b).collect();
+    if let Some(orders) = orders {
+        orders.extend(preflib::ballot_orders(
+            &parsed_ballots,
+            &validated_candidates,
+            cfs,
+            weights,
+        )?);
+    }
+    let mut total = source_total(cfs, &parsed_ballots, &ballots, rules, weights);
+    metrics.ballots = total.parsed;
+    metrics.warnings = (codes::num_warnings() - warnings_before) as u64;
+    metrics.log();
+    total.metrics = metrics;
+    info!(
+        "read_ranking_data: {}: {} ballots parsed, {} ballots kept ({} votes)",
+        total.file_path, total.parsed, total.ballots, total.weight
+    );
+    Ok((ballots, validated_candidates, total))
+}
+
+// The validation keeps the ballots with a positive weighted count.
+/// The ranks of a ballot beyond maxRankingsAllowed, which the tabulation does not look at.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct TruncatedRanks {
+    pub id: Option<String>,
+    /// The number of ranks with a mark beyond the limit.
+    pub truncated_ranks: u32,
+    /// The marks of these ranks, as they are in the file.
+    pub choices: Vec<String>,
+}
+
+// The truncation itself is done by the tabulation: the raw choices are only available here.
+fn truncated_ranks(pb: &ParsedBallot, max_rankings: Option<u32>) -> Option<TruncatedRanks> {
+    let max_rankings = max_rankings? as usize;
+    let marks: Vec<&Vec<String>> = pb
+        .choices
+        .iter()
+        .skip(max_rankings)
+        .filter(|group| group.iter().any(|c| !c.trim().is_empty()))
+        .collect();
+    if marks.is_empty() {
+        return None;
+    }
+    Some(TruncatedRanks {
+        id: pb.id.clone(),
+        truncated_ranks: marks.len() as u32,
+        choices: marks.into_iter().flatten().cloned().collect(),
+    })
+}
+
+fn source_total(
+    cfs: &FileSource,
+    parsed_ballots: &[ParsedBallot],
+    ballots: &[Ballot],
+    rules: &RcvRules,
+    weights: Option<&Weights>,
+) -> SourceTotal {
+    let parsed: u64 = parsed_ballots.iter().map(|pb| pb.count.unwrap_or(1)).sum();
+    let max_rankings = rules.max_rankings_allowed.parse::<u32>().ok();
+    let mut truncated: u64 = 0;
+    for pb in parsed_ballots.iter() {
+        if let Some(tr) = truncated_ranks(pb, max_rankings) {
+            debug!(
+                "source_total: ballot {:?}: {} ranks beyond maxRankingsAllowed are not counted: {:?}",
+                tr.id, tr.truncated_ranks, tr.choices
+            );
+            truncated += pb.count.unwrap_or(1);
+        }
+    }
+    let kept: u64 = if ballots.is_empty() {
+        0
+    } else {
+        parsed_ballots
+            .iter()
+            .filter(|pb| {
+                weights
+                    .map(|w| w.weight(pb.category.as_deref()).0)
+                    .unwrap_or(1)
+                    > 0
+            })
+            .map(|pb| pb.count.unwrap_or(1))
+            .sum()
+    };
+    let mut precincts: BTreeMap<String, u64> = BTreeMap::new();
+    let mut styles: BTreeMap<String, u64> = BTreeMap::new();
+    for pb in parsed_ballots.iter() {
+        if let Some(precinct) = pb.precinct.as_ref() {
+            *precincts.entry(precinct.clone()).or_insert(0) += pb.count.unwrap_or(1);
+        }
+        if let Some(style) = pb.style.as_ref() {
+            *styles.entry(style.clone()).or_insert(0) += pb.count.unwrap_or(1);
+        }
+    }
+    SourceTotal {
+        file_path: cfs.file_path.clone(),
+        provider: cfs.provider.clone(),
+        parsed,
+        ballots: kept,
+        weight: ballots.iter().map(|b| b.count).sum(),
+        truncated,
+        metrics: metrics::SourceMetrics::default(),
+        precincts,
+        styles,
+    }
+}
+
+fn source_totals_to_json(totals: &[SourceTotal]) -> JSValue {
+    let l: Vec<JSValue> = totals
+        .iter()
+        .map(|t| {
+            let mut js = json!({
+                "file": t.file_path,
+                "provider": t.provider,
+                "parsedBallots": t.parsed.to_string(),
+                "ballots": t.ballots.to_string(),
+                "weight": t.weight.to_string(),
+                "truncatedBallots": t.truncated.to_string(),
+            });
+            // The parsed ballots by precinct and by ballot style, as strings like the counts.
+            let by_group = |m: &BTreeMap<String, u64>| -> JSValue {
+                m.iter()
+                    .map(|(k, n)| (k.clone(), json!(n.to_string())))
+                    .collect::<serde_json::Map<String, JSValue>>()
+                    .into()
+            };
+            if !t.precincts.is_empty() {
+                js["precincts"] = by_group(&t.precincts);
+            }
+            if !t.styles.is_empty() {
+                js["ballotStyles"] = by_group(&t.styles);
+            }
+            js
+        })
+        .collect();
+    json!(l)
+}
+
+/// The count of a ballot, multiplied by the weight of its category. A count of 1 by default.
+pub fn weighted_count(pb: &ParsedBallot, weights: Option<&Weights>) -> RcvResult<u64> {
+    let count = pb.count.unwrap_or(1);
+    let weight = weights
+        .map(|w| w.weight(pb.category.as_deref()).0)
+        .unwrap_or(1);
+    count.checked_mul(weight).context(WeightOverflowSnafu {
+        id: pb.id.clone().unwrap_or_default(),
+        count,
+        weight,
+    })
+}
+
+// Unknown categories get the default weight, with a warning the first time they are seen. The
+// ballots are validated first: their weighted counts fit.
+fn add_category_totals(
+    totals: &mut CategoryTotals,
+    parsed_ballots: &[&ParsedBallot],
+    weights: &Weights,
+) -> RcvResult<()> {
+    for pb in parsed_ballots.iter() {
+        let count = pb.count.unwrap_or(1);
+        let votes = weighted_count(pb, Some(weights))?;
+        let (weight, known) = weights.weight(pb.category.as_deref());
+        let total = totals.entry(pb.category.clone()).or_insert_with(|| {
+            if let (Some(category), false) = (pb.category.as_ref(), known) {
+                codes::warning(
+                    WarningCode::UnknownCategory,
+                    format!(
+                        "Unknown voter category {:?}, using the default weight {}",
+                        category, weight
+                    ),
+                );
+            }
+            CategoryTotal {
+                ballots: 0,
+                weight,
+                votes: 0,
+            }
+        });
+        let category = pb.category.as_deref().unwrap_or("without a category");
+        total.ballots = total
+            .ballots
+            .checked_add(count)
+            .context(CountOverflowSnafu {
+                what: format!("the ballots of the category {}", category),
+            })?;
+        total.votes = total.votes.checked_add(votes).context(CountOverflowSnafu {
+            what: format!("the votes of the category {}", category),
+        })?;
+    }
+    Ok(())
+}
+
+fn category_totals_to_json(totals: &CategoryTotals) -> JSValue {
+    let l: Vec<JSValue> = totals
+        .iter()
+        .map(|(category, total)| {
+            json!({
+                "category": category,
+                "ballots": total.ballots.to_string(),
+                "weight": total.weight.to_string(),
+                "weightedVotes": total.votes.to_string(),
+            })
+        })
+        .collect();
+    json!(l)
+}
+
+// Reads the ballots as found in the file, and the candidates (inferred from the ballots if
+// they are not provided).
+fn read_parsed_ballots(
+    root_path: String,
+    cfs: &FileSource,
+    candidates_o: Option<&Vec<RcvCandidate>>,
+) -> RcvResult<(Vec<ParsedBallot>, Vec<RcvCandidate>)> {
     let p: PathBuf = [root_path.clone(), cfs.file_path.clone()].iter().collect();
     let p2 = p.as_path().display().to_string();
     info!("Attempting to read rank file {:?}", p2);
-    let cand_names = || {
-        let candidates = candidates_o.context(MissingMandatoryCandidatesSnafu {})?;
-        let names: Vec<String> = candidates.iter().map(|c| c.name.clone()).collect();
-        Ok(names)
+    // The csv files with the ranks in the cells are read as csv_likert files. Without declared
+    // candidates, the candidates are the names of the header.
+    let likert_cfs: FileSource;
+    let header_candidates: Vec<RcvCandidate>;
+    let (cfs, candidates_o) = if cfs.provider == "csv" && cfs.layout()? == CsvLayout::RanksInCells {
+        likert_cfs = cfs.as_ranks_in_cells()?;
+        let candidates = match candidates_o {
+            Some(cs) => cs,
+            None => {
+                header_candidates =
+                    io_csv::header_candidates(&p2, &likert_cfs).context(OpeningFileSnafu {
+                        root_path: root_path.clone(),
+                    })?;
+                &header_candidates
+            }
+        };
+        (&likert_cfs, Some(candidates))
+    } else {
+        (cfs, candidates_o)
     };
-    let parsed_ballots = match cfs.provider.as_str() {
-        "ess" => io_ess::read_excel_file(p2, cfs).context(OpeningFileSnafu { root_path })?,
-        "cdf" => io_cdf::read_json(p2).context(OpeningFileSnafu { root_path })?,
-        "dominion" => io_dominion::read_dominion(&p2).context(OpeningFileSnafu { root_path })?,
-        "msforms_ranking" => {
-            io_msforms::read_msforms_ranking(p2, cfs).context(OpeningFileSnafu { root_path })?
-        }
-        "msforms_likert" => io_msforms::read_msforms_likert(p2, cfs, &cand_names()?)
-            .context(OpeningFileSnafu { root_path })?,
-        "msforms_likert_transpose" => io_msforms::read_msforms_likert_transpose(p2, cfs)
-            .context(OpeningFileSnafu { root_path })?,
-        "csv" => io_csv::read_csv_ranking(p2, cfs).context(OpeningFileSnafu { root_path })?,
-        "csv_likert" => io_csv::read_csv_likert(p2, cfs, &cand_names()?)
-            .context(OpeningFileSnafu { root_path })?,
-        x => {
-            return Err(RcvError::UnknownFormat {
-                format: x.to_string(),
-            })
+    let provider = find_provider(cfs.provider.as_str())?;
+    if candidates_o.map(|cs| cs.is_empty()).unwrap_or(false) {
+        return Err(RcvError::EmptyCandidates {
+            provider: provider.name.to_string(),
+        });
+    }
+    if let Some(reason) = provider.candidates_required {
+        ensure!(
+            candidates_o.is_some(),
+            MissingMandatoryCandidatesSnafu {
+                provider: provider.name,
+                reason
+            }
+        );
+    }
+    ensure!(
+        cfs.challenged_column.is_none() || provider.supports_challenged,
+        ChallengedNotSupportedSnafu {
+            provider: provider.name
+        }
+    );
+    ensure!(
+        cfs.category_column.is_none() || provider.supports_categories,
+        CategoriesNotSupportedSnafu {
+            provider: provider.name
+        }
+    );
+    ensure!(
+        cfs.timestamp_column.is_none() || provider.supports_timestamps,
+        TimestampsNotSupportedSnafu {
+            provider: provider.name
+        }
+    );
+    ensure!(
+        cfs.respondent_column.is_none() || provider.supports_respondents,
+        RespondentsNotSupportedSnafu {
+            provider: provider.name
+        }
+    );
+    ensure!(
+        cfs.expected_candidate_count.is_none() || provider.name == "dominion",
+        ExpectedCandidateCountNotSupportedSnafu {
+            provider: provider.name
+        }
+    );
+    ensure!(
+        cfs.style_column_index.is_none() || provider.name == "ess",
+        StylesNotSupportedSnafu {
+            provider: provider.name
         }
+    );
+    let parsed_ballots = (provider.read)(p2, cfs, candidates_o.map_or(&[], |cs| cs.as_slice()))
+        .context(OpeningFileSnafu { root_path })?;
+    let parsed_ballots = match cfs.shuffle_seed {
+        Some(seed) => shuffle_ballots(parsed_ballots, seed),
+        None => parsed_ballots,
     };
     let validated_candidates: Vec<RcvCandidate> = if let Some(cs) = candidates_o {
-        assert!(!cs.is_empty(), "no candidate specified");
         cs.to_vec()
     } else {
-        let mut names: HashSet<String> = HashSet::new();
+        // Labels that validate_ballots treats specially are not candidates.
+        let is_label = |name: &str| {
+            name == "UWI"
+                || cfs.undervote_label.as_deref() == Some(name)
+                || cfs.overvote_label.as_deref() == Some(name)
+                || cfs
+                    .overvote_delimiter
+                    .as_ref()
+                    .map(|delim| name.contains(delim.as_str()))
+                    .unwrap_or(false)
+        };
+        // The candidates are sorted by name, like in the library, so that their order does not
+        // depend on the order of the ballots.
+        let mut names: HashSet<&str> = HashSet::new();
+        let mut cs: Vec<RcvCandidate> = Vec::new();
         for b in parsed_ballots.iter() {
             for group in b.choices.iter() {
-                for name in group.iter() {
-                    if !names.contains(name) && !name.is_empty() {
-                        names.insert(name.clone());
+                for name in group.iter().map(|n| n.trim()) {
+                    if !name.is_empty() && !is_label(name) && names.insert(name) {
+                        cs.push(RcvCandidate {
+                            name: name.to_string(),
+                            code: None,
+                            excluded: Some(false),
+                            inferred: true,
+                        });
                     }
                 }
             }
         }
-        let mut cs: Vec<RcvCandidate> = names
-            .iter()
-            .map(|n| RcvCandidate {
-                name: n.clone(),
-                code: None,
-                excluded: Some(false),
-            })
-            .collect();
-        cs.sort_by_key(|c| c.name.clone());
+        cs.sort_by(|c1, c2| c1.name.cmp(&c2.name));
         cs
     };
-    let ballots = validate_ballots(&parsed_ballots, &validated_candidates, cfs, rules)?;
-    Ok((ballots, validated_candidates))
+    Ok((parsed_ballots, validated_candidates))
+}
+
+// A permutation of the ballots that only depends on the seed and on the number of ballots: each
+// position is keyed by the SHA-256 digest of the seed and the position, as for the random
+// tiebreaks.
+fn shuffle_ballots(ballots: Vec<ParsedBallot>, seed: u64) -> Vec<ParsedBallot> {
+    let mut keyed: Vec<(String, ParsedBallot)> = ballots
+        .into_iter()
+        .enumerate()
+        .map(|(idx, b)| (sha256::digest(format!("{:020}{:020}", seed, idx)), b))
+        .collect();
+    keyed.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+    keyed.into_iter().map(|(_, b)| b).collect()
 }
 
+// The names of the declared candidates, normalized as in the tabulation (see
+// VoteRules::name_normalization).
+fn declared_names(candidates: &[RcvCandidate]) -> HashSet<CandidateName> {
+    candidates
+        .iter()
+        .map(|c| CandidateName::new(&c.name, NameNormalization::default()))
+        .collect()
+}
+
+// The labels of the special choices of a file source.
+fn choice_labels(source: &FileSource) -> ChoiceLabels {
+    ChoiceLabels {
+        undervote_label: source.undervote_label.clone(),
+        overvote_label: source.overvote_label.clone(),
+        overvote_delimiter: source.overvote_delimiter.clone(),
+        blank_as_undeclared_write_in: source.treat_blank_as_undeclared_write_in.unwrap_or(false),
+    }
+}
+
+// Interprets the content of one rank of a ballot, in the same way as Builder::add_vote.
+fn validate_choice(
+    s: &[String],
+    candidate_names: &HashSet<CandidateName>,
+    labels: &ChoiceLabels,
+) -> BallotChoice {
+    classify_choice(
+        s,
+        Some(candidate_names),
+        NameNormalization::default(),
+        labels,
+    )
+}
+
+// The key that chooses the ballots logged with --debug-sample-rate: the id of the ballot, or its
+// position in the file if it has none. It is passed to the library with the ballot (Ballot::id),
+// so that both stages log the same ballots.
+fn debug_sample_key(pb: &ParsedBallot, idx: usize) -> String {
+    match pb.id.as_ref() {
+        Some(id) => id.clone(),
+        None => format!("#{}", idx + 1),
+    }
+}
+
+// True if the validation of the ballot is logged: all the ballots, or a sample of them with
+// --debug-sample-rate, so that two runs log the same ballots.
+fn debug_sampled(source: &FileSource, pb: &ParsedBallot, idx: usize) -> bool {
+    match source.debug_sample_rate {
+        None => true,
+        Some(rate) => rate.contains(&debug_sample_key(pb, idx)),
+    }
+}
+
+// The validated ballots, with the index of their parsed ballot. The ballots without votes (a
+// weighted count of 0, or no candidates) are dropped.
 fn validate_ballots(
     parsed_ballots: &[ParsedBallot],
     candidates: &[RcvCandidate],
     source: &FileSource,
     _rules: &RcvRules,
-) -> RcvResult<Vec<Ballot>> {
-    let candidate_names: HashSet<String> = candidates.iter().map(|c| c.name.clone()).collect();
-    let mut res: Vec<Ballot> = Vec::new();
+    weights: Option<&Weights>,
+) -> RcvResult<Vec<(usize, Ballot)>> {
+    let candidate_names = declared_names(candidates);
+    let labels = choice_labels(source);
+    let mut res: Vec<(usize, Ballot)> = Vec::new();
+    // The cells with the overvote delimiter that are not lists of candidates.
+    let mut unsplittable: BTreeSet<String> = BTreeSet::new();
 
-    let treat_blank_as_undeclared_write_in =
-        source.treat_blank_as_undeclared_write_in.unwrap_or(false);
-
-    for pb in parsed_ballots.iter() {
-        let mut choices: Vec<BallotChoice> = Vec::new();
-
-        for s in pb.choices.iter() {
-            let res: BallotChoice = match &s[..] {
-                [] => BallotChoice::Undervote,
-                [_, _, ..] => BallotChoice::Overvote,
-                [c] if candidate_names.contains(c) => BallotChoice::Candidate(c.to_string()),
-                [c] if c == "UWI" => BallotChoice::UndeclaredWriteIn,
-                [c] if source.undervote_label == Some(c.to_string()) => BallotChoice::Undervote,
-                [c] if source.overvote_label == Some(c.to_string()) => BallotChoice::Overvote,
-                [c] if c.is_empty() => {
-                    if treat_blank_as_undeclared_write_in {
-                        BallotChoice::UndeclaredWriteIn
-                    } else {
-                        BallotChoice::Blank
-                    }
-                }
-                [c] => {
-                    if let Some(delim) = source.overvote_delimiter.clone() {
-                        if c.contains(&delim) {
-                            BallotChoice::Overvote
-                        } else {
-                            BallotChoice::UndeclaredWriteIn
-                        }
-                    } else {
-                        BallotChoice::UndeclaredWriteIn
+    for (idx, pb) in parsed_ballots.iter().enumerate() {
+        let logged = debug_sampled(source, pb, idx);
+        // The readers keep the cells as they are in the files: the surrounding whitespace is
+        // only removed here, so that a cell with only spaces or tabs is a blank.
+        let trimmed: Vec<Vec<String>> = pb
+            .choices
+            .iter()
+            .map(|group| group.iter().map(|c| c.trim().to_string()).collect())
+            .collect();
+        let choices: Vec<BallotChoice> = trimmed
+            .iter()
+            .map(|s| validate_choice(s, &candidate_names, &labels))
+            .collect();
+        if let Some(delim) = source.overvote_delimiter.as_deref() {
+            for (group, choice) in trimmed.iter().zip(choices.iter()) {
+                if let ([c], BallotChoice::UndeclaredWriteIn) = (group.as_slice(), choice) {
+                    if c.contains(delim) {
+                        unsplittable.insert(c.clone());
                     }
                 }
-            };
-            choices.push(res);
+            }
         }
 
-        debug!(
-            "validate_ballots: Choices for ballot {:?}: {:?}",
-            pb.id, choices
-        );
+        // The key is only kept with a sample: the library logs the same ballots.
+        let key_o = source.debug_sample_rate.map(|_| debug_sample_key(pb, idx));
+        if logged {
+            debug!(
+                "validate_ballots: ballot {}: cells {:?} -> choices {:?}",
+                debug_sample_key(pb, idx),
+                pb.choices,
+                choices
+            );
+        }
 
         // Default of 1 if not specified
-        let count = pb.count.unwrap_or(1);
+        let count = weighted_count(pb, weights)?;
 
         if count > 0 && !candidates.is_empty() {
-            let v = Ballot {
-                candidates: choices,
-                count,
-            };
+            let mut v = Ballot::new(choices, count);
+            v.challenged = pb.challenged;
+            // The id of the source, kept for the exports. The ballots without one get a key
+            // only with a sample: the library logs the same ballots.
+            v.id = pb.id.clone().or(key_o);
+            if logged {
+                debug!(
+                    "validate_ballots: ballot {}: adding vote {:?}",
+                    debug_sample_key(pb, idx),
+                    v.clone()
+                );
+            }
+            res.push((idx, v));
+        } else if logged {
             debug!(
-                "validate_ballots: ballot {:?}: adding vote {:?}",
-                pb.id,
-                v.clone()
+                "validate_ballots: ballot {}: dropped (count {}, {} candidates)",
+                debug_sample_key(pb, idx),
+                count,
+                candidates.len()
             );
-            res.push(v);
         }
     }
+    for c in unsplittable.iter() {
+        codes::warning(
+            WarningCode::UnsplittableOvervote,
+            format!(
+                "The choice {:?} contains the overvote delimiter but is not a list of candidates, it is treated as a write-in",
+                c
+            ),
+        );
+    }
     Ok(res)
 }
 
 fn validate_rules(rcv_rules: &RcvRules) -> RcvResult<VoteRules> {
-    let res = VoteRules {
-        tiebreak_mode: match rcv_rules.tiebreak_mode.as_str() {
-            "useCandidateOrder" => TieBreakMode::UseCandidateOrder,
-            "random" => {
-                let seed = match rcv_rules.random_seed.clone().map(|s| s.parse::<u32>()) {
-                    Some(Result::Ok(x)) => x,
-                    x => {
-                        whatever!(
-                            "Cannot use tiebreak mode {:?} (currently not implemented)",
-                            x
-                        )
-                    }
-                };
-                TieBreakMode::Random(seed)
-            }
-            x => {
-                whatever!(
-                    "Cannot use tiebreak mode {:?} (currently not implemented)",
-                    x
-                )
-            }
-        },
-        max_skipped_rank_allowed: match rcv_rules.max_skipped_ranks_allowed.as_str() {
-            "unlimited" => MaxSkippedRank::Unlimited,
-            "0" => MaxSkippedRank::ExhaustOnFirstOccurence,
-            x => match x.parse() {
-                Ok(num) => MaxSkippedRank::MaxAllowed(num),
-                _ => {
-                    whatever!(
-                        "Value '{:?}' cannot be understood for maxSkippedRanksAllowed",
-                        rcv_rules.max_rankings_allowed
-                    )
-                }
-            },
-        },
-        overvote_rule: rcv_rules.overvote_rule()?,
-        winner_election_mode: match rcv_rules.winner_election_mode.as_str() {
-            "singleWinnerMajority" => WinnerElectionMode::SingelWinnerMajority,
-            x => {
-                whatever!(
-                    "Cannot use election mode {:?}: currently not implemented",
-                    x
-                )
-            }
-        },
-        // number_of_winners: 1,         // TODO: implement
-        // minimum_vote_threshold: None, // TODO: implement
-        max_rankings_allowed: match rcv_rules.max_rankings_allowed.parse::<u32>() {
-            Err(_) if rcv_rules.max_rankings_allowed == "max" => None,
-            Result::Ok(x) if x > 0 => Some(x),
-            x => {
+    let mut res = VoteRules::default();
+    res.tiebreak_mode = match rcv_rules.tiebreak_mode.as_str() {
+        "useCandidateOrder" => TieBreakMode::UseCandidateOrder,
+        "random" => {
+            let seed = rcv_rules
+                .random_seed()?
+                .context(MissingRandomSeedSnafu {})?;
+            TieBreakMode::Random(seed)
+        }
+        x => {
+            whatever!(
+                "Cannot use tiebreak mode {:?} (currently not implemented)",
+                x
+            )
+        }
+    };
+    res.max_skipped_rank_allowed = match rcv_rules.max_skipped_ranks_allowed.as_str() {
+        "unlimited" => MaxSkippedRank::Unlimited,
+        "0" => MaxSkippedRank::ExhaustOnFirstOccurence,
+        x => match x.parse() {
+            Ok(num) => MaxSkippedRank::MaxAllowed(num),
+            _ => {
                 whatever!(
-                    "Failed to understand maxRankingsAllowed option: {:?}: currently not implemented",
-                    x
+                    "Value '{:?}' cannot be understood for maxSkippedRanksAllowed",
+                    rcv_rules.max_rankings_allowed
                 )
             }
         },
-        elimination_algorithm: {
-            if rcv_rules.batch_elimination.unwrap_or(false) {
-                EliminationAlgorithm::Batch
-            } else {
-                EliminationAlgorithm::Single
-            }
-        },
-        duplicate_candidate_mode: match rcv_rules.exhaust_on_duplicate_candidate {
-            Some(true) => DuplicateCandidateMode::Exhaust,
-            _ => DuplicateCandidateMode::SkipDuplicate,
-        },
     };
+    res.blank_counts_as_skipped = rcv_rules.blank_counts_as_skipped.unwrap_or(true);
+    res.overvote_rule = rcv_rules.overvote_rule()?;
+    res.overvote_scope = rcv_rules.overvote_scope()?;
+    res.winner_election_mode = match rcv_rules.winner_election_mode.as_str() {
+        "singleWinnerMajority" => {
+            rcv_rules.check_number_of_winners()?;
+            WinnerElectionMode::SingelWinnerMajority
+        }
+        x => {
+            whatever!(
+                "Cannot use election mode {:?}: currently not implemented",
+                x
+            )
+        }
+    };
+    // number_of_winners: 1,         // TODO: implement
+    // minimum_vote_threshold: None, // TODO: implement
+    res.max_rankings_allowed = match rcv_rules.max_rankings_allowed.parse::<u32>() {
+        Err(_) if rcv_rules.max_rankings_allowed == "max" => None,
+        Result::Ok(x) if x > 0 => Some(x),
+        x => {
+            whatever!(
+                "Failed to understand maxRankingsAllowed option: {:?}: currently not implemented",
+                x
+            )
+        }
+    };
+    res.elimination_algorithm = {
+        if rcv_rules.batch_elimination.unwrap_or(false) {
+            EliminationAlgorithm::Batch
+        } else {
+            EliminationAlgorithm::Single
+        }
+    };
+    res.duplicate_candidate_mode = match rcv_rules.exhaust_on_duplicate_candidate {
+        Some(true) => DuplicateCandidateMode::Exhaust,
+        _ => DuplicateCandidateMode::SkipDuplicate,
+    };
+    res.exhaust_rankings_cap = rcv_rules.exhaust_rankings_cap;
+    res.continue_to_completion = rcv_rules.continue_to_completion.unwrap_or(false);
+    res.legacy_random_tiebreak = rcv_rules.legacy_random_tiebreak.unwrap_or(false);
+    res.nota_candidate = rcv_rules.nota_candidate.clone();
+    res.protect_nota = rcv_rules.protect_nota.unwrap_or(false);
+    res.uwi_round1_reporting = match rcv_rules.uwi_round1_reporting.as_deref() {
+        None | Some("current") => UwiRound1Reporting::Current,
+        Some("reference") => UwiRound1Reporting::Reference,
+        Some(x) => {
+            whatever!(
+                "Value {:?} cannot be understood for uwiRound1Reporting: expected \"current\" or \"reference\"",
+                x
+            )
+        }
+    };
+    // The custom strategies are only available in the library.
+    res.elimination_strategy = None;
+    res.track_rank_origin = rcv_rules.track_rank_origin.unwrap_or(false);
+    res.name_normalization = NameNormalization::default();
     Ok(res)
 }
 
-fn build_summary_js(config: &RcvConfig, rv: &VotingResult) -> JSValue {
-    let c = OutputConfig {
+/// The terminal state of a tabulation, as written in the `status` field of the summary.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum SummaryStatus {
+    WinnerDeclared,
+    NoWinner,
+    /// The "None of the above" candidate won: nobody is elected.
+    NotaPrevailed,
+    Error,
+    /// Some file sources could not be read, and were skipped (--skip-bad-sources).
+    Partial,
+    /// The tabulation was interrupted (Ctrl-C): the summary only has the completed rounds.
+    Interrupted,
+}
+
+impl SummaryStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SummaryStatus::WinnerDeclared => "winnerDeclared",
+            SummaryStatus::NoWinner => "noWinner",
+            SummaryStatus::NotaPrevailed => "notaPrevailed",
+            SummaryStatus::Error => "error",
+            SummaryStatus::Partial => "partial",
+            SummaryStatus::Interrupted => "interrupted",
+        }
+    }
+
+    /// The exit code of the command line program for this status.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SummaryStatus::WinnerDeclared => 0,
+            SummaryStatus::Error => 1,
+            SummaryStatus::NoWinner | SummaryStatus::NotaPrevailed => 2,
+            SummaryStatus::Partial => 3,
+            // As the shells do for the programs killed by SIGINT.
+            SummaryStatus::Interrupted => 130,
+        }
+    }
+}
+
+fn output_config(config: &RcvConfig, threshold: Option<String>) -> OutputConfig {
+    OutputConfig {
         contest: config.output_settings.contest_name.clone(),
         date: config.output_settings.contest_date.clone(),
         jurisdiction: config.output_settings.contest_juridiction.clone(),
         office: config.output_settings.contest_office.clone(),
-        threshold: Some(rv.threshold.to_string()),
+        threshold,
+    }
+}
+
+fn summary_status(rv: &VotingResult) -> (SummaryStatus, String) {
+    if rv.nota_prevailed {
+        return (
+            SummaryStatus::NotaPrevailed,
+            "none of the above prevailed, no candidate is elected".to_string(),
+        );
+    }
+    match rv.winners.as_ref() {
+        Some(winners) if !winners.is_empty() => (
+            SummaryStatus::WinnerDeclared,
+            format!("winner declared: {}", winners.join(", ")),
+        ),
+        _ => (
+            SummaryStatus::NoWinner,
+            "the tabulation ended without a winner".to_string(),
+        ),
+    }
+}
+
+// The metrics of competitiveness on one line, for the log of the tabulation.
+fn competitiveness_to_text(report: &ranked_voting::metrics::CompetitivenessReport) -> String {
+    let mut parts: Vec<String> = vec![format!("{} rounds", report.rounds)];
+    if let Some(e) = report.effective_candidates {
+        parts.push(format!("{:.2} effective candidates", e));
+    }
+    if let Some(m) = report.final_round_margin_percent() {
+        parts.push(format!("final round margin {:.2}%", m));
+    }
+    match report.come_from_behind {
+        Some(true) => parts.push("come from behind".to_string()),
+        Some(false) => parts.push("winner leading from the first round".to_string()),
+        None => {}
+    }
+    if let Some(e) = report.exhausted_percent() {
+        parts.push(format!("{:.2}% exhausted", e));
+    }
+    parts.join(", ")
+}
+
+// The summary written when the run failed.
+fn error_summary<'a>(config: &RcvConfig, err: &RcvError) -> Summary<'a> {
+    Summary::error(config, error_message(err))
+}
+
+// The message of an error of the tabulation.
+fn voting_error_text(err: &VotingErrors) -> String {
+    match err {
+        VotingErrors::EmptyElection => "there is no valid ballot to tabulate".to_string(),
+        VotingErrors::NoConvergence => "the tabulation did not converge".to_string(),
+        VotingErrors::NoCandidateToEliminate => "no candidate could be eliminated".to_string(),
+        VotingErrors::InvalidBallot(msg) => format!("invalid ballot: {}", msg),
+        VotingErrors::Interrupted => "the tabulation was interrupted".to_string(),
+        VotingErrors::UnknownCandidate(name) => format!("unknown candidate {}", name),
+    }
+}
+
+/// The message of an error, followed by the messages of its causes (for example the error of
+/// the system when a file cannot be read).
+pub fn error_message(err: &RcvError) -> String {
+    let mut message = err.to_string();
+    // The message of a tabulation error already describes its cause.
+    if matches!(err, RcvError::RvVoting { .. }) {
+        return message;
+    }
+    let mut cause = std::error::Error::source(err);
+    while let Some(c) = cause {
+        message = format!("{}: {}", message, c);
+        cause = match c.downcast_ref::<RcvError>() {
+            Some(RcvError::RvVoting { .. }) => None,
+            _ => c.source(),
+        };
+    }
+    message
+}
+
+// Legacy references (including the ones from the reference implementation) do not have
+// the status fields: they are not compared in this case.
+fn summary_for_comparison(summary: &JSValue, reference: &JSValue) -> JSValue {
+    let mut res = summary.clone();
+    if let Some(obj) = res.as_object_mut() {
+        if reference.get("status").is_none() {
+            obj.remove("status");
+            obj.remove("reason");
+        }
+        // The list of the excluded candidates is specific to timrcv.
+        if reference.get("excludedCandidates").is_none() {
+            obj.remove("excludedCandidates");
+        }
+    }
+    res
+}
+
+// The winners of a summary: the elected entries of the last round.
+fn summary_winners(summary: &JSValue) -> BTreeSet<String> {
+    let last_round = summary["results"]
+        .as_array()
+        .and_then(|rounds| rounds.last());
+    last_round
+        .and_then(|round| round["tallyResults"].as_array())
+        .map(|results| {
+            results
+                .iter()
+                .filter_map(|r| r["elected"].as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// The differences of threshold, winners and number of rounds with the reference summary
+// (--reference-strict), one message for each.
+fn strict_mismatches(summary: &JSValue, reference: &JSValue) -> Vec<String> {
+    let mut mismatches: Vec<String> = Vec::new();
+    let threshold = &summary["config"]["threshold"];
+    let ref_threshold = &reference["config"]["threshold"];
+    if threshold != ref_threshold {
+        mismatches.push(format!(
+            "threshold {} instead of {}",
+            threshold, ref_threshold
+        ));
+    }
+    let (winners, ref_winners) = (summary_winners(summary), summary_winners(reference));
+    if winners != ref_winners {
+        mismatches.push(format!(
+            "winners {:?} instead of {:?}",
+            winners, ref_winners
+        ));
+    }
+    let num_rounds = summary["results"].as_array().map(|r| r.len()).unwrap_or(0);
+    let ref_num_rounds = reference["results"]
+        .as_array()
+        .map(|r| r.len())
+        .unwrap_or(0);
+    if num_rounds != ref_num_rounds {
+        mismatches.push(format!(
+            "{} rounds instead of {}",
+            num_rounds, ref_num_rounds
+        ));
+    }
+    mismatches
+}
+
+// Replaces the results of a summary with aggregated candidates by the full results. The config
+// section is kept, as it may have the sources.
+fn with_full_results(summary: &mut JSValue, full: JSValue) {
+    if let (Some(obj), JSValue::Object(full)) = (summary.as_object_mut(), full) {
+        for (key, value) in full.into_iter().filter(|(key, _)| key != "config") {
+            obj.insert(key, value);
+        }
+    }
+}
+
+// Fails early if the reference summary cannot be read as JSON (its content is only compared
+// after the tabulation), or if the path of the summary is a directory or in a missing directory.
+fn check_output_paths(
+    check_summary_path: &Option<String>,
+    out_flag: &str,
+    out_path_o: &Option<String>,
+) -> RcvResult<()> {
+    if let Some(path) = check_summary_path {
+        let metadata = fs::metadata(path).context(ReferenceOpenSnafu { path })?;
+        ensure!(metadata.is_file(), ReferenceNotAFileSnafu { path });
+        let file = fs::File::open(path).context(ReferenceOpenSnafu { path })?;
+        serde_json::from_reader::<_, serde::de::IgnoredAny>(std::io::BufReader::new(file))
+            .context(ReferenceParseSnafu { path })?;
+    }
+    // The templated paths are checked once they are rendered.
+    if let Some(path) = out_path_o.as_deref() {
+        output_path::check(out_flag, path)?;
+    }
+    Ok(())
+}
+
+// Writes the summary to a file, without building the whole text in memory. The summary is
+// written to a temporary file first and then renamed, so that an interrupted run never leaves
+// a truncated summary.
+fn write_summary(summary: &Summary, path: &str) -> RcvResult<()> {
+    let tmp_path = format!("{}.tmp", path);
+    let res = write_summary_through(summary, path, &tmp_path);
+    if res.is_err() {
+        // The temporary file may not exist, depending on the step that failed.
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+fn write_summary_through(summary: &Summary, path: &str, tmp_path: &str) -> RcvResult<()> {
+    let file = fs::File::create(tmp_path).context(SummaryWriteSnafu { path: tmp_path })?;
+    let mut writer = std::io::BufWriter::new(file);
+    summary
+        .write_pretty(&mut writer)
+        .map_err(std::io::Error::from)
+        .context(SummaryWriteSnafu { path: tmp_path })?;
+    std::io::Write::flush(&mut writer).context(SummaryWriteSnafu { path: tmp_path })?;
+    drop(writer);
+    fs::rename(tmp_path, path).context(SummaryWriteSnafu { path })
+}
+
+// Replaces the placeholders of the path of the summary, and checks its directory.
+fn render_output_path(
+    output_names: &output_name::OutputNames,
+    out_flag: &str,
+    out_path_o: &Option<String>,
+) -> RcvResult<Option<String>> {
+    match out_path_o.as_deref() {
+        Some(template) if output_name::is_template(template) => {
+            let path = Some(output_names.render(template));
+            check_output_paths(&None, out_flag, &path)?;
+            Ok(path)
+        }
+        _ => Ok(out_path_o.clone()),
+    }
+}
+
+// Writes the summary to the output of the election: a file, the standard output ('stdout') or
+// nowhere (empty path).
+fn write_output_summary(
+    summary: &Summary,
+    out_flag: &str,
+    out_path_o: &Option<String>,
+) -> RcvResult<()> {
+    if let Some(out_p) = out_path_o.as_deref() {
+        if out_p == "stdout" {
+            summary
+                .write_pretty(std::io::stdout().lock())
+                .map_err(std::io::Error::from)
+                .context(SummaryWriteSnafu { path: out_p })?;
+        } else if out_p.is_empty() {
+        } else {
+            debug!("Writing output to {}", out_p);
+            write_summary(summary, out_p).map_err(|e| output_path::explain(out_flag, out_p, e))?;
+            info!("Output written to {}", out_p);
+        }
+    }
+    Ok(())
+}
+
+/// A declared candidate that does not take part in the tabulation, with the option of the
+/// configuration that removed it.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct RemovedCandidate {
+    pub name: String,
+    pub removed_by: String,
+}
+
+// The declared candidates removed before the first round. Only the `excluded` flag of the
+// candidates removes candidates for now.
+fn removed_candidates(cands: &[RcvCandidate]) -> Vec<RemovedCandidate> {
+    cands
+        .iter()
+        .filter(|c| c.excluded == Some(true))
+        .map(|c| RemovedCandidate {
+            name: c.name.clone(),
+            removed_by: "excluded flag".to_string(),
+        })
+        .collect()
+}
+
+// Fails before the first round if all the declared candidates are removed: the library would
+// only see undeclared write-ins, without saying why.
+fn audit_candidates(cands: &[RcvCandidate]) -> RcvResult<()> {
+    let removed = removed_candidates(cands);
+    ensure!(
+        removed.is_empty() || removed.len() < cands.len(),
+        NoContinuingCandidatesSnafu { removed }
+    );
+    Ok(())
+}
+
+/// The removed candidates as a text table, with one line per candidate.
+pub fn removed_candidates_table(removed: &[RemovedCandidate]) -> String {
+    let header = ("candidate", "removed by");
+    let width = removed
+        .iter()
+        .map(|r| r.name.chars().count())
+        .chain(std::iter::once(header.0.len()))
+        .max()
+        .unwrap_or_default();
+    let mut lines = vec![
+        format!("{:width$} | {}", header.0, header.1),
+        format!("{}-+-{}", "-".repeat(width), "-".repeat(header.1.len())),
+    ];
+    for r in removed.iter() {
+        lines.push(format!("{:width$} | {}", r.name, r.removed_by));
+    }
+    lines.join("\n")
+}
+
+// The builder of the election, with its ballots. The ballots are moved into the builder.
+fn election_builder(
+    rules: &VoteRules,
+    candidates_o: &Option<Vec<RcvCandidate>>,
+    data: &mut Vec<Ballot>,
+) -> RcvResult<ranked_voting::Builder> {
+    let mut builder = ranked_voting::Builder::new(rules).context(RvVotingSnafu {})?;
+
+    if let Some(cands) = candidates_o {
+        audit_candidates(cands)?;
+        let candidate_names: Vec<String> = cands.iter().map(|c| c.name.clone()).collect();
+        let excluded_names: Vec<String> = removed_candidates(cands)
+            .into_iter()
+            .map(|r| r.name)
+            .collect();
+        let inferred_names: Vec<String> = cands
+            .iter()
+            .filter(|c| c.inferred)
+            .map(|c| c.name.clone())
+            .collect();
+        builder = builder
+            .candidates(&candidate_names)
+            .context(RvVotingSnafu {})?
+            .exclude(&excluded_names)
+            .context(RvVotingSnafu {})?
+            .inferred(&inferred_names)
+            .context(RvVotingSnafu {})?;
+    }
+
+    // The ballots are only taken once the candidates are checked: they are not lost if the
+    // builder fails.
+    for ballot in std::mem::take(data) {
+        builder.add_ballot(ballot).context(RvVotingSnafu {})?;
+    }
+    Ok(builder)
+}
+
+// How a tabulation is run, without effect on its results: the flag that cancels it, the number
+// of candidates listed in the log of each round and the share of the ballots whose reading is
+// logged (--debug-sample-rate).
+#[derive(Clone, Copy, Default)]
+struct TabulationOptions<'a> {
+    cancel: Option<&'a Arc<AtomicBool>>,
+    log_top: Option<usize>,
+    debug_sample_rate: Option<SampleRate>,
+}
+
+// With a cancellation flag, an interrupted tabulation fails with RcvError::Interrupted and the
+// rounds completed so far.
+//
+// The ballots are moved into the builder and given back after the tabulation, instead of being
+// copied: they are still needed after it (for the tabulation without the challenged ballots,
+// --analysis and the outputs).
+fn tabulate(
+    rules: &VoteRules,
+    candidates_o: &Option<Vec<RcvCandidate>>,
+    data: &mut Vec<Ballot>,
+    partial_o: Option<&mut partial::PartialWriter>,
+    options: TabulationOptions,
+) -> RcvResult<VotingResult> {
+    let mut builder = election_builder(rules, candidates_o, data)?;
+    if let Some(flag) = options.cancel {
+        builder = builder.cancellation(flag.clone());
+    }
+    if let Some(top) = options.log_top {
+        builder = builder.log_top(top);
+    }
+    if let Some(rate) = options.debug_sample_rate {
+        builder = builder.debug_sample_rate(rate);
+    }
+    let mut rounds: Vec<RoundStats> = Vec::new();
+    let mut partial_o = partial_o;
+    let res = ranked_voting::run_election_observed(&builder, &mut |rs| {
+        if options.cancel.is_some() {
+            rounds.push(rs.clone());
+        }
+        if let Some(partial) = partial_o.as_mut() {
+            partial.observe(rs);
+        }
+    });
+    *data = builder.into_ballots();
+    match res {
+        Err(VotingErrors::Interrupted) => InterruptedSnafu { rounds }.fail(),
+        res => res.context(RvVotingSnafu {}),
+    }
+}
+
+// The differences between the tabulations with and without the challenged ballots.
+fn challenged_comparison_js(
+    included: &VotingResult,
+    excluded: &VotingResult,
+    num_challenged: u64,
+) -> JSValue {
+    let first_round = |rv: &VotingResult| -> HashMap<String, u64> {
+        rv.round_stats
+            .first()
+            .map(|rs| rs.tally.iter().cloned().collect())
+            .unwrap_or_default()
     };
+    let included_tally = first_round(included);
+    let excluded_tally = first_round(excluded);
+    let mut names: Vec<&String> = included_tally.keys().chain(excluded_tally.keys()).collect();
+    names.sort();
+    names.dedup();
+    let mut delta: JSMap<String, JSValue> = JSMap::new();
+    for name in names {
+        let incl = *included_tally.get(name).unwrap_or(&0) as i64;
+        let excl = *excluded_tally.get(name).unwrap_or(&0) as i64;
+        delta.insert(name.clone(), json!(incl - excl));
+    }
     json!({
-        "config": c,
-         "results": result_stats_to_json(rv) })
+        "challengedBallots": num_challenged,
+        "sameWinners": included.winners == excluded.winners,
+        "winnersIncluded": included.winners,
+        "winnersExcluded": excluded.winners,
+        "firstRoundDelta": delta,
+    })
 }
 
-// override_out_path: used in test mode to disregard any output to disk.
-pub fn run_election(
-    config_path_o: Option<String>,
-    check_summary_path: Option<String>,
-    in_path: Option<String>,
-    out_path: Option<String>,
-    override_out_path: bool,
-    args_o: Option<Args>,
-) -> RcvResult<()> {
+// The tabulations of an election. With 'both', the tabulation without the challenged ballots
+// and their number are also kept.
+struct Tabulation {
+    result: VotingResult,
+    excluded: Option<(VotingResult, u64)>,
+}
+
+impl Tabulation {
+    fn status(&self) -> SummaryStatus {
+        match self.excluded.as_ref() {
+            None => summary_status(&self.result).0,
+            Some((excluded, _)) => {
+                match (summary_status(&self.result).0, summary_status(excluded).0) {
+                    (SummaryStatus::WinnerDeclared, SummaryStatus::WinnerDeclared) => {
+                        SummaryStatus::WinnerDeclared
+                    }
+                    _ => SummaryStatus::NoWinner,
+                }
+            }
+        }
+    }
+
+    // The results for the outputs: the small candidates merged (aggregateBelow) and the
+    // undeclared write-ins renamed (undeclaredWriteInDisplayName). None if the outputs have the
+    // full results.
+    fn published(&self, settings: &OutputSettings) -> RcvResult<Option<Tabulation>> {
+        let below = settings.aggregate_below;
+        let uwi_name = settings.undeclared_write_in_display_name.as_deref();
+        if below.is_none() && uwi_name.is_none() {
+            return Ok(None);
+        }
+        let publish = |result: &VotingResult| {
+            let result = match below {
+                Some(below) => aggregate::aggregate_below(result, below),
+                None => result.clone(),
+            };
+            match uwi_name {
+                Some(name) => aggregate::rename_undeclared(&result, name),
+                None => result,
+            }
+        };
+        Ok(Some(Tabulation {
+            result: publish(&self.result),
+            excluded: self
+                .excluded
+                .as_ref()
+                .map(|(excluded, num)| (publish(excluded), *num)),
+        }))
+    }
+
+    fn summary(&self, config: &RcvConfig) -> RcvResult<Summary<'_>> {
+        match self.excluded.as_ref() {
+            None => Summary::new(config, &self.result),
+            Some((excluded, num_challenged)) => Ok(Summary {
+                included: Some(Box::new(Summary::new(config, &self.result)?)),
+                excluded: Some(Box::new(Summary::new(config, excluded)?)),
+                comparison: Some(challenged_comparison_js(
+                    &self.result,
+                    excluded,
+                    *num_challenged,
+                )),
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+fn tabulate_with_challenged(
+    rules: &VoteRules,
+    candidates_o: &Option<Vec<RcvCandidate>>,
+    data: &mut Vec<Ballot>,
+    challenged_mode: ChallengedMode,
+    partial_o: Option<&mut partial::PartialWriter>,
+    options: TabulationOptions,
+) -> RcvResult<Tabulation> {
+    // Only the challenged ballots are copied when they are set apart.
+    let unchallenged = |data: &[Ballot]| -> Vec<Ballot> {
+        data.iter().filter(|b| !b.challenged).cloned().collect()
+    };
+    match challenged_mode {
+        ChallengedMode::Include => Ok(Tabulation {
+            result: tabulate(rules, candidates_o, data, partial_o, options)?,
+            excluded: None,
+        }),
+        ChallengedMode::Exclude => {
+            let mut ballots = unchallenged(data);
+            Ok(Tabulation {
+                result: tabulate(rules, candidates_o, &mut ballots, partial_o, options)?,
+                excluded: None,
+            })
+        }
+        ChallengedMode::Both => {
+            // The partial results are the ones of the tabulation with all the ballots.
+            let included = tabulate(rules, candidates_o, data, partial_o, options)?;
+            let mut ballots = unchallenged(data);
+            let excluded = tabulate(rules, candidates_o, &mut ballots, None, options)?;
+            let num_challenged: u64 = data.iter().filter(|b| b.challenged).map(|b| b.count).sum();
+            Ok(Tabulation {
+                result: included,
+                excluded: Some((excluded, num_challenged)),
+            })
+        }
+    }
+}
+
+/// The configuration of a run: the configuration file, or a default one for the input file, with
+/// the overrides of the command line. All the commands resolve their configuration here, so that
+/// a flag has the same effect with or without a configuration file.
+///
+/// The precedence is, from the highest: the flags of the command line (`--input-type`,
+/// `--choices`, `--excel-worksheet-name`, `--rule`...), which apply to all the file sources, then
+/// the overlays (`--overlay`, the last one first), then the configuration file, then the
+/// defaults. Only `--rule` warns (`W010`) when it changes a rule
+/// of the configuration.
+pub fn resolve_config(
+    config_path_o: &Option<String>,
+    in_path: &Option<String>,
+    args_o: &Option<Args>,
+) -> RcvResult<RcvConfig> {
+    let overlays: &[String] = args_o.as_ref().map_or(&[], |a| a.overlay.as_slice());
     let mut config: RcvConfig = {
         if let Some(config_path) = config_path_o.as_ref() {
             let config_p = Path::new(config_path.as_str());
             debug!("Opening file {:?}", config_p);
+            let size = fs::metadata(config_p)
+                .context(ConfigOpeningJsonSnafu {})?
+                .len();
+            ensure!(
+                size <= MAX_CONFIG_SIZE,
+                ConfigTooLargeSnafu {
+                    path: config_path,
+                    size,
+                    limit: MAX_CONFIG_SIZE
+                }
+            );
             let config_str =
                 fs::read_to_string(config_path.clone()).context(ConfigOpeningJsonSnafu {})?;
-            serde_json::from_str(&config_str).context(ParsingJsonSnafu {})?
+            let config: RcvConfig = if overlays.is_empty() {
+                serde_json::from_str(&config_str).context(ParsingJsonSnafu {})?
+            } else {
+                let config_js: JSValue =
+                    serde_json::from_str(&config_str).context(ParsingJsonSnafu {})?;
+                with_overlays(config_js, overlays)?
+            };
+            check_config_paths(&config, config_p)?;
+            config
         } else {
-            RcvConfig::config_from_args(&in_path)?
+            let config = RcvConfig::config_from_args(in_path)?;
+            if overlays.is_empty() {
+                config
+            } else {
+                let config_js = serde_json::to_value(&config).context(ParsingJsonSnafu {})?;
+                with_overlays(config_js, overlays)?
+            }
         }
     };
 
     // Adding all the extra rules that may be required from the arguments
-    if let Some(args) = args_o {
+    if let Some(args) = args_o.as_ref() {
         for input in config.cvr_file_sources.iter_mut() {
             if let Some(choices) = args.choices.as_ref() {
                 input.choices = Some(choices.clone());
@@ -430,120 +2089,1076 @@ pub fn run_election(
             if let Some(name) = args.excel_worksheet_name.as_ref() {
                 input.excel_worksheet_name = Some(name.clone());
             }
+            if let Some(jobs) = args.jobs {
+                input.jobs = Some(jobs);
+            }
+            if args.no_sanity_checks {
+                input.sanity_checks = Some(false);
+            }
+            if args.likert {
+                input.layout = Some("ranks-in-cells".to_string());
+            }
+            if args.strict_columns {
+                input.strict_columns = Some(true);
+            }
+            if args.allow_unknown_choices {
+                input.allow_unknown_choices = Some(true);
+            }
+            if args.strict {
+                input.strict_inference = Some(true);
+            }
+            if let Some(seed) = args.shuffle_ballots_seed {
+                input.shuffle_seed = Some(seed);
+            }
+            if let Some(rate) = args.debug_sample_rate {
+                input.debug_sample_rate = Some(rate);
+            }
         }
-    }
-
-    // Moved here because the borrow checker struggles inside the closure.
-    let current_dir = std::env::current_dir()
-        .ok()
-        .context(MissingParentDirSnafu {})?;
-    let root_path: &Path = {
-        if let Some(config_path) = config_path_o.as_ref() {
-            let config_p = Path::new(config_path.as_str());
-            config_p.parent().context(MissingParentDirSnafu {})?
-        } else {
-            current_dir.as_path()
+        if let Some(schema) = args.summary_schema.as_ref() {
+            config.output_settings.summary_schema = Some(schema.clone());
+        }
+        if !args.rule.is_empty() {
+            set_rules(&mut config.rules, &args.rule, config_path_o.is_some())?;
         }
-    };
-    debug!("run_election: config: {:?}", &config);
-
-    // Validate the rules:
-    let rules = validate_rules(&config.rules)?;
-
-    if config.cvr_file_sources.is_empty() {
-        unimplemented!("no file sources detected");
     }
+    set_weights_column(&mut config);
+    check_candidate_keys(&config.candidates)?;
+    check_nota_candidate(&config.rules, &config.candidates)?;
+    aggregate::check_output_names(&config.output_settings, &config.candidates)?;
+    let allow_duplicates = args_o.as_ref().is_some_and(|a| a.allow_duplicate_sources);
+    check_duplicate_sources(&mut config, config_path_o, allow_duplicates)?;
+    Ok(config)
+}
 
-    let config_candidates = if config_path_o.is_none() {
-        None
-    } else {
-        Some(&config.candidates)
+// Two file sources that are the same file (after resolving the relative paths and the symbolic
+// links) with the same options would count each ballot twice: this fails, or the second one is
+// dropped with a warning if the duplicates are allowed. The same file with other options (for
+// example another contest of the export) is read for each source, with a warning.
+fn check_duplicate_sources(
+    config: &mut RcvConfig,
+    config_path_o: &Option<String>,
+    allow_duplicates: bool,
+) -> RcvResult<()> {
+    let root_path = config_root_path(config_path_o)?;
+    // The options of a source, without its path.
+    let options = |cfs: &FileSource| {
+        let mut options = cfs.clone();
+        options.file_path = String::new();
+        options
     };
-
-    let mut validated_candidates_o: Option<Vec<RcvCandidate>> = None;
-    let mut data: Vec<Ballot> = Vec::new();
-    for cfs in config.cvr_file_sources.iter() {
-        let (mut file_data, file_validated_candidates) = read_ranking_data(
-            root_path.as_os_str().to_str().unwrap().to_string(),
-            cfs,
-            config_candidates,
-            &config.rules,
-        )?;
-        data.append(&mut file_data);
-        // TODO: there is a small chance that different files have different set of candidates.
-        // In this case though, it is much more preferable to provide the list of candidates.
-        // Not a high priority.
-        validated_candidates_o = Some(file_validated_candidates);
+    let mut seen: Vec<(PathBuf, FileSource, String)> = Vec::new();
+    let mut sources: Vec<FileSource> = Vec::new();
+    for cfs in config.cvr_file_sources.drain(..) {
+        // The paths that do not exist are left to the readers, which report them.
+        let canon = match fs::canonicalize(root_path.join(&cfs.file_path)) {
+            Ok(p) => p,
+            Err(_) => {
+                sources.push(cfs);
+                continue;
+            }
+        };
+        let cfs_options = options(&cfs);
+        let same_file: Vec<&(PathBuf, FileSource, String)> =
+            seen.iter().filter(|(p, _, _)| p == &canon).collect();
+        if let Some((_, _, first)) = same_file.iter().find(|(_, o, _)| o == &cfs_options) {
+            ensure!(
+                allow_duplicates,
+                DuplicateFileSourceSnafu {
+                    first: first.clone(),
+                    second: cfs.file_path.clone()
+                }
+            );
+            codes::warning(
+                WarningCode::DuplicateSource,
+                format!(
+                    "The file source {} is the same file with the same options as {}: it is only read once",
+                    cfs.file_path, first
+                ),
+            );
+            continue;
+        }
+        if let Some((_, _, first)) = same_file.first() {
+            codes::warning(
+                WarningCode::SameFileSources,
+                format!(
+                    "The file sources {} and {} are the same file with different options: the file is read for each source, check that its ballots are not counted twice",
+                    first, cfs.file_path
+                ),
+            );
+        }
+        seen.push((canon, cfs_options, cfs.file_path.clone()));
+        sources.push(cfs);
     }
+    config.cvr_file_sources = sources;
+    Ok(())
+}
 
-    debug!("run_election:data: {:?} vote records", data.len());
-    assert!(validated_candidates_o.is_some());
+// The names and the non-empty codes of the declared candidates identify them: they must be
+// unique.
+fn check_candidate_keys(candidates: &[RcvCandidate]) -> RcvResult<()> {
+    let mut names: HashSet<CandidateName> = HashSet::new();
+    let mut codes: HashMap<&str, &str> = HashMap::new();
+    for c in candidates.iter() {
+        ensure!(
+            names.insert(CandidateName::new(&c.name, NameNormalization::default())),
+            DuplicateCandidateNameSnafu { name: &c.name }
+        );
+        let code = match c.code.as_deref().map(|code| code.trim()) {
+            Some(code) if !code.is_empty() => code,
+            _ => continue,
+        };
+        if let Some(first) = codes.insert(code, &c.name) {
+            return DuplicateCandidateCodeSnafu {
+                code,
+                first,
+                second: &c.name,
+            }
+            .fail();
+        }
+    }
+    Ok(())
+}
 
-    let mut builder = ranked_voting::Builder::new(&rules).context(RvVotingSnafu {})?;
+// The "None of the above" candidate must be one of the declared candidates: a typo in its name
+// would count it as a regular candidate. Without declared candidates, the candidates are only
+// known from the ballots, and the name is not checked.
+fn check_nota_candidate(rules: &RcvRules, candidates: &[RcvCandidate]) -> RcvResult<()> {
+    let name = match rules.nota_candidate.as_deref() {
+        Some(name) if !candidates.is_empty() => name,
+        _ => return Ok(()),
+    };
+    let key = CandidateName::new(name, NameNormalization::default());
+    ensure!(
+        candidates
+            .iter()
+            .any(|c| CandidateName::new(&c.name, NameNormalization::default()) == key),
+        UnknownNotaCandidateSnafu {
+            name,
+            candidates: candidates
+                .iter()
+                .map(|c| format!("{:?}", c.name))
+                .collect::<Vec<String>>()
+                .join(", "),
+        }
+    );
+    Ok(())
+}
 
-    if let Some(cands) = validated_candidates_o {
-        let mut candidate_names: Vec<String> = Vec::new();
-        for c in cands {
-            if c.excluded != Some(true) {
-                candidate_names.push(c.name);
+// The category column of the weights applies to the sources that do not override it, if their
+// provider reads the categories. The ballots of the other sources get the default weight, as
+// the ballots of a source whose header does not have this column (see
+// FileSource::category_column_index).
+fn set_weights_column(config: &mut RcvConfig) {
+    let weights = match config.weights.as_ref() {
+        Some(w) => w,
+        None => return,
+    };
+    for input in config.cvr_file_sources.iter_mut() {
+        if input.category_column.is_some() {
+            continue;
+        }
+        match find_provider(&input.provider) {
+            Ok(provider) if provider.supports_categories => {
+                input.category_column = Some(weights.column.clone());
+                input.category_from_weights = true;
             }
+            // An unknown provider is reported when the source is read.
+            Ok(provider) => codes::warning(
+                WarningCode::UnknownCategory,
+                format!(
+                    "the provider {} of the file source {} does not read the voter categories: its ballots get the default weight",
+                    provider.name, input.file_path
+                ),
+            ),
+            Err(_) => {}
         }
-        builder = builder
-            .candidates(&candidate_names)
-            .context(RvVotingSnafu {})?;
     }
+}
 
-    for ballot in data {
-        builder.add_vote_2(&ballot).context(RvVotingSnafu {})?;
+// Merges the overlays over the configuration, in their order.
+fn with_overlays(mut config_js: JSValue, overlays: &[String]) -> RcvResult<RcvConfig> {
+    for path in overlays.iter() {
+        overlay::apply_file(&mut config_js, path)?;
     }
+    serde_json::from_value(config_js).context(ParsingJsonSnafu {})
+}
 
-    let result = ranked_voting::run_election(&builder).context(RvVotingSnafu {})?;
-
-    // Assemble the final json
-    let result_js = build_summary_js(&config, &result);
+// The rules of --rule key=value, in their order. They are checked right away, so that a typo
+// is reported before the ballots are read.
+fn set_rules(rules: &mut RcvRules, args: &[String], has_config: bool) -> RcvResult<()> {
+    for arg in args.iter() {
+        let (key, value) = arg
+            .split_once('=')
+            .context(RuleArgumentSnafu { arg: arg.as_str() })?;
+        let key = key.trim();
+        let previous = rules.clone();
+        rules.set_rule(key, value.trim())?;
+        if has_config && *rules != previous {
+            codes::warning(
+                WarningCode::RuleOverride,
+                format!(
+                    "the rule {} of the configuration is overridden by --rule {}",
+                    key, arg
+                ),
+            );
+        }
+    }
+    validate_rules(rules)?;
+    Ok(())
+}
+
+// The configurations are a few kilobytes: a larger file is most likely not a configuration.
+const MAX_CONFIG_SIZE: u64 = 10 * 1024 * 1024;
+
+// The file sources must not be the configuration itself, nor the summary of a previous run.
+// The paths that do not exist are left to the readers, which report them.
+fn check_config_paths(config: &RcvConfig, config_p: &Path) -> RcvResult<()> {
+    let root_path = config_root_path(&Some(config_p.display().to_string()))?;
+    let config_canon = fs::canonicalize(config_p).ok();
+    let summary_canon = config
+        .output_settings
+        .output_directory
+        .as_ref()
+        .and_then(|p| fs::canonicalize(p).ok())
+        .map(|p| p.join("summary.json"));
+    for cfs in config.cvr_file_sources.iter() {
+        let source_canon = match fs::canonicalize(root_path.join(&cfs.file_path)) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        ensure!(
+            Some(&source_canon) != config_canon.as_ref(),
+            SourceIsConfigSnafu {
+                file_path: cfs.file_path.clone()
+            }
+        );
+        if Some(&source_canon) == summary_canon.as_ref() {
+            codes::warning(
+                WarningCode::SourceIsOutput,
+                format!(
+                    "The file source {} is the summary written in the output directory: it will be overwritten by this run",
+                    cfs.file_path
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+// The directory against which the file paths of the configuration are resolved.
+fn config_root_path(config_path_o: &Option<String>) -> RcvResult<PathBuf> {
+    if let Some(config_path) = config_path_o.as_ref() {
+        let config_p = Path::new(config_path.as_str());
+        Ok(config_p
+            .parent()
+            .context(MissingParentDirSnafu {})?
+            .to_path_buf())
+    } else {
+        std::env::current_dir()
+            .ok()
+            .context(MissingParentDirSnafu {})
+    }
+}
+
+/// Serves the JSON-RPC requests of the standard input (serve --stdio).
+pub fn run_serve(stdio: bool) -> RcvResult<()> {
+    ensure!(stdio, ServeTransportSnafu {});
+    serve::serve_stdio()
+}
+
+/// Writes the outputs of --out-flat-csv, --out-rctab-csv, --out-fairvote-row and --out-xlsx
+/// from a summary instead of a tabulation (render --from-summary).
+pub fn run_render(from_summary: String, args_o: Option<Args>) -> RcvResult<()> {
+    let (config, result) = render::result_from_summary(&from_summary)?;
+    let mut output_names =
+        output_name::OutputNames::new(&config.output_settings, SystemTime::now());
+    output_names.set_winners(result.winners.as_deref());
+    let name_order = config.output_settings.name_order()?;
+    let options = RunOptions::from_args(&args_o)?;
+    output_path::check_options(&options, None)?;
+    if let Some(row_path) = options.out_fairvote_row.as_ref() {
+        fairvote::append_fairvote_row(row_path, &config, &result)
+            .map_err(|e| output_path::explain("--out-fairvote-row", row_path, e))?;
+        info!(
+            "FairVote row written to {} ({})",
+            row_path,
+            render::RENDERED_NOTE
+        );
+    }
+    if let Some(xlsx_path) = options.out_xlsx.as_ref() {
+        write_xlsx(xlsx_path, &config, &result, &name_order)
+            .map_err(|e| output_path::explain("--out-xlsx", xlsx_path, e))?;
+    }
+    if let Some(flat_path) = options.out_flat_csv.as_ref() {
+        let flat_path = &output_names.render(flat_path);
+        output_path::check("--out-flat-csv", flat_path)?;
+        io_csv::write_flat_csv(flat_path, &result, &name_order)
+            .map_err(|e| output_path::explain("--out-flat-csv", flat_path, e))?;
+        info!(
+            "Flat table of the rounds written to {} ({})",
+            flat_path,
+            render::RENDERED_NOTE
+        );
+    }
+    if let Some(rctab_path) = options.out_rctab_csv.as_ref() {
+        let rctab_path = &output_names.render(rctab_path);
+        output_path::check("--out-rctab-csv", rctab_path)?;
+        rctab_csv::write_rctab_csv(rctab_path, &config, &result)
+            .map_err(|e| output_path::explain("--out-rctab-csv", rctab_path, e))?;
+        info!(
+            "RCTab summary table written to {} ({})",
+            rctab_path,
+            render::RENDERED_NOTE
+        );
+    }
+    Ok(())
+}
+
+/// Prints the most common ranking patterns of the ballots, without tabulating the election.
+pub fn run_patterns(
+    config_path_o: Option<String>,
+    in_path: Option<String>,
+    top: usize,
+    as_json: bool,
+    args_o: Option<Args>,
+) -> RcvResult<()> {
+    let fail_on = fail_on_codes(&args_o)?;
+    codes::take_warnings();
+    let summary = read_patterns(&config_path_o, &in_path, &args_o)?;
+    codes::check_fail_on(&codes::take_warnings(), &fail_on)?;
+    if as_json {
+        let pretty_js = serde_json::to_string_pretty(&patterns::patterns_to_json(&summary, top))
+            .context(ParsingJsonSnafu {})?;
+        println!("{}", pretty_js);
+    } else {
+        println!("{}", patterns::patterns_to_text(&summary, top));
+    }
+    Ok(())
+}
+
+fn read_patterns(
+    config_path_o: &Option<String>,
+    in_path: &Option<String>,
+    args_o: &Option<Args>,
+) -> RcvResult<patterns::PatternSummary> {
+    let config = resolve_config(config_path_o, in_path, args_o)?;
+    let root_path = config_root_path(config_path_o)?;
+    let config_candidates = if config_path_o.is_none() {
+        None
+    } else {
+        Some(&config.candidates)
+    };
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for cfs in config.cvr_file_sources.iter() {
+        let (parsed_ballots, candidates) = read_parsed_ballots(
+            root_path.as_os_str().to_str().unwrap().to_string(),
+            cfs,
+            config_candidates,
+        )?;
+        patterns::add_patterns(&mut counts, &parsed_ballots, &candidates, cfs);
+    }
+    Ok(patterns::summarize_patterns(
+        &counts,
+        &config.output_settings.name_order()?,
+    ))
+}
+
+/// A file source that could not be read, and was skipped (--skip-bad-sources).
+#[derive(Debug, Clone)]
+pub struct FailedSource {
+    pub file_path: String,
+    pub provider: String,
+    pub error: String,
+}
+
+fn failed_sources_to_json(failed_sources: &[FailedSource]) -> JSValue {
+    let l: Vec<JSValue> = failed_sources
+        .iter()
+        .map(|fs| {
+            json!({
+                "filePath": fs.file_path,
+                "provider": fs.provider,
+                "error": fs.error,
+            })
+        })
+        .collect();
+    JSValue::Array(l)
+}
+
+// Reads and validates the ballots of all the file sources. If `failed_sources` is set, the
+// sources that cannot be read are recorded there and skipped, instead of failing. The number
+// of ballots of each source that is read is added to `source_totals`.
+fn read_election_data(
+    config: &RcvConfig,
+    config_path_o: &Option<String>,
+    category_totals: &mut CategoryTotals,
+    source_totals: &mut Vec<SourceTotal>,
+    mut failed_sources: Option<&mut Vec<FailedSource>>,
+    mut orders: Option<&mut Vec<preflib::BallotOrder>>,
+) -> RcvResult<(Vec<Ballot>, Option<Vec<RcvCandidate>>)> {
+    let root_path = config_root_path(config_path_o)?;
+    let config_candidates = if config_path_o.is_none() {
+        None
+    } else {
+        Some(&config.candidates)
+    };
+
+    if let Some(candidates) = config_candidates {
+        sanity::check_declared_near_misses(candidates);
+    }
+
+    let mut validated_candidates_o: Option<Vec<RcvCandidate>> = None;
+    let mut data: Vec<Ballot> = Vec::new();
+    let mut num_parsed: u64 = 0;
+    let mut last_error: Option<RcvError> = None;
+    for cfs in config.cvr_file_sources.iter() {
+        let totals_before = category_totals.clone();
+        let res = read_ranking_data(
+            root_path.as_os_str().to_str().unwrap().to_string(),
+            cfs,
+            config_candidates,
+            &config.rules,
+            config.weights.as_ref(),
+            category_totals,
+            orders.as_deref_mut(),
+        );
+        let (mut file_data, file_validated_candidates, total) = match (res, failed_sources.as_mut())
+        {
+            (Ok(x), _) => x,
+            (Err(e), Some(failed)) => {
+                // Many errors do not have a message: the debug form is more useful then.
+                let inner = match &e {
+                    RcvError::OpeningFile { source, .. } => source.as_ref(),
+                    e => e,
+                };
+                let error = match inner.to_string() {
+                    s if s.is_empty() => format!("{:?}", inner),
+                    s => s,
+                };
+                codes::warning(
+                    WarningCode::SkippedSource,
+                    format!(
+                        "SKIPPING file source {} ({}): [{}] {}",
+                        cfs.file_path,
+                        cfs.provider,
+                        e.code().code(),
+                        error
+                    ),
+                );
+                failed.push(FailedSource {
+                    file_path: cfs.file_path.clone(),
+                    provider: cfs.provider.clone(),
+                    error,
+                });
+                *category_totals = totals_before;
+                last_error = Some(e);
+                continue;
+            }
+            (Err(e), None) => return Err(e),
+        };
+        data.append(&mut file_data);
+        num_parsed += total.parsed;
+        source_totals.push(total);
+        // The declared candidates are the same for all the sources. The inferred candidates
+        // of the next sources are added after the ones already seen.
+        match validated_candidates_o.as_mut() {
+            None => validated_candidates_o = Some(file_validated_candidates),
+            Some(cs) => {
+                for c in file_validated_candidates {
+                    if !cs.iter().any(|c2| c2.name == c.name) {
+                        cs.push(c);
+                    }
+                }
+            }
+        }
+    }
+
+    // All the sources were skipped: there is nothing to tabulate.
+    if let Some(e) = last_error.filter(|_| validated_candidates_o.is_none()) {
+        return Err(e);
+    }
+    // A source may be empty, but not all of them.
+    ensure!(
+        num_parsed > 0,
+        NoBallotsSnafu {
+            paths: source_totals
+                .iter()
+                .map(|t| t.file_path.clone())
+                .collect::<Vec<String>>()
+        }
+    );
+    debug!("read_election_data: {:?} vote records", data.len());
+    assert!(validated_candidates_o.is_some());
+    Ok((data, validated_candidates_o))
+}
+
+/// Writes a test case with a sample of the ballots of the election in `out_dir`.
+pub fn run_make_fixture(
+    config_path_o: Option<String>,
+    out_dir: String,
+    max_ballots: usize,
+    args_o: Option<Args>,
+) -> RcvResult<()> {
+    let config_path = config_path_o.context(MissingInputSnafu {})?;
+    fixture::make_fixture(&config_path, &out_dir, max_ballots, &args_o)
+}
+
+/// Tabulates the election and compares the rounds with the official rounds.
+///
+/// Returns true if all the official rounds match the tabulation.
+pub fn run_verify_rounds(
+    config_path_o: Option<String>,
+    in_path: Option<String>,
+    official_path: String,
+    as_json: bool,
+    args_o: Option<Args>,
+) -> RcvResult<bool> {
+    let fail_on = fail_on_codes(&args_o)?;
+    codes::take_warnings();
+    let report = verify_rounds(&config_path_o, &in_path, &official_path, &args_o)?;
+    let warnings = codes::take_warnings();
+    if as_json {
+        let mut js = verify_rounds::report_to_json(&report);
+        js["warnings"] = codes::warnings_to_json(&warnings);
+        let pretty_js = serde_json::to_string_pretty(&js).context(ParsingJsonSnafu {})?;
+        println!("{}", pretty_js);
+    } else {
+        println!("{}", verify_rounds::report_to_text(&report));
+    }
+    codes::check_fail_on(&warnings, &fail_on)?;
+    Ok(report.is_match())
+}
+
+fn verify_rounds(
+    config_path_o: &Option<String>,
+    in_path: &Option<String>,
+    official_path: &str,
+    args_o: &Option<Args>,
+) -> RcvResult<ranked_voting::verify::VerificationReport> {
+    let challenged_mode =
+        ChallengedMode::parse(args_o.as_ref().and_then(|a| a.with_challenged.as_deref()))?;
+    ensure!(
+        challenged_mode != ChallengedMode::Both,
+        VerifyRoundsChallengedBothSnafu {}
+    );
+    let config = resolve_config(config_path_o, in_path, args_o)?;
+    let rules = validate_rules(&config.rules)?;
+    let official = verify_rounds::read_official_rounds(official_path)?;
+    let (mut data, validated_candidates_o) = read_election_data(
+        &config,
+        config_path_o,
+        &mut CategoryTotals::new(),
+        &mut Vec::new(),
+        None,
+        None,
+    )?;
+    if challenged_mode == ChallengedMode::Exclude {
+        data.retain(|b| !b.challenged);
+    }
+    snapshot::ValidatedElection::new(&rules, validated_candidates_o, &data)?
+        .verify_round_sequence(&rules, &official)
+}
+
+#[cfg(feature = "xlsx")]
+fn write_xlsx(
+    path: &str,
+    config: &RcvConfig,
+    result: &VotingResult,
+    order: &NameOrder,
+) -> RcvResult<()> {
+    xlsx::write_workbook(path, config, result, order)
+}
+
+#[cfg(not(feature = "xlsx"))]
+fn write_xlsx(
+    _path: &str,
+    _config: &RcvConfig,
+    _result: &VotingResult,
+    _order: &NameOrder,
+) -> RcvResult<()> {
+    Err(RcvError::OptionNotEnabled {
+        option: "--out-xlsx".to_string(),
+        feature: "xlsx".to_string(),
+    })
+}
+
+/// Runs the election of a resolved configuration (see `resolve_config`), with the options of the
+/// run (see `RunOptions::from_args`). The relative paths of
+/// the configuration are resolved against the directory of `config_path_o`. The warnings emitted
+/// since the last `codes::take_warnings`, including the ones of the resolution of the
+/// configuration, count for `--fail-on`. `override_out_path` is used in test mode to disregard
+/// any output to disk: the summary only goes to `out_path`, and nowhere if it is not set or
+/// empty.
+///
+/// Once the output paths are checked, any error still writes a summary with the `error` status
+/// and the message of the error, unless the summary was already written.
+pub fn run_election(
+    config: RcvConfig,
+    options: &RunOptions,
+    config_path_o: &Option<String>,
+    check_summary_path: Option<String>,
+    out_path: Option<String>,
+    override_out_path: bool,
+) -> RcvResult<SummaryStatus> {
+    let mut error_output: Option<(String, Option<String>)> = None;
+    let res = run_election_checked(
+        &config,
+        options,
+        config_path_o,
+        check_summary_path,
+        out_path,
+        override_out_path,
+        &mut error_output,
+    );
+    if let (Err(e), Some((out_flag, out_path_o))) = (res.as_ref(), error_output) {
+        // The error of the run is returned even if its summary cannot be written.
+        if let Err(write_e) =
+            write_output_summary(&error_summary(&config, e), &out_flag, &out_path_o)
+        {
+            codes::warning(
+                WarningCode::UnwrittenOutput,
+                format!(
+                    "run_election: the summary of the error is not written: {}",
+                    write_e
+                ),
+            );
+        }
+    }
+    res
+}
+
+// The run of the election. `error_output` is the output of the summary (its flag and its path)
+// while an error must still write a summary.
+fn run_election_checked(
+    config: &RcvConfig,
+    options: &RunOptions,
+    config_path_o: &Option<String>,
+    check_summary_path: Option<String>,
+    out_path: Option<String>,
+    override_out_path: bool,
+    error_output: &mut Option<(String, Option<String>)>,
+) -> RcvResult<SummaryStatus> {
+    let started = SystemTime::now();
+    debug!("run_election: config: {:?}", config);
+    // The paths of the outputs are checked before the (possibly long) tabulation.
+    let default_out_path = config.output_settings.output_directory.clone().map(|p| {
+        let pb: PathBuf = [p, "summary.json".to_string()].iter().collect();
+        pb.as_os_str().to_str().unwrap().to_string()
+    });
+    // The summary of outputDirectory is reported with the option of the configuration.
+    let out_flag = if override_out_path || out_path.is_some() {
+        "--out"
+    } else {
+        "outputDirectory"
+    };
+    let out_path_o = if override_out_path {
+        out_path
+    } else {
+        out_path.or(default_out_path)
+    };
+    check_output_paths(&check_summary_path, out_flag, &out_path_o)?;
+    output_path::check_options(options, config_path_o.as_deref())?;
+    // The templated paths are checked with the values known before the tabulation.
+    let mut output_names = output_name::OutputNames::new(&config.output_settings, started);
+    let templates = [
+        (out_flag, out_path_o.as_ref()),
+        ("--out-flat-csv", options.out_flat_csv.as_ref()),
+        ("--out-rctab-csv", options.out_rctab_csv.as_ref()),
+    ];
+    for (flag, template_o) in templates {
+        if let Some(path) = template_o.and_then(|t| output_names.render_before_winner(t)) {
+            output_path::check(flag, &path)?;
+        }
+    }
+    // The summary of an error has no winner: its path is not checked.
+    *error_output = Some((
+        out_flag.to_string(),
+        out_path_o.as_deref().map(|p| output_names.render(p)),
+    ));
+
+    // Validate the rules:
+    let rules = validate_rules(&config.rules)?;
+    let name_order = config.output_settings.name_order()?;
+    // The rounding of the percentages is used after the tabulation: it is checked now.
+    config.output_settings.percent_rounding()?;
+
+    // A second run on the same inputs is refused before the tabulation.
+    let ledger_o = options.ledger.as_ref();
+    let ledger_inputs = match ledger_o {
+        Some(ledger_path) => {
+            let inputs = ledger::input_digests(
+                config,
+                &config_root_path(config_path_o)?,
+                options.load_validated.as_deref(),
+            );
+            ledger::check_duplicate(ledger_path, &inputs, options.allow_duplicate_ledger)?;
+            inputs
+        }
+        None => Vec::new(),
+    };
+
+    let mut category_totals = CategoryTotals::new();
+    let mut source_totals: Vec<SourceTotal> = Vec::new();
+    let mut failed_sources: Vec<FailedSource> = Vec::new();
+    let load_path_o = options.load_validated.as_ref();
+    let preflib_path_o = options.export_preflib.as_ref();
+    let mut orders: Vec<preflib::BallotOrder> = Vec::new();
+    let (mut data, validated_candidates_o) = if let Some(load_path) = load_path_o {
+        ensure!(preflib_path_o.is_none(), PreflibFromSnapshotSnafu {});
+        let election = snapshot::ValidatedElection::load(load_path)?;
+        election.check_rules(load_path, &rules, options.allow_rules_mismatch)?;
+        (election.ballots, election.candidates)
+    } else {
+        ensure!(!config.cvr_file_sources.is_empty(), NoFileSourcesSnafu {});
+        read_election_data(
+            config,
+            config_path_o,
+            &mut category_totals,
+            &mut source_totals,
+            Some(&mut failed_sources).filter(|_| options.skip_bad_sources),
+            Some(&mut orders).filter(|_| preflib_path_o.is_some()),
+        )?
+    };
+    // The inferred candidates are only known once the ballots are read.
+    if let Some(candidates) = validated_candidates_o.as_ref() {
+        aggregate::check_output_names(&config.output_settings, candidates)?;
+    }
+
+    if let Some(save_path) = options.save_validated.as_ref() {
+        let election =
+            snapshot::ValidatedElection::new(&rules, validated_candidates_o.clone(), &data)?;
+        election
+            .save(save_path)
+            .map_err(|e| output_path::explain("--save-validated", save_path, e))?;
+    }
+
+    // The outputs with one row per ballot are checked for the rare ranking patterns.
+    let privacy = options.privacy;
+    if let Some(export_path) = options.export_simple_csv.as_ref() {
+        let ballots = privacy.check("--export-simple-csv", &data, |b| {
+            (b.candidates.clone(), b.count)
+        })?;
+        let labels = io_csv::SimpleCsvLabels::from_sources(&config.cvr_file_sources);
+        let file_name = Path::new(export_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let source = io_csv::write_simple_csv(export_path, &file_name, &ballots, &labels)
+            .map_err(|e| output_path::explain("--export-simple-csv", export_path, e))?;
+        let export_config_path =
+            io_csv::write_simple_csv_config(export_path, config, source, config_path_o.as_deref())?;
+        info!(
+            "Validated ballots exported to {}, with their configuration in {}",
+            export_path, export_config_path
+        );
+    }
+    if let Some(preflib_path) = preflib_path_o {
+        let orders = privacy.check("--export-preflib", &orders, |(ranks, count)| {
+            (ranks.clone(), *count)
+        })?;
+        preflib::write_toi(
+            preflib_path,
+            &config.output_settings.contest_name,
+            validated_candidates_o.as_deref().unwrap_or_default(),
+            &orders,
+        )
+        .map_err(|e| output_path::explain("--export-preflib", preflib_path, e))?;
+        info!("Ballots exported in the PrefLib format to {}", preflib_path);
+    }
+
+    let challenged_mode = options.challenged_mode;
+
+    // The audit is a gate before the tabulation, on the ballots that are tabulated (all of
+    // them with 'both').
+    if let Some(audit_path) = options.audit_first_choices.as_ref() {
+        let ballots: Vec<Ballot> = data
+            .iter()
+            .filter(|b| challenged_mode != ChallengedMode::Exclude || !b.challenged)
+            .cloned()
+            .collect();
+        audit::check_first_choices(
+            audit_path,
+            &ballots,
+            &validated_candidates_o,
+            options.audit_tolerance,
+        )?;
+    }
+
+    let mut partial_o = match options.out_partial.as_ref() {
+        Some(dir) => Some(partial::PartialWriter::new(dir, config)?),
+        None => None,
+    };
+
+    // Ctrl-C only interrupts the tabulation itself.
+    let interrupt_o = options.interrupt.as_ref();
+    let sigint_o = interrupt_o.map(interrupt::install);
+    let outcome = tabulate_with_challenged(
+        &rules,
+        &validated_candidates_o,
+        &mut data,
+        challenged_mode,
+        partial_o.as_mut(),
+        TabulationOptions {
+            cancel: interrupt_o,
+            log_top: options.log_top,
+            debug_sample_rate: options.debug_sample_rate,
+        },
+    );
+    drop(sigint_o);
+    // An interrupted tabulation only writes the rounds done so far, and no other output.
+    if let Err(RcvError::Interrupted { rounds }) = outcome.as_ref() {
+        codes::warning(
+            WarningCode::TabulationInterrupted,
+            format!(
+                "run_election: the tabulation was interrupted after {} rounds",
+                rounds.len()
+            ),
+        );
+        if !rounds.is_empty() {
+            let summary = Summary::interrupted(config, rounds)?;
+            let out_path_o = render_output_path(&output_names, out_flag, &out_path_o)?;
+            *error_output = None;
+            write_output_summary(&summary, out_flag, &out_path_o)?;
+        }
+        return Ok(SummaryStatus::Interrupted);
+    }
+    // Tabulation failures still produce a summary, which is written before returning the error.
+    let (tabulation_o, tabulation_error) = match outcome {
+        Ok(tabulation) => (Some(tabulation), None),
+        Err(e @ RcvError::RvVoting { .. }) => (None, Some(e)),
+        Err(e) => return Err(e),
+    };
+    // The outputs may merge the small candidates and rename the undeclared write-ins, while the
+    // tabulation and the comparison with the reference use the full results.
+    let published_o: Option<Tabulation> = match tabulation_o.as_ref() {
+        Some(tabulation) => tabulation.published(&config.output_settings)?,
+        None => None,
+    };
+    if let Some(tabulation) = tabulation_o.as_ref() {
+        output_names.set_winners(tabulation.result.winners.as_deref());
+    }
+    // Assemble the final summary
+    let (mut summary, mut status) = match (tabulation_o.as_ref(), tabulation_error.as_ref()) {
+        (Some(tabulation), _) => {
+            let result = &tabulation.result;
+            info!(
+                "Competitiveness: {}",
+                competitiveness_to_text(&ranked_voting::metrics::competitiveness(result))
+            );
+            // The ballots of the tabulation in the result (all of them with 'both').
+            let tabulated = || {
+                data.iter()
+                    .filter(|b| challenged_mode != ChallengedMode::Exclude || !b.challenged)
+            };
+            let impact = rules_impact::RulesImpact::new(&rules, result, tabulated());
+            info!("Impact of the rules on the ballots:\n{}", impact.to_text());
+            let published = published_o.as_ref().unwrap_or(tabulation);
+            if let Some(row_path) = options.out_fairvote_row.as_ref() {
+                fairvote::append_fairvote_row(row_path, config, &published.result)
+                    .map_err(|e| output_path::explain("--out-fairvote-row", row_path, e))?;
+                info!("FairVote row written to {}", row_path);
+            }
+            if let Some(xlsx_path) = options.out_xlsx.as_ref() {
+                write_xlsx(xlsx_path, config, &published.result, &name_order)
+                    .map_err(|e| output_path::explain("--out-xlsx", xlsx_path, e))?;
+            }
+            if let Some(flat_path) = options.out_flat_csv.as_ref() {
+                let flat_path = &output_names.render(flat_path);
+                output_path::check("--out-flat-csv", flat_path)?;
+                io_csv::write_flat_csv(flat_path, &published.result, &name_order)
+                    .map_err(|e| output_path::explain("--out-flat-csv", flat_path, e))?;
+                info!("Flat table of the rounds written to {}", flat_path);
+            }
+            if let Some(rctab_path) = options.out_rctab_csv.as_ref() {
+                let rctab_path = &output_names.render(rctab_path);
+                output_path::check("--out-rctab-csv", rctab_path)?;
+                rctab_csv::write_rctab_csv(rctab_path, config, &published.result)
+                    .map_err(|e| output_path::explain("--out-rctab-csv", rctab_path, e))?;
+                info!("RCTab summary table written to {}", rctab_path);
+            }
+            let mut summary = published.summary(config)?;
+            if config.output_settings.summary_schema()? == SummarySchema::V2 {
+                summary.rules_impact = Some(summary::RulesImpactSummary::new(&impact));
+            }
+            if options.analysis == Some(analysis::Analysis::HeadToHeadWinner) {
+                let ballots: Vec<Ballot> = tabulated().cloned().collect();
+                let rows = analysis::head_to_head_winner(result, &ballots);
+                info!(
+                    "Head-to-head comparison of the eliminated candidates with the winners:\n{}",
+                    analysis::head_to_head_to_text(&rows)
+                );
+                summary.analysis =
+                    Some(json!({ "headToHeadWinner": analysis::head_to_head_to_json(&rows) }));
+            }
+            (summary, tabulation.status())
+        }
+        (None, Some(e)) => (error_summary(config, e), SummaryStatus::Error),
+        (None, None) => unreachable!("run_election: no tabulation and no error"),
+    };
+    if !failed_sources.is_empty() {
+        summary.failed_sources = Some(failed_sources_to_json(&failed_sources));
+        if status != SummaryStatus::Error {
+            status = SummaryStatus::Partial;
+            summary.reason = Some(format!(
+                "{} of {} file sources could not be read, {}",
+                failed_sources.len(),
+                config.cvr_file_sources.len(),
+                summary.reason.as_deref().unwrap_or_default()
+            ));
+            summary.status = Some(status.label());
+        }
+    }
+    // The categories are not kept in the snapshots.
+    if config.weights.is_some() && load_path_o.is_none() {
+        summary.categories = Some(category_totals_to_json(&category_totals));
+    }
+    // The sources are not kept in the snapshots either.
+    let emit_provenance =
+        options.emit_provenance || config.output_settings.summary_schema()? == SummarySchema::V2;
+    if emit_provenance && load_path_o.is_none() {
+        summary.config_mut().sources = Some(source_totals_to_json(&source_totals));
+    }
+    if let Some(metrics_path) = options.out_metrics.as_deref() {
+        let source_metrics: Vec<metrics::SourceMetrics> =
+            source_totals.iter().map(|t| t.metrics.clone()).collect();
+        metrics::write_metrics(metrics_path, &source_metrics)
+            .map_err(|e| output_path::explain("--out-metrics", metrics_path, e))?;
+        info!("Metrics written to {}", metrics_path);
+    }
+    let mut warnings = codes::take_warnings();
+    if !warnings.is_empty() && config.output_settings.summary_schema()? == SummarySchema::V2 {
+        summary.warnings = Some(codes::warnings_to_json(&warnings));
+    }
+
+    if log_enabled!(Level::Debug) {
+        let pretty_js_stats =
+            serde_json::to_string_pretty(&summary).context(ParsingJsonSnafu {})?;
+        debug!("stats:{}", pretty_js_stats);
+    }
 
-    let pretty_js_stats = serde_json::to_string_pretty(&result_js).context(ParsingJsonSnafu {})?;
-    debug!("stats:{}", pretty_js_stats);
+    if options.verify_output {
+        let mismatches = reconcile::summary_mismatches(&summary.to_json()?);
+        for m in mismatches.iter() {
+            codes::warning(
+                WarningCode::VerificationMismatch,
+                format!("unreconciled summary: {}", m),
+            );
+        }
+        ensure!(
+            mismatches.is_empty(),
+            OutputUnreconciledSnafu { mismatches }
+        );
+    }
 
-    // The reference summary, if provided for comparison
-    if let Some(ref_summary_path) = check_summary_path {
+    // The reference summary, if provided for comparison. A partial tabulation is not compared.
+    if !failed_sources.is_empty() && check_summary_path.is_some() {
+        codes::warning(
+            WarningCode::ReferenceNotChecked,
+            "run_election: some file sources were skipped, the reference summary is not checked"
+                .to_string(),
+        );
+    } else if let Some(ref_summary_path) = check_summary_path {
         let summary_ref = read_summary(ref_summary_path).context(ReferenceOpeningFileSnafu {})?;
         let pretty_js_summary_ref =
             serde_json::to_string_pretty(&summary_ref).context(ParsingJsonSnafu {})?;
-        if pretty_js_summary_ref != pretty_js_stats {
-            warn!("Found differences with the reference string");
+        let mut summary_js = summary.to_json()?;
+        if let (Some(tabulation), Some(_)) = (tabulation_o.as_ref(), published_o.as_ref()) {
+            with_full_results(&mut summary_js, tabulation.summary(config)?.to_json()?);
+        }
+        let compared = summary_for_comparison(&summary_js, &summary_ref);
+        if options.reference_strict {
+            let mismatches = strict_mismatches(&compared, &summary_ref);
+            for m in mismatches.iter() {
+                codes::warning(
+                    WarningCode::VerificationMismatch,
+                    format!("reference mismatch: {}", m),
+                );
+            }
+            ensure!(
+                mismatches.is_empty(),
+                ReferenceStrictMismatchSnafu { mismatches }
+            );
+        }
+        let pretty_js_compared =
+            serde_json::to_string_pretty(&compared).context(ParsingJsonSnafu {})?;
+        if pretty_js_summary_ref != pretty_js_compared {
             print_diff(
                 pretty_js_summary_ref.as_str(),
-                pretty_js_stats.as_ref(),
+                pretty_js_compared.as_ref(),
                 "\n",
             );
-            whatever!("Difference detected between calculated summary and reference summary")
+            return ReferenceMismatchSnafu {}.fail();
         }
     }
 
-    let default_out_path = config.output_settings.output_directory.map(|p| {
-        let pb: PathBuf = vec![p, "summary.json".to_string()].iter().collect();
-        pb.as_os_str().to_str().unwrap().to_string()
-    });
+    let out_path_o = render_output_path(&output_names, out_flag, &out_path_o)?;
+    *error_output = None;
+    write_output_summary(&summary, out_flag, &out_path_o)?;
 
-    if let Some(out_p) = if override_out_path {
-        out_path
-    } else {
-        out_path.or(default_out_path)
-    } {
-        if out_p == "stdout" {
-            print!("{}", pretty_js_stats);
-        } else if out_p.is_empty() {
-        } else {
-            debug!("Writing output to {}", out_p);
-            fs::write(out_p.clone(), pretty_js_stats).context(SummaryWriteSnafu {
-                path: out_p.clone(),
-            })?;
-            info!("Output written to {}", out_p);
+    // The partial files are only removed once the tabulation succeeded and its summary is
+    // written. The tabulation is complete: a failure of the partial files is only a warning.
+    if let (Some(partial), Some(_)) = (partial_o, tabulation_o.as_ref()) {
+        if let Err(e) = partial.finish(options.keep_partials) {
+            codes::warning(WarningCode::UnwrittenOutput, format!("partial: {}", e));
         }
     }
 
-    Ok(())
+    if let Some(ledger_path) = ledger_o {
+        let entry = ledger::LedgerEntry {
+            rules: &rules,
+            inputs: &ledger_inputs,
+            ballots: Some(source_totals.iter().map(|t| t.ballots).sum())
+                .filter(|_| load_path_o.is_none()),
+            votes: data.iter().map(|b| b.count).sum(),
+            status,
+            winners: tabulation_o
+                .as_ref()
+                .and_then(|t| t.result.winners.as_deref()),
+            output: out_path_o.as_deref().filter(|p| !p.is_empty()),
+        };
+        ledger::append(ledger_path, &entry)
+            .map_err(|e| output_path::explain("--ledger", ledger_path, e))?;
+        info!("Ledger entry appended to {}", ledger_path);
+    }
+
+    if let Some(e) = tabulation_error {
+        return Err(e);
+    }
+    // The warnings raised after the summary is complete also fail the run.
+    warnings.extend(codes::take_warnings());
+    codes::check_fail_on(&warnings, &options.fail_on)?;
+    Ok(status)
+}
+
+// The warnings passed to --fail-on.
+fn fail_on_codes(args_o: &Option<Args>) -> RcvResult<BTreeSet<WarningCode>> {
+    match args_o.as_ref().and_then(|a| a.fail_on.as_deref()) {
+        Some(s) => codes::parse_fail_on(s),
+        None => Ok(BTreeSet::new()),
+    }
+}
+
+// Resolves the configuration of the paths (see `resolve_config`) and runs the election with it,
+// as the command line does. The warnings of a previous run are dropped first.
+fn run_election_at(
+    config_path_o: Option<String>,
+    check_summary_path: Option<String>,
+    in_path: Option<String>,
+    out_path: Option<String>,
+    override_out_path: bool,
+    args_o: Option<Args>,
+) -> RcvResult<SummaryStatus> {
+    codes::take_warnings();
+    let config = resolve_config(&config_path_o, &in_path, &args_o)?;
+    let options = RunOptions::from_args(&args_o)?;
+    run_election(
+        config,
+        &options,
+        &config_path_o,
+        check_summary_path,
+        out_path,
+        override_out_path,
+    )
 }
 
 fn run_election_test(test_name: &str, config_lpath: &str, summary_lpath: &str, is_local: bool) {
@@ -554,8 +3169,12 @@ fn run_election_test(test_name: &str, config_lpath: &str, summary_lpath: &str, i
         "/home/tjhunter/work/elections/rcv/src/test/resources/network/brightspots/rcv/test_data",
     )
     };
+    run_election_test_in(test_dir, test_name, config_lpath, summary_lpath)
+}
+
+fn run_election_test_in(test_dir: &str, test_name: &str, config_lpath: &str, summary_lpath: &str) {
     info!("Running test {}", test_name);
-    let res = run_election(
+    let res = run_election_at(
         Some(format!("{}/{}/{}", test_dir, test_name, config_lpath)),
         Some(format!("{}/{}/{}", test_dir, test_name, summary_lpath)),
         None,
@@ -564,388 +3183,7312 @@ fn run_election_test(test_name: &str, config_lpath: &str, summary_lpath: &str, i
         None,
     );
     if let Err(e) = res {
-        warn!("Error occured {:?}", e);
         eprintln!("An error occured {:?}", e);
         if let Some(bt) = ErrorCompat::backtrace(&e) {
             eprintln!("trace: {}", bt);
         } else {
             eprintln!("No trace found");
         }
-        panic!("Exiting hard");
+        panic!("Exiting hard");
+    }
+}
+
+pub fn test_wrapper(test_name: &str) {
+    run_election_test(
+        test_name,
+        format!("{}_config.json", test_name).as_str(),
+        format!("{}_expected_summary.json", test_name).as_str(),
+        false,
+    )
+}
+
+pub fn test_wrapper_local(test_name: &str) {
+    run_election_test(
+        test_name,
+        format!("{}_config.json", test_name).as_str(),
+        format!("{}_expected_summary.json", test_name).as_str(),
+        true,
+    )
+}
+
+/// Exports the validated ballots of a local test in the simple CSV format, then runs the
+/// election again from the exported file and checks it against the same reference summary.
+pub fn test_wrapper_local_roundtrip(test_name: &str) {
+    let test_dir = format!("./tests/{}", test_name);
+    let config_path = format!("{}/{}_config.json", test_dir, test_name);
+    let summary_path = format!("{}/{}_expected_summary.json", test_dir, test_name);
+    let out_dir = std::env::temp_dir().join(format!("timrcv_roundtrip_{}", test_name));
+    fs::create_dir_all(&out_dir).unwrap();
+    let export_path = out_dir.join("ballots.csv").display().to_string();
+    // The configuration of the export is never overwritten.
+    let _ = fs::remove_file(out_dir.join("ballots_config.json"));
+
+    // The fixtures are small: each ballot is written, whatever its ranking pattern.
+    let args = Args::parse_from([
+        "timrcv",
+        "--export-simple-csv",
+        export_path.as_str(),
+        "--privacy-override",
+    ]);
+    run_election_at(
+        Some(config_path.clone()),
+        Some(summary_path.clone()),
+        None,
+        None,
+        true,
+        Some(args),
+    )
+    .unwrap();
+
+    // The configuration written with the export.
+    let roundtrip_config_path = out_dir.join("ballots_config.json");
+    run_election_at(
+        Some(roundtrip_config_path.display().to_string()),
+        Some(summary_path),
+        None,
+        None,
+        true,
+        None,
+    )
+    .unwrap();
+}
+
+/// Runs a local test with its ballots in several orders (--shuffle-ballots-seed): the summary
+/// must always be the reference summary.
+pub fn test_wrapper_local_shuffled(test_name: &str) {
+    let test_dir = format!("./tests/{}", test_name);
+    let config_path = format!("{}/{}_config.json", test_dir, test_name);
+    let summary_path = format!("{}/{}_expected_summary.json", test_dir, test_name);
+    for seed in ["1", "2", "3"] {
+        let args = Args::parse_from(["timrcv", "--shuffle-ballots-seed", seed]);
+        if let Err(e) = run_election_at(
+            Some(config_path.clone()),
+            Some(summary_path.clone()),
+            None,
+            None,
+            true,
+            Some(args),
+        ) {
+            panic!("{} with the seed {}: {:?}", test_name, seed, e);
+        }
+    }
+}
+
+/// Runs a local test with --verify-output: the totals of the summary must reconcile.
+pub fn test_wrapper_local_verified(test_name: &str) -> RcvResult<SummaryStatus> {
+    let test_dir = format!("./tests/{}", test_name);
+    run_election_at(
+        Some(format!("{}/{}_config.json", test_dir, test_name)),
+        Some(format!("{}/{}_expected_summary.json", test_dir, test_name)),
+        None,
+        None,
+        true,
+        Some(Args::parse_from(["timrcv", "--verify-output"])),
+    )
+}
+
+/// A new empty directory for the files of a test, in the temporary directory.
+pub fn test_temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes the configuration in the directory of a test, runs the election with the arguments of
+/// the command line, and returns the summary written next to the configuration.
+pub fn run_json_config(dir: &Path, config: &JSValue, args: &[&str]) -> RcvResult<JSValue> {
+    let config_path = dir.join("config.json").display().to_string();
+    fs::write(&config_path, config.to_string()).unwrap();
+    let out_path = dir.join("summary.json").display().to_string();
+    let _ = fs::remove_file(&out_path);
+    run_election_at(
+        Some(config_path),
+        None,
+        None,
+        Some(out_path.clone()),
+        true,
+        Some(Args::parse_from(
+            std::iter::once("timrcv").chain(args.iter().copied()),
+        )),
+    )?;
+    Ok(serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::test_wrapper;
+    use super::test_wrapper_local;
+    use super::test_wrapper_local_roundtrip;
+    use super::test_wrapper_local_shuffled;
+    use super::test_wrapper_local_verified;
+    use super::{
+        add_category_totals, debug_sampled, read_election_data, read_parsed_ballots,
+        read_ranking_data, removed_candidates_table, resolve_config, run_election, run_election_at,
+        run_election_test, run_election_test_in, run_render, shuffle_ballots,
+        summary_for_comparison, tabulate, truncated_ranks, validate_ballots, validate_rules,
+        weighted_count, Args, CategoryTotals, FileSource, JSValue, ParsedBallot, RcvCandidate,
+        RcvConfig, RcvError, RcvResult, RcvRules, RunOptions, Summary, SummarySchema,
+        SummaryStatus, TabulationOptions, PROVIDERS,
+    };
+    use super::{provider_list, provider_manifest, run_json_config, test_temp_dir};
+    use crate::rcv::config_reader::NumberLocale;
+    use crate::rcv::io_common::parse_integer;
+    #[cfg(feature = "parquet")]
+    use crate::rcv::io_parquet;
+    use crate::rcv::{
+        aggregate, analysis, codes, collation, dedup, fixture, io_cdf, io_csv, io_dominion, io_ess,
+        io_msforms, output_name, output_path, overlay, partial, patterns, preflib, read_patterns,
+        reconcile, render, rules_impact, serve, snapshot, summary, verify_rounds, NameOrder,
+        OutputSettings,
+    };
+    use clap::Parser;
+    use ranked_voting::{
+        run_election1, Ballot, BallotChoice, OverVoteScope, TieBreakMode, VoteRules, VotingResult,
+    };
+    use serde_json::json;
+    use std::collections::HashSet;
+    use std::fs;
+    #[cfg(feature = "parquet")]
+    use std::path::Path;
+
+    fn build_summary_js(config: &RcvConfig, rv: &VotingResult) -> RcvResult<JSValue> {
+        Summary::new(config, rv)?.to_json()
+    }
+
+    fn result_stats_to_json(rv: &VotingResult, schema: SummarySchema) -> Vec<JSValue> {
+        summary::round_summaries(rv, schema, false)
+            .iter()
+            .map(|round| serde_json::to_value(round).unwrap())
+            .collect()
+    }
+
+    // #[test]
+    // fn _2013_minneapolis_mayor() {
+    //     test_wrapper("2013_minneapolis_mayor");
+    // }
+
+    // Takes about 100s to complete on github actions, disabled for the time being.
+    #[test]
+    #[ignore = "SLOW"]
+    fn _2013_minneapolis_mayor_scale() {
+        test_wrapper("2013_minneapolis_mayor_scale");
+    }
+
+    #[test]
+    fn _2015_portland_mayor() {
+        test_wrapper("2015_portland_mayor");
+    }
+
+    #[test]
+    #[ignore = "TODO implement clearBallot provider"]
+    fn clear_ballot_kansas_primary() {
+        // TODO P1
+        test_wrapper("clear_ballot_kansas_primary");
+    }
+
+    #[test]
+    #[ignore = "TODO GH-17"]
+    fn continue_tabulation_test() {
+        test_wrapper("continue_tabulation_test");
+    }
+
+    #[test]
+    #[ignore = "GH-17"]
+    fn continue_until_two_with_batch_elimination_test() {
+        test_wrapper("continue_until_two_with_batch_elimination_test");
+    }
+
+    #[test]
+    fn dominion_alaska() {
+        test_wrapper("dominion_alaska");
+    }
+
+    #[test]
+    fn dominion_kansas() {
+        test_wrapper("dominion_kansas");
+    }
+
+    #[test]
+    #[ignore = "TODO GH-10"]
+    fn dominion_multi_file() {
+        test_wrapper("dominion_multi_file");
+    }
+
+    #[test]
+    fn dominion_no_precinct_data() {
+        test_wrapper("dominion_no_precinct_data");
+    }
+
+    #[test]
+    fn dominion_wyoming() {
+        test_wrapper("dominion_wyoming");
+    }
+
+    #[test]
+    fn duplicate_test() {
+        test_wrapper("duplicate_test");
+    }
+
+    #[test]
+    #[ignore = "TODO P3 stopCountingAndAsk"]
+    fn excluded_test() {
+        test_wrapper("excluded_test");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 exhaustIfMultipleContinuing"]
+    fn exhaust_if_multiple_continuing() {
+        test_wrapper("exhaust_if_multiple_continuing");
+    }
+
+    #[test]
+    #[ignore = "TODO implement hart input"]
+    fn hart_cedar_park_school_board() {
+        test_wrapper("hart_cedar_park_school_board");
+    }
+
+    #[test]
+    #[ignore = "TODO implement hart input"]
+    fn hart_travis_county_officers() {
+        test_wrapper("hart_travis_county_officers");
+    }
+
+    #[test]
+    #[ignore = "Alreday caught by the parser"]
+    fn invalid_params_test() {
+        test_wrapper("invalid_params_test");
+    }
+
+    #[test]
+    #[ignore = "Alreday caught by the parser"]
+    fn invalid_sources_test() {
+        test_wrapper("invalid_sources_test");
+    }
+
+    #[test]
+    #[ignore]
+    fn minimum_threshold_test() {
+        // TODO P1
+        test_wrapper("minimum_threshold_test");
+    }
+
+    #[test]
+    #[ignore = "TODO P3 stopCountingAndAsk"]
+    fn minneapolis_multi_seat_threshold() {
+        test_wrapper("minneapolis_multi_seat_threshold");
+    }
+
+    #[test]
+    fn missing_precinct_example() {
+        test_wrapper("missing_precinct_example");
+    }
+
+    #[test]
+    #[ignore = "TODO P1 bottomsUpUsingPercentageThreshold"]
+    fn multi_seat_bottoms_up_with_threshold() {
+        test_wrapper("multi_seat_bottoms_up_with_threshold");
+    }
+
+    #[test]
+    #[ignore = "TODO P1 implement multiWinnerAllowMultipleWinnersPerRound"]
+    fn multi_seat_uwi_test() {
+        test_wrapper("multi_seat_uwi_test");
+    }
+
+    #[test]
+    #[ignore = "TODO implement cdf provider"]
+    fn nist_xml_cdf_2() {
+        test_wrapper("nist_xml_cdf_2");
+    }
+
+    #[test]
+    #[ignore = "TODO GH-16"]
+    fn no_one_meets_minimum() {
+        test_wrapper("no_one_meets_minimum");
+    }
+
+    #[test]
+    fn precinct_example() {
+        test_wrapper("precinct_example");
+    }
+
+    #[test]
+    #[ignore = "TODO P3 stopCountingAndAsk"]
+    fn sample_interactive_tiebreak() {
+        test_wrapper("sample_interactive_tiebreak");
+    }
+
+    #[test]
+    #[ignore = "TODO P1 multiPassIrv"]
+    fn sequential_with_batch() {
+        test_wrapper("sequential_with_batch");
+    }
+
+    #[test]
+    #[ignore = "TODO P1 multiPassIrv"]
+    fn sequential_with_continue_until_two() {
+        test_wrapper("sequential_with_continue_until_two");
+    }
+
+    #[test]
+    fn skip_to_next_test() {
+        test_wrapper("skip_to_next_test");
+    }
+
+    #[test]
+    fn test_set_0_skipped_first_choice() {
+        test_wrapper("test_set_0_skipped_first_choice");
+    }
+
+    #[test]
+    fn test_set_1_exhaust_at_overvote() {
+        test_wrapper("test_set_1_exhaust_at_overvote");
+    }
+
+    #[test]
+    fn test_set_2_overvote_skip_to_next() {
+        test_wrapper("test_set_2_overvote_skip_to_next");
+    }
+
+    #[test]
+    fn test_set_3_skipped_choice_exhaust() {
+        test_wrapper("test_set_3_skipped_choice_exhaust");
+    }
+
+    #[test]
+    fn test_set_4_skipped_choice_next() {
+        test_wrapper("test_set_4_skipped_choice_next");
+    }
+
+    #[test]
+    fn test_set_5_two_skipped_choice_exhaust() {
+        test_wrapper("test_set_5_two_skipped_choice_exhaust");
+    }
+
+    #[test]
+    fn test_set_6_duplicate_exhaust() {
+        test_wrapper("test_set_6_duplicate_exhaust");
+    }
+
+    #[test]
+    fn test_set_7_duplicate_skip_to_next() {
+        test_wrapper("test_set_7_duplicate_skip_to_next");
+    }
+
+    #[test]
+    fn test_set_8_multi_cdf() {
+        test_wrapper("test_set_8_multi_cdf");
+    }
+
+    #[test]
+    #[ignore = "TODO P1 election mode multiWinnerAllowOnlyOneWinnerPerRound"]
+    fn test_set_allow_only_one_winner_per_round() {
+        test_wrapper("test_set_allow_only_one_winner_per_round");
+    }
+
+    #[test]
+    #[ignore = "P3 tiebreak stopCountingAndAsk"]
+    fn test_set_multi_winner_fractional_threshold() {
+        test_wrapper("test_set_multi_winner_fractional_threshold");
+    }
+
+    #[test]
+    #[ignore = "P3 tiebreak stopCountingAndAsk"]
+    fn test_set_multi_winner_whole_threshold() {
+        test_wrapper("test_set_multi_winner_whole_threshold");
+    }
+
+    #[test]
+    #[ignore = "P1 unknown overvote rule: exhaustIfMultipleContinuing"]
+    fn test_set_overvote_delimiter() {
+        test_wrapper("test_set_overvote_delimiter");
+    }
+
+    #[test]
+    #[ignore = "TODO P1 random"]
+    fn test_set_treat_blank_as_undeclared_write_in() {
+        test_wrapper("test_set_treat_blank_as_undeclared_write_in");
+    }
+
+    #[test]
+    #[ignore = "P1 tiebreak generatePermutation"]
+    fn tiebreak_generate_permutation_test() {
+        test_wrapper("tiebreak_generate_permutation_test");
+    }
+
+    #[test]
+    #[ignore = "P1 tiebreak previousRoundCountsThenRandom"]
+    fn tiebreak_previous_round_counts_then_random_test() {
+        test_wrapper("tiebreak_previous_round_counts_then_random_test");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 output format is different"]
+    fn tiebreak_seed_test() {
+        test_wrapper("tiebreak_seed_test");
+    }
+
+    #[test]
+    fn tiebreak_use_permutation_in_config_test() {
+        test_wrapper("tiebreak_use_permutation_in_config_test");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 provider cdf"]
+    fn unisyn_xml_cdf_city_chief_of_police() {
+        test_wrapper("unisyn_xml_cdf_city_chief_of_police");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 provider cdf"]
+    fn unisyn_xml_cdf_city_coroner() {
+        test_wrapper("unisyn_xml_cdf_city_coroner");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 provider cdf"]
+    fn unisyn_xml_cdf_city_council_member() {
+        test_wrapper("unisyn_xml_cdf_city_council_member");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 provider cdf"]
+    fn unisyn_xml_cdf_city_mayor() {
+        test_wrapper("unisyn_xml_cdf_city_mayor");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 provider cdf"]
+    fn unisyn_xml_cdf_city_tax_collector() {
+        test_wrapper("unisyn_xml_cdf_city_tax_collector");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 provider cdf"]
+    fn unisyn_xml_cdf_county_coroner() {
+        test_wrapper("unisyn_xml_cdf_county_coroner");
+    }
+
+    #[test]
+    #[ignore = "TODO P2 provider cdf"]
+    fn unisyn_xml_cdf_county_sheriff() {
+        test_wrapper("unisyn_xml_cdf_county_sheriff");
+    }
+
+    #[test]
+    fn uwi_cannot_win_test() {
+        test_wrapper("uwi_cannot_win_test");
+    }
+
+    // ********** Tests specific to timrcv *************
+
+    #[test]
+    fn msforms_1() {
+        test_wrapper_local("msforms_1");
+    }
+
+    #[test]
+    fn msforms_likert() {
+        test_wrapper_local("msforms_likert");
+    }
+
+    #[test]
+    fn msforms_likert_transpose() {
+        test_wrapper_local("msforms_likert_transpose");
+    }
+
+    #[test]
+    fn msforms_worksheets() {
+        test_wrapper_local("msforms_worksheets");
+    }
+
+    #[test]
+    fn msforms_hidden_sheet() {
+        // The hidden settings sheet and the chart sheet are skipped.
+        test_wrapper_local("msforms_hidden_sheet");
+    }
+
+    #[test]
+    fn excel_ambiguous_worksheet() {
+        let path = "./tests/msforms_worksheets/msforms_worksheets.xlsx";
+        let cfs: FileSource = serde_json::from_value(json!({
+            "provider": "msforms_ranking",
+            "filePath": path,
+            "idColumnIndex": "A",
+            "firstVoteColumnIndex": "F",
+        }))
+        .unwrap();
+        let err = io_msforms::read_msforms_ranking(path.to_string(), &cfs).unwrap_err();
+        assert!(matches!(*err, RcvError::ExcelAmbiguousWorksheet { .. }));
+        assert_eq!(err.code(), codes::ErrorCode::UnreadableInput);
+        let message = err.to_string();
+        assert!(message.contains("'Form Responses 1', 'Form Responses 2'"));
+        assert!(message.contains("--excel-worksheet-name"));
+    }
+
+    #[test]
+    fn resolve_config_precedence() {
+        let config_path = Some("./tests/msforms_likert/msforms_likert_config.json".to_string());
+        let sources = |args_o: Option<Args>| {
+            resolve_config(&config_path, &None, &args_o)
+                .unwrap()
+                .cvr_file_sources
+        };
+        // The configuration file applies without the flags.
+        for args_o in [None, Some(Args::parse_from(["timrcv"]))] {
+            let source = &sources(args_o)[0];
+            assert_eq!(source.provider, "msforms_likert");
+            assert_eq!(source.choices.as_ref().map(|c| c.len()), Some(5));
+            assert_eq!(source.excel_worksheet_name.as_deref(), Some("Form1"));
+        }
+        // The flags take precedence over the configuration file.
+        let args = Args::parse_from([
+            "timrcv",
+            "--input-type",
+            "msforms_ranking",
+            "--choices",
+            "Option 1",
+            "--choices",
+            "Option 2",
+        ]);
+        let source = &sources(Some(args))[0];
+        assert_eq!(source.provider, "msforms_ranking");
+        assert_eq!(
+            source.choices,
+            Some(vec!["Option 1".to_string(), "Option 2".to_string()])
+        );
+        assert_eq!(source.excel_worksheet_name.as_deref(), Some("Form1"));
+        // And over the defaults without a configuration file.
+        let args = Args::parse_from(["timrcv", "--input-type", "cdf", "--choices", "A"]);
+        let config = resolve_config(&None, &Some("ballots.json".to_string()), &Some(args)).unwrap();
+        assert_eq!(config.cvr_file_sources[0].provider, "cdf");
+        assert_eq!(
+            config.cvr_file_sources[0].choices,
+            Some(vec!["A".to_string()])
+        );
+    }
+
+    #[test]
+    fn nota_candidate_must_be_declared() {
+        let out_dir = std::env::temp_dir().join("timrcv_nota_candidate");
+        fs::create_dir_all(&out_dir).unwrap();
+        let config_path = Some("./tests/csv_simple_1/csv_simple_1_config.json".to_string());
+        let with_nota = |nota: &str| {
+            let path = out_dir.join("nota.json").display().to_string();
+            fs::write(&path, json!({"rules": {"notaCandidate": nota}}).to_string()).unwrap();
+            Some(Args::parse_from(["timrcv", "--overlay", path.as_str()]))
+        };
+
+        // A typo in the name of the candidate.
+        let err = resolve_config(&config_path, &None, &with_nota("Non of the above")).unwrap_err();
+        assert!(
+            matches!(&err, RcvError::UnknownNotaCandidate { name, .. } if name == "Non of the above"),
+            "{:?}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            "notaCandidate: \"Non of the above\" is not a declared candidate (the candidates are \"A\", \"B\", \"C\", \"D\")"
+        );
+        assert_eq!(err.code().code(), "E001");
+
+        // The names are compared as when the ballots are matched with the candidates.
+        assert!(resolve_config(&config_path, &None, &with_nota(" D ")).is_ok());
+        // Without declared candidates, the name is not checked.
+        let args = Args::parse_from(["timrcv", "--rule", "notaCandidate=N"]);
+        assert!(resolve_config(&None, &Some("ballots.csv".to_string()), &Some(args)).is_ok());
+    }
+
+    #[test]
+    fn candidate_codes() {
+        let out_dir = std::env::temp_dir().join("timrcv_candidate_codes");
+        fs::create_dir_all(&out_dir).unwrap();
+        let config_path = "./tests/csv_simple_1/csv_simple_1_config.json".to_string();
+        let with_codes = |codes: JSValue, flags: &[&str]| {
+            let path = out_dir.join("codes.json").display().to_string();
+            fs::write(&path, json!({ "candidates": codes }).to_string()).unwrap();
+            let mut args = vec!["timrcv", "--overlay", path.as_str()];
+            args.extend_from_slice(flags);
+            Args::parse_from(args)
+        };
+
+        // Two candidates with the same code.
+        let args = with_codes(
+            json!([{"name": "A", "code": "X"}, {"name": "C", "code": " X"}]),
+            &[],
+        );
+        let err = resolve_config(&Some(config_path.clone()), &None, &Some(args)).unwrap_err();
+        assert!(
+            matches!(&err, RcvError::DuplicateCandidateCode { code, first, second }
+                if code == "X" && first == "A" && second == "C"),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.code().code(), "E001");
+
+        // Only A and C have a code: the tally by code only has them, with the votes of the tally.
+        let args = with_codes(
+            json!([{"name": "A", "code": "a1"}, {"name": "B", "code": ""}, {"name": "C", "code": "c3"}]),
+            &["--summary-schema", "v2"],
+        );
+        let out_path = out_dir.join("summary.json").display().to_string();
+        run_election_at(
+            Some(config_path.clone()),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        let by_code: Vec<JSValue> = summary["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["tallyByCode"].clone())
+            .collect();
+        assert_eq!(
+            by_code,
+            vec![
+                json!({"a1": "40", "c3": "20"}),
+                json!({"a1": "40", "c3": "20"}),
+                json!({"a1": "60"}),
+            ]
+        );
+
+        // Without codes, there is no tally by code.
+        let args = Args::parse_from(["timrcv", "--summary-schema", "v2"]);
+        run_election_at(
+            Some(config_path),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert!(summary["results"][0].get("tallyByCode").is_none());
+    }
+
+    #[test]
+    fn config_overlays() {
+        let out_dir = std::env::temp_dir().join("timrcv_overlays");
+        fs::create_dir_all(&out_dir).unwrap();
+        let write = |name: &str, js: JSValue| {
+            let path = out_dir.join(name).display().to_string();
+            fs::write(&path, js.to_string()).unwrap();
+            path
+        };
+        let exclude_b = write(
+            "exclude_b.json",
+            json!({
+                "candidates": [{"name": "B", "excluded": true}],
+                "rules": {"overvoteRule": "alwaysSkipToNextRank"},
+            }),
+        );
+        let day_2 = write(
+            "day_2.json",
+            json!({"rules": {"rulesDescription": "Canvass day 2"}}),
+        );
+        let config_path = "./tests/csv_inactive_ballots/csv_inactive_ballots_config.json";
+
+        // The changes of an overlay, key by key.
+        let mut config_js: JSValue =
+            serde_json::from_str(&fs::read_to_string(config_path).unwrap()).unwrap();
+        assert_eq!(
+            overlay::apply_file(&mut config_js, &exclude_b).unwrap(),
+            vec![
+                "candidates[B].excluded: (none) -> true".to_string(),
+                "rules.overvoteRule: \"exhaustImmediately\" -> \"alwaysSkipToNextRank\""
+                    .to_string(),
+            ]
+        );
+
+        // The overlays apply in their order, and the flags after them.
+        let args = Args::parse_from([
+            "timrcv",
+            "--overlay",
+            exclude_b.as_str(),
+            "--overlay",
+            day_2.as_str(),
+            "--rule",
+            "maxSkippedRanksAllowed=2",
+        ]);
+        let config =
+            resolve_config(&Some(config_path.to_string()), &None, &Some(args.clone())).unwrap();
+        let names: Vec<(&str, Option<bool>)> = config
+            .candidates
+            .iter()
+            .map(|c| (c.name.as_str(), c.excluded))
+            .collect();
+        assert_eq!(names, vec![("A", None), ("B", Some(true)), ("C", None)]);
+        assert_eq!(config.rules._overvote_rule, "alwaysSkipToNextRank");
+        assert_eq!(
+            config.rules.rules_description.as_deref(),
+            Some("Canvass day 2")
+        );
+        assert_eq!(config.rules.max_skipped_ranks_allowed, "2");
+
+        // Without B and with the overvotes skipped, C wins in the first round instead of A.
+        let out_path = out_dir.join("summary.json").display().to_string();
+        run_election_at(
+            Some(config_path.to_string()),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(summary["results"][0]["tally"], json!({"A": "8", "C": "9"}));
+        assert_eq!(
+            summary["results"][0]["tallyResults"][0]["elected"],
+            json!("C")
+        );
+
+        let not_an_object = write("list.json", json!([]));
+        let args = Args::parse_from(["timrcv", "--overlay", not_an_object.as_str()]);
+        assert!(matches!(
+            resolve_config(&Some(config_path.to_string()), &None, &Some(args)),
+            Err(RcvError::InvalidOverlay { .. })
+        ));
+    }
+
+    #[test]
+    fn serve_session() {
+        let mut session = serve::Session::default();
+        let mut request = |id: u32, method: &str, params: JSValue| -> JSValue {
+            let line = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+            serde_json::from_str(&session.handle_line(&line.to_string()).unwrap()).unwrap()
+        };
+        let config_path = "./tests/csv_inactive_ballots/csv_inactive_ballots_config.json";
+
+        assert_eq!(
+            request(1, "tabulate", json!({})),
+            json!({"jsonrpc": "2.0", "id": 1, "error": {
+                "code": -32600, "message": "no configuration loaded"}})
+        );
+        assert_eq!(
+            request(2, "loadConfig", json!({ "path": config_path }))["result"],
+            json!({"candidates": 3, "sources": 1})
+        );
+        assert_eq!(
+            request(3, "parseSources", json!({}))["result"],
+            json!({"ballots": 20, "votes": 20})
+        );
+        assert_eq!(
+            request(4, "tabulate", json!({}))["result"],
+            json!({"reason": "winner declared: A", "rounds": 2,
+                "status": "winnerDeclared", "winners": ["A"]})
+        );
+        assert_eq!(
+            request(5, "getRounds", json!({}))["result"][0]["tally"],
+            json!({"A": "6", "B": "5", "C": "4"})
+        );
+        // The rules change without reading the ballots again.
+        assert_eq!(
+            request(
+                6,
+                "tabulate",
+                json!({"rules": {"overvoteRule": "alwaysSkipToNextRank"}})
+            )["result"]["winners"],
+            json!(["A"])
+        );
+        assert_eq!(
+            request(7, "getRounds", json!({}))["result"][0]["tally"],
+            json!({"A": "8", "B": "5", "C": "4"})
+        );
+        // The errors of timrcv have their stable codes.
+        let error = &request(8, "tabulate", json!({"rules": {"noSuchRule": "1"}}))["error"];
+        assert_eq!(error["code"], json!(-32001));
+        assert_eq!(
+            error["data"],
+            json!({"code": "E001", "name": "invalid-configuration"})
+        );
+        let error = &request(9, "loadConfig", json!({"path": "./tests/missing.json"}))["error"];
+        assert_eq!(error["data"]["code"], json!("E001"));
+        assert_eq!(
+            request(10, "count", json!({}))["error"]["code"],
+            json!(-32601)
+        );
+        assert_eq!(
+            request(11, "shutdown", json!({})),
+            json!({"jsonrpc": "2.0", "id": 11, "result": null})
+        );
+        assert!(session.shutdown);
+        let parse_error: JSValue =
+            serde_json::from_str(&session.handle_line("{").unwrap()).unwrap();
+        assert_eq!(parse_error["error"]["code"], json!(-32700));
+
+        // The notifications get no response, even for an error.
+        let mut session = serve::Session::default();
+        let notification = |method: &str, params: JSValue| {
+            json!({"jsonrpc": "2.0", "method": method, "params": params}).to_string()
+        };
+        assert_eq!(
+            session.handle_line(&notification("loadConfig", json!({ "path": config_path }))),
+            None
+        );
+        assert_eq!(session.handle_line(&notification("count", json!({}))), None);
+        assert_eq!(
+            session.handle_line(&notification("shutdown", json!({}))),
+            None
+        );
+        assert!(session.shutdown);
+    }
+
+    #[test]
+    fn msforms_extra_column() {
+        test_wrapper_local("msforms_extra_column");
+
+        // The votes of the extra column are ignored, with a warning.
+        let config = resolve_config(
+            &Some("./tests/msforms_extra_column/msforms_extra_column_config.json".to_string()),
+            &None,
+            &None,
+        )
+        .unwrap();
+        let candidate_names: Vec<String> =
+            config.candidates.iter().map(|c| c.name.clone()).collect();
+        codes::take_warnings();
+        io_msforms::read_msforms_likert(
+            "./tests/msforms_extra_column/msforms_extra_column.xlsx".to_string(),
+            &config.cvr_file_sources[0],
+            &candidate_names,
+        )
+        .unwrap();
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::ExtraCandidateColumns);
+        assert!(
+            warnings[0].message.contains("\"D\" (3 responses)"),
+            "{}",
+            warnings[0].message
+        );
+
+        let err = run_election_at(
+            Some("./tests/msforms_extra_column/msforms_extra_column_config.json".to_string()),
+            None,
+            None,
+            Some("".to_string()),
+            true,
+            Some(Args::parse_from(["timrcv", "--strict-columns"])),
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), codes::ErrorCode::UnknownCandidates);
+        match err {
+            RcvError::OpeningFile { source, .. } => {
+                assert!(matches!(*source, RcvError::ExtraCandidateColumns { .. }));
+                assert!(source.to_string().contains("\"D\" (3 responses)"));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn rules_impact() {
+        // The two ballots exhausted by their overvote are more than the margin of one vote.
+        test_wrapper_local("rules_impact");
+
+        let config_path = "./tests/rules_impact/rules_impact_config.json".to_string();
+        let mut config = resolve_config(&Some(config_path.clone()), &None, &None).unwrap();
+        let (mut data, candidates_o) = read_election_data(
+            &config,
+            &Some(config_path),
+            &mut CategoryTotals::new(),
+            &mut Vec::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        let rules = validate_rules(&config.rules).unwrap();
+        let result = tabulate(
+            &rules,
+            &candidates_o,
+            &mut data,
+            None,
+            TabulationOptions::default(),
+        )
+        .unwrap();
+        let impact = rules_impact::RulesImpact::new(&rules, &result, data.iter());
+        assert_eq!(impact.final_round_margin, Some(1));
+        assert_eq!(impact.rules[0].rule, "overvoteRule");
+        assert_eq!(impact.rules[0].ballots, 2);
+        assert!(impact.exceeds_margin());
+        assert!(impact
+            .to_text()
+            .contains("rule impact exceeds winning margin (1 votes)"));
+
+        // When the overvotes are skipped, the same ballots are affected, with one rank each.
+        config
+            .rules
+            .set_rule("overvoteRule", "alwaysSkipToNextRank")
+            .unwrap();
+        let rules = validate_rules(&config.rules).unwrap();
+        let result = tabulate(
+            &rules,
+            &candidates_o,
+            &mut data,
+            None,
+            TabulationOptions::default(),
+        )
+        .unwrap();
+        let impact = rules_impact::RulesImpact::new(&rules, &result, data.iter());
+        let overvotes = &impact.rules[0];
+        assert_eq!(
+            (overvotes.effect, overvotes.ballots, overvotes.ranks),
+            ("skipped", 2, Some(2))
+        );
+        assert!(impact.exceeds_margin());
+
+        // Without a rule that affects more ballots than the margin, there is no note.
+        data.truncate(13);
+        let result = tabulate(
+            &rules,
+            &candidates_o,
+            &mut data,
+            None,
+            TabulationOptions::default(),
+        )
+        .unwrap();
+        let impact = rules_impact::RulesImpact::new(&rules, &result, data.iter());
+        assert!(!impact.exceeds_margin());
+        assert!(!impact.to_text().contains("sensitivity"));
+
+        // Only the repeated rankings followed by another ranking change how a ballot is read,
+        // within the 3 rankings allowed.
+        let ballot = |choices: &[&str]| {
+            Ballot::new(
+                choices
+                    .iter()
+                    .map(|c| match *c {
+                        "" => BallotChoice::Undervote,
+                        name => BallotChoice::Candidate(name.to_string()),
+                    })
+                    .collect(),
+                1,
+            )
+        };
+        let duplicates = [
+            ballot(&["A", "A"]),
+            ballot(&["A", "B", "A", ""]),
+            ballot(&["A", "A", "B"]),
+            ballot(&["B", "B", "A"]),
+            ballot(&["A", "A", "A", "B"]),
+        ];
+        let impact = rules_impact::RulesImpact::new(&rules, &result, duplicates.iter());
+        let repeated = &impact.rules[1];
+        assert_eq!(repeated.rule, "exhaustOnDuplicateCandidate");
+        assert_eq!((repeated.ballots, repeated.ranks), (2, Some(2)));
+
+        // The counts of the summary are strings.
+        let js = serde_json::to_value(summary::RulesImpactSummary::new(&impact)).unwrap();
+        assert_eq!(
+            js["finalRoundMargin"],
+            json!(impact.final_round_margin.unwrap().to_string())
+        );
+        assert_eq!(js["rules"][1]["ballots"], json!("2"));
+        assert_eq!(js["rules"][1]["ranks"], json!("2"));
+    }
+
+    #[test]
+    fn csv_likert_extra_choices() {
+        // The form gained the ranks "4th" and "5th" during the collection.
+        test_wrapper_local("csv_likert_extra_choices");
+
+        let config_path = "./tests/csv_likert_extra_choices/csv_likert_extra_choices_config.json";
+        let config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        let names: Vec<String> = config.candidates.iter().map(|c| c.name.clone()).collect();
+        let path = "./tests/csv_likert_extra_choices/example.csv".to_string();
+        codes::take_warnings();
+        let ballots =
+            io_csv::read_csv_likert(path.clone(), &config.cvr_file_sources[0], &names).unwrap();
+        assert_eq!(
+            ballots[4].choices,
+            vec![vec!["Alice"], vec!["Carol"], vec!["Bob"]]
+        );
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::UnknownChoices);
+        assert!(
+            warnings[0]
+                .message
+                .contains("\"4th\" appeared 3 times, \"5th\" appeared 3 times"),
+            "{}",
+            warnings[0].message
+        );
+
+        let mut cfs = config.cvr_file_sources[0].clone();
+        cfs.allow_unknown_choices = None;
+        let path = "./tests/csv_likert_extra_choices/example.csv".to_string();
+        let err = io_csv::read_csv_likert(path.clone(), &cfs, &names).unwrap_err();
+        assert!(matches!(*err, RcvError::UnknownChoiceLabels { .. }));
+        assert_eq!(err.code(), codes::ErrorCode::MalformedBallots);
+        assert!(
+            err.to_string().contains("\"4th\" appeared 3 times"),
+            "{}",
+            err
+        );
+
+        // The flag allows them for all the sources.
+        let args = Args::parse_from(["timrcv", "--allow-unknown-choices"]);
+        cfs.choices = Some(vec!["1st".to_string(), "2nd".to_string()]);
+        let mut config = config.clone();
+        config.cvr_file_sources = vec![cfs];
+        let dir = std::env::temp_dir().join("timrcv_extra_choices");
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(&path, dir.join("example.csv")).unwrap();
+        let edited_path = dir.join("config.json").display().to_string();
+        fs::write(&edited_path, serde_json::to_string(&config).unwrap()).unwrap();
+        let config = resolve_config(&Some(edited_path), &None, &Some(args)).unwrap();
+        assert_eq!(config.cvr_file_sources[0].allow_unknown_choices, Some(true));
+        let ballots = io_csv::read_csv_likert(
+            dir.join("example.csv").display().to_string(),
+            &config.cvr_file_sources[0],
+            &names,
+        )
+        .unwrap();
+        assert_eq!(ballots[0].choices, vec![vec!["Alice"], vec!["Bob"]]);
+
+        // The labels of an msforms_likert source that are not in the choices.
+        let config = resolve_config(
+            &Some("./tests/msforms_likert/msforms_likert_config.json".to_string()),
+            &None,
+            &None,
+        )
+        .unwrap();
+        let names: Vec<String> = config.candidates.iter().map(|c| c.name.clone()).collect();
+        let mut cfs = config.cvr_file_sources[0].clone();
+        cfs.choices.as_mut().unwrap().truncate(3);
+        let err =
+            io_msforms::read_msforms_likert("./tests/msforms_data.xlsx".to_string(), &cfs, &names)
+                .unwrap_err();
+        assert!(matches!(*err, RcvError::UnknownChoiceLabels { .. }));
+        assert!(
+            err.to_string().contains("\"Option 2-4\" appeared 1 times"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn msforms_all_worksheets() {
+        let cfs: FileSource = serde_json::from_value(json!({
+            "provider": "msforms_ranking",
+            "filePath": "msforms_worksheets.xlsx",
+            "firstVoteColumnIndex": "F",
+            "allWorksheets": true,
+        }))
+        .unwrap();
+        let ballots = io_msforms::read_msforms_ranking(
+            "./tests/msforms_worksheets/msforms_worksheets.xlsx".to_string(),
+            &cfs,
+        )
+        .unwrap();
+        assert_eq!(ballots.len(), 7);
+        let ids: Vec<String> = ballots.iter().map(|b| b.id.clone().unwrap()).collect();
+        assert!(ids[..4]
+            .iter()
+            .all(|id| id.starts_with("Form Responses 1:")));
+        assert!(ids[4..]
+            .iter()
+            .all(|id| id.starts_with("Form Responses 2:")));
+        let unique_ids: HashSet<&String> = ids.iter().collect();
+        assert_eq!(unique_ids.len(), ids.len());
+    }
+
+    #[test]
+    fn msforms_worksheets_same_submission() {
+        // The submission 4 is in both worksheets: it keeps the id of the ID column.
+        let cfs: FileSource = serde_json::from_value(json!({
+            "provider": "msforms_ranking",
+            "filePath": "msforms_worksheets_overlap.xlsx",
+            "idColumnIndex": "A",
+            "firstVoteColumnIndex": "F",
+            "allWorksheets": true,
+        }))
+        .unwrap();
+        codes::take_warnings();
+        let ballots = io_msforms::read_msforms_ranking(
+            "./tests/msforms_worksheets/msforms_worksheets_overlap.xlsx".to_string(),
+            &cfs,
+        )
+        .unwrap();
+        let ids: Vec<&str> = ballots.iter().map(|b| b.id.as_deref().unwrap()).collect();
+        assert_eq!(ids, vec!["1", "2", "3", "4", "4", "6", "7"]);
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::IdenticalBallots);
+        assert!(warnings[0].message.starts_with(
+            "1 ballots of ./tests/msforms_worksheets/msforms_worksheets_overlap.xlsx have the id \
+             of a ballot of an earlier worksheet, for example 4 in Form Responses 1 and Form \
+             Responses 2"
+        ));
+    }
+
+    #[test]
+    fn msforms_single_worksheet_ids() {
+        // With a single worksheet, the ids are also the cells of the ID column.
+        let cfs = |id_column: Option<&str>| -> FileSource {
+            let mut source = json!({
+                "provider": "msforms_ranking",
+                "filePath": "msforms_worksheets_overlap.xlsx",
+                "firstVoteColumnIndex": "F",
+                "excelWorksheetName": "Form Responses 2",
+            });
+            if let Some(id_column) = id_column {
+                source["idColumnIndex"] = json!(id_column);
+            }
+            serde_json::from_value(source).unwrap()
+        };
+        let path = "./tests/msforms_worksheets/msforms_worksheets_overlap.xlsx";
+        let ballots = io_msforms::read_msforms_ranking(path.to_string(), &cfs(Some("A"))).unwrap();
+        let ids: Vec<&str> = ballots.iter().map(|b| b.id.as_deref().unwrap()).collect();
+        assert_eq!(ids, vec!["4", "6", "7"]);
+
+        // Without it, the ids are the line numbers, without the name of the worksheet.
+        let ballots = io_msforms::read_msforms_ranking(path.to_string(), &cfs(None)).unwrap();
+        let ids: Vec<&str> = ballots.iter().map(|b| b.id.as_deref().unwrap()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "msforms_worksheets_overlap.xlsx-00000000",
+                "msforms_worksheets_overlap.xlsx-00000001",
+                "msforms_worksheets_overlap.xlsx-00000002"
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_simple_1() {
+        test_wrapper_local("csv_simple_1");
+    }
+
+    #[test]
+    fn csv_simple_2() {
+        test_wrapper_local("csv_simple_2");
+    }
+
+    #[test]
+    fn csv_excel_export() {
+        // The rows are padded with empty cells up to 25 columns and followed by 200 empty rows,
+        // as in the exports of spreadsheets. With maxSkippedRanksAllowed at 0, the padding would
+        // exhaust the ballots on skipped rankings.
+        test_wrapper_local("csv_excel_export");
+        let config = resolve_config(
+            &Some("./tests/csv_excel_export/csv_excel_export_config.json".to_string()),
+            &None,
+            &None,
+        )
+        .unwrap();
+        codes::take_warnings();
+        let ballots = io_csv::read_csv_ranking(
+            "./tests/csv_excel_export/example.csv".to_string(),
+            &config.cvr_file_sources[0],
+        )
+        .unwrap();
+        let warnings = codes::take_warnings();
+        assert_eq!(ballots.len(), 5);
+        assert_eq!(ballots[1].choices, vec![vec!["B"]]);
+        assert!(ballots
+            .iter()
+            .all(|b| b.choices.last().map(|g| !g.is_empty()).unwrap_or(false)));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::EmptyRows);
+
+        // The empty rows between two ballots are blank ballots: only the ones at the end of the
+        // file are skipped, with both readers.
+        let dir = std::env::temp_dir().join("timrcv_csv_empty_rows");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ballots.csv").display().to_string();
+        let mut content = "id,count,rank1,rank2\nv1,1,A,B\n,,,\n,,,\nv2,1,B,A\n".to_string();
+        for k in 0..5000 {
+            content.push_str(&format!("w{},1,C,\n", k));
+        }
+        content.push_str(&",,,\n".repeat(3000));
+        fs::write(&path, content).unwrap();
+        for jobs in [None, Some(4)] {
+            let mut source = config.cvr_file_sources[0].clone();
+            source.jobs = jobs;
+            codes::take_warnings();
+            let ballots = io_csv::read_csv_ranking(path.clone(), &source).unwrap();
+            let warnings = codes::take_warnings();
+            assert_eq!(ballots.len(), 5004);
+            assert!(ballots[1].choices.is_empty() && ballots[2].choices.is_empty());
+            assert_eq!(ballots[3].choices, vec![vec!["B"], vec!["A"]]);
+            assert_eq!(warnings.len(), 1);
+            assert!(warnings[0].message.starts_with("3000 empty rows"));
+        }
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let config_path = "./tests/csv_simple_1/csv_simple_1_config.json".to_string();
+        let summary_path = "./tests/csv_simple_1/csv_simple_1_expected_summary.json".to_string();
+        let out_dir = std::env::temp_dir().join("timrcv_snapshot");
+        fs::create_dir_all(&out_dir).unwrap();
+        let snapshot_path = out_dir.join("ballots.snapshot").display().to_string();
+        let run = |flag: &str, path: &str| {
+            run_election_at(
+                Some(config_path.clone()),
+                Some(summary_path.clone()),
+                None,
+                Some("".to_string()),
+                true,
+                Some(Args::parse_from(["timrcv", flag, path])),
+            )
+        };
+
+        // The ballots of the snapshot give the same summary as the file sources.
+        run("--save-validated", &snapshot_path).unwrap();
+        run("--load-validated", &snapshot_path).unwrap();
+
+        let mut bytes = fs::read(&snapshot_path).unwrap();
+        bytes[20] ^= 1;
+        let tampered_path = out_dir.join("tampered.snapshot").display().to_string();
+        fs::write(&tampered_path, bytes).unwrap();
+        assert!(matches!(
+            run("--load-validated", &tampered_path),
+            Err(RcvError::InvalidSnapshot { .. })
+        ));
+
+        // The rules of csv_simple_1 are not the default rules.
+        let election = snapshot::ValidatedElection::load(&snapshot_path).unwrap();
+        assert!(matches!(
+            election.check_rules(&snapshot_path, &VoteRules::default(), false),
+            Err(RcvError::SnapshotRulesMismatch { .. })
+        ));
+        election
+            .check_rules(&snapshot_path, &VoteRules::default(), true)
+            .unwrap();
+        assert_eq!(election.ballots.iter().map(|b| b.count).sum::<u64>(), 100);
+        assert_eq!(election.candidates.as_ref().unwrap().len(), 4);
+
+        // The API gives the same snapshot, which tabulates as the ballots.
+        let config = resolve_config(&Some(config_path.clone()), &None, &None).unwrap();
+        let rules = validate_rules(&config.rules).unwrap();
+        election.check_rules(&snapshot_path, &rules, false).unwrap();
+        let api_path = out_dir.join("api.snapshot").display().to_string();
+        snapshot::ValidatedElection::new(&rules, election.candidates.clone(), &election.ballots)
+            .unwrap()
+            .save(&api_path)
+            .unwrap();
+        assert_eq!(
+            fs::read(&api_path).unwrap(),
+            fs::read(&snapshot_path).unwrap()
+        );
+
+        // The rounds of the snapshot are the rounds of the reference summary.
+        let mut official = verify_rounds::read_official_rounds(&summary_path).unwrap();
+        let report = election.verify_round_sequence(&rules, &official).unwrap();
+        assert!(report.is_match());
+        assert_eq!(report.matching_rounds, official.len());
+        official[0].tally[0].1 += 3;
+        let report = election.verify_round_sequence(&rules, &official).unwrap();
+        let divergence = report.divergence.unwrap();
+        assert_eq!(divergence.round.0, 1);
+        assert_eq!(divergence.deltas[0].delta(), -3);
+
+        // The fingerprint only depends on the values of the rules.
+        let mut other = rules.clone();
+        assert_eq!(
+            snapshot::rules_fingerprint(&other),
+            snapshot::rules_fingerprint(&rules)
+        );
+        other.max_rankings_allowed = Some(2);
+        assert_ne!(
+            snapshot::rules_fingerprint(&other),
+            snapshot::rules_fingerprint(&rules)
+        );
+
+        // The count of the aggregated ballots is checked.
+        let ballot = Ballot::new(vec![BallotChoice::Candidate("A".to_string())], u64::MAX);
+        let err = snapshot::aggregate_ballots(&[ballot.clone(), ballot]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the total count of the identical ballots is too large"
+        );
+    }
+
+    #[test]
+    fn csv_uwi_round1() {
+        test_wrapper_local("csv_uwi_round1");
+        run_election_test(
+            "csv_uwi_round1",
+            "csv_uwi_round1_reference_config.json",
+            "csv_uwi_round1_reference_expected_summary.json",
+            true,
+        );
+    }
+
+    #[test]
+    fn csv_excluded() {
+        test_wrapper_local("csv_excluded");
+        let summary: JSValue = serde_json::from_str(
+            &fs::read_to_string("./tests/csv_excluded/csv_excluded_expected_summary.json").unwrap(),
+        )
+        .unwrap();
+        // The excluded candidate has its own section, and is never in a tally.
+        assert_eq!(
+            summary["excludedCandidates"],
+            json!([{"name": "C", "votes": "3"}])
+        );
+        for round in summary["results"].as_array().unwrap() {
+            assert!(round["tally"].get("C").is_none());
+        }
+        // Its ballots are transferred in the first round.
+        let first_round = summary["results"][0]["tallyResults"].as_array().unwrap();
+        assert!(first_round.contains(&json!({
+            "eliminated": "C",
+            "transfers": {"A": "1", "D": "1", "exhausted": "1"}
+        })));
+    }
+
+    #[test]
+    fn csv_inactive_ballots() {
+        test_wrapper_local("csv_inactive_ballots");
+        let config_path = "./tests/csv_inactive_ballots/csv_inactive_ballots_config.json";
+        let mut config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        assert!(config.output_settings.inactive_ballots_block().unwrap());
+        config.output_settings.rctab_compatibility = None;
+        assert!(!config.output_settings.inactive_ballots_block().unwrap());
+        config.output_settings.rctab_compatibility = Some("1.0".to_string());
+        assert_eq!(
+            config
+                .output_settings
+                .inactive_ballots_block()
+                .unwrap_err()
+                .to_string(),
+            "unknown RCTab compatibility version: 1.0, only 1.3 is supported"
+        );
+    }
+
+    #[test]
+    fn csv_simple_likert() {
+        test_wrapper_local("csv_simple_likert");
+    }
+
+    #[test]
+    fn csv_likert_header_pattern() {
+        test_wrapper_local("csv_likert_header_pattern");
+    }
+
+    #[test]
+    fn candidate_header_pattern_mapping() {
+        let path = "./tests/csv_likert_header_pattern/example.csv".to_string();
+        let source = |pattern: &str| -> FileSource {
+            serde_json::from_value(json!({
+                "provider": "csv_likert",
+                "filePath": "",
+                "firstVoteRowIndex": "2",
+                "candidateHeaderPattern": pattern,
+            }))
+            .unwrap()
+        };
+        let names = |l: &[&str]| -> Vec<String> { l.iter().map(|s| s.to_string()).collect() };
+
+        let template = source("Who do you vote for? [{}]");
+        let regex = source(r"\[(.+)\]$");
+        let expected =
+            io_csv::read_csv_likert(path.clone(), &template, &names(&["Alice", "Bob", "Carol"]))
+                .unwrap();
+        assert_eq!(expected.len(), 5);
+        assert_eq!(expected[0].choices[0], vec!["Alice".to_string()]);
+        let from_regex =
+            io_csv::read_csv_likert(path.clone(), &regex, &names(&["Alice", "Bob", "Carol"]))
+                .unwrap();
+        assert_eq!(from_regex, expected);
+
+        let err = io_csv::read_csv_likert(path.clone(), &template, &names(&["Alice", "Dave"]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "candidates not found in the header: 'Dave'. Unmatched header columns: 'Bob', 'Carol'"
+        );
+
+        let err = io_csv::read_csv_likert(path, &source("(.+) \\[(.+)\\]"), &names(&["Alice"]))
+            .unwrap_err();
+        assert!(matches!(*err, RcvError::InvalidHeaderPattern { .. }));
+
+        // Two questions about the same candidate: the pattern cannot choose between them.
+        let dir = test_temp_dir("timrcv_header_pattern_duplicate");
+        let path = dir.join("example.csv").display().to_string();
+        fs::write(
+            &path,
+            "Timestamp,First [Alice],First [Bob],Second [Alice]\nt,1,2,1\n",
+        )
+        .unwrap();
+        let err = io_csv::read_csv_likert(path, &regex, &names(&["Alice", "Bob"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the header columns 2 ('First [Alice]') and 4 ('Second [Alice]') both give the candidate name 'Alice'"
+        );
+    }
+
+    #[test]
+    fn ess_simple() {
+        test_wrapper_local("ess_simple");
+    }
+
+    #[test]
+    fn ess_precinct_style() {
+        test_wrapper_local("ess_precinct_style");
+        // The machine column, between the ballot style and the votes, is skipped.
+        let config_path = "./tests/ess_precinct_style/ess_precinct_style_config.json";
+        let config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        let (parsed, _) = read_parsed_ballots(
+            "./tests/ess_precinct_style".to_string(),
+            &config.cvr_file_sources[0],
+            Some(&config.candidates),
+        )
+        .unwrap();
+        assert_eq!(parsed[0].precinct.as_deref(), Some("101"));
+        assert_eq!(parsed[0].style.as_deref(), Some("Style 1"));
+        assert_eq!(parsed[0].choices, vec![vec!["A"], vec!["B"], vec![""]]);
+
+        // The totals by precinct and by ballot style are in the provenance of the sources.
+        let out_dir = std::env::temp_dir().join("timrcv_ess_precinct_style");
+        fs::create_dir_all(&out_dir).unwrap();
+        let out_path = out_dir.join("summary.json").display().to_string();
+        run_election_at(
+            Some(config_path.to_string()),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            Some(Args::parse_from(["timrcv", "--emit-provenance"])),
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        let source = &summary["config"]["sources"][0];
+        assert_eq!(
+            source["precincts"],
+            json!({"101": "3", "102": "3", "103": "1"})
+        );
+        assert_eq!(
+            source["ballotStyles"],
+            json!({"Style 1": "3", "Style 2": "4"})
+        );
+
+        // The metadata columns must be before the votes.
+        let mut cfs = config.cvr_file_sources[0].clone();
+        cfs.style_column_index = Some(json!("6"));
+        let err = match read_parsed_ballots(
+            "./tests/ess_precinct_style".to_string(),
+            &cfs,
+            Some(&config.candidates),
+        )
+        .unwrap_err()
+        {
+            RcvError::OpeningFile { source, .. } => *source,
+            e => panic!("unexpected error {:?}", e),
+        };
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+        assert_eq!(
+            err.to_string(),
+            "styleColumnIndex is the column 6, which is not before firstVoteColumnIndex 5: the precinct and the ballot style must be before the votes"
+        );
+        // Only the ess provider has ballot styles.
+        cfs.provider = "csv".to_string();
+        cfs.style_column_index = Some(json!("3"));
+        let err = read_parsed_ballots(
+            "./tests/ess_precinct_style".to_string(),
+            &cfs,
+            Some(&config.candidates),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, RcvError::StylesNotSupported { .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    // The ballots of csv_simple_2, one rank per column.
+    #[cfg(feature = "parquet")]
+    fn csv_simple_2_rankings() -> Vec<Vec<Option<&'static str>>> {
+        vec![
+            vec![Some("A"), Some("B"), None, Some("D")],
+            vec![Some("A"), Some("C"), Some("B"), None],
+            vec![Some("B"), Some("A"), Some("D"), Some("C")],
+            vec![Some("B"), Some("C"), Some("A"), Some("D")],
+            vec![Some("C"), Some("A"), Some("B"), Some("D")],
+            vec![Some("D"), Some("B"), Some("A"), Some("C")],
+        ]
+    }
+
+    #[cfg(feature = "parquet")]
+    fn write_parquet(path: &Path, columns: Vec<(&str, arrow_array::ArrayRef)>) {
+        let batch = arrow_array::RecordBatch::try_from_iter(columns).unwrap();
+        let file = fs::File::create(path).unwrap();
+        let mut writer = parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    // Writes the ballots of csv_simple_2 with one column per rank.
+    #[cfg(feature = "parquet")]
+    fn write_parquet_wide(path: &Path) {
+        use arrow_array::{ArrayRef, StringArray};
+        use std::sync::Arc;
+        let rankings = csv_simple_2_rankings();
+        let ids: ArrayRef = Arc::new(StringArray::from_iter_values(
+            (1..=rankings.len()).map(|i| format!("ballot-{}", i)),
+        ));
+        let names: Vec<String> = (1..=4).map(|i| format!("rank_{}", i)).collect();
+        let mut columns = vec![("ballot_id", ids)];
+        for (rank, name) in names.iter().enumerate() {
+            let col: ArrayRef = Arc::new(StringArray::from(
+                rankings.iter().map(|r| r[rank]).collect::<Vec<_>>(),
+            ));
+            columns.push((name.as_str(), col));
+        }
+        write_parquet(path, columns);
+    }
+
+    // The configuration of csv_simple_2, with the file source of the ballots.
+    fn csv_simple_2_config(source: JSValue) -> JSValue {
+        let mut config: JSValue = serde_json::from_str(
+            &fs::read_to_string("./tests/csv_simple_2/csv_simple_2_config.json").unwrap(),
+        )
+        .unwrap();
+        config["cvrFileSources"] = json!([source]);
+        config
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_simple() {
+        // The same ballots as csv_simple_2 give the same summary.
+        let dir = test_temp_dir("timrcv_parquet_simple");
+        write_parquet_wide(&dir.join("ballots.parquet"));
+        let csv_path = fs::canonicalize("./tests/csv_simple_2/example.csv").unwrap();
+        let csv_config = csv_simple_2_config(json!({
+            "filePath": csv_path.display().to_string(),
+            "provider": "csv",
+            "firstVoteRowIndex": "1",
+        }));
+        let csv_summary = run_json_config(&dir, &csv_config, &[]).unwrap();
+        let parquet_config = csv_simple_2_config(json!({
+            "filePath": "ballots.parquet",
+            "provider": "parquet",
+            "idColumnName": "ballot_id",
+            "rankColumnPrefix": "rank_",
+            "rankColumnCount": 4,
+        }));
+        let parquet_summary = run_json_config(&dir, &parquet_config, &[]).unwrap();
+        assert_eq!(parquet_summary, csv_summary);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_negative_count() {
+        use arrow_array::{ArrayRef, Int64Array, StringArray};
+        use std::sync::Arc;
+        let dir = test_temp_dir("timrcv_parquet_negative_count");
+        let path = dir.join("ballots.parquet");
+        let choices: ArrayRef = Arc::new(StringArray::from(vec!["A", "B"]));
+        let counts: ArrayRef = Arc::new(Int64Array::from(vec![1, -1]));
+        write_parquet(&path, vec![("rank_1", choices), ("count", counts)]);
+        let source: FileSource = serde_json::from_value(json!({
+            "provider": "parquet",
+            "filePath": "ballots.parquet",
+            "countColumnName": "count",
+            "rankColumnPrefix": "rank_",
+            "rankColumnCount": 1,
+        }))
+        .unwrap();
+        // The line and the column of the count start at 1.
+        let err = io_parquet::read_parquet(path.display().to_string(), &source).unwrap_err();
+        assert!(
+            matches!(*err, RcvError::LineParse { lineno: 2, col: 2 }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_list_column() {
+        use arrow_array::builder::{ListBuilder, StringBuilder};
+        use arrow_array::ArrayRef;
+        use std::sync::Arc;
+        let out_dir = test_temp_dir("timrcv_parquet_list");
+        let wide_path = out_dir.join("wide.parquet");
+        let list_path = out_dir.join("list.parquet");
+        write_parquet_wide(&wide_path);
+        let mut builder = ListBuilder::new(StringBuilder::new());
+        for ranking in csv_simple_2_rankings() {
+            for choice in ranking {
+                builder.values().append_option(choice);
+            }
+            builder.append(true);
+        }
+        let choices: ArrayRef = Arc::new(builder.finish());
+        write_parquet(&list_path, vec![("choices", choices)]);
+
+        let source = |extra: JSValue| -> FileSource {
+            let mut js = json!({"provider": "parquet", "filePath": ""});
+            js.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            serde_json::from_value(js).unwrap()
+        };
+        let wide = io_parquet::read_parquet(
+            wide_path.display().to_string(),
+            &source(json!({"rankColumnPrefix": "rank_", "rankColumnCount": 4})),
+        )
+        .unwrap();
+        let list = io_parquet::read_parquet(
+            list_path.display().to_string(),
+            &source(json!({"rankingsColumn": "choices"})),
+        )
+        .unwrap();
+        assert_eq!(wide.len(), 6);
+        assert_eq!(
+            wide[1].choices,
+            vec![vec!["A"], vec!["C"], vec!["B"], vec![]]
+        );
+        assert_eq!(
+            wide.iter().map(|pb| &pb.choices).collect::<Vec<_>>(),
+            list.iter().map(|pb| &pb.choices).collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    #[test]
+    fn parquet_requires_feature() {
+        let dir = test_temp_dir("timrcv_parquet_feature");
+        let config = csv_simple_2_config(json!({
+            "filePath": "ballots.parquet",
+            "provider": "parquet",
+            "rankColumnPrefix": "rank_",
+            "rankColumnCount": 4,
+        }));
+        let res = run_json_config(&dir, &config, &[]);
+        match res {
+            Err(RcvError::OpeningFile { source, .. }) => {
+                assert!(matches!(*source, RcvError::FeatureNotEnabled { .. }))
+            }
+            _ => panic!("{:?}", res),
+        }
+    }
+
+    #[test]
+    fn cdf_simple() {
+        test_wrapper_local("cdf_simple");
+    }
+
+    #[test]
+    fn cdf_contest_id() {
+        // A report with many contests, each with its own candidates, and the ballots of all the
+        // contests.
+        let num_contests = 200;
+        let num_ballots = 30;
+        let contests: Vec<JSValue> = (0..num_contests)
+            .map(|c| {
+                json!({
+                    "@id": format!("contest-{}", c),
+                    "ContestSelection": [
+                        {"@id": format!("cs-{}-A", c), "CandidateIds": [format!("c-{}-A", c)]},
+                        {"@id": format!("cs-{}-B", c), "CandidateIds": [format!("c-{}-B", c)]},
+                    ],
+                })
+            })
+            .collect();
+        let candidates: Vec<JSValue> = (0..num_contests)
+            .flat_map(|c| ["A", "B"].map(|n| json!({"@id": format!("c-{}-{}", c, n), "Name": n})))
+            .collect();
+        let cvrs: Vec<JSValue> = (0..num_ballots)
+            .map(|b| {
+                let cvr_contests: Vec<JSValue> = (0..num_contests)
+                    .map(|c| {
+                        let first = if b % 3 == 0 { "B" } else { "A" };
+                        json!({
+                            "ContestId": format!("contest-{}", c),
+                            "CVRContestSelection": [{
+                                "ContestSelectionId": format!("cs-{}-{}", c, first),
+                                "SelectionPosition": [{"NumberVotes": 1, "Rank": 1}],
+                            }],
+                        })
+                    })
+                    .collect();
+                json!({
+                    "BallotPrePrintedId": b.to_string(),
+                    "CVRSnapshot": [{"CVRContest": cvr_contests}],
+                })
+            })
+            .collect();
+        let report = json!({
+            "Election": [{"Candidate": candidates, "Contest": contests}],
+            "CVR": cvrs,
+        });
+        let dir = std::env::temp_dir().join("timrcv_cdf_contest_id");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("report.json").display().to_string();
+        fs::write(&path, report.to_string()).unwrap();
+        let source = |contest_id: Option<&str>| -> FileSource {
+            serde_json::from_value(
+                json!({"provider": "cdf", "filePath": "report.json", "contestId": contest_id}),
+            )
+            .unwrap()
+        };
+
+        // Only the ballots of the selected contest are built.
+        let ballots = io_cdf::read_json(path.clone(), &source(Some("contest-7"))).unwrap();
+        assert_eq!(ballots.len(), num_ballots);
+        let first_choices: Vec<&str> = ballots.iter().map(|b| b.choices[0][0].as_str()).collect();
+        assert_eq!(first_choices.iter().filter(|n| **n == "B").count(), 10);
+
+        // Without contestId, every contest is read.
+        let ballots = io_cdf::read_json(path.clone(), &source(None)).unwrap();
+        assert_eq!(ballots.len(), num_ballots * num_contests);
+
+        let err = io_cdf::read_json(path, &source(Some("contest-200"))).unwrap_err();
+        assert!(matches!(*err, RcvError::CdfMissingContest { .. }));
+        assert_eq!(err.code().code(), "E002");
+    }
+
+    #[test]
+    fn dominion_simple() {
+        test_wrapper_local("dominion_simple");
+    }
+
+    #[test]
+    fn dominion_codes() {
+        // The manifest has the codes of the candidates in the Description, and some names in the
+        // ExternalId.
+        test_wrapper_local("dominion_codes");
+
+        let config_path = "./tests/dominion_codes/dominion_codes_config.json";
+        let config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        let path = "./tests/dominion_codes/cvr";
+        let first_choices = |candidates: &[RcvCandidate]| -> Vec<String> {
+            io_dominion::read_dominion(path, &config.cvr_file_sources[0], candidates)
+                .unwrap()
+                .iter()
+                .map(|b| b.choices[0][0].clone())
+                .collect()
+        };
+        assert_eq!(
+            first_choices(&config.candidates),
+            vec![
+                "Ann Smith",
+                "Ann Smith",
+                "Bob Jones",
+                "Bob Jones",
+                "Clara Lee",
+                "Dan Brown"
+            ]
+        );
+        // Without declared candidates, the names are the descriptions.
+        assert_eq!(
+            first_choices(&[]),
+            vec!["ANN", "ANN", "BOB", "BOB", "CLA", "Dan Brown"]
+        );
+    }
+
+    #[test]
+    fn dominion_truncated_manifest() {
+        // The manifest only has the candidates A and B.
+        let config_path = "./tests/dominion_truncated/dominion_truncated_config.json";
+        let config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        let read = |cfs: &FileSource| {
+            let err = read_parsed_ballots(
+                "./tests/dominion_truncated".to_string(),
+                cfs,
+                Some(&config.candidates),
+            )
+            .unwrap_err();
+            match err {
+                RcvError::OpeningFile { source, .. } => *source,
+                e => panic!("unexpected error {:?}", e),
+            }
+        };
+        let err = read(&config.cvr_file_sources[0]);
+        assert_eq!(err.code(), codes::ErrorCode::UnreadableInput);
+        assert!(
+            err.to_string().ends_with(
+                "has 2 candidates instead of the 4 of expectedCandidateCount, it may be \
+                 truncated. Candidates found: \"A\", \"B\""
+            ),
+            "{}",
+            err
+        );
+
+        // Without the expected count, all the missing ids are reported at once.
+        let mut cfs = config.cvr_file_sources[0].clone();
+        cfs.expected_candidate_count = None;
+        let err = read(&cfs);
+        assert_eq!(err.code(), codes::ErrorCode::UnknownCandidates);
+        assert!(
+            err.to_string().ends_with(
+                "have candidate ids that are not in the candidate manifest: 3 (5 marks), 4 (5 marks)"
+            ),
+            "{}",
+            err
+        );
+
+        // The option needs a manifest.
+        let cfs: FileSource = serde_json::from_value(json!({
+            "provider": "csv",
+            "filePath": "example.csv",
+            "expectedCandidateCount": 3,
+        }))
+        .unwrap();
+        let err = read_parsed_ballots("./tests/csv_simple_1".to_string(), &cfs, None).unwrap_err();
+        assert!(matches!(
+            err,
+            RcvError::ExpectedCandidateCountNotSupported { .. }
+        ));
+    }
+
+    #[test]
+    fn csv_challenged() {
+        test_wrapper_local("csv_challenged");
+    }
+
+    #[test]
+    fn challenged_ballots_flip_winner() {
+        let out_dir = std::env::temp_dir().join("timrcv_challenged_both");
+        fs::create_dir_all(&out_dir).unwrap();
+        let out_path = out_dir.join("summary.json").display().to_string();
+        let args = Args::parse_from(["timrcv", "--with-challenged", "both"]);
+        run_election_at(
+            Some("./tests/csv_challenged/csv_challenged_config.json".to_string()),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(out_path).unwrap()).unwrap();
+        let comparison = &summary["comparison"];
+        assert_eq!(comparison["sameWinners"], json!(false));
+        assert_eq!(comparison["winnersIncluded"], json!(["A"]));
+        assert_eq!(comparison["winnersExcluded"], json!(["B"]));
+        assert_eq!(comparison["challengedBallots"], json!(2));
+        assert_eq!(comparison["firstRoundDelta"], json!({"A": 2, "B": 0}));
+        assert_eq!(summary["included"]["results"][0]["tally"]["A"], json!("4"));
+        assert_eq!(summary["excluded"]["results"][0]["tally"]["A"], json!("2"));
+    }
+
+    #[test]
+    fn csv_weights() {
+        test_wrapper_local("csv_weights");
+    }
+
+    #[test]
+    fn weights_flip_winner() {
+        let out_dir = std::env::temp_dir().join("timrcv_weights");
+        fs::create_dir_all(&out_dir).unwrap();
+        let config_path = "./tests/csv_weights/csv_weights_config.json";
+        let run = |config: &str| -> JSValue {
+            let out_path = out_dir.join("summary.json").display().to_string();
+            run_election_at(
+                Some(config.to_string()),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                None,
+            )
+            .unwrap();
+            serde_json::from_str(&fs::read_to_string(out_path).unwrap()).unwrap()
+        };
+
+        let weighted = run(config_path);
+        assert_eq!(weighted["reason"], json!("winner declared: B"));
+        assert_eq!(
+            weighted["categories"],
+            json!([
+                {"category": "board", "ballots": "2", "weight": "3", "weightedVotes": "6"},
+                {"category": "guest", "ballots": "1", "weight": "1", "weightedVotes": "1"},
+                {"category": "member", "ballots": "3", "weight": "1", "weightedVotes": "3"},
+            ])
+        );
+
+        // The same election without the weights.
+        let mut config_js: JSValue =
+            serde_json::from_str(&fs::read_to_string(config_path).unwrap()).unwrap();
+        config_js.as_object_mut().unwrap().remove("weights");
+        config_js["cvrFileSources"][0]["filePath"] =
+            json!(fs::canonicalize("./tests/csv_weights/example.csv")
+                .unwrap()
+                .display()
+                .to_string());
+        let unweighted_path = out_dir.join("config.json");
+        fs::write(&unweighted_path, config_js.to_string()).unwrap();
+        let unweighted = run(&unweighted_path.display().to_string());
+        assert_eq!(unweighted["reason"], json!("winner declared: A"));
+        assert!(unweighted.get("categories").is_none());
+
+        // A weighted count that does not fit is an error of the ballot.
+        let config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        let ballot = ParsedBallot {
+            id: Some("v4".to_string()),
+            count: Some(u64::MAX / 2),
+            choices: vec![vec!["B".to_string()]],
+            challenged: false,
+            category: Some("board".to_string()),
+            respondent: None,
+            precinct: None,
+            style: None,
+        };
+        let err = weighted_count(&ballot, config.weights.as_ref()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "ballot v4: the count {} with the weight 3 of its category is too large",
+                u64::MAX / 2
+            )
+        );
+        assert_eq!(err.code(), codes::ErrorCode::MalformedBallots);
+        assert_eq!(weighted_count(&ballot, None).unwrap(), u64::MAX / 2);
+    }
+
+    #[test]
+    fn category_totals_count_the_validated_ballots() {
+        // The observers have a weight of 0: their ballots are dropped by the validation, and
+        // are not in the totals of the categories.
+        let out_dir = std::env::temp_dir().join("timrcv_weights_validated");
+        fs::create_dir_all(&out_dir).unwrap();
+        let csv_path = out_dir.join("example.csv");
+        fs::write(
+            &csv_path,
+            "id,category,choice 1,choice 2\nv1,member,A,B\nv2,board,B,A\nv3,observer,C,A\n",
+        )
+        .unwrap();
+        let config_path = "./tests/csv_weights/csv_weights_config.json";
+        let mut config_js: JSValue =
+            serde_json::from_str(&fs::read_to_string(config_path).unwrap()).unwrap();
+        config_js["cvrFileSources"][0]["filePath"] = json!(csv_path.display().to_string());
+        config_js["weights"]["values"]["observer"] = json!(0);
+        let weighted_path = out_dir.join("config.json");
+        fs::write(&weighted_path, config_js.to_string()).unwrap();
+        let out_path = out_dir.join("summary.json").display().to_string();
+        run_election_at(
+            Some(weighted_path.display().to_string()),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            None,
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(
+            summary["categories"],
+            json!([
+                {"category": "board", "ballots": "1", "weight": "3", "weightedVotes": "3"},
+                {"category": "member", "ballots": "1", "weight": "1", "weightedVotes": "1"},
+            ])
+        );
+    }
+
+    #[test]
+    fn weights_column_only_where_declared() {
+        // A second source without the column of the weights: its ballots get the default weight.
+        let out_dir = std::env::temp_dir().join("timrcv_weights_sources");
+        fs::create_dir_all(&out_dir).unwrap();
+        let extra_path = out_dir.join("extra.csv");
+        fs::write(&extra_path, "id,choice 1,choice 2\nx1,C,A\nx2,C,B\n").unwrap();
+        let config_path = "./tests/csv_weights/csv_weights_config.json";
+        let mut config_js: JSValue =
+            serde_json::from_str(&fs::read_to_string(config_path).unwrap()).unwrap();
+        let mut extra = config_js["cvrFileSources"][0].clone();
+        extra["filePath"] = json!(extra_path.display().to_string());
+        extra["firstVoteColumnIndex"] = json!("2");
+        config_js["cvrFileSources"][0]["filePath"] =
+            json!(fs::canonicalize("./tests/csv_weights/example.csv")
+                .unwrap()
+                .display()
+                .to_string());
+        config_js["cvrFileSources"]
+            .as_array_mut()
+            .unwrap()
+            .push(extra);
+        let two_sources_path = out_dir.join("config.json");
+        fs::write(&two_sources_path, config_js.to_string()).unwrap();
+        let out_path = out_dir.join("summary.json").display().to_string();
+        run_election_at(
+            Some(two_sources_path.display().to_string()),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            Some(Args::parse_from(["timrcv", "--summary-schema", "v2"])),
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(
+            summary["categories"],
+            json!([
+                {"category": null, "ballots": "2", "weight": "1", "weightedVotes": "2"},
+                {"category": "board", "ballots": "2", "weight": "3", "weightedVotes": "6"},
+                {"category": "guest", "ballots": "1", "weight": "1", "weightedVotes": "1"},
+                {"category": "member", "ballots": "3", "weight": "1", "weightedVotes": "3"},
+            ])
+        );
+        let warnings = summary["warnings"].as_array().unwrap();
+        assert!(warnings.iter().any(|w| w["message"]
+            .as_str()
+            .unwrap()
+            .contains("has no column category of the weights")));
+
+        // The totals of a category that do not fit are an error.
+        let config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        let ballot = ParsedBallot {
+            id: Some("v4".to_string()),
+            count: Some(u64::MAX / 2),
+            choices: vec![vec!["B".to_string()]],
+            challenged: false,
+            category: Some("member".to_string()),
+            respondent: None,
+            precinct: None,
+            style: None,
+        };
+        let mut totals = CategoryTotals::new();
+        let err = add_category_totals(
+            &mut totals,
+            &[&ballot, &ballot, &ballot],
+            config.weights.as_ref().unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the total count of the ballots of the category member is too large"
+        );
+        assert_eq!(err.code(), codes::ErrorCode::MalformedBallots);
+    }
+
+    // The validated ballots, without their indices.
+    fn validated_ballots(
+        parsed: &[ParsedBallot],
+        candidates: &[RcvCandidate],
+        source: &FileSource,
+        rules: &RcvRules,
+    ) -> Vec<Ballot> {
+        let validated = validate_ballots(parsed, candidates, source, rules, None).unwrap();
+        validated.into_iter().map(|(_, b)| b).collect()
+    }
+
+    #[test]
+    fn code_table_is_stable() {
+        // The codes are used by scripts: existing lines must never change.
+        assert_eq!(
+            codes::code_table(),
+            vec![
+                "W001 short-row",
+                "W002 unsplittable-overvote",
+                "W003 unknown-category",
+                "W004 fairvote-missing-columns",
+                "W005 compressed-ranks",
+                "W006 skipped-source",
+                "W007 ranks-in-cells",
+                "W008 extra-candidate-columns",
+                "W009 source-is-output",
+                "W010 rule-override",
+                "W011 rank-out-of-range",
+                "W012 duplicate-ranks",
+                "W013 near-miss-candidate",
+                "W014 too-many-candidates",
+                "W015 outside-time-window",
+                "W016 empty-rows",
+                "W017 multi-name-cells",
+                "W018 duplicate-source",
+                "W019 same-file-sources",
+                "W020 identical-ballots",
+                "W021 rare-patterns",
+                "W022 unknown-choices",
+                "W023 unwritten-output",
+                "W024 tabulation-interrupted",
+                "W025 verification-mismatch",
+                "W026 reference-not-checked",
+                "W027 collation-unavailable",
+                "W028 snapshot-rules-mismatch",
+                "E000 other",
+                "E001 invalid-configuration",
+                "E002 unreadable-input",
+                "E003 malformed-ballots",
+                "E004 unknown-candidates",
+                "E005 tabulation-failed",
+                "E006 output-failed",
+                "E007 verification-failed",
+                "E008 failed-on-warning",
+                "E009 privacy-threshold",
+            ]
+        );
+        assert_eq!(
+            codes::WarningCode::parse("unknown-category"),
+            codes::WarningCode::parse("W003")
+        );
+    }
+
+    #[test]
+    fn fail_on_warning() {
+        // The ballots of the category 'guest' have no weight in the configuration.
+        let out_dir = std::env::temp_dir().join("timrcv_fail_on");
+        fs::create_dir_all(&out_dir).unwrap();
+        let out_path = out_dir.join("summary.json").display().to_string();
+        let run = |cli: &[&str]| {
+            run_election_at(
+                Some("./tests/csv_weights/csv_weights_config.json".to_string()),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                Some(Args::parse_from(cli)),
+            )
+        };
+
+        run(&["timrcv", "--fail-on", "W001,W002", "--summary-schema", "v2"]).unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(summary["warnings"][0]["code"], json!("W003"));
+        assert_eq!(summary["warnings"][0]["name"], json!("unknown-category"));
+
+        let err = run(&["timrcv", "--fail-on", "W001,unknown-category"]).unwrap_err();
+        assert!(matches!(err, RcvError::FailedOnWarnings { .. }));
+        assert_eq!(err.code(), codes::ErrorCode::FailedOnWarning);
+        assert_eq!(
+            err.to_string(),
+            "failing because of the warnings W003 (--fail-on)"
+        );
+
+        let err = run(&["timrcv", "--fail-on", "W999"]).unwrap_err();
+        assert_eq!(err.code().code(), "E001");
+    }
+
+    #[test]
+    fn csv_ranks_in_cells() {
+        let dir = std::env::temp_dir().join("timrcv_ranks_in_cells");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ballots.csv");
+        fs::write(&path, "Alice,Bob,Carol\n1,2,\n2,1,3\n1,3,2\n").unwrap();
+
+        // Read as a simple csv file, the ranks are write-ins: the layout is suggested.
+        codes::take_warnings();
+        let cfs: FileSource = serde_json::from_value(json!({
+            "filePath": "ballots.csv",
+            "provider": "csv",
+            "firstVoteRowIndex": "2",
+        }))
+        .unwrap();
+        let root = dir.display().to_string();
+        let (ballots, _) = read_parsed_ballots(root.clone(), &cfs, None).unwrap();
+        assert_eq!(
+            ballots[0].choices,
+            vec![vec!["1".to_string()], vec!["2".to_string()]]
+        );
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::RanksInCells);
+
+        // The same with the default firstVoteRowIndex, which reads the header as a ballot.
+        let default_cfs: FileSource = serde_json::from_value(json!({
+            "filePath": "ballots.csv",
+            "provider": "csv",
+        }))
+        .unwrap();
+        let (ballots, _) = read_parsed_ballots(root.clone(), &default_cfs, None).unwrap();
+        assert_eq!(ballots.len(), 4);
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::RanksInCells);
+        assert!(
+            warnings[0].message.contains("8 of 8 choices")
+                && warnings[0]
+                    .message
+                    .contains("the header is read as a ballot"),
+            "{}",
+            warnings[0].message
+        );
+
+        // With the layout, the header gives the candidates.
+        let mut cfs = cfs;
+        cfs.layout = Some("ranks-in-cells".to_string());
+        let (ballots, candidates) = read_parsed_ballots(root, &cfs, None).unwrap();
+        let names: Vec<&str> = candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Alice", "Bob", "Carol"]);
+        assert_eq!(
+            ballots[1].choices,
+            vec![
+                vec!["Bob".to_string()],
+                vec!["Alice".to_string()],
+                vec!["Carol".to_string()]
+            ]
+        );
+        assert!(codes::take_warnings().is_empty());
+
+        // The same with the --likert flag and no configuration.
+        let out_path = dir.join("summary.json").display().to_string();
+        let status = run_election_at(
+            None,
+            None,
+            Some(path.display().to_string()),
+            Some(out_path.clone()),
+            true,
+            Some(Args::parse_from(["timrcv", "--likert"])),
+        )
+        .unwrap();
+        assert_eq!(status, SummaryStatus::WinnerDeclared);
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(summary["reason"], json!("winner declared: Alice"));
+    }
+
+    #[test]
+    fn compress_ranks_likert() {
+        // The first ballot ranks A then C, with a gap at rank 2. A is eliminated first.
+        let dir = std::env::temp_dir().join("timrcv_compress_ranks");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ballots.csv"), "A,B,C\n1,,3\n,1,\n,1,\n,,1\n,,1\n").unwrap();
+        let out_path = dir.join("summary.json").display().to_string();
+        let run = |compress: bool| -> JSValue {
+            let config = json!({
+                "outputSettings": {"contestName": "compress", "summarySchema": "v2"},
+                "cvrFileSources": [{
+                    "filePath": "ballots.csv",
+                    "provider": "csv_likert",
+                    "firstVoteRowIndex": "2",
+                    "compressRanks": compress,
+                }],
+                "candidates": [{"name": "A"}, {"name": "B"}, {"name": "C"}],
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "exhaustImmediately",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "0",
+                    "maxRankingsAllowed": "8",
+                },
+            });
+            let path = dir.join("config.json");
+            fs::write(&path, config.to_string()).unwrap();
+            run_election_at(
+                Some(path.display().to_string()),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                None,
+            )
+            .unwrap();
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap()
+        };
+
+        // The skipped rank exhausts the ballot, and B wins the tie with C.
+        let strict = run(false);
+        assert_eq!(
+            strict["results"][0]["tallyResults"][0]["transfers"],
+            json!({"exhausted": "1"})
+        );
+        assert_eq!(strict["reason"], json!("winner declared: B"));
+        assert!(strict.get("warnings").is_none());
+
+        let compressed = run(true);
+        assert_eq!(
+            compressed["results"][0]["tallyResults"][0]["transfers"],
+            json!({"C": "1"})
+        );
+        assert_eq!(compressed["reason"], json!("winner declared: C"));
+        assert_eq!(compressed["warnings"][0]["code"], json!("W005"));
+    }
+
+    #[test]
+    fn make_fixture_from_local_tests() {
+        let dir = std::env::temp_dir().join("timrcv_make_fixture");
+        let _ = fs::remove_dir_all(&dir);
+        let test_dir = dir.display().to_string();
+        // The CSV file is sampled, and keeps all the candidates.
+        fixture::make_fixture(
+            "./tests/csv_simple_2/csv_simple_2_config.json",
+            &dir.join("csv_sampled").display().to_string(),
+            3,
+            &None,
+        )
+        .unwrap();
+        let rows = fs::read_to_string(dir.join("csv_sampled").join("example.csv")).unwrap();
+        assert_eq!(rows, "A,B,,D\nA,C,B,\nB,A,D,C\n");
+        run_election_test_in(
+            &test_dir,
+            "csv_sampled",
+            "csv_sampled_config.json",
+            "csv_sampled_expected_summary.json",
+        );
+
+        // The Excel file is converted to the simple CSV format.
+        fixture::make_fixture(
+            "./tests/msforms_1/msforms_1_config.json",
+            &dir.join("msforms_converted").display().to_string(),
+            200,
+            &None,
+        )
+        .unwrap();
+        let config_js: JSValue = serde_json::from_str(
+            &fs::read_to_string(
+                dir.join("msforms_converted")
+                    .join("msforms_converted_config.json"),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config_js["cvrFileSources"][0]["provider"], json!("csv"));
+        run_election_test_in(
+            &test_dir,
+            "msforms_converted",
+            "msforms_converted_config.json",
+            "msforms_converted_expected_summary.json",
+        );
+        // The tabulation of the converted ballots is the same as the original one.
+        let expected = |path: &str| -> JSValue {
+            let js: JSValue = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+            js["results"].clone()
+        };
+        assert_eq!(
+            expected(
+                &dir.join("msforms_converted")
+                    .join("msforms_converted_expected_summary.json")
+                    .display()
+                    .to_string()
+            ),
+            expected("./tests/msforms_1/msforms_1_expected_summary.json")
+        );
+    }
+
+    #[test]
+    fn fixture_sample_keeps_candidates() {
+        let ballot = |names: &[&str]| ParsedBallot {
+            id: None,
+            count: Some(1),
+            choices: names.iter().map(|n| vec![n.to_string()]).collect(),
+            challenged: false,
+            category: None,
+            respondent: None,
+            precinct: None,
+            style: None,
+        };
+        let mut ballots: Vec<ParsedBallot> = (0..10).map(|_| ballot(&["A", "B"])).collect();
+        ballots.push(ballot(&["C"]));
+        let candidates: Vec<RcvCandidate> = ["A", "B", "C"]
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: None,
+                inferred: false,
+            })
+            .collect();
+        assert_eq!(
+            fixture::sample_indices(&ballots, &candidates, 4),
+            vec![0, 1, 5, 10]
+        );
+        // All the candidates are kept, even above the maximum.
+        assert_eq!(
+            fixture::sample_indices(&ballots, &candidates, 1),
+            vec![0, 10]
+        );
+    }
+
+    #[test]
+    fn integers_with_thousands_separators() {
+        use NumberLocale::*;
+        // Trailing non-breaking spaces are common in exported spreadsheets.
+        assert_eq!(parse_integer(" 12\u{a0}", Plain), Ok(12));
+        assert_eq!(parse_integer("1 234", En), Ok(1234));
+        assert_eq!(parse_integer("1\u{a0}234", Eu), Ok(1234));
+        assert!(parse_integer("1 234", Plain).is_err());
+        assert_eq!(parse_integer("1.234", Eu), Ok(1234));
+        assert_eq!(parse_integer("1.234", En), Err("decimal number"));
+        assert_eq!(parse_integer("1,234,567", En), Ok(1234567));
+        assert_eq!(parse_integer("12,5", Eu), Err("decimal number"));
+        assert_eq!(
+            parse_integer("12,5", En),
+            Err("the thousands separators are not between groups of 3 digits")
+        );
+        assert_eq!(
+            parse_integer("12,5", Plain),
+            Err("unexpected separator for this numberLocale")
+        );
+        assert_eq!(parse_integer("x", Eu), Err("not an integer"));
+
+        // The errors give the position of the cell.
+        let dir = std::env::temp_dir().join("timrcv_number_locale");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ballots.csv").display().to_string();
+        fs::write(&path, "id,count,choice 1\nv1,1.234,A\nv2,\"12,5\",B\n").unwrap();
+        let source = |locale: &str| -> FileSource {
+            serde_json::from_value(json!({
+                "provider": "csv",
+                "filePath": "",
+                "firstVoteRowIndex": "2",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+                "numberLocale": locale,
+            }))
+            .unwrap()
+        };
+        let err = io_csv::read_csv_ranking(path.clone(), &source("eu")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 3, column 2: cannot read \"12,5\" as an integer: decimal number"
+        );
+        let err = io_csv::read_csv_ranking(path.clone(), &source("en")).unwrap_err();
+        assert!(matches!(*err, RcvError::InvalidNumber { lineno: 2, .. }));
+
+        let err = source("fr").number_locale().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown numberLocale \"fr\", the locales are: plain, en, eu"
+        );
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+    }
+
+    #[test]
+    fn sanity_check_wrong_column() {
+        let out_dir = std::env::temp_dir().join("timrcv_wrong_column");
+        fs::create_dir_all(&out_dir).unwrap();
+        let out_path = out_dir.join("summary.json").display().to_string();
+        let config_path = "./tests/csv_wrong_column/csv_wrong_column_config.json";
+        let run = |args: Option<Args>| {
+            run_election_at(
+                Some(config_path.to_string()),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                args,
+            )
+        };
+
+        // The precinct column is read as the first choice.
+        let err = run(None).unwrap_err();
+        assert!(matches!(err, RcvError::CandidateHitRate { .. }));
+        let msg = err.to_string();
+        assert!(msg.contains("only 0.0% of the first choices"), "{}", msg);
+        assert!(msg.contains("'North' (3), 'South' (3)"), "{}", msg);
+        assert!(
+            msg.contains("did you mean \"firstVoteColumnIndex\": \"3\"?"),
+            "{}",
+            msg
+        );
+
+        let args = Args::parse_from(["timrcv", "--no-sanity-checks"]);
+        run(Some(args)).unwrap();
+    }
+
+    #[test]
+    fn verify_official_rounds() {
+        let dir = std::env::temp_dir().join("timrcv_verify_rounds");
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string());
+        let verify = |official: JSValue| {
+            let path = dir.join("rounds.json");
+            fs::write(&path, official.to_string()).unwrap();
+            verify_rounds(&config_path, &None, path.to_str().unwrap(), &None).unwrap()
+        };
+
+        let report = verify(json!([
+            {"round": 1, "tally": {"A": 2, "B": 2, "C": 1, "D": 1}},
+            {"round": 2, "tally": {"A": "2", "B": "3", "C": "1"}},
+        ]));
+        assert!(report.is_match());
+        assert_eq!(report.matching_rounds, 2);
+        assert_eq!(report.computed_rounds, 4);
+
+        let report = verify(json!([
+            {"round": 1, "tally": {"A": 2, "B": 2, "C": 1, "D": 1}},
+            {"round": 2, "tally": {"A": 2, "B": 4, "C": 1}},
+        ]));
+        assert_eq!(report.matching_rounds, 1);
+        let js = verify_rounds::report_to_json(&report);
+        assert_eq!(js["match"], json!(false));
+        assert_eq!(js["divergence"]["round"], json!(2));
+        assert_eq!(
+            js["divergence"]["deltas"],
+            json!([{"candidate": "B", "official": 4, "computed": 3, "delta": -1}])
+        );
+
+        let path = dir.join("too_large.json");
+        fs::write(
+            &path,
+            json!([{"round": 4294967297u64, "tally": {"A": 2}}]).to_string(),
+        )
+        .unwrap();
+        let err = verify_rounds::read_official_rounds(path.to_str().unwrap()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("the round number 4294967297 is too large"),
+            "{}",
+            err
+        );
+
+        // The challenged ballots are counted as in the tabulation.
+        let config_path = Some("./tests/csv_challenged/csv_challenged_config.json".to_string());
+        let path = dir.join("challenged.json");
+        fs::write(
+            &path,
+            json!([{"round": 1, "tally": {"A": 2, "B": 3}}]).to_string(),
+        )
+        .unwrap();
+        let verify_with = |mode: &str| {
+            let args = Args::parse_from(["timrcv", "--with-challenged", mode]);
+            verify_rounds(&config_path, &None, path.to_str().unwrap(), &Some(args))
+        };
+        assert!(!verify_with("include").unwrap().is_match());
+        assert!(verify_with("exclude").unwrap().is_match());
+        assert!(matches!(
+            verify_with("both"),
+            Err(RcvError::VerifyRoundsChallengedBoth {})
+        ));
+    }
+
+    #[test]
+    fn overvote_delimiter_in_candidate_names() {
+        let candidates: Vec<RcvCandidate> = ["Smith | Jones Coalition", "Lee", "Park"]
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: None,
+                inferred: false,
+            })
+            .collect();
+        let source: FileSource = serde_json::from_value(json!({
+            "provider": "csv",
+            "filePath": "",
+            "overvoteDelimiter": "|",
+        }))
+        .unwrap();
+        let ballot = ParsedBallot {
+            id: None,
+            count: None,
+            choices: [
+                "Smith | Jones Coalition",
+                " Smith | Jones Coalition ",
+                "Lee|Park",
+                "Lee | Lee",
+                "Lee | Bogus",
+                "|",
+            ]
+            .iter()
+            .map(|c| vec![c.to_string()])
+            .collect(),
+            challenged: false,
+            category: None,
+            respondent: None,
+            precinct: None,
+            style: None,
+        };
+        let config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        let ballots = validated_ballots(&[ballot], &candidates, &source, &config.rules);
+        assert_eq!(
+            ballots[0].candidates,
+            vec![
+                BallotChoice::Candidate("Smith | Jones Coalition".to_string()),
+                BallotChoice::Candidate("Smith | Jones Coalition".to_string()),
+                BallotChoice::Overvote,
+                BallotChoice::Candidate("Lee".to_string()),
+                BallotChoice::UndeclaredWriteIn,
+                BallotChoice::Overvote,
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_cells_are_blank() {
+        let candidates: Vec<RcvCandidate> = ["Alice", "Bob"]
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: None,
+                inferred: false,
+            })
+            .collect();
+        let config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        let expected = vec![
+            BallotChoice::Candidate("Alice".to_string()),
+            BallotChoice::Blank,
+            BallotChoice::Blank,
+            BallotChoice::Candidate("Bob".to_string()),
+        ];
+
+        // A csv file with a space and a tab.
+        let dir = std::env::temp_dir().join("timrcv_whitespace_cells");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("ballots.csv"),
+            "id,c1,c2,c3,c4\n1,Alice, ,\t,Bob \n",
+        )
+        .unwrap();
+        let cfs: FileSource = serde_json::from_value(json!({
+            "filePath": "ballots.csv",
+            "provider": "csv",
+            "firstVoteRowIndex": "2",
+            "firstVoteColumnIndex": "2",
+        }))
+        .unwrap();
+        let (parsed, _) =
+            read_parsed_ballots(dir.display().to_string(), &cfs, Some(&candidates)).unwrap();
+        // The parsed ballots keep the cells as they are in the file.
+        assert_eq!(parsed[0].choices[1], vec![" ".to_string()]);
+        let ballots = validated_ballots(&parsed, &candidates, &cfs, &config.rules);
+        assert_eq!(ballots[0].candidates, expected);
+
+        // The ranking cell of a MS Forms file, split on the semicolons.
+        let cfs: FileSource = serde_json::from_value(json!({
+            "filePath": "",
+            "provider": "ms_forms",
+        }))
+        .unwrap();
+        let parsed = ParsedBallot {
+            id: None,
+            count: None,
+            choices: "Alice; ;\t;Bob"
+                .split(';')
+                .map(|c| vec![c.to_string()])
+                .collect(),
+            challenged: false,
+            category: None,
+            respondent: None,
+            precinct: None,
+            style: None,
+        };
+        let ballots = validated_ballots(&[parsed], &candidates, &cfs, &config.rules);
+        assert_eq!(ballots[0].candidates, expected);
+        assert!(ballots[0]
+            .candidates
+            .iter()
+            .all(|c| *c != BallotChoice::UndeclaredWriteIn));
+    }
+
+    #[test]
+    fn builder_and_readers_classify_the_same() {
+        use ranked_voting::Builder;
+
+        let names = ["Alice", "Bob", "Carol"];
+        let candidates: Vec<RcvCandidate> = names
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: None,
+                inferred: false,
+            })
+            .collect();
+        let source: FileSource = serde_json::from_value(json!({
+            "filePath": "",
+            "provider": "csv",
+            "overvoteDelimiter": "|",
+            "overvoteLabel": "OVER",
+            "undervoteLabel": "UNDER",
+        }))
+        .unwrap();
+        let config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        let rules = validate_rules(&config.rules).unwrap();
+        // The same logical ballots: duplicates and blanks inside a group, labels, write-ins,
+        // delimited cells and empty groups.
+        let ballots: Vec<(Vec<Vec<&str>>, u64)> = vec![
+            (vec![vec!["Alice", " Alice"], vec!["Bob"]], 4),
+            (vec![vec!["Bob", ""], vec!["Carol"]], 2),
+            (vec![vec!["Carol", "Bob"], vec!["Alice"]], 2),
+            (vec![vec!["UWI"], vec!["Bob|Bob"], vec!["Alice"]], 1),
+            (vec![vec!["UNDER"], vec![], vec!["Carol"]], 2),
+            (vec![vec!["OVER"], vec!["Carol"]], 1),
+            (vec![vec!["Dave"], vec!["Alice|Carol"]], 1),
+            (vec![vec![" ", ""], vec!["Carol "]], 1),
+        ];
+        let parsed: Vec<ParsedBallot> = ballots
+            .iter()
+            .map(|(groups, count)| ParsedBallot {
+                id: None,
+                count: Some(*count),
+                choices: groups
+                    .iter()
+                    .map(|g| g.iter().map(|c| c.to_string()).collect())
+                    .collect(),
+                challenged: false,
+                category: None,
+                respondent: None,
+                precinct: None,
+                style: None,
+            })
+            .collect();
+
+        let validated = validated_ballots(&parsed, &candidates, &source, &config.rules);
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        let mut from_readers = Builder::new(&rules).unwrap().candidates(&names).unwrap();
+        for ballot in validated.iter() {
+            from_readers.add_ballot(ballot.clone()).unwrap();
+        }
+        let mut from_builder = Builder::new(&rules)
+            .unwrap()
+            .candidates(&names)
+            .unwrap()
+            .labels(super::choice_labels(&source));
+        for pb in parsed.iter() {
+            from_builder
+                .add_vote(&pb.choices, pb.count.unwrap() as u32)
+                .unwrap();
+        }
+
+        assert_eq!(
+            validated[0].candidates[0],
+            BallotChoice::Candidate("Alice".to_string())
+        );
+        assert_eq!(
+            validated[1].candidates[0],
+            BallotChoice::Candidate("Bob".to_string())
+        );
+        assert_eq!(validated[2].candidates[0], BallotChoice::Overvote);
+        assert_eq!(validated[7].candidates[0], BallotChoice::Blank);
+        // The order of the tallies is not specified.
+        let sorted = |mut res: VotingResult| {
+            for round in res.round_stats.iter_mut() {
+                round.tally.sort();
+            }
+            res
+        };
+        assert_eq!(
+            sorted(ranked_voting::run_election(&from_builder).unwrap()),
+            sorted(ranked_voting::run_election(&from_readers).unwrap())
+        );
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn xlsx_workbook() {
+        use calamine::{open_workbook, DataType, Reader, Xlsx};
+
+        let dir = std::env::temp_dir().join("timrcv_xlsx");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("results.xlsx").display().to_string();
+        let args = Args::parse_from(["timrcv", "--out-xlsx", &path]);
+        run_election_at(
+            Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string()),
+            None,
+            None,
+            Some("".to_string()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+
+        let mut workbook: Xlsx<_> = open_workbook(&path).unwrap();
+        assert_eq!(
+            workbook.sheet_names().to_vec(),
+            vec![
+                "Summary",
+                "Candidates",
+                "Round 1",
+                "Round 2",
+                "Round 3",
+                "Round 4"
+            ]
+        );
+        let summary = workbook.worksheet_range("Summary").unwrap().unwrap();
+        assert_eq!(
+            summary.get_value((0, 1)),
+            Some(&DataType::String("CSV 1".to_string()))
+        );
+        assert_eq!(
+            summary.get_value((4, 1)),
+            Some(&DataType::String("A".to_string()))
+        );
+        assert_eq!(summary.get_value((5, 1)), Some(&DataType::Float(4.0)));
+        assert_eq!(summary.get_value((6, 1)), Some(&DataType::Float(4.0)));
+        assert_eq!(summary.get_value((9, 1)), Some(&DataType::Float(0.0)));
+
+        let candidates = workbook.worksheet_range("Candidates").unwrap().unwrap();
+        assert_eq!(
+            candidates.get_value((1, 0)),
+            Some(&DataType::String("A".to_string()))
+        );
+        assert_eq!(
+            candidates.get_value((1, 2)),
+            Some(&DataType::String("elected".to_string()))
+        );
+        assert_eq!(
+            candidates.get_value((4, 0)),
+            Some(&DataType::String("D".to_string()))
+        );
+        assert_eq!(
+            candidates.get_value((4, 2)),
+            Some(&DataType::String("eliminated".to_string()))
+        );
+        assert_eq!(candidates.get_value((4, 3)), Some(&DataType::Float(1.0)));
+
+        // The counts are numbers, not strings.
+        let round2 = workbook.worksheet_range("Round 2").unwrap().unwrap();
+        let row = |idx: usize| -> Vec<DataType> {
+            (0..4)
+                .map(|c| round2.get((idx, c)).unwrap().clone())
+                .collect()
+        };
+        assert_eq!(
+            row(1),
+            vec![
+                DataType::String("B".to_string()),
+                DataType::Float(3.0),
+                DataType::Float(50.0),
+                DataType::String("continuing".to_string()),
+            ]
+        );
+        assert_eq!(
+            row(3),
+            vec![
+                DataType::String("C".to_string()),
+                DataType::Float(1.0),
+                DataType::Float(16.7),
+                DataType::String("eliminated".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rctab_csv_golden() {
+        // The golden file is written by hand, in the layout of the summary.csv of RCTab 1.3:
+        // the tallies and the inactive ballots come from the reference summary of the contest,
+        // and the threshold of each round is the majority of its active ballots (8 with 15
+        // active ballots in round 1, 6 with 11 in round 2).
+        let dir = std::env::temp_dir().join("timrcv_rctab_csv");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("summary.csv").display().to_string();
+        let args = Args::parse_from(["timrcv", "--out-rctab-csv", &path]);
+        run_election_at(
+            Some("./tests/csv_inactive_ballots/csv_inactive_ballots_config.json".to_string()),
+            None,
+            None,
+            Some("".to_string()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+        let golden = fs::read_to_string(
+            "./tests/csv_inactive_ballots/csv_inactive_ballots_rctab_summary.csv",
+        )
+        .unwrap();
+        let version_line = format!("Generated By,timrcv {}", env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .replace(&version_line, "Generated By,timrcv VERSION"),
+            golden
+        );
+    }
+
+    #[test]
+    fn render_from_summary() {
+        let dir = std::env::temp_dir().join("timrcv_render");
+        fs::create_dir_all(&dir).unwrap();
+        let rctab_path = dir.join("summary.csv").display().to_string();
+        let tabulated_rctab_path = dir.join("tabulated_summary.csv").display().to_string();
+        let flat_path = dir.join("flat.csv").display().to_string();
+        let tabulated_flat_path = dir.join("tabulated_flat.csv").display().to_string();
+        let summary_path = "./tests/csv_simple_2/csv_simple_2_expected_summary.json".to_string();
+        let args = Args::parse_from([
+            "timrcv",
+            "render",
+            "--from-summary",
+            &summary_path,
+            "--out-rctab-csv",
+            &rctab_path,
+            "--out-flat-csv",
+            &flat_path,
+        ]);
+        run_render(summary_path.clone(), Some(args)).unwrap();
+
+        // The same tables as from the ballots, marked as rendered.
+        let args = Args::parse_from([
+            "timrcv",
+            "--out-flat-csv",
+            &tabulated_flat_path,
+            "--out-rctab-csv",
+            &tabulated_rctab_path,
+        ]);
+        run_election_at(
+            Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string()),
+            None,
+            None,
+            Some("".to_string()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&flat_path).unwrap(),
+            fs::read_to_string(&tabulated_flat_path).unwrap()
+        );
+        let version_line = format!(
+            "Generated By,\"timrcv {} (rendered from summary, not tabulated)\"",
+            env!("CARGO_PKG_VERSION")
+        );
+        let rendered = fs::read_to_string(&rctab_path).unwrap();
+        assert!(rendered.contains(&version_line), "{}", rendered);
+        assert_eq!(
+            rendered.replace(
+                &version_line,
+                &format!("Generated By,timrcv {}", env!("CARGO_PKG_VERSION"))
+            ),
+            fs::read_to_string(&tabulated_rctab_path).unwrap()
+        );
+
+        let (_, result) = render::result_from_summary(&summary_path).unwrap();
+        let expected: JSValue =
+            serde_json::from_str(&fs::read_to_string(&summary_path).unwrap()).unwrap();
+        assert_eq!(
+            result.threshold.to_string(),
+            expected["config"]["threshold"].as_str().unwrap()
+        );
+        let err = render::result_from_summary("./tests/csv_simple_2/csv_simple_2_config.json")
+            .unwrap_err();
+        assert!(matches!(err, RcvError::InvalidSummary { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn interrupted_tabulation_writes_completed_rounds() {
+        use std::sync::atomic::AtomicBool;
+        use std::sync::Arc;
+        let dir = std::env::temp_dir().join("timrcv_interrupted");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("summary.json");
+        let _ = fs::remove_file(&path);
+        // The flag is set before the tabulation: it stops once the first round is complete.
+        let mut options = RunOptions::from_args(&None).unwrap();
+        options.interrupt = Some(Arc::new(AtomicBool::new(true)));
+        let config_path = Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string());
+        let config = resolve_config(&config_path, &None, &None).unwrap();
+        let status = run_election(
+            config,
+            &options,
+            &config_path,
+            None,
+            Some(path.display().to_string()),
+            true,
+        )
+        .unwrap();
+        assert_eq!(status, SummaryStatus::Interrupted);
+        assert_eq!(status.exit_code(), 130);
+        let summary: JSValue = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(summary["status"], json!("interrupted"));
+        assert_eq!(
+            summary["results"],
+            json!([{
+                "round": 1,
+                "tally": {"A": "2", "B": "2", "C": "1", "D": "1"},
+                "tallyResults": [{"eliminated": "D", "transfers": {"B": "1"}}],
+            }])
+        );
+        // No temporary file is left behind.
+        assert!(!dir.join("summary.json.tmp").exists());
+    }
+
+    #[test]
+    fn failed_summary_write_removes_temporary_file() {
+        let dir = std::env::temp_dir().join("timrcv_failed_summary_write");
+        // The summary cannot replace a directory: the rename fails.
+        let path = dir.join("summary.json");
+        fs::create_dir_all(&path).unwrap();
+        let path = path.display().to_string();
+        let err = super::write_summary(&Summary::default(), &path).unwrap_err();
+        assert!(matches!(err, RcvError::SummaryWrite { .. }), "{:?}", err);
+        assert!(!dir.join("summary.json.tmp").exists());
+    }
+
+    #[test]
+    fn audit_first_choices() {
+        let dir = std::env::temp_dir().join("timrcv_audit_first_choices");
+        fs::create_dir_all(&dir).unwrap();
+        let run = |totals: &str, tolerance: &str| {
+            let audit_path = dir.join("totals.csv").display().to_string();
+            fs::write(&audit_path, totals).unwrap();
+            let out = dir.join("summary.json").display().to_string();
+            let args = Args::parse_from([
+                "timrcv",
+                "--audit-first-choices",
+                audit_path.as_str(),
+                "--audit-tolerance",
+                tolerance,
+            ]);
+            let _ = fs::remove_file(&out);
+            let res = run_election_at(
+                Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string()),
+                None,
+                None,
+                Some(out.clone()),
+                true,
+                Some(args),
+            );
+            (res, std::path::Path::new(&out).exists())
+        };
+
+        // Matching totals, with a header and names with spaces around them.
+        let (res, written) = run("candidate,count\nA,2\n B ,2\nC,1\nD,1\n", "0");
+        assert_eq!(res.unwrap(), SummaryStatus::WinnerDeclared);
+        assert!(written);
+
+        // A one-vote discrepancy fails before the tabulation, unless it is tolerated.
+        let (res, written) = run("A,2\nB,3\nC,1\nD,1\n", "0");
+        let err = res.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the first choices differ from the audit file {} by more than 0 votes: B: 2 computed, 3 in the audit file (-1)",
+                dir.join("totals.csv").display()
+            )
+        );
+        assert_eq!(err.code(), codes::ErrorCode::VerificationFailed);
+        // The summary only has the error.
+        assert!(written);
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(dir.join("summary.json")).unwrap()).unwrap();
+        assert_eq!(summary["status"], json!("error"));
+        assert_eq!(summary["reason"], json!(err.to_string()));
+        assert_eq!(summary["results"], json!([]));
+        let (res, _) = run("A,2\nB,3\nC,1\nD,1\n", "1");
+        assert!(res.is_ok());
+
+        // A candidate missing from the audit file has no votes in it.
+        let (res, _) = run("A,2\nB,2\nC,1\n", "0");
+        match res.unwrap_err() {
+            RcvError::AuditMismatch { mismatches, .. } => assert_eq!(
+                mismatches,
+                vec!["D: 1 computed, missing from the audit file".to_string()]
+            ),
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        // A count that is not a number after the first row.
+        let (res, _) = run("A,2\nB,two\n", "0");
+        assert!(matches!(res, Err(RcvError::AuditParse { lineno: 2, .. })));
+    }
+
+    #[test]
+    fn output_name_template_with_all_placeholders() {
+        use std::time::{Duration, UNIX_EPOCH};
+        let settings: OutputSettings = serde_json::from_value(json!({
+            "contestName": "Mayor: 2024",
+            "contestOffice": "City/Council",
+            "contestJurisdiction": "Springfield",
+            "contestDate": "2024-11-05",
+        }))
+        .unwrap();
+        let started = UNIX_EPOCH + Duration::from_secs(1730840580);
+        let mut names = output_name::OutputNames::new(&settings, started);
+        let template = "out/{contest}_{office}_{jurisdiction}_{date}_{timestamp}_{winner}.json";
+        assert_eq!(
+            names.render(template),
+            "out/Mayor_ 2024_City_Council_Springfield_2024-11-05_20241105T210300Z_none.json"
+        );
+        names.set_winners(Some(&["Anna <A>".to_string()]));
+        assert_eq!(
+            names.render("summary_{winner}.json"),
+            "summary_Anna _A_.json"
+        );
+    }
+
+    #[test]
+    fn output_name_template_literal_braces() {
+        // Only the known placeholders are templates: the other braces are in the file name.
+        assert!(!output_name::is_template("summary_{n}.json"));
+        assert!(!output_name::is_template("summary}_{office.json"));
+        assert!(output_name::is_template("summary_{n}_{office}.json"));
+        let dir = std::env::temp_dir().join("timrcv_output_braces");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("summary_{n}.json");
+        let _ = fs::remove_file(&path);
+        run_election_at(
+            Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string()),
+            None,
+            None,
+            Some(path.display().to_string()),
+            true,
+            None,
+        )
+        .unwrap();
+        assert!(path.is_file());
+    }
+
+    #[test]
+    fn output_name_template_missing_directory() {
+        // The directory of a template is checked before the ballots are read.
+        let missing = std::env::temp_dir().join("timrcv_output_missing");
+        let _ = fs::remove_dir_all(&missing);
+        let run = |template: &str, in_path: Option<&str>| {
+            run_election_at(
+                Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string()),
+                None,
+                in_path.map(|p| p.to_string()),
+                Some(missing.join(template).display().to_string()),
+                true,
+                None,
+            )
+        };
+        // The ballots of a missing input would fail to be read.
+        let err = run(
+            "{office}/summary_{winner}.json",
+            Some("./tests/missing.csv"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, RcvError::OutputDirMissing { .. }));
+        assert!(err.to_string().contains("timrcv_output_missing/office"));
+        // A directory that depends on the winner is only checked after the tabulation.
+        let err = run("{winner}/summary.json", None).unwrap_err();
+        assert!(matches!(err, RcvError::OutputDirMissing { .. }));
+        assert!(err.to_string().contains("timrcv_output_missing/A"));
+    }
+
+    #[test]
+    fn output_name_template_renders_the_winner() {
+        let dir = std::env::temp_dir().join("timrcv_output_name");
+        fs::create_dir_all(&dir).unwrap();
+        let template = dir.join("summary_{office}_{date}_{winner}.json");
+        run_election_at(
+            Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string()),
+            None,
+            None,
+            Some(template.display().to_string()),
+            true,
+            None,
+        )
+        .unwrap();
+        let summary: JSValue = serde_json::from_str(
+            &fs::read_to_string(dir.join("summary_office_2020-07-19_A.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(summary["status"], json!("winnerDeclared"));
+    }
+
+    #[test]
+    fn fairvote_row_golden_and_append() {
+        let dir = std::env::temp_dir().join("timrcv_fairvote");
+        fs::create_dir_all(&dir).unwrap();
+        let row_path = dir.join("rows.csv");
+        let _ = fs::remove_file(&row_path);
+        let args = Args::parse_from(["timrcv", "--out-fairvote-row", row_path.to_str().unwrap()]);
+        let run = || {
+            run_election_at(
+                Some("./tests/csv_simple_2/csv_simple_2_config.json".to_string()),
+                None,
+                None,
+                Some("".to_string()),
+                true,
+                Some(args.clone()),
+            )
+            .unwrap()
+        };
+        let golden =
+            fs::read_to_string("./tests/csv_simple_2/csv_simple_2_fairvote_row.csv").unwrap();
+
+        run();
+        assert_eq!(fs::read_to_string(&row_path).unwrap(), golden);
+
+        // A second election is appended without repeating the header.
+        run();
+        let contents = fs::read_to_string(&row_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        let golden_lines: Vec<&str> = golden.lines().collect();
+        assert_eq!(
+            lines,
+            vec![golden_lines[0], golden_lines[1], golden_lines[1]]
+        );
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn exhausted_ballots_from_inactive_ballots() {
+        use calamine::{open_workbook, DataType, Reader, Xlsx};
+
+        let dir = std::env::temp_dir().join("timrcv_exhausted");
+        fs::create_dir_all(&dir).unwrap();
+        let row_path = dir.join("rows.csv").display().to_string();
+        let xlsx_path = dir.join("results.xlsx").display().to_string();
+        let _ = fs::remove_file(&row_path);
+        let args = Args::parse_from([
+            "timrcv",
+            "--out-fairvote-row",
+            &row_path,
+            "--out-xlsx",
+            &xlsx_path,
+        ]);
+        run_election_at(
+            Some("./tests/csv_inactive_ballots/csv_inactive_ballots_config.json".to_string()),
+            None,
+            None,
+            Some("".to_string()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+
+        // 2 ballots are inactive before the first round, 6 in the final round.
+        let contents = fs::read_to_string(&row_path).unwrap();
+        let row: Vec<&str> = contents.lines().nth(1).unwrap().split(',').collect();
+        assert_eq!(row[14], "4");
+
+        let mut workbook: Xlsx<_> = open_workbook(&xlsx_path).unwrap();
+        let summary = workbook.worksheet_range("Summary").unwrap().unwrap();
+        assert_eq!(summary.get_value((9, 1)), Some(&DataType::Float(6.0)));
+        for (sheet, exhausted) in [("Round 1", 2.0), ("Round 2", 6.0)] {
+            let round = workbook.worksheet_range(sheet).unwrap().unwrap();
+            let last = round.height() - 1;
+            assert_eq!(
+                round.get_value((last as u32, 0)),
+                Some(&DataType::String("Exhausted".to_string()))
+            );
+            assert_eq!(
+                round.get_value((last as u32, 1)),
+                Some(&DataType::Float(exhausted))
+            );
+        }
+    }
+
+    #[test]
+    fn csv_patterns() {
+        let summary = read_patterns(
+            &Some("./tests/csv_patterns/csv_patterns_config.json".to_string()),
+            &None,
+            &None,
+        )
+        .unwrap();
+        let patterns: Vec<(&str, u64)> = summary
+            .patterns
+            .iter()
+            .map(|p| (p.pattern.as_str(), p.count))
+            .collect();
+        assert_eq!(
+            patterns,
+            vec![
+                ("A > B > C", 3),
+                ("B > A", 2),
+                ("A > (skip) > C", 1),
+                ("A > [B/C]", 1),
+                ("C", 1)
+            ]
+        );
+        assert_eq!(summary.total_ballots, 8);
+        assert_eq!(summary.unique_patterns, 5);
+        assert_eq!(summary.singletons, 3);
+
+        let js = patterns::patterns_to_json(&summary, 2);
+        assert_eq!(js["patterns"].as_array().unwrap().len(), 2);
+        assert_eq!(js["patterns"][1]["percent"], json!(25.0));
+    }
+
+    #[test]
+    fn csv_id_and_count_columns() {
+        let cfs: FileSource = serde_json::from_value(json!({
+            "provider": "csv",
+            "filePath": "example.csv",
+            "idColumnIndex": "1",
+            "countColumnIndex": 2,
+            "firstVoteColumnIndex": "3",
+        }))
+        .unwrap();
+        let ballots =
+            io_csv::read_csv_ranking("./tests/csv_simple_1/example.csv".to_string(), &cfs).unwrap();
+        assert_eq!(ballots[0].id, Some("id1".to_string()));
+        assert_eq!(ballots[0].count, Some(20));
+        assert_eq!(ballots[3].count, Some(10));
+        assert_eq!(
+            ballots[0].choices,
+            vec![vec!["A"], vec!["B"], vec!["C"], vec!["D"]]
+        );
+    }
+
+    #[test]
+    fn empty_count_cells() {
+        use calamine::DataType;
+
+        let dir = std::env::temp_dir().join("timrcv_empty_count_cells");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ballots.csv").display().to_string();
+        let cfs: FileSource = serde_json::from_value(json!({
+            "provider": "csv",
+            "filePath": "",
+            "firstVoteRowIndex": "2",
+            "idColumnIndex": "1",
+            "countColumnIndex": "2",
+            "firstVoteColumnIndex": "3",
+        }))
+        .unwrap();
+        // The empty cells, with or without spaces, count once.
+        fs::write(&path, "id,count,choice 1\nv1,3,A\nv2,,B\nv3, ,A\nv4,2,B\n").unwrap();
+        let ballots = io_csv::read_csv_ranking(path.clone(), &cfs).unwrap();
+        let counts: Vec<Option<u64>> = ballots.iter().map(|pb| pb.count).collect();
+        assert_eq!(counts, vec![Some(3), None, None, Some(2)]);
+        let parsed: u64 = ballots.iter().map(|pb| pb.count.unwrap_or(1)).sum();
+        assert_eq!(parsed, 7);
+
+        // A count that is not empty must still be a number.
+        fs::write(&path, "id,count,choice 1\nv1,3,A\nv2,,B\nv3,x,A\n").unwrap();
+        let err = io_csv::read_csv_ranking(path, &cfs).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 4, column 2: cannot read \"x\" as an integer: not an integer"
+        );
+
+        // The same rules for the counts of the Excel files.
+        use NumberLocale::*;
+        let count = |cell: DataType, locale| io_ess::excel_count(&cell, 2, 5, locale);
+        assert_eq!(count(DataType::Int(4), Plain).unwrap(), Some(4));
+        assert_eq!(count(DataType::Float(2.0), Plain).unwrap(), Some(2));
+        assert_eq!(count(DataType::Empty, Plain).unwrap(), None);
+        let err = io_ess::excel_count(&DataType::Float(1.5), 4, 5, Plain).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 4, column 5: cannot read \"1.5\" as an integer: decimal number"
+        );
+        // A negative count is refused, instead of wrapping or saturating.
+        let err = count(DataType::Int(-3), Plain).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 2, column 5: cannot read \"-3\" as an integer: negative number"
+        );
+        assert!(matches!(
+            count(DataType::Float(-3.0), Plain),
+            Err(RcvError::InvalidNumber { .. })
+        ));
+        // The counts written as text follow the numberLocale, and the other text is a choice.
+        let text = |s: &str| DataType::String(s.to_string());
+        assert_eq!(count(text("1 234"), En).unwrap(), Some(1234));
+        assert_eq!(count(text("1.234"), Eu).unwrap(), Some(1234));
+        assert_eq!(count(text(" 7\u{a0}"), Plain).unwrap(), Some(7));
+        let err = count(text("12,5"), Eu).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 2, column 5: cannot read \"12,5\" as an integer: decimal number"
+        );
+        assert_eq!(count(text("Alice"), Plain).unwrap(), None);
+    }
+
+    #[test]
+    fn column_indices_are_one_based() {
+        let cfs = |idx: JSValue| -> FileSource {
+            serde_json::from_value(json!({
+                "provider": "csv",
+                "filePath": "example.csv",
+                "idColumnIndex": idx.clone(),
+                "countColumnIndex": idx.clone(),
+                "firstVoteColumnIndex": idx,
+            }))
+            .unwrap()
+        };
+        for idx in [json!(1), json!("1"), json!("A")] {
+            let c = cfs(idx);
+            assert_eq!(c.id_column_index_int().unwrap(), Some(0));
+            assert_eq!(c.count_column_index_int().unwrap(), Some(0));
+            assert_eq!(c.first_vote_column_index().unwrap(), 0);
+        }
+        assert_eq!(cfs(json!("C")).id_column_index_int().unwrap(), Some(2));
+        assert_eq!(cfs(json!("z")).id_column_index_int().unwrap(), Some(25));
+        assert_eq!(cfs(json!("AA")).id_column_index_int().unwrap(), Some(26));
+        assert_eq!(cfs(json!("AB")).id_column_index_int().unwrap(), Some(27));
+        for idx in ["", "é", "ABCD"] {
+            let err = cfs(json!(idx)).id_column_index_int().unwrap_err();
+            assert!(matches!(err, RcvError::InvalidColumnLetter { .. }));
+            assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+        }
+        assert!(matches!(
+            cfs(json!(0)).id_column_index_int(),
+            Err(RcvError::ColumnIndexZero {})
+        ));
+    }
+
+    #[test]
+    fn export_simple_csv_roundtrip() {
+        // A ballot set with every kind of choice: overvotes, undervotes, blanks, write-ins and
+        // repeated candidates, with ids, counts, weights and challenged ballots, in two sources
+        // with different numbers of ranks. The second source has neither ids nor counts.
+        let dir = std::env::temp_dir().join("timrcv_export_simple_csv_roundtrip");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("ballots.csv"),
+            "id,count,status,category,choice 1,choice 2,choice 3,choice 4\n\
+             b1,3,,member,A,B,C,\n\
+             b2,1,challenged,board,B,A|C,,\n\
+             b3,2,,member,,  ,Z,A\n\
+             b4,1,,board,C,C,B,A\n\
+             b5,4,,member,A|B,,,\n\
+             b6,1,,,Z,,,\n",
+        )
+        .unwrap();
+        fs::write(dir.join("more.csv"), "choice 1,choice 2\nB,C\nC,A\nC,\n").unwrap();
+        let config_path = dir.join("config.json").display().to_string();
+        let export_path = dir.join("export.csv").display().to_string();
+        let export_config = dir.join("export_config.json").display().to_string();
+        let config = json!({
+            "outputSettings": {"contestName": "roundtrip"},
+            "cvrFileSources": [
+                {
+                    "filePath": "ballots.csv",
+                    "provider": "csv",
+                    "firstVoteRowIndex": "2",
+                    "idColumnIndex": "1",
+                    "countColumnIndex": "2",
+                    "challengedColumn": "3",
+                    "challengedLabel": "challenged",
+                    "firstVoteColumnIndex": "5",
+                    "overvoteDelimiter": "|",
+                },
+                {
+                    "filePath": "more.csv",
+                    "provider": "csv",
+                    "firstVoteRowIndex": "2",
+                    "firstVoteColumnIndex": "1",
+                },
+            ],
+            "candidates": [{"name": "A"}, {"name": "B"}, {"name": "C"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "alwaysSkipToNextRank",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "max",
+            },
+            "weights": {"column": "category", "values": {"board": 2, "member": 1}, "default": 1},
+        });
+        fs::write(&config_path, config.to_string()).unwrap();
+        let _ = fs::remove_file(&export_config);
+        let run = |config_path: &str, args: Option<Args>| -> JSValue {
+            let out_path = dir.join("summary.json").display().to_string();
+            run_election_at(
+                Some(config_path.to_string()),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                args,
+            )
+            .unwrap();
+            serde_json::from_str(&fs::read_to_string(out_path).unwrap()).unwrap()
+        };
+        let args = Args::parse_from([
+            "timrcv",
+            "--export-simple-csv",
+            &export_path,
+            "--privacy-override",
+        ]);
+        let original = run(&config_path, Some(args));
+        let exported = run(&export_config, None);
+        assert_eq!(exported["results"], original["results"]);
+        assert_eq!(exported["summary"], original["summary"]);
+        // The same ballots, with their weighted counts.
+        let read = |path: &str| -> Vec<Ballot> {
+            let config = resolve_config(&Some(path.to_string()), &None, &None).unwrap();
+            read_election_data(
+                &config,
+                &Some(path.to_string()),
+                &mut CategoryTotals::new(),
+                &mut Vec::new(),
+                None,
+                None,
+            )
+            .unwrap()
+            .0
+        };
+        assert_eq!(read(&export_config), read(&config_path));
+
+        // The ids of the sources are kept. The readers name the rows of a file without an id
+        // column by their file and their line.
+        let ids: Vec<String> = fs::read_to_string(&export_path)
+            .unwrap()
+            .lines()
+            .map(|l| l.split(',').next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                "b1",
+                "b2",
+                "b3",
+                "b4",
+                "b5",
+                "b6",
+                "more.csv-00000002",
+                "more.csv-00000003",
+                "more.csv-00000004"
+            ]
+        );
+    }
+
+    #[test]
+    fn export_simple_csv_roundtrip_fixtures() {
+        for test_name in [
+            "cdf_simple",
+            "csv_simple_1",
+            "csv_simple_2",
+            "csv_inactive_ballots",
+            "csv_simple_likert",
+            "dominion_simple",
+            "ess_simple",
+            "msforms_1",
+            "msforms_likert",
+            "msforms_likert_transpose",
+            "msforms_worksheets",
+        ] {
+            test_wrapper_local_roundtrip(test_name);
+        }
+    }
+
+    #[test]
+    fn export_simple_csv_labels() {
+        let dir = std::env::temp_dir().join("timrcv_export_simple_csv_labels");
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.json").display().to_string();
+        let export_path = dir.join("export.csv").display().to_string();
+        let export_config = dir.join("export_config.json").display().to_string();
+        let args = Args::parse_from([
+            "timrcv",
+            "--export-simple-csv",
+            &export_path,
+            "--privacy-override",
+        ]);
+        let read = |path: &str| -> Vec<Ballot> {
+            let config = resolve_config(&Some(path.to_string()), &None, &None).unwrap();
+            read_election_data(
+                &config,
+                &Some(path.to_string()),
+                &mut CategoryTotals::new(),
+                &mut Vec::new(),
+                None,
+                None,
+            )
+            .unwrap()
+            .0
+        };
+        // The ballots have overvotes, undervotes (also at the end), blanks and write-ins.
+        for labels in [Some(("OV", "UV")), None] {
+            let mut source = json!({
+                "filePath": "ballots.csv",
+                "provider": "csv",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+            });
+            let (ballots, expected) = match labels {
+                Some((overvote, undervote)) => {
+                    source["overvoteLabel"] = json!(overvote);
+                    source["undervoteLabel"] = json!(undervote);
+                    (
+                        "id1,3,A,OV,B\nid2,2,UV,B,UV\nid3,1,  ,A,Z\nid4,1,OV,,C\n",
+                        "id1,3,A,OV,B\nid2,2,UV,B,UV\nid3,1, ,A,UWI\nid4,1,OV,UV,C\n",
+                    )
+                }
+                None => {
+                    source["overvoteDelimiter"] = json!("|");
+                    (
+                        "id1,3,A,A|B,B\nid2,2,,B,\nid3,1,  ,A,Z\nid4,1,B|C,,C\n",
+                        "id1,3,A,__OVERVOTE__,B\nid2,2,__UNDERVOTE__,B,\nid3,1, ,A,UWI\nid4,1,__OVERVOTE__,__UNDERVOTE__,C\n",
+                    )
+                }
+            };
+            fs::write(dir.join("ballots.csv"), ballots).unwrap();
+            let config = json!({
+                "outputSettings": {"contestName": "labels"},
+                "cvrFileSources": [source],
+                "candidates": [{"name": "A"}, {"name": "B"}, {"name": "C"}],
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "alwaysSkipToNextRank",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "unlimited",
+                    "maxRankingsAllowed": "max",
+                },
+            });
+            fs::write(&config_path, config.to_string()).unwrap();
+            let _ = fs::remove_file(&export_config);
+            run_election_at(
+                Some(config_path.clone()),
+                None,
+                None,
+                Some("".to_string()),
+                true,
+                Some(args.clone()),
+            )
+            .unwrap();
+            assert_eq!(fs::read_to_string(&export_path).unwrap(), expected);
+            assert_eq!(read(&export_config), read(&config_path));
+        }
+
+        // The same export again replaces the configuration written by the previous export.
+        let rerun = |config_path: &str| {
+            run_election_at(
+                Some(config_path.to_string()),
+                None,
+                None,
+                Some("".to_string()),
+                true,
+                Some(args.clone()),
+            )
+        };
+        fs::remove_file(&export_path).unwrap();
+        rerun(&config_path).unwrap();
+        assert_eq!(read(&export_config), read(&config_path));
+        // The configuration of the election is never replaced, even if an export wrote it: the
+        // run stops before the export.
+        let err = rerun(&export_config).unwrap_err();
+        assert!(matches!(err, RcvError::ExportConfigExists { .. }));
+        assert_eq!(err.code(), codes::ErrorCode::OutputFailed);
+        // Nor is a configuration that a previous export did not write.
+        let manual_config = fs::read_to_string(&config_path).unwrap();
+        fs::write(&export_config, &manual_config).unwrap();
+        fs::remove_file(&export_path).unwrap();
+        let err = rerun(&config_path).unwrap_err();
+        assert!(matches!(err, RcvError::ExportConfigExists { .. }));
+        assert!(!std::path::Path::new(&export_path).exists());
+        assert_eq!(fs::read_to_string(&export_config).unwrap(), manual_config);
+
+        // Without the undervote label of the sources, the undervotes have the default label, and
+        // the ones at the end of the ballots are kept.
+        let ballots = vec![Ballot::new(
+            vec![
+                BallotChoice::Overvote,
+                BallotChoice::Undervote,
+                BallotChoice::Candidate("A".to_string()),
+                BallotChoice::Undervote,
+            ],
+            1,
+        )
+        .challenged()];
+        let labels = io_csv::SimpleCsvLabels::from_sources(&[]);
+        let source =
+            io_csv::write_simple_csv(&export_path, "export.csv", &ballots, &labels).unwrap();
+        assert_eq!(source["overvoteLabel"], json!("__OVERVOTE__"));
+        assert_eq!(source["undervoteLabel"], json!("__UNDERVOTE__"));
+        assert_eq!(source["firstVoteColumnIndex"], json!("4"));
+        assert_eq!(
+            fs::read_to_string(&export_path).unwrap(),
+            "00000001,1,challenged,__OVERVOTE__,__UNDERVOTE__,A,__UNDERVOTE__\n"
+        );
+        let mut config: RcvConfig = serde_json::from_value(json!({
+            "outputSettings": {"contestName": "labels"},
+            "cvrFileSources": [source],
+            "candidates": [{"name": "A"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "alwaysSkipToNextRank",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "unlimited",
+                "maxRankingsAllowed": "max",
+            },
+        }))
+        .unwrap();
+        config.cvr_file_sources[0].file_path = export_path.clone();
+        let (read_back, _) = read_election_data(
+            &config,
+            &None,
+            &mut CategoryTotals::new(),
+            &mut Vec::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(read_back[0].candidates, ballots[0].candidates);
+    }
+
+    #[test]
+    fn export_preflib() {
+        // The example of the manual.
+        let dir = std::env::temp_dir().join("timrcv_export_preflib");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("ballots.csv"),
+            "id1,3,A,B,C\nid2,2,A,B,\nid3,1,B,A|C,\nid4,1,,B,A\nid5,1,C,C,A\nid6,1,A,B,C\nid7,1,,,\n",
+        )
+        .unwrap();
+        let config = json!({
+            "outputSettings": {"contestName": "Example"},
+            "cvrFileSources": [{
+                "filePath": "ballots.csv",
+                "provider": "csv",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+                "overvoteDelimiter": "|",
+            }],
+            "candidates": [{"name": "A"}, {"name": "B"}, {"name": "C"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json").display().to_string();
+        fs::write(&config_path, config.to_string()).unwrap();
+        let toi_path = dir.join("example.toi").display().to_string();
+        let args = Args::parse_from(["timrcv", "--export-preflib", &toi_path, "--privacy-k", "1"]);
+        run_election_at(
+            Some(config_path.clone()),
+            None,
+            None,
+            Some("".to_string()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&toi_path).unwrap(),
+            "# FILE NAME: example.toi
+# TITLE: Example
+# DATA TYPE: toi
+# MODIFICATION TYPE: original
+# NUMBER ALTERNATIVES: 3
+# NUMBER VOTERS: 9
+# NUMBER UNIQUE ORDERS: 5
+# ALTERNATIVE NAME 1: A
+# ALTERNATIVE NAME 2: B
+# ALTERNATIVE NAME 3: C
+4: 1,2,3
+2: 1,2
+1: 2,1
+1: 2,{1,3}
+1: 3,1
+"
+        );
+
+        // A snapshot does not have the candidates of the overvotes.
+        let args = Args::parse_from([
+            "timrcv",
+            "--export-preflib",
+            &toi_path,
+            "--load-validated",
+            "snapshot.bin",
+        ]);
+        let err = run_election_at(
+            Some(config_path),
+            None,
+            None,
+            Some("".to_string()),
+            true,
+            Some(args),
+        )
+        .unwrap_err();
+        assert!(matches!(err, RcvError::PreflibFromSnapshot {}), "{:?}", err);
+    }
+
+    #[test]
+    fn export_preflib_overflow() {
+        let path = std::env::temp_dir()
+            .join("timrcv_export_preflib_overflow.toi")
+            .display()
+            .to_string();
+        let candidates: Vec<RcvCandidate> = ["A", "B"]
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: None,
+                inferred: false,
+            })
+            .collect();
+        let order = |names: &[&str], count: u64| -> preflib::BallotOrder {
+            (names.iter().map(|n| vec![n.to_string()]).collect(), count)
+        };
+        // The same order twice.
+        let orders = vec![order(&["A", "B"], u64::MAX), order(&["A", "B"], 1)];
+        let err = preflib::write_toi(&path, "overflow", &candidates, &orders).unwrap_err();
+        assert!(matches!(err, RcvError::CountOverflow { .. }), "{:?}", err);
+        // Two different orders.
+        let orders = vec![order(&["A", "B"], u64::MAX), order(&["B"], 1)];
+        let err = preflib::write_toi(&path, "overflow", &candidates, &orders).unwrap_err();
+        assert!(matches!(err, RcvError::CountOverflow { .. }), "{:?}", err);
+        assert_eq!(err.code(), codes::ErrorCode::MalformedBallots);
+    }
+
+    #[test]
+    fn privacy_guard() {
+        // The ballot of id5 is the only one with its ranking pattern.
+        let dir = std::env::temp_dir().join("timrcv_privacy_guard");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("ballots.csv"),
+            "id1,5,A,B\nid2,3,B,A\nid3,2,B,A\nid4,6,C,A\nid5,1,C,B\n",
+        )
+        .unwrap();
+        let config = json!({
+            "outputSettings": {"contestName": "Privacy"},
+            "cvrFileSources": [{
+                "filePath": "ballots.csv",
+                "provider": "csv",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+            }],
+            "candidates": [{"name": "A"}, {"name": "B"}, {"name": "C"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json").display().to_string();
+        fs::write(&config_path, config.to_string()).unwrap();
+        let export_path = dir.join("export.csv").display().to_string();
+        let _ = fs::remove_file(&export_path);
+        let run = |flags: &[&str]| {
+            let _ = fs::remove_file(dir.join("export_config.json"));
+            let mut argv = vec!["timrcv", "--export-simple-csv", &export_path];
+            argv.extend(flags);
+            run_election_at(
+                Some(config_path.clone()),
+                None,
+                None,
+                Some("".to_string()),
+                true,
+                Some(Args::parse_from(argv)),
+            )
+        };
+        // The rows of the export, without their ids.
+        let rows = || -> Vec<String> {
+            fs::read_to_string(&export_path)
+                .unwrap()
+                .lines()
+                .map(|l| l.split_once(',').unwrap().1.to_string())
+                .collect()
+        };
+
+        // The export is refused, and nothing is written.
+        let err = run(&[]).unwrap_err();
+        assert_eq!(err.code(), codes::ErrorCode::PrivacyThreshold);
+        assert_eq!(
+            err.to_string(),
+            "--export-simple-csv would have 1 ranking patterns cast by fewer than 5 votes (the smallest by 1), \
+            which may identify the voters: pass --privacy-suppress-rare to leave them out, or --privacy-override \
+            to write them anyway"
+        );
+        assert!(fs::metadata(&export_path).is_err());
+        // B > A is cast by 5 votes over two rows: only C > B is rare. With a threshold of 1, each
+        // ballot is written.
+        run(&["--privacy-k", "1"]).unwrap();
+        assert_eq!(rows().len(), 5);
+
+        // The rare pattern is left out.
+        run(&["--privacy-suppress-rare"]).unwrap();
+        assert_eq!(rows(), vec!["5,A,B", "3,B,A", "2,B,A", "6,C,A"]);
+        let export_warning = |flags: &[&str]| {
+            let _ = fs::remove_file(dir.join("export_config.json"));
+            let summary_path = dir.join("summary.json").display().to_string();
+            let mut argv = vec![
+                "timrcv",
+                "--export-simple-csv",
+                &export_path,
+                "--summary-schema",
+                "v2",
+            ];
+            argv.extend(flags);
+            run_election_at(
+                Some(config_path.clone()),
+                None,
+                None,
+                Some(summary_path.clone()),
+                true,
+                Some(Args::parse_from(argv)),
+            )
+            .unwrap();
+            let summary: JSValue =
+                serde_json::from_str(&fs::read_to_string(&summary_path).unwrap()).unwrap();
+            summary["warnings"][0].clone()
+        };
+        let warning = export_warning(&["--privacy-suppress-rare"]);
+        assert_eq!(warning["code"], json!("W021"));
+        assert_eq!(
+            warning["message"],
+            json!("--export-simple-csv: 1 ranking patterns cast by fewer than 5 votes (1 votes) are left out (--privacy-suppress-rare)")
+        );
+
+        // The rare pattern is written anyway.
+        let warning = export_warning(&["--privacy-override"]);
+        assert_eq!(warning["name"], json!("rare-patterns"));
+        assert_eq!(rows().len(), 5);
+    }
+
+    #[test]
+    fn verify_output_local_fixtures() {
+        let mut names: Vec<String> = fs::read_dir("./tests")
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|n| {
+                std::path::Path::new(&format!("./tests/{}/{}_expected_summary.json", n, n)).exists()
+            })
+            .collect();
+        names.sort();
+        assert!(names.len() > 10, "{:?}", names);
+        for name in names.iter() {
+            if let Err(e) = test_wrapper_local_verified(name) {
+                panic!("{}: {:?}", name, e);
+            }
+        }
+    }
+
+    #[test]
+    fn reconcile_summary_mismatches() {
+        let summary: JSValue = serde_json::from_str(
+            &fs::read_to_string("./tests/csv_excel_export/csv_excel_export_expected_summary.json")
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            reconcile::summary_mismatches(&summary),
+            Vec::<String>::new()
+        );
+
+        // A vote lost in the transfers of round 2.
+        let mut lost = summary.clone();
+        lost["results"][1]["tallyResults"][0]["transfers"]["exhausted"] = json!("1");
+        assert_eq!(
+            reconcile::summary_mismatches(&lost),
+            vec![
+                "round 2: C is eliminated with 5 votes but transfers 4".to_string(),
+                "round 3: 13 votes and 1 inactive ballots instead of 15 ballots".to_string(),
+            ]
+        );
+        // A winner below the threshold.
+        let mut below = summary;
+        below["config"]["threshold"] = json!("9");
+        below["results"][2]["tally"] = json!({"A": "5", "B": "8", "C": "0"});
+        assert_eq!(
+            reconcile::summary_mismatches(&below),
+            vec!["round 3: B is elected with 8 votes, below the threshold 9".to_string()]
+        );
+    }
+
+    #[test]
+    fn verify_output_random_elections() {
+        // Random elections, drawn from the digests of the seed: the totals of the summary must
+        // reconcile whatever the ballots and the rules.
+        let out_dir = std::env::temp_dir().join("timrcv_random_elections");
+        fs::create_dir_all(&out_dir).unwrap();
+        let names = ["A", "B", "C", "D", "E", "F"];
+        for seed in 0..40u64 {
+            let draw = |idx: u64, modulo: u64| -> u64 {
+                let digest = sha256::digest(format!("{:020}{:020}", seed, idx));
+                u64::from_str_radix(&digest[..15], 16).unwrap() % modulo
+            };
+            let num_candidates = 2 + draw(0, 5) as usize;
+            let mut rows: Vec<String> = Vec::new();
+            for b in 0..(5 + draw(1, 60)) {
+                let num_ranks = draw(1000 * (b + 1), 5);
+                // The rows have 4 ranks, with the empty cells of the shorter ballots at the end.
+                let ranks: Vec<&str> = (0..4)
+                    .map(
+                        |r| match draw(1000 * (b + 1) + r + 1, num_candidates as u64 + 2) {
+                            _ if r >= num_ranks => "",
+                            k if (k as usize) < num_candidates => names[k as usize],
+                            k if k as usize == num_candidates => "",
+                            _ => "OV",
+                        },
+                    )
+                    .collect();
+                rows.push(format!(
+                    "{},{},{}",
+                    b + 1,
+                    1 + draw(2000 * (b + 1), 3),
+                    ranks.join(",")
+                ));
+            }
+            let overvote_rule = ["exhaustImmediately", "alwaysSkipToNextRank"][draw(2, 2) as usize];
+            let max_skipped = ["0", "1", "unlimited"][draw(3, 3) as usize];
+            let csv_path = out_dir.join(format!("ballots_{}.csv", seed));
+            fs::write(&csv_path, rows.join("\n")).unwrap();
+            let config = json!({
+                "outputSettings": {
+                    "contestName": format!("Random {}", seed),
+                    "rctabCompatibility": Some("1.3").filter(|_| seed % 2 == 0),
+                },
+                "cvrFileSources": [{
+                    "filePath": csv_path.display().to_string(),
+                    "provider": "csv",
+                    "overvoteLabel": "OV",
+                    "idColumnIndex": "1",
+                    "countColumnIndex": "2",
+                    "firstVoteColumnIndex": "3",
+                }],
+                "candidates": names[..num_candidates]
+                    .iter()
+                    .map(|n| json!({ "name": n }))
+                    .collect::<Vec<_>>(),
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxRankingsAllowed": "max",
+                    "overvoteRule": overvote_rule,
+                    "maxSkippedRanksAllowed": max_skipped,
+                    "exhaustOnDuplicateCandidate": draw(4, 2) == 0,
+                    "batchElimination": draw(5, 2) == 0,
+                }
+            });
+            let config_path = out_dir.join(format!("config_{}.json", seed));
+            fs::write(&config_path, config.to_string()).unwrap();
+            let res = run_election_at(
+                Some(config_path.display().to_string()),
+                None,
+                None,
+                Some("".to_string()),
+                true,
+                Some(Args::parse_from(["timrcv", "--verify-output"])),
+            );
+            if let Err(e) = res {
+                panic!("seed {}: {:?}\n{}\n{}", seed, e, config, rows.join("\n"));
+            }
+        }
+    }
+
+    #[test]
+    fn shuffled_ballots_same_summary() {
+        let ballots: Vec<ParsedBallot> = (0..10)
+            .map(|i| ParsedBallot {
+                id: Some(i.to_string()),
+                count: None,
+                choices: vec![],
+                challenged: false,
+                category: None,
+                respondent: None,
+                precinct: None,
+                style: None,
+            })
+            .collect();
+        let ids = |seed: u64| -> Vec<String> {
+            shuffle_ballots(ballots.clone(), seed)
+                .into_iter()
+                .map(|b| b.id.unwrap())
+                .collect()
+        };
+        // The same seed gives the same permutation, and another seed another one.
+        assert_eq!(ids(1), ids(1));
+        assert_ne!(ids(1), ids(2));
+        let mut sorted = ids(1);
+        sorted.sort_by_key(|id| id.parse::<u32>().unwrap());
+        assert_eq!(sorted, (0..10).map(|i| i.to_string()).collect::<Vec<_>>());
+
+        for test_name in [
+            "cdf_simple",
+            "csv_challenged",
+            "csv_continuation_rows",
+            "csv_excluded",
+            "csv_inactive_ballots",
+            "csv_likert_header_pattern",
+            "csv_simple_1",
+            "csv_simple_2",
+            "csv_simple_likert",
+            "csv_time_window",
+            "csv_uwi_round1",
+            "csv_weights",
+            "dominion_simple",
+            "ess_simple",
+            "msforms_1",
+            "msforms_extra_column",
+            "msforms_likert",
+            "msforms_likert_transpose",
+            "msforms_worksheets",
+        ] {
+            test_wrapper_local_shuffled(test_name);
+        }
     }
-}
 
-pub fn test_wrapper(test_name: &str) {
-    run_election_test(
-        test_name,
-        format!("{}_config.json", test_name).as_str(),
-        format!("{}_expected_summary.json", test_name).as_str(),
-        false,
-    )
-}
+    fn read_likert_candidates(candidates: Option<&Vec<RcvCandidate>>) -> RcvError {
+        let cfs: FileSource = serde_json::from_value(json!({
+            "provider": "csv_likert",
+            "filePath": "example.csv",
+        }))
+        .unwrap();
+        let rules = RcvConfig::config_from_args(&Some("example.csv".to_string()))
+            .unwrap()
+            .rules;
+        read_ranking_data(
+            "./tests/csv_simple_likert".to_string(),
+            &cfs,
+            candidates,
+            &rules,
+            None,
+            &mut CategoryTotals::new(),
+            None,
+        )
+        .unwrap_err()
+    }
 
-pub fn test_wrapper_local(test_name: &str) {
-    run_election_test(
-        test_name,
-        format!("{}_config.json", test_name).as_str(),
-        format!("{}_expected_summary.json", test_name).as_str(),
-        true,
-    )
-}
+    #[test]
+    fn likert_missing_candidates() {
+        let err = read_likert_candidates(None);
+        assert!(matches!(err, RcvError::MissingMandatoryCandidates { .. }));
+        assert_eq!(
+            err.to_string(),
+            "provider 'csv_likert' requires the candidates list because column headers are matched against candidate names"
+        );
+    }
 
-#[cfg(test)]
-mod tests {
+    #[test]
+    fn likert_empty_candidates() {
+        let err = read_likert_candidates(Some(&vec![]));
+        assert!(matches!(err, RcvError::EmptyCandidates { .. }));
+    }
 
-    use super::test_wrapper;
-    use super::test_wrapper_local;
+    fn rules_with_seed(seed: JSValue) -> RcvRules {
+        let rules = RcvConfig::config_from_args(&Some("example.csv".to_string()))
+            .unwrap()
+            .rules;
+        let mut rules_js = serde_json::to_value(rules).unwrap();
+        rules_js["tiebreakMode"] = json!("random");
+        rules_js["randomSeed"] = seed;
+        serde_json::from_value(rules_js).unwrap()
+    }
 
-    // #[test]
-    // fn _2013_minneapolis_mayor() {
-    //     test_wrapper("2013_minneapolis_mayor");
-    // }
+    #[test]
+    fn random_seed_parsing() {
+        assert_eq!(rules_with_seed(json!(42)).random_seed().unwrap(), Some(42));
+        assert_eq!(
+            rules_with_seed(json!("42")).random_seed().unwrap(),
+            Some(42)
+        );
+        assert_eq!(rules_with_seed(JSValue::Null).random_seed().unwrap(), None);
+        // Java long values used by the reference implementation.
+        assert_eq!(
+            rules_with_seed(json!("9223372036854775807"))
+                .random_seed()
+                .unwrap(),
+            Some(9223372036854775807)
+        );
+        assert_eq!(
+            rules_with_seed(json!(u64::MAX)).random_seed().unwrap(),
+            Some(u64::MAX)
+        );
+        let rules = validate_rules(&rules_with_seed(json!(3344556677889900u64))).unwrap();
+        assert_eq!(rules.tiebreak_mode, TieBreakMode::Random(3344556677889900));
+    }
 
-    // Takes about 100s to complete on github actions, disabled for the time being.
     #[test]
-    #[ignore = "SLOW"]
-    fn _2013_minneapolis_mayor_scale() {
-        test_wrapper("2013_minneapolis_mayor_scale");
+    fn csv_random_seed() {
+        // A configuration saved by the reference implementation, with a seed above 2^53 in a
+        // string: the tiebreak between C and D depends on all the bits of the seed.
+        test_wrapper_local("csv_random_seed");
+        let config_path = "./tests/csv_random_seed/csv_random_seed_config.json";
+        let config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        let rules = validate_rules(&config.rules).unwrap();
+        assert_eq!(rules.tiebreak_mode, TieBreakMode::Random(9007199254740993));
+
+        // The seed that a conversion to a float would give eliminates C instead.
+        let mut config: JSValue =
+            serde_json::from_str(&fs::read_to_string(config_path).unwrap()).unwrap();
+        config["rules"]["randomSeed"] = json!("9007199254740992");
+        let csv_path = fs::canonicalize("./tests/csv_random_seed/example.csv").unwrap();
+        config["cvrFileSources"][0]["filePath"] = json!(csv_path.display().to_string());
+        let summary = run_json_config(&test_temp_dir("timrcv_random_seed"), &config, &[]).unwrap();
+        assert_eq!(
+            summary["results"][0]["tallyResults"][0]["eliminated"],
+            json!("C")
+        );
     }
 
     #[test]
-    fn _2015_portland_mayor() {
-        test_wrapper("2015_portland_mayor");
+    fn random_seed_invalid() {
+        for seed in [json!(-5), json!("-5"), json!("abc"), json!(1.5), json!([1])] {
+            let err = rules_with_seed(seed).random_seed().unwrap_err();
+            assert!(matches!(err, RcvError::InvalidRandomSeed { .. }));
+            assert!(err.to_string().starts_with("randomSeed must be"));
+        }
+        let err = validate_rules(&rules_with_seed(JSValue::Null)).unwrap_err();
+        assert!(matches!(err, RcvError::MissingRandomSeed {}));
     }
 
     #[test]
-    #[ignore = "TODO implement clearBallot provider"]
-    fn clear_ballot_kansas_primary() {
-        // TODO P1
-        test_wrapper("clear_ballot_kansas_primary");
+    fn summary_status_winner_and_no_winner() {
+        let config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        let res = run_election1(&[vec!["A", "B"], vec!["A"]], &VoteRules::default()).unwrap();
+        let js = build_summary_js(&config, &res).unwrap();
+        assert_eq!(js["status"], json!("winnerDeclared"));
+        assert_eq!(js["reason"], json!("winner declared: A"));
+
+        let no_winner = VotingResult::default();
+        let js = build_summary_js(&config, &no_winner).unwrap();
+        assert_eq!(js["status"], json!("noWinner"));
+        assert_eq!(SummaryStatus::NoWinner.exit_code(), 2);
+
+        let mut nota_rules = VoteRules::default();
+        nota_rules.nota_candidate = Some("N".to_string());
+        let res = run_election1(&[vec!["N"], vec!["N"], vec!["A"]], &nota_rules).unwrap();
+        let js = build_summary_js(&config, &res).unwrap();
+        assert_eq!(js["status"], json!("notaPrevailed"));
+        assert_eq!(SummaryStatus::NotaPrevailed.exit_code(), 2);
     }
 
     #[test]
-    #[ignore = "TODO GH-17"]
-    fn continue_tabulation_test() {
-        test_wrapper("continue_tabulation_test");
+    fn summary_status_error() {
+        let dir = std::env::temp_dir().join("timrcv_status_error");
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.json").display().to_string();
+        let out_path = dir.join("summary.json").display().to_string();
+        // The only ballot is blank: there is nothing to tabulate.
+        fs::write(dir.join("blank.csv"), ",\n").unwrap();
+        let config = json!({
+            "outputSettings": {"contestName": "status error"},
+            "cvrFileSources": [{"filePath": "blank.csv", "provider": "csv", "sanityChecks": false}],
+            "candidates": [{"name": "A"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        fs::write(&config_path, config.to_string()).unwrap();
+        let err = run_election_at(
+            Some(config_path.clone()),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, RcvError::RvVoting { .. }));
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(summary["status"], json!("error"));
+        assert_eq!(
+            summary["reason"],
+            json!("there is no valid ballot to tabulate")
+        );
+        assert_eq!(summary["results"], json!([]));
+
+        // The errors before the tabulation also write a summary.
+        let _ = fs::remove_file(&out_path);
+        let mut config = config;
+        config["cvrFileSources"][0]["filePath"] = json!("missing.csv");
+        fs::write(&config_path, config.to_string()).unwrap();
+        let err = run_election_at(
+            Some(config_path),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), codes::ErrorCode::UnreadableInput);
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(summary["status"], json!("error"));
+        assert!(!summary["reason"].as_str().unwrap().is_empty());
+        assert_eq!(summary["results"], json!([]));
     }
 
     #[test]
-    #[ignore = "GH-17"]
-    fn continue_until_two_with_batch_elimination_test() {
-        test_wrapper("continue_until_two_with_batch_elimination_test");
+    fn informational_rounds_only_in_v2() {
+        let mut rules = VoteRules::default();
+        rules.continue_to_completion = true;
+        let res = run_election1(&[vec!["A", "B"], vec!["A", "B"], vec!["B", "A"]], &rules).unwrap();
+        let v1 = result_stats_to_json(&res, SummarySchema::V1);
+        let v2 = result_stats_to_json(&res, SummarySchema::V2);
+        assert_eq!(v1.len(), 1);
+        assert_eq!(v2.len(), 2);
+        assert_eq!(v2[1]["informational"], json!(true));
+        assert_eq!(v2[1]["notes"], json!([{"type": "informational"}]));
+        assert!(v1[0].get("notes").is_none());
     }
 
     #[test]
-    fn dominion_alaska() {
-        test_wrapper("dominion_alaska");
+    fn round_notes_in_v2() {
+        let ballots = [vec!["A"], vec!["B"], vec!["C", "A"], vec!["C", "A"]];
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        let v1 = result_stats_to_json(&res, SummarySchema::V1);
+        let v2 = result_stats_to_json(&res, SummarySchema::V2);
+        assert!(v1[0].get("notes").is_none());
+        assert_eq!(
+            v2[0]["notes"],
+            json!([{"candidates": ["B", "A"], "eliminated": "B", "type": "tiebreak"}])
+        );
+        assert!(v2[1].get("notes").is_none());
+    }
+
+    // Checks that the rounds of the summary are numbered 1, 2, 3...
+    fn assert_consecutive_rounds(res: &VotingResult) {
+        let rounds: Vec<JSValue> = result_stats_to_json(res, SummarySchema::V2)
+            .iter()
+            .map(|js| js["round"].clone())
+            .collect();
+        let expected: Vec<JSValue> = (1..=rounds.len()).map(|idx| json!(idx)).collect();
+        assert_eq!(rounds, expected);
     }
 
     #[test]
-    fn dominion_kansas() {
-        test_wrapper("dominion_kansas");
+    fn rounds_numbered_from_one() {
+        let ballots = [
+            vec!["A", "B"],
+            vec!["A", "C"],
+            vec!["B", "C"],
+            vec!["B", "A"],
+            vec!["C", "B"],
+        ];
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        assert_eq!(res.round_stats.len(), 2);
+        assert_consecutive_rounds(&res);
+
+        // The round that removes the undeclared write-ins is round 1.
+        let mut builder = ranked_voting::Builder::new(&VoteRules::default())
+            .unwrap()
+            .candidates(&["A".to_string(), "B".to_string(), "C".to_string()])
+            .unwrap();
+        for ballot in ballots.iter().chain([vec!["X", "A"]].iter()) {
+            let choices: Vec<Vec<String>> = ballot.iter().map(|c| vec![c.to_string()]).collect();
+            builder.add_vote(&choices, 1).unwrap();
+        }
+        let res = ranked_voting::run_election(&builder).unwrap();
+        assert_eq!(res.round_stats[0].tally_result_eliminated.len(), 1);
+        assert!(res.round_stats[0].tally_results_elected.is_empty());
+        assert_consecutive_rounds(&res);
     }
 
     #[test]
-    #[ignore = "TODO GH-10"]
-    fn dominion_multi_file() {
-        test_wrapper("dominion_multi_file");
+    fn candidates_only_in_v2() {
+        let mut config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        let mut builder = ranked_voting::Builder::new(&VoteRules::default()).unwrap();
+        builder.add_vote_simple(&["B".to_string()]).unwrap();
+        builder.add_vote_simple(&["A".to_string()]).unwrap();
+        builder.add_vote_simple(&["B".to_string()]).unwrap();
+        let res = ranked_voting::run_election(&builder).unwrap();
+        let v1 = build_summary_js(&config, &res).unwrap();
+        assert!(v1.get("candidates").is_none());
+        config.output_settings.summary_schema = Some("v2".to_string());
+        let v2 = build_summary_js(&config, &res).unwrap();
+        assert_eq!(
+            v2["candidates"],
+            json!([
+                {"name": "A", "code": null, "excluded": false, "inferred": true},
+                {"name": "B", "code": null, "excluded": false, "inferred": true},
+            ])
+        );
+        assert!(v1.get("firstChoicePercent").is_none());
+        assert_eq!(v2["firstChoicePercent"], json!({"A": "33.3", "B": "66.7"}));
+        // B is elected in the first round: nobody is eliminated.
+        assert_eq!(
+            v2["eliminationOrder"],
+            json!([{"name": "B", "round": 1, "votes": "2", "sharePercent": "66.7", "elected": true}])
+        );
+        assert_eq!(v2["winnerRankedOn"], json!({"B": "2"}));
+        assert!(v1.get("firstMajorityRound").is_none());
+        assert_eq!(v2["firstMajorityRound"], json!({"B": 1}));
+        assert!(v1.get("competitiveness").is_none());
+        assert_eq!(
+            v2["competitiveness"],
+            json!({
+                "comeFromBehind": false,
+                "effectiveCandidates": 1.8,
+                "exhaustedPercent": 0.0,
+                "finalRoundMarginPercent": 33.33,
+                "rounds": 1,
+            })
+        );
     }
 
     #[test]
-    fn dominion_no_precinct_data() {
-        test_wrapper("dominion_no_precinct_data");
+    fn overvote_scope_rule() {
+        let mut config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        config
+            .rules
+            .set_rule("overvoteScope", "wholeBallot")
+            .unwrap();
+        // The default overvote rule skips the overvotes.
+        let err = validate_rules(&config.rules).unwrap_err();
+        assert!(err.to_string().contains("exhaustImmediately"), "{}", err);
+        config
+            .rules
+            .set_rule("overvoteRule", "exhaustImmediately")
+            .unwrap();
+        let rules = validate_rules(&config.rules).unwrap();
+        assert_eq!(rules.overvote_scope, OverVoteScope::WholeBallot);
+        config.rules.set_rule("overvoteScope", "perRank").unwrap();
+        assert!(validate_rules(&config.rules).is_err());
     }
 
     #[test]
-    fn dominion_wyoming() {
-        test_wrapper("dominion_wyoming");
+    fn number_of_winners_rule() {
+        let mut config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        config.rules.set_rule("numberOfWinners", "1").unwrap();
+        assert!(validate_rules(&config.rules).is_ok());
+        // Any other number is refused, whatever the number of candidates.
+        config.rules.set_rule("numberOfWinners", "5").unwrap();
+        let err = validate_rules(&config.rules).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "numberOfWinners 5: only the elections with a single winner are implemented"
+        );
+        config.rules.set_rule("numberOfWinners", "all").unwrap();
+        assert!(validate_rules(&config.rules).is_err());
     }
 
     #[test]
-    fn duplicate_test() {
-        test_wrapper("duplicate_test");
+    fn rank_origin_in_v2() {
+        let mut config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        config.rules.set_rule("trackRankOrigin", "true").unwrap();
+        let rules = validate_rules(&config.rules).unwrap();
+        let res = run_election1(
+            &[vec!["A"], vec!["B", "A"], vec!["A"], vec!["C", "B"]],
+            &rules,
+        )
+        .unwrap();
+        assert!(build_summary_js(&config, &res)
+            .unwrap()
+            .get("rankOrigin")
+            .is_none());
+        config.output_settings.summary_schema = Some("v2".to_string());
+        let js = build_summary_js(&config, &res).unwrap();
+        let origins = js["rankOrigin"].as_array().unwrap();
+        assert_eq!(
+            origins.last().unwrap(),
+            &json!({"candidate": "A", "round": 3, "votesByRank": [
+                {"rank": 1, "votes": "2"},
+                {"rank": 2, "votes": "1"},
+            ]})
+        );
     }
 
     #[test]
-    #[ignore = "TODO P3 stopCountingAndAsk"]
-    fn excluded_test() {
-        test_wrapper("excluded_test");
+    fn candidate_order_config_and_inferred() {
+        // A and B are tied, and the candidate order breaks the tie.
+        let dir = std::env::temp_dir().join("timrcv_candidate_order");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ballots.csv"), "B\nA\n").unwrap();
+        let out_path = dir.join("summary.json").display().to_string();
+        let run = |config_path: Option<String>, in_path: Option<String>| -> JSValue {
+            let args = Args::parse_from(["timrcv", "--summary-schema", "v2"]);
+            run_election_at(
+                config_path,
+                None,
+                in_path,
+                Some(out_path.clone()),
+                true,
+                Some(args),
+            )
+            .unwrap();
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap()
+        };
+        let names = |js: &JSValue| -> Vec<String> {
+            js["candidates"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|c| c["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let with_config = |cands: &[&str]| -> JSValue {
+            let config = json!({
+                "outputSettings": {"contestName": "order"},
+                "cvrFileSources": [{"filePath": "ballots.csv", "provider": "csv"}],
+                "candidates": cands.iter().map(|c| json!({"name": c})).collect::<Vec<_>>(),
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "exhaustImmediately",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "1",
+                    "maxRankingsAllowed": "8",
+                },
+            });
+            let path = dir.join("config.json");
+            fs::write(&path, config.to_string()).unwrap();
+            run(Some(path.display().to_string()), None)
+        };
+
+        // Without a configuration, the candidates are sorted by name.
+        let inferred = run(None, Some(dir.join("ballots.csv").display().to_string()));
+        assert_eq!(names(&inferred), vec!["A", "B"]);
+        assert_eq!(inferred["reason"], json!("winner declared: A"));
+
+        let declared = with_config(&["A", "B"]);
+        assert_eq!(names(&declared), names(&inferred));
+        assert_eq!(declared["results"], inferred["results"]);
+
+        let declared = with_config(&["B", "A"]);
+        assert_eq!(names(&declared), vec!["B", "A"]);
+        assert_eq!(declared["reason"], json!("winner declared: B"));
     }
 
     #[test]
-    #[ignore = "TODO P2 exhaustIfMultipleContinuing"]
-    fn exhaust_if_multiple_continuing() {
-        test_wrapper("exhaust_if_multiple_continuing");
+    fn candidates_inferred_flag() {
+        // The candidates of a configuration are declared, the ones of an input without a
+        // configuration are found in the ballots.
+        let dir = std::env::temp_dir().join("timrcv_candidates_inferred");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("declared.csv"), "A\nA\n").unwrap();
+        fs::write(dir.join("inferred.csv"), "B\nB\n").unwrap();
+        let out_path = dir.join("summary.json").display().to_string();
+        let run = |config_path: Option<String>, in_path: Option<String>| -> JSValue {
+            let args = Args::parse_from(["timrcv", "--summary-schema", "v2"]);
+            run_election_at(
+                config_path,
+                None,
+                in_path,
+                Some(out_path.clone()),
+                true,
+                Some(args),
+            )
+            .unwrap();
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap()
+        };
+        let config = json!({
+            "outputSettings": {"contestName": "inferred"},
+            "cvrFileSources": [{"filePath": "declared.csv", "provider": "csv"}],
+            "candidates": [{"name": "A"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json");
+        fs::write(&config_path, config.to_string()).unwrap();
+
+        let declared = run(Some(config_path.display().to_string()), None);
+        assert_eq!(
+            declared["candidates"],
+            json!([{"name": "A", "code": null, "excluded": false, "inferred": false}])
+        );
+        let inferred = run(None, Some(dir.join("inferred.csv").display().to_string()));
+        assert_eq!(
+            inferred["candidates"],
+            json!([{"name": "B", "code": null, "excluded": false, "inferred": true}])
+        );
     }
 
     #[test]
-    #[ignore = "TODO implement hart input"]
-    fn hart_cedar_park_school_board() {
-        test_wrapper("hart_cedar_park_school_board");
+    fn rank_statistics_only_in_v2() {
+        let mut config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        let res = run_election1(&[vec!["A", "B"], vec!["A"]], &VoteRules::default()).unwrap();
+        let v1 = build_summary_js(&config, &res).unwrap();
+        assert!(v1.get("rankStatistics").is_none());
+        config.output_settings.summary_schema = Some("v2".to_string());
+        let v2 = build_summary_js(&config, &res).unwrap();
+        assert_eq!(
+            v2["rankStatistics"],
+            json!({
+                "positions": [
+                    {"position": 1, "lastMark": "1", "skipThenReturn": "0"},
+                    {"position": 2, "lastMark": "1", "skipThenReturn": "0"},
+                ],
+                "totalVotes": "2",
+                "skipThenReturnVotes": "0",
+                "skipThenReturnPercent": 0.0,
+            })
+        );
+    }
+
+    fn csv_source(jobs: Option<usize>, extra: JSValue) -> FileSource {
+        let mut js = json!({"provider": "csv", "filePath": "", "jobs": jobs});
+        js.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(js).unwrap()
+    }
+
+    // A file in the simple format, with a header and some blank choices.
+    fn write_generated_csv(path: &std::path::Path, num_rows: usize) {
+        let names = ["A", "B", "C", "D"];
+        let mut contents = "id,count,choice 1,choice 2,choice 3\n".to_string();
+        for i in 0..num_rows {
+            contents.push_str(&format!(
+                "id{},{},{},{},{}\n",
+                i,
+                i % 3 + 1,
+                names[i % 4],
+                if i % 7 == 0 { "" } else { names[(i + 1) % 4] },
+                names[(i + 2) % 4]
+            ));
+        }
+        fs::write(path, contents).unwrap();
     }
 
     #[test]
-    #[ignore = "TODO implement hart input"]
-    fn hart_travis_county_officers() {
-        test_wrapper("hart_travis_county_officers");
+    fn csv_parallel_matches_sequential() {
+        let dir = std::env::temp_dir().join("timrcv_csv_parallel");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ballots.csv");
+        write_generated_csv(&path, 20000);
+        let path = path.display().to_string();
+        for extra in [
+            json!({"firstVoteRowIndex": "2", "idColumnIndex": "1", "countColumnIndex": "2", "firstVoteColumnIndex": "3"}),
+            json!({"firstVoteRowIndex": "2", "firstVoteColumnIndex": "3"}),
+        ] {
+            let sequential =
+                io_csv::read_csv_ranking(path.clone(), &csv_source(None, extra.clone())).unwrap();
+            assert_eq!(sequential.len(), 20000);
+            for jobs in [2, 4, 7] {
+                let parallel =
+                    io_csv::read_csv_ranking(path.clone(), &csv_source(Some(jobs), extra.clone()))
+                        .unwrap();
+                assert_eq!(parallel, sequential);
+            }
+        }
     }
 
     #[test]
-    #[ignore = "Alreday caught by the parser"]
-    fn invalid_params_test() {
-        test_wrapper("invalid_params_test");
+    fn csv_parallel_falls_back_with_quotes() {
+        let dir = std::env::temp_dir().join("timrcv_csv_parallel_quotes");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ballots.csv").display().to_string();
+        let mut contents = String::new();
+        for i in 0..200 {
+            contents.push_str(&format!("\"line\n{}\",A,B\n", i));
+        }
+        fs::write(&path, contents).unwrap();
+        let extra = json!({"idColumnIndex": "1", "firstVoteColumnIndex": "2"});
+        let sequential =
+            io_csv::read_csv_ranking(path.clone(), &csv_source(None, extra.clone())).unwrap();
+        let parallel = io_csv::read_csv_ranking(path, &csv_source(Some(4), extra)).unwrap();
+        assert_eq!(sequential.len(), 200);
+        assert_eq!(parallel, sequential);
     }
 
     #[test]
-    #[ignore = "Alreday caught by the parser"]
-    fn invalid_sources_test() {
-        test_wrapper("invalid_sources_test");
+    fn csv_parallel_errors() {
+        let dir = test_temp_dir("timrcv_csv_parallel_errors");
+        let path = dir.join("ballots.csv");
+        let extra = json!({"firstVoteRowIndex": "2", "idColumnIndex": "1", "countColumnIndex": "2", "firstVoteColumnIndex": "3"});
+        let read = |jobs: Option<usize>| {
+            io_csv::read_csv_ranking(path.display().to_string(), &csv_source(jobs, extra.clone()))
+                .unwrap_err()
+        };
+
+        // An invalid count in the last range has the line number of the sequential reader.
+        write_generated_csv(&path, 20000);
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, contents.replace("\nid18000,1,", "\nid18000,one,")).unwrap();
+        let sequential = read(None);
+        assert!(sequential.to_string().contains("18002"), "{}", sequential);
+        for jobs in [2, 4, 7] {
+            assert_eq!(read(Some(jobs)).to_string(), sequential.to_string());
+        }
+
+        // A row with an extra column.
+        write_generated_csv(&path, 20000);
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, contents.replace("\nid15000,", "\nid15000,x,")).unwrap();
+        let err = read(Some(4));
+        assert!(
+            matches!(
+                *err,
+                RcvError::CsvRowLength {
+                    lineno: 15002,
+                    len: 6,
+                    expected: 5
+                }
+            ),
+            "{:?}",
+            err
+        );
+
+        // A quote spans the ranges: the file is read with a single thread.
+        write_generated_csv(&path, 20000);
+        let contents = fs::read_to_string(&path).unwrap();
+        let quoted = contents
+            .replace("\nid1000,", "\n\"id1000,")
+            .replace("\nid19000,", "\nid19000\",");
+        fs::write(&path, quoted).unwrap();
+        let sequential =
+            io_csv::read_csv_ranking(path.display().to_string(), &csv_source(None, extra.clone()))
+                .unwrap();
+        let parallel = io_csv::read_csv_ranking(
+            path.display().to_string(),
+            &csv_source(Some(4), extra.clone()),
+        )
+        .unwrap();
+        assert_eq!(parallel, sequential);
     }
 
+    // Run with: RUST_LOG=info cargo test --release csv_parallel_benchmark -- --ignored --nocapture
     #[test]
     #[ignore]
-    fn minimum_threshold_test() {
-        // TODO P1
-        test_wrapper("minimum_threshold_test");
+    fn csv_parallel_benchmark() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let dir = std::env::temp_dir().join("timrcv_csv_parallel_benchmark");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ballots.csv");
+        write_generated_csv(&path, 1_000_000);
+        let path = path.display().to_string();
+        let extra =
+            json!({"firstVoteRowIndex": "2", "idColumnIndex": "1", "firstVoteColumnIndex": "3"});
+        for jobs in [None, Some(2), Some(4), Some(8)] {
+            let start = std::time::Instant::now();
+            let res =
+                io_csv::read_csv_ranking(path.clone(), &csv_source(jobs, extra.clone())).unwrap();
+            log::info!(
+                "jobs {:?}: {} rows in {:?}",
+                jobs,
+                res.len(),
+                start.elapsed()
+            );
+            assert_eq!(res.len(), 1_000_000);
+        }
     }
 
+    /// Every provider must be documented in the manual, listed in providers.json and
+    /// exercised by at least one local fixture.
     #[test]
-    #[ignore = "TODO P3 stopCountingAndAsk"]
-    fn minneapolis_multi_seat_threshold() {
-        test_wrapper("minneapolis_multi_seat_threshold");
+    fn providers_documented_and_tested() {
+        let manual = fs::read_to_string("./ranked_voting/src/manual.rs").unwrap();
+        let manifest = provider_manifest();
+        let manifest_names: Vec<&str> = manifest.iter().map(|p| p.name.as_str()).collect();
+
+        let mut fixture_providers: HashSet<String> = HashSet::new();
+        for entry in fs::read_dir("./tests").unwrap() {
+            let dir = entry.unwrap().path();
+            let name = dir.file_name().unwrap().to_string_lossy().to_string();
+            let config_path = dir.join(format!("{}_config.json", name));
+            if let Ok(contents) = fs::read_to_string(config_path) {
+                let config: RcvConfig = serde_json::from_str(&contents).unwrap();
+                for cfs in config.cvr_file_sources.iter() {
+                    fixture_providers.insert(cfs.provider.clone());
+                }
+            }
+        }
+        // The parquet files are written by the parquet tests, which compare them with the
+        // csv_simple_2 fixture.
+        fixture_providers.insert("parquet".to_string());
+
+        for provider in PROVIDERS.iter() {
+            assert!(
+                manual.contains(&format!("### `{}`", provider.name)),
+                "provider {} has no section in the manual",
+                provider.name
+            );
+            let entry = manifest.iter().find(|p| p.name == provider.name);
+            assert!(
+                entry.is_some(),
+                "provider {} is missing from providers.json",
+                provider.name
+            );
+            assert_eq!(
+                entry.unwrap().candidates_required,
+                provider.candidates_required.is_some(),
+                "candidatesRequired of provider {} in providers.json",
+                provider.name
+            );
+            assert!(
+                fixture_providers.contains(provider.name),
+                "provider {} has no test fixture under tests/",
+                provider.name
+            );
+        }
+        assert_eq!(manifest_names.len(), PROVIDERS.len());
     }
 
+    /// The lists of the input formats of the manual and of the README are generated from
+    /// providers.json. Run the test with UPDATE_PROVIDER_LIST=1 to write them.
     #[test]
-    fn missing_precinct_example() {
-        test_wrapper("missing_precinct_example");
+    fn manual_provider_list() {
+        let expected = provider_list();
+        for path in ["./ranked_voting/src/manual.rs", "./README.md"] {
+            let doc = fs::read_to_string(path).unwrap();
+            // The list ends with the first empty line.
+            let start_marker = "The following formats are supported:\n";
+            let start = doc.find(start_marker).unwrap() + start_marker.len();
+            let end = start + doc[start..].find("\n\n").unwrap() + 1;
+            if std::env::var_os("UPDATE_PROVIDER_LIST").is_some() {
+                fs::write(
+                    path,
+                    format!("{}{}{}", &doc[..start], expected, &doc[end..]),
+                )
+                .unwrap();
+                continue;
+            }
+            assert_eq!(
+                &doc[start..end],
+                expected,
+                "the list of {} is not the one of providers.json (run with UPDATE_PROVIDER_LIST=1)",
+                path
+            );
+        }
     }
 
     #[test]
-    #[ignore = "TODO P1 bottomsUpUsingPercentageThreshold"]
-    fn multi_seat_bottoms_up_with_threshold() {
-        test_wrapper("multi_seat_bottoms_up_with_threshold");
+    fn skip_bad_sources() {
+        let dir = std::env::temp_dir().join("timrcv_skip_bad_sources");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("good.csv"), "id1,2,A,B\nid2,1,B,A\n").unwrap();
+        fs::write(dir.join("corrupt.csv"), "id3,two,B,A\n").unwrap();
+        let source = |path: &str| {
+            json!({
+                "filePath": path,
+                "provider": "csv",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+            })
+        };
+        let config = json!({
+            "outputSettings": {"contestName": "skip"},
+            "cvrFileSources": [source("good.csv"), source("corrupt.csv")],
+            "candidates": [{"name": "A"}, {"name": "B"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json").display().to_string();
+        fs::write(&config_path, config.to_string()).unwrap();
+        let out_path = dir.join("summary.json").display().to_string();
+        // Any reference: it is not compared when a source is skipped.
+        let reference = "./tests/csv_simple_1/csv_simple_1_expected_summary.json".to_string();
+        let run = |args: &[&str]| {
+            run_election_at(
+                Some(config_path.clone()),
+                Some(reference.clone()),
+                None,
+                Some(out_path.clone()),
+                true,
+                Some(Args::parse_from(args)),
+            )
+        };
+
+        let err = run(&["timrcv"]).unwrap_err();
+        assert_eq!(err.code(), codes::ErrorCode::MalformedBallots);
+
+        let status = run(&["timrcv", "--skip-bad-sources"]).unwrap();
+        assert_eq!(status, SummaryStatus::Partial);
+        assert_eq!(status.exit_code(), 3);
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(summary["status"], json!("partial"));
+        assert_eq!(
+            summary["reason"],
+            json!("1 of 2 file sources could not be read, winner declared: A")
+        );
+        let failed = summary["failedSources"].as_array().unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0]["filePath"], json!("corrupt.csv"));
+        assert!(failed[0]["error"].as_str().unwrap().contains("\"two\""));
+        assert_eq!(summary["results"][0]["tally"], json!({"A": "2", "B": "1"}));
     }
 
     #[test]
-    #[ignore = "TODO P1 implement multiWinnerAllowMultipleWinnersPerRound"]
-    fn multi_seat_uwi_test() {
-        test_wrapper("multi_seat_uwi_test");
+    fn source_totals() {
+        let dir = std::env::temp_dir().join("timrcv_source_totals");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("early.csv"), "id1,2,A,B\nid2,1,B,A\n").unwrap();
+        fs::write(dir.join("election_day.csv"), "id3,4,B,\nid4,1,A,B\n").unwrap();
+        let source = |path: &str| {
+            json!({
+                "filePath": path,
+                "provider": "csv",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+            })
+        };
+        let config = json!({
+            "outputSettings": {"contestName": "sources"},
+            "cvrFileSources": [source("early.csv"), source("election_day.csv")],
+            "candidates": [{"name": "A"}, {"name": "B"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json").display().to_string();
+        fs::write(&config_path, config.to_string()).unwrap();
+        let out_path = dir.join("summary.json").display().to_string();
+        let run = |args: &[&str]| -> JSValue {
+            run_election_at(
+                Some(config_path.clone()),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                Some(Args::parse_from(args)),
+            )
+            .unwrap();
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap()
+        };
+
+        assert!(run(&["timrcv"])["config"].get("sources").is_none());
+        let expected = json!([
+            {"file": "early.csv", "provider": "csv", "parsedBallots": "3", "ballots": "3", "weight": "3", "truncatedBallots": "0"},
+            {"file": "election_day.csv", "provider": "csv", "parsedBallots": "5", "ballots": "5", "weight": "5", "truncatedBallots": "0"},
+        ]);
+        assert_eq!(
+            run(&["timrcv", "--emit-provenance"])["config"]["sources"],
+            expected
+        );
+        let summary = run(&["timrcv", "--summary-schema", "v2"]);
+        assert_eq!(summary["config"]["sources"], expected);
+        let total: u64 = summary["config"]["sources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["weight"].as_str().unwrap().parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(
+            summary["rankStatistics"]["totalVotes"],
+            json!(total.to_string())
+        );
     }
 
     #[test]
-    #[ignore = "TODO implement cdf provider"]
-    fn nist_xml_cdf_2() {
-        test_wrapper("nist_xml_cdf_2");
+    fn config_paths() {
+        let dir = std::env::temp_dir().join("timrcv_config_paths");
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.json").display().to_string();
+        let write_config = |file_path: &str, output_dir: &str| {
+            let config = json!({
+                "outputSettings": {"contestName": "paths", "outputDirectory": output_dir},
+                "cvrFileSources": [{"filePath": file_path, "provider": "csv"}],
+                "candidates": [{"name": "A"}, {"name": "B"}],
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "exhaustImmediately",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "1",
+                    "maxRankingsAllowed": "8",
+                },
+            });
+            fs::write(&config_path, config.to_string()).unwrap();
+        };
+
+        write_config("./config.json", "");
+        let err = resolve_config(&Some(config_path.clone()), &None, &None).unwrap_err();
+        assert!(matches!(err, RcvError::SourceIsConfig { .. }));
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+
+        // The summary of a previous run is still read, with a warning.
+        fs::write(dir.join("summary.json"), "{}").unwrap();
+        write_config("summary.json", dir.to_str().unwrap());
+        codes::take_warnings();
+        resolve_config(&Some(config_path.clone()), &None, &None).unwrap();
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::SourceIsOutput);
+
+        let large_path = dir.join("large.json");
+        let large = fs::File::create(&large_path).unwrap();
+        large.set_len(64 * 1024 * 1024).unwrap();
+        let err =
+            resolve_config(&Some(large_path.display().to_string()), &None, &None).unwrap_err();
+        assert!(matches!(err, RcvError::ConfigTooLarge { .. }));
+        assert!(err.to_string().contains("the limit is 10485760 bytes"));
+        fs::remove_file(&large_path).unwrap();
     }
 
+    // The symbolic links of the test are only created on unix.
+    #[cfg(unix)]
     #[test]
-    #[ignore = "TODO GH-16"]
-    fn no_one_meets_minimum() {
-        test_wrapper("no_one_meets_minimum");
+    fn duplicate_sources() {
+        let dir = std::env::temp_dir().join("timrcv_duplicate_sources");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("ballots.csv"), "A,B\nB,A\n").unwrap();
+        std::os::unix::fs::symlink(dir.join("ballots.csv"), dir.join("sub/link.csv")).unwrap();
+        let config_path = dir.join("config.json").display().to_string();
+        let write_config = |sources: JSValue| {
+            let config = json!({
+                "outputSettings": {"contestName": "duplicates"},
+                "cvrFileSources": sources,
+                "candidates": [{"name": "A"}, {"name": "B"}],
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "exhaustImmediately",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "1",
+                    "maxRankingsAllowed": "8",
+                },
+            });
+            fs::write(&config_path, config.to_string()).unwrap();
+        };
+
+        // The same file through a relative path and a symbolic link.
+        for other in ["./sub/../ballots.csv", "sub/link.csv"] {
+            write_config(json!([
+                {"filePath": "ballots.csv", "provider": "csv"},
+                {"filePath": other, "provider": "csv"},
+            ]));
+            let err = resolve_config(&Some(config_path.clone()), &None, &None).unwrap_err();
+            assert!(
+                matches!(&err, RcvError::DuplicateFileSource { first, second }
+                    if first == "ballots.csv" && second == other),
+                "{:?}",
+                err
+            );
+            assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+        }
+
+        // With --allow-duplicate-sources, the file is read once.
+        let args = Args::parse_from(["timrcv", "--allow-duplicate-sources"]);
+        codes::take_warnings();
+        let config = resolve_config(&Some(config_path.clone()), &None, &Some(args)).unwrap();
+        assert_eq!(config.cvr_file_sources.len(), 1);
+        assert_eq!(config.cvr_file_sources[0].file_path, "ballots.csv");
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::DuplicateSource);
+
+        // The same file for two contests is read twice, with a warning.
+        write_config(json!([
+            {"filePath": "ballots.csv", "provider": "csv", "contestId": "1"},
+            {"filePath": "sub/link.csv", "provider": "csv", "contestId": "2"},
+        ]));
+        let config = resolve_config(&Some(config_path.clone()), &None, &None).unwrap();
+        assert_eq!(config.cvr_file_sources.len(), 2);
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::SameFileSources);
     }
 
     #[test]
-    fn precinct_example() {
-        test_wrapper("precinct_example");
+    fn partial_rounds() {
+        let dir = std::env::temp_dir().join("timrcv_partial_rounds");
+        let _ = fs::remove_dir_all(&dir);
+        let config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        let mut ballots: Vec<Vec<&str>> = Vec::new();
+        for (name, count) in [("A", 5), ("B", 4), ("C", 3), ("D", 2), ("E", 1)] {
+            ballots.extend(std::iter::repeat_n(vec![name], count));
+        }
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        assert_eq!(res.round_stats.len(), 4);
+
+        let mut writer = partial::PartialWriter::new(dir.to_str().unwrap(), &config).unwrap();
+        writer.observe(&res.round_stats[0]);
+        writer.observe(&res.round_stats[1]);
+        let read = |name: &str| -> JSValue {
+            serde_json::from_str(&fs::read_to_string(dir.join(name)).unwrap()).unwrap()
+        };
+        let round_2 = read("round_002.json");
+        assert_eq!(round_2["round"], json!(2));
+        assert_eq!(
+            round_2["tally"],
+            json!({"A": "5", "B": "4", "C": "3", "D": "2"})
+        );
+        assert_eq!(round_2["tallyResults"][0]["eliminated"], json!("D"));
+        let summary = read("partial_summary.json");
+        assert_eq!(
+            summary["config"]["contest"],
+            config.output_settings.contest_name
+        );
+        assert_eq!(summary["results"], json!([read("round_001.json"), round_2]));
+        assert!(!dir.join("round_003.json").exists());
+        let names: HashSet<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names.len(), 3);
+
+        writer.observe(&res.round_stats[2]);
+        writer.observe(&res.round_stats[3]);
+        assert_eq!(
+            read("partial_summary.json")["results"]
+                .as_array()
+                .unwrap()
+                .len(),
+            4
+        );
+        writer.finish(false).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
     }
 
     #[test]
-    #[ignore = "TODO P3 stopCountingAndAsk"]
-    fn sample_interactive_tiebreak() {
-        test_wrapper("sample_interactive_tiebreak");
+    fn partial_rounds_after_summary() {
+        let dir = std::env::temp_dir().join("timrcv_partial_after_summary");
+        let _ = fs::remove_dir_all(&dir);
+        let partial_dir = dir.join("partial");
+        fs::create_dir_all(&partial_dir).unwrap();
+        let in_path = dir.join("ballots.csv");
+        fs::write(&in_path, "A\nA\nB\nC\n").unwrap();
+        let out_path = dir.join("summary.json");
+        let run = || {
+            let args = Args::parse_from(["timrcv", "--out-partial", partial_dir.to_str().unwrap()]);
+            run_election_at(
+                None,
+                None,
+                Some(in_path.display().to_string()),
+                Some(out_path.display().to_string()),
+                true,
+                Some(args),
+            )
+        };
+
+        // The partial files are removed once the summary is written.
+        assert_eq!(run().unwrap(), SummaryStatus::WinnerDeclared);
+        assert!(out_path.exists());
+        assert_eq!(fs::read_dir(&partial_dir).unwrap().count(), 0);
+
+        // A partial file that cannot be written does not stop the tabulation.
+        fs::remove_file(&out_path).unwrap();
+        fs::create_dir(partial_dir.join("round_001.json")).unwrap();
+        assert_eq!(run().unwrap(), SummaryStatus::WinnerDeclared);
+        assert!(out_path.exists());
     }
 
     #[test]
-    #[ignore = "TODO P1 multiPassIrv"]
-    fn sequential_with_batch() {
-        test_wrapper("sequential_with_batch");
+    fn flat_csv() {
+        let path = std::env::temp_dir().join("timrcv_flat.csv");
+        let mut ballots: Vec<Vec<&str>> = Vec::new();
+        for (ballot, count) in [
+            (vec!["A"], 5),
+            (vec!["B"], 3),
+            (vec!["C", "B"], 1),
+            (vec!["C"], 1),
+        ] {
+            ballots.extend(std::iter::repeat_n(ballot, count));
+        }
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        io_csv::write_flat_csv(path.to_str().unwrap(), &res, &NameOrder::Bytes).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            contents.lines().collect::<Vec<_>>(),
+            vec![
+                "round,candidate,votes,status,transfer_to,transfer_votes",
+                "1,A,5,continuing,,",
+                "1,B,3,continuing,,",
+                "1,C,2,eliminated,,",
+                "1,C,2,eliminated,B,1",
+                "1,C,2,eliminated,exhausted,1",
+                "2,A,5,elected,,",
+                "2,B,4,continuing,,",
+            ]
+        );
     }
 
     #[test]
-    #[ignore = "TODO P1 multiPassIrv"]
-    fn sequential_with_continue_until_two() {
-        test_wrapper("sequential_with_continue_until_two");
+    fn all_candidates_removed() {
+        let cand = |name: &str, excluded: Option<bool>| RcvCandidate {
+            name: name.to_string(),
+            code: None,
+            excluded,
+            inferred: false,
+        };
+        let mut ballots = vec![Ballot::new(
+            vec![BallotChoice::Candidate("A".to_string())],
+            1,
+        )];
+        let cands = Some(vec![cand("A", Some(true)), cand("Bobby", Some(true))]);
+        let err = tabulate(
+            &VoteRules::default(),
+            &cands,
+            &mut ballots,
+            None,
+            TabulationOptions::default(),
+        )
+        .unwrap_err();
+        let removed = match &err {
+            RcvError::NoContinuingCandidates { removed } => removed.clone(),
+            _ => panic!("unexpected error {:?}", err),
+        };
+        let attribution: Vec<(&str, &str)> = removed
+            .iter()
+            .map(|r| (r.name.as_str(), r.removed_by.as_str()))
+            .collect();
+        assert_eq!(
+            attribution,
+            vec![("A", "excluded flag"), ("Bobby", "excluded flag")]
+        );
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+        assert_eq!(
+            removed_candidates_table(&removed),
+            [
+                "candidate | removed by",
+                "----------+-----------",
+                "A         | excluded flag",
+                "Bobby     | excluded flag",
+            ]
+            .join("\n")
+        );
+
+        // One continuing candidate is enough.
+        let cands = Some(vec![cand("A", None), cand("Bobby", Some(true))]);
+        let res = tabulate(
+            &VoteRules::default(),
+            &cands,
+            &mut ballots,
+            None,
+            TabulationOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(res.winners, Some(vec!["A".to_string()]));
+    }
+
+    // Runs the CLI on one input file, and returns the summary or the error.
+    fn run_tiny_election(
+        dir: &std::path::Path,
+        input: &str,
+        input_type: &str,
+    ) -> Result<JSValue, RcvError> {
+        let out_path = dir.join("summary.json");
+        let _ = fs::remove_file(&out_path);
+        let args = Args::parse_from(["timrcv", "--input-type", input_type]);
+        run_election_at(
+            None,
+            None,
+            Some(dir.join(input).display().to_string()),
+            Some(out_path.display().to_string()),
+            true,
+            Some(args),
+        )?;
+        Ok(serde_json::from_str(&fs::read_to_string(out_path).unwrap()).unwrap())
+    }
+
+    fn check_tiny_elections(dir: &std::path::Path, input_type: &str, ext: &str) {
+        let summary = run_tiny_election(dir, &format!("one.{}", ext), input_type).unwrap();
+        assert_eq!(summary["status"], json!("winnerDeclared"));
+        assert_eq!(summary["config"]["threshold"], json!("1"));
+        assert_eq!(summary["results"][0]["tally"], json!({"A": "1"}));
+        assert_eq!(
+            summary["results"][0]["tallyResults"][0]["elected"],
+            json!("A")
+        );
+
+        let err = run_tiny_election(dir, &format!("blank.{}", ext), input_type).unwrap_err();
+        assert!(matches!(err, RcvError::NoCandidatesInInput { .. }));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "no candidates found in input {}",
+                dir.join(format!("blank.{}", ext)).display()
+            )
+        );
+
+        let err = run_tiny_election(dir, &format!("empty.{}", ext), input_type).unwrap_err();
+        assert!(matches!(err, RcvError::NoBallots { .. }));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "input file {} contains no ballots",
+                dir.join(format!("empty.{}", ext)).display()
+            )
+        );
     }
 
     #[test]
-    fn skip_to_next_test() {
-        test_wrapper("skip_to_next_test");
+    fn tiny_elections_csv() {
+        let dir = std::env::temp_dir().join("timrcv_tiny_csv");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("one.csv"), "A\n").unwrap();
+        fs::write(dir.join("blank.csv"), ",\n").unwrap();
+        fs::write(dir.join("empty.csv"), "").unwrap();
+        check_tiny_elections(&dir, "csv", "csv");
+
+        // An empty source is only refused when all the sources are empty.
+        let config_path = dir.join("config.json").display().to_string();
+        let out_path = dir.join("summary.json").display().to_string();
+        let write_config = |files: &[&str]| {
+            let sources: Vec<JSValue> = files
+                .iter()
+                .map(|f| json!({"filePath": f, "provider": "csv"}))
+                .collect();
+            let config = json!({
+                "outputSettings": {"contestName": "empty sources"},
+                "cvrFileSources": sources,
+                "candidates": [{"name": "A"}],
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "exhaustImmediately",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "1",
+                    "maxRankingsAllowed": "8",
+                },
+            });
+            fs::write(&config_path, config.to_string()).unwrap();
+        };
+        let run = || {
+            run_election_at(
+                Some(config_path.clone()),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                None,
+            )
+        };
+        write_config(&["one.csv", "empty.csv"]);
+        assert_eq!(run().unwrap(), SummaryStatus::WinnerDeclared);
+        fs::write(dir.join("empty2.csv"), "").unwrap();
+        write_config(&["empty.csv", "empty2.csv"]);
+        let err = run().unwrap_err();
+        assert!(matches!(err, RcvError::NoBallots { .. }));
+        assert_eq!(
+            err.to_string(),
+            "input files empty.csv, empty2.csv contain no ballots"
+        );
+        // A configuration without any source is refused.
+        write_config(&[]);
+        let err = run().unwrap_err();
+        assert!(matches!(err, RcvError::NoFileSources {}));
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
     }
 
+    #[cfg(feature = "xlsx")]
     #[test]
-    fn test_set_0_skipped_first_choice() {
-        test_wrapper("test_set_0_skipped_first_choice");
+    fn tiny_elections_msforms() {
+        use crate::rcv::xlsx::{write_sheets, Cell, Sheet};
+
+        let dir = std::env::temp_dir().join("timrcv_tiny_msforms");
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, rows: Vec<Cell>| {
+            // The ranking comes first, since it is the default first vote column.
+            let mut sheet_rows = vec![vec![
+                Cell::Text("Ranking".to_string()),
+                Cell::Text("ID".to_string()),
+            ]];
+            for (idx, c) in rows.into_iter().enumerate() {
+                sheet_rows.push(vec![c, Cell::Number((idx + 1) as f64)]);
+            }
+            let sheet = Sheet {
+                name: "Form1".to_string(),
+                rows: sheet_rows,
+            };
+            write_sheets(dir.join(name).to_str().unwrap(), &[sheet]).unwrap();
+        };
+        write("one.xlsx", vec![Cell::Text("A;".to_string())]);
+        write("blank.xlsx", vec![Cell::Empty]);
+        write("empty.xlsx", vec![]);
+        check_tiny_elections(&dir, "msforms_ranking", "xlsx");
     }
 
+    #[cfg(feature = "xlsx")]
     #[test]
-    fn test_set_1_exhaust_at_overvote() {
-        test_wrapper("test_set_1_exhaust_at_overvote");
+    fn likert_transpose_multi_name_cells() {
+        use crate::rcv::xlsx::{write_sheets, Cell, Sheet};
+
+        let dir = std::env::temp_dir().join("timrcv_multi_name_cells");
+        fs::create_dir_all(&dir).unwrap();
+        let text = |s: &str| Cell::Text(s.to_string());
+        // The second voter selected Alice and Bob as their first choice in a checkbox grid.
+        let sheet = Sheet {
+            name: "Form1".to_string(),
+            rows: vec![
+                vec![text("ID"), text("choice 1"), text("choice 2")],
+                vec![Cell::Number(1.0), text("Alice"), text("Bob")],
+                vec![Cell::Number(2.0), text("Alice;Bob"), text("Carol")],
+                vec![Cell::Number(3.0), text("Carol;"), Cell::Empty],
+            ],
+        };
+        let path = dir.join("grid.xlsx").display().to_string();
+        write_sheets(&path, &[sheet]).unwrap();
+        let cfs: FileSource = serde_json::from_value(json!({
+            "filePath": "grid.xlsx",
+            "provider": "msforms_likert_transpose",
+            "choices": ["choice 1", "choice 2"],
+        }))
+        .unwrap();
+        codes::take_warnings();
+        let parsed = io_msforms::read_msforms_likert_transpose(path.clone(), &cfs).unwrap();
+        let warnings = codes::take_warnings();
+        assert_eq!(parsed[1].choices, vec![vec!["Alice", "Bob"], vec!["Carol"]]);
+        assert_eq!(parsed[2].choices, vec![vec!["Carol"]]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::MultiNameCells);
+
+        let candidates: Vec<RcvCandidate> = ["Alice", "Bob", "Carol"]
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: None,
+                inferred: false,
+            })
+            .collect();
+        let config = RcvConfig::config_from_args(&Some("grid.xlsx".to_string())).unwrap();
+        let ballots = validated_ballots(&parsed, &candidates, &cfs, &config.rules);
+        assert_eq!(
+            ballots[1].candidates,
+            vec![
+                BallotChoice::Overvote,
+                BallotChoice::Candidate("Carol".to_string())
+            ]
+        );
+
+        // Another delimiter, with overvoteDelimiter.
+        let cfs: FileSource = serde_json::from_value(json!({
+            "filePath": "grid.xlsx",
+            "provider": "msforms_likert_transpose",
+            "choices": ["choice 1", "choice 2"],
+            "overvoteDelimiter": "|",
+        }))
+        .unwrap();
+        let parsed = io_msforms::read_msforms_likert_transpose(path, &cfs).unwrap();
+        assert_eq!(parsed[1].choices, vec![vec!["Alice;Bob"], vec!["Carol"]]);
+    }
+
+    fn sorted_names(order: &NameOrder) -> Vec<&'static str> {
+        let mut names = vec!["Zelda", "Åsa", "adam", "Bertil"];
+        names.sort_by(|n1, n2| order.compare(n1, n2));
+        names
     }
 
     #[test]
-    fn test_set_2_overvote_skip_to_next() {
-        test_wrapper("test_set_2_overvote_skip_to_next");
+    fn collation_bytes() {
+        let order = collation::name_order(None).unwrap();
+        assert_eq!(sorted_names(&order), vec!["Bertil", "Zelda", "adam", "Åsa"]);
+    }
+
+    #[cfg(not(feature = "collation"))]
+    #[test]
+    fn collation_without_feature() {
+        // The names are still sorted, by their bytes.
+        codes::take_warnings();
+        let order = collation::name_order(Some("sv")).unwrap();
+        assert_eq!(sorted_names(&order), vec!["Bertil", "Zelda", "adam", "Åsa"]);
+        // The warning has a code, so that --fail-on can reject it.
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::WarningCode::CollationUnavailable);
+        let fail_on = codes::parse_fail_on("collation-unavailable").unwrap();
+        assert!(codes::check_fail_on(&warnings, &fail_on).is_err());
+    }
+
+    #[cfg(feature = "collation")]
+    #[test]
+    fn collation_locales() {
+        let sv = collation::name_order(Some("sv")).unwrap();
+        assert_eq!(sorted_names(&sv), vec!["adam", "Bertil", "Zelda", "Åsa"]);
+        let en = collation::name_order(Some("en")).unwrap();
+        assert_eq!(sorted_names(&en), vec!["adam", "Åsa", "Bertil", "Zelda"]);
+        let err = collation::name_order(Some("not a tag")).unwrap_err();
+        assert!(matches!(err, RcvError::InvalidCollation { .. }));
+
+        // The candidates with the same votes in the flat table.
+        let ballots = vec![vec!["Åsa"], vec!["Zelda"], vec!["Bertil"]];
+        let res = run_election1(&ballots, &VoteRules::default()).unwrap();
+        let first_round: Vec<String> = res
+            .to_flat_rows_by(|n1, n2| sv.compare(n1, n2))
+            .into_iter()
+            .filter(|row| row.round == 1 && row.transfer_to.is_none())
+            .map(|row| row.candidate)
+            .collect();
+        assert_eq!(first_round, vec!["Bertil", "Zelda", "Åsa"]);
     }
 
     #[test]
-    fn test_set_3_skipped_choice_exhaust() {
-        test_wrapper("test_set_3_skipped_choice_exhaust");
+    fn csv_continuation_rows() {
+        test_wrapper_local("csv_continuation_rows");
+        // The parallel reader is not used for continuation rows.
+        let source = |id_column: Option<&str>| {
+            csv_source(
+                Some(4),
+                json!({
+                    "firstVoteRowIndex": "2",
+                    "idColumnIndex": id_column,
+                    "countColumnIndex": "2",
+                    "firstVoteColumnIndex": "3",
+                    "continuationRows": true,
+                }),
+            )
+        };
+        let with_id = || source(Some("1"));
+
+        let path = "./tests/csv_continuation_rows/example.csv".to_string();
+        let ballots = io_csv::read_csv_ranking(path.clone(), &with_id()).unwrap();
+        assert_eq!(ballots.len(), 3);
+        assert_eq!(ballots[1].id, Some("v2".to_string()));
+        assert_eq!(ballots[1].count, Some(4));
+        assert_eq!(ballots[1].choices, vec![vec!["B"], vec![], vec!["C"]]);
+
+        let err = io_csv::read_csv_ranking(path, &source(None)).unwrap_err();
+        assert!(matches!(*err, RcvError::ContinuationRowsWithoutId {}));
+
+        // The count of a continuation row must match the count of its ballot.
+        let dir = std::env::temp_dir().join("timrcv_continuation_rows");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ballots.csv").display().to_string();
+        fs::write(&path, "id,count,rank1\nv1,2,A\nv1,2,B\nv2,1,B\nv2,3,A\n").unwrap();
+        let err = io_csv::read_csv_ranking(path, &with_id()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 5: the continuation row has the count 3, but the ballot has the count 1"
+        );
+        assert_eq!(err.code(), codes::ErrorCode::MalformedBallots);
     }
 
     #[test]
-    fn test_set_4_skipped_choice_next() {
-        test_wrapper("test_set_4_skipped_choice_next");
+    fn analysis_head_to_head_winner() {
+        // The centrist C is eliminated first, but a majority prefers C to the winner L.
+        let dir = std::env::temp_dir().join("timrcv_head_to_head");
+        fs::create_dir_all(&dir).unwrap();
+        let input_path = dir.join("ballots.csv").display().to_string();
+        let out_path = dir.join("summary.json").display().to_string();
+        let mut contents = String::new();
+        for (pattern, count) in [("L,C,R", 35), ("R,C,L", 33), ("C,L,R", 17), ("C,R,L", 15)] {
+            contents.push_str(&format!("{}\n", pattern).repeat(count));
+        }
+        fs::write(&input_path, contents).unwrap();
+
+        let args = Args::parse_from(["timrcv", "--analysis", "head-to-head-winner"]);
+        run_election_at(
+            None,
+            None,
+            Some(input_path.clone()),
+            Some(out_path.clone()),
+            true,
+            Some(args),
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(
+            summary["results"][1]["tallyResults"][0]["elected"],
+            json!("L")
+        );
+        assert_eq!(
+            summary["analysis"]["headToHeadWinner"],
+            json!([
+                {
+                    "candidate": "C",
+                    "eliminatedRound": 1,
+                    "winner": "L",
+                    "candidateVotes": 65,
+                    "winnerVotes": 35,
+                    "beatsWinner": true,
+                },
+                {
+                    "candidate": "R",
+                    "eliminatedRound": 2,
+                    "winner": "L",
+                    "candidateVotes": 48,
+                    "winnerVotes": 52,
+                    "beatsWinner": false,
+                },
+            ])
+        );
+
+        // The ballots that rank neither candidate are not counted.
+        let ballots = vec![
+            Ballot::new(vec![BallotChoice::Candidate("C".to_string())], 2),
+            Ballot::new(
+                vec![
+                    BallotChoice::Overvote,
+                    BallotChoice::Candidate("B".to_string()),
+                ],
+                3,
+            ),
+        ];
+        let counts = analysis::pairwise_counts(&ballots, &[("A", "B")]);
+        assert_eq!((counts[0].c1_votes, counts[0].c2_votes), (0, 3));
+
+        let args = Args::parse_from(["timrcv", "--analysis", "condorcet"]);
+        let err =
+            run_election_at(None, None, Some(input_path), None, true, Some(args)).unwrap_err();
+        assert_eq!(err.to_string(), "unknown analysis: condorcet");
     }
 
-    #[test]
-    fn test_set_5_two_skipped_choice_exhaust() {
-        test_wrapper("test_set_5_two_skipped_choice_exhaust");
+    // A writer that keeps the size of its largest write.
+    #[derive(Default)]
+    struct LargestWrite {
+        largest: usize,
+        total: usize,
     }
 
-    #[test]
-    fn test_set_6_duplicate_exhaust() {
-        test_wrapper("test_set_6_duplicate_exhaust");
+    impl std::io::Write for LargestWrite {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.largest = self.largest.max(buf.len());
+            self.total += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
     }
 
     #[test]
-    fn test_set_7_duplicate_skip_to_next() {
-        test_wrapper("test_set_7_duplicate_skip_to_next");
+    fn summary_streaming() {
+        // 200 candidates with distinct tallies: one elimination per round.
+        let mut ballots: Vec<Ballot> = (0..200)
+            .map(|k| {
+                Ballot::new(
+                    vec![
+                        BallotChoice::Candidate(format!("C{:03}", k)),
+                        BallotChoice::Candidate(format!("C{:03}", (k + 1) % 200)),
+                    ],
+                    k + 1,
+                )
+            })
+            .collect();
+        let mut config = RcvConfig::config_from_args(&Some("example.csv".to_string())).unwrap();
+        let rules = validate_rules(&config.rules).unwrap();
+        let res = tabulate(
+            &rules,
+            &None,
+            &mut ballots,
+            None,
+            TabulationOptions::default(),
+        )
+        .unwrap();
+        assert!(res.round_stats.len() > 100);
+        // The summary is written piece by piece, never as a whole text.
+        for schema in ["v1", "v2"] {
+            config.output_settings.summary_schema = Some(schema.to_string());
+            let summary = Summary::new(&config, &res).unwrap();
+            let mut writes = LargestWrite::default();
+            summary.write_pretty(&mut writes).unwrap();
+            assert!(
+                writes.largest * 100 < writes.total,
+                "{} bytes in a single write, {} in total",
+                writes.largest,
+                writes.total
+            );
+        }
+
+        // The summaries written to the files are the reference summaries of the fixtures.
+        let dir = std::env::temp_dir().join("timrcv_summary_streaming");
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "csv_simple_2",
+            "csv_excluded",
+            "csv_challenged",
+            "csv_weights",
+        ] {
+            let out_path = dir.join(format!("{}.json", name)).display().to_string();
+            run_election_at(
+                Some(format!("./tests/{}/{}_config.json", name, name)),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                None,
+            )
+            .unwrap();
+            let written: JSValue =
+                serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+            let expected: JSValue = serde_json::from_str(
+                &fs::read_to_string(format!("./tests/{}/{}_expected_summary.json", name, name))
+                    .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(
+                summary_for_comparison(&written, &expected),
+                expected,
+                "{}",
+                name
+            );
+        }
     }
 
     #[test]
-    fn test_set_8_multi_cdf() {
-        test_wrapper("test_set_8_multi_cdf");
+    fn tabulation_does_not_copy_ballots() {
+        // The ballots are moved to the builder and given back after the tabulation: their
+        // choices are the same allocations. The allocations of the tabulation itself are counted
+        // in the tests of the library (ranked_voting/tests/allocations.rs).
+        let mut ballots: Vec<Ballot> = (0..1000)
+            .map(|k| {
+                Ballot::new(
+                    vec![
+                        BallotChoice::Candidate(format!("C{}", k % 3)),
+                        BallotChoice::Candidate(format!("C{}", (k + 1) % 3)),
+                    ],
+                    1,
+                )
+            })
+            .collect();
+        let choices_ptrs: Vec<*const BallotChoice> =
+            ballots.iter().map(|b| b.candidates.as_ptr()).collect();
+        let candidates_o = Some(
+            (0..3)
+                .map(|k| RcvCandidate {
+                    name: format!("C{}", k),
+                    code: None,
+                    excluded: None,
+                    inferred: false,
+                })
+                .collect(),
+        );
+        let rules = VoteRules::default();
+        tabulate(
+            &rules,
+            &candidates_o,
+            &mut ballots,
+            None,
+            TabulationOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(ballots.len(), 1000);
+        assert!(ballots
+            .iter()
+            .zip(choices_ptrs.iter())
+            .all(|(b, ptr)| b.candidates.as_ptr() == *ptr));
     }
 
     #[test]
-    #[ignore = "TODO P1 election mode multiWinnerAllowOnlyOneWinnerPerRound"]
-    fn test_set_allow_only_one_winner_per_round() {
-        test_wrapper("test_set_allow_only_one_winner_per_round");
+    fn truncated_ranks_beyond_max_rankings() {
+        let ballot = |choices: &[&str]| ParsedBallot {
+            id: Some("b1".to_string()),
+            count: Some(2),
+            choices: choices
+                .iter()
+                .map(|c| {
+                    if c.is_empty() {
+                        vec![]
+                    } else {
+                        vec![c.to_string()]
+                    }
+                })
+                .collect(),
+            challenged: false,
+            category: None,
+            respondent: None,
+            precinct: None,
+            style: None,
+        };
+        let tr = truncated_ranks(&ballot(&["A", "B", "C", "D", " E ", "F"]), Some(3)).unwrap();
+        assert_eq!(tr.id, Some("b1".to_string()));
+        assert_eq!(tr.truncated_ranks, 3);
+        assert_eq!(tr.choices, vec!["D", " E ", "F"]);
+        // The skipped ranks beyond the limit are not marks.
+        let tr = truncated_ranks(&ballot(&["A", "B", "C", "", "E", " "]), Some(3)).unwrap();
+        assert_eq!((tr.truncated_ranks, tr.choices), (1, vec!["E".to_string()]));
+        assert_eq!(
+            truncated_ranks(&ballot(&["A", "B", "C", ""]), Some(3)),
+            None
+        );
+        assert_eq!(truncated_ranks(&ballot(&["A", "B", "C", "D"]), None), None);
+
+        let dir = std::env::temp_dir().join("timrcv_truncated_ranks");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ballots.csv"), "b1,2,A,B,C,D,E,F\nb2,1,B,A,,,,\n").unwrap();
+        let config = json!({
+            "outputSettings": {"contestName": "truncated"},
+            "cvrFileSources": [{
+                "filePath": "ballots.csv",
+                "provider": "csv",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+            }],
+            "candidates": [{"name": "A"}, {"name": "B"}, {"name": "C"}, {"name": "D"}, {"name": "E"}, {"name": "F"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "3",
+            },
+        });
+        let config_path = dir.join("config.json").display().to_string();
+        fs::write(&config_path, config.to_string()).unwrap();
+        let out_path = dir.join("summary.json").display().to_string();
+        run_election_at(
+            Some(config_path),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            Some(Args::parse_from(["timrcv", "--emit-provenance"])),
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(
+            summary["config"]["sources"][0]["truncatedBallots"],
+            json!("2")
+        );
     }
 
     #[test]
-    #[ignore = "P3 tiebreak stopCountingAndAsk"]
-    fn test_set_multi_winner_fractional_threshold() {
-        test_wrapper("test_set_multi_winner_fractional_threshold");
+    fn output_paths_checked_before_tabulation() {
+        let dir = std::env::temp_dir().join("timrcv_output_paths");
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = "./tests/csv_simple_1/csv_simple_1_config.json".to_string();
+        let export_path = dir.join("ballots.csv");
+        let run = |reference: &str, out: &str| {
+            let _ = fs::remove_file(&export_path);
+            let _ = fs::remove_file(dir.join("ballots_config.json"));
+            let args = Args::parse_from([
+                "timrcv",
+                "--export-simple-csv",
+                export_path.to_str().unwrap(),
+            ]);
+            let reference = Some(reference.to_string()).filter(|r| !r.is_empty());
+            let res = run_election_at(
+                Some(config_path.clone()),
+                reference,
+                None,
+                Some(out.to_string()),
+                true,
+                Some(args),
+            );
+            // The ballots are exported before the tabulation: after an error, they were not even
+            // read.
+            assert_eq!(export_path.exists(), res.is_ok());
+            res
+        };
+
+        let missing = dir.join("missing.json").display().to_string();
+        let err = run(&missing, "").unwrap_err();
+        assert!(matches!(err, RcvError::ReferenceOpen { .. }));
+        assert_eq!(
+            err.to_string(),
+            format!("cannot open the reference summary {}", missing)
+        );
+        assert_eq!(err.code(), codes::ErrorCode::UnreadableInput);
+
+        let err = run(dir.to_str().unwrap(), "").unwrap_err();
+        assert!(matches!(err, RcvError::ReferenceNotAFile { .. }));
+
+        let invalid = dir.join("invalid.json");
+        fs::write(&invalid, "{\"results\": [").unwrap();
+        let err = run(invalid.to_str().unwrap(), "").unwrap_err();
+        assert!(matches!(err, RcvError::ReferenceParse { .. }));
+
+        let out = dir
+            .join("missing_dir")
+            .join("summary.json")
+            .display()
+            .to_string();
+        let err = run("", &out).unwrap_err();
+        let missing_dir = dir.join("missing_dir").display().to_string();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the directory {} of --out {} does not exist, create it first (mkdir -p {})",
+                missing_dir, out, missing_dir
+            )
+        );
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+
+        let out = dir.display().to_string();
+        let err = run("", &out).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "--out {} is a directory, append a file name, for example {}",
+                out,
+                dir.join("summary.json").display()
+            )
+        );
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+
+        // The structure of the reference is only checked by the comparison.
+        let reference = "./tests/csv_simple_1/csv_simple_1_expected_summary.json";
+        run(reference, "").unwrap();
+        run(reference, "stdout").unwrap();
+        run("", dir.join("summary.json").to_str().unwrap()).unwrap();
     }
 
     #[test]
-    #[ignore = "P3 tiebreak stopCountingAndAsk"]
-    fn test_set_multi_winner_whole_threshold() {
-        test_wrapper("test_set_multi_winner_whole_threshold");
+    fn output_write_errors_explained() {
+        let write_error = |kind: std::io::ErrorKind| RcvError::SummaryWrite {
+            source: std::io::Error::from(kind),
+            path: "results.json".to_string(),
+        };
+        let err = output_path::explain(
+            "--out-xlsx",
+            "results.xlsx",
+            write_error(std::io::ErrorKind::StorageFull),
+        );
+        assert!(matches!(err, RcvError::OutputDiskFull { .. }));
+        assert!(err
+            .to_string()
+            .starts_with("the disk is full, cannot write --out-xlsx /"));
+        assert_eq!(err.code(), codes::ErrorCode::OutputFailed);
+
+        let err = output_path::explain(
+            "--ledger",
+            "ledger.jsonl",
+            write_error(std::io::ErrorKind::PermissionDenied),
+        );
+        match &err {
+            RcvError::OutputPermissionDenied { flag, path, .. } => {
+                assert_eq!(flag, "--ledger");
+                assert!(std::path::Path::new(path).is_absolute());
+                assert!(path.ends_with("ledger.jsonl"));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert_eq!(err.code(), codes::ErrorCode::OutputFailed);
+
+        // The other errors are not changed.
+        let err = output_path::explain(
+            "--out",
+            "results.json",
+            write_error(std::io::ErrorKind::Interrupted),
+        );
+        assert!(matches!(err, RcvError::SummaryWrite { .. }));
     }
 
     #[test]
-    #[ignore = "P1 unknown overvote rule: exhaustIfMultipleContinuing"]
-    fn test_set_overvote_delimiter() {
-        test_wrapper("test_set_overvote_delimiter");
+    fn nfd_reference_names() {
+        let dir = std::env::temp_dir().join("timrcv_nfd_reference_names");
+        fs::create_dir_all(&dir).unwrap();
+        let (nfc, nfd) = ("Jos\u{e9}", "Jose\u{301}");
+        // The ballots and the reference are written on macOS, the configuration is in NFC.
+        fs::write(
+            dir.join("ballots.csv"),
+            format!("{nfd},Ana\n{nfd},Ana\nAna,{nfd}\n"),
+        )
+        .unwrap();
+        let config = json!({
+            "outputSettings": {"contestName": format!("Mayor {}", nfc)},
+            "cvrFileSources": [{"filePath": "ballots.csv", "provider": "csv"}],
+            "candidates": [{"name": nfc}, {"name": "Ana"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json").display().to_string();
+        fs::write(&config_path, config.to_string()).unwrap();
+        let summary_path = dir.join("summary.json").display().to_string();
+        run_election_at(
+            Some(config_path.clone()),
+            None,
+            None,
+            Some(summary_path.clone()),
+            true,
+            None,
+        )
+        .unwrap();
+        let summary = fs::read_to_string(&summary_path).unwrap();
+        assert!(
+            summary.contains(nfc) && !summary.contains(nfd),
+            "{}",
+            summary
+        );
+
+        let reference_path = dir.join("reference.json").display().to_string();
+        fs::write(&reference_path, summary.replace(nfc, nfd)).unwrap();
+        let status = run_election_at(
+            Some(config_path),
+            Some(reference_path),
+            None,
+            Some("".to_string()),
+            true,
+            None,
+        )
+        .unwrap();
+        assert_eq!(status, SummaryStatus::WinnerDeclared);
     }
 
     #[test]
-    #[ignore = "TODO P1 random"]
-    fn test_set_treat_blank_as_undeclared_write_in() {
-        test_wrapper("test_set_treat_blank_as_undeclared_write_in");
+    fn inline_rules() {
+        let rule_args = |rules: &[&str]| {
+            let mut cli = vec!["timrcv"];
+            for r in rules.iter() {
+                cli.push("--rule");
+                cli.push(r);
+            }
+            Some(Args::parse_from(cli))
+        };
+        let input = Some("./tests/csv_simple_1/example.csv".to_string());
+
+        // Without --config, the rules are set on top of the defaults.
+        codes::take_warnings();
+        let config = resolve_config(
+            &None,
+            &input,
+            &rule_args(&[
+                "overvoteRule=alwaysSkipToNextRank",
+                "maxSkippedRanksAllowed=1",
+                "exhaustOnDuplicateCandidate=true",
+                "tiebreakMode=random",
+                "randomSeed=42",
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.rules._overvote_rule, "alwaysSkipToNextRank");
+        assert_eq!(config.rules.max_skipped_ranks_allowed, "1");
+        assert_eq!(config.rules.exhaust_on_duplicate_candidate, Some(true));
+        assert_eq!(config.rules.tiebreak_mode, "random");
+        assert_eq!(config.rules.random_seed().unwrap(), Some(42));
+        assert!(codes::take_warnings().is_empty());
+
+        // The unknown rules, the invalid values and the malformed arguments are refused.
+        let err = resolve_config(&None, &input, &rule_args(&["overvoteRules=x"])).unwrap_err();
+        assert!(matches!(err, RcvError::UnknownRule { .. }), "{:?}", err);
+        assert!(err.to_string().contains("overvoteRule,"), "{}", err);
+        let err = resolve_config(
+            &None,
+            &input,
+            &rule_args(&["exhaustOnDuplicateCandidate=yes"]),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, RcvError::InvalidRuleValue { .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+        let err =
+            resolve_config(&None, &input, &rule_args(&["tiebreakMode=coinToss"])).unwrap_err();
+        assert!(err.to_string().contains("coinToss"), "{}", err);
+        let err = resolve_config(&None, &input, &rule_args(&["tiebreakMode=random"])).unwrap_err();
+        assert!(matches!(err, RcvError::MissingRandomSeed {}), "{:?}", err);
+        let err = resolve_config(&None, &input, &rule_args(&["batchElimination"])).unwrap_err();
+        assert!(matches!(err, RcvError::RuleArgument { .. }), "{:?}", err);
+
+        // With --config, the rules of the configuration are overridden with a warning.
+        let config_path = Some("./tests/csv_simple_1/csv_simple_1_config.json".to_string());
+        let config = resolve_config(
+            &config_path,
+            &None,
+            &rule_args(&["overvoteRule=exhaustImmediately", "batchElimination=true"]),
+        )
+        .unwrap();
+        assert_eq!(config.rules._overvote_rule, "exhaustImmediately");
+        assert_eq!(config.rules.batch_elimination, Some(true));
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0].code, codes::WarningCode::RuleOverride);
+        assert!(warnings[0].message.contains("batchElimination"));
     }
 
     #[test]
-    #[ignore = "P1 tiebreak generatePermutation"]
-    fn tiebreak_generate_permutation_test() {
-        test_wrapper("tiebreak_generate_permutation_test");
+    fn source_metrics() {
+        let dir = std::env::temp_dir().join("timrcv_source_metrics");
+        fs::create_dir_all(&dir).unwrap();
+        let early = "id1,2,A,B\nid2,1,A/X,B\n";
+        let election_day = "id3,4,B,\nid4,1,A,B\nid5,1,B,A\n";
+        fs::write(dir.join("early.csv"), early).unwrap();
+        fs::create_dir_all(dir.join("day")).unwrap();
+        fs::write(dir.join("day").join("election_day.csv"), election_day).unwrap();
+        let source = |path: &str| {
+            json!({
+                "filePath": path,
+                "provider": "csv",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+                "overvoteDelimiter": "/",
+            })
+        };
+        let config = json!({
+            "outputSettings": {"contestName": "metrics"},
+            "cvrFileSources": [source("early.csv"), source("day/election_day.csv")],
+            "candidates": [{"name": "A"}, {"name": "B"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json").display().to_string();
+        fs::write(&config_path, config.to_string()).unwrap();
+        let metrics_path = dir.join("metrics.json").display().to_string();
+        run_election_at(
+            Some(config_path),
+            None,
+            None,
+            Some("".to_string()),
+            true,
+            Some(Args::parse_from([
+                "timrcv",
+                "--out-metrics",
+                metrics_path.as_str(),
+            ])),
+        )
+        .unwrap();
+        let metrics: JSValue =
+            serde_json::from_str(&fs::read_to_string(&metrics_path).unwrap()).unwrap();
+        let sources = metrics["sources"].as_array().unwrap();
+        assert_eq!(sources.len(), 2);
+        // The paths are reduced to the names of the files.
+        assert_eq!(sources[0]["file"], json!("early.csv"));
+        assert_eq!(sources[1]["file"], json!("election_day.csv"));
+        for (source, (content, ballots, warnings)) in sources
+            .iter()
+            .zip([(early, 3, 1), (election_day, 6, 0)].iter())
+        {
+            assert_eq!(source["provider"], json!("csv"));
+            assert_eq!(source["bytes"], json!(content.len()), "{}", source);
+            assert_eq!(source["parsedBallots"], json!(ballots), "{}", source);
+            assert_eq!(source["warnings"], json!(warnings), "{}", source);
+            assert!(source["parseMillis"].as_f64().unwrap() >= 0.0);
+            assert!(source["validationMillis"].as_f64().unwrap() >= 0.0);
+        }
     }
 
     #[test]
-    #[ignore = "P1 tiebreak previousRoundCountsThenRandom"]
-    fn tiebreak_previous_round_counts_then_random_test() {
-        test_wrapper("tiebreak_previous_round_counts_then_random_test");
+    fn likert_ranks_out_of_range() {
+        let dir = std::env::temp_dir().join("timrcv_likert_ranks");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ranks.csv").display().to_string();
+        fs::write(
+            &path,
+            "id,A,B,C\nr1,1,2,3\nr2,0,1,99\nr3,1,1,2\nr4,99,1,\nr5,2,1000000000,1\n",
+        )
+        .unwrap();
+        let source = |max_rank: Option<u32>| -> FileSource {
+            serde_json::from_value(json!({
+                "provider": "csv_likert",
+                "filePath": "",
+                "firstVoteRowIndex": "2",
+                "idColumnIndex": "1",
+                "maxRank": max_rank,
+            }))
+            .unwrap()
+        };
+        let names: Vec<String> = ["A", "B", "C"].iter().map(|s| s.to_string()).collect();
+        let choices = |l: &[&[&str]]| -> Vec<Vec<String>> {
+            l.iter()
+                .map(|c| c.iter().map(|s| s.to_string()).collect())
+                .collect()
+        };
+
+        codes::take_warnings();
+        let ballots = io_csv::read_csv_likert(path.clone(), &source(None), &names).unwrap();
+        assert_eq!(ballots[0].choices, choices(&[&["A"], &["B"], &["C"]]));
+        // The ranks 0 and 99 are dropped, the other ranks of the ballot are kept.
+        assert_eq!(ballots[1].choices, choices(&[&["B"]]));
+        // The duplicate ranks are an overvote.
+        assert_eq!(ballots[2].choices, choices(&[&["A", "B"], &["C"]]));
+        assert_eq!(ballots[3].choices, choices(&[&["B"]]));
+        // A huge rank does not allocate a huge ballot.
+        assert_eq!(ballots[4].choices, choices(&[&["C"], &["A"]]));
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert_eq!(warnings[0].code, codes::WarningCode::RankOutOfRange);
+        assert!(
+            warnings[0].message.contains(
+                "\"99\" appeared 2 times, \"0\" appeared 1 times, \"1000000000\" appeared 1 times"
+            ),
+            "{}",
+            warnings[0].message
+        );
+        assert_eq!(warnings[1].code, codes::WarningCode::DuplicateRanks);
+        assert!(warnings[1].message.contains(": 1 ballots"));
+
+        // With a larger maxRank, the 99 is a (far) rank.
+        let ballots = io_csv::read_csv_likert(path, &source(Some(100)), &names).unwrap();
+        assert_eq!(ballots[3].choices.len(), 99);
+        assert_eq!(ballots[3].choices[98], vec!["A".to_string()]);
+        codes::take_warnings();
     }
 
     #[test]
-    #[ignore = "TODO P2 output format is different"]
-    fn tiebreak_seed_test() {
-        test_wrapper("tiebreak_seed_test");
+    fn ledger_entries() {
+        use crate::rcv::ledger;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let dir = std::env::temp_dir().join("timrcv_ledger");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let ballots_path = dir.join("ballots.csv");
+        fs::write(&ballots_path, "id1,2,A,B\nid2,1,B,A\n").unwrap();
+        let config = json!({
+            "outputSettings": {"contestName": "ledger"},
+            "cvrFileSources": [{
+                "filePath": "ballots.csv",
+                "provider": "csv",
+                "idColumnIndex": "1",
+                "countColumnIndex": "2",
+                "firstVoteColumnIndex": "3",
+            }],
+            "candidates": [{"name": "A"}, {"name": "B"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json").display().to_string();
+        fs::write(&config_path, config.to_string()).unwrap();
+        let out_path = dir.join("summary.json").display().to_string();
+        let ledger_path = dir.join("ledger.jsonl").display().to_string();
+        let run = |extra: &[&str]| {
+            let mut cli = vec!["timrcv", "--ledger", ledger_path.as_str()];
+            cli.extend(extra);
+            run_election_at(
+                Some(config_path.clone()),
+                None,
+                None,
+                Some(out_path.clone()),
+                true,
+                Some(Args::parse_from(cli)),
+            )
+        };
+        let lines = || -> Vec<String> {
+            fs::read_to_string(&ledger_path)
+                .unwrap()
+                .lines()
+                .map(|l| l.to_string())
+                .collect()
+        };
+
+        // The ledger is created by the first run.
+        run(&[]).unwrap();
+        let first = lines();
+        assert_eq!(first.len(), 1);
+        let entry: JSValue = serde_json::from_str(&first[0]).unwrap();
+        assert_eq!(entry["version"], json!(env!("CARGO_PKG_VERSION")));
+        assert_eq!(
+            entry["inputs"],
+            json!([{"file": "ballots.csv", "sha256": sha256::digest("id1,2,A,B\nid2,1,B,A\n")}])
+        );
+        assert_eq!(entry["ballots"], json!(3));
+        assert_eq!(entry["winners"], json!(["A"]));
+        assert_eq!(entry["status"], json!("winnerDeclared"));
+        assert_eq!(entry["output"], json!(out_path));
+        assert_eq!(entry["rulesFingerprint"].as_str().unwrap().len(), 64);
+
+        // The same inputs again are refused, and the ledger is unchanged.
+        let err = run(&[]).unwrap_err();
+        assert!(matches!(err, RcvError::LedgerDuplicate { .. }), "{:?}", err);
+        assert_eq!(lines(), first);
+
+        // New inputs are appended, as are duplicates that are allowed explicitly.
+        fs::write(&ballots_path, "id1,2,A,B\nid2,3,B,A\n").unwrap();
+        run(&[]).unwrap();
+        run(&["--allow-duplicate-ledger"]).unwrap();
+        let all = lines();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0], first[0]);
+        let last: JSValue = serde_json::from_str(&all[2]).unwrap();
+        assert_eq!(last["winners"], json!(["B"]));
+        assert_eq!(
+            last["inputs"],
+            serde_json::from_str::<JSValue>(&all[1]).unwrap()["inputs"]
+        );
+
+        assert_eq!(
+            ledger::utc_timestamp(UNIX_EPOCH + Duration::from_secs(1730840580)),
+            "2024-11-05T21:03:00Z"
+        );
+        assert_eq!(
+            ledger::utc_timestamp(UNIX_EPOCH + Duration::from_secs(951782400)),
+            "2000-02-29T00:00:00Z"
+        );
     }
 
     #[test]
-    fn tiebreak_use_permutation_in_config_test() {
-        test_wrapper("tiebreak_use_permutation_in_config_test");
+    fn percent_rounding_option() {
+        let dir = test_temp_dir("timrcv_percent_rounding");
+        // A has 1 of the 16 votes: 6.25%.
+        fs::write(dir.join("ballots.csv"), "id1,1,A,B\nid2,15,B,A\n").unwrap();
+        let run = |rounding: Option<&str>| -> RcvResult<JSValue> {
+            let config = json!({
+                "outputSettings": {
+                    "contestName": "rounding",
+                    "summarySchema": "v2",
+                    "percentRounding": rounding,
+                },
+                "cvrFileSources": [{
+                    "filePath": "ballots.csv",
+                    "provider": "csv",
+                    "idColumnIndex": "1",
+                    "countColumnIndex": "2",
+                    "firstVoteColumnIndex": "3",
+                }],
+                "candidates": [{"name": "A"}, {"name": "B"}],
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "exhaustImmediately",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "1",
+                    "maxRankingsAllowed": "8",
+                },
+            });
+            run_json_config(&dir, &config, &[])
+        };
+        for (rounding, a, b) in [
+            (None, "6.3", "93.8"),
+            (Some("halfUp"), "6.3", "93.8"),
+            (Some("truncate"), "6.2", "93.7"),
+            (Some("halfEven"), "6.2", "93.8"),
+        ] {
+            let summary = run(rounding).unwrap();
+            assert_eq!(
+                summary["firstChoicePercent"],
+                json!({"A": a, "B": b}),
+                "{:?}",
+                rounding
+            );
+        }
+        let err = run(Some("up")).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown percent rounding"),
+            "{}",
+            err
+        );
     }
 
     #[test]
-    #[ignore = "TODO P2 provider cdf"]
-    fn unisyn_xml_cdf_city_chief_of_police() {
-        test_wrapper("unisyn_xml_cdf_city_chief_of_police");
+    fn rank_columns_by_header() {
+        let dir = std::env::temp_dir().join("timrcv_rank_headers");
+        fs::create_dir_all(&dir).unwrap();
+        let source = |pattern: Option<&str>| -> FileSource {
+            serde_json::from_value(json!({
+                "provider": "csv",
+                "filePath": "",
+                "firstVoteRowIndex": "2",
+                "idColumnIndex": "1",
+                "rankColumnsByHeader": true,
+                "rankHeaderPattern": pattern,
+            }))
+            .unwrap()
+        };
+        let read = |content: &str, pattern: Option<&str>| {
+            let path = dir.join("ballots.csv").display().to_string();
+            fs::write(&path, content).unwrap();
+            io_csv::read_csv_ranking(path, &source(pattern))
+        };
+        let choices = |l: &[&str]| -> Vec<Vec<String>> {
+            l.iter()
+                .map(|c| {
+                    if c.is_empty() {
+                        vec![]
+                    } else {
+                        vec![c.to_string()]
+                    }
+                })
+                .collect()
+        };
+
+        // Shuffled headers: the choices follow the ranks, the other columns are ignored.
+        let ballots = read(
+            "id, Rank 2 ,notes,rank 1,RANK 3\nv1,B,hello,A,C\nv2,,x,C,\n",
+            Some("rank {}"),
+        )
+        .unwrap();
+        assert_eq!(ballots[0].choices, choices(&["A", "B", "C"]));
+        // The empty cells at the end are dropped.
+        assert_eq!(ballots[1].choices, choices(&["C"]));
+        let ballots = read("id,rank2,notes,rank1,rank3\nv1,B,hello,A,C\n", None).unwrap();
+        assert_eq!(ballots[0].id.as_deref(), Some("v1"));
+        assert_eq!(ballots[0].choices, choices(&["A", "B", "C"]));
+
+        // Gapped and duplicate ranks.
+        let err = read("id,rank1,rank3\nv1,A,B\n", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the rank columns of {} have a gap: the rank 2 is missing before \"rank3\"",
+                dir.join("ballots.csv").display()
+            )
+        );
+        let err = read("id,rank1,rank2,Rank2\nv1,A,B,C\n", None).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("have the rank 2 twice: \"rank2\" and \"Rank2\""),
+            "{}",
+            err
+        );
+        let err = read("id,choice1\nv1,A\n", None).unwrap_err();
+        assert!(matches!(*err, RcvError::RankHeaders { .. }), "{}", err);
     }
 
     #[test]
-    #[ignore = "TODO P2 provider cdf"]
-    fn unisyn_xml_cdf_city_coroner() {
-        test_wrapper("unisyn_xml_cdf_city_coroner");
+    fn aggregate_below_outputs() {
+        let dir = std::env::temp_dir().join("timrcv_aggregate_below");
+        fs::create_dir_all(&dir).unwrap();
+        // The write-ins X and Y have one vote each.
+        fs::write(
+            dir.join("ballots.csv"),
+            "v1,3,A,B\nv2,2,B,A\nv3,1,X,B\nv4,1,Y,\n",
+        )
+        .unwrap();
+        let config_path = dir.join("config.json").display().to_string();
+        let out_path = dir.join("summary.json").display().to_string();
+        let full_path = dir.join("full_summary.json").display().to_string();
+        let flat_path = dir.join("rounds.csv").display().to_string();
+        let run = |below: Option<u64>, reference: Option<String>, out: &str| -> JSValue {
+            let config = json!({
+                "outputSettings": {
+                    "contestName": "aggregate",
+                    "summarySchema": "v2",
+                    "aggregateBelow": below,
+                },
+                "cvrFileSources": [{
+                    "filePath": "ballots.csv",
+                    "provider": "csv",
+                    "idColumnIndex": "1",
+                    "countColumnIndex": "2",
+                    "firstVoteColumnIndex": "3",
+                }],
+                "candidates": [{"name": "A"}, {"name": "B"}, {"name": "X"}, {"name": "Y"}],
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "exhaustImmediately",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "1",
+                    "maxRankingsAllowed": "8",
+                },
+            });
+            fs::write(&config_path, config.to_string()).unwrap();
+            let args = Args::parse_from(["timrcv", "--out-flat-csv", flat_path.as_str()]);
+            run_election_at(
+                Some(config_path.clone()),
+                reference,
+                None,
+                Some(out.to_string()),
+                true,
+                Some(args),
+            )
+            .unwrap();
+            serde_json::from_str(&fs::read_to_string(out).unwrap()).unwrap()
+        };
+        let full = run(None, None, &full_path);
+        assert_eq!(
+            full["results"][0]["tally"],
+            json!({"A": "3", "B": "2", "X": "1", "Y": "1"})
+        );
+        // The reference is compared with the full results.
+        let summary = run(Some(2), Some(full_path.clone()), &out_path);
+        let other = "Other (2 names)";
+        assert_eq!(
+            summary["results"][0]["tally"],
+            json!({"A": "3", "B": "2", other: "2"})
+        );
+        assert_eq!(
+            summary["results"][1]["tallyResults"],
+            json!([{"eliminated": other, "transfers": {"B": "1"}}])
+        );
+        assert_eq!(
+            summary["firstChoicePercent"],
+            json!({"A": "42.9", "B": "28.6", other: "28.6"})
+        );
+        assert_eq!(
+            summary["eliminationOrder"][0],
+            json!({"name": other, "round": 1, "votes": "1", "sharePercent": "14.3", "elected": false})
+        );
+        assert_eq!(summary["status"], json!("winnerDeclared"));
+        let flat = fs::read_to_string(&flat_path).unwrap();
+        assert!(
+            flat.contains("1,Other (2 names),2,eliminated,exhausted,1\n"),
+            "{}",
+            flat
+        );
+        assert!(!flat.contains(",X,") && !flat.contains(",Y,"), "{}", flat);
+        // With a higher limit, all the candidates are merged except the winner.
+        let summary = run(Some(10), None, &out_path);
+        assert_eq!(
+            summary["results"][0]["tally"],
+            json!({"A": "3", "Other (3 names)": "4"})
+        );
     }
 
     #[test]
-    #[ignore = "TODO P2 provider cdf"]
-    fn unisyn_xml_cdf_city_council_member() {
-        test_wrapper("unisyn_xml_cdf_city_council_member");
+    fn undeclared_write_in_display_name() {
+        let dir = std::env::temp_dir().join("timrcv_uwi_display_name");
+        fs::create_dir_all(&dir).unwrap();
+        // Z is an undeclared write-in.
+        fs::write(
+            dir.join("ballots.csv"),
+            "v1,3,A,B
+v2,2,B,A
+v3,1,Z,B
+",
+        )
+        .unwrap();
+        let config_path = dir.join("config.json").display().to_string();
+        let out_path = dir.join("summary.json").display().to_string();
+        let english_path = dir.join("english_summary.json").display().to_string();
+        let flat_path = dir.join("rounds.csv").display().to_string();
+        let run = |name: Option<&str>, reference: Option<String>, out: &str| {
+            let config = json!({
+                "outputSettings": {
+                    "contestName": "uwi",
+                    "summarySchema": "v2",
+                    "undeclaredWriteInDisplayName": name,
+                },
+                "cvrFileSources": [{
+                    "filePath": "ballots.csv",
+                    "provider": "csv",
+                    "idColumnIndex": "1",
+                    "countColumnIndex": "2",
+                    "firstVoteColumnIndex": "3",
+                }],
+                "candidates": [{"name": "A"}, {"name": "B"}],
+                "rules": {
+                    "tiebreakMode": "useCandidateOrder",
+                    "overvoteRule": "exhaustImmediately",
+                    "winnerElectionMode": "singleWinnerMajority",
+                    "numberOfWinners": "1",
+                    "maxSkippedRanksAllowed": "1",
+                    "maxRankingsAllowed": "8",
+                },
+            });
+            fs::write(&config_path, config.to_string()).unwrap();
+            let args = Args::parse_from(["timrcv", "--out-flat-csv", flat_path.as_str()]);
+            run_election_at(
+                Some(config_path.clone()),
+                reference,
+                None,
+                Some(out.to_string()),
+                true,
+                Some(args),
+            )
+            .map(|_| serde_json::from_str::<JSValue>(&fs::read_to_string(out).unwrap()).unwrap())
+        };
+        let english = run(None, None, &english_path).unwrap();
+        assert_eq!(
+            english["results"][0]["tally"],
+            json!({"A": "3", "B": "2", "Undeclared Write-ins": "1"})
+        );
+        // The English reference is compared with the full results.
+        let label = "Candidatos no declarados";
+        let summary = run(Some(label), Some(english_path.clone()), &out_path).unwrap();
+        assert_eq!(
+            summary["results"][0]["tally"],
+            json!({"A": "3", "B": "2", label: "1"})
+        );
+        assert_eq!(
+            summary["results"][0]["tallyResults"],
+            json!([{"eliminated": label, "transfers": {"B": "1"}}])
+        );
+        let flat = fs::read_to_string(&flat_path).unwrap();
+        assert!(
+            flat.contains("1,Candidatos no declarados,1,eliminated,B,1\n"),
+            "{}",
+            flat
+        );
+        assert!(!flat.contains("Undeclared"), "{}", flat);
+        // The name of a candidate would merge the write-ins with this candidate. The names are
+        // compared as the names of the ballots, and the configuration is refused before reading
+        // the ballots.
+        let _ = fs::remove_file(&out_path);
+        let err = run(Some(" B "), None, &out_path).unwrap_err();
+        assert!(
+            matches!(err, RcvError::OutputNameCollision { .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+        assert!(
+            err.to_string()
+                .contains("is the name of the candidate \"B\""),
+            "{}",
+            err
+        );
+        assert!(!std::path::Path::new(&out_path).exists());
+
+        // The label of the merged candidates only collides with aggregateBelow.
+        let candidates: Vec<RcvCandidate> = ["A", "Other (2 names)"]
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: None,
+                inferred: true,
+            })
+            .collect();
+        let settings = |uwi: Option<&str>, below: Option<u64>| -> OutputSettings {
+            serde_json::from_value(json!({
+                "contestName": "uwi",
+                "undeclaredWriteInDisplayName": uwi,
+                "aggregateBelow": below,
+            }))
+            .unwrap()
+        };
+        assert!(
+            aggregate::check_output_names(&settings(Some("Other (3 names)"), None), &[]).is_ok()
+        );
+        let err = aggregate::check_output_names(&settings(Some("other (3 names)"), Some(10)), &[])
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("label of the candidates merged"),
+            "{}",
+            err
+        );
+        assert!(aggregate::check_output_names(&settings(None, None), &candidates).is_ok());
+        let err =
+            aggregate::check_output_names(&settings(None, Some(10)), &candidates).unwrap_err();
+        assert!(err.to_string().starts_with("aggregateBelow:"), "{}", err);
+        // The inferred candidates are checked as well.
+        let err =
+            aggregate::check_output_names(&settings(Some("A"), None), &candidates).unwrap_err();
+        assert!(
+            matches!(err, RcvError::OutputNameCollision { .. }),
+            "{:?}",
+            err
+        );
     }
 
     #[test]
-    #[ignore = "TODO P2 provider cdf"]
-    fn unisyn_xml_cdf_city_mayor() {
-        test_wrapper("unisyn_xml_cdf_city_mayor");
+    fn reference_strict() {
+        let dir = std::env::temp_dir().join("timrcv_reference_strict");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ballots.csv"), "A,B\nA,\nB,A\nC,B\nB,\n").unwrap();
+        let in_path = dir.join("ballots.csv").display().to_string();
+        let out_path = dir.join("summary.json").display().to_string();
+        let ref_path = dir.join("reference.json").display().to_string();
+        let run = |reference: Option<&JSValue>, strict: bool| -> RcvResult<SummaryStatus> {
+            if let Some(reference) = reference {
+                fs::write(&ref_path, reference.to_string()).unwrap();
+            }
+            let args = if strict {
+                Args::parse_from(["timrcv", "--reference-strict"])
+            } else {
+                Args::parse_from(["timrcv"])
+            };
+            run_election_at(
+                None,
+                reference.map(|_| ref_path.clone()),
+                Some(in_path.clone()),
+                Some(out_path.clone()),
+                true,
+                Some(args),
+            )
+        };
+        run(None, false).unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        assert_eq!(summary["config"]["threshold"], json!("3"));
+        run(Some(&summary), true).unwrap();
+
+        // The normal mode already fails on a different threshold: the whole summary is compared,
+        // including the config section with the threshold, and not only the results (see
+        // summary_for_comparison). The strict mode only adds a message for each difference.
+        let mut wrong_threshold = summary.clone();
+        wrong_threshold["config"]["threshold"] = json!("4");
+        let err = run(Some(&wrong_threshold), false).unwrap_err();
+        assert!(matches!(err, RcvError::ReferenceMismatch {}), "{}", err);
+        let err = run(Some(&wrong_threshold), true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the summary differs from the reference summary: threshold \"3\" instead of \"4\""
+        );
+
+        // Each difference is reported.
+        let mut wrong_rounds = summary.clone();
+        let rounds = wrong_rounds["results"].as_array_mut().unwrap();
+        let num_rounds = rounds.len();
+        rounds.pop();
+        let err = run(Some(&wrong_rounds), true).unwrap_err();
+        match err {
+            RcvError::ReferenceStrictMismatch { mismatches } => assert_eq!(
+                mismatches,
+                vec![
+                    "winners {\"B\"} instead of {}".to_string(),
+                    format!("{} rounds instead of {}", num_rounds, num_rounds - 1),
+                ]
+            ),
+            e => panic!("{}", e),
+        }
     }
 
     #[test]
-    #[ignore = "TODO P2 provider cdf"]
-    fn unisyn_xml_cdf_city_tax_collector() {
-        test_wrapper("unisyn_xml_cdf_city_tax_collector");
+    fn near_miss_party_labels() {
+        use crate::rcv::sanity::{self, NearMiss};
+        assert_eq!(
+            sanity::near_miss("Jane Doe (DEM)", "Jane Doe (GOP)"),
+            Some(NearMiss::PartyLabel)
+        );
+        assert_eq!(
+            sanity::near_miss("Jane Doe (DM)", "Jane Doe (DEM)"),
+            Some(NearMiss::Typo)
+        );
+        assert_eq!(
+            sanity::near_miss("Jon Smith", "John Smith"),
+            Some(NearMiss::Typo)
+        );
+        assert_eq!(sanity::near_miss("A", "B"), None);
+
+        let dir = std::env::temp_dir().join("timrcv_near_miss");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("ballots.csv"),
+            "Jane Doe (DEM),John Roe\nJane Doe (GOP),John Roe\nJane Doe (GOP),\nJane Doe (DM),\n",
+        )
+        .unwrap();
+        let config_path = dir.join("config.json").display().to_string();
+        let out_path = dir.join("summary.json").display().to_string();
+        let config = json!({
+            "outputSettings": {"contestName": "fusion", "summarySchema": "v2"},
+            "cvrFileSources": [{"filePath": "ballots.csv", "provider": "csv"}],
+            "candidates": [
+                {"name": "Jane Doe (DEM)"},
+                {"name": "Jane Doe (GOP)"},
+                {"name": "John Roe"},
+            ],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        fs::write(&config_path, config.to_string()).unwrap();
+        run_election_at(
+            Some(config_path),
+            None,
+            None,
+            Some(out_path.clone()),
+            true,
+            None,
+        )
+        .unwrap();
+        let summary: JSValue =
+            serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap();
+        // The declared party lines are not a warning, the typo is.
+        let warnings = summary["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0]["code"], json!("W013"));
+        let message = warnings[0]["message"].as_str().unwrap();
+        assert!(
+            message.ends_with("'Jane Doe (DM)' (1 times) is close to 'Jane Doe (DEM)'"),
+            "{}",
+            message
+        );
+        // The party labels are kept: the two lines are distinct candidates.
+        assert_eq!(
+            summary["results"][0]["tally"],
+            json!({
+                "Jane Doe (DEM)": "1",
+                "Jane Doe (GOP)": "2",
+                "John Roe": "0",
+                "Undeclared Write-ins": "1",
+            })
+        );
+
+        let candidates: Vec<RcvCandidate> = [
+            "Jane Doe (DEM)",
+            "Jane Doe (GOP)",
+            "Jon Smith",
+            "John Smith",
+        ]
+        .iter()
+        .map(|n| RcvCandidate {
+            name: n.to_string(),
+            code: None,
+            excluded: None,
+            inferred: false,
+        })
+        .collect();
+        assert_eq!(
+            sanity::check_declared_near_misses(&candidates),
+            vec![
+                (
+                    "Jane Doe (DEM)".to_string(),
+                    "Jane Doe (GOP)".to_string(),
+                    NearMiss::PartyLabel
+                ),
+                (
+                    "Jon Smith".to_string(),
+                    "John Smith".to_string(),
+                    NearMiss::Typo
+                ),
+            ]
+        );
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].message.contains("'Jon Smith' and 'John Smith'"));
     }
 
     #[test]
-    #[ignore = "TODO P2 provider cdf"]
-    fn unisyn_xml_cdf_county_coroner() {
-        test_wrapper("unisyn_xml_cdf_county_coroner");
+    fn too_many_inferred_candidates() {
+        use crate::rcv::{sanity, WarningCode};
+
+        let dir = std::env::temp_dir().join("timrcv_too_many_candidates");
+        fs::create_dir_all(&dir).unwrap();
+        // The first column has comments, which are read as the first choices.
+        let in_path = dir.join("ballots.csv").display().to_string();
+        let rows: Vec<String> = (1..=30).map(|i| format!("comment {},A,B\n", i)).collect();
+        fs::write(&in_path, rows.concat()).unwrap();
+        let out_path = dir.join("summary.json").display().to_string();
+        let run = |flags: &[&str]| -> RcvResult<JSValue> {
+            let mut argv = vec!["timrcv", "--summary-schema", "v2"];
+            argv.extend(flags);
+            run_election_at(
+                None,
+                None,
+                Some(in_path.clone()),
+                Some(out_path.clone()),
+                true,
+                Some(Args::parse_from(argv)),
+            )?;
+            Ok(serde_json::from_str(&fs::read_to_string(&out_path).unwrap()).unwrap())
+        };
+
+        let summary = run(&[]).unwrap();
+        let warnings = summary["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0]["code"], json!("W014"));
+        let message = warnings[0]["message"].as_str().unwrap();
+        assert!(
+            message.starts_with("32 candidates were inferred from the 30 ballots"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("'A' (30), 'B' (30), 'comment 1' (1), 'comment 10' (1)"),
+            "{}",
+            message
+        );
+
+        let err = run(&["--strict"]).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                RcvError::TooManyCandidates {
+                    num_candidates: 32,
+                    votes: 30,
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+
+        // Without the comments, there are only two candidates, unless fewer are allowed.
+        fs::write(&in_path, "A,B\n".repeat(30)).unwrap();
+        let summary = run(&["--strict"]).unwrap();
+        assert!(summary.get("warnings").is_none(), "{:?}", summary);
+
+        let cfs: FileSource = serde_json::from_value(json!({
+            "provider": "csv",
+            "filePath": "ballots.csv",
+            "maxInferredCandidates": 1,
+        }))
+        .unwrap();
+        let ballots = io_csv::read_csv_ranking(in_path.clone(), &cfs).unwrap();
+        let candidates: Vec<RcvCandidate> = ["A", "B"]
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: Some(false),
+                inferred: false,
+            })
+            .collect();
+        codes::take_warnings();
+        sanity::check_inferred_candidates(&in_path, &ballots, &candidates, &cfs).unwrap();
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0].code, WarningCode::TooManyCandidates);
     }
 
     #[test]
-    #[ignore = "TODO P2 provider cdf"]
-    fn unisyn_xml_cdf_county_sheriff() {
-        test_wrapper("unisyn_xml_cdf_county_sheriff");
+    fn near_miss_compares_nfc_names() {
+        use crate::rcv::sanity::{self, NearMiss};
+        let cfs: FileSource =
+            serde_json::from_value(json!({"provider": "csv", "filePath": "ballots.csv"})).unwrap();
+        let ballot = |name: &str| ParsedBallot {
+            id: None,
+            count: None,
+            choices: vec![vec![name.to_string()]],
+            challenged: false,
+            category: None,
+            respondent: None,
+            precinct: None,
+            style: None,
+        };
+        let candidates: Vec<RcvCandidate> = ["Jos\u{e9} Garcia", "Ann Lee"]
+            .iter()
+            .map(|n| RcvCandidate {
+                name: n.to_string(),
+                code: None,
+                excluded: None,
+                inferred: false,
+            })
+            .collect();
+        // The name with a combining accent is the declared candidate, not a typo of it.
+        let ballots = vec![ballot("Jose\u{301} Garcia"), ballot("Ann Lee")];
+        codes::take_warnings();
+        assert_eq!(
+            sanity::check_undeclared_near_misses("ballots.csv", &ballots, &candidates, &cfs),
+            vec![]
+        );
+        assert!(codes::take_warnings().is_empty());
+        let ballots = vec![ballot("Jose\u{301} Garsia")];
+        assert_eq!(
+            sanity::check_undeclared_near_misses("ballots.csv", &ballots, &candidates, &cfs),
+            vec![(
+                "Jos\u{e9} Garsia".to_string(),
+                "Jos\u{e9} Garcia".to_string(),
+                NearMiss::Typo
+            )]
+        );
+        codes::take_warnings();
     }
 
     #[test]
-    fn uwi_cannot_win_test() {
-        test_wrapper("uwi_cannot_win_test");
+    fn debug_sample_rate() {
+        let parsed: Vec<ParsedBallot> = (0..1000)
+            .map(|idx| ParsedBallot {
+                // The ballots without id are chosen by their position.
+                id: Some(format!("id{}", idx)).filter(|_| idx % 2 == 0),
+                count: None,
+                choices: vec![vec!["A".to_string()]],
+                challenged: false,
+                category: None,
+                respondent: None,
+                precinct: None,
+                style: None,
+            })
+            .collect();
+        let sampled = |rate_o: Option<&str>| -> Vec<usize> {
+            let mut argv = vec!["timrcv"];
+            argv.extend(
+                rate_o
+                    .map(|rate| ["--debug-sample-rate", rate])
+                    .iter()
+                    .flatten(),
+            );
+            let args = Args::parse_from(argv);
+            let input = Some("./tests/csv_simple_1/example.csv".to_string());
+            let config = resolve_config(&None, &input, &Some(args)).unwrap();
+            let cfs = &config.cvr_file_sources[0];
+            (0..parsed.len())
+                .filter(|idx| debug_sampled(cfs, &parsed[*idx], *idx))
+                .collect()
+        };
+        // By default, all the ballots are logged.
+        assert_eq!(sampled(None).len(), 1000);
+        // Two runs log the same ballots.
+        let first = sampled(Some("0.05"));
+        assert_eq!(first, sampled(Some("0.05")));
+        assert!(first.len() > 20 && first.len() < 80, "{}", first.len());
+        assert!(sampled(Some("0")).is_empty());
     }
 
-    // ********** Tests specific to timrcv *************
+    // The debug lines of the ballots whose id starts with "dsr-", logged while the tests run.
+    static SAMPLE_LINES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
 
-    #[test]
-    fn msforms_1() {
-        test_wrapper_local("msforms_1");
-    }
+    struct SampleLogger;
 
-    #[test]
-    fn msforms_likert() {
-        test_wrapper_local("msforms_likert");
+    impl log::Log for SampleLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Debug
+        }
+
+        fn log(&self, record: &log::Record) {
+            let line = record.args().to_string();
+            if line.contains("ballot dsr-") {
+                SAMPLE_LINES.lock().unwrap().push(line);
+            }
+        }
+
+        fn flush(&self) {}
     }
 
     #[test]
-    fn msforms_likert_transpose() {
-        test_wrapper_local("msforms_likert_transpose");
+    fn debug_sample_rate_logs_both_stages() {
+        static LOGGER: SampleLogger = SampleLogger;
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let dir = std::env::temp_dir().join("timrcv_debug_sample_rate");
+        fs::create_dir_all(&dir).unwrap();
+        let in_path = dir.join("ballots.csv");
+        let rows: Vec<String> = (0..200)
+            .map(|idx| format!("dsr-{},{}", idx, ["A", "B", "C"][idx % 3]))
+            .collect();
+        fs::write(&in_path, rows.join("\n")).unwrap();
+        let config = json!({
+            "outputSettings": {"contestName": "sample"},
+            "cvrFileSources": [{"filePath": "ballots.csv", "provider": "csv",
+                                "idColumnIndex": 1, "firstVoteColumnIndex": 2}],
+            "candidates": [{"name": "A"}, {"name": "B"}, {"name": "C"}],
+            "rules": {
+                "tiebreakMode": "useCandidateOrder",
+                "overvoteRule": "exhaustImmediately",
+                "winnerElectionMode": "singleWinnerMajority",
+                "numberOfWinners": "1",
+                "maxSkippedRanksAllowed": "1",
+                "maxRankingsAllowed": "8",
+            },
+        });
+        let config_path = dir.join("config.json");
+        fs::write(&config_path, config.to_string()).unwrap();
+        let run = |rate: &str| -> Vec<String> {
+            SAMPLE_LINES.lock().unwrap().clear();
+            let args = Args::parse_from(["timrcv", "--debug-sample-rate", rate]);
+            run_election_at(
+                Some(config_path.display().to_string()),
+                None,
+                None,
+                Some(dir.join("summary.json").display().to_string()),
+                true,
+                Some(args),
+            )
+            .unwrap();
+            SAMPLE_LINES.lock().unwrap().clone()
+        };
+        // The ballots logged by the validation and by the first round.
+        let ids = |lines: &[String], prefix: &str| -> std::collections::BTreeSet<String> {
+            lines
+                .iter()
+                .filter_map(|l| l.strip_prefix(prefix))
+                .map(|l| l.split([':', ' ']).next().unwrap().to_string())
+                .collect()
+        };
+
+        let lines = run("0.1");
+        let validated = ids(&lines, "validate_ballots: ballot ");
+        let checked = ids(&lines, "checks: ballot ");
+        assert!(
+            validated.len() > 5 && validated.len() < 50,
+            "{:?}",
+            validated
+        );
+        assert_eq!(validated, checked);
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with("checks: ballot dsr-") && l.ends_with("counts for A")));
+
+        assert!(run("0").is_empty());
+        log::set_max_level(log::LevelFilter::Off);
     }
 
     #[test]
-    fn csv_simple_1() {
-        test_wrapper_local("csv_simple_1");
+    fn csv_identical_ballots() {
+        // The three submissions of bob@example.org count once: A wins instead of B.
+        test_wrapper_local("csv_identical_ballots");
+        let config_path = "./tests/csv_identical_ballots/csv_identical_ballots_config.json";
+        let config = resolve_config(&Some(config_path.to_string()), &None, &None).unwrap();
+        let read = |extra: JSValue| {
+            let mut js = serde_json::to_value(&config.cvr_file_sources[0]).unwrap();
+            js.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            let cfs: FileSource = serde_json::from_value(js).unwrap();
+            codes::take_warnings();
+            let (_, _, total) = read_ranking_data(
+                "./tests/csv_identical_ballots".to_string(),
+                &cfs,
+                Some(&config.candidates),
+                &config.rules,
+                None,
+                &mut CategoryTotals::new(),
+                None,
+            )
+            .unwrap();
+            (total.parsed, codes::take_warnings())
+        };
+
+        // Off by default: all the submissions are counted.
+        let (parsed, warnings) = read(json!({"deduplicateIdenticalBallots": null}));
+        assert_eq!(parsed, 7);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        let (parsed, warnings) = read(json!({}));
+        assert_eq!(parsed, 5);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0].code, codes::WarningCode::IdenticalBallots);
+        assert_eq!(
+            warnings[0].message,
+            "2 identical ballots of example.csv were dropped (deduplicateIdenticalBallots), \
+             the most submitted: B > A from \"bob@example.org\" (3 submissions)"
+        );
+
+        // Without the respondent, the ballot of dan@example.org is also a duplicate.
+        let (parsed, warnings) = read(json!({"respondentColumn": null}));
+        assert_eq!(parsed, 4);
+        assert!(
+            warnings[0]
+                .message
+                .ends_with("the most submitted: B > A (4 submissions)"),
+            "{}",
+            warnings[0].message
+        );
     }
 
     #[test]
-    fn csv_simple_2() {
-        test_wrapper_local("csv_simple_2");
+    fn identical_ballots_with_counts() {
+        let ballot = |count: u64, challenged: bool| ParsedBallot {
+            id: None,
+            count: Some(count),
+            choices: vec![vec!["A".to_string()], vec!["B".to_string()]],
+            challenged,
+            category: None,
+            respondent: None,
+            precinct: None,
+            style: None,
+        };
+        // The rows with other counts or another challenged flag are not duplicates: their
+        // votes are all kept.
+        let parsed = vec![
+            ballot(3, false),
+            ballot(2, false),
+            ballot(3, true),
+            ballot(3, false),
+        ];
+        codes::take_warnings();
+        let kept = dedup::deduplicate("counts.csv", parsed.clone());
+        assert_eq!(kept, parsed[..3].to_vec());
+        assert_eq!(codes::take_warnings().len(), 1);
     }
 
     #[test]
-    fn csv_simple_likert() {
-        test_wrapper_local("csv_simple_likert");
+    fn csv_time_window() {
+        use crate::rcv::time_window::{excel_timestamp, format_timestamp, parse_timestamp};
+        use crate::rcv::WarningCode;
+        use calamine::DataType;
+
+        // The deadline is 2024-11-05T20:00:00-05:00: the ballots submitted at the deadline or
+        // later are dropped, whatever the offset of their timestamp.
+        test_wrapper_local("csv_time_window");
+        let source = |extra: JSValue| {
+            let mut js = json!({
+                "firstVoteRowIndex": "2",
+                "idColumnIndex": "1",
+                "firstVoteColumnIndex": "3",
+                "timestampColumn": "submitted",
+            });
+            js.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            csv_source(None, js)
+        };
+        let ids = |ballots: &[ParsedBallot]| -> Vec<String> {
+            ballots.iter().map(|pb| pb.id.clone().unwrap()).collect()
+        };
+        let path = "./tests/csv_time_window/example.csv".to_string();
+        codes::take_warnings();
+        let cfs = source(json!({"acceptUntil": "2024-11-05T20:00:00-05:00"}));
+        let ballots = io_csv::read_csv_ranking(path.clone(), &cfs).unwrap();
+        assert_eq!(ids(&ballots), vec!["v1", "v2", "v3", "v4", "v8"]);
+        let warnings = codes::take_warnings();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0].code, WarningCode::OutsideTimeWindow);
+        assert!(
+            warnings[0].message.ends_with(
+                "3 ballots were submitted outside of the window until 2024-11-06T01:00:00Z \
+                 (excluded) and are dropped (the earliest at 2024-11-06T01:00:00Z, the latest \
+                 at 2024-11-06T13:15:00Z)"
+            ),
+            "{}",
+            warnings[0].message
+        );
+
+        // The start of the window is included, and the column may be given by its index.
+        let cfs = source(json!({
+            "timestampColumn": "B",
+            "acceptFrom": "2024-11-05T14:30:00Z",
+            "acceptUntil": "2024-11-06T01:00:00Z",
+        }));
+        let ballots = io_csv::read_csv_ranking(path.clone(), &cfs).unwrap();
+        assert_eq!(ids(&ballots), vec!["v2", "v3", "v4"]);
+        codes::take_warnings();
+
+        // The same filter for the likert layout.
+        let cfs = source(json!({
+            "layout": "ranks-in-cells",
+            "acceptFrom": "2024-11-05",
+        }));
+        let dir = std::env::temp_dir().join("timrcv_time_window");
+        fs::create_dir_all(&dir).unwrap();
+        let likert_path = dir.join("likert.csv").display().to_string();
+        fs::write(
+            &likert_path,
+            "id,submitted,A,B\nv1,2024-11-04T23:59:59Z,1,2\nv2,2024-11-05T00:00:00Z,2,1\n",
+        )
+        .unwrap();
+        let names = vec!["A".to_string(), "B".to_string()];
+        let ballots = io_csv::read_csv_likert(likert_path, &cfs, &names).unwrap();
+        assert_eq!(ids(&ballots), vec!["v2"]);
+        codes::take_warnings();
+
+        // A few timestamps that cannot be read are kept, too many are an error.
+        let bad_path = dir.join("bad.csv").display().to_string();
+        let mut contents = "id,submitted,rank1\n".to_string();
+        for i in 0..20 {
+            contents.push_str(&format!("v{},2024-11-05T10:{:02}:00Z,A\n", i, i));
+        }
+        contents.push_str("v20,yesterday,B\n");
+        fs::write(&bad_path, &contents).unwrap();
+        let cfs = source(json!({"acceptUntil": "2024-11-05T10:10:00Z"}));
+        let ballots = io_csv::read_csv_ranking(bad_path.clone(), &cfs).unwrap();
+        assert_eq!(ballots.len(), 11);
+        let warnings = codes::take_warnings();
+        assert!(
+            warnings[0]
+                .message
+                .ends_with("; 1 ballots have a timestamp that cannot be read and are kept"),
+            "{}",
+            warnings[0].message
+        );
+        contents.push_str("v21,,B\n");
+        fs::write(&bad_path, &contents).unwrap();
+        let err = io_csv::read_csv_ranking(bad_path, &cfs).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "2 of the 22 timestamps of {} cannot be read, such as \"yesterday\" at row 22. \
+                 Check timestampColumn",
+                dir.join("bad.csv").display()
+            )
+        );
+        assert_eq!(err.code(), codes::ErrorCode::MalformedBallots);
+
+        // The window must be consistent.
+        for (extra, reason) in [
+            (
+                json!({}),
+                "timestampColumn requires acceptFrom or acceptUntil",
+            ),
+            (
+                json!({"acceptFrom": "2024-11-06", "acceptUntil": "2024-11-05"}),
+                "acceptFrom (2024-11-06T00:00:00Z) must be before acceptUntil \
+                 (2024-11-05T00:00:00Z)",
+            ),
+            (
+                json!({"acceptUntil": "05/11/2024"}),
+                "acceptUntil \"05/11/2024\" is not a date or a time in the RFC 3339 format",
+            ),
+        ] {
+            let err = io_csv::read_csv_ranking(path.clone(), &source(extra)).unwrap_err();
+            assert_eq!(err.to_string(), format!("invalid time window: {}", reason));
+            assert_eq!(err.code(), codes::ErrorCode::InvalidConfiguration);
+        }
+
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("1970-01-01t01:00+01:00"), Some(0));
+        assert_eq!(parse_timestamp("1970-01-01 00:00:01.750"), Some(1));
+        assert_eq!(parse_timestamp("2024-02-29"), Some(1709164800));
+        assert_eq!(parse_timestamp("2023-02-29"), None);
+        assert_eq!(parse_timestamp("2024-11-05T25:00:00Z"), None);
+        assert_eq!(format_timestamp(-1), "1969-12-31T23:59:59Z");
+        // Excel counts the days since 1899-12-30.
+        assert_eq!(
+            excel_timestamp(&DataType::DateTime(45601.5)),
+            parse_timestamp("2024-11-05T12:00:00Z")
+        );
+        assert_eq!(
+            excel_timestamp(&DataType::String("2024-11-05T12:00:00Z".to_string())),
+            parse_timestamp("2024-11-05T12:00:00Z")
+        );
+        assert_eq!(excel_timestamp(&DataType::Empty), None);
     }
 }
@@ -0,0 +1,259 @@
+// The aggregation of the small candidates in the published outputs (outputSettings.aggregateBelow).
+//
+// Some privacy rules require the candidates with few votes (typically the write-ins) to be
+// reported together. The candidates whose final tally is below the limit are merged into a
+// single "Other" entry in a copy of the result, which is used for the outputs. The tabulation
+// and the comparison with a reference summary use the full result.
+//
+// The undeclared write-ins are renamed the same way in the published outputs
+// (outputSettings.undeclaredWriteInDisplayName), for example for the outputs in Spanish.
+
+use crate::rcv::*;
+
+// The name of the merged entry, with the number of merged names.
+fn other_label(num_names: usize) -> String {
+    if num_names == 1 {
+        "Other (1 name)".to_string()
+    } else {
+        format!("Other ({} names)", num_names)
+    }
+}
+
+/// The candidates to merge: the candidates who are not winners and whose tally in the last
+/// round in which they appear is below `below`, in the order of their names.
+pub fn merged_candidates(result: &VotingResult, below: u64) -> BTreeSet<String> {
+    let winners: &[String] = result.winners.as_deref().unwrap_or_default();
+    let mut final_tally: HashMap<&str, u64> = HashMap::new();
+    for rs in result.round_stats.iter() {
+        for (name, count) in rs.tally.iter() {
+            final_tally.insert(name, *count);
+        }
+    }
+    final_tally
+        .into_iter()
+        .filter(|(name, count)| *count < below && !winners.iter().any(|w| w == name))
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+// Sums the counts by name once the merged names are replaced by the label, in the order of the
+// first occurrence of each name.
+fn merge_counts(
+    counts: &[(String, u64)],
+    merged: &BTreeSet<String>,
+    label: &str,
+) -> Vec<(String, u64)> {
+    let mut res: Vec<(String, u64)> = Vec::new();
+    for (name, count) in counts.iter() {
+        let name = if merged.contains(name) { label } else { name };
+        match res.iter_mut().find(|(n, _)| n == name) {
+            Some((_, total)) => *total += count,
+            None => res.push((name.to_string(), *count)),
+        }
+    }
+    res
+}
+
+fn merge_round(rs: &RoundStats, merged: &BTreeSet<String>, label: &str) -> RoundStats {
+    let mut eliminated: Vec<EliminationStats> = Vec::new();
+    for es in rs.tally_result_eliminated.iter() {
+        let transfers = merge_counts(&es.transfers, merged, label);
+        if !merged.contains(&es.name) {
+            eliminated.push(EliminationStats {
+                name: es.name.clone(),
+                transfers,
+                exhausted: es.exhausted,
+            });
+            continue;
+        }
+        // The merged candidates eliminated in the same round are a single elimination.
+        match eliminated.iter_mut().find(|e| e.name == label) {
+            Some(other) => {
+                let mut all_transfers = other.transfers.clone();
+                all_transfers.extend(transfers);
+                other.transfers = merge_counts(&all_transfers, merged, label);
+                other.exhausted += es.exhausted;
+            }
+            None => eliminated.push(EliminationStats {
+                name: label.to_string(),
+                transfers,
+                exhausted: es.exhausted,
+            }),
+        }
+    }
+    for es in eliminated.iter_mut() {
+        es.sort_transfers();
+    }
+    let mut res = rs.clone();
+    res.tally = merge_counts(&rs.tally, merged, label);
+    res.tally_result_eliminated = eliminated;
+    res
+}
+
+// The rank origins of the merged candidates are summed by round and by rank, in the order of the
+// first occurrence of each name in the round.
+fn merge_rank_origin(
+    origins: &[RankOrigin],
+    merged: &BTreeSet<String>,
+    label: &str,
+) -> Vec<RankOrigin> {
+    let mut res: Vec<RankOrigin> = Vec::new();
+    for o in origins.iter() {
+        if !merged.contains(&o.candidate) {
+            res.push(o.clone());
+            continue;
+        }
+        match res
+            .iter_mut()
+            .find(|r| r.round == o.round && r.candidate == label)
+        {
+            Some(other) => {
+                let mut by_rank: BTreeMap<u32, u64> = other.votes_by_rank.iter().cloned().collect();
+                for (rank, count) in o.votes_by_rank.iter() {
+                    *by_rank.entry(*rank).or_insert(0) += count;
+                }
+                other.votes_by_rank = by_rank.into_iter().collect();
+            }
+            None => res.push(RankOrigin {
+                candidate: label.to_string(),
+                ..o.clone()
+            }),
+        }
+    }
+    res
+}
+
+/// The result with the candidates whose final tally is below `below` merged into an
+/// "Other (n names)" entry. The winners are never merged. The result is returned unchanged if
+/// there is no candidate to merge.
+pub fn aggregate_below(result: &VotingResult, below: u64) -> VotingResult {
+    let merged = merged_candidates(result, below);
+    if merged.is_empty() {
+        return result.clone();
+    }
+    let label = other_label(merged.len());
+    debug!("aggregate_below: merging {:?} into {}", merged, label);
+    // The merged candidates eliminated in the same round are one entry, with their votes
+    // together. The total of the round does not change.
+    let mut elimination_order: Vec<EliminationEntry> = Vec::new();
+    for e in result.elimination_order.iter() {
+        if !merged.contains(&e.name) {
+            elimination_order.push(e.clone());
+            continue;
+        }
+        let idx = elimination_order
+            .iter()
+            .position(|o| o.name == label && o.round == e.round);
+        let votes = idx.map(|i| elimination_order[i].votes).unwrap_or(0) + e.votes;
+        let total: u64 = result
+            .round_stats
+            .iter()
+            .find(|rs| rs.round == e.round)
+            .map(|rs| rs.tally.iter().map(|(_, count)| count).sum())
+            .unwrap_or(0);
+        let entry = EliminationEntry {
+            name: label.clone(),
+            votes,
+            share_percent: PercentRounding::default().format(votes, total, 1),
+            ..e.clone()
+        };
+        match idx {
+            Some(i) => elimination_order[i] = entry,
+            None => elimination_order.push(entry),
+        }
+    }
+    let mut candidates: Vec<CandidateInfo> = result
+        .candidates
+        .iter()
+        .filter(|c| !merged.contains(&c.name))
+        .cloned()
+        .collect();
+    candidates.push(CandidateInfo {
+        name: label.clone(),
+        code: None,
+        excluded: false,
+        inferred: false,
+    });
+    let mut res = result.clone();
+    res.round_stats = result
+        .round_stats
+        .iter()
+        .map(|rs| merge_round(rs, &merged, &label))
+        .collect();
+    res.elimination_order = elimination_order;
+    res.candidates = candidates;
+    res.rank_origin = result
+        .rank_origin
+        .as_ref()
+        .map(|origins| merge_rank_origin(origins, &merged, &label));
+    res
+}
+
+// Whether a name is a label of the merged entry, "Other (n names)", up to the normalization.
+fn is_other_label(name: &str) -> bool {
+    let name = CandidateName::new(name, NameNormalization::default())
+        .key()
+        .to_lowercase();
+    name.strip_prefix("other (")
+        .and_then(|s| {
+            s.strip_suffix(" names)")
+                .or_else(|| s.strip_suffix(" name)"))
+        })
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Checks that the names of the published outputs do not collide: the undeclared write-ins
+/// renamed by undeclaredWriteInDisplayName must not take the name of a candidate or the label
+/// of the candidates merged by aggregateBelow, and no candidate may have this label. The names
+/// are compared as when the ballots are matched with the candidates.
+pub fn check_output_names(settings: &OutputSettings, candidates: &[RcvCandidate]) -> RcvResult<()> {
+    let below = settings.aggregate_below.is_some();
+    if let Some(name) = settings.undeclared_write_in_display_name.as_deref() {
+        let key = CandidateName::new(name, NameNormalization::default());
+        if let Some(c) = candidates
+            .iter()
+            .find(|c| CandidateName::new(&c.name, NameNormalization::default()) == key)
+        {
+            return OutputNameCollisionSnafu {
+                setting: "undeclaredWriteInDisplayName",
+                name,
+                reason: format!("is the name of the candidate {:?}", c.name),
+            }
+            .fail();
+        }
+        ensure!(
+            !(below && is_other_label(name)),
+            OutputNameCollisionSnafu {
+                setting: "undeclaredWriteInDisplayName",
+                name,
+                reason: "is the label of the candidates merged by aggregateBelow",
+            }
+        );
+    }
+    if let Some(c) = candidates.iter().find(|c| below && is_other_label(&c.name)) {
+        return OutputNameCollisionSnafu {
+            setting: "aggregateBelow",
+            name: &c.name,
+            reason: "is the name of a candidate and the label of the merged candidates",
+        }
+        .fail();
+    }
+    Ok(())
+}
+
+/// The result with the undeclared write-ins under another name. The name must not be the name
+/// of a candidate (see check_output_names).
+pub fn rename_undeclared(result: &VotingResult, name: &str) -> VotingResult {
+    let merged: BTreeSet<String> = [UNDECLARED_WRITE_INS.to_string()].into_iter().collect();
+    let mut res = result.clone();
+    res.round_stats = result
+        .round_stats
+        .iter()
+        .map(|rs| merge_round(rs, &merged, name))
+        .collect();
+    res.rank_origin = result
+        .rank_origin
+        .as_ref()
+        .map(|origins| merge_rank_origin(origins, &merged, name));
+    res
+}
@@ -0,0 +1,185 @@
+// Analyses of the outcome of a tabulation (--analysis).
+//
+// The head-to-head analysis compares each eliminated candidate with each winner, using the
+// rankings of the validated ballots. A candidate who beats a winner in this pairwise count was
+// squeezed out by the elimination order rather than rejected by the voters.
+
+use crate::rcv::*;
+
+/// The analyses that can be requested with --analysis.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Analysis {
+    /// Each eliminated candidate against each winner.
+    HeadToHeadWinner,
+}
+
+impl Analysis {
+    pub fn parse(name: &str) -> RcvResult<Analysis> {
+        match name {
+            "head-to-head-winner" => Ok(Analysis::HeadToHeadWinner),
+            x => whatever!("unknown analysis: {}", x),
+        }
+    }
+}
+
+/// The votes of two candidates in a pairwise count.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct PairwiseCount {
+    pub c1: String,
+    pub c2: String,
+    /// The votes of the ballots that prefer c1 to c2.
+    pub c1_votes: u64,
+    /// The votes of the ballots that prefer c2 to c1.
+    pub c2_votes: u64,
+}
+
+// The rank of the first choice of each candidate in a ballot. The overvotes and the other
+// choices do not rank any candidate.
+fn candidate_ranks(ballot: &Ballot) -> HashMap<&str, usize> {
+    let mut ranks: HashMap<&str, usize> = HashMap::new();
+    for (idx, choice) in ballot.candidates.iter().enumerate() {
+        if let BallotChoice::Candidate(name) = choice {
+            ranks.entry(name.as_str()).or_insert(idx);
+        }
+    }
+    ranks
+}
+
+/// Counts the votes of each pair of candidates. A ballot prefers the candidate that it ranks
+/// first, or the only one of the two that it ranks. The ballots that rank neither candidate are
+/// not counted.
+pub fn pairwise_counts(ballots: &[Ballot], pairs: &[(&str, &str)]) -> Vec<PairwiseCount> {
+    let mut counts: Vec<PairwiseCount> = pairs
+        .iter()
+        .map(|(c1, c2)| PairwiseCount {
+            c1: c1.to_string(),
+            c2: c2.to_string(),
+            c1_votes: 0,
+            c2_votes: 0,
+        })
+        .collect();
+    for ballot in ballots.iter() {
+        let ranks = candidate_ranks(ballot);
+        for (count, (c1, c2)) in counts.iter_mut().zip(pairs.iter()) {
+            match (ranks.get(c1), ranks.get(c2)) {
+                (Some(r1), Some(r2)) if r1 < r2 => count.c1_votes += ballot.count,
+                (Some(_), None) => count.c1_votes += ballot.count,
+                (Some(r1), Some(r2)) if r2 < r1 => count.c2_votes += ballot.count,
+                (None, Some(_)) => count.c2_votes += ballot.count,
+                _ => {}
+            }
+        }
+    }
+    counts
+}
+
+/// An eliminated candidate against a winner.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct HeadToHead {
+    pub candidate: String,
+    /// The round in which the candidate was eliminated.
+    pub eliminated_round: u32,
+    pub winner: String,
+    pub candidate_votes: u64,
+    pub winner_votes: u64,
+}
+
+impl HeadToHead {
+    pub fn beats_winner(&self) -> bool {
+        self.candidate_votes > self.winner_votes
+    }
+}
+
+// The eliminated candidates with the round of their elimination. The candidates that lose in
+// the round that elects the winners are eliminated in that round.
+fn eliminated_candidates(result: &VotingResult, winners: &[String]) -> Vec<(String, u32)> {
+    let mut eliminated: Vec<(String, u32)> = result
+        .elimination_order
+        .iter()
+        .filter(|e| !e.elected)
+        .map(|e| (e.name.clone(), e.round.0))
+        .collect();
+    if let Some(last) = result.round_stats.iter().rfind(|rs| !rs.informational) {
+        let mut losers: Vec<&(String, u64)> = last
+            .tally
+            .iter()
+            .filter(|(name, _)| !winners.contains(name))
+            .collect();
+        losers.sort_by(|(n1, c1), (n2, c2)| c1.cmp(c2).then_with(|| n1.cmp(n2)));
+        eliminated.extend(losers.iter().map(|(name, _)| (name.clone(), last.round.0)));
+    }
+    eliminated
+}
+
+/// Compares each eliminated candidate with each winner, in the order of the eliminations. The
+/// ballots should be the ones of the tabulation.
+pub fn head_to_head_winner(result: &VotingResult, ballots: &[Ballot]) -> Vec<HeadToHead> {
+    let winners: &[String] = result.winners.as_deref().unwrap_or_default();
+    if winners.is_empty() {
+        return vec![];
+    }
+    let eliminated = eliminated_candidates(result, winners);
+    let pairs: Vec<(&str, &str)> = eliminated
+        .iter()
+        .flat_map(|(c, _)| winners.iter().map(move |w| (c.as_str(), w.as_str())))
+        .collect();
+    let rounds = eliminated
+        .iter()
+        .flat_map(|(_, round)| winners.iter().map(move |_| *round));
+    pairwise_counts(ballots, &pairs)
+        .into_iter()
+        .zip(rounds)
+        .map(|(count, round)| HeadToHead {
+            candidate: count.c1,
+            eliminated_round: round,
+            winner: count.c2,
+            candidate_votes: count.c1_votes,
+            winner_votes: count.c2_votes,
+        })
+        .collect()
+}
+
+pub fn head_to_head_to_json(rows: &[HeadToHead]) -> JSValue {
+    let rows_js: Vec<JSValue> = rows
+        .iter()
+        .map(|h| {
+            json!({
+                "candidate": h.candidate,
+                "eliminatedRound": h.eliminated_round,
+                "winner": h.winner,
+                "candidateVotes": h.candidate_votes,
+                "winnerVotes": h.winner_votes,
+                "beatsWinner": h.beats_winner(),
+            })
+        })
+        .collect();
+    json!(rows_js)
+}
+
+pub fn head_to_head_to_text(rows: &[HeadToHead]) -> String {
+    let mut lines: Vec<String> = vec![format!(
+        "{:>6}  {:<20} {:<20} {:>10} {:>10}",
+        "round", "candidate", "winner", "candidate", "winner"
+    )];
+    for h in rows.iter() {
+        lines.push(format!(
+            "{:>6}  {:<20} {:<20} {:>10} {:>10}{}",
+            h.eliminated_round,
+            h.candidate,
+            h.winner,
+            h.candidate_votes,
+            h.winner_votes,
+            if h.beats_winner() {
+                "  beats the winner"
+            } else {
+                ""
+            }
+        ));
+    }
+    let num_beating = rows.iter().filter(|h| h.beats_winner()).count();
+    lines.push(format!(
+        "{} eliminated candidate(s) would beat a winner head-to-head",
+        num_beating
+    ));
+    lines.join("\n")
+}
@@ -0,0 +1,202 @@
+// The audit of the first choices against an independent export of the totals, usually from the
+// voting machines (--audit-first-choices).
+//
+// The audit file has two columns: the name of the candidate and its number of first choices. A
+// first row whose count is not a number is a header. The computed totals are the choices at the
+// first rank of the validated ballots, with their counts (and their weights), before the
+// tabulation: a ballot whose first rank is blank, an undervote or an overvote has no first
+// choice, and the undeclared write-ins are counted together under "Undeclared Write-ins",
+// before they are eliminated in the first round. A candidate that is missing from the audit
+// file has 0 votes in it.
+
+use crate::rcv::*;
+
+// The name of the undeclared write-ins in the totals, as in the rounds.
+const UWI_LABEL: &str = "Undeclared Write-ins";
+
+/// The first choices of a candidate, computed and in the audit file.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct AuditRow {
+    pub candidate: String,
+    pub computed: u64,
+    /// None if the candidate is not in the audit file.
+    pub expected: Option<u64>,
+}
+
+impl AuditRow {
+    /// The difference between the computed votes and the audit file.
+    pub fn delta(&self) -> i64 {
+        self.computed as i64 - self.expected.unwrap_or(0) as i64
+    }
+
+    fn describe(&self) -> String {
+        match self.expected {
+            Some(expected) => format!(
+                "{}: {} computed, {} in the audit file ({:+})",
+                self.candidate,
+                self.computed,
+                expected,
+                self.delta()
+            ),
+            None => format!(
+                "{}: {} computed, missing from the audit file",
+                self.candidate, self.computed
+            ),
+        }
+    }
+}
+
+/// Reads the totals of the audit file, in the order of the file.
+pub fn read_totals(path: &str) -> RcvResult<Vec<(String, u64)>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .context(AuditOpenSnafu { path })?;
+    let mut res: Vec<(String, u64)> = Vec::new();
+    for (idx, record) in reader.records().enumerate() {
+        let lineno = idx + 1;
+        let record = record.context(AuditOpenSnafu { path })?;
+        let cells: Vec<&str> = record.iter().map(|c| c.trim()).collect();
+        if cells.iter().all(|c| c.is_empty()) {
+            continue;
+        }
+        let (name, count) = match cells.as_slice() {
+            [name, count, ..] => (*name, *count),
+            _ => {
+                return AuditParseSnafu {
+                    path,
+                    lineno,
+                    reason: "expected two columns: candidate,count".to_string(),
+                }
+                .fail()
+            }
+        };
+        let count = match count.parse::<u64>() {
+            Ok(count) => count,
+            Err(_) if idx == 0 => continue,
+            Err(_) => {
+                return AuditParseSnafu {
+                    path,
+                    lineno,
+                    reason: format!("the count {:?} is not a number", count),
+                }
+                .fail()
+            }
+        };
+        let key = CandidateName::new(name, NameNormalization::default());
+        ensure!(
+            !res.iter()
+                .any(|(n, _)| CandidateName::new(n, NameNormalization::default()) == key),
+            AuditParseSnafu {
+                path,
+                lineno,
+                reason: format!("the candidate {:?} is listed twice", name),
+            }
+        );
+        res.push((key.as_str().to_string(), count));
+    }
+    Ok(res)
+}
+
+/// The first choices of the ballots, with all the declared candidates (in their order) even if
+/// they have no first choice, then the other names by name.
+pub fn first_choice_totals(
+    data: &[Ballot],
+    candidates_o: &Option<Vec<RcvCandidate>>,
+) -> Vec<(String, u64)> {
+    let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+    for ballot in data.iter() {
+        let name = match ballot.candidates.first() {
+            Some(BallotChoice::Candidate(name)) => name.as_str(),
+            Some(BallotChoice::UndeclaredWriteIn) => UWI_LABEL,
+            _ => continue,
+        };
+        *counts.entry(name.to_string()).or_insert(0) += ballot.count;
+    }
+    let mut res: Vec<(String, u64)> = Vec::new();
+    for c in candidates_o.iter().flatten() {
+        if c.excluded != Some(true) {
+            res.push((c.name.clone(), counts.remove(&c.name).unwrap_or(0)));
+        }
+    }
+    res.extend(counts);
+    res
+}
+
+/// Compares the computed first choices with the audit file. The names of the audit file match
+/// the candidates as in the ballots.
+pub fn compare(computed: &[(String, u64)], expected: &[(String, u64)]) -> Vec<AuditRow> {
+    let key = |name: &str| CandidateName::new(name, NameNormalization::default());
+    let mut rows: Vec<AuditRow> = computed
+        .iter()
+        .map(|(name, count)| AuditRow {
+            candidate: name.clone(),
+            computed: *count,
+            expected: expected
+                .iter()
+                .find(|(n, _)| key(n) == key(name))
+                .map(|(_, c)| *c),
+        })
+        .collect();
+    for (name, count) in expected.iter() {
+        if !computed.iter().any(|(n, _)| key(n) == key(name)) {
+            rows.push(AuditRow {
+                candidate: name.clone(),
+                computed: 0,
+                expected: Some(*count),
+            });
+        }
+    }
+    rows
+}
+
+pub fn audit_to_text(rows: &[AuditRow]) -> String {
+    let mut lines: Vec<String> = vec![format!(
+        "{:<30} {:>10} {:>10} {:>8}",
+        "candidate", "computed", "audit", "delta"
+    )];
+    for r in rows.iter() {
+        lines.push(format!(
+            "{:<30} {:>10} {:>10} {:>+8}",
+            r.candidate,
+            r.computed,
+            r.expected
+                .map(|e| e.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            r.delta()
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Checks the first choices of the ballots against the audit file: the differences are
+/// reported, and the audit fails if one of them is larger than the tolerance.
+pub fn check_first_choices(
+    path: &str,
+    data: &[Ballot],
+    candidates_o: &Option<Vec<RcvCandidate>>,
+    tolerance: u64,
+) -> RcvResult<Vec<AuditRow>> {
+    let expected = read_totals(path)?;
+    let rows = compare(&first_choice_totals(data, candidates_o), &expected);
+    info!(
+        "Audit of the first choices against {}:\n{}",
+        path,
+        audit_to_text(&rows)
+    );
+    let mismatches: Vec<String> = rows
+        .iter()
+        .filter(|r| r.delta().unsigned_abs() > tolerance)
+        .map(|r| r.describe())
+        .collect();
+    ensure!(
+        mismatches.is_empty(),
+        AuditMismatchSnafu {
+            path,
+            tolerance,
+            mismatches
+        }
+    );
+    Ok(rows)
+}
@@ -0,0 +1,492 @@
+// Stable codes for the warnings and the errors, so that scripts can check for specific
+// conditions instead of matching the messages.
+//
+// The codes are part of the interface: a code is never renumbered or reused for another
+// condition. New conditions get new codes at the end of the lists.
+
+use std::cell::RefCell;
+
+use crate::rcv::*;
+
+/// The conditions that are reported as warnings. The tabulation continues, unless the code is
+/// passed to `--fail-on`.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum WarningCode {
+    /// W001: a row of a CSV file has fewer columns than the columns of the configuration.
+    ShortRow,
+    /// W002: a choice contains the overvote delimiter but is not a list of candidates. It is
+    /// treated as a write-in.
+    UnsplittableOvervote,
+    /// W003: a voter category is not in the weights, and gets the default weight.
+    UnknownCategory,
+    /// W004: some columns of the FairVote row cannot be computed for this election.
+    FairVoteMissingColumns,
+    /// W005: some ballots had gaps in their ranks, which were renumbered (compressRanks).
+    CompressedRanks,
+    /// W006: a file source cannot be read and is skipped (--skip-bad-sources).
+    SkippedSource,
+    /// W007: a csv file seems to have the candidates in the header and the ranks in the cells.
+    RanksInCells,
+    /// W008: a spreadsheet has columns of ranks for candidates that are not declared. These
+    /// columns are ignored (unless --strict-columns is passed).
+    ExtraCandidateColumns,
+    /// W009: a file source is the summary that is written in the output directory, so that the
+    /// summary of a run is read as ballots by the next run.
+    SourceIsOutput,
+    /// W010: a rule of the configuration is overridden by --rule.
+    RuleOverride,
+    /// W011: a likert input has ranks outside of 1..=maxRank, which are ignored.
+    RankOutOfRange,
+    /// W012: a likert input has ballots that give the same rank to several candidates.
+    DuplicateRanks,
+    /// W013: a name is close to a declared candidate, or two declared candidates are close to
+    /// each other, which is likely a typo.
+    NearMissCandidate,
+    /// W014: suspiciously many candidates are inferred from a source without declared
+    /// candidates, which usually means that the choice columns are wrong.
+    TooManyCandidates,
+    /// W015: some ballots were submitted outside of the time window of their source
+    /// (acceptFrom, acceptUntil) and are dropped, or their timestamp cannot be read.
+    OutsideTimeWindow,
+    /// W016: a CSV file has empty rows, usually at the end of a spreadsheet export. They are
+    /// skipped.
+    EmptyRows,
+    /// W017: a likert transpose input has cells with several candidates for the same rank, as
+    /// with checkbox grids. They are overvotes at that rank.
+    MultiNameCells,
+    /// W018: a file source is listed twice with the same options, and is only read once
+    /// (--allow-duplicate-sources).
+    DuplicateSource,
+    /// W019: two file sources are the same file with different options, for example two
+    /// contests of the same export. Each source is read.
+    SameFileSources,
+    /// W020: identical ballots of a source were dropped (deduplicateIdenticalBallots).
+    IdenticalBallots,
+    /// W021: an output with one row per ballot has ranking patterns cast by fewer voters than
+    /// the privacy threshold. They are left out (--privacy-suppress-rare) or written anyway
+    /// (--privacy-override).
+    RarePatterns,
+    /// W022: the cells of a likert input that are not labels of the choices are ignored
+    /// (allowUnknownChoices).
+    UnknownChoices,
+    /// W023: an output that the result does not depend on cannot be written: the summary of a
+    /// failed run, the rounds written during the tabulation (--out-partial), or the removal of
+    /// the partial files.
+    UnwrittenOutput,
+    /// W024: the tabulation was interrupted, and only the rounds done so far are written.
+    TabulationInterrupted,
+    /// W025: a total of the summary does not reconcile (--verify-output), or the summary differs
+    /// from the reference summary (--reference-strict). The run then fails.
+    VerificationMismatch,
+    /// W026: some file sources were skipped (--skip-bad-sources), so the reference summary is
+    /// not checked.
+    ReferenceNotChecked,
+    /// W027: a collation is configured but timrcv is built without the 'collation' feature. The
+    /// names are sorted by their bytes.
+    CollationUnavailable,
+    /// W028: a snapshot of the validated ballots was written with other rules
+    /// (--allow-rules-mismatch).
+    SnapshotRulesMismatch,
+}
+
+impl WarningCode {
+    pub const ALL: [WarningCode; 28] = [
+        WarningCode::ShortRow,
+        WarningCode::UnsplittableOvervote,
+        WarningCode::UnknownCategory,
+        WarningCode::FairVoteMissingColumns,
+        WarningCode::CompressedRanks,
+        WarningCode::SkippedSource,
+        WarningCode::RanksInCells,
+        WarningCode::ExtraCandidateColumns,
+        WarningCode::SourceIsOutput,
+        WarningCode::RuleOverride,
+        WarningCode::RankOutOfRange,
+        WarningCode::DuplicateRanks,
+        WarningCode::NearMissCandidate,
+        WarningCode::TooManyCandidates,
+        WarningCode::OutsideTimeWindow,
+        WarningCode::EmptyRows,
+        WarningCode::MultiNameCells,
+        WarningCode::DuplicateSource,
+        WarningCode::SameFileSources,
+        WarningCode::IdenticalBallots,
+        WarningCode::RarePatterns,
+        WarningCode::UnknownChoices,
+        WarningCode::UnwrittenOutput,
+        WarningCode::TabulationInterrupted,
+        WarningCode::VerificationMismatch,
+        WarningCode::ReferenceNotChecked,
+        WarningCode::CollationUnavailable,
+        WarningCode::SnapshotRulesMismatch,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            WarningCode::ShortRow => "W001",
+            WarningCode::UnsplittableOvervote => "W002",
+            WarningCode::UnknownCategory => "W003",
+            WarningCode::FairVoteMissingColumns => "W004",
+            WarningCode::CompressedRanks => "W005",
+            WarningCode::SkippedSource => "W006",
+            WarningCode::RanksInCells => "W007",
+            WarningCode::ExtraCandidateColumns => "W008",
+            WarningCode::SourceIsOutput => "W009",
+            WarningCode::RuleOverride => "W010",
+            WarningCode::RankOutOfRange => "W011",
+            WarningCode::DuplicateRanks => "W012",
+            WarningCode::NearMissCandidate => "W013",
+            WarningCode::TooManyCandidates => "W014",
+            WarningCode::OutsideTimeWindow => "W015",
+            WarningCode::EmptyRows => "W016",
+            WarningCode::MultiNameCells => "W017",
+            WarningCode::DuplicateSource => "W018",
+            WarningCode::SameFileSources => "W019",
+            WarningCode::IdenticalBallots => "W020",
+            WarningCode::RarePatterns => "W021",
+            WarningCode::UnknownChoices => "W022",
+            WarningCode::UnwrittenOutput => "W023",
+            WarningCode::TabulationInterrupted => "W024",
+            WarningCode::VerificationMismatch => "W025",
+            WarningCode::ReferenceNotChecked => "W026",
+            WarningCode::CollationUnavailable => "W027",
+            WarningCode::SnapshotRulesMismatch => "W028",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningCode::ShortRow => "short-row",
+            WarningCode::UnsplittableOvervote => "unsplittable-overvote",
+            WarningCode::UnknownCategory => "unknown-category",
+            WarningCode::FairVoteMissingColumns => "fairvote-missing-columns",
+            WarningCode::CompressedRanks => "compressed-ranks",
+            WarningCode::SkippedSource => "skipped-source",
+            WarningCode::RanksInCells => "ranks-in-cells",
+            WarningCode::ExtraCandidateColumns => "extra-candidate-columns",
+            WarningCode::SourceIsOutput => "source-is-output",
+            WarningCode::RuleOverride => "rule-override",
+            WarningCode::RankOutOfRange => "rank-out-of-range",
+            WarningCode::DuplicateRanks => "duplicate-ranks",
+            WarningCode::NearMissCandidate => "near-miss-candidate",
+            WarningCode::TooManyCandidates => "too-many-candidates",
+            WarningCode::OutsideTimeWindow => "outside-time-window",
+            WarningCode::EmptyRows => "empty-rows",
+            WarningCode::MultiNameCells => "multi-name-cells",
+            WarningCode::DuplicateSource => "duplicate-source",
+            WarningCode::SameFileSources => "same-file-sources",
+            WarningCode::IdenticalBallots => "identical-ballots",
+            WarningCode::RarePatterns => "rare-patterns",
+            WarningCode::UnknownChoices => "unknown-choices",
+            WarningCode::UnwrittenOutput => "unwritten-output",
+            WarningCode::TabulationInterrupted => "tabulation-interrupted",
+            WarningCode::VerificationMismatch => "verification-mismatch",
+            WarningCode::ReferenceNotChecked => "reference-not-checked",
+            WarningCode::CollationUnavailable => "collation-unavailable",
+            WarningCode::SnapshotRulesMismatch => "snapshot-rules-mismatch",
+        }
+    }
+
+    /// Accepts either the code (`W003`) or the name (`unknown-category`).
+    pub fn parse(s: &str) -> Option<WarningCode> {
+        let s = s.trim();
+        WarningCode::ALL
+            .iter()
+            .find(|c| c.code().eq_ignore_ascii_case(s) || c.name() == s)
+            .cloned()
+    }
+}
+
+/// The categories of the errors. Each error stops the program.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum ErrorCode {
+    /// E000: any other error.
+    Other,
+    /// E001: the configuration or the arguments are invalid.
+    InvalidConfiguration,
+    /// E002: an input file cannot be opened or read.
+    UnreadableInput,
+    /// E003: the input file can be read, but some ballots are malformed.
+    MalformedBallots,
+    /// E004: the candidates of the input do not match the declared candidates.
+    UnknownCandidates,
+    /// E005: the tabulation failed.
+    TabulationFailed,
+    /// E006: an output file cannot be written.
+    OutputFailed,
+    /// E007: the tabulation does not match the reference summary or the official rounds, or the
+    /// totals of the summary do not reconcile (--verify-output).
+    VerificationFailed,
+    /// E008: a warning passed to `--fail-on` was raised.
+    FailedOnWarning,
+    /// E009: an output with one row per ballot would have ranking patterns cast by fewer voters
+    /// than the privacy threshold (--privacy-k).
+    PrivacyThreshold,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 10] = [
+        ErrorCode::Other,
+        ErrorCode::InvalidConfiguration,
+        ErrorCode::UnreadableInput,
+        ErrorCode::MalformedBallots,
+        ErrorCode::UnknownCandidates,
+        ErrorCode::TabulationFailed,
+        ErrorCode::OutputFailed,
+        ErrorCode::VerificationFailed,
+        ErrorCode::FailedOnWarning,
+        ErrorCode::PrivacyThreshold,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::Other => "E000",
+            ErrorCode::InvalidConfiguration => "E001",
+            ErrorCode::UnreadableInput => "E002",
+            ErrorCode::MalformedBallots => "E003",
+            ErrorCode::UnknownCandidates => "E004",
+            ErrorCode::TabulationFailed => "E005",
+            ErrorCode::OutputFailed => "E006",
+            ErrorCode::VerificationFailed => "E007",
+            ErrorCode::FailedOnWarning => "E008",
+            ErrorCode::PrivacyThreshold => "E009",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCode::Other => "other",
+            ErrorCode::InvalidConfiguration => "invalid-configuration",
+            ErrorCode::UnreadableInput => "unreadable-input",
+            ErrorCode::MalformedBallots => "malformed-ballots",
+            ErrorCode::UnknownCandidates => "unknown-candidates",
+            ErrorCode::TabulationFailed => "tabulation-failed",
+            ErrorCode::OutputFailed => "output-failed",
+            ErrorCode::VerificationFailed => "verification-failed",
+            ErrorCode::FailedOnWarning => "failed-on-warning",
+            ErrorCode::PrivacyThreshold => "privacy-threshold",
+        }
+    }
+}
+
+/// All the codes, one `<code> <name>` line per code.
+pub fn code_table() -> Vec<String> {
+    let warnings = WarningCode::ALL.iter().map(|c| (c.code(), c.name()));
+    let errors = ErrorCode::ALL.iter().map(|c| (c.code(), c.name()));
+    warnings
+        .chain(errors)
+        .map(|(code, name)| format!("{} {}", code, name))
+        .collect()
+}
+
+impl RcvError {
+    pub fn code(&self) -> ErrorCode {
+        use RcvError::*;
+        match self {
+            OpeningFile { source, .. } => source.code(),
+            ReferenceOpeningFile { source } => source.code(),
+            MissingInput {}
+            | UnknownFormat { .. }
+            | MissingMandatoryCandidates { .. }
+            | EmptyCandidates { .. }
+            | InvalidRandomSeed { .. }
+            | MissingRandomSeed {}
+            | ColumnIndexZero {}
+            | InvalidColumnLetter { .. }
+            | NoFileSources {}
+            | ChallengedNotSupported { .. }
+            | CategoriesNotSupported { .. }
+            | TimestampsNotSupported { .. }
+            | RespondentsNotSupported { .. }
+            | ExpectedCandidateCountNotSupported { .. }
+            | StylesNotSupported { .. }
+            | MetadataColumnAfterVotes { .. }
+            | InvalidTimeWindow { .. }
+            | InvalidHeaderPattern { .. }
+            | FeatureNotEnabled { .. }
+            | OptionNotEnabled { .. }
+            | ParquetRankColumns {}
+            | MissingParentDir {}
+            | ConfigOpeningJson { .. }
+            | ConfigTooLarge { .. }
+            | InvalidOverlay { .. }
+            | ServeTransport {}
+            | SourceIsConfig { .. }
+            | InvalidCollation { .. }
+            | ContinuationRowsWithoutId {}
+            | RankColumnsWithoutHeader {}
+            | ReferenceNotAFile { .. }
+            | OutputDirMissing { .. }
+            | OutputIsDirectory { .. }
+            | RuleArgument { .. }
+            | UnknownNumberLocale { .. }
+            | UnknownRule { .. }
+            | InvalidRuleValue { .. }
+            | NoContinuingCandidates { .. }
+            | DuplicateCandidateName { .. }
+            | UnknownNotaCandidate { .. }
+            | OutputNameCollision { .. }
+            | DuplicateCandidateCode { .. }
+            | DuplicateFileSource { .. }
+            | PreflibFromSnapshot {}
+            | VerifyRoundsChallengedBoth {}
+            | UnknownWarningCode { .. }
+            | LedgerDuplicate { .. }
+            | SnapshotRulesMismatch { .. } => ErrorCode::InvalidConfiguration,
+            OpeningExcel { .. }
+            | EmptyExcel {}
+            | ServeIo { .. }
+            | ExcelMissingWorksheet { .. }
+            | ExcelAmbiguousWorksheet { .. }
+            | ParquetOpen { .. }
+            | CsvOpenError { .. }
+            | CsvEmpty {}
+            | NoBallots { .. }
+            | CdfParsingJson {}
+            | CdfMissingContest { .. }
+            | DominionParsingJson {}
+            | CandidateCountMismatch { .. }
+            | OpeningJson { .. }
+            | ParsingJson { .. }
+            | SnapshotOpen { .. }
+            | ReferenceOpen { .. }
+            | ReferenceParse { .. }
+            | AuditOpen { .. }
+            | AuditParse { .. }
+            | LedgerRead { .. }
+            | LedgerParse { .. }
+            | InvalidSnapshot { .. } => ErrorCode::UnreadableInput,
+            #[cfg(feature = "parquet")]
+            ParquetRead { .. } | ArrowRead { .. } => ErrorCode::UnreadableInput,
+            LineParse { .. }
+            | InvalidNumber { .. }
+            | WeightOverflow { .. }
+            | CountOverflow { .. }
+            | CategoryColumnNotFound { .. }
+            | TimestampColumnNotFound { .. }
+            | RespondentColumnNotFound { .. }
+            | UnparseableTimestamps { .. }
+            | ExcelWrongCellType { .. }
+            | ParquetMissingColumn { .. }
+            | ParquetWrongType { .. }
+            | CsvLineParse { .. }
+            | CsvChunkLineParse { .. }
+            | CsvRowLength { .. }
+            | CsvLineToShort { .. }
+            | ContinuationCount { .. }
+            | RankHeaders { .. }
+            | DominionParsingCandidateId { .. }
+            | MissingChoices {}
+            | UnknownChoiceLabels { .. }
+            | ParsingJsonNumber {}
+            | InvalidId { .. } => ErrorCode::MalformedBallots,
+            ExcelCannotFindCandidateInHeader { .. }
+            | CandidatesNotInHeader { .. }
+            | DuplicateHeaderName { .. }
+            | ExtraCandidateColumns { .. }
+            | CandidateHitRate { .. }
+            | TooManyCandidates { .. }
+            | NoCandidatesInInput { .. }
+            | DominionMissingCandidateId { .. } => ErrorCode::UnknownCandidates,
+            RvVoting { .. } | Interrupted { .. } => ErrorCode::TabulationFailed,
+            CsvWrite { .. }
+            | SummaryWrite { .. }
+            | PreflibWrite { .. }
+            | ExportConfigWrite { .. }
+            | ExportConfigExists { .. }
+            | SnapshotWrite { .. }
+            | PartialWrite { .. }
+            | MetricsWrite { .. }
+            | LedgerWrite { .. }
+            | OutputPermissionDenied { .. }
+            | OutputDiskFull { .. } => ErrorCode::OutputFailed,
+            #[cfg(feature = "xlsx")]
+            XlsxWrite { .. } => ErrorCode::OutputFailed,
+            OfficialRounds { .. }
+            | InvalidSummary { .. }
+            | ReferenceMismatch {}
+            | ReferenceStrictMismatch { .. }
+            | OutputUnreconciled { .. }
+            | AuditMismatch { .. } => ErrorCode::VerificationFailed,
+            FailedOnWarnings { .. } => ErrorCode::FailedOnWarning,
+            PrivacyThreshold { .. } => ErrorCode::PrivacyThreshold,
+            Whatever { .. } => ErrorCode::Other,
+        }
+    }
+}
+
+/// A warning raised while reading or tabulating an election.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+}
+
+// The warnings are collected per thread: the readers that use several threads report their
+// warnings from the calling thread.
+thread_local! {
+    static WARNINGS: RefCell<Vec<Warning>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Logs a warning with its code, and keeps it for the summary and for `--fail-on`.
+pub fn warning(code: WarningCode, message: String) {
+    warn!("[{}] {}", code.code(), message);
+    WARNINGS.with(|w| w.borrow_mut().push(Warning { code, message }));
+}
+
+/// The number of warnings raised so far.
+pub fn num_warnings() -> usize {
+    WARNINGS.with(|w| w.borrow().len())
+}
+
+/// Returns the warnings raised so far, and clears them.
+pub fn take_warnings() -> Vec<Warning> {
+    WARNINGS.with(|w| w.take())
+}
+
+pub fn warnings_to_json(warnings: &[Warning]) -> JSValue {
+    let l: Vec<JSValue> = warnings
+        .iter()
+        .map(|w| {
+            json!({
+                "code": w.code.code(),
+                "name": w.code.name(),
+                "message": w.message,
+            })
+        })
+        .collect();
+    JSValue::Array(l)
+}
+
+/// The warnings that turn into errors, from a comma-separated list of codes or names.
+pub fn parse_fail_on(s: &str) -> RcvResult<BTreeSet<WarningCode>> {
+    let mut res = BTreeSet::new();
+    for part in s.split(',').filter(|p| !p.trim().is_empty()) {
+        let code = WarningCode::parse(part).context(UnknownWarningCodeSnafu {
+            code: part.trim(),
+            known: WarningCode::ALL
+                .iter()
+                .map(|c| c.code())
+                .collect::<Vec<_>>()
+                .join(", "),
+        })?;
+        res.insert(code);
+    }
+    Ok(res)
+}
+
+/// Fails if one of the warnings is in `fail_on`.
+pub fn check_fail_on(warnings: &[Warning], fail_on: &BTreeSet<WarningCode>) -> RcvResult<()> {
+    let raised: BTreeSet<&str> = warnings
+        .iter()
+        .filter(|w| fail_on.contains(&w.code))
+        .map(|w| w.code.code())
+        .collect();
+    ensure!(
+        raised.is_empty(),
+        FailedOnWarningsSnafu {
+            codes: raised.into_iter().collect::<Vec<_>>().join(", ")
+        }
+    );
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// The order of the names in the outputs (outputSettings.collation).
+//
+// By default, the names are sorted by the order of their bytes. With the `collation` feature, a
+// BCP-47 language tag selects the collation of that locale, so that for example `Åsa` sorts
+// after `Zelda` in Swedish and before `Bertil` in English.
+
+use std::cmp::Ordering;
+
+use crate::rcv::*;
+
+/// How the names are compared when the outputs are sorted alphabetically.
+#[derive(Debug)]
+pub enum NameOrder {
+    /// The order of the bytes of the names.
+    Bytes,
+    /// The collation of a locale.
+    #[cfg(feature = "collation")]
+    Locale(Box<icu_collator::Collator>),
+}
+
+impl NameOrder {
+    pub fn compare(&self, n1: &str, n2: &str) -> Ordering {
+        match self {
+            NameOrder::Bytes => n1.cmp(n2),
+            #[cfg(feature = "collation")]
+            NameOrder::Locale(collator) => collator.compare(n1, n2).then_with(|| n1.cmp(n2)),
+        }
+    }
+}
+
+#[cfg(feature = "collation")]
+pub fn name_order(tag: Option<&str>) -> RcvResult<NameOrder> {
+    use icu_collator::{Collator, CollatorOptions};
+
+    let tag = match tag {
+        None => return Ok(NameOrder::Bytes),
+        Some(tag) => tag,
+    };
+    let locale: icu_locid::Locale = tag.parse().ok().context(InvalidCollationSnafu { tag })?;
+    let collator = Collator::try_new(&(&locale).into(), CollatorOptions::new())
+        .ok()
+        .context(InvalidCollationSnafu { tag })?;
+    debug!("name_order: collation of the locale {}", locale);
+    Ok(NameOrder::Locale(Box::new(collator)))
+}
+
+#[cfg(not(feature = "collation"))]
+pub fn name_order(tag: Option<&str>) -> RcvResult<NameOrder> {
+    if let Some(tag) = tag {
+        codes::warning(
+            WarningCode::CollationUnavailable,
+            format!(
+                "the collation '{}' requires timrcv to be built with the 'collation' feature, the names are sorted by their bytes",
+                tag
+            ),
+        );
+    }
+    Ok(NameOrder::Bytes)
+}
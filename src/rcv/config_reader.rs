@@ -1,5 +1,6 @@
 use crate::rcv::*;
 
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JSValue;
 
@@ -19,6 +20,71 @@ pub struct OutputSettings {
     pub tabulate_by_precinct: Option<bool>,
     #[serde(rename = "generateCdfJson")]
     pub generate_cdf_json: Option<bool>,
+    // New options specific to timrcv
+    #[serde(rename = "summarySchema")]
+    pub summary_schema: Option<String>,
+    #[serde(rename = "rctabCompatibility")]
+    pub rctab_compatibility: Option<String>,
+    pub collation: Option<String>,
+    #[serde(rename = "percentRounding")]
+    pub percent_rounding: Option<String>,
+    #[serde(rename = "aggregateBelow")]
+    pub aggregate_below: Option<u64>,
+    /// The name of the undeclared write-ins in the outputs, instead of "Undeclared Write-ins".
+    #[serde(rename = "undeclaredWriteInDisplayName")]
+    pub undeclared_write_in_display_name: Option<String>,
+    /// The summary the outputs are rendered from, instead of a tabulation (render
+    /// --from-summary). The outputs that have room for it say so.
+    #[serde(skip)]
+    pub rendered_from: Option<String>,
+}
+
+/// The versions of the JSON summary.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum SummarySchema {
+    /// The format of the reference implementation.
+    V1,
+    /// The format of the reference implementation, with additional information
+    /// specific to timrcv.
+    V2,
+}
+
+impl OutputSettings {
+    pub fn summary_schema(&self) -> RcvResult<SummarySchema> {
+        match self.summary_schema.as_deref() {
+            None | Some("v1") => Ok(SummarySchema::V1),
+            Some("v2") => Ok(SummarySchema::V2),
+            Some(x) => whatever!("unknown summary schema: {}", x),
+        }
+    }
+
+    /// How the percentages of the outputs are rounded.
+    pub fn percent_rounding(&self) -> RcvResult<PercentRounding> {
+        match self.percent_rounding.as_deref() {
+            None | Some("halfUp") => Ok(PercentRounding::HalfUp),
+            Some("truncate") => Ok(PercentRounding::Truncate),
+            Some("halfEven") => Ok(PercentRounding::HalfEven),
+            Some(x) => whatever!("unknown percent rounding: {}", x),
+        }
+    }
+
+    /// The order of the names when the outputs are sorted alphabetically.
+    pub fn name_order(&self) -> RcvResult<NameOrder> {
+        collation::name_order(self.collation.as_deref())
+    }
+
+    /// True if the rounds of the summary have the inactive ballots by reason, as in the
+    /// summaries of RCTab 1.3.
+    pub fn inactive_ballots_block(&self) -> RcvResult<bool> {
+        match self.rctab_compatibility.as_deref() {
+            None => Ok(false),
+            Some("1.3") => Ok(true),
+            Some(x) => whatever!(
+                "unknown RCTab compatibility version: {}, only 1.3 is supported",
+                x
+            ),
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +96,27 @@ pub struct OutputConfig {
     pub threshold: Option<String>,
 }
 
+/// Where the candidates are in a file of the csv provider.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum CsvLayout {
+    /// The cells contain the names of the candidates, one column per rank.
+    ChoicesInCells,
+    /// The header contains the names of the candidates, and the cells contain the ranks (as
+    /// with the csv_likert provider).
+    RanksInCells,
+}
+
+/// How the integers (counts and ranks) are written in the input files.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum NumberLocale {
+    /// Only digits.
+    Plain,
+    /// Commas or spaces between the thousands, and a dot for the decimals: `1,234`.
+    En,
+    /// Dots or spaces between the thousands, and a comma for the decimals: `1.234`.
+    Eu,
+}
+
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct FileSource {
     pub provider: String,
@@ -44,7 +131,9 @@ pub struct FileSource {
     #[serde(rename = "idColumnIndex")]
     pub id_column_index: Option<JSValue>,
     #[serde(rename = "precinctColumnIndex")]
-    pub precinct_column_index: Option<String>,
+    pub precinct_column_index: Option<JSValue>,
+    #[serde(rename = "styleColumnIndex")]
+    pub style_column_index: Option<JSValue>,
     #[serde(rename = "overvoteDelimiter")]
     pub overvote_delimiter: Option<String>,
     #[serde(rename = "overvoteLabel")]
@@ -60,15 +149,114 @@ pub struct FileSource {
     pub count_column_index: Option<JSValue>,
     #[serde(rename = "excelWorksheetName")]
     pub excel_worksheet_name: Option<String>,
+    #[serde(rename = "excelWorksheetNames")]
+    pub excel_worksheet_names: Option<Vec<String>>,
+    #[serde(rename = "allWorksheets")]
+    pub all_worksheets: Option<bool>,
     #[serde(rename = "choices")]
     pub choices: Option<Vec<String>>,
+    #[serde(rename = "challengedColumn")]
+    pub challenged_column: Option<JSValue>,
+    #[serde(rename = "challengedLabel")]
+    pub challenged_label: Option<String>,
+    #[serde(rename = "rankColumnPrefix")]
+    pub rank_column_prefix: Option<String>,
+    #[serde(rename = "rankColumnCount")]
+    pub rank_column_count: Option<usize>,
+    #[serde(rename = "rankingsColumn")]
+    pub rankings_column: Option<String>,
+    #[serde(rename = "idColumnName")]
+    pub id_column_name: Option<String>,
+    #[serde(rename = "countColumnName")]
+    pub count_column_name: Option<String>,
+    #[serde(rename = "jobs")]
+    pub jobs: Option<usize>,
+    #[serde(rename = "categoryColumn")]
+    pub category_column: Option<JSValue>,
+    #[serde(rename = "candidateHeaderPattern")]
+    pub candidate_header_pattern: Option<String>,
+    #[serde(rename = "sanityChecks")]
+    pub sanity_checks: Option<bool>,
+    #[serde(rename = "compressRanks")]
+    pub compress_ranks: Option<bool>,
+    #[serde(rename = "numberLocale")]
+    pub number_locale: Option<String>,
+    #[serde(rename = "layout")]
+    pub layout: Option<String>,
+    #[serde(rename = "strictColumns")]
+    pub strict_columns: Option<bool>,
+    #[serde(rename = "allowUnknownChoices")]
+    pub allow_unknown_choices: Option<bool>,
+    #[serde(rename = "continuationRows")]
+    pub continuation_rows: Option<bool>,
+    #[serde(rename = "maxRank")]
+    pub max_rank: Option<u32>,
+    #[serde(rename = "rankColumnsByHeader")]
+    pub rank_columns_by_header: Option<bool>,
+    #[serde(rename = "rankHeaderPattern")]
+    pub rank_header_pattern: Option<String>,
+    #[serde(rename = "maxInferredCandidates")]
+    pub max_inferred_candidates: Option<usize>,
+    #[serde(rename = "strictInference")]
+    pub strict_inference: Option<bool>,
+    #[serde(rename = "timestampColumn")]
+    pub timestamp_column: Option<JSValue>,
+    #[serde(rename = "acceptFrom")]
+    pub accept_from: Option<String>,
+    #[serde(rename = "acceptUntil")]
+    pub accept_until: Option<String>,
+    #[serde(rename = "deduplicateIdenticalBallots")]
+    pub deduplicate_identical_ballots: Option<bool>,
+    #[serde(rename = "respondentColumn")]
+    pub respondent_column: Option<JSValue>,
+    #[serde(rename = "expectedCandidateCount")]
+    pub expected_candidate_count: Option<usize>,
+    // Only set by --shuffle-ballots-seed.
+    #[serde(skip)]
+    pub shuffle_seed: Option<u64>,
+    // Only set by --debug-sample-rate.
+    #[serde(skip)]
+    pub debug_sample_rate: Option<SampleRate>,
+    // Set when category_column is the column of the weights, and not declared by the source.
+    #[serde(skip)]
+    pub category_from_weights: bool,
+}
+
+// A header pattern is either a template with `{}` in place of the capture, or a regular
+// expression with one capture group.
+fn header_regex(option: &str, pattern: &str, capture: &str, ignore_case: bool) -> RcvResult<Regex> {
+    let re_str = match pattern.split_once("{}") {
+        Some((prefix, suffix)) => format!(
+            "{}^{}{}{}$",
+            if ignore_case { "(?i)" } else { "" },
+            regex::escape(prefix.trim_start()),
+            capture,
+            regex::escape(suffix.trim_end())
+        ),
+        None => pattern.to_string(),
+    };
+    let re = Regex::new(&re_str).map_err(|e| RcvError::InvalidHeaderPattern {
+        option: option.to_string(),
+        pattern: pattern.to_string(),
+        reason: e.to_string(),
+    })?;
+    ensure!(
+        re.captures_len() == 2,
+        InvalidHeaderPatternSnafu {
+            option,
+            pattern,
+            reason: "the pattern needs exactly one capture group".to_string(),
+        }
+    );
+    Ok(re)
 }
 
+// All the column indices of the configuration are 1-based numbers or Excel-style letters.
+// The accessors convert them once to 0-based indices.
 impl FileSource {
     pub fn first_vote_column_index(&self) -> RcvResult<usize> {
         if self._first_vote_column_index.is_some() {
-            let x = read_js_int(&self._first_vote_column_index)?;
-            Ok(x - 1)
+            read_js_column_index(&self._first_vote_column_index)
         } else {
             Ok(0)
         }
@@ -85,18 +273,148 @@ impl FileSource {
 
     pub fn id_column_index_int(&self) -> RcvResult<Option<usize>> {
         if self.id_column_index.is_some() {
-            read_js_int(&self.id_column_index).map(Some)
+            read_js_column_index(&self.id_column_index).map(Some)
         } else {
             Ok(None)
         }
     }
+
     pub fn count_column_index_int(&self) -> RcvResult<Option<usize>> {
         if self.count_column_index.is_some() {
-            read_js_int(&self.count_column_index).map(Some)
+            read_js_column_index(&self.count_column_index).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn precinct_column_index_int(&self) -> RcvResult<Option<usize>> {
+        if self.precinct_column_index.is_some() {
+            read_js_column_index(&self.precinct_column_index).map(Some)
         } else {
             Ok(None)
         }
     }
+
+    pub fn style_column_index_int(&self) -> RcvResult<Option<usize>> {
+        if self.style_column_index.is_some() {
+            read_js_column_index(&self.style_column_index).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn challenged_column_index_int(&self) -> RcvResult<Option<usize>> {
+        if self.challenged_column.is_some() {
+            read_js_column_index(&self.challenged_column).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The 0-based index of the column with the voter category. A name is looked up in the
+    /// header row, if the provider has one. The column of the weights is only used by the
+    /// sources that have it: the ballots of the others have no category.
+    pub fn category_column_index(&self, header: Option<&[String]>) -> RcvResult<Option<usize>> {
+        let res = named_column_index(&self.category_column, header, |name| {
+            RcvError::CategoryColumnNotFound {
+                column: name.to_string(),
+            }
+        });
+        match res {
+            Err(RcvError::CategoryColumnNotFound { column }) if self.category_from_weights => {
+                codes::warning(
+                    WarningCode::UnknownCategory,
+                    format!(
+                        "the file source {} has no column {} of the weights: its ballots get the default weight",
+                        self.file_path, column
+                    ),
+                );
+                Ok(None)
+            }
+            res => res,
+        }
+    }
+
+    /// The 0-based index of the column with the time of submission of the ballots, as for
+    /// [FileSource::category_column_index].
+    pub fn timestamp_column_index(&self, header: Option<&[String]>) -> RcvResult<Option<usize>> {
+        named_column_index(&self.timestamp_column, header, |name| {
+            RcvError::TimestampColumnNotFound {
+                column: name.to_string(),
+            }
+        })
+    }
+
+    /// The 0-based index of the column that identifies the respondent of a form (an email
+    /// address, for example), for deduplicateIdenticalBallots. As for
+    /// [FileSource::category_column_index].
+    pub fn respondent_column_index(&self, header: Option<&[String]>) -> RcvResult<Option<usize>> {
+        named_column_index(&self.respondent_column, header, |name| {
+            RcvError::RespondentColumnNotFound {
+                column: name.to_string(),
+            }
+        })
+    }
+
+    /// The regular expression that extracts the name of a candidate from a header. The pattern
+    /// is either a template with `{}` in place of the name, such as `Who do you vote for? [{}]`,
+    /// or a regular expression with one capture group.
+    pub fn candidate_header_regex(&self) -> RcvResult<Option<Regex>> {
+        match self.candidate_header_pattern.as_ref() {
+            Some(pattern) => {
+                header_regex("candidateHeaderPattern", pattern, "(.*)", false).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The regular expression that extracts the rank from the header of a rank column, for
+    /// rankColumnsByHeader. The pattern is a template with `{}` in place of the number (`rank{}`
+    /// by default, which matches `rank1`, `Rank2`, ...), or a regular expression with one
+    /// capture group.
+    pub fn rank_header_regex(&self) -> RcvResult<Regex> {
+        let pattern = self.rank_header_pattern.as_deref().unwrap_or("rank{}");
+        header_regex("rankHeaderPattern", pattern, r"(\d+)", true)
+    }
+
+    /// The thousands separators accepted in the counts and the ranks (numberLocale), plain by
+    /// default.
+    pub fn number_locale(&self) -> RcvResult<NumberLocale> {
+        match self.number_locale.as_deref() {
+            None | Some("plain") => Ok(NumberLocale::Plain),
+            Some("en") => Ok(NumberLocale::En),
+            Some("eu") => Ok(NumberLocale::Eu),
+            Some(x) => UnknownNumberLocaleSnafu { value: x }.fail(),
+        }
+    }
+
+    pub fn layout(&self) -> RcvResult<CsvLayout> {
+        match self.layout.as_deref() {
+            None | Some("choices-in-cells") => Ok(CsvLayout::ChoicesInCells),
+            Some("ranks-in-cells") => Ok(CsvLayout::RanksInCells),
+            Some(x) => whatever!("unknown layout: {}", x),
+        }
+    }
+
+    /// The same source, read with the csv_likert provider. The header is always the first row,
+    /// so the ballots start at the second row at least.
+    pub fn as_ranks_in_cells(&self) -> RcvResult<FileSource> {
+        let mut res = self.clone();
+        res.provider = "csv_likert".to_string();
+        if self.first_vote_row_index()? == 0 {
+            res._first_vote_row_index = Some(JSValue::String("2".to_string()));
+        }
+        Ok(res)
+    }
+
+    /// True if the content of the challenged column marks the ballot as challenged.
+    /// Without a challengedLabel, any non-empty content marks the ballot.
+    pub fn is_challenged(&self, cell: &str) -> bool {
+        match self.challenged_label.as_ref() {
+            Some(label) => cell.trim() == label,
+            None => !cell.trim().is_empty(),
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -104,6 +422,10 @@ pub struct RcvCandidate {
     pub name: String,
     pub code: Option<String>,
     pub excluded: Option<bool>,
+    /// True if the candidate was not declared but found in the ballots (or in the header of a
+    /// ranks-in-cells file). It is never read from or written to the configuration.
+    #[serde(skip)]
+    pub inferred: bool,
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -114,8 +436,10 @@ pub struct RcvRules {
     pub _overvote_rule: String,
     #[serde(rename = "winnerElectionMode")]
     pub winner_election_mode: String,
+    #[serde(rename = "numberOfWinners")]
+    pub number_of_winners: Option<JSValue>,
     #[serde(rename = "randomSeed")]
-    pub random_seed: Option<String>,
+    _random_seed: Option<JSValue>,
     #[serde(rename = "maxSkippedRanksAllowed")]
     pub max_skipped_ranks_allowed: String,
     #[serde(rename = "maxRankingsAllowed")]
@@ -126,9 +450,113 @@ pub struct RcvRules {
     pub batch_elimination: Option<bool>,
     #[serde(rename = "exhaustOnDuplicateCandidate")]
     pub exhaust_on_duplicate_candidate: Option<bool>,
+    // New options specific to timrcv
+    #[serde(rename = "exhaustRankingsCap")]
+    pub exhaust_rankings_cap: Option<u32>,
+    #[serde(rename = "continueToCompletion")]
+    pub continue_to_completion: Option<bool>,
+    #[serde(rename = "legacyRandomTiebreak")]
+    pub legacy_random_tiebreak: Option<bool>,
+    #[serde(rename = "notaCandidate")]
+    pub nota_candidate: Option<String>,
+    #[serde(rename = "protectNota")]
+    pub protect_nota: Option<bool>,
+    #[serde(rename = "uwiRound1Reporting")]
+    pub uwi_round1_reporting: Option<String>,
+    #[serde(rename = "blankCountsAsSkipped")]
+    pub blank_counts_as_skipped: Option<bool>,
+    #[serde(rename = "trackRankOrigin")]
+    pub track_rank_origin: Option<bool>,
+    #[serde(rename = "overvoteScope")]
+    pub overvote_scope: Option<String>,
 }
 
 impl RcvRules {
+    /// The seed for the random tiebreak mode. It may be written as a JSON number or string,
+    /// and accepts the full range of 64-bit seeds used by the reference implementation.
+    pub fn random_seed(&self) -> RcvResult<Option<u64>> {
+        match self._random_seed.as_ref() {
+            None | Some(JSValue::Null) => Ok(None),
+            Some(v) => {
+                let seed = match v {
+                    JSValue::Number(n) => n.as_u64(),
+                    JSValue::String(s) => s.trim().parse::<u64>().ok(),
+                    _ => None,
+                };
+                seed.map(Some).context(InvalidRandomSeedSnafu {
+                    value: v.to_string(),
+                })
+            }
+        }
+    }
+
+    /// Checks that the configuration asks for a single winner: the elections with several winners
+    /// are not implemented, and their number of winners is refused instead of being ignored.
+    pub fn check_number_of_winners(&self) -> RcvResult<()> {
+        match self.number_of_winners()? {
+            1 => Ok(()),
+            n => whatever!(
+                "numberOfWinners {}: only the elections with a single winner are implemented",
+                n
+            ),
+        }
+    }
+
+    /// The number of winners of the configuration, 1 if it is not set.
+    pub fn number_of_winners(&self) -> RcvResult<u64> {
+        let number = match self.number_of_winners.as_ref() {
+            None | Some(JSValue::Null) => return Ok(1),
+            Some(JSValue::Number(n)) => n.as_u64(),
+            Some(JSValue::String(s)) => s.trim().parse::<u64>().ok(),
+            Some(_) => None,
+        };
+        match number {
+            Some(n) => Ok(n),
+            None => whatever!(
+                "Value {} cannot be understood for numberOfWinners",
+                self.number_of_winners.as_ref().unwrap()
+            ),
+        }
+    }
+
+    /// Sets one rule from its name in the configuration (--rule key=value). The value is taken as
+    /// a string, or as a JSON value (true, 3) for the rules that are not strings.
+    pub fn set_rule(&mut self, key: &str, value: &str) -> RcvResult<()> {
+        let mut fields = match serde_json::to_value(&*self).context(ParsingJsonSnafu {})? {
+            JSValue::Object(fields) => fields,
+            _ => whatever!("the rules are not a JSON object"),
+        };
+        if !fields.contains_key(key) {
+            let known: Vec<&str> = fields.keys().map(|k| k.as_str()).collect();
+            return UnknownRuleSnafu {
+                key,
+                known: known.join(", "),
+            }
+            .fail();
+        }
+        let mut attempts = vec![JSValue::String(value.to_string())];
+        attempts.extend(serde_json::from_str::<JSValue>(value).ok());
+        let mut reason: Option<String> = None;
+        for attempt in attempts {
+            fields.insert(key.to_string(), attempt);
+            match serde_json::from_value::<RcvRules>(JSValue::Object(fields.clone())) {
+                Ok(rules) => {
+                    *self = rules;
+                    return Ok(());
+                }
+                Err(e) => {
+                    reason.get_or_insert(e.to_string());
+                }
+            }
+        }
+        InvalidRuleValueSnafu {
+            key,
+            value,
+            reason: reason.unwrap_or_default(),
+        }
+        .fail()
+    }
+
     pub fn overvote_rule(&self) -> RcvResult<OverVoteRule> {
         match self._overvote_rule.as_str() {
             "exhaustImmediately" => Ok(OverVoteRule::ExhaustImmediately),
@@ -137,6 +565,47 @@ impl RcvRules {
             _ => whatever!("unknown overvote rule: {}", self._overvote_rule),
         }
     }
+
+    /// The rankings read for the overvotes. The whole ballot is only read with the
+    /// exhaustImmediately rule.
+    pub fn overvote_scope(&self) -> RcvResult<OverVoteScope> {
+        match self.overvote_scope.as_deref() {
+            None | Some("upToNextContinuing") => Ok(OverVoteScope::UpToNextContinuing),
+            Some("wholeBallot") => {
+                ensure_whatever!(
+                    self.overvote_rule()? == OverVoteRule::ExhaustImmediately,
+                    "overvoteScope wholeBallot needs the overvoteRule exhaustImmediately, not {}",
+                    self._overvote_rule
+                );
+                Ok(OverVoteScope::WholeBallot)
+            }
+            Some(x) => whatever!(
+                "Value {:?} cannot be understood for overvoteScope: expected \"upToNextContinuing\" or \"wholeBallot\"",
+                x
+            ),
+        }
+    }
+}
+
+/// The weights of the ballots by voter category, for example to count the votes of the board
+/// members three times.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct Weights {
+    /// The column with the category of each ballot: a name from the header row or an index.
+    pub column: JSValue,
+    pub values: HashMap<String, u64>,
+    /// The weight of the unknown categories and of the ballots without a category (default 1).
+    pub default: Option<u64>,
+}
+
+impl Weights {
+    /// The weight of a category, and whether the category is known.
+    pub fn weight(&self, category: Option<&str>) -> (u64, bool) {
+        match category.and_then(|c| self.values.get(c)) {
+            Some(w) => (*w, true),
+            None => (self.default.unwrap_or(1), false),
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -147,6 +616,8 @@ pub struct RcvConfig {
     pub cvr_file_sources: Vec<FileSource>,
     pub candidates: Vec<RcvCandidate>,
     pub rules: RcvRules,
+    // New options specific to timrcv
+    pub weights: Option<Weights>,
 }
 
 impl RcvConfig {
@@ -160,6 +631,7 @@ impl RcvConfig {
             _first_vote_row_index: None,
             id_column_index: None,
             precinct_column_index: None,
+            style_column_index: None,
             overvote_delimiter: None,
             undervote_label: None,
             overvote_label: None,
@@ -168,6 +640,39 @@ impl RcvConfig {
             count_column_index: None,
             choices: None,
             excel_worksheet_name: None,
+            excel_worksheet_names: None,
+            all_worksheets: None,
+            challenged_column: None,
+            challenged_label: None,
+            rank_column_prefix: None,
+            rank_column_count: None,
+            rankings_column: None,
+            id_column_name: None,
+            count_column_name: None,
+            jobs: None,
+            category_column: None,
+            candidate_header_pattern: None,
+            sanity_checks: None,
+            compress_ranks: None,
+            number_locale: None,
+            layout: None,
+            strict_columns: None,
+            allow_unknown_choices: None,
+            continuation_rows: None,
+            max_rank: None,
+            rank_columns_by_header: None,
+            rank_header_pattern: None,
+            max_inferred_candidates: None,
+            strict_inference: None,
+            timestamp_column: None,
+            accept_from: None,
+            accept_until: None,
+            deduplicate_identical_ballots: None,
+            respondent_column: None,
+            expected_candidate_count: None,
+            shuffle_seed: None,
+            debug_sample_rate: None,
+            category_from_weights: false,
         }];
         let res = RcvConfig {
             output_settings: OutputSettings {
@@ -178,6 +683,13 @@ impl RcvConfig {
                 contest_office: None,
                 tabulate_by_precinct: None,
                 generate_cdf_json: None,
+                summary_schema: None,
+                rctab_compatibility: None,
+                collation: None,
+                percent_rounding: None,
+                aggregate_below: None,
+                undeclared_write_in_display_name: None,
+                rendered_from: None,
             },
             cvr_file_sources,
             candidates: Vec::new(),
@@ -185,13 +697,24 @@ impl RcvConfig {
                 tiebreak_mode: "useCandidateOrder".to_string(),
                 _overvote_rule: "alwaysSkipToNextRank".to_string(),
                 winner_election_mode: "singleWinnerMajority".to_string(),
-                random_seed: None,
+                number_of_winners: None,
+                _random_seed: None,
                 max_skipped_ranks_allowed: "100000".to_string(),
                 max_rankings_allowed: "max".to_string(),
                 batch_elimination: Some(true),
                 exhaust_on_duplicate_candidate: Some(false),
+                exhaust_rankings_cap: None,
+                continue_to_completion: None,
+                legacy_random_tiebreak: None,
+                nota_candidate: None,
+                protect_nota: None,
+                uwi_round1_reporting: None,
+                blank_counts_as_skipped: None,
+                track_rank_origin: None,
+                overvote_scope: None,
                 rules_description: Some("timrcv_defaultv1".to_string()),
             },
+            weights: None,
         };
         Ok(res)
     }
@@ -200,7 +723,9 @@ impl RcvConfig {
 pub fn read_summary(path: String) -> BRcvResult<JSValue> {
     let contents = fs::read_to_string(path.clone()).context(OpeningJsonSnafu { path })?;
     // debug!("read content: {:?}", contents);
-    let mut js: JSValue = serde_json::from_str(contents.as_str()).context(ParsingJsonSnafu {})?;
+    let js: JSValue = serde_json::from_str(contents.as_str()).context(ParsingJsonSnafu {})?;
+    // The names of the summaries written on macOS may have combining accents (NFD).
+    let mut js = summary::nfc_json(js);
     // Order the tally results to ensure stability
     // Remove the mention of the undeclared write-in's when they have zero votes associated to them.
     let results_ordered: Vec<JSValue> = js["results"]
@@ -258,18 +783,56 @@ pub fn read_summary(path: String) -> BRcvResult<JSValue> {
     Ok(js)
 }
 
+// A column given by its index or by its name in the header.
+fn named_column_index(
+    x: &Option<JSValue>,
+    header: Option<&[String]>,
+    not_found: impl Fn(&str) -> RcvError,
+) -> RcvResult<Option<usize>> {
+    match x.as_ref() {
+        None | Some(JSValue::Null) => Ok(None),
+        Some(JSValue::String(name)) => {
+            if let Some(idx) = header.and_then(|h| h.iter().position(|c| c.trim() == name)) {
+                Ok(Some(idx))
+            } else if name.chars().count() == 1 || name.parse::<usize>().is_ok() {
+                read_js_column_index(x).map(Some)
+            } else {
+                Err(not_found(name))
+            }
+        }
+        Some(_) => read_js_column_index(x).map(Some),
+    }
+}
+
+// Reads a 1-based column index or an Excel-style column letter, and returns the 0-based index.
+fn read_js_column_index(x: &Option<JSValue>) -> RcvResult<usize> {
+    match x {
+        Some(JSValue::String(s)) if s.chars().all(|c| c.is_alphabetic()) => read_js_int(x),
+        _ => {
+            let idx = read_js_int(x)?;
+            ensure!(idx > 0, ColumnIndexZeroSnafu {});
+            Ok(idx - 1)
+        }
+    }
+}
+
 fn read_js_int(x: &Option<JSValue>) -> RcvResult<usize> {
     match x {
         Some(JSValue::Number(n)) => n
             .as_u64()
             .map(|x| x as usize)
             .context(ParsingJsonNumberSnafu {}),
-        // Parsing the Excel-style columns
+        // Parsing the Excel-style columns: A is 0, Z is 25, AA is 26...
         Some(JSValue::String(s)) if s.chars().all(|c| c.is_alphabetic()) => {
-            // Just treating the simple case for now. It should be expanded to more than 26 columns.
-            assert_eq!(s.chars().count(), 1);
-            let c1: char = s.to_lowercase().chars().next().unwrap();
-            Ok((c1 as usize) - ('a' as usize))
+            ensure!(
+                !s.is_empty() && s.len() <= 3 && s.chars().all(|c| c.is_ascii_alphabetic()),
+                InvalidColumnLetterSnafu { value: s.clone() }
+            );
+            let idx = s
+                .to_ascii_uppercase()
+                .bytes()
+                .fold(0, |acc, c| acc * 26 + (c - b'A') as usize + 1);
+            Ok(idx - 1)
         }
         Some(JSValue::String(s)) => s.parse::<usize>().ok().context(ParsingJsonNumberSnafu {}),
         _ => None.context(ParsingJsonNumberSnafu {}),
@@ -0,0 +1,112 @@
+// The deduplication of the identical ballots of a source (deduplicateIdenticalBallots), for the
+// forms that accept several submissions of the same person, such as Google Forms without sign-in.
+//
+// Two ballots are identical if they have the same normalized choices, the same category, the same
+// count, the same challenged flag and the same respondent (respondentColumn), if the source has
+// one. The ids and the timestamps of the submissions are not compared. The normalized choices are the trimmed entries in NFC, without
+// the empty ranks at the end. The first ballot is kept, and the others are dropped with a
+// warning W020 that lists the most duplicated ballots.
+
+use std::collections::HashMap;
+
+use crate::rcv::patterns::{PATTERN_EMPTY, PATTERN_SKIP};
+use crate::rcv::*;
+
+// The number of duplicated ballots listed in the warning.
+const NUM_REPORTED: usize = 3;
+
+// The respondent, the category, the count, the challenged flag and the normalized choices.
+type Key = (
+    Option<String>,
+    Option<String>,
+    Option<u64>,
+    bool,
+    Vec<Vec<String>>,
+);
+
+fn normalized_choices(pb: &ParsedBallot) -> Vec<Vec<String>> {
+    let mut choices: Vec<Vec<String>> = pb
+        .choices
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .map(|c| nfc(c.trim()).to_string())
+                .filter(|c| !c.is_empty())
+                .collect()
+        })
+        .collect();
+    while choices.last().is_some_and(|group| group.is_empty()) {
+        choices.pop();
+    }
+    choices
+}
+
+fn display(key: &Key) -> String {
+    let (respondent, _, _, _, choices) = key;
+    let pattern = if choices.is_empty() {
+        PATTERN_EMPTY.to_string()
+    } else {
+        choices
+            .iter()
+            .map(|group| match group.as_slice() {
+                [] => PATTERN_SKIP.to_string(),
+                _ => group.join("+"),
+            })
+            .collect::<Vec<_>>()
+            .join(" > ")
+    };
+    match respondent {
+        Some(respondent) => format!("{} from {:?}", pattern, respondent),
+        None => pattern,
+    }
+}
+
+/// The ballots without the identical ballots after their first occurrence.
+pub fn deduplicate(path: &str, parsed_ballots: Vec<ParsedBallot>) -> Vec<ParsedBallot> {
+    // The number of ballots of each key, and the order of their first occurrence.
+    let mut seen: HashMap<Key, (usize, usize)> = HashMap::new();
+    let mut res: Vec<ParsedBallot> = Vec::new();
+    for pb in parsed_ballots.into_iter() {
+        let key: Key = (
+            pb.respondent.clone(),
+            pb.category.clone(),
+            pb.count,
+            pb.challenged,
+            normalized_choices(&pb),
+        );
+        let order = seen.len();
+        let entry = seen.entry(key).or_insert((0, order));
+        entry.0 += 1;
+        if entry.0 == 1 {
+            res.push(pb);
+        } else {
+            debug!("deduplicate: {}: dropping the ballot {:?}", path, pb.id);
+        }
+    }
+    let num_dropped: usize = seen.values().map(|(n, _)| n - 1).sum();
+    if num_dropped == 0 {
+        return res;
+    }
+    let mut duplicated: Vec<(&Key, usize, usize)> = seen
+        .iter()
+        .filter(|(_, (n, _))| *n > 1)
+        .map(|(key, (n, order))| (key, *n, *order))
+        .collect();
+    duplicated.sort_by(|(_, n1, o1), (_, n2, o2)| n2.cmp(n1).then_with(|| o1.cmp(o2)));
+    let top: Vec<String> = duplicated
+        .iter()
+        .take(NUM_REPORTED)
+        .map(|(key, n, _)| format!("{} ({} submissions)", display(key), n))
+        .collect();
+    codes::warning(
+        WarningCode::IdenticalBallots,
+        format!(
+            "{} identical ballots of {} were dropped (deduplicateIdenticalBallots), the most submitted: {}",
+            num_dropped,
+            path,
+            top.join(", ")
+        ),
+    );
+    res
+}
@@ -0,0 +1,173 @@
+// One row of aggregate statistics per election, in the layout of the FairVote "RCV data"
+// spreadsheet that collects many elections.
+
+use std::fs::OpenOptions;
+
+use crate::rcv::*;
+
+/// The columns of the row, in order.
+pub const FAIRVOTE_COLUMNS: [&str; 16] = [
+    "jurisdiction",
+    "state",
+    "date",
+    "year",
+    "office",
+    "contest",
+    "candidates",
+    "rounds",
+    "winner",
+    "first_round_leader",
+    "winner_first_round_votes",
+    "winner_final_round_votes",
+    "final_round_margin",
+    "first_round_votes",
+    "exhausted_ballots",
+    "exhausted_percent",
+];
+
+fn percent(count: u64, total: u64, rounding: PercentRounding) -> String {
+    if total == 0 {
+        "".to_string()
+    } else {
+        rounding.format(count, total, 2)
+    }
+}
+
+fn tally_of(rs: &RoundStats, name: &str) -> Option<u64> {
+    rs.tally.iter().find(|(n, _)| n == name).map(|(_, c)| *c)
+}
+
+/// The values of the row, in the order of [FAIRVOTE_COLUMNS]. The values that cannot be
+/// computed for this election are empty.
+pub fn fairvote_row(
+    config: &RcvConfig,
+    result: &VotingResult,
+    rounding: PercentRounding,
+) -> Vec<String> {
+    let settings = &config.output_settings;
+    // The informational rounds do not count: the final round is the one that elects the winner.
+    let rounds: Vec<&RoundStats> = result
+        .round_stats
+        .iter()
+        .filter(|rs| !rs.informational)
+        .collect();
+    let first_round = rounds.first();
+    let final_round = rounds.last();
+    let total = |rs: Option<&&RoundStats>| -> Option<u64> {
+        rs.map(|rs| rs.tally.iter().map(|(_, c)| c).sum())
+    };
+    let first_round_votes = total(first_round);
+    let winner: Option<String> = result
+        .winners
+        .as_ref()
+        .filter(|w| w.len() == 1)
+        .map(|w| w[0].clone());
+    // In case of a tie, the first candidate in the order of registration.
+    let first_round_leader = first_round.and_then(|rs| {
+        result
+            .candidates
+            .iter()
+            .filter_map(|c| tally_of(rs, &c.name).map(|count| (count, &c.name)))
+            .rev()
+            .max_by_key(|(count, _)| *count)
+            .map(|(_, name)| name.clone())
+    });
+    let winner_votes = |rs: Option<&&RoundStats>| -> Option<u64> {
+        match (rs, winner.as_ref()) {
+            (Some(rs), Some(w)) => tally_of(rs, w),
+            _ => None,
+        }
+    };
+    let final_round_margin = final_round.and_then(|rs| {
+        let mut counts: Vec<u64> = rs.tally.iter().map(|(_, c)| *c).collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        match counts.as_slice() {
+            [first, second, ..] => Some(first - second),
+            [first] => Some(*first),
+            [] => None,
+        }
+    });
+    // The ballots that stop counting after the first round. The inactive ballots of a round are
+    // the ones inactive at its start: each round exhausts the difference with the next round.
+    let exhausted = first_round.map(|_| {
+        rounds
+            .windows(2)
+            .map(|w| {
+                w[1].inactive_ballots
+                    .total()
+                    .saturating_sub(w[0].inactive_ballots.total())
+            })
+            .sum::<u64>()
+    });
+    let year = settings
+        .contest_date
+        .as_ref()
+        .and_then(|d| d.get(0..4))
+        .filter(|y| y.chars().all(|c| c.is_ascii_digit()))
+        .map(|y| y.to_string());
+    let num = |x: Option<u64>| x.map(|x| x.to_string()).unwrap_or_default();
+
+    vec![
+        settings.contest_juridiction.clone().unwrap_or_default(),
+        // The state is not part of the configuration.
+        "".to_string(),
+        settings.contest_date.clone().unwrap_or_default(),
+        year.unwrap_or_default(),
+        settings.contest_office.clone().unwrap_or_default(),
+        settings.contest_name.clone(),
+        result.candidates.len().to_string(),
+        rounds.len().to_string(),
+        winner.clone().unwrap_or_default(),
+        first_round_leader.unwrap_or_default(),
+        num(winner_votes(first_round)),
+        num(winner_votes(final_round)),
+        num(final_round_margin),
+        num(first_round_votes),
+        num(exhausted),
+        match (exhausted, first_round_votes) {
+            (Some(e), Some(t)) => percent(e, t, rounding),
+            _ => "".to_string(),
+        },
+    ]
+}
+
+/// Appends the row of the election to the given file. The file and its header are created
+/// if needed.
+pub fn append_fairvote_row(path: &str, config: &RcvConfig, result: &VotingResult) -> RcvResult<()> {
+    let row = fairvote_row(config, result, config.output_settings.percent_rounding()?);
+    let missing: Vec<&str> = FAIRVOTE_COLUMNS
+        .iter()
+        .zip(row.iter())
+        .filter(|(_, v)| v.is_empty())
+        .map(|(c, _)| *c)
+        .collect();
+    if !missing.is_empty() {
+        codes::warning(
+            WarningCode::FairVoteMissingColumns,
+            format!(
+                "The FairVote row has no value for the columns: {}",
+                missing.join(", ")
+            ),
+        );
+    }
+
+    let needs_header = fs::metadata(path).map(|m| m.len() == 0).unwrap_or(true);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(csv::Error::from)
+        .context(CsvWriteSnafu { path })?;
+    let mut writer = csv::Writer::from_writer(file);
+    if needs_header {
+        writer
+            .write_record(FAIRVOTE_COLUMNS)
+            .context(CsvWriteSnafu { path })?;
+    }
+    writer.write_record(&row).context(CsvWriteSnafu { path })?;
+    writer
+        .flush()
+        .map_err(csv::Error::from)
+        .context(CsvWriteSnafu { path })?;
+    Ok(())
+}
@@ -0,0 +1,246 @@
+// Generation of self-contained test cases (a configuration, small ballot files and the
+// expected summary) from the configuration of a real election.
+
+use std::path::Path;
+
+use crate::rcv::io_csv::{
+    SIMPLE_CSV_CHALLENGED_LABEL as CHALLENGED_LABEL, SIMPLE_CSV_OVERVOTE_DELIMITER,
+};
+use crate::rcv::*;
+
+/// Chooses at most `max_ballots` ballots, in the order of the file. Every candidate that appears
+/// in the ballots is kept in at least one ballot, even if this requires more ballots than the
+/// maximum. The other ballots are spread evenly over the file.
+pub fn sample_indices(
+    ballots: &[ParsedBallot],
+    candidates: &[RcvCandidate],
+    max_ballots: usize,
+) -> Vec<usize> {
+    if ballots.len() <= max_ballots {
+        return (0..ballots.len()).collect();
+    }
+    let mut missing: HashSet<&str> = candidates.iter().map(|c| c.name.as_str()).collect();
+    let mut selected: BTreeSet<usize> = BTreeSet::new();
+    for (idx, pb) in ballots.iter().enumerate() {
+        let mut found = false;
+        for name in pb.choices.iter().flatten() {
+            found |= missing.remove(name.as_str());
+        }
+        if found {
+            selected.insert(idx);
+        }
+    }
+    let others: Vec<usize> = (0..ballots.len())
+        .filter(|idx| !selected.contains(idx))
+        .collect();
+    let num_others = max_ballots.saturating_sub(selected.len());
+    for k in 0..num_others {
+        selected.insert(others[k * others.len() / num_others]);
+    }
+    selected.into_iter().collect()
+}
+
+// Copies the header rows and the sampled rows of a CSV file. Returns false if the rows do not
+// match the ballots, in which case the ballots are converted instead.
+fn copy_csv_rows(
+    src_path: &str,
+    dest_path: &str,
+    cfs: &FileSource,
+    num_ballots: usize,
+    indices: &[usize],
+) -> RcvResult<bool> {
+    let num_header_rows = cfs.first_vote_row_index()?;
+    let records = io_csv::read_records(src_path)?;
+    if records.len() != num_header_rows + num_ballots {
+        return Ok(false);
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(dest_path)
+        .context(CsvWriteSnafu { path: dest_path })?;
+    let rows = records[..num_header_rows]
+        .iter()
+        .chain(indices.iter().map(|idx| &records[num_header_rows + idx]));
+    for record in rows {
+        writer
+            .write_record(record)
+            .context(CsvWriteSnafu { path: dest_path })?;
+    }
+    writer
+        .flush()
+        .map_err(csv::Error::from)
+        .context(CsvWriteSnafu { path: dest_path })?;
+    Ok(true)
+}
+
+// Writes the sampled ballots in the simple CSV format, and returns the file source to read
+// them. The choices are written as parsed, so that the labels of the source still apply.
+fn convert_ballots(
+    dest_path: &str,
+    file_name: &str,
+    cfs: &FileSource,
+    ballots: &[&ParsedBallot],
+) -> RcvResult<JSValue> {
+    let delimiter = cfs
+        .overvote_delimiter
+        .clone()
+        .unwrap_or_else(|| SIMPLE_CSV_OVERVOTE_DELIMITER.to_string());
+    let with_category = ballots.iter().any(|pb| pb.category.is_some());
+    let with_challenged = ballots.iter().any(|pb| pb.challenged);
+    let num_choices = ballots.iter().map(|pb| pb.choices.len()).max().unwrap_or(0);
+
+    let mut source = json!({
+        "filePath": file_name,
+        "provider": "csv",
+        "firstVoteRowIndex": "1",
+        "idColumnIndex": "1",
+        "countColumnIndex": "2",
+        "overvoteDelimiter": delimiter,
+        "treatBlankAsUndeclaredWriteIn": cfs.treat_blank_as_undeclared_write_in,
+        "overvoteLabel": cfs.overvote_label,
+        "undervoteLabel": cfs.undervote_label,
+        "undeclaredWriteInLabel": cfs.undeclared_write_in_label,
+    });
+    let mut next_column = 3;
+    if with_category {
+        source["categoryColumn"] = json!(next_column.to_string());
+        next_column += 1;
+    }
+    if with_challenged {
+        source["challengedColumn"] = json!(next_column.to_string());
+        source["challengedLabel"] = json!(CHALLENGED_LABEL);
+        next_column += 1;
+    }
+    source["firstVoteColumnIndex"] = json!(next_column.to_string());
+
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_path(dest_path)
+        .context(CsvWriteSnafu { path: dest_path })?;
+    for (idx, pb) in ballots.iter().enumerate() {
+        let mut record: Vec<String> = vec![
+            pb.id.clone().unwrap_or_else(|| format!("{:08}", idx + 1)),
+            pb.count.unwrap_or(1).to_string(),
+        ];
+        if with_category {
+            record.push(pb.category.clone().unwrap_or_default());
+        }
+        if with_challenged {
+            let label = if pb.challenged { CHALLENGED_LABEL } else { "" };
+            record.push(label.to_string());
+        }
+        record.extend(pb.choices.iter().map(|group| group.join(&delimiter)));
+        record.resize(next_column - 1 + num_choices, "".to_string());
+        writer
+            .write_record(&record)
+            .context(CsvWriteSnafu { path: dest_path })?;
+    }
+    writer
+        .flush()
+        .map_err(csv::Error::from)
+        .context(CsvWriteSnafu { path: dest_path })?;
+    Ok(source)
+}
+
+/// Writes a test case in `out_dir`, named after the directory: `<name>_config.json`, the
+/// sampled ballot files and `<name>_expected_summary.json`.
+///
+/// The CSV sources keep their format. The other sources are converted to the simple CSV format,
+/// and their file source is adjusted in the configuration.
+pub fn make_fixture(
+    config_path: &str,
+    out_dir: &str,
+    max_ballots: usize,
+    args_o: &Option<Args>,
+) -> RcvResult<()> {
+    let config_path_o = Some(config_path.to_string());
+    let config = resolve_config(&config_path_o, &None, args_o)?;
+    let root_path = config_root_path(&config_path_o)?;
+    let contents = fs::read_to_string(config_path).context(ConfigOpeningJsonSnafu {})?;
+    let mut config_js: JSValue = serde_json::from_str(&contents).context(ParsingJsonSnafu {})?;
+
+    fs::create_dir_all(out_dir).context(SummaryWriteSnafu { path: out_dir })?;
+    let name = Path::new(out_dir)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context(MissingParentDirSnafu {})?
+        .to_string();
+
+    let mut file_names: HashSet<String> = HashSet::new();
+    for (idx, cfs) in config.cvr_file_sources.iter().enumerate() {
+        let (ballots, candidates) = read_parsed_ballots(
+            root_path.display().to_string(),
+            cfs,
+            Some(&config.candidates),
+        )?;
+        let indices = sample_indices(&ballots, &candidates, max_ballots);
+        info!(
+            "make_fixture: source {}: {} of {} ballots",
+            cfs.file_path,
+            indices.len(),
+            ballots.len()
+        );
+
+        let src_path: PathBuf = [root_path.clone(), PathBuf::from(&cfs.file_path)]
+            .iter()
+            .collect();
+        let is_csv = cfs.provider == "csv" || cfs.provider == "csv_likert";
+        let stem = src_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("ballots");
+        let mut file_name = if is_csv {
+            format!("{}.csv", stem)
+        } else {
+            format!("{}_converted.csv", stem)
+        };
+        if !file_names.insert(file_name.clone()) {
+            file_name = format!("{}_{}", idx + 1, file_name);
+            file_names.insert(file_name.clone());
+        }
+        let dest_path = Path::new(out_dir).join(&file_name).display().to_string();
+
+        let copied = is_csv
+            && copy_csv_rows(
+                &src_path.display().to_string(),
+                &dest_path,
+                cfs,
+                ballots.len(),
+                &indices,
+            )?;
+        let source_js = &mut config_js["cvrFileSources"][idx];
+        if copied {
+            source_js["filePath"] = json!(file_name);
+        } else {
+            let sampled: Vec<&ParsedBallot> = indices.iter().map(|i| &ballots[*i]).collect();
+            *source_js = convert_ballots(&dest_path, &file_name, cfs, &sampled)?;
+        }
+    }
+
+    let fixture_config_path = Path::new(out_dir)
+        .join(format!("{}_config.json", name))
+        .display()
+        .to_string();
+    let pretty_config = serde_json::to_string_pretty(&config_js).context(ParsingJsonSnafu {})?;
+    fs::write(&fixture_config_path, pretty_config).context(SummaryWriteSnafu {
+        path: fixture_config_path.clone(),
+    })?;
+
+    let summary_path = Path::new(out_dir)
+        .join(format!("{}_expected_summary.json", name))
+        .display()
+        .to_string();
+    let config_path_o = Some(fixture_config_path);
+    let config = resolve_config(&config_path_o, &None, &None)?;
+    run_election(
+        config,
+        &RunOptions::from_args(&None)?,
+        &config_path_o,
+        None,
+        Some(summary_path.clone()),
+        true,
+    )?;
+    info!("make_fixture: test case written to {}", out_dir);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+// The handling of Ctrl-C during a tabulation.
+//
+// While the tabulation runs, the first SIGINT sets the cancellation flag of the tabulation: the
+// rounds completed so far are written to the summary with the status "interrupted". The flag is
+// only checked between two rounds. A second SIGINT exits at once, without writing anything.
+// Before and after the tabulation (while the ballots are read, or in the other commands), Ctrl-C
+// stops the program as usual.
+
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Arc;
+
+// The flag set by the signal handler, null when no tabulation runs. A signal handler can only
+// reach static data.
+static FLAG: AtomicPtr<AtomicBool> = AtomicPtr::new(std::ptr::null_mut());
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_signal: libc::c_int) {
+    let flag = FLAG.load(Ordering::SeqCst);
+    // The flag is kept alive by the SigintHandler that stored it.
+    if !flag.is_null() && unsafe { (*flag).swap(true, Ordering::SeqCst) } {
+        // Only async-signal-safe functions may be called here.
+        unsafe { libc::_exit(super::SummaryStatus::Interrupted.exit_code()) };
+    }
+}
+
+/// The handler of SIGINT, installed until it is dropped. On the platforms without signals, the
+/// flag is never set.
+pub struct SigintHandler {
+    flag: Arc<AtomicBool>,
+}
+
+/// Installs the handler of SIGINT, which sets the flag. The default handler is restored when the
+/// returned handler is dropped.
+pub fn install(flag: &Arc<AtomicBool>) -> SigintHandler {
+    let handler = SigintHandler { flag: flag.clone() };
+    FLAG.store(
+        Arc::as_ptr(&handler.flag) as *mut AtomicBool,
+        Ordering::SeqCst,
+    );
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t);
+    }
+    handler
+}
+
+impl Drop for SigintHandler {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+        // Only clears the flag of this handler: another one may have been installed since.
+        let _ = FLAG.compare_exchange(
+            Arc::as_ptr(&self.flag) as *mut AtomicBool,
+            std::ptr::null_mut(),
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use serde_json::value::RawValue;
 use snafu::OptionExt;
 
 use crate::rcv::io_common::{assemble_choices, get_count};
@@ -6,7 +7,10 @@ use std::collections::HashMap;
 
 use crate::rcv::io_common::make_default_id_str;
 
-pub fn read_json(path: String) -> BRcvResult<Vec<ParsedBallot>> {
+/// Reads the ballots of a CDF report. With the `contestId` of the file source, only the contest
+/// with this id is read: the selections of the other contests are skipped without being parsed,
+/// so that a statewide report with hundreds of contests only builds the ballots of one contest.
+pub fn read_json(path: String, cfs: &FileSource) -> BRcvResult<Vec<ParsedBallot>> {
     let contents =
         fs::read_to_string(path.clone()).context(OpeningJsonSnafu { path: path.clone() })?;
 
@@ -15,11 +19,33 @@ pub fn read_json(path: String) -> BRcvResult<Vec<ParsedBallot>> {
 
     let default_id = make_default_id_str(&path);
 
+    // The contests without id are read with any contestId.
+    let contest_filter = cfs.contest_id.as_deref();
+    let is_selected = |contest_id: &Option<String>| match (contest_filter, contest_id) {
+        (Some(selected), Some(id)) => selected == id,
+        _ => true,
+    };
+
     // Mapping from id to candidate name
     let mut candidateids_mapping: HashMap<String, String> = HashMap::new();
     let mut candidate_contest_mapping: HashMap<String, String> = HashMap::new();
-    let e = cvrr.election.get(0).context(CdfParsingJsonSnafu {})?;
-    for c in e.contests.iter() {
+    let e = cvrr.election.first().context(CdfParsingJsonSnafu {})?;
+    if let Some(selected) = contest_filter {
+        let ids: Vec<&str> = e
+            .contests
+            .iter()
+            .filter_map(|c| c.contest_id.as_deref())
+            .collect();
+        ensure!(
+            ids.is_empty() || ids.contains(&selected),
+            CdfMissingContestSnafu {
+                path: path.clone(),
+                contest_id: selected,
+                contests: ids.join(", "),
+            }
+        );
+    }
+    for c in e.contests.iter().filter(|c| is_selected(&c.contest_id)) {
         for cs in c.contest_selection.iter() {
             for cid in cs.candidate_ids.iter() {
                 candidate_contest_mapping.insert(cid.clone(), cs.candidate_selection_id.clone());
@@ -27,9 +53,12 @@ pub fn read_json(path: String) -> BRcvResult<Vec<ParsedBallot>> {
         }
     }
     for c in e.candidates.iter() {
-        let contest_id = candidate_contest_mapping
-            .get(&c.candidate_id)
-            .context(CdfParsingJsonSnafu {})?;
+        let contest_id = match candidate_contest_mapping.get(&c.candidate_id) {
+            Some(contest_id) => contest_id,
+            // A candidate of another contest.
+            None if contest_filter.is_some() => continue,
+            None => return Err(Box::new(RcvError::CdfParsingJson {})),
+        };
         candidateids_mapping.insert(contest_id.clone(), c.candidate_name.clone());
     }
 
@@ -41,10 +70,12 @@ pub fn read_json(path: String) -> BRcvResult<Vec<ParsedBallot>> {
     let mut ballots: Vec<ParsedBallot> = Vec::new();
     for cvr in cvrr.cvr.iter() {
         for snap in cvr.snapshots.iter() {
-            for contest in snap.contests.iter() {
+            for contest in snap.contests.iter().filter(|c| is_selected(&c.contest_id)) {
+                let selections: Vec<CVRContestSelection> =
+                    serde_json::from_str(contest.selection.get()).context(ParsingJsonSnafu {})?;
                 let mut num_votes: Vec<u64> = vec![];
                 let mut ranks: Vec<(String, u32)> = vec![];
-                for selection in contest.selection.iter() {
+                for selection in selections.iter() {
                     let candidate_name = candidateids_mapping
                         .get(&selection.selection_id)
                         .context(CdfParsingJsonSnafu {})?;
@@ -58,6 +89,11 @@ pub fn read_json(path: String) -> BRcvResult<Vec<ParsedBallot>> {
                     id: Some(id),
                     count: get_count(&num_votes),
                     choices: assemble_choices(&ranks),
+                    challenged: false,
+                    category: None,
+                    respondent: None,
+                    precinct: None,
+                    style: None,
                 };
                 ballots.push(b);
             }
@@ -83,24 +119,27 @@ struct CVRContestSelection {
     pub positions: Vec<CVRSelectionPosition>,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
-struct CVRContest {
-    #[serde(rename = "CVRContestSelection")]
-    pub selection: Vec<CVRContestSelection>,
+// The selections are kept as raw JSON, and only parsed for the contest that is read.
+#[derive(Debug, Serialize, Deserialize)]
+struct CVRContest<'a> {
+    #[serde(rename = "ContestId")]
+    pub contest_id: Option<String>,
+    #[serde(rename = "CVRContestSelection", borrow)]
+    pub selection: &'a RawValue,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
-struct CVRSnapshot {
-    #[serde(rename = "CVRContest")]
-    pub contests: Vec<CVRContest>,
+#[derive(Debug, Serialize, Deserialize)]
+struct CVRSnapshot<'a> {
+    #[serde(rename = "CVRContest", borrow)]
+    pub contests: Vec<CVRContest<'a>>,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
-struct Cvr {
+#[derive(Debug, Serialize, Deserialize)]
+struct Cvr<'a> {
     #[serde(rename = "BallotPrePrintedId")]
     pub ballot_id: String,
-    #[serde(rename = "CVRSnapshot")]
-    pub snapshots: Vec<CVRSnapshot>,
+    #[serde(rename = "CVRSnapshot", borrow)]
+    pub snapshots: Vec<CVRSnapshot<'a>>,
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -121,6 +160,8 @@ struct CandidateSelection {
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 struct Contest {
+    #[serde(rename = "@id")]
+    pub contest_id: Option<String>,
     #[serde(rename = "ContestSelection")]
     pub contest_selection: Vec<CandidateSelection>,
 }
@@ -133,10 +174,10 @@ struct Election {
     pub contests: Vec<Contest>,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
-struct CastVoteRecordReport {
+#[derive(Debug, Serialize, Deserialize)]
+struct CastVoteRecordReport<'a> {
     #[serde(rename = "Election")]
     election: Vec<Election>,
-    #[serde(rename = "CVR")]
-    cvr: Vec<Cvr>,
+    #[serde(rename = "CVR", borrow)]
+    cvr: Vec<Cvr<'a>>,
 }
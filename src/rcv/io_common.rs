@@ -1,5 +1,12 @@
+use std::collections::BTreeMap;
 use std::path::Path;
 
+use log::{debug, info};
+
+use crate::rcv::codes::{self, WarningCode};
+use crate::rcv::config_reader::NumberLocale;
+use crate::rcv::RcvError;
+
 fn simplify_file_name(path: &str) -> String {
     Path::new(path)
         .file_name()
@@ -9,21 +16,230 @@ fn simplify_file_name(path: &str) -> String {
         .to_string()
 }
 
+// The longest ballot that assemble_choices builds. A rank beyond it is most likely a sentinel
+// value, and would otherwise allocate a huge ballot of empty choices.
+const MAX_ASSEMBLED_RANK: u32 = 1000;
+
 pub fn assemble_choices(ranks: &[(String, u32)]) -> Vec<Vec<String>> {
     // TODO: print something when the ballot is completely empty
-    let max_sels = ranks.iter().map(|(_, rank)| *rank).max().unwrap_or(0);
+    let max_sels = ranks
+        .iter()
+        .map(|(_, rank)| *rank)
+        .filter(|rank| *rank <= MAX_ASSEMBLED_RANK)
+        .max()
+        .unwrap_or(0);
     let mut choices: Vec<Vec<String>> = vec![];
     for _ in 0..max_sels {
         choices.push(vec![]);
     }
     for (cname, rank) in ranks.iter() {
-        if let Some(elt) = choices.get_mut((rank - 1) as usize) {
-            elt.push(cname.clone());
+        match choices.get_mut((*rank as usize).wrapping_sub(1)) {
+            Some(elt) => elt.push(cname.clone()),
+            None => debug!("assemble_choices: rank {} of {} is ignored", rank, cname),
         }
     }
     choices
 }
 
+/// Checks the ranks of the likert inputs: the ranks outside of 1..=maxRank are dropped, and
+/// counted by value for the warning of the file.
+pub struct RankFilter {
+    max_rank: u32,
+    dropped: BTreeMap<String, u64>,
+    num_duplicates: u64,
+}
+
+impl RankFilter {
+    pub fn new(max_rank: u32) -> RankFilter {
+        RankFilter {
+            max_rank,
+            dropped: BTreeMap::new(),
+            num_duplicates: 0,
+        }
+    }
+
+    /// The rank of a cell, if it is in 1..=maxRank.
+    pub fn check(&mut self, cell: &str, rank: u64) -> Option<u32> {
+        match u32::try_from(rank) {
+            Ok(rank) if rank >= 1 && rank <= self.max_rank => Some(rank),
+            _ => {
+                *self.dropped.entry(cell.trim().to_string()).or_insert(0) += 1;
+                None
+            }
+        }
+    }
+
+    /// Counts the ballots that give the same rank to several candidates. They are kept: the
+    /// rank is an overvote.
+    pub fn check_duplicates(&mut self, ranks: &[(String, u32)]) {
+        let mut seen: Vec<u32> = ranks.iter().map(|(_, rank)| *rank).collect();
+        seen.sort_unstable();
+        if seen.windows(2).any(|w| w[0] == w[1]) {
+            self.num_duplicates += 1;
+        }
+    }
+
+    /// Warns about the dropped ranks, with the number of times each value appeared, and about
+    /// the ballots with duplicate ranks.
+    pub fn warn(&self, path: &str) {
+        if !self.dropped.is_empty() {
+            let mut values: Vec<(&String, &u64)> = self.dropped.iter().collect();
+            values.sort_by(|(v1, n1), (v2, n2)| n2.cmp(n1).then_with(|| v1.cmp(v2)));
+            let histogram: Vec<String> = values
+                .iter()
+                .map(|(v, n)| format!("{:?} appeared {} times", v, n))
+                .collect();
+            codes::warning(
+                WarningCode::RankOutOfRange,
+                format!(
+                    "{}: some ranks are not between 1 and {} (maxRank) and were ignored: {}. \
+                     A value that appears often is likely a sentinel for 'no opinion'",
+                    path,
+                    self.max_rank,
+                    histogram.join(", ")
+                ),
+            );
+        }
+        if self.num_duplicates > 0 {
+            codes::warning(
+                WarningCode::DuplicateRanks,
+                format!(
+                    "{}: {} ballots give the same rank to several candidates, which is an overvote",
+                    path, self.num_duplicates
+                ),
+            );
+        }
+    }
+}
+
+/// Counts the cells of the likert inputs that are not labels of the `choices`, for example the
+/// rank levels added to a form after the configuration was written.
+pub struct UnknownChoices {
+    num_choices: usize,
+    labels: BTreeMap<String, u64>,
+}
+
+impl UnknownChoices {
+    pub fn new(num_choices: usize) -> UnknownChoices {
+        UnknownChoices {
+            num_choices,
+            labels: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, cell: &str) {
+        *self.labels.entry(cell.to_string()).or_insert(0) += 1;
+    }
+
+    /// Fails with the unknown labels and their number of cells, unless they are allowed
+    /// (allowUnknownChoices). They are then dropped with a warning.
+    pub fn finish(&self, path: &str, allow: bool) -> Result<(), Box<RcvError>> {
+        if self.labels.is_empty() {
+            return Ok(());
+        }
+        let mut values: Vec<(&String, &u64)> = self.labels.iter().collect();
+        values.sort_by(|(v1, n1), (v2, n2)| n2.cmp(n1).then_with(|| v1.cmp(v2)));
+        let labels: Vec<String> = values
+            .iter()
+            .map(|(v, n)| format!("{:?} appeared {} times", v, n))
+            .collect();
+        if !allow {
+            return Err(Box::new(RcvError::UnknownChoiceLabels {
+                path: path.to_string(),
+                num_choices: self.num_choices,
+                labels: labels.join(", "),
+            }));
+        }
+        codes::warning(
+            WarningCode::UnknownChoices,
+            format!(
+                "{}: some cells are not among the {} choices and were ignored (allowUnknownChoices): {}",
+                path,
+                self.num_choices,
+                labels.join(", ")
+            ),
+        );
+        Ok(())
+    }
+}
+
+/// Renumbers the ranks of a ballot to consecutive integers starting at 1, keeping the ties:
+/// the ranks 1, 4 and 7 become 1, 2 and 3. Returns true if some ranks changed.
+pub fn compress_ranks(ranks: &mut [(String, u32)]) -> bool {
+    let mut distinct: Vec<u32> = ranks.iter().map(|(_, rank)| *rank).collect();
+    distinct.sort_unstable();
+    distinct.dedup();
+    let mut changed = false;
+    for (_, rank) in ranks.iter_mut() {
+        let compressed = distinct.binary_search(rank).unwrap_or(0) as u32 + 1;
+        changed |= compressed != *rank;
+        *rank = compressed;
+    }
+    changed
+}
+
+pub fn warn_compressed_ranks(path: &str, num_compressed: usize) {
+    if num_compressed > 0 {
+        codes::warning(
+            WarningCode::CompressedRanks,
+            format!(
+                "{} ballots of {} had gaps in their ranks, which were compressed (compressRanks)",
+                num_compressed, path
+            ),
+        );
+    }
+}
+
+/// Logs the number of ballots of a file with a count column that have an empty count, which
+/// are counted once.
+pub fn log_default_counts(path: &str, num_defaults: usize) {
+    if num_defaults > 0 {
+        info!(
+            "{} ballots of {} have an empty count, which defaults to 1",
+            num_defaults, path
+        );
+    }
+}
+
+/// Parses a count or a rank. The whitespaces around the number, including the non-breaking
+/// spaces, are ignored, and the thousands separators of the locale are accepted if they
+/// separate groups of 3 digits. Returns the reason if the cell is not an integer.
+pub fn parse_integer(cell: &str, locale: NumberLocale) -> Result<u64, &'static str> {
+    let s = cell.trim();
+    let spaces = [' ', '\u{a0}', '\u{202f}'];
+    let (separators, decimal): (&[char], Option<char>) = match locale {
+        NumberLocale::Plain => (&[], None),
+        NumberLocale::En => (&[',', ' ', '\u{a0}', '\u{202f}'], Some('.')),
+        NumberLocale::Eu => (&['.', ' ', '\u{a0}', '\u{202f}'], Some(',')),
+    };
+    if s.is_empty() {
+        return Err("empty value");
+    }
+    if decimal.map(|d| s.contains(d)).unwrap_or(false) {
+        return Err("decimal number");
+    }
+    let groups: Vec<&str> = s.split(|c| separators.contains(&c)).collect();
+    if !groups
+        .iter()
+        .all(|g| !g.is_empty() && g.chars().all(|c| c.is_ascii_digit()))
+    {
+        return Err(
+            if s.contains(|c| spaces.contains(&c) || c == '.' || c == ',') {
+                "unexpected separator for this numberLocale"
+            } else {
+                "not an integer"
+            },
+        );
+    }
+    if groups.len() > 1 && (groups[0].len() > 3 || groups[1..].iter().any(|g| g.len() != 3)) {
+        return Err("the thousands separators are not between groups of 3 digits");
+    }
+    groups
+        .concat()
+        .parse::<u64>()
+        .map_err(|_| "number too large")
+}
+
 pub fn get_count(num_votes: &[u64]) -> Option<u64> {
     // TODO: check that all the votes have the same weight
     num_votes.first().cloned()
@@ -1,56 +1,720 @@
-// Primitives for reading CSV files.
+// Primitives for reading and writing CSV files.
 
 use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::thread;
 
-use crate::rcv::io_common::{assemble_choices, make_default_id_lineno};
-use crate::rcv::io_msforms::get_col_index_mapping;
+use regex::Regex;
+
+use crate::rcv::io_common::{
+    assemble_choices, compress_ranks, log_default_counts, make_default_id_lineno, parse_integer,
+    warn_compressed_ranks, RankFilter, UnknownChoices,
+};
+use crate::rcv::io_msforms::{get_col_index_mapping, get_ranked_choices};
+use crate::rcv::time_window::{self, TimeWindow};
 use crate::rcv::*;
 
+// The columns of the ranking format, as 0-based indices.
+struct RankingColumns {
+    id_idx_o: Option<usize>,
+    count_idx_o: Option<usize>,
+    challenged_idx_o: Option<usize>,
+    category_idx_o: Option<usize>,
+    respondent_idx_o: Option<usize>,
+    timestamp_idx_o: Option<usize>,
+    choices_start_col: usize,
+    // With rankColumnsByHeader, the column of each rank, in the order of the ranks.
+    rank_cols_o: Option<Vec<usize>>,
+    number_locale: NumberLocale,
+}
+
+impl RankingColumns {
+    fn new(path: &str, cfs: &FileSource) -> RcvResult<RankingColumns> {
+        let by_header = cfs.rank_columns_by_header == Some(true);
+        ensure!(
+            !by_header || cfs.first_vote_row_index()? > 0,
+            RankColumnsWithoutHeaderSnafu {}
+        );
+        // The category, timestamp and respondent columns may be named in the header, if there
+        // is one.
+        let named = cfs.category_column.is_some()
+            || cfs.timestamp_column.is_some()
+            || cfs.respondent_column.is_some();
+        let header = if (by_header || named) && cfs.first_vote_row_index()? > 0 {
+            header_row(path)?
+        } else {
+            None
+        };
+        let rank_cols_o = match (by_header, header.as_ref()) {
+            (true, Some(header)) => Some(rank_columns(path, header, &cfs.rank_header_regex()?)?),
+            (true, None) => return CsvEmptySnafu {}.fail(),
+            (false, _) => None,
+        };
+        Ok(RankingColumns {
+            id_idx_o: cfs.id_column_index_int()?,
+            count_idx_o: cfs.count_column_index_int()?,
+            challenged_idx_o: cfs.challenged_column_index_int()?,
+            category_idx_o: cfs.category_column_index(header.as_deref())?,
+            respondent_idx_o: cfs.respondent_column_index(header.as_deref())?,
+            timestamp_idx_o: cfs.timestamp_column_index(header.as_deref())?,
+            choices_start_col: cfs.first_vote_column_index()?,
+            rank_cols_o,
+            number_locale: cfs.number_locale()?,
+        })
+    }
+
+    fn indices(&self) -> [Option<usize>; 7] {
+        let last_choice_col = match self.rank_cols_o.as_ref() {
+            Some(cols) => cols.iter().max().cloned(),
+            None => Some(self.choices_start_col),
+        };
+        [
+            self.id_idx_o,
+            self.count_idx_o,
+            self.challenged_idx_o,
+            self.category_idx_o,
+            self.respondent_idx_o,
+            self.timestamp_idx_o,
+            last_choice_col,
+        ]
+    }
+}
+
+// The columns of the ranks, from the headers that match the pattern, in the order of the ranks
+// whatever their position in the file. The ranks must be 1, 2, ... without duplicates or gaps.
+// The other columns are ignored.
+fn rank_columns(path: &str, header: &[String], re: &Regex) -> RcvResult<Vec<usize>> {
+    let invalid = |reason: String| RcvError::RankHeaders {
+        path: path.to_string(),
+        reason,
+    };
+    let mut ranks: Vec<(u64, usize)> = header
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, h)| {
+            let rank = re
+                .captures(h.trim())?
+                .get(1)?
+                .as_str()
+                .parse::<u64>()
+                .ok()?;
+            Some((rank, idx))
+        })
+        .collect();
+    ensure!(
+        !ranks.is_empty(),
+        RankHeadersSnafu {
+            path,
+            reason: format!("are not found: no header matches {}", re.as_str()),
+        }
+    );
+    ranks.sort();
+    for (pos, w) in ranks.iter().enumerate() {
+        let expected = pos as u64 + 1;
+        if pos > 0 && ranks[pos - 1].0 == w.0 {
+            return Err(invalid(format!(
+                "have the rank {} twice: {:?} and {:?}",
+                w.0,
+                header[ranks[pos - 1].1],
+                header[w.1]
+            )));
+        }
+        if w.0 != expected {
+            return Err(invalid(format!(
+                "have a gap: the rank {} is missing before {:?}",
+                expected, header[w.1]
+            )));
+        }
+    }
+    debug!("rank_columns: {:?}", ranks);
+    Ok(ranks.into_iter().map(|(_, idx)| idx).collect())
+}
+
+// The first row of the file.
+fn header_row(path: &str) -> RcvResult<Option<Vec<String>>> {
+    let mut records = get_reader(&path.to_string())?.into_records();
+    match records.next() {
+        Some(header_r) => {
+            let header = header_r.context(CsvLineParseSnafu {})?;
+            Ok(Some(header.iter().map(|s| s.to_string()).collect()))
+        }
+        None => Ok(None),
+    }
+}
+
+// The trimmed text of a column such as the category, if not empty.
+fn non_empty_cell_csv(line: &csv::StringRecord, idx_o: &Option<usize>) -> Option<String> {
+    idx_o
+        .and_then(|idx| line.get(idx))
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+fn ranking_ballot(
+    line: &csv::StringRecord,
+    lineno: usize,
+    columns: &RankingColumns,
+    cfs: &FileSource,
+    get_id: &impl Fn(&csv::StringRecord, &Option<usize>, usize) -> RcvResult<String>,
+) -> RcvResult<ParsedBallot> {
+    let id = get_id(line, &columns.id_idx_o, lineno)?;
+    let count = get_count_csv(line, &columns.count_idx_o, lineno, columns.number_locale)?;
+    let challenged = get_challenged_csv(line, &columns.challenged_idx_o, cfs, lineno)?;
+    let category = non_empty_cell_csv(line, &columns.category_idx_o);
+    let respondent = non_empty_cell_csv(line, &columns.respondent_idx_o);
+
+    let choices_parsed = choice_cells(line, columns);
+    debug!(
+        "read_csv_ranking: lineno: {:?} row: {:?}",
+        lineno, &choices_parsed
+    );
+
+    Ok(ParsedBallot {
+        id: Some(id),
+        count,
+        choices: choices_parsed,
+        challenged,
+        category,
+        respondent,
+        precinct: None,
+        style: None,
+    })
+}
+
+// The choices of a row, one cell per rank.
+fn choice_cells(line: &csv::StringRecord, columns: &RankingColumns) -> Vec<Vec<String>> {
+    let cell = |s: &str| {
+        if s.is_empty() {
+            Vec::new()
+        } else {
+            vec![s.to_string()]
+        }
+    };
+    match columns.rank_cols_o.as_ref() {
+        Some(cols) => cols
+            .iter()
+            .map(|idx| cell(line.get(*idx).unwrap_or_default()))
+            .collect(),
+        None => line
+            .iter()
+            .skip(columns.choices_start_col)
+            .map(cell)
+            .collect(),
+    }
+}
+
+// Appends the choices of a continuation row to the ballot of the previous rows. The count of a
+// continuation row is either empty or the count of the ballot.
+fn append_continuation(
+    ballot: &mut ParsedBallot,
+    line: &csv::StringRecord,
+    lineno: usize,
+    columns: &RankingColumns,
+) -> RcvResult<()> {
+    if let Some(count_idx) = columns.count_idx_o {
+        let cell = line
+            .get(count_idx)
+            .context(CsvLineToShortSnafu { lineno })?;
+        if !cell.trim().is_empty() {
+            let count = parse_cell(cell, lineno, count_idx, columns.number_locale)?;
+            let expected = ballot.count.unwrap_or(1);
+            ensure!(
+                count == expected,
+                ContinuationCountSnafu {
+                    lineno,
+                    count,
+                    expected
+                }
+            );
+        }
+    }
+    debug!(
+        "read_csv_ranking: lineno: {:?} continues the ballot {:?}",
+        lineno, ballot.id
+    );
+    ballot.choices.extend(choice_cells(line, columns));
+    Ok(())
+}
+
 pub fn read_csv_ranking(path: String, cfs: &FileSource) -> BRcvResult<Vec<ParsedBallot>> {
-    let get_id = make_get_id(&path);
+    let columns = RankingColumns::new(&path, cfs)?;
+    // A ballot may span several consecutive rows with the same id.
+    let continuation = cfs.continuation_rows == Some(true);
+    ensure!(
+        !continuation || columns.id_idx_o.is_some(),
+        ContinuationRowsWithoutIdSnafu {}
+    );
+    let mut window_o = TimeWindow::new(cfs, columns.timestamp_idx_o)?;
+    // The chunks of the parallel reader could split a ballot.
+    if let Some(jobs) = cfs
+        .jobs
+        .filter(|jobs| *jobs > 1 && !continuation && window_o.is_none())
+    {
+        if let Some((mut res, num_empty)) = read_csv_ranking_parallel(&path, cfs, &columns, jobs)? {
+            finish_ballots(&path, &mut res, num_empty);
+            check_default_counts(&path, &columns.count_idx_o, &res);
+            return Ok(res);
+        }
+    }
 
-    let id_idx_o = cfs.id_column_index_int()?;
-    let choices_start_col = cfs.first_vote_column_index()?;
-    let count_idx_o = cfs.count_column_index_int()?;
+    let get_id = make_get_id(&path);
+    let mut warned = false;
 
     let mut res: Vec<ParsedBallot> = Vec::new();
+    // The id of the last ballot dropped by the time window, to drop its continuation rows.
+    let mut dropped_id: Option<String> = None;
     // No header expected in the simple format
     let (records, row_offset) = get_records(&path, cfs)?;
 
+    let mut read_row = |line: &csv::StringRecord, lineno: usize| -> BRcvResult<()> {
+        check_column_count(line, &columns.indices(), lineno, &mut warned);
+        if continuation {
+            let id = get_id(line, &columns.id_idx_o, lineno)?;
+            if let Some(prev) = res.last_mut().filter(|pb| pb.id.as_ref() == Some(&id)) {
+                append_continuation(prev, line, lineno, &columns)?;
+                return Ok(());
+            }
+            if dropped_id.as_ref() == Some(&id) {
+                return Ok(());
+            }
+        }
+        if !in_window(&mut window_o, line, lineno) {
+            if continuation {
+                dropped_id = Some(get_id(line, &columns.id_idx_o, lineno)?);
+            }
+            return Ok(());
+        }
+        res.push(ranking_ballot(line, lineno, &columns, cfs, &get_id)?);
+        Ok(())
+    };
+    // The empty rows are held until a row with a cell follows them: only the empty rows at the
+    // end of the file are skipped. The others are blank ballots, as are the rows of a file
+    // without any cell.
+    let mut empty_rows: Vec<(usize, csv::StringRecord)> = Vec::new();
+    let mut has_cells = false;
     for (idx, line_r) in records.enumerate() {
         let lineno = idx + row_offset + 1;
         debug!("{:?} {:?}", lineno, line_r);
         let line = line_r.context(CsvLineParseSnafu {})?;
-        let id = get_id(&line, &id_idx_o, lineno)?;
+        if is_empty_row(&line) {
+            empty_rows.push((lineno, line));
+            continue;
+        }
+        has_cells = true;
+        for (empty_lineno, empty_line) in empty_rows.drain(..) {
+            read_row(&empty_line, empty_lineno)?;
+        }
+        read_row(&line, lineno)?;
+    }
+    if !has_cells {
+        for (empty_lineno, empty_line) in empty_rows.drain(..) {
+            read_row(&empty_line, empty_lineno)?;
+        }
+    }
+    if let Some(window) = window_o.as_ref() {
+        window.finish(&path)?;
+    }
+    finish_ballots(&path, &mut res, empty_rows.len());
+    check_default_counts(&path, &columns.count_idx_o, &res);
+    Ok(res)
+}
+
+// True if all the cells of the row are empty, like the rows at the end of spreadsheet exports.
+// A cell with spaces is not empty: it was typed in. Only the empty rows at the end of the file
+// are skipped.
+fn is_empty_row(line: &csv::StringRecord) -> bool {
+    line.iter().all(|cell| cell.is_empty())
+}
+
+// The spreadsheet exports pad all the rows with empty cells up to the widest row: the empty
+// choices at the end of a ballot are dropped, since they are not skipped rankings. This is done
+// once the continuation rows are appended, whose positions depend on the empty cells. The empty
+// rows skipped at the end of the file are reported in a single warning.
+fn finish_ballots(path: &str, ballots: &mut [ParsedBallot], num_empty: usize) {
+    for pb in ballots.iter_mut() {
+        while pb.choices.last().is_some_and(|group| group.is_empty()) {
+            pb.choices.pop();
+        }
+    }
+    if num_empty > 0 {
+        codes::warning(
+            WarningCode::EmptyRows,
+            format!("{} empty rows of {} are skipped", num_empty, path),
+        );
+    }
+}
+
+// True if the row is kept by the time window of the source, if there is one.
+fn in_window(window_o: &mut Option<TimeWindow>, line: &csv::StringRecord, lineno: usize) -> bool {
+    match window_o.as_mut() {
+        Some(window) => {
+            let cell = line.get(window.column()).unwrap_or_default();
+            window.accept(lineno, cell, time_window::parse_timestamp(cell))
+        }
+        None => true,
+    }
+}
+
+// Splits the file into byte ranges that start at the beginning of a line. A quoted field may
+// contain a newline: the ranges that start inside a quoted field are found once they are read
+// (see Chunk::quotes).
+fn chunk_ranges(path: &str, jobs: usize) -> RcvResult<Vec<(u64, u64)>> {
+    let file = File::open(path)
+        .map_err(csv::Error::from)
+        .context(CsvOpenSnafu {})?;
+    let len = file
+        .metadata()
+        .map_err(csv::Error::from)
+        .context(CsvOpenSnafu {})?
+        .len();
+    let mut reader = BufReader::new(file);
+    let mut bounds: Vec<u64> = vec![0];
+    for k in 1..jobs {
+        let target = len * (k as u64) / (jobs as u64);
+        if target <= *bounds.last().unwrap() {
+            continue;
+        }
+        reader
+            .seek(SeekFrom::Start(target))
+            .map_err(csv::Error::from)
+            .context(CsvOpenSnafu {})?;
+        let mut skipped: Vec<u8> = Vec::new();
+        let num_skipped = reader
+            .read_until(b'\n', &mut skipped)
+            .map_err(csv::Error::from)
+            .context(CsvOpenSnafu {})?;
+        let bound = target + num_skipped as u64;
+        if bound < len {
+            bounds.push(bound);
+        }
+    }
+    bounds.push(len);
+    Ok(bounds.windows(2).map(|w| (w[0], w[1])).collect())
+}
+
+// Counts the quotes of the bytes read from a range.
+struct QuoteCount<R> {
+    inner: R,
+    quotes: u64,
+}
 
-        let count = get_count_csv(&line, &count_idx_o, lineno)?;
+impl<R: Read> Read for QuoteCount<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.quotes += buf[..n].iter().filter(|b| **b == b'"').count() as u64;
+        Ok(n)
+    }
+}
+
+// The error that stopped the reading of a range.
+enum ChunkError {
+    Csv(csv::Error),
+    // The row does not have the number of columns of the rows before it.
+    RowLength(usize),
+    // The row is not a valid ballot. It is read again with its line number in the file, for
+    // the error.
+    Ballot(csv::StringRecord),
+}
+
+// The ballots read from one byte range of the file.
+struct Chunk {
+    // The ballots, with the index of their row in the chunk.
+    ballots: Vec<(usize, ParsedBallot)>,
+    num_rows: usize,
+    // The number of empty rows at the end of the chunk, which are the last ballots.
+    trailing_empty: usize,
+    row_len: Option<usize>,
+    // The first row that misses a configured column: the index of the row in the chunk,
+    // its length and the missing column.
+    first_short: Option<(usize, usize, usize)>,
+    // The number of quotes in the range. The quotes of a quoted field come in pairs: a range
+    // starts inside a quoted field when the ranges before it have an odd number of quotes.
+    quotes: u64,
+    // The index of the row in the chunk, and the error that stopped the reading there.
+    error: Option<(usize, ChunkError)>,
+}
+
+// Reads the ballots of a byte range. The line numbers (and the default ids) are relative to
+// the start of the range. The reading stops at the first error, which is only reported if the
+// range does not start inside a quoted field. The errors of the file itself are returned.
+fn read_chunk(
+    path: &str,
+    (start, end): (u64, u64),
+    first_row: usize,
+    columns: &RankingColumns,
+    cfs: &FileSource,
+) -> std::io::Result<Chunk> {
+    let get_id = make_get_id(path);
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(QuoteCount {
+            inner: file.take(end - start),
+            quotes: 0,
+        });
+    let mut chunk = Chunk {
+        ballots: Vec::new(),
+        num_rows: 0,
+        trailing_empty: 0,
+        row_len: None,
+        first_short: None,
+        quotes: 0,
+        error: None,
+    };
+    let mut line = csv::StringRecord::new();
+    loop {
+        let idx = chunk.num_rows;
+        match reader.read_record(&mut line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                chunk.error = Some((idx, ChunkError::Csv(e)));
+                break;
+            }
+        }
+        // The sequential reader requires all the rows to have the same number of columns.
+        if *chunk.row_len.get_or_insert(line.len()) != line.len() {
+            chunk.error = Some((idx, ChunkError::RowLength(line.len())));
+            break;
+        }
+        chunk.num_rows += 1;
+        if idx < first_row {
+            continue;
+        }
+        if is_empty_row(&line) {
+            chunk.trailing_empty += 1;
+        } else {
+            chunk.trailing_empty = 0;
+        }
+        if chunk.first_short.is_none() {
+            if let Some(max_idx) = missing_column(&line, &columns.indices()) {
+                chunk.first_short = Some((idx, line.len(), max_idx));
+            }
+        }
+        match ranking_ballot(&line, idx + 1, columns, cfs, &get_id) {
+            Ok(pb) => chunk.ballots.push((idx, pb)),
+            Err(_) => {
+                chunk.error = Some((idx, ChunkError::Ballot(line)));
+                break;
+            }
+        }
+    }
+    // The quotes after an error are also counted, for the ranges after this one.
+    let mut counter = reader.into_inner();
+    std::io::copy(&mut counter, &mut std::io::sink())?;
+    chunk.quotes = counter.quotes;
+    Ok(chunk)
+}
+
+// The error of a chunk, with the line number in the file. `num_rows` is the number of rows
+// before the chunk.
+fn chunk_error(
+    path: &str,
+    (idx, error): (usize, ChunkError),
+    num_rows: usize,
+    expected_len: usize,
+    columns: &RankingColumns,
+    cfs: &FileSource,
+) -> RcvError {
+    let lineno = num_rows + idx + 1;
+    match error {
+        ChunkError::Csv(source) => RcvError::CsvChunkLineParse { lineno, source },
+        ChunkError::RowLength(len) => RcvError::CsvRowLength {
+            lineno,
+            len,
+            expected: expected_len,
+        },
+        ChunkError::Ballot(line) => {
+            match ranking_ballot(&line, lineno, columns, cfs, &make_get_id(path)) {
+                Err(e) => e,
+                Ok(_) => unreachable!("the line {} of {} was rejected", lineno, path),
+            }
+        }
+    }
+}
 
-        let choices_parsed: Vec<Vec<String>> = line
+// Reads the ranking format with several threads. The ballots are in the same order and have
+// the same ids as with the sequential reader. Returns them with the number of empty rows
+// skipped at the end of the file.
+// Returns None when the sequential reader should be used instead, because the file cannot be
+// split on lines: a range starts inside a quoted field, or the header is not in the first range.
+fn read_csv_ranking_parallel(
+    path: &str,
+    cfs: &FileSource,
+    columns: &RankingColumns,
+    jobs: usize,
+) -> RcvResult<Option<(Vec<ParsedBallot>, usize)>> {
+    let ranges = chunk_ranges(path, jobs)?;
+    debug!("read_csv_ranking_parallel: ranges: {:?}", ranges);
+    // The header rows are all expected in the first range.
+    let first_row = cfs.first_vote_row_index()?;
+    let mut chunks: Vec<Chunk> = thread::scope(|s| {
+        let handles: Vec<_> = ranges
             .iter()
-            .skip(choices_start_col)
-            .map(|s| {
-                if s.is_empty() {
-                    Vec::new()
-                } else {
-                    vec![s.to_string()]
-                }
+            .enumerate()
+            .map(|(k, range)| {
+                let skipped_rows = if k == 0 { first_row } else { 0 };
+                s.spawn(move || read_chunk(path, *range, skipped_rows, columns, cfs))
             })
             .collect();
-        debug!(
-            "read_csv_ranking: lineno: {:?} row: {:?}",
-            lineno, &choices_parsed
-        );
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("CSV reader thread panicked"))
+            .collect::<std::io::Result<Vec<Chunk>>>()
+    })
+    .map_err(csv::Error::from)
+    .context(CsvOpenSnafu {})?;
+    let mut quotes: u64 = 0;
+    for chunk in chunks.iter() {
+        if quotes % 2 == 1 {
+            info!(
+                "read_csv_ranking_parallel: a range of {} starts inside a quoted field, reading it with a single thread",
+                path
+            );
+            return Ok(None);
+        }
+        quotes += chunk.quotes;
+    }
+    if chunks.len() > 1 && chunks[0].num_rows < first_row && chunks[0].error.is_none() {
+        return Ok(None);
+    }
 
-        let pb = ParsedBallot {
-            id: Some(id),
-            count,
-            choices: choices_parsed,
-        };
-        res.push(pb);
+    // The rows of all the chunks have the length of the first row.
+    let expected_len = chunks.iter().find_map(|c| c.row_len);
+    let mut num_rows = 0;
+    for chunk in chunks.iter_mut() {
+        if let Some(len) = chunk.row_len.filter(|len| Some(*len) != expected_len) {
+            chunk.error = Some((0, ChunkError::RowLength(len)));
+        }
+        if let Some(error) = chunk.error.take() {
+            let expected = expected_len.unwrap_or_default();
+            return Err(chunk_error(path, error, num_rows, expected, columns, cfs));
+        }
+        num_rows += chunk.num_rows;
+    }
+
+    let default_id = make_default_id_lineno(path);
+    let mut res: Vec<ParsedBallot> = Vec::new();
+    let mut num_rows = 0;
+    // The empty rows at the end of the file may span several chunks.
+    let mut num_empty = 0;
+    for chunk in chunks.iter().rev() {
+        num_empty += chunk.trailing_empty;
+        if chunk.trailing_empty < chunk.ballots.len() {
+            break;
+        }
     }
+    let num_ballots: usize = chunks.iter().map(|c| c.ballots.len()).sum();
+    if num_empty == num_ballots {
+        num_empty = 0;
+    }
+    let mut warned = false;
+    for (k, chunk) in chunks.into_iter().enumerate() {
+        if let Some((idx, len, max_idx)) = chunk.first_short {
+            if !warned {
+                warn_short_row(num_rows + idx + 1, len, max_idx);
+                warned = true;
+            }
+        }
+        for (idx, mut pb) in chunk.ballots {
+            // Only the first range starts at the beginning of the file.
+            if k > 0 && columns.id_idx_o.is_none() {
+                pb.id = Some(default_id(num_rows + idx + 1));
+            }
+            res.push(pb);
+        }
+        num_rows += chunk.num_rows;
+    }
+    res.truncate(res.len() - num_empty);
+    Ok(Some((res, num_empty)))
+}
+
+/// The names of the header, from the first choice column, as the candidates of a file with the
+/// ranks in the cells.
+pub fn header_candidates(path: &str, cfs: &FileSource) -> BRcvResult<Vec<RcvCandidate>> {
+    let header: Vec<String> = header_row(path)?.context(CsvEmptySnafu {})?;
+    let res = header
+        .iter()
+        .skip(cfs.first_vote_column_index()?)
+        .map(|h| h.trim())
+        .filter(|h| !h.is_empty())
+        .map(|h| RcvCandidate {
+            name: h.to_string(),
+            code: None,
+            excluded: Some(false),
+            inferred: true,
+        })
+        .collect();
     Ok(res)
 }
 
+/// The minimum fraction of the choices that must be ranks to suggest the ranks-in-cells layout.
+const RANKS_IN_CELLS_RATE: f64 = 0.9;
+
+/// Warns if a csv file seems to have the ranks in the cells: the header of the choice columns
+/// has names, and almost all the choices are numbers between 1 and the number of columns.
+/// With the default `firstVoteRowIndex`, the header is the first ballot, which is not counted.
+pub fn suggest_ranks_in_cells(
+    path: &str,
+    cfs: &FileSource,
+    ballots: &[ParsedBallot],
+    candidate_names: &[String],
+) -> RcvResult<()> {
+    let header_is_ballot = cfs.first_vote_row_index()? == 0;
+    let header = match header_row(path)? {
+        Some(h) => h,
+        None => return Ok(()),
+    };
+    let names: Vec<&str> = header
+        .iter()
+        .skip(cfs.first_vote_column_index()?)
+        .map(|h| h.trim())
+        .filter(|h| !h.is_empty())
+        .collect();
+    if names.is_empty() || names.iter().any(|h| h.parse::<f64>().is_ok()) {
+        return Ok(());
+    }
+    let max_rank = names.len() as u64;
+    let mut num_cells: u64 = 0;
+    let mut num_ranks: u64 = 0;
+    for cell in ballots
+        .iter()
+        .skip(header_is_ballot as usize)
+        .flat_map(|pb| pb.choices.iter().flatten())
+    {
+        num_cells += 1;
+        let is_rank = !candidate_names.contains(cell)
+            && matches!(cell.trim().parse::<u64>(), Ok(r) if r >= 1 && r <= max_rank);
+        if is_rank {
+            num_ranks += 1;
+        }
+    }
+    if num_cells > 0 && (num_ranks as f64) >= RANKS_IN_CELLS_RATE * (num_cells as f64) {
+        codes::warning(
+            WarningCode::RanksInCells,
+            format!(
+                "{}: {} of {} choices are numbers between 1 and {}, and the header has names ({}). \
+                 If the cells contain the ranks of the candidates of the header, use \
+                 \"layout\": \"ranks-in-cells\" or --likert{}",
+                path,
+                num_ranks,
+                num_cells,
+                max_rank,
+                names.iter().take(3).cloned().collect::<Vec<_>>().join(", "),
+                if header_is_ballot {
+                    " (the header is read as a ballot, the layout starts at the second row)"
+                } else {
+                    ""
+                }
+            ),
+        );
+    }
+    Ok(())
+}
+
 pub fn read_csv_likert(
     path: String,
     cfs: &FileSource,
@@ -60,18 +724,43 @@ pub fn read_csv_likert(
 
     let id_idx_o = cfs.id_column_index_int()?;
     let count_idx_o = cfs.count_column_index_int()?;
+    let challenged_idx_o = cfs.challenged_column_index_int()?;
 
+    let header: Vec<String> = header_row(&path)?.context(CsvEmptySnafu {})?;
+    let category_idx_o = cfs.category_column_index(Some(&header))?;
+    let respondent_idx_o = cfs.respondent_column_index(Some(&header))?;
+    let timestamp_idx_o = cfs.timestamp_column_index(Some(&header))?;
+    let mut window_o = TimeWindow::new(cfs, timestamp_idx_o)?;
     let mappings: Vec<(usize, String)> = {
-        // has_header=false because we want to read the header
-        let reader = get_reader(&path)?;
-        let header_r = reader.into_records().next().context(CsvEmptySnafu {})?;
-        let header = header_r.context(CsvLineParseSnafu {})?;
-        let col_names: Vec<Option<String>> =
-            header.into_iter().map(|s| Some(s.to_string())).collect();
-        get_col_index_mapping(candidate_names, &col_names)?
+        let col_names: Vec<Option<String>> = header.iter().map(|s| Some(s.clone())).collect();
+        get_col_index_mapping(
+            candidate_names,
+            &col_names,
+            cfs.candidate_header_regex()?.as_ref(),
+        )?
     };
     debug!("read_csv_likert: mappings: {:?}", &mappings);
 
+    let number_locale = cfs.number_locale()?;
+    let configured_indices = [
+        id_idx_o,
+        count_idx_o,
+        challenged_idx_o,
+        category_idx_o,
+        respondent_idx_o,
+        timestamp_idx_o,
+    ];
+    let mut warned = false;
+    let mut num_compressed = 0;
+    let mut rank_filter = RankFilter::new(cfs.max_rank.unwrap_or(mappings.len() as u32));
+    // With choices, the cells are their labels instead of the numbers of the ranks.
+    let ranked_choices_o: Option<HashMap<String, u32>> = match cfs.choices {
+        Some(_) => Some(get_ranked_choices(cfs)?.into_iter().collect()),
+        None => None,
+    };
+    let mut unknown_choices =
+        UnknownChoices::new(ranked_choices_o.as_ref().map(|m| m.len()).unwrap_or(0));
+
     let mut res: Vec<ParsedBallot> = Vec::new();
 
     let (records, row_offset) = get_records(&path, cfs)?;
@@ -79,8 +768,15 @@ pub fn read_csv_likert(
         let lineno = idx + row_offset + 1;
         debug!("{:?} {:?}", lineno, line_r);
         let line = line_r.context(CsvLineParseSnafu {})?;
+        check_column_count(&line, &configured_indices, lineno, &mut warned);
+        if !in_window(&mut window_o, &line, lineno) {
+            continue;
+        }
         let id = get_id(&line, &id_idx_o, lineno)?;
-        let count = get_count_csv(&line, &count_idx_o, lineno)?;
+        let count = get_count_csv(&line, &count_idx_o, lineno, number_locale)?;
+        let challenged = get_challenged_csv(&line, &challenged_idx_o, cfs, lineno)?;
+        let category = non_empty_cell_csv(&line, &category_idx_o);
+        let respondent = non_empty_cell_csv(&line, &respondent_idx_o);
 
         let mut ranks: Vec<(String, u32)> = Vec::new();
         for (pos, cname) in mappings.iter() {
@@ -88,15 +784,27 @@ pub fn read_csv_likert(
                 .get(*pos)
                 .context(CsvLineToShortSnafu { lineno })?
                 .trim();
-            if !rank_str.is_empty() {
-                let rank = rank_str
-                    .parse::<u32>()
-                    // TODO: could return the parsing error as well
-                    .ok()
-                    .context(LineParseSnafu { lineno, col: *pos })?;
+            if rank_str.is_empty() {
+                continue;
+            }
+            let rank = match ranked_choices_o.as_ref() {
+                Some(ranked_choices) => match ranked_choices.get(rank_str) {
+                    Some(rank) => *rank as u64,
+                    None => {
+                        unknown_choices.record(rank_str);
+                        continue;
+                    }
+                },
+                None => parse_cell(rank_str, lineno, *pos, number_locale)?,
+            };
+            if let Some(rank) = rank_filter.check(rank_str, rank) {
                 ranks.push((cname.clone(), rank));
             }
         }
+        rank_filter.check_duplicates(&ranks);
+        if cfs.compress_ranks == Some(true) && compress_ranks(&mut ranks) {
+            num_compressed += 1;
+        }
 
         let choices_parsed = assemble_choices(&ranks);
 
@@ -104,33 +812,118 @@ pub fn read_csv_likert(
             id: Some(id),
             count,
             choices: choices_parsed,
+            challenged,
+            category,
+            respondent,
+            precinct: None,
+            style: None,
         };
         res.push(pb);
     }
+    unknown_choices.finish(&path, cfs.allow_unknown_choices == Some(true))?;
+    if let Some(window) = window_o.as_ref() {
+        window.finish(&path)?;
+    }
+    warn_compressed_ranks(&path, num_compressed);
+    rank_filter.warn(&path);
+    check_default_counts(&path, &count_idx_o, &res);
     Ok(res)
 }
 
+// The index of a configured column that the row does not contain, if any.
+fn missing_column(line: &csv::StringRecord, indices: &[Option<usize>]) -> Option<usize> {
+    indices
+        .iter()
+        .flatten()
+        .max()
+        .filter(|max_idx| **max_idx >= line.len())
+        .cloned()
+}
+
+fn warn_short_row(lineno: usize, len: usize, max_idx: usize) {
+    codes::warning(
+        WarningCode::ShortRow,
+        format!(
+            "row {} has {} columns, but the configuration refers to column {}",
+            lineno,
+            len,
+            max_idx + 1
+        ),
+    );
+}
+
+// Warns once, at the first row that does not contain all the configured columns.
+fn check_column_count(
+    line: &csv::StringRecord,
+    indices: &[Option<usize>],
+    lineno: usize,
+    warned: &mut bool,
+) {
+    if *warned {
+        return;
+    }
+    if let Some(max_idx) = missing_column(line, indices) {
+        warn_short_row(lineno, line.len(), max_idx);
+        *warned = true;
+    }
+}
+
+fn get_challenged_csv(
+    line: &csv::StringRecord,
+    challenged_idx_o: &Option<usize>,
+    cfs: &FileSource,
+    lineno: usize,
+) -> RcvResult<bool> {
+    if let Some(challenged_idx) = challenged_idx_o {
+        let cell = line
+            .get(*challenged_idx)
+            .context(CsvLineToShortSnafu { lineno })?;
+        Ok(cfs.is_challenged(cell))
+    } else {
+        Ok(false)
+    }
+}
+
+// Parses an integer cell, with the position of the cell in the error.
+fn parse_cell(cell: &str, lineno: usize, idx: usize, locale: NumberLocale) -> RcvResult<u64> {
+    parse_integer(cell, locale).map_err(|reason| RcvError::InvalidNumber {
+        lineno,
+        col: idx + 1,
+        content: cell.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+// The count of a row. An empty count cell is the default count of 1, and is left as None to be
+// reported by check_default_counts.
 fn get_count_csv(
     line: &csv::StringRecord,
     count_idx_o: &Option<usize>,
     lineno: usize,
+    locale: NumberLocale,
 ) -> RcvResult<Option<u64>> {
     let count: Option<u64> = if let Some(count_idx) = count_idx_o {
-        line.get(count_idx - 1)
-            .context(CsvLineToShortSnafu { lineno })?
-            .parse::<u64>()
-            .ok()
-            .map(Some)
-            .context(LineParseSnafu {
-                lineno,
-                col: *count_idx,
-            })?
+        let cell = line
+            .get(*count_idx)
+            .context(CsvLineToShortSnafu { lineno })?;
+        if cell.trim().is_empty() {
+            None
+        } else {
+            Some(parse_cell(cell, lineno, *count_idx, locale)?)
+        }
     } else {
         Some(1)
     };
     Ok(count)
 }
 
+fn check_default_counts(path: &str, count_idx_o: &Option<usize>, ballots: &[ParsedBallot]) {
+    if count_idx_o.is_some() {
+        let num_defaults = ballots.iter().filter(|pb| pb.count.is_none()).count();
+        log_default_counts(path, num_defaults);
+    }
+}
+
 fn make_get_id(
     path: &str,
 ) -> impl Fn(&csv::StringRecord, &Option<usize>, usize) -> RcvResult<String> {
@@ -154,6 +947,14 @@ fn get_reader(path: &String) -> RcvResult<csv::Reader<File>> {
         .context(CsvOpenSnafu {})
 }
 
+/// Reads all the rows of a CSV file, including the header rows.
+pub fn read_records(path: &str) -> RcvResult<Vec<csv::StringRecord>> {
+    get_reader(&path.to_string())?
+        .into_records()
+        .collect::<Result<Vec<_>, _>>()
+        .context(CsvLineParseSnafu {})
+}
+
 fn get_records(
     path: &String,
     cfs: &FileSource,
@@ -168,3 +969,248 @@ fn get_records(
     }
     Ok((records, first_row))
 }
+
+/// Writes the rounds as a flat table (see [VotingResult::to_flat_rows]), with a header. The
+/// columns of the rows that are not transfers are empty.
+pub fn write_flat_csv(path: &str, result: &VotingResult, order: &NameOrder) -> RcvResult<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_path(path)
+        .context(CsvWriteSnafu { path })?;
+    writer
+        .write_record([
+            "round",
+            "candidate",
+            "votes",
+            "status",
+            "transfer_to",
+            "transfer_votes",
+        ])
+        .context(CsvWriteSnafu { path })?;
+    for row in result.to_flat_rows_by(|n1, n2| order.compare(n1, n2)) {
+        writer
+            .write_record(&[
+                row.round.to_string(),
+                row.candidate,
+                row.votes.to_string(),
+                row.status,
+                row.transfer_to.unwrap_or_default(),
+                row.transfer_votes
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+            ])
+            .context(CsvWriteSnafu { path })?;
+    }
+    writer
+        .flush()
+        .map_err(csv::Error::from)
+        .context(CsvWriteSnafu { path })?;
+    Ok(())
+}
+
+/// The delimiter of the overvotes when the choices are written as parsed, in the converted
+/// ballots of the test cases.
+pub const SIMPLE_CSV_OVERVOTE_DELIMITER: &str = "|";
+
+/// The label used to mark undeclared write-ins when writing the simple CSV format.
+/// It is already understood by the readers.
+pub const SIMPLE_CSV_UWI_LABEL: &str = "UWI";
+
+/// The label of the overvotes in the simple CSV format, if the file sources do not have one.
+pub const SIMPLE_CSV_OVERVOTE_LABEL: &str = "__OVERVOTE__";
+
+/// The label of the undervotes in the simple CSV format, if the file sources do not have one. The
+/// undervotes are never empty cells, which the readers drop at the end of a ballot.
+pub const SIMPLE_CSV_UNDERVOTE_LABEL: &str = "__UNDERVOTE__";
+
+/// The cell of the blank choices in the simple CSV format: a space, which the readers keep as a
+/// blank, while an empty cell is an undervote.
+pub const SIMPLE_CSV_BLANK: &str = " ";
+
+/// The label of the challenged ballots in the simple CSV format.
+pub const SIMPLE_CSV_CHALLENGED_LABEL: &str = "challenged";
+
+/// The labels of the overvotes and of the undervotes in the simple CSV format.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct SimpleCsvLabels {
+    pub overvote: String,
+    pub undervote: String,
+}
+
+impl SimpleCsvLabels {
+    /// The labels of the file sources, if they all have the same non-empty label, and the
+    /// defaults otherwise.
+    pub fn from_sources(sources: &[FileSource]) -> SimpleCsvLabels {
+        let shared = |label: fn(&FileSource) -> &Option<String>| {
+            let first = sources.first().and_then(|s| label(s).clone())?;
+            let same = sources.iter().all(|s| label(s).as_ref() == Some(&first));
+            Some(first).filter(|l| same && !l.trim().is_empty())
+        };
+        SimpleCsvLabels {
+            overvote: shared(|s| &s.overvote_label)
+                .unwrap_or_else(|| SIMPLE_CSV_OVERVOTE_LABEL.to_string()),
+            undervote: shared(|s| &s.undervote_label)
+                .unwrap_or_else(|| SIMPLE_CSV_UNDERVOTE_LABEL.to_string()),
+        }
+    }
+}
+
+/// Writes validated ballots in the simple CSV format: an id column, a count column, a column with
+/// the challenged ballots if there are some, and then one column per choice. The id is the id of
+/// the ballot in its source, or its position in the export if it has none. All the rows are
+/// padded to the same length with empty cells, which the readers drop at the end of the ballots.
+///
+/// Returns the file source that reads the same ballots back, with `file_name` as its path. The
+/// undervotes have a label, so that the ones at the end of a ballot are kept.
+pub fn write_simple_csv(
+    path: &str,
+    file_name: &str,
+    ballots: &[Ballot],
+    labels: &SimpleCsvLabels,
+) -> RcvResult<JSValue> {
+    let with_challenged = ballots.iter().any(|b| b.challenged);
+    let num_choices = ballots
+        .iter()
+        .map(|b| b.candidates.len())
+        .max()
+        .unwrap_or(0);
+    let num_columns = num_choices + 2 + with_challenged as usize;
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_path(path)
+        .context(CsvWriteSnafu { path })?;
+    for (idx, ballot) in ballots.iter().enumerate() {
+        let id = ballot
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("{:08}", idx + 1));
+        let mut record: Vec<String> = vec![id, ballot.count.to_string()];
+        if with_challenged {
+            let label = if ballot.challenged {
+                SIMPLE_CSV_CHALLENGED_LABEL
+            } else {
+                ""
+            };
+            record.push(label.to_string());
+        }
+        for choice in ballot.candidates.iter() {
+            let cell = match choice {
+                BallotChoice::Candidate(name) => name.clone(),
+                BallotChoice::UndeclaredWriteIn => SIMPLE_CSV_UWI_LABEL.to_string(),
+                BallotChoice::Overvote => labels.overvote.clone(),
+                BallotChoice::Undervote => labels.undervote.clone(),
+                BallotChoice::Blank => SIMPLE_CSV_BLANK.to_string(),
+            };
+            record.push(cell);
+        }
+        record.resize(num_columns, "".to_string());
+        writer
+            .write_record(&record)
+            .context(CsvWriteSnafu { path })?;
+    }
+    writer
+        .flush()
+        .map_err(csv::Error::from)
+        .context(CsvWriteSnafu { path })?;
+
+    let mut source = json!({
+        "filePath": file_name,
+        "provider": "csv",
+        "idColumnIndex": "1",
+        "countColumnIndex": "2",
+        "firstVoteColumnIndex": (3 + with_challenged as usize).to_string(),
+        "overvoteLabel": labels.overvote,
+        "undervoteLabel": labels.undervote,
+    });
+    if with_challenged {
+        source["challengedColumn"] = json!("3");
+        source["challengedLabel"] = json!(SIMPLE_CSV_CHALLENGED_LABEL);
+    }
+    Ok(source)
+}
+
+/// The path of the configuration written next to a simple CSV export: `ballots.csv` has
+/// `ballots_config.json`.
+pub fn simple_csv_config_path(path: &str) -> String {
+    let export_path = Path::new(path);
+    let stem = export_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("ballots");
+    export_path
+        .with_file_name(format!("{}_config.json", stem))
+        .display()
+        .to_string()
+}
+
+// Whether the configuration was written by a previous export to `path`: its only file source is
+// this export.
+fn is_simple_csv_config(path: &str, config_path: &str) -> bool {
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let sources = fs::read_to_string(config_path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<JSValue>(&s).ok())
+        .and_then(|js| js.get("cvrFileSources").cloned());
+    match sources
+        .as_ref()
+        .and_then(|s| s.as_array())
+        .map(|s| s.as_slice())
+    {
+        Some([source]) => source["filePath"] == json!(file_name) && source["provider"] == "csv",
+        _ => false,
+    }
+}
+
+/// Checks that the configuration of a simple CSV export can be written. An existing file is only
+/// replaced if a previous export to the same path wrote it, and if it is not the configuration of
+/// the election itself (`election_config_o`).
+pub fn check_simple_csv_config(path: &str, election_config_o: Option<&str>) -> RcvResult<()> {
+    let config_path = simple_csv_config_path(path);
+    if !Path::new(&config_path).exists() {
+        return Ok(());
+    }
+    let canonical = |p: &str| fs::canonicalize(p).ok();
+    let is_election_config = election_config_o
+        .is_some_and(|c| canonical(c).is_some() && canonical(c) == canonical(&config_path));
+    ensure!(
+        !is_election_config && is_simple_csv_config(path, &config_path),
+        ExportConfigExistsSnafu { path: config_path }
+    );
+    Ok(())
+}
+
+/// Writes the configuration that tabulates a simple CSV export next to it (see
+/// [simple_csv_config_path]): the configuration of the election, with `source` as its only file
+/// source. The counts of the export are already weighted, so the weights are left out. An
+/// existing file is only replaced as allowed by [check_simple_csv_config]. Returns the path of
+/// the configuration.
+pub fn write_simple_csv_config(
+    path: &str,
+    config: &RcvConfig,
+    source: JSValue,
+    election_config_o: Option<&str>,
+) -> RcvResult<String> {
+    check_simple_csv_config(path, election_config_o)?;
+    let config_path = simple_csv_config_path(path);
+    let mut config_js = serde_json::to_value(config)
+        .map_err(std::io::Error::from)
+        .context(ExportConfigWriteSnafu {
+            path: config_path.clone(),
+        })?;
+    config_js["cvrFileSources"] = json!([source]);
+    if let Some(obj) = config_js.as_object_mut() {
+        obj.remove("weights");
+    }
+    let file = fs::File::create(&config_path).context(ExportConfigWriteSnafu {
+        path: config_path.clone(),
+    })?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &config_js)
+        .map_err(std::io::Error::from)
+        .context(ExportConfigWriteSnafu {
+            path: config_path.clone(),
+        })?;
+    Ok(config_path)
+}
@@ -1,12 +1,14 @@
-use snafu::OptionExt;
-
 use crate::rcv::{
     io_common::{assemble_choices, get_count},
     *,
 };
 use std::collections::HashMap;
 
-pub fn read_dominion(path: &str) -> BRcvResult<Vec<ParsedBallot>> {
+pub fn read_dominion(
+    path: &str,
+    cfs: &FileSource,
+    candidates: &[RcvCandidate],
+) -> BRcvResult<Vec<ParsedBallot>> {
     let manifest: CandidateManifest = {
         let p: PathBuf = [path, "CandidateManifest.json"].iter().collect();
         let cvr_export_path = p.as_path().display().to_string();
@@ -21,6 +23,23 @@ pub fn read_dominion(path: &str) -> BRcvResult<Vec<ParsedBallot>> {
         debug!("Read rank file {:?}", cvr_export_path);
         serde_json::from_str(contents.as_str()).context(ParsingJsonSnafu {})?
     };
+    // A manifest that was not fully downloaded may still be valid JSON.
+    if let Some(expected) = cfs.expected_candidate_count {
+        ensure!(
+            manifest.candidates.len() == expected,
+            CandidateCountMismatchSnafu {
+                path,
+                expected,
+                found: manifest.candidates.len(),
+                names: manifest
+                    .candidates
+                    .iter()
+                    .map(|c| format!("{:?}", c.name))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            }
+        );
+    }
 
     let cvrr: CvrExport = {
         let p: PathBuf = [path, "CvrExport.json"].iter().collect();
@@ -36,12 +55,14 @@ pub fn read_dominion(path: &str) -> BRcvResult<Vec<ParsedBallot>> {
 
     let mut candidate_id_mapping: HashMap<u32, String> = HashMap::new();
     for c in manifest.candidates.iter() {
-        candidate_id_mapping.insert(c.id, c.name.clone());
+        candidate_id_mapping.insert(c.id, manifest_name(c, candidates));
     }
 
     debug!("candidate_id_mapping {:?}", candidate_id_mapping);
 
     let mut ballots: Vec<ParsedBallot> = vec![];
+    // The number of marks of each candidate id that is not in the manifest.
+    let mut missing_ids: BTreeMap<u32, u64> = BTreeMap::new();
 
     // Very simple parsing for now, assuming that there is a single contest.
     for s in cvrr.sessions.iter() {
@@ -51,9 +72,13 @@ pub fn read_dominion(path: &str) -> BRcvResult<Vec<ParsedBallot>> {
             for contest in card.contests.iter() {
                 for mark in contest.marks.iter() {
                     debug!("mark {:?}", mark);
-                    let candidate_name = candidate_id_mapping
-                        .get(&mark.candidate_id)
-                        .context(DominionParsingJsonSnafu {})?;
+                    let candidate_name = match candidate_id_mapping.get(&mark.candidate_id) {
+                        Some(name) => name,
+                        None => {
+                            *missing_ids.entry(mark.candidate_id).or_insert(0) += 1;
+                            continue;
+                        }
+                    };
                     // TODO: could use here isvote / isambiguous
                     num_votes.push(1);
                     ranks.push((candidate_name.clone(), mark.rank));
@@ -63,15 +88,62 @@ pub fn read_dominion(path: &str) -> BRcvResult<Vec<ParsedBallot>> {
                 id: None, // TODO
                 count: get_count(&num_votes),
                 choices: assemble_choices(&ranks),
+                challenged: false,
+                category: None,
+                respondent: None,
+                precinct: None,
+                style: None,
             };
             debug!("ballot: {:?}", b.clone());
             ballots.push(b);
         }
     }
+    ensure!(
+        missing_ids.is_empty(),
+        DominionMissingCandidateIdSnafu {
+            path,
+            ids: missing_ids
+                .iter()
+                .map(|(id, n)| format!("{} ({} marks)", id, n))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    );
 
     Ok(ballots)
 }
 
+// The name of a candidate of the manifest. Some manifests have a short code in the Description,
+// and the name in the ExternalId. The Description or the ExternalId that is the name of a
+// declared candidate is used first, then the declared candidate with one of them as its code.
+// Without a match, the name is the Description.
+fn manifest_name(c: &Candidate, candidates: &[RcvCandidate]) -> String {
+    let external_id = match c.external_id.as_ref() {
+        Some(JSValue::String(s)) => Some(s.clone()),
+        Some(JSValue::Number(n)) => Some(n.to_string()),
+        _ => None,
+    };
+    let fields: Vec<&str> = std::iter::once(c.name.as_str())
+        .chain(external_id.as_deref())
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let by_name = fields
+        .iter()
+        .find_map(|f| candidates.iter().find(|d| d.name.trim() == *f));
+    let by_code = || {
+        fields.iter().find_map(|f| {
+            candidates
+                .iter()
+                .find(|d| d.code.as_deref().map(|code| code.trim()) == Some(*f))
+        })
+    };
+    match by_name.or_else(by_code) {
+        Some(declared) => declared.name.clone(),
+        None => c.name.clone(),
+    }
+}
+
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 struct Mark {
     #[serde(rename = "CandidateId")]
@@ -116,6 +188,9 @@ struct Candidate {
     pub name: String,
     #[serde(rename = "Id")]
     pub id: u32,
+    // A string or a number, when the manifest has it.
+    #[serde(rename = "ExternalId")]
+    pub external_id: Option<JSValue>,
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
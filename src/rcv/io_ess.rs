@@ -1,15 +1,13 @@
 use snafu::OptionExt;
 
-use crate::rcv::{io_common::make_default_id_lineno, *};
+use crate::rcv::{
+    io_common::{log_default_counts, make_default_id_lineno, parse_integer},
+    io_msforms::get_range,
+    *,
+};
 
 pub fn read_excel_file(path: String, cfs: &FileSource) -> BRcvResult<Vec<ParsedBallot>> {
-    let p = path.clone();
-    let mut workbook: Xlsx<_> =
-        open_workbook(p).context(OpeningExcelSnafu { path: path.clone() })?;
-    let wrange = workbook
-        .worksheet_range_at(0)
-        .context(EmptyExcelSnafu {})?
-        .context(OpeningExcelSnafu { path: path.clone() })?;
+    let wrange = get_range(&path, cfs)?;
 
     let default_id = make_default_id_lineno(&path);
 
@@ -17,59 +15,148 @@ pub fn read_excel_file(path: String, cfs: &FileSource) -> BRcvResult<Vec<ParsedB
     debug!("read_excel_file: header: {:?}", header);
     let start_range = cfs.first_vote_column_index()?;
     debug!("read_excel_file: start_range: {:?}", start_range);
+    // The precinct and the ballot style are among the leading columns, before the votes.
+    let precinct_idx_o = metadata_column(
+        "precinctColumnIndex",
+        cfs.precinct_column_index_int()?,
+        start_range,
+    )?;
+    let style_idx_o = metadata_column(
+        "styleColumnIndex",
+        cfs.style_column_index_int()?,
+        start_range,
+    )?;
 
     let mut iter = wrange.rows();
     // TODO check for correctness
     iter.next();
     let mut res: Vec<ParsedBallot> = Vec::new();
+    let number_locale = cfs.number_locale()?;
+    let mut has_counts = false;
     for (idx, row) in iter.enumerate() {
-        // Not looking at configuration for now: dropping the first column (id) and assuming that the last column is the weight.
-        let choices = &row[start_range..];
-        let mut cs: Vec<Vec<String>> = Vec::new();
+        // The leading columns (id, precinct, ballot style) are before the votes, and the last
+        // column may be the weight.
+        let mut choices = &row[start_range..];
         let num_row_choices = choices.len();
-        for (idx, elt) in choices.iter().enumerate() {
-            let bco = read_choice_calamine2(elt, idx == num_row_choices - 1)?;
-            if let Some(bc) = bco {
-                // TODO: justify why the whitespaces are removed.
-                // This is required for test 2015_portland_mayor.
-                cs.push(vec![bc.trim().to_string()]);
-            }
-        }
-        // Count: look for it at the last cell.
+        // Count: look for it at the last cell. The count is not a choice.
         let last_elt = choices.last().context(EmptyExcelSnafu {})?;
-        // TODO implement count
-        let count: Option<u64> = match last_elt {
-            calamine::DataType::Float(f) => Some(*f as u64),
-            calamine::DataType::Int(i) => Some(*i as u64),
-            calamine::DataType::String(_) => None,
-            calamine::DataType::Empty => None,
-            _ => {
-                return Err(Box::new(RcvError::ExcelWrongCellType {
-                    lineno: (idx + 2) as u64,
-                    content: format!("{:?}", last_elt),
-                }));
-            }
-        };
+        let count = excel_count(
+            last_elt,
+            idx + 2,
+            start_range + num_row_choices,
+            number_locale,
+        )?;
+        if count.is_some() {
+            choices = &choices[..num_row_choices - 1];
+        }
+        has_counts |= count.is_some();
+        let mut cs: Vec<Vec<String>> = Vec::new();
+        for elt in choices.iter() {
+            let bc = read_choice_calamine2(elt)?;
+            // TODO: justify why the whitespaces are removed.
+            // This is required for test 2015_portland_mayor.
+            cs.push(vec![bc.trim().to_string()]);
+        }
         let pb = ParsedBallot {
             id: Some(default_id(idx)),
             count,
             choices: cs,
+            challenged: false,
+            category: None,
+            respondent: None,
+            precinct: metadata_cell(row, precinct_idx_o),
+            style: metadata_cell(row, style_idx_o),
         };
         res.push(pb);
     }
+    // Without any count, the last column is a choice and not a count column.
+    if has_counts {
+        let num_defaults = res.iter().filter(|pb| pb.count.is_none()).count();
+        log_default_counts(&path, num_defaults);
+    }
     Ok(res)
 }
 
-fn read_choice_calamine2(
+// The 0-based index of a column of the ballot metadata, which must be before the votes.
+fn metadata_column(option: &str, idx_o: Option<usize>, first: usize) -> RcvResult<Option<usize>> {
+    match idx_o {
+        Some(idx) if idx >= first => MetadataColumnAfterVotesSnafu {
+            option,
+            column: idx + 1,
+            first: first + 1,
+        }
+        .fail(),
+        _ => Ok(idx_o),
+    }
+}
+
+// The content of a metadata cell, as text. The precincts are often numbers.
+fn metadata_cell(row: &[calamine::DataType], idx_o: Option<usize>) -> Option<String> {
+    let cell = row.get(idx_o?)?;
+    let s = match cell {
+        calamine::DataType::Float(f) if f.fract() == 0.0 => (*f as i64).to_string(),
+        calamine::DataType::Empty => return None,
+        c => c.to_string(),
+    };
+    Some(s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+/// The count in the last cell of a row, as in the CSV files: an empty cell is the default
+/// count (None), and a count with decimals or a negative count is an error. A text cell is a
+/// count if it looks like a number (digits and separators), read with the numberLocale of the
+/// source, and a choice otherwise.
+pub fn excel_count(
     cell: &calamine::DataType,
-    is_last_column: bool,
-) -> RcvResult<Option<String>> {
+    lineno: usize,
+    col: usize,
+    locale: NumberLocale,
+) -> RcvResult<Option<u64>> {
+    let invalid = |content: String, reason: &str| RcvError::InvalidNumber {
+        lineno,
+        col,
+        content,
+        reason: reason.to_string(),
+    };
+    match cell {
+        // A count with decimals is a formatting mistake, not a count to round.
+        calamine::DataType::Float(f) if f.fract() != 0.0 => {
+            Err(invalid(f.to_string(), "decimal number"))
+        }
+        calamine::DataType::Float(f) if *f < 0.0 => Err(invalid(f.to_string(), "negative number")),
+        calamine::DataType::Float(f) if *f >= u64::MAX as f64 => {
+            Err(invalid(f.to_string(), "number too large"))
+        }
+        calamine::DataType::Float(f) => Ok(Some(*f as u64)),
+        calamine::DataType::Int(i) => u64::try_from(*i)
+            .map(Some)
+            .map_err(|_| invalid(i.to_string(), "negative number")),
+        calamine::DataType::String(s) if looks_like_number(s) => parse_integer(s, locale)
+            .map(Some)
+            .map_err(|reason| invalid(s.clone(), reason)),
+        calamine::DataType::String(_) => Ok(None),
+        calamine::DataType::Empty => Ok(None),
+        _ => Err(RcvError::ExcelWrongCellType {
+            lineno: lineno as u64,
+            content: format!("{:?}", cell),
+        }),
+    }
+}
+
+// A text cell with only digits and separators, and at least one digit: a count written as text,
+// for example "1 234".
+fn looks_like_number(s: &str) -> bool {
+    let s = s.trim();
+    s.chars().any(|c| c.is_ascii_digit())
+        && s.chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | ' ' | '\u{a0}' | '\u{202f}'))
+}
+
+// The cells of the choices. The count in the ESS format is the last column, and is already
+// removed (see excel_count).
+fn read_choice_calamine2(cell: &calamine::DataType) -> RcvResult<String> {
     match cell {
-        calamine::DataType::String(s) => Ok(Some(s.clone())),
-        calamine::DataType::Empty => Ok(Some("".to_string())),
-        // The last column may contain the count in the ESS format -> drop it in this case.
-        calamine::DataType::Float(_) if is_last_column => Ok(None),
-        calamine::DataType::Int(_) if is_last_column => Ok(None),
+        calamine::DataType::String(s) => Ok(s.clone()),
+        calamine::DataType::Empty => Ok("".to_string()),
         _ => whatever!(
             "TODO MSG:read_choice_calamine: could not understand cell {:?}",
             cell
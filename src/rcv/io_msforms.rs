@@ -1,19 +1,42 @@
 use calamine::DataType;
+use regex::Regex;
 use std::collections::HashMap;
+use std::io::{Read, Seek};
 
 use crate::rcv::{
-    io_common::{assemble_choices, make_default_id_lineno},
+    io_common::{
+        assemble_choices, compress_ranks, make_default_id_lineno, warn_compressed_ranks,
+        RankFilter, UnknownChoices,
+    },
+    time_window::{excel_timestamp, TimeWindow},
     *,
 };
 
-pub fn read_msforms_ranking(path: String, cfs: &FileSource) -> BRcvResult<Vec<ParsedBallot>> {
-    let default_id = make_default_id_lineno(&path);
+// The separator of the selections of a cell, as in the exports of MS Forms. The overvoteDelimiter
+// of the source replaces it.
+const MULTI_NAME_DELIMITER: &str = ";";
 
-    let wrange = get_range(&path, cfs)?;
+pub fn read_msforms_ranking(path: String, cfs: &FileSource) -> BRcvResult<Vec<ParsedBallot>> {
+    read_worksheets(&path, cfs, |wrange, ids| {
+        read_msforms_ranking_range(&path, wrange, cfs, ids)
+    })
+}
 
+fn read_msforms_ranking_range(
+    path: &str,
+    wrange: &calamine::Range<DataType>,
+    cfs: &FileSource,
+    ids: &BallotIds,
+) -> BRcvResult<Vec<ParsedBallot>> {
     let header = wrange.rows().next().context(EmptyExcelSnafu {})?;
     debug!("read_excel_file: header: {:?}", header);
-    let start_range = cfs.first_vote_column_index()? + 1;
+    let start_range = cfs.first_vote_column_index()?;
+    let category_idx_o = cfs.category_column_index(Some(&header_names(header)))?;
+    let respondent_idx_o = cfs.respondent_column_index(Some(&header_names(header)))?;
+    let mut window_o = TimeWindow::new(
+        cfs,
+        cfs.timestamp_column_index(Some(&header_names(header)))?,
+    )?;
     debug!("read_excel_file: start_range: {:?}", start_range);
 
     let mut iter = wrange.rows();
@@ -27,6 +50,9 @@ pub fn read_msforms_ranking(path: String, cfs: &FileSource) -> BRcvResult<Vec<Pa
             idx,
             &row.get(start_range)
         );
+        if !in_window(&mut window_o, row, idx) {
+            continue;
+        }
 
         // Hardcode the parsing of the row for now. The CSV crate does not help as
         // much as anticipated in these situations.
@@ -34,6 +60,8 @@ pub fn read_msforms_ranking(path: String, cfs: &FileSource) -> BRcvResult<Vec<Pa
         let choices_s = row.get(start_range).context(EmptyExcelSnafu {})?;
         let choices_parsed: Vec<Vec<String>> = match choices_s {
             calamine::DataType::String(s) => s.split(';').map(|s| vec![s.to_string()]).collect(),
+            // The respondent did not rank any option.
+            calamine::DataType::Empty => vec![],
             _ => {
                 return Err(Box::new(RcvError::ExcelWrongCellType {
                     lineno: idx as u64,
@@ -45,13 +73,21 @@ pub fn read_msforms_ranking(path: String, cfs: &FileSource) -> BRcvResult<Vec<Pa
         debug!("read_excel_file: idx: {:?} row: {:?}", idx, &choices_parsed);
 
         let pb = ParsedBallot {
-            id: Some(default_id(idx)),
+            id: Some(ids.id(idx, row)),
             // MS forms are not expected to handle weights for the time being.
             count: Some(1),
             choices: choices_parsed,
+            challenged: false,
+            category: non_empty_cell(row, &category_idx_o),
+            respondent: non_empty_cell(row, &respondent_idx_o),
+            precinct: None,
+            style: None,
         };
         res.push(pb);
     }
+    if let Some(window) = window_o.as_ref() {
+        window.finish(path)?;
+    }
     Ok(res)
 }
 
@@ -60,16 +96,28 @@ pub fn read_msforms_likert(
     cfs: &FileSource,
     candidate_names: &[String],
 ) -> BRcvResult<Vec<ParsedBallot>> {
-    let default_id = make_default_id_lineno(&path);
-
-    let wrange = get_range(&path, cfs)?;
+    read_worksheets(&path, cfs, |wrange, ids| {
+        read_msforms_likert_range(&path, wrange, cfs, candidate_names, ids)
+    })
+}
 
+fn read_msforms_likert_range(
+    path: &str,
+    wrange: &calamine::Range<DataType>,
+    cfs: &FileSource,
+    candidate_names: &[String],
+    ids: &BallotIds,
+) -> BRcvResult<Vec<ParsedBallot>> {
     let header = wrange.rows().next().context(EmptyExcelSnafu {})?;
     debug!("read_msforms_likert: header: {:?}", header);
+    let category_idx_o = cfs.category_column_index(Some(&header_names(header)))?;
+    let respondent_idx_o = cfs.respondent_column_index(Some(&header_names(header)))?;
+    let timestamp_idx_o = cfs.timestamp_column_index(Some(&header_names(header)))?;
+    let mut window_o = TimeWindow::new(cfs, timestamp_idx_o)?;
 
     // Find the mapping between the columns and the candidate names.
     // Every candidate should have its name associated to a column
-    let col_indexes = get_col_index(candidate_names, header)?;
+    let col_indexes = get_col_index(candidate_names, header, cfs)?;
 
     debug!("read_msforms_likert: col_indexes: {:?}", col_indexes);
 
@@ -77,26 +125,43 @@ pub fn read_msforms_likert(
 
     debug!("read_msforms_likert: ranked_choices: {:?}", ranked_choices);
 
+    check_extra_columns(
+        path,
+        wrange,
+        &col_indexes,
+        &category_idx_o,
+        &ranked_choices,
+        cfs,
+    )?;
+
     let mut iter = wrange.rows();
     // TODO check for correctness
     // Not looking at configuration for now: dropping the first column (id) and assuming that the last column is the weight.
     iter.next();
     let mut res: Vec<ParsedBallot> = Vec::new();
+    let mut num_compressed = 0;
+    let mut rank_filter = RankFilter::new(cfs.max_rank.unwrap_or(col_indexes.len() as u32));
+    let mut unknown_choices = UnknownChoices::new(ranked_choices.len());
     for (idx, row) in iter.enumerate() {
         debug!("read_msforms_likert: idx: {:?} row: {:?}", idx, &row);
+        if !in_window(&mut window_o, row, idx) {
+            continue;
+        }
 
         let mut choices: Vec<(String, u32)> = Vec::new();
 
         for (idx, cand_name) in col_indexes.iter() {
             let v: calamine::DataType = row.get(*idx).cloned().context(EmptyExcelSnafu {})?;
             match v {
-                calamine::DataType::String(s) => {
-                    let choice_index = ranked_choices
-                        .get(&s)
-                        .cloned()
-                        .context(EmptyExcelSnafu {})?;
-                    choices.push((cand_name.clone(), choice_index as u32));
-                }
+                calamine::DataType::String(s) if s.trim().is_empty() => {}
+                calamine::DataType::String(s) => match ranked_choices.get(&s) {
+                    Some(choice_index) => {
+                        if let Some(rank) = rank_filter.check(&s, *choice_index as u64) {
+                            choices.push((cand_name.clone(), rank));
+                        }
+                    }
+                    None => unknown_choices.record(&s),
+                },
                 calamine::DataType::Empty => {
                     // No choice made, skip.
                 }
@@ -114,16 +179,31 @@ pub fn read_msforms_likert(
             idx, &choices, &row
         );
 
+        rank_filter.check_duplicates(&choices);
+        if cfs.compress_ranks == Some(true) && compress_ranks(&mut choices) {
+            num_compressed += 1;
+        }
         let choices_parsed = assemble_choices(&choices);
 
         let pb = ParsedBallot {
-            id: Some(default_id(idx)),
+            id: Some(ids.id(idx, row)),
             // MS forms are not expected to handle weights for the time being.
             count: Some(1),
             choices: choices_parsed,
+            challenged: false,
+            category: non_empty_cell(row, &category_idx_o),
+            respondent: non_empty_cell(row, &respondent_idx_o),
+            precinct: None,
+            style: None,
         };
         res.push(pb);
     }
+    unknown_choices.finish(path, cfs.allow_unknown_choices == Some(true))?;
+    if let Some(window) = window_o.as_ref() {
+        window.finish(path)?;
+    }
+    warn_compressed_ranks(path, num_compressed);
+    rank_filter.warn(path);
     Ok(res)
 }
 
@@ -131,11 +211,23 @@ pub fn read_msforms_likert_transpose(
     path: String,
     cfs: &FileSource,
 ) -> BRcvResult<Vec<ParsedBallot>> {
-    let default_id = make_default_id_lineno(&path);
-    let wrange = get_range(&path, cfs)?;
+    read_worksheets(&path, cfs, |wrange, ids| {
+        read_msforms_likert_transpose_range(&path, wrange, cfs, ids)
+    })
+}
 
+fn read_msforms_likert_transpose_range(
+    path: &str,
+    wrange: &calamine::Range<DataType>,
+    cfs: &FileSource,
+    ids: &BallotIds,
+) -> BRcvResult<Vec<ParsedBallot>> {
     let header = wrange.rows().next().context(EmptyExcelSnafu {})?;
     debug!("read_msforms_likert_transpose: header: {:?}", header);
+    let mut window_o = TimeWindow::new(
+        cfs,
+        cfs.timestamp_column_index(Some(&header_names(header)))?,
+    )?;
 
     let ranked_choices: Vec<(String, u32)> = get_ranked_choices(cfs)?;
     let choice_names: Vec<String> = ranked_choices.iter().map(|p| p.0.clone()).collect();
@@ -158,19 +250,42 @@ pub fn read_msforms_likert_transpose(
     // TODO check for correctness
     // Not looking at configuration for now: dropping the first column (id) and assuming that the last column is the weight.
     iter.next();
+    // The names of a cell with several selections, as in the exports of checkbox grids.
+    let delimiter = cfs
+        .overvote_delimiter
+        .as_deref()
+        .unwrap_or(MULTI_NAME_DELIMITER);
     let mut res: Vec<ParsedBallot> = Vec::new();
+    let mut num_compressed = 0;
+    let mut num_multi_names = 0;
     for (idx, row) in iter.enumerate() {
         debug!(
             "read_msforms_likert_transpose: idx: {:?} row: {:?}",
             idx, &row
         );
+        if !in_window(&mut window_o, row, idx) {
+            continue;
+        }
 
         let mut choices: Vec<(String, u32)> = Vec::new();
         for (col_idx, rank) in col_indexes.iter() {
             let v: calamine::DataType = row.get(*col_idx).cloned().context(EmptyExcelSnafu {})?;
             match v {
                 calamine::DataType::String(cand_name) => {
-                    choices.push((cand_name.clone(), *rank));
+                    let names: Vec<&str> = cand_name
+                        .split(delimiter)
+                        .map(|n| n.trim())
+                        .filter(|n| !n.is_empty())
+                        .collect();
+                    match names.as_slice() {
+                        [] => choices.push((cand_name.clone(), *rank)),
+                        [name] => choices.push((name.to_string(), *rank)),
+                        // The names at the same rank are an overvote.
+                        names => {
+                            num_multi_names += 1;
+                            choices.extend(names.iter().map(|n| (n.to_string(), *rank)));
+                        }
+                    }
                 }
                 calamine::DataType::Empty => {
                     // No choice made, skip.
@@ -187,48 +302,141 @@ pub fn read_msforms_likert_transpose(
             "read_msforms_likert_transpose: idx: {:?} choices: {:?}",
             idx, &choices
         );
+        if cfs.compress_ranks == Some(true) && compress_ranks(&mut choices) {
+            num_compressed += 1;
+        }
         let choices_parsed = assemble_choices(&choices);
 
         let pb = ParsedBallot {
-            id: Some(default_id(idx)),
+            id: Some(ids.id(idx, row)),
             // MS forms are not expected to handle weights for the time being.
             count: Some(1),
             choices: choices_parsed,
+            challenged: false,
+            category: None,
+            respondent: None,
+            precinct: None,
+            style: None,
         };
         res.push(pb);
     }
+    if let Some(window) = window_o.as_ref() {
+        window.finish(path)?;
+    }
+    warn_compressed_ranks(path, num_compressed);
+    if num_multi_names > 0 {
+        codes::warning(
+            WarningCode::MultiNameCells,
+            format!(
+                "{} cells of {} have several candidates for the same rank, which are overvotes",
+                num_multi_names, path
+            ),
+        );
+    }
     Ok(res)
 }
 
+// The names of the columns of the header.
+fn header_names(header: &[DataType]) -> Vec<String> {
+    header.iter().map(|c| c.to_string()).collect()
+}
+
+// True if the row is kept by the time window of the source, if there is one. The rows of the
+// error messages start at 1 with the header.
+fn in_window(window_o: &mut Option<TimeWindow>, row: &[DataType], idx: usize) -> bool {
+    match window_o.as_mut() {
+        Some(window) => {
+            let cell = row.get(window.column()).cloned().unwrap_or(DataType::Empty);
+            window.accept(idx + 2, &cell.to_string(), excel_timestamp(&cell))
+        }
+        None => true,
+    }
+}
+
+// The trimmed text of a column such as the category, if not empty.
+fn non_empty_cell(row: &[DataType], idx_o: &Option<usize>) -> Option<String> {
+    idx_o
+        .and_then(|idx| row.get(idx))
+        .map(|c| c.to_string().trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
 /// Given the header of a file (names of each of the columns), and the names of the candidates,
-/// finds the mapping from each candidate to a column index position.
+/// finds the mapping from each candidate to a column index position. With a pattern, the name
+/// of the candidate is extracted from the header first, and the headers that do not match the
+/// pattern are ignored.
 pub fn get_col_index_mapping(
     req_col_names: &[String],
     header: &[Option<String>],
+    pattern: Option<&Regex>,
 ) -> BRcvResult<Vec<(usize, String)>> {
-    let col_names: HashMap<String, usize> = header
+    let names: Vec<Option<String>> = header
         .iter()
-        .enumerate()
-        .filter_map(|(idx, x)| x.as_ref().map(|s| (s.clone(), idx)))
+        .map(|x| match (x, pattern) {
+            (Some(s), Some(re)) => re
+                .captures(s.trim())
+                .and_then(|c| c.get(1))
+                .map(|m| m.as_str().trim().to_string()),
+            (Some(s), None) => Some(s.clone()),
+            (None, _) => None,
+        })
         .collect();
+    // The columns of the candidates must be unique: a pattern may extract the same name from
+    // two questions.
+    let column = |idx: usize| format!("{} ('{}')", idx + 1, header[idx].as_deref().unwrap_or(""));
+    let mut col_names: HashMap<String, usize> = HashMap::new();
+    for (idx, name) in names
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, x)| x.as_ref().map(|s| (idx, s)))
+    {
+        if let Some(first) = col_names.get(name) {
+            if req_col_names.contains(name) {
+                return Err(Box::new(RcvError::DuplicateHeaderName {
+                    name: name.clone(),
+                    first: column(*first),
+                    second: column(idx),
+                }));
+            }
+        } else {
+            col_names.insert(name.clone(), idx);
+        }
+    }
 
     debug!("read_msforms_likert: col_names: {:?}", col_names);
 
-    let mut col_indexes: Vec<(usize, String)> = Vec::new();
-    for cname in req_col_names {
-        let idx = col_names
-            .get(cname)
-            .context(ExcelCannotFindCandidateInHeaderSnafu {
-                candidate_name: cname,
-            })?;
-        col_indexes.push((*idx, cname.clone()));
+    let missing: Vec<&String> = req_col_names
+        .iter()
+        .filter(|cname| !col_names.contains_key(*cname))
+        .collect();
+    if !missing.is_empty() {
+        let unmatched: Vec<&String> = names
+            .iter()
+            .flatten()
+            .filter(|s| !s.is_empty() && !req_col_names.contains(s))
+            .collect();
+        let quoted = |l: Vec<&String>| -> String {
+            l.iter()
+                .map(|s| format!("'{}'", s))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        return Err(Box::new(RcvError::CandidatesNotInHeader {
+            missing: quoted(missing),
+            unmatched: quoted(unmatched),
+        }));
     }
-    Ok(col_indexes)
+
+    Ok(req_col_names
+        .iter()
+        .map(|cname| (col_names[cname], cname.clone()))
+        .collect())
 }
 
 fn get_col_index(
     req_col_names: &[String],
     header: &[DataType],
+    cfs: &FileSource,
 ) -> BRcvResult<Vec<(usize, String)>> {
     let remapped: Vec<Option<String>> = header
         .iter()
@@ -237,7 +445,69 @@ fn get_col_index(
             _ => None,
         })
         .collect();
-    get_col_index_mapping(req_col_names, &remapped)
+    get_col_index_mapping(
+        req_col_names,
+        &remapped,
+        cfs.candidate_header_regex()?.as_ref(),
+    )
+}
+
+// Looks for the columns that are not candidates but only contain the labels of the choices, for
+// example a candidate added to the form after the configuration was written. Their votes would
+// be lost: this is a warning, or an error with strictColumns.
+fn check_extra_columns(
+    path: &str,
+    wrange: &calamine::Range<DataType>,
+    col_indexes: &[(usize, String)],
+    category_idx_o: &Option<usize>,
+    ranked_choices: &HashMap<String, u32>,
+    cfs: &FileSource,
+) -> BRcvResult<()> {
+    let header = wrange.rows().next().context(EmptyExcelSnafu {})?;
+    let mut extra: Vec<String> = Vec::new();
+    for (col_idx, name) in header.iter().enumerate() {
+        if col_indexes.iter().any(|(idx, _)| *idx == col_idx) || *category_idx_o == Some(col_idx) {
+            continue;
+        }
+        // A candidate column only contains the labels of the choices, or nothing.
+        let mut num_responses = 0;
+        let mut only_choices = true;
+        for row in wrange.rows().skip(1) {
+            match row.get(col_idx) {
+                Some(calamine::DataType::String(s)) if ranked_choices.contains_key(s) => {
+                    num_responses += 1
+                }
+                Some(calamine::DataType::String(s)) if s.is_empty() => {}
+                None | Some(calamine::DataType::Empty) => {}
+                _ => only_choices = false,
+            }
+        }
+        if num_responses > 0 && only_choices {
+            let name = match name {
+                calamine::DataType::String(s) => format!("{:?}", s),
+                _ => format!("#{}", col_idx + 1),
+            };
+            extra.push(format!("{} ({} responses)", name, num_responses));
+        }
+    }
+    if extra.is_empty() {
+        return Ok(());
+    }
+    if cfs.strict_columns == Some(true) {
+        return Err(Box::new(RcvError::ExtraCandidateColumns {
+            path: path.to_string(),
+            columns: extra.join(", "),
+        }));
+    }
+    codes::warning(
+        WarningCode::ExtraCandidateColumns,
+        format!(
+            "The columns {} of {} contain ranks but are not declared candidates, they are ignored",
+            extra.join(", "),
+            path
+        ),
+    );
+    Ok(())
 }
 
 // Maps a column index to a rank
@@ -270,7 +540,7 @@ fn get_col_index_choices(
     Ok(col_indexes)
 }
 
-fn get_ranked_choices(cfs: &FileSource) -> BRcvResult<Vec<(String, u32)>> {
+pub fn get_ranked_choices(cfs: &FileSource) -> BRcvResult<Vec<(String, u32)>> {
     let res = cfs
         .choices
         .clone()
@@ -283,7 +553,115 @@ fn get_ranked_choices(cfs: &FileSource) -> BRcvResult<Vec<(String, u32)>> {
     Ok(res)
 }
 
-fn get_range(path: &String, cfs: &FileSource) -> BRcvResult<calamine::Range<DataType>> {
+// The ids of the ballots of a worksheet. The id is the cell of idColumnIndex (the id of the
+// submission), so that a submission copied in two worksheets keeps its id. Without this cell, the
+// id is the line number, prefixed with the name of the worksheet when several worksheets may be
+// selected.
+struct BallotIds {
+    default_id: Box<dyn Fn(usize) -> String>,
+    worksheet_o: Option<String>,
+    id_idx_o: Option<usize>,
+}
+
+impl BallotIds {
+    fn id(&self, idx: usize, row: &[DataType]) -> String {
+        match (non_empty_cell(row, &self.id_idx_o), &self.worksheet_o) {
+            (Some(id), _) => id,
+            (None, None) => (self.default_id)(idx),
+            (None, Some(worksheet)) => format!("{}:{}", worksheet, (self.default_id)(idx)),
+        }
+    }
+}
+
+/// Reads the ballots of all the worksheets selected by the file source. The ballots with the id
+/// of a ballot of an earlier worksheet are reported, as the worksheets may have the same
+/// submissions.
+fn read_worksheets(
+    path: &String,
+    cfs: &FileSource,
+    read_range: impl Fn(&calamine::Range<DataType>, &BallotIds) -> BRcvResult<Vec<ParsedBallot>>,
+) -> BRcvResult<Vec<ParsedBallot>> {
+    let mut res: Vec<ParsedBallot> = Vec::new();
+    // The worksheet of each id, and the ids found again in a later worksheet.
+    let mut worksheets: HashMap<String, String> = HashMap::new();
+    let mut repeated: Vec<(String, String, String)> = Vec::new();
+    for (worksheet_o, wrange) in get_ranges(path, cfs)? {
+        let ids = BallotIds {
+            default_id: Box::new(make_default_id_lineno(path)),
+            worksheet_o,
+            id_idx_o: cfs.id_column_index_int()?,
+        };
+        let mut ballots = read_range(&wrange, &ids)?;
+        if let Some(worksheet) = ids.worksheet_o.as_ref() {
+            for id in ballots.iter().filter_map(|pb| pb.id.as_ref()) {
+                if let Some(first) = worksheets.get(id) {
+                    repeated.push((id.clone(), first.clone(), worksheet.clone()));
+                }
+            }
+            for id in ballots.iter().filter_map(|pb| pb.id.as_ref()) {
+                worksheets
+                    .entry(id.clone())
+                    .or_insert_with(|| worksheet.clone());
+            }
+        }
+        res.append(&mut ballots);
+    }
+    if let Some((id, first, worksheet)) = repeated.first() {
+        codes::warning(
+            WarningCode::IdenticalBallots,
+            format!(
+                "{} ballots of {} have the id of a ballot of an earlier worksheet, for example {} in {} and {}: the worksheets may have the same submissions (see deduplicateIdenticalBallots)",
+                repeated.len(),
+                path,
+                id,
+                first,
+                worksheet
+            ),
+        );
+    }
+    Ok(res)
+}
+
+// The worksheet name is only returned when multiple worksheets may be selected.
+fn get_ranges(
+    path: &String,
+    cfs: &FileSource,
+) -> BRcvResult<Vec<(Option<String>, calamine::Range<DataType>)>> {
+    if cfs.excel_worksheet_names.is_none() && !cfs.all_worksheets.unwrap_or(false) {
+        return Ok(vec![(None, get_range(path, cfs)?)]);
+    }
+    let mut workbook: Xlsx<_> =
+        open_workbook(path.clone()).context(OpeningExcelSnafu { path: path.clone() })?;
+    let res: Vec<(Option<String>, calamine::Range<DataType>)> =
+        if let Some(worksheet_names) = cfs.excel_worksheet_names.as_ref() {
+            let mut ranges = Vec::new();
+            for worksheet_name in worksheet_names.iter() {
+                let wrange = workbook
+                    .worksheet_range(worksheet_name)
+                    .context(ExcelMissingWorksheetSnafu {
+                        worksheet_name: worksheet_name.clone(),
+                    })?
+                    .context(OpeningExcelSnafu { path: path.clone() })?;
+                ranges.push((Some(worksheet_name.clone()), wrange));
+            }
+            ranges
+        } else {
+            workbook
+                .worksheets()
+                .into_iter()
+                .map(|(worksheet_name, wrange)| (Some(worksheet_name), wrange))
+                .collect()
+        };
+    debug!(
+        "get_ranges: path: {:?} worksheets: {:?}",
+        &path,
+        res.iter().map(|p| p.0.clone()).collect::<Vec<_>>()
+    );
+    ensure!(!res.is_empty(), EmptyExcelSnafu {});
+    Ok(res)
+}
+
+pub fn get_range(path: &String, cfs: &FileSource) -> BRcvResult<calamine::Range<DataType>> {
     let worksheet_name_o = cfs.excel_worksheet_name.clone();
     debug!(
         "read_excel_file: path: {:?} worksheet: {:?}",
@@ -302,21 +680,76 @@ fn get_range(path: &String, cfs: &FileSource) -> BRcvResult<calamine::Range<Data
 
         Ok(wrange)
     } else {
-        let all_worksheets = workbook.worksheets();
-        match all_worksheets.as_slice() {
-            [] => unimplemented!("Empty worksheet"),
-            [(worksheet_name, wrange)] => {
-                debug!(
-                    "read_excel_file: path: {:?} worksheet: {:?}",
-                    &path, &worksheet_name
-                );
-                Ok(wrange.clone())
-            }
-            _ => {
-                unimplemented!(
-                    "read_excel_file: too many worksheets, the worksheet name must be provided"
-                );
-            }
+        auto_select_range(path, &mut workbook)
+    }
+}
+
+/// The worksheet read when the file source does not name one. The chart sheets (without cells)
+/// and the hidden sheets are skipped: the workbook must have exactly one other worksheet.
+pub fn auto_select_range<RS: Read + Seek>(
+    path: &str,
+    workbook: &mut Xlsx<RS>,
+) -> BRcvResult<calamine::Range<DataType>> {
+    let sheet_names = workbook.sheet_names().to_vec();
+    ensure!(!sheet_names.is_empty(), EmptyExcelSnafu {});
+    let hidden = hidden_worksheets(path);
+    let data_worksheets: Vec<(String, calamine::Range<DataType>)> = workbook
+        .worksheets()
+        .into_iter()
+        .filter(|(name, wrange)| !wrange.is_empty() && !hidden.contains(name))
+        .collect();
+    match data_worksheets.as_slice() {
+        [(worksheet_name, wrange)] => {
+            debug!(
+                "read_excel_file: path: {:?} worksheet: {:?} (hidden: {:?})",
+                &path, &worksheet_name, &hidden
+            );
+            Ok(wrange.clone())
         }
+        _ => Err(Box::new(RcvError::ExcelAmbiguousWorksheet {
+            path: path.to_string(),
+            worksheets: sheet_names
+                .iter()
+                .map(|n| format!("'{}'", n))
+                .collect::<Vec<_>>()
+                .join(", "),
+        })),
     }
 }
+
+// The names of the hidden worksheets, from the workbook part of the file: calamine does not
+// expose the visibility of the sheets. A file that cannot be read has no hidden worksheet.
+#[cfg(feature = "xlsx")]
+fn hidden_worksheets(path: &str) -> Vec<String> {
+    let read = || -> Option<String> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).ok()?).ok()?;
+        let mut contents = String::new();
+        archive
+            .by_name("xl/workbook.xml")
+            .ok()?
+            .read_to_string(&mut contents)
+            .ok()?;
+        Some(contents)
+    };
+    let contents = read().unwrap_or_default();
+    let sheet_re = Regex::new(r"<sheet\s[^>]*>").unwrap();
+    let name_re = Regex::new(r#"\sname="([^"]*)""#).unwrap();
+    let state_re = Regex::new(r#"\sstate="(hidden|veryHidden)""#).unwrap();
+    sheet_re
+        .find_iter(&contents)
+        .filter(|m| state_re.is_match(m.as_str()))
+        .filter_map(|m| name_re.captures(m.as_str()))
+        .map(|c| {
+            c[1].replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "xlsx"))]
+fn hidden_worksheets(_path: &str) -> Vec<String> {
+    Vec::new()
+}
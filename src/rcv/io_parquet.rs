@@ -0,0 +1,174 @@
+// Reader for ballots stored in Apache Parquet files.
+//
+// The ballots are read one record batch at a time. The choices are either in several
+// columns that share a prefix (`rank_1`, `rank_2`, ...) or in a single column that contains
+// the list of the choices of each ballot.
+
+use std::fs::File;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Int32Type, Int64Type, UInt32Type, UInt64Type};
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_schema::DataType;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::rcv::io_common::make_default_id_lineno;
+use crate::rcv::*;
+
+/// Where the choices are located in the record batches.
+enum RankColumns {
+    Wide(Vec<String>),
+    List(String),
+}
+
+fn rank_columns(cfs: &FileSource) -> RcvResult<RankColumns> {
+    match (
+        cfs.rankings_column.as_ref(),
+        cfs.rank_column_prefix.as_ref(),
+    ) {
+        (Some(name), None) => Ok(RankColumns::List(name.clone())),
+        (None, Some(prefix)) => {
+            let count = cfs.rank_column_count.context(ParquetRankColumnsSnafu {})?;
+            Ok(RankColumns::Wide(
+                (1..=count).map(|i| format!("{}{}", prefix, i)).collect(),
+            ))
+        }
+        _ => Err(RcvError::ParquetRankColumns {}),
+    }
+}
+
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> RcvResult<&'a ArrayRef> {
+    batch
+        .column_by_name(name)
+        .context(ParquetMissingColumnSnafu { column: name })
+}
+
+fn wrong_type(name: &str, col: &dyn Array) -> RcvError {
+    RcvError::ParquetWrongType {
+        column: name.to_string(),
+        data_type: col.data_type().to_string(),
+    }
+}
+
+fn int_value(name: &str, col: &dyn Array, row: usize) -> RcvResult<Option<i128>> {
+    if col.is_null(row) {
+        return Ok(None);
+    }
+    let x = match col.data_type() {
+        DataType::Int32 => col.as_primitive::<Int32Type>().value(row) as i128,
+        DataType::Int64 => col.as_primitive::<Int64Type>().value(row) as i128,
+        DataType::UInt32 => col.as_primitive::<UInt32Type>().value(row) as i128,
+        DataType::UInt64 => col.as_primitive::<UInt64Type>().value(row) as i128,
+        _ => return Err(wrong_type(name, col)),
+    };
+    Ok(Some(x))
+}
+
+// Ids may be stored as strings or as integers.
+fn string_value(name: &str, col: &dyn Array, row: usize) -> RcvResult<Option<String>> {
+    if col.is_null(row) {
+        return Ok(None);
+    }
+    match col.data_type() {
+        DataType::Utf8 => Ok(Some(col.as_string::<i32>().value(row).to_string())),
+        DataType::LargeUtf8 => Ok(Some(col.as_string::<i64>().value(row).to_string())),
+        _ => Ok(int_value(name, col, row)?.map(|x| x.to_string())),
+    }
+}
+
+fn choice_value(name: &str, col: &dyn Array, row: usize) -> RcvResult<Vec<String>> {
+    let s = match col.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 => string_value(name, col, row)?,
+        _ => return Err(wrong_type(name, col)),
+    };
+    Ok(match s {
+        Some(s) if !s.is_empty() => vec![s],
+        _ => Vec::new(),
+    })
+}
+
+fn list_choices(name: &str, col: &dyn Array, row: usize) -> RcvResult<Vec<Vec<String>>> {
+    if col.is_null(row) {
+        return Ok(Vec::new());
+    }
+    let values = match col.data_type() {
+        DataType::List(_) => col.as_list::<i32>().value(row),
+        DataType::LargeList(_) => col.as_list::<i64>().value(row),
+        _ => return Err(wrong_type(name, col)),
+    };
+    (0..values.len())
+        .map(|idx| choice_value(name, values.as_ref(), idx))
+        .collect()
+}
+
+pub fn read_parquet(path: String, cfs: &FileSource) -> BRcvResult<Vec<ParsedBallot>> {
+    let default_id = make_default_id_lineno(&path);
+    let ranks = rank_columns(cfs)?;
+
+    let file = File::open(&path).context(ParquetOpenSnafu { path: path.clone() })?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .context(ParquetReadSnafu { path: path.clone() })?
+        .build()
+        .context(ParquetReadSnafu { path: path.clone() })?;
+
+    let mut res: Vec<ParsedBallot> = Vec::new();
+    for batch_r in reader {
+        let batch = batch_r.context(ArrowReadSnafu { path: path.clone() })?;
+        let id_col = match cfs.id_column_name.as_ref() {
+            Some(name) => Some((name.as_str(), column(&batch, name)?)),
+            None => None,
+        };
+        let count_col = match cfs.count_column_name.as_ref() {
+            Some(name) => Some((name.as_str(), column(&batch, name)?)),
+            None => None,
+        };
+        let rank_cols: Vec<(&str, &ArrayRef)> = match &ranks {
+            RankColumns::Wide(names) => names
+                .iter()
+                .map(|name| column(&batch, name).map(|col| (name.as_str(), col)))
+                .collect::<RcvResult<Vec<_>>>()?,
+            RankColumns::List(name) => vec![(name.as_str(), column(&batch, name)?)],
+        };
+
+        for row in 0..batch.num_rows() {
+            let lineno = res.len() + 1;
+            let id = match id_col {
+                Some((name, col)) => string_value(name, col.as_ref(), row)?,
+                None => None,
+            }
+            .unwrap_or_else(|| default_id(lineno));
+            let count = match count_col {
+                Some((name, col)) => match int_value(name, col.as_ref(), row)? {
+                    Some(x) => Some(u64::try_from(x).map_err(|_| RcvError::LineParse {
+                        lineno,
+                        col: batch.schema().index_of(name).map_or(0, |idx| idx + 1),
+                    })?),
+                    None => None,
+                },
+                None => None,
+            };
+            let choices = match &ranks {
+                RankColumns::Wide(_) => rank_cols
+                    .iter()
+                    .map(|(name, col)| choice_value(name, col.as_ref(), row))
+                    .collect::<RcvResult<Vec<_>>>()?,
+                RankColumns::List(_) => {
+                    let (name, col) = rank_cols[0];
+                    list_choices(name, col.as_ref(), row)?
+                }
+            };
+            debug!("read_parquet: lineno: {:?} row: {:?}", lineno, &choices);
+            res.push(ParsedBallot {
+                id: Some(id),
+                count,
+                choices,
+                challenged: false,
+                category: None,
+                respondent: None,
+                precinct: None,
+                style: None,
+            });
+        }
+    }
+    Ok(res)
+}
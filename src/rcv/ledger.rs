@@ -0,0 +1,132 @@
+// The ledger of the official runs (--ledger): one line of JSON is appended to the ledger after
+// each run, with the digests of the inputs and the outcome. The previous lines are never
+// rewritten.
+//
+// Running twice on the same inputs is most likely an accidental double publication: it is
+// refused unless --allow-duplicate-ledger is passed.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::rcv::*;
+
+/// The digest of an input file. The digest is None if the file cannot be read.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct InputDigest {
+    pub file: String,
+    pub sha256: Option<String>,
+}
+
+/// The digests of the file sources, or of the snapshot when the ballots are loaded from one.
+pub fn input_digests(
+    config: &RcvConfig,
+    root_path: &Path,
+    load_path_o: Option<&str>,
+) -> Vec<InputDigest> {
+    let digest = |file: &str, path: PathBuf| InputDigest {
+        file: file.to_string(),
+        sha256: fs::read(path).ok().map(|bytes| sha256::digest(&bytes[..])),
+    };
+    match load_path_o {
+        Some(load_path) => vec![digest(load_path, PathBuf::from(load_path))],
+        None => config
+            .cvr_file_sources
+            .iter()
+            .map(|cfs| digest(&cfs.file_path, root_path.join(&cfs.file_path)))
+            .collect(),
+    }
+}
+
+fn digests_to_json(inputs: &[InputDigest]) -> JSValue {
+    let l: Vec<JSValue> = inputs
+        .iter()
+        .map(|d| json!({"file": d.file, "sha256": d.sha256}))
+        .collect();
+    JSValue::Array(l)
+}
+
+// The last entry of the ledger, if the file exists and is not empty.
+fn last_entry(path: &str) -> RcvResult<Option<JSValue>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(LedgerReadSnafu { path }),
+    };
+    match content.lines().rev().find(|l| !l.trim().is_empty()) {
+        None => Ok(None),
+        Some(line) => Ok(Some(
+            serde_json::from_str(line).context(LedgerParseSnafu { path })?,
+        )),
+    }
+}
+
+/// Fails if the last entry of the ledger has the same inputs, unless duplicates are allowed.
+pub fn check_duplicate(path: &str, inputs: &[InputDigest], allow_duplicate: bool) -> RcvResult<()> {
+    if allow_duplicate {
+        return Ok(());
+    }
+    if let Some(last) = last_entry(path)? {
+        ensure!(
+            last.get("inputs") != Some(&digests_to_json(inputs)),
+            LedgerDuplicateSnafu {
+                path,
+                timestamp: last
+                    .get("timestamp")
+                    .and_then(|t| t.as_str())
+                    .unwrap_or_default()
+            }
+        );
+    }
+    Ok(())
+}
+
+/// The outcome of a run, as recorded in the ledger.
+pub struct LedgerEntry<'a> {
+    pub rules: &'a VoteRules,
+    pub inputs: &'a [InputDigest],
+    /// The ballots kept by the validation (not known for a snapshot), and their votes.
+    pub ballots: Option<u64>,
+    pub votes: u64,
+    pub status: SummaryStatus,
+    pub winners: Option<&'a [String]>,
+    pub output: Option<&'a str>,
+}
+
+impl LedgerEntry<'_> {
+    fn to_json(&self) -> JSValue {
+        json!({
+            "timestamp": utc_timestamp(SystemTime::now()),
+            "version": env!("CARGO_PKG_VERSION"),
+            "rulesFingerprint": snapshot::rules_fingerprint(self.rules),
+            "inputs": digests_to_json(self.inputs),
+            "ballots": self.ballots,
+            "votes": self.votes,
+            "status": self.status.label(),
+            "winners": self.winners,
+            "output": self.output,
+        })
+    }
+}
+
+/// Appends the entry to the ledger, which is created if needed.
+pub fn append(path: &str, entry: &LedgerEntry) -> RcvResult<()> {
+    let mut line = serde_json::to_string(&entry.to_json()).context(ParsingJsonSnafu {})?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(LedgerWriteSnafu { path })?;
+    file.write_all(line.as_bytes())
+        .context(LedgerWriteSnafu { path })
+}
+
+// The time in UTC, in the RFC 3339 format (2024-11-05T21:03:00Z).
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    time_window::format_timestamp(secs as i64)
+}
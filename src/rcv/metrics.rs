@@ -0,0 +1,70 @@
+// The metrics of a run (--out-metrics), for the dashboards that follow the reading of many
+// file sources on election night.
+//
+// Each file source has one entry, which is also logged as one line of JSON as soon as the
+// source is read. The paths are reduced to the name of the file, so that the metrics can be
+// shared without the layout of the disks.
+
+use std::time::Duration;
+
+use crate::rcv::*;
+
+/// The reading of one file source.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct SourceMetrics {
+    /// The name of the file, without its directory.
+    pub name: String,
+    pub provider: String,
+    /// The size of the file, or 0 if it cannot be found.
+    pub bytes: u64,
+    /// The ballots in the file, before the validation.
+    pub ballots: u64,
+    pub parse_time: Duration,
+    pub validation_time: Duration,
+    /// The warnings raised while reading and validating the file.
+    pub warnings: u64,
+}
+
+impl SourceMetrics {
+    pub fn new(root_path: &str, cfs: &FileSource) -> SourceMetrics {
+        let path = Path::new(root_path).join(&cfs.file_path);
+        SourceMetrics {
+            name: Path::new(&cfs.file_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            provider: cfs.provider.clone(),
+            bytes: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            ..SourceMetrics::default()
+        }
+    }
+
+    pub fn to_json(&self) -> JSValue {
+        json!({
+            "file": self.name,
+            "provider": self.provider,
+            "bytes": self.bytes,
+            "parsedBallots": self.ballots,
+            "parseMillis": millis(self.parse_time),
+            "validationMillis": millis(self.validation_time),
+            "warnings": self.warnings,
+        })
+    }
+
+    /// Logs the metrics as one line of JSON.
+    pub fn log(&self) {
+        info!("source_metrics: {}", self.to_json());
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    (d.as_secs_f64() * 1e6).round() / 1e3
+}
+
+pub fn write_metrics(path: &str, sources: &[SourceMetrics]) -> RcvResult<()> {
+    let js = json!({
+        "sources": sources.iter().map(|m| m.to_json()).collect::<Vec<JSValue>>(),
+    });
+    let s = serde_json::to_string_pretty(&js).context(ParsingJsonSnafu {})?;
+    fs::write(path, s).context(MetricsWriteSnafu { path })
+}
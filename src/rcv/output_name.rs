@@ -0,0 +1,131 @@
+// The names of the output files, templated by the metadata of the contest: --out, the default
+// summary of outputDirectory, --out-flat-csv and --out-rctab-csv.
+//
+// A placeholder is a known name between braces, for example `summary_{office}_{date}.json`;
+// the other braces are kept as they are. The values are sanitized so that they cannot add
+// directories or characters that some file systems refuse. The winner is only known after the
+// tabulation: the directories of the templates are checked before it with the other values,
+// and the templates are rendered once it is done.
+
+use std::time::SystemTime;
+
+use crate::rcv::*;
+
+const PLACEHOLDERS: [&str; 6] = [
+    "contest",
+    "office",
+    "jurisdiction",
+    "date",
+    "timestamp",
+    "winner",
+];
+
+// The value of {winner} when there is no winner.
+const NO_WINNER: &str = "none";
+
+// True if a known placeholder starts the text.
+fn placeholder_at(text: &str, name: &str) -> bool {
+    text.strip_prefix('{')
+        .and_then(|t| t.strip_prefix(name))
+        .is_some_and(|t| t.starts_with('}'))
+}
+
+/// True if the path has placeholders. The other braces are part of the file name.
+pub fn is_template(path: &str) -> bool {
+    path.match_indices('{')
+        .any(|(i, _)| PLACEHOLDERS.iter().any(|p| placeholder_at(&path[i..], p)))
+}
+
+// Replaces the characters that are not allowed in the file names of the common file systems.
+fn sanitize(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+fn render_with(template: &str, value: impl Fn(&str) -> String) -> String {
+    let mut res = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match PLACEHOLDERS.iter().find(|p| placeholder_at(rest, p)) {
+            Some(name) => {
+                res.push_str(&sanitize(&value(name)));
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                res.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// The values of the placeholders of a run.
+pub struct OutputNames {
+    contest: String,
+    office: String,
+    jurisdiction: String,
+    date: String,
+    timestamp: String,
+    winner: String,
+}
+
+impl OutputNames {
+    /// The values from the output settings. The timestamp is the start of the run, in UTC
+    /// (20241105T210300Z), so that all the outputs of a run have the same one.
+    pub fn new(settings: &OutputSettings, started: SystemTime) -> OutputNames {
+        OutputNames {
+            contest: settings.contest_name.clone(),
+            office: settings.contest_office.clone().unwrap_or_default(),
+            jurisdiction: settings.contest_juridiction.clone().unwrap_or_default(),
+            date: settings.contest_date.clone().unwrap_or_default(),
+            timestamp: ledger::utc_timestamp(started).replace(['-', ':'], ""),
+            winner: NO_WINNER.to_string(),
+        }
+    }
+
+    /// Sets the winners of the tabulation, joined with '_' if there are several.
+    pub fn set_winners(&mut self, winners: Option<&[String]>) {
+        self.winner = match winners {
+            Some(winners) if !winners.is_empty() => winners.join("_"),
+            _ => NO_WINNER.to_string(),
+        };
+    }
+
+    /// Renders a template before the tabulation, so that its directory is checked up front.
+    /// None if the path is not a template, or if its directory depends on the winner.
+    pub fn render_before_winner(&self, template: &str) -> Option<String> {
+        let winner_dir = Path::new(template)
+            .parent()
+            .is_some_and(|d| d.to_string_lossy().contains("{winner}"));
+        if !is_template(template) || winner_dir {
+            return None;
+        }
+        Some(self.render(template))
+    }
+
+    /// Replaces the placeholders of a path.
+    pub fn render(&self, template: &str) -> String {
+        render_with(template, |name| {
+            match name {
+                "contest" => &self.contest,
+                "office" => &self.office,
+                "jurisdiction" => &self.jurisdiction,
+                "date" => &self.date,
+                "timestamp" => &self.timestamp,
+                _ => &self.winner,
+            }
+            .clone()
+        })
+    }
+}
@@ -0,0 +1,169 @@
+// The checks of the paths of the outputs (--out, --out-xlsx, --out-flat-csv, ...), so that a
+// wrong path fails with the flag that caused it instead of a bare error of the system.
+//
+// Before the tabulation, a path that is a directory or whose directory does not exist is
+// refused. The permissions and the free space are only known when the file is written: the
+// errors of the writing are then explained with the flag, the absolute path and the owner of the
+// file or of its directory.
+
+use std::io::ErrorKind;
+
+use crate::rcv::*;
+
+// The flags of the output files, with the file name suggested when the path is a directory.
+const OUTPUTS: [(&str, &str); 11] = [
+    ("--out", "summary.json"),
+    ("outputDirectory", "summary.json"),
+    ("--out-xlsx", "results.xlsx"),
+    ("--out-flat-csv", "rounds.csv"),
+    ("--out-rctab-csv", "summary.csv"),
+    ("--out-fairvote-row", "fairvote.csv"),
+    ("--out-metrics", "metrics.json"),
+    ("--save-validated", "ballots.snapshot"),
+    ("--export-simple-csv", "ballots.csv"),
+    ("--export-preflib", "ballots.toi"),
+    ("--ledger", "ledger.jsonl"),
+];
+
+fn example(flag: &str, path: &str) -> String {
+    let name = OUTPUTS
+        .iter()
+        .find(|(f, _)| *f == flag)
+        .map_or("output", |(_, name)| name);
+    Path::new(path).join(name).display().to_string()
+}
+
+// The directory of a path, or None for a file of the current directory.
+fn parent(path: &str) -> Option<&Path> {
+    Path::new(path)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+}
+
+/// Checks that the output of a flag can be created: the path is not a directory, and its
+/// directory exists. The standard output, the empty path and the templates are not checked.
+pub fn check(flag: &str, path: &str) -> RcvResult<()> {
+    if path.is_empty() || path == "stdout" || output_name::is_template(path) {
+        return Ok(());
+    }
+    ensure!(
+        !Path::new(path).is_dir(),
+        OutputIsDirectorySnafu {
+            flag,
+            path,
+            example: example(flag, path),
+        }
+    );
+    if let Some(parent) = parent(path).filter(|p| !p.is_dir()) {
+        return OutputDirMissingSnafu {
+            flag,
+            path,
+            parent: parent.display().to_string(),
+        }
+        .fail();
+    }
+    Ok(())
+}
+
+/// Checks the outputs of the arguments before the tabulation. The paths of the summary are
+/// checked separately, since they may come from the configuration.
+/// `config_path_o` is the configuration of the election, which an export never replaces.
+pub fn check_options(options: &RunOptions, config_path_o: Option<&str>) -> RcvResult<()> {
+    let outputs = [
+        ("--out-xlsx", &options.out_xlsx),
+        ("--out-flat-csv", &options.out_flat_csv),
+        ("--out-rctab-csv", &options.out_rctab_csv),
+        ("--out-fairvote-row", &options.out_fairvote_row),
+        ("--out-metrics", &options.out_metrics),
+        ("--save-validated", &options.save_validated),
+        ("--export-simple-csv", &options.export_simple_csv),
+        ("--export-preflib", &options.export_preflib),
+        ("--ledger", &options.ledger),
+    ];
+    for (flag, path_o) in outputs.iter() {
+        if let Some(path) = path_o.as_deref() {
+            check(flag, path)?;
+        }
+    }
+    if let Some(path) = options.export_simple_csv.as_deref() {
+        io_csv::check_simple_csv_config(path, config_path_o)?;
+    }
+    Ok(())
+}
+
+// The first error of the system in the causes of an error.
+fn io_cause(e: &RcvError) -> Option<&std::io::Error> {
+    let mut cause: Option<&(dyn std::error::Error + 'static)> = std::error::Error::source(e);
+    while let Some(c) = cause {
+        if let Some(io_e) = c.downcast_ref::<std::io::Error>() {
+            return Some(io_e);
+        }
+        cause = c.source();
+    }
+    None
+}
+
+// The absolute path, resolved against the current directory if the file does not exist yet.
+fn absolute(path: &str) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| PathBuf::from(path))
+        .display()
+        .to_string()
+}
+
+// The owner of the file, or of its directory if the file does not exist yet.
+#[cfg(unix)]
+fn owner(path: &str) -> String {
+    use std::os::unix::fs::MetadataExt;
+    let metadata =
+        fs::metadata(path).or_else(|_| fs::metadata(parent(path).unwrap_or(Path::new("."))));
+    match metadata {
+        Ok(m) => format!("uid {}, mode {:o}", m.uid(), m.mode() & 0o777),
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+#[cfg(not(unix))]
+fn owner(_path: &str) -> String {
+    "unknown".to_string()
+}
+
+/// Explains the error of the writing of an output with the flag that caused it. The errors that
+/// do not come from the path or the disk are returned unchanged.
+pub fn explain(flag: &str, path: &str, e: RcvError) -> RcvError {
+    let io_e = match io_cause(&e) {
+        Some(io_e) => io_e,
+        None => return e,
+    };
+    let reason = io_e.to_string();
+    match io_e.kind() {
+        ErrorKind::IsADirectory => OutputIsDirectorySnafu {
+            flag,
+            path,
+            example: example(flag, path),
+        }
+        .build(),
+        ErrorKind::NotFound if parent(path).is_some_and(|p| !p.is_dir()) => OutputDirMissingSnafu {
+            flag,
+            path,
+            parent: parent(path).unwrap().display().to_string(),
+        }
+        .build(),
+        ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => {
+            OutputPermissionDeniedSnafu {
+                flag,
+                path: absolute(path),
+                owner: owner(path),
+                reason,
+            }
+            .build()
+        }
+        ErrorKind::StorageFull | ErrorKind::QuotaExceeded => OutputDiskFullSnafu {
+            flag,
+            path: absolute(path),
+            reason,
+        }
+        .build(),
+        _ => e,
+    }
+}
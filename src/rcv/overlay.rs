@@ -0,0 +1,109 @@
+// The overlays of the configuration (--overlay): partial configurations that are merged over the
+// configuration file, for example to change the excluded candidates during a canvass without
+// editing the configuration.
+//
+// The objects are merged key by key, and the other values of the overlay replace the ones of the
+// configuration. The candidates are matched by name: the fields of a candidate of the overlay
+// are merged over the candidate of the same name, and the new names are added at the end of the
+// candidates. Every value that an overlay changes is logged.
+
+use crate::rcv::*;
+
+fn invalid(path: &str, reason: &str) -> RcvError {
+    RcvError::InvalidOverlay {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Reads the overlay file and merges it over the configuration. Returns the changes, as
+/// `key: old -> new` lines.
+pub fn apply_file(config: &mut JSValue, path: &str) -> RcvResult<Vec<String>> {
+    let contents = fs::read_to_string(path).context(ConfigOpeningJsonSnafu {})?;
+    let overlay: JSValue = serde_json::from_str(&contents).context(ParsingJsonSnafu {})?;
+    ensure!(
+        overlay.is_object(),
+        InvalidOverlaySnafu {
+            path,
+            reason: "expected a JSON object"
+        }
+    );
+    let mut changes: Vec<String> = Vec::new();
+    merge(config, &overlay, "", path, &mut changes)?;
+    if changes.is_empty() {
+        info!("The overlay {} does not change the configuration", path);
+    } else {
+        info!(
+            "The overlay {} changes the configuration:\n  {}",
+            path,
+            changes.join("\n  ")
+        );
+    }
+    Ok(changes)
+}
+
+fn merge(
+    base: &mut JSValue,
+    overlay: &JSValue,
+    key_path: &str,
+    path: &str,
+    changes: &mut Vec<String>,
+) -> RcvResult<()> {
+    match (base, overlay) {
+        (JSValue::Object(base), JSValue::Object(overlay)) => {
+            for (key, value) in overlay.iter() {
+                let sub_path = if key_path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", key_path, key)
+                };
+                if key_path.is_empty() && key == "candidates" {
+                    let candidates = base.entry(key.clone()).or_insert_with(|| json!([]));
+                    merge_candidates(candidates, value, path, changes)?;
+                } else if let Some(base_value) = base.get_mut(key) {
+                    merge(base_value, value, &sub_path, path, changes)?;
+                } else {
+                    changes.push(format!("{}: (none) -> {}", sub_path, value));
+                    base.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        (base, overlay) => {
+            if base != overlay {
+                changes.push(format!("{}: {} -> {}", key_path, base, overlay));
+                *base = overlay.clone();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn merge_candidates(
+    base: &mut JSValue,
+    overlay: &JSValue,
+    path: &str,
+    changes: &mut Vec<String>,
+) -> RcvResult<()> {
+    let overlay = overlay
+        .as_array()
+        .ok_or_else(|| invalid(path, "the candidates are not a list"))?;
+    let base = base
+        .as_array_mut()
+        .ok_or_else(|| invalid(path, "the candidates of the configuration are not a list"))?;
+    for candidate in overlay.iter() {
+        let name = candidate["name"]
+            .as_str()
+            .ok_or_else(|| invalid(path, "a candidate has no name"))?;
+        match base.iter_mut().find(|c| c["name"].as_str() == Some(name)) {
+            Some(base_candidate) => {
+                let key_path = format!("candidates[{}]", name);
+                merge(base_candidate, candidate, &key_path, path, changes)?;
+            }
+            None => {
+                changes.push(format!("candidates: added {}", candidate));
+                base.push(candidate.clone());
+            }
+        }
+    }
+    Ok(())
+}
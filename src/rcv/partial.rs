@@ -0,0 +1,103 @@
+// The rounds written to disk as soon as they are tabulated (--out-partial), so that a long
+// tabulation that crashes still leaves the rounds done so far.
+//
+// Each round is written to round_NNN.json, in the shape of the rounds of the summary, and
+// partial_summary.json has all the rounds so far. The files are written to a temporary file
+// first and then renamed, so that they are never seen half-written.
+
+use crate::rcv::*;
+
+const PARTIAL_SUMMARY: &str = "partial_summary.json";
+
+pub struct PartialWriter {
+    dir: PathBuf,
+    config: OutputConfig,
+    schema: SummarySchema,
+    inactive_ballots: bool,
+    rounds: Vec<JSValue>,
+    files: Vec<PathBuf>,
+    // The first error: the observer of the tabulation cannot return it.
+    error: Option<RcvError>,
+}
+
+impl PartialWriter {
+    pub fn new(dir: &str, config: &RcvConfig) -> RcvResult<PartialWriter> {
+        fs::create_dir_all(dir).context(PartialWriteSnafu { path: dir })?;
+        Ok(PartialWriter {
+            dir: PathBuf::from(dir),
+            config: output_config(config, None),
+            schema: config.output_settings.summary_schema()?,
+            inactive_ballots: config.output_settings.inactive_ballots_block()?,
+            rounds: Vec::new(),
+            files: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Writes the round and the partial summary. After an error, the next rounds are not
+    /// written.
+    pub fn observe(&mut self, round: &RoundStats) {
+        if self.error.is_some() || (round.informational && self.schema == SummarySchema::V1) {
+            return;
+        }
+        if let Err(e) = self.write_round(round) {
+            codes::warning(
+                WarningCode::UnwrittenOutput,
+                format!("partial: {}, the next rounds are not written", e),
+            );
+            self.error = Some(e);
+        }
+    }
+
+    fn write_round(&mut self, round: &RoundStats) -> RcvResult<()> {
+        // The round is not known to be the last one: its eliminations are always written.
+        let js = serde_json::to_value(summary::RoundSummary::new(
+            round,
+            false,
+            self.inactive_ballots,
+        ))
+        .context(ParsingJsonSnafu {})?;
+        let round_path = self.dir.join(format!("round_{:03}.json", round.round.0));
+        write_atomic(&round_path, &js)?;
+        self.files.push(round_path);
+        self.rounds.push(js);
+        let summary = json!({
+            "config": self.config,
+            "results": self.rounds,
+        });
+        let summary_path = self.dir.join(PARTIAL_SUMMARY);
+        write_atomic(&summary_path, &summary)?;
+        if self.rounds.len() == 1 {
+            self.files.push(summary_path);
+        }
+        debug!("partial: round {} written", round.round.0);
+        Ok(())
+    }
+
+    /// Removes the partial files once the final summary is written, unless `keep` is set, and
+    /// returns the first error of the writes or of the removals.
+    pub fn finish(self, keep: bool) -> RcvResult<()> {
+        if !keep {
+            for path in self.files.iter() {
+                fs::remove_file(path).context(PartialWriteSnafu {
+                    path: path.display().to_string(),
+                })?;
+            }
+        }
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+fn write_atomic(path: &Path, js: &JSValue) -> RcvResult<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    let contents = serde_json::to_string_pretty(js).context(ParsingJsonSnafu {})?;
+    fs::write(&tmp_path, contents).context(PartialWriteSnafu {
+        path: tmp_path.display().to_string(),
+    })?;
+    fs::rename(&tmp_path, path).context(PartialWriteSnafu {
+        path: path.display().to_string(),
+    })
+}
@@ -0,0 +1,168 @@
+// Aggregation of the ballots by ranking pattern, to inspect the data before tabulating.
+
+use crate::rcv::*;
+
+/// Displayed for a skipped rank (undervote or blank).
+pub const PATTERN_SKIP: &str = "(skip)";
+/// Displayed for an undeclared write-in.
+pub const PATTERN_WRITE_IN: &str = "(write-in)";
+/// Displayed for an overvote when the names of the candidates are not known.
+pub const PATTERN_OVERVOTE: &str = "(overvote)";
+/// Displayed for a ballot without any choice.
+pub const PATTERN_EMPTY: &str = "(empty)";
+
+/// The number of ballots that share the same ranking pattern.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct PatternCount {
+    pub pattern: String,
+    pub count: u64,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct PatternSummary {
+    pub total_ballots: u64,
+    pub unique_patterns: usize,
+    /// The number of patterns that appear in a single ballot.
+    pub singletons: usize,
+    /// All the patterns, by decreasing count.
+    pub patterns: Vec<PatternCount>,
+}
+
+fn pattern_element(group: &[String], choice: &BallotChoice, source: &FileSource) -> String {
+    match choice {
+        BallotChoice::Candidate(name) => name.clone(),
+        BallotChoice::UndeclaredWriteIn => PATTERN_WRITE_IN.to_string(),
+        BallotChoice::Undervote | BallotChoice::Blank => PATTERN_SKIP.to_string(),
+        BallotChoice::Overvote => {
+            let names: Vec<String> = match (group, source.overvote_delimiter.as_ref()) {
+                ([c], Some(delim)) if c.contains(delim.as_str()) => c
+                    .split(delim.as_str())
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+                ([_, _, ..], _) => group.to_vec(),
+                _ => vec![],
+            };
+            if names.is_empty() {
+                PATTERN_OVERVOTE.to_string()
+            } else {
+                format!("[{}]", names.join("/"))
+            }
+        }
+    }
+}
+
+/// The compact notation of the choices of a ballot, for example `A > (skip) > C` or
+/// `A > [B/C]`. Skipped ranks at the end of the ballot are not displayed.
+pub fn ballot_pattern(
+    pb: &ParsedBallot,
+    candidate_names: &HashSet<CandidateName>,
+    source: &FileSource,
+) -> String {
+    let labels = choice_labels(source);
+    let mut elements: Vec<String> = pb
+        .choices
+        .iter()
+        .map(|group| {
+            let choice = validate_choice(group, candidate_names, &labels);
+            pattern_element(group, &choice, source)
+        })
+        .collect();
+    while elements.last().map(|s| s.as_str()) == Some(PATTERN_SKIP) {
+        elements.pop();
+    }
+    if elements.is_empty() {
+        PATTERN_EMPTY.to_string()
+    } else {
+        elements.join(" > ")
+    }
+}
+
+/// Adds the ballots of one file source to the pattern counts.
+pub fn add_patterns(
+    counts: &mut HashMap<String, u64>,
+    parsed_ballots: &[ParsedBallot],
+    candidates: &[RcvCandidate],
+    source: &FileSource,
+) {
+    let candidate_names = declared_names(candidates);
+    for pb in parsed_ballots.iter() {
+        // Same default as validate_ballots
+        let count = pb.count.unwrap_or(1);
+        if count > 0 {
+            *counts
+                .entry(ballot_pattern(pb, &candidate_names, source))
+                .or_insert(0) += count;
+        }
+    }
+}
+
+pub fn summarize_patterns(counts: &HashMap<String, u64>, order: &NameOrder) -> PatternSummary {
+    let mut patterns: Vec<PatternCount> = counts
+        .iter()
+        .map(|(pattern, count)| PatternCount {
+            pattern: pattern.clone(),
+            count: *count,
+        })
+        .collect();
+    // Ties are ordered by pattern to keep the output stable.
+    patterns.sort_by(|p1, p2| {
+        p2.count
+            .cmp(&p1.count)
+            .then_with(|| order.compare(&p1.pattern, &p2.pattern))
+    });
+    PatternSummary {
+        total_ballots: patterns.iter().map(|p| p.count).sum(),
+        unique_patterns: patterns.len(),
+        singletons: patterns.iter().filter(|p| p.count == 1).count(),
+        patterns,
+    }
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        ((count as f64) * 10000.0 / (total as f64)).round() / 100.0
+    }
+}
+
+pub fn patterns_to_json(summary: &PatternSummary, top: usize) -> JSValue {
+    let patterns: Vec<JSValue> = summary
+        .patterns
+        .iter()
+        .take(top)
+        .map(|p| {
+            json!({
+                "pattern": p.pattern,
+                "count": p.count,
+                "percent": percent(p.count, summary.total_ballots),
+            })
+        })
+        .collect();
+    json!({
+        "totalBallots": summary.total_ballots,
+        "uniquePatterns": summary.unique_patterns,
+        "singletons": summary.singletons,
+        "patterns": patterns,
+    })
+}
+
+pub fn patterns_to_text(summary: &PatternSummary, top: usize) -> String {
+    let mut lines: Vec<String> = vec![
+        format!(
+            "{} ballots, {} unique patterns, {} singletons",
+            summary.total_ballots, summary.unique_patterns, summary.singletons
+        ),
+        format!("{:>10} {:>8}  pattern", "count", "%"),
+    ];
+    for p in summary.patterns.iter().take(top) {
+        lines.push(format!(
+            "{:>10} {:>8.2}  {}",
+            p.count,
+            percent(p.count, summary.total_ballots),
+            p.pattern
+        ));
+    }
+    lines.join("\n")
+}
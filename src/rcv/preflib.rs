@@ -0,0 +1,171 @@
+// The export of the ballots in the PrefLib format of the orders with ties (--export-preflib),
+// to share the datasets with the researchers who use the PrefLib tools.
+//
+// The file has a header of `# KEY: value` lines, with the index of each alternative (the
+// candidates in their order of declaration, then the undeclared write-ins if some ballots have
+// them), then one line per distinct order, with the number of ballots that cast it:
+//
+//   # ALTERNATIVE NAME 1: A
+//   # ALTERNATIVE NAME 2: B
+//   # ALTERNATIVE NAME 3: C
+//   3: 1,2
+//   1: 2,{1,3}
+//
+// The orders are the ranks of the validated ballots, with their weighted counts:
+// - an overvote is a tie between the candidates of the rank (`{1,3}`). An overvote label without
+//   the names of the candidates has no preference, and is omitted;
+// - the blanks, the undervotes and the candidates already ranked are omitted;
+// - the ballots without any candidate are not in the file.
+// The data type is `soi` (strict orders, incomplete) if no order has a tie, `toi` otherwise. The
+// orders are sorted by decreasing count, then by order.
+
+use std::io::Write;
+
+use crate::rcv::*;
+
+/// The ranks of a ballot, each with the candidates of the rank, and its count.
+pub type BallotOrder = (Vec<Vec<String>>, u64);
+
+// The candidates of a rank with several entries, or with the overvote delimiter.
+fn overvoted(
+    group: &[String],
+    candidate_names: &HashSet<CandidateName>,
+    labels: &ChoiceLabels,
+) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for entry in group.iter() {
+        let fragments: Vec<&str> = match labels.overvote_delimiter.as_deref() {
+            Some(delim) if entry.contains(delim) => entry.split(delim).collect(),
+            _ => vec![entry.as_str()],
+        };
+        for fragment in fragments {
+            let name = match validate_choice(&[fragment.to_string()], candidate_names, labels) {
+                BallotChoice::Candidate(name) => name,
+                BallotChoice::UndeclaredWriteIn => UNDECLARED_WRITE_IN_LABEL.to_string(),
+                _ => continue,
+            };
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// The orders of the ballots of a source, classified as in the validation.
+pub fn ballot_orders(
+    parsed_ballots: &[ParsedBallot],
+    candidates: &[RcvCandidate],
+    source: &FileSource,
+    weights: Option<&Weights>,
+) -> RcvResult<Vec<BallotOrder>> {
+    let candidate_names = declared_names(candidates);
+    let labels = choice_labels(source);
+    let mut orders: Vec<BallotOrder> = Vec::new();
+    for pb in parsed_ballots.iter() {
+        let count = weighted_count(pb, weights)?;
+        let mut ranked: HashSet<String> = HashSet::new();
+        let mut ranks: Vec<Vec<String>> = Vec::new();
+        for group in pb.choices.iter() {
+            let group: Vec<String> = group.iter().map(|c| c.trim().to_string()).collect();
+            let names = match validate_choice(&group, &candidate_names, &labels) {
+                BallotChoice::Candidate(name) => vec![name],
+                BallotChoice::UndeclaredWriteIn => vec![UNDECLARED_WRITE_IN_LABEL.to_string()],
+                BallotChoice::Overvote => overvoted(&group, &candidate_names, &labels),
+                BallotChoice::Undervote | BallotChoice::Blank => continue,
+            };
+            let rank: Vec<String> = names
+                .into_iter()
+                .filter(|n| ranked.insert(n.clone()))
+                .collect();
+            if !rank.is_empty() {
+                ranks.push(rank);
+            }
+        }
+        if count > 0 && !ranks.is_empty() {
+            orders.push((ranks, count));
+        }
+    }
+    Ok(orders)
+}
+
+/// Writes the orders in the PrefLib format. The alternatives are the candidates, then the
+/// undeclared write-ins if an order has them.
+pub fn write_toi(
+    path: &str,
+    title: &str,
+    candidates: &[RcvCandidate],
+    orders: &[BallotOrder],
+) -> RcvResult<()> {
+    let mut alternatives: Vec<&str> = candidates.iter().map(|c| c.name.as_str()).collect();
+    let has_uwi = orders
+        .iter()
+        .flat_map(|(ranks, _)| ranks.iter().flatten())
+        .any(|n| n == UNDECLARED_WRITE_IN_LABEL);
+    if has_uwi && !alternatives.contains(&UNDECLARED_WRITE_IN_LABEL) {
+        alternatives.push(UNDECLARED_WRITE_IN_LABEL);
+    }
+    let index = |name: &str| alternatives.iter().position(|a| *a == name).map(|i| i + 1);
+
+    // The same orders are counted together.
+    let mut counts: BTreeMap<Vec<Vec<usize>>, u64> = BTreeMap::new();
+    for (ranks, count) in orders.iter() {
+        let indices: Vec<Vec<usize>> = ranks
+            .iter()
+            .map(|rank| {
+                let mut rank: Vec<usize> = rank.iter().filter_map(|n| index(n)).collect();
+                rank.sort_unstable();
+                rank
+            })
+            .filter(|rank| !rank.is_empty())
+            .collect();
+        let total = counts.entry(indices).or_insert(0);
+        *total = total
+            .checked_add(*count)
+            .context(CountOverflowSnafu { what: "an order" })?;
+    }
+    let mut lines: Vec<(Vec<Vec<usize>>, u64)> = counts.into_iter().collect();
+    lines.sort_by(|(o1, c1), (o2, c2)| c2.cmp(c1).then_with(|| o1.cmp(o2)));
+    let has_ties = lines
+        .iter()
+        .any(|(ranks, _)| ranks.iter().any(|r| r.len() > 1));
+
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut out = String::new();
+    out.push_str(&format!("# FILE NAME: {}\n", file_name));
+    out.push_str(&format!("# TITLE: {}\n", title));
+    out.push_str(&format!(
+        "# DATA TYPE: {}\n",
+        if has_ties { "toi" } else { "soi" }
+    ));
+    out.push_str("# MODIFICATION TYPE: original\n");
+    out.push_str(&format!("# NUMBER ALTERNATIVES: {}\n", alternatives.len()));
+    let num_voters = lines
+        .iter()
+        .try_fold(0u64, |total, (_, c)| total.checked_add(*c))
+        .context(CountOverflowSnafu { what: "the voters" })?;
+    out.push_str(&format!("# NUMBER VOTERS: {}\n", num_voters));
+    out.push_str(&format!("# NUMBER UNIQUE ORDERS: {}\n", lines.len()));
+    for (idx, name) in alternatives.iter().enumerate() {
+        out.push_str(&format!("# ALTERNATIVE NAME {}: {}\n", idx + 1, name));
+    }
+    for (ranks, count) in lines.iter() {
+        let ranks: Vec<String> = ranks
+            .iter()
+            .map(|rank| {
+                let rank: Vec<String> = rank.iter().map(|i| i.to_string()).collect();
+                match rank.as_slice() {
+                    [single] => single.clone(),
+                    _ => format!("{{{}}}", rank.join(",")),
+                }
+            })
+            .collect();
+        out.push_str(&format!("{}: {}\n", count, ranks.join(",")));
+    }
+    let mut file = fs::File::create(path).context(PreflibWriteSnafu { path })?;
+    file.write_all(out.as_bytes())
+        .context(PreflibWriteSnafu { path })
+}
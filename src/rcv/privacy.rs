@@ -0,0 +1,103 @@
+// The privacy guard of the outputs with one row per ballot (--export-simple-csv,
+// --export-preflib). In a small precinct, a ranking pattern cast by a single voter can identify
+// this voter, for example with the knowledge of a few of the votes.
+//
+// The guard computes the k-anonymity of the ranking patterns of an output: the number of votes of
+// the smallest group of identical patterns. Below the threshold (--privacy-k, 5 by default), the
+// output is refused, unless the rare patterns are left out (--privacy-suppress-rare) or the
+// output is written anyway (--privacy-override). Both are reported with a warning W021.
+//
+// The snapshots of --save-validated are not guarded: they are for the tabulations of the same
+// operator, and need all the ballots.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::rcv::*;
+
+/// The default threshold of the k-anonymity of the ranking patterns.
+pub const DEFAULT_K: u64 = 5;
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct PrivacyGuard {
+    /// The number of votes under which a ranking pattern is rare.
+    pub k: u64,
+    /// Writes the rare patterns anyway (--privacy-override).
+    pub allow: bool,
+    /// Leaves the rare patterns out of the output (--privacy-suppress-rare).
+    pub suppress: bool,
+}
+
+impl PrivacyGuard {
+    pub fn from_args(args_o: &Option<Args>) -> PrivacyGuard {
+        PrivacyGuard {
+            k: args_o
+                .as_ref()
+                .and_then(|a| a.privacy_k)
+                .unwrap_or(DEFAULT_K),
+            allow: args_o.as_ref().is_some_and(|a| a.privacy_override),
+            suppress: args_o.as_ref().is_some_and(|a| a.privacy_suppress_rare),
+        }
+    }
+
+    /// The items of an output, without the items of the rare patterns if they are suppressed.
+    /// `pattern` gives the ranking pattern of an item and its number of votes.
+    pub fn check<'a, T: Clone, K: Hash + Eq>(
+        &self,
+        output: &str,
+        items: &'a [T],
+        pattern: impl Fn(&T) -> (K, u64),
+    ) -> RcvResult<Cow<'a, [T]>> {
+        let mut groups: HashMap<K, u64> = HashMap::new();
+        for item in items.iter() {
+            let (key, count) = pattern(item);
+            *groups.entry(key).or_insert(0) += count;
+        }
+        let rare: Vec<u64> = groups.values().copied().filter(|n| *n < self.k).collect();
+        let smallest = match rare.iter().min() {
+            Some(n) => *n,
+            None => return Ok(Cow::Borrowed(items)),
+        };
+        let rare_votes: u64 = rare.iter().sum();
+        if self.suppress {
+            codes::warning(
+                WarningCode::RarePatterns,
+                format!(
+                    "{}: {} ranking patterns cast by fewer than {} votes ({} votes) are left out (--privacy-suppress-rare)",
+                    output,
+                    rare.len(),
+                    self.k,
+                    rare_votes
+                ),
+            );
+            let kept: Vec<T> = items
+                .iter()
+                .filter(|item| groups[&pattern(item).0] >= self.k)
+                .cloned()
+                .collect();
+            return Ok(Cow::Owned(kept));
+        }
+        ensure!(
+            self.allow,
+            PrivacyThresholdSnafu {
+                output,
+                rare: rare.len(),
+                smallest,
+                k: self.k,
+            }
+        );
+        codes::warning(
+            WarningCode::RarePatterns,
+            format!(
+                "{}: {} ranking patterns cast by fewer than {} votes ({} votes, the smallest by {}) are written (--privacy-override)",
+                output,
+                rare.len(),
+                self.k,
+                rare_votes,
+                smallest
+            ),
+        );
+        Ok(Cow::Borrowed(items))
+    }
+}
@@ -0,0 +1,202 @@
+// The results in the layout of the summary CSV file of RCTab (--out-rctab-csv), to compare the
+// outputs of both tools.
+//
+// The file has three blocks: the contest information, the contest summary and the table of the
+// rounds. Each round has three columns: the votes, their share of the active ballots and the
+// transfer, which is the change of the votes of the candidate in the next round. The ballots
+// without any ranking are not part of the tabulation: the RCTab row of their number is left out.
+
+use crate::rcv::*;
+
+// The rows of the inactive ballots, in the order of RCTab.
+const INACTIVE_ROWS: [&str; 5] = [
+    "Inactive Ballots by Overvotes",
+    "Inactive Ballots by Skipped Rankings",
+    "Inactive Ballots by Exhausted Choices",
+    "Inactive Ballots by Repeated Rankings",
+    "Inactive Ballots Total",
+];
+
+fn inactive_counts(ib: &InactiveBallots) -> [u64; 5] {
+    [
+        ib.overvotes,
+        ib.skipped_rankings,
+        ib.exhausted_choices,
+        ib.repeated_rankings,
+        ib.total(),
+    ]
+}
+
+fn tally_of(rs: &RoundStats, name: &str) -> Option<u64> {
+    rs.tally.iter().find(|(n, _)| n == name).map(|(_, c)| *c)
+}
+
+// The candidates in the order of RCTab: the candidates that stay longer first, and by
+// descending votes in their last round.
+fn candidate_order(result: &VotingResult) -> Vec<String> {
+    let mut last_rounds: Vec<(String, usize, u64)> = Vec::new();
+    for (idx, rs) in result.round_stats.iter().enumerate() {
+        for (name, count) in rs.tally.iter() {
+            match last_rounds.iter_mut().find(|(n, _, _)| n == name) {
+                Some(entry) => *entry = (name.clone(), idx, *count),
+                None => last_rounds.push((name.clone(), idx, *count)),
+            }
+        }
+    }
+    last_rounds
+        .sort_by(|(n1, r1, c1), (n2, r2, c2)| r2.cmp(r1).then(c2.cmp(c1)).then_with(|| n1.cmp(n2)));
+    last_rounds.into_iter().map(|(name, _, _)| name).collect()
+}
+
+/// The rows of the file. The rows have different lengths, as in RCTab.
+pub fn rctab_rows(config: &RcvConfig, result: &VotingResult) -> RcvResult<Vec<Vec<String>>> {
+    let settings = &config.output_settings;
+    let rounding = settings.percent_rounding()?;
+    let winners: Vec<String> = result.winners.clone().unwrap_or_default();
+    let num_winners = config.rules.number_of_winners()?;
+    let first_round = result.round_stats.first();
+    let num_ballots: u64 = first_round
+        .map(|rs| rs.tally.iter().map(|(_, c)| c).sum::<u64>() + rs.inactive_ballots.total())
+        .unwrap_or(0);
+    let row = |cells: &[&str]| -> Vec<String> { cells.iter().map(|c| c.to_string()).collect() };
+
+    let mut rows: Vec<Vec<String>> = vec![
+        row(&["Contest Information"]),
+        row(&[
+            "Generated By",
+            &match settings.rendered_from {
+                Some(_) => format!(
+                    "timrcv {} ({})",
+                    env!("CARGO_PKG_VERSION"),
+                    render::RENDERED_NOTE
+                ),
+                None => format!("timrcv {}", env!("CARGO_PKG_VERSION")),
+            },
+        ]),
+        row(&["CSV Format Version", "1"]),
+        row(&[
+            "Type of Election",
+            if num_winners > 1 {
+                "Multi-Winner"
+            } else {
+                "Single-Winner"
+            },
+        ]),
+        row(&["Contest", &settings.contest_name]),
+        row(&[
+            "Jurisdiction",
+            settings.contest_juridiction.as_deref().unwrap_or_default(),
+        ]),
+        row(&[
+            "Office",
+            settings.contest_office.as_deref().unwrap_or_default(),
+        ]),
+        row(&["Date", settings.contest_date.as_deref().unwrap_or_default()]),
+        row(&["Winner(s)", &winners.join(", ")]),
+        row(&["Final Threshold", &result.threshold.to_string()]),
+        vec![],
+        row(&["Contest Summary"]),
+        row(&["Number to be Elected", &num_winners.to_string()]),
+        row(&["Number of Candidates", &result.candidates.len().to_string()]),
+        row(&["Total Number of Ballots", &num_ballots.to_string()]),
+        vec![],
+    ];
+
+    let rounds = &result.round_stats;
+    let mut header = row(&["Rounds"]);
+    let mut eliminated = row(&["Eliminated"]);
+    let mut elected = row(&["Elected"]);
+    for (idx, rs) in rounds.iter().enumerate() {
+        header.extend([
+            format!("Round {} Votes", rs.round),
+            "% of vote".to_string(),
+            "transfer".to_string(),
+        ]);
+        // As in the summary, the eliminated candidates are not output for the last round.
+        let names: Vec<&str> = rs
+            .tally_result_eliminated
+            .iter()
+            .filter(|_| idx + 1 < rounds.len())
+            .map(|es| es.name.as_str())
+            .collect();
+        eliminated.extend([names.join("; "), "".to_string(), "".to_string()]);
+        elected.extend([
+            rs.tally_results_elected.join("; "),
+            "".to_string(),
+            "".to_string(),
+        ]);
+    }
+    rows.extend([header, eliminated, elected]);
+
+    for name in candidate_order(result) {
+        let mut cells = vec![name.clone()];
+        for (idx, rs) in rounds.iter().enumerate() {
+            let active: u64 = rs.tally.iter().map(|(_, c)| c).sum();
+            match tally_of(rs, &name) {
+                Some(count) => {
+                    let next = match rounds.get(idx + 1) {
+                        Some(next_rs) => tally_of(next_rs, &name).unwrap_or(0),
+                        None => count,
+                    };
+                    cells.extend([
+                        count.to_string(),
+                        format!("{}%", rounding.format(count, active, 2)),
+                        (next as i64 - count as i64).to_string(),
+                    ]);
+                }
+                None => cells.extend(["".to_string(), "".to_string(), "".to_string()]),
+            }
+        }
+        rows.push(cells);
+    }
+
+    let mut active_row = row(&["Active Ballots"]);
+    let mut threshold_row = row(&["Current Round Threshold"]);
+    let mut inactive: Vec<Vec<String>> = INACTIVE_ROWS.iter().map(|r| row(&[r])).collect();
+    for (idx, rs) in rounds.iter().enumerate() {
+        let active: u64 = rs.tally.iter().map(|(_, c)| c).sum();
+        active_row.extend([active.to_string(), "".to_string(), "".to_string()]);
+        threshold_row.extend([rs.threshold.to_string(), "".to_string(), "".to_string()]);
+        let counts = inactive_counts(&rs.inactive_ballots);
+        let next_counts = rounds
+            .get(idx + 1)
+            .map(|next_rs| inactive_counts(&next_rs.inactive_ballots))
+            .unwrap_or(counts);
+        for (k, cells) in inactive.iter_mut().enumerate() {
+            cells.extend([
+                counts[k].to_string(),
+                "".to_string(),
+                (next_counts[k] as i64 - counts[k] as i64).to_string(),
+            ]);
+        }
+    }
+    rows.push(active_row);
+    rows.push(threshold_row);
+    rows.extend(inactive);
+    Ok(rows)
+}
+
+/// Writes the results in the layout of the RCTab summary CSV file.
+pub fn write_rctab_csv(path: &str, config: &RcvConfig, result: &VotingResult) -> RcvResult<()> {
+    let mut out: Vec<u8> = Vec::new();
+    for row in rctab_rows(config, result)? {
+        // The csv writer would quote an empty record, RCTab writes an empty line.
+        if row.is_empty() {
+            out.push(b'\n');
+            continue;
+        }
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_writer(&mut out);
+        writer.write_record(&row).context(CsvWriteSnafu { path })?;
+        writer
+            .flush()
+            .map_err(csv::Error::from)
+            .context(CsvWriteSnafu { path })?;
+    }
+    fs::write(path, out)
+        .map_err(csv::Error::from)
+        .context(CsvWriteSnafu { path })?;
+    Ok(())
+}
@@ -0,0 +1,145 @@
+// The invariants of the totals of a summary (--verify-output).
+//
+// The checks only use the summary, so that they hold whatever the path of the tabulation:
+// - the votes of each round and the inactive ballots add up to the ballots of the first round.
+//   Without the inactiveBallots blocks, the inactive ballots are the exhausted votes of the
+//   previous rounds;
+// - the transfers of an eliminated candidate add up to its votes, and the next round is the
+//   current one with the transfers applied;
+// - an elected candidate has at least the threshold, unless it is one of the last two
+//   continuing candidates and has at least half of the votes (a tie, or a threshold of the first
+//   round);
+// - the transfers of an excluded candidate add up to its first choices. They are already part of
+//   the first round.
+
+use crate::rcv::*;
+
+fn count(js: &JSValue) -> u64 {
+    match js {
+        JSValue::Number(n) => n.as_u64().unwrap_or(0),
+        JSValue::String(s) => s.trim().parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+fn counts(js: &JSValue) -> BTreeMap<String, u64> {
+    js.as_object()
+        .map(|m| m.iter().map(|(k, v)| (k.clone(), count(v))).collect())
+        .unwrap_or_default()
+}
+
+fn inactive_total(round_js: &JSValue) -> Option<u64> {
+    round_js
+        .get("inactiveBallots")
+        .and_then(|b| b.as_object())
+        .map(|b| b.values().map(count).sum())
+}
+
+/// The totals of the summary that do not reconcile, as messages. The summary is consistent if
+/// the list is empty.
+pub fn summary_mismatches(summary: &JSValue) -> Vec<String> {
+    let mut mismatches: Vec<String> = Vec::new();
+    let rounds: &[JSValue] = summary["results"]
+        .as_array()
+        .map(|r| r.as_slice())
+        .unwrap_or_default();
+    let threshold = count(&summary["config"]["threshold"]);
+    let excluded: BTreeMap<String, u64> = summary["excludedCandidates"]
+        .as_array()
+        .map(|es| {
+            es.iter()
+                .map(|e| {
+                    (
+                        e["name"].as_str().unwrap_or_default().to_string(),
+                        count(&e["votes"]),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let first_tally = rounds
+        .first()
+        .map(|r| counts(&r["tally"]))
+        .unwrap_or_default();
+    let initial: u64 =
+        first_tally.values().sum::<u64>() + rounds.first().and_then(inactive_total).unwrap_or(0);
+    let mut exhausted: u64 = 0;
+    for (idx, round_js) in rounds.iter().enumerate() {
+        let round = round_js["round"].as_u64().unwrap_or(idx as u64 + 1);
+        let tally = counts(&round_js["tally"]);
+        let total: u64 = tally.values().sum();
+        let inactive = inactive_total(round_js).unwrap_or(exhausted);
+        if total + inactive != initial {
+            mismatches.push(format!(
+                "round {}: {} votes and {} inactive ballots instead of {} ballots",
+                round, total, inactive, initial
+            ));
+        }
+
+        let mut next_tally = tally.clone();
+        for result in round_js["tallyResults"].as_array().into_iter().flatten() {
+            let transfers = counts(&result["transfers"]);
+            let transferred: u64 = transfers.values().sum();
+            if let Some(name) = result["elected"].as_str() {
+                let votes = tally.get(name).copied().unwrap_or(0);
+                let last_two = tally.len() <= 2 && votes * 2 >= total;
+                if votes < threshold && !last_two {
+                    mismatches.push(format!(
+                        "round {}: {} is elected with {} votes, below the threshold {}",
+                        round, name, votes, threshold
+                    ));
+                }
+                continue;
+            }
+            let name = match result["eliminated"].as_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            if round == 1 && !tally.contains_key(name) {
+                if let Some(votes) = excluded.get(name) {
+                    if transferred != *votes {
+                        mismatches.push(format!(
+                            "round 1: the excluded candidate {} transfers {} votes instead of {}",
+                            name, transferred, votes
+                        ));
+                    }
+                    continue;
+                }
+            }
+            let votes = tally.get(name).copied().unwrap_or(0);
+            if transferred != votes {
+                mismatches.push(format!(
+                    "round {}: {} is eliminated with {} votes but transfers {}",
+                    round, name, votes, transferred
+                ));
+            }
+            next_tally.remove(name);
+            for (to, votes) in transfers.iter() {
+                if to == "exhausted" {
+                    exhausted += votes;
+                } else {
+                    *next_tally.entry(to.clone()).or_insert(0) += votes;
+                }
+            }
+        }
+
+        // The next round is this round with the transfers applied. The candidates without votes
+        // may be eliminated without being reported.
+        next_tally.retain(|_, votes| *votes > 0);
+        if let Some(next_js) = rounds.get(idx + 1) {
+            let mut next = counts(&next_js["tally"]);
+            next.retain(|_, votes| *votes > 0);
+            if next != next_tally {
+                mismatches.push(format!(
+                    "round {}: the tally {:?} is not the tally of round {} with the transfers, {:?}",
+                    round + 1,
+                    next,
+                    round,
+                    next_tally
+                ));
+            }
+        }
+    }
+    mismatches
+}
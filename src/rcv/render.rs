@@ -0,0 +1,224 @@
+// The outputs of an election rendered from its summary, when the summary is the only data
+// available (render --from-summary).
+//
+// The summary is read permissively: a summary of timrcv (v1 or v2) or of RCTab, with the counts
+// as numbers or strings. The result has the rounds with their transfers and inactive ballots,
+// the winners (the candidates elected in the rounds), the threshold of the summary, the
+// candidates in their order of appearance, the elimination order and the excluded candidates.
+// The statistics that need the ballots (the rank positions, the rank origins and the ballots
+// that rank the winners) are empty.
+
+use crate::rcv::*;
+
+/// The mark of the outputs rendered from a summary.
+pub const RENDERED_NOTE: &str = "rendered from summary, not tabulated";
+
+// The key of the exhausted votes in the transfers.
+const EXHAUSTED: &str = "exhausted";
+
+fn invalid(path: &str, reason: &str) -> RcvError {
+    RcvError::InvalidSummary {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn count(js: &JSValue) -> Option<u64> {
+    match js {
+        JSValue::Number(n) => n.as_u64(),
+        JSValue::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn counts(path: &str, round: u32, js: &JSValue) -> RcvResult<Vec<(String, u64)>> {
+    let mut res: Vec<(String, u64)> = Vec::new();
+    for (name, count_js) in js.as_object().into_iter().flatten() {
+        let c = count(count_js).ok_or_else(|| {
+            invalid(
+                path,
+                &format!("round {}: invalid count {} for {}", round, count_js, name),
+            )
+        })?;
+        res.push((name.clone(), c));
+    }
+    Ok(res)
+}
+
+fn inactive_ballots(js: &JSValue) -> InactiveBallots {
+    let c = |key: &str| count(&js[key]).unwrap_or(0);
+    InactiveBallots {
+        exhausted_choices: c("exhaustedChoices"),
+        overvotes: c("overvotes"),
+        skipped_rankings: c("skippedRankings"),
+        repeated_rankings: c("repeatedRankings"),
+    }
+}
+
+fn read_round(path: &str, idx: usize, round_js: &JSValue) -> RcvResult<RoundStats> {
+    let round = round_js["round"]
+        .as_u64()
+        .map(|r| r as u32)
+        .unwrap_or(idx as u32 + 1);
+    ensure!(
+        round_js["tally"].is_object(),
+        InvalidSummarySnafu {
+            path,
+            reason: format!("round {} has no tally", round)
+        }
+    );
+    // As in the tabulation: by descending votes, then by name.
+    let mut tally = counts(path, round, &round_js["tally"])?;
+    tally.sort_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then_with(|| n1.cmp(n2)));
+    let mut elected: Vec<String> = Vec::new();
+    let mut eliminated: Vec<EliminationStats> = Vec::new();
+    for result_js in round_js["tallyResults"].as_array().into_iter().flatten() {
+        if let Some(name) = result_js["elected"].as_str() {
+            elected.push(name.to_string());
+        } else if let Some(name) = result_js["eliminated"].as_str() {
+            let transfers = counts(path, round, &result_js["transfers"])?;
+            let mut es = EliminationStats {
+                name: name.to_string(),
+                exhausted: transfers
+                    .iter()
+                    .filter(|(n, _)| n == EXHAUSTED)
+                    .map(|(_, c)| c)
+                    .sum(),
+                transfers: transfers
+                    .into_iter()
+                    .filter(|(n, _)| n != EXHAUSTED)
+                    .collect(),
+            };
+            es.sort_transfers();
+            eliminated.push(es);
+        }
+    }
+    // The summary does not have the threshold of each round: it is the majority of the tally,
+    // as in the tabulation.
+    let threshold = match tally.iter().map(|(_, c)| c).sum::<u64>() {
+        0 => 0,
+        total => total / 2 + 1,
+    };
+    let mut rs = RoundStats::new(RoundId(round), tally);
+    rs.tally_results_elected = elected;
+    rs.tally_result_eliminated = eliminated;
+    rs.informational = round_js["informational"].as_bool().unwrap_or(false);
+    rs.inactive_ballots = round_js
+        .get("inactiveBallots")
+        .map(inactive_ballots)
+        .unwrap_or_default();
+    rs.threshold = threshold;
+    Ok(rs)
+}
+
+/// Reads the summary as the result of a tabulation, with the configuration of its contest.
+pub fn result_from_summary(path: &str) -> RcvResult<(RcvConfig, VotingResult)> {
+    let contents = fs::read_to_string(path).context(OpeningJsonSnafu { path })?;
+    let js: JSValue = serde_json::from_str(&contents).context(ParsingJsonSnafu {})?;
+    let rounds_js = js["results"]
+        .as_array()
+        .ok_or_else(|| invalid(path, "expected the rounds in \"results\""))?;
+    let round_stats: Vec<RoundStats> = rounds_js
+        .iter()
+        .enumerate()
+        .map(|(idx, round_js)| read_round(path, idx, round_js))
+        .collect::<RcvResult<_>>()?;
+    ensure!(
+        !round_stats.is_empty(),
+        InvalidSummarySnafu {
+            path,
+            reason: "the summary has no round"
+        }
+    );
+
+    let excluded: Vec<(String, u64)> = js["excludedCandidates"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|e| Some((e["name"].as_str()?.to_string(), count(&e["votes"])?)))
+        .collect();
+    // The declared candidates of a v2 summary, or the candidates of the tallies.
+    let mut candidates: Vec<CandidateInfo> = js["candidates"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|c| {
+            Some(CandidateInfo {
+                name: c["name"].as_str()?.to_string(),
+                code: c["code"].as_str().map(|s| s.to_string()),
+                excluded: c["excluded"].as_bool().unwrap_or(false),
+                inferred: c["inferred"].as_bool().unwrap_or(false),
+            })
+        })
+        .collect();
+    if candidates.is_empty() {
+        for (name, _) in round_stats.iter().flat_map(|rs| rs.tally.iter()) {
+            if !candidates.iter().any(|c| &c.name == name) {
+                candidates.push(CandidateInfo {
+                    name: name.clone(),
+                    code: None,
+                    excluded: false,
+                    inferred: false,
+                });
+            }
+        }
+    }
+
+    let decisive: Vec<&RoundStats> = round_stats.iter().filter(|rs| !rs.informational).collect();
+    let winners: Vec<String> = decisive
+        .iter()
+        .flat_map(|rs| rs.tally_results_elected.iter().cloned())
+        .collect();
+    // The excluded candidates are eliminated in the first round, but they are not part of the
+    // elimination order. The winners come last.
+    let entry = |rs: &RoundStats, name: &str, elected: bool| {
+        let votes = rs
+            .tally
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, c)| *c)
+            .unwrap_or(0);
+        let total: u64 = rs.tally.iter().map(|(_, c)| c).sum();
+        EliminationEntry {
+            name: name.to_string(),
+            round: rs.round,
+            votes,
+            share_percent: PercentRounding::default().format(votes, total, 1),
+            elected,
+        }
+    };
+    let mut elimination_order: Vec<EliminationEntry> = Vec::new();
+    for rs in decisive.iter() {
+        let mut names: Vec<EliminationEntry> = rs
+            .tally_result_eliminated
+            .iter()
+            .filter(|es| !excluded.iter().any(|(n, _)| n == &es.name))
+            .map(|es| entry(rs, &es.name, false))
+            .collect();
+        names.sort_by(|e1, e2| e1.votes.cmp(&e2.votes).then_with(|| e1.name.cmp(&e2.name)));
+        elimination_order.extend(names);
+    }
+    if let Some(last) = decisive.last() {
+        elimination_order.extend(winners.iter().map(|w| entry(last, w, true)));
+    }
+
+    let mut config = RcvConfig::config_from_args(&Some(path.to_string()))?;
+    let settings = &mut config.output_settings;
+    let text = |key: &str| js["config"][key].as_str().map(|s| s.to_string());
+    if let Some(contest) = text("contest") {
+        settings.contest_name = contest;
+    }
+    settings.contest_date = text("date");
+    settings.contest_juridiction = text("jurisdiction");
+    settings.contest_office = text("office");
+    settings.rendered_from = Some(path.to_string());
+
+    let mut result = VotingResult::default();
+    result.winners = Some(winners).filter(|w| !w.is_empty());
+    result.threshold = count(&js["config"]["threshold"]).unwrap_or(0);
+    result.round_stats = round_stats;
+    result.candidates = candidates;
+    result.elimination_order = elimination_order;
+    result.excluded = excluded;
+    Ok((config, result))
+}
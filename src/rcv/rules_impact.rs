@@ -0,0 +1,225 @@
+// The impact of the rules on the ballots of a tabulation, printed at the end of the run and
+// written under 'rulesImpact' in the v2 summary.
+//
+// For each rule that can change how a ballot counts, the votes of the ballots it affected:
+// - overvoteRule: the ballots exhausted by an overvote, or the ballots whose overvoted ranks
+//   were skipped;
+// - exhaustOnDuplicateCandidate: the ballots exhausted by a repeated candidate, or the ballots
+//   whose repeated rankings were skipped to reach a later ranking;
+// - maxSkippedRanksAllowed: the ballots exhausted by too many skipped ranks;
+// - maxRankingsAllowed: the ballots with marks beyond the limit, which are not counted.
+//
+// The exhausted ballots are the inactive ballots of the final round. The skipped and truncated
+// ranks are counted on the ballots given to the tabulation, whether or not the tabulation
+// reached them. When a rule affected more votes than the margin of the final round, the choice
+// of this rule may have decided the outcome.
+
+use crate::rcv::*;
+
+/// The ballots affected by one rule.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct RuleImpact {
+    /// The option of the rules, as in the configuration.
+    pub rule: &'static str,
+    /// The value of the option.
+    pub setting: String,
+    /// What the rule did to the ballots: exhausted, skipped or truncated.
+    pub effect: &'static str,
+    /// The votes of the affected ballots.
+    pub ballots: u64,
+    /// The ranks skipped or truncated in these ballots, for the rules that do not exhaust them.
+    pub ranks: Option<u64>,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct RulesImpact {
+    pub rules: Vec<RuleImpact>,
+    /// The difference of votes between the first two candidates of the final round, if there is
+    /// a winner.
+    pub final_round_margin: Option<u64>,
+}
+
+fn is_mark(choice: &BallotChoice) -> bool {
+    !matches!(choice, BallotChoice::Blank | BallotChoice::Undervote)
+}
+
+// The votes of the ballots with at least one of the given ranks, and the number of these ranks.
+fn count_ranks<'a>(
+    ballots: &[&'a Ballot],
+    ranks: impl Fn(&'a Ballot) -> u64,
+) -> (u64, Option<u64>) {
+    let mut votes: u64 = 0;
+    let mut num_ranks: u64 = 0;
+    for b in ballots.iter() {
+        let n = ranks(b);
+        if n > 0 {
+            votes += b.count;
+            num_ranks += n * b.count;
+        }
+    }
+    (votes, Some(num_ranks))
+}
+
+// The repeated rankings of a candidate that change how the ballot is read: the ones followed by
+// a mark that is not itself a repeated ranking. The ballot would stop at them if they exhausted
+// it, while the other repeated rankings are not followed by anything the tabulation could read.
+fn skipped_duplicates(choices: &[BallotChoice]) -> u64 {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let repeated: Vec<bool> = choices
+        .iter()
+        .map(|c| match c {
+            BallotChoice::Candidate(name) => !seen.insert(name.as_str()),
+            _ => false,
+        })
+        .collect();
+    let mut num_skipped: u64 = 0;
+    let mut mark_after = false;
+    for (choice, is_repeated) in choices.iter().zip(repeated).rev() {
+        if is_repeated {
+            num_skipped += mark_after as u64;
+        } else if is_mark(choice) {
+            mark_after = true;
+        }
+    }
+    num_skipped
+}
+
+impl RulesImpact {
+    /// The impact of the rules on the ballots of the tabulation of the result.
+    pub fn new<'a>(
+        rules: &VoteRules,
+        result: &VotingResult,
+        ballots: impl IntoIterator<Item = &'a Ballot>,
+    ) -> RulesImpact {
+        let ballots: Vec<&Ballot> = ballots.into_iter().collect();
+        let max_rankings = rules
+            .max_rankings_allowed
+            .map_or(usize::MAX, |m| m as usize);
+        // The inactive ballots at the end of the last round that is not informational.
+        let inactive = result
+            .round_stats
+            .iter()
+            .rfind(|rs| !rs.informational)
+            .map(|rs| rs.inactive_ballots.clone())
+            .unwrap_or_default();
+        let mut impacts: Vec<RuleImpact> = Vec::new();
+
+        let (setting, effect, (votes, ranks)) = match rules.overvote_rule {
+            OverVoteRule::ExhaustImmediately => (
+                "exhaustImmediately",
+                "exhausted",
+                (inactive.overvotes, None),
+            ),
+            OverVoteRule::AlwaysSkipToNextRank => (
+                "alwaysSkipToNextRank",
+                "skipped",
+                count_ranks(&ballots, |b| {
+                    b.candidates
+                        .iter()
+                        .take(max_rankings)
+                        .filter(|c| **c == BallotChoice::Overvote)
+                        .count() as u64
+                }),
+            ),
+        };
+        impacts.push(RuleImpact {
+            rule: "overvoteRule",
+            setting: setting.to_string(),
+            effect,
+            ballots: votes,
+            ranks,
+        });
+
+        let (setting, effect, (votes, ranks)) = match rules.duplicate_candidate_mode {
+            DuplicateCandidateMode::Exhaust => {
+                ("true", "exhausted", (inactive.repeated_rankings, None))
+            }
+            DuplicateCandidateMode::SkipDuplicate => (
+                "false",
+                "skipped",
+                count_ranks(&ballots, |b| {
+                    skipped_duplicates(&b.candidates[..b.candidates.len().min(max_rankings)])
+                }),
+            ),
+        };
+        impacts.push(RuleImpact {
+            rule: "exhaustOnDuplicateCandidate",
+            setting: setting.to_string(),
+            effect,
+            ballots: votes,
+            ranks,
+        });
+
+        let max_skipped = match rules.max_skipped_rank_allowed {
+            MaxSkippedRank::Unlimited => None,
+            MaxSkippedRank::ExhaustOnFirstOccurence => Some(0),
+            MaxSkippedRank::MaxAllowed(n) => Some(n),
+        };
+        if let Some(n) = max_skipped {
+            impacts.push(RuleImpact {
+                rule: "maxSkippedRanksAllowed",
+                setting: n.to_string(),
+                effect: "exhausted",
+                ballots: inactive.skipped_rankings,
+                ranks: None,
+            });
+        }
+
+        if let Some(n) = rules.max_rankings_allowed {
+            let (votes, ranks) = count_ranks(&ballots, |b| {
+                b.candidates
+                    .iter()
+                    .skip(max_rankings)
+                    .filter(|c| is_mark(c))
+                    .count() as u64
+            });
+            impacts.push(RuleImpact {
+                rule: "maxRankingsAllowed",
+                setting: n.to_string(),
+                effect: "truncated",
+                ballots: votes,
+                ranks,
+            });
+        }
+
+        RulesImpact {
+            rules: impacts,
+            final_round_margin: result
+                .winners
+                .as_ref()
+                .and_then(|_| ranked_voting::metrics::competitiveness(result).final_round_margin),
+        }
+    }
+
+    /// True if a rule affected more votes than the margin of the final round.
+    pub fn exceeds_margin(&self) -> bool {
+        match self.final_round_margin {
+            Some(margin) => self.rules.iter().any(|r| r.ballots > margin),
+            None => false,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines: Vec<String> = vec![format!(
+            "{:<28} {:<20} {:<10} {:>10} {:>10}",
+            "rule", "setting", "effect", "ballots", "ranks"
+        )];
+        for r in self.rules.iter() {
+            lines.push(format!(
+                "{:<28} {:<20} {:<10} {:>10} {:>10}",
+                r.rule,
+                r.setting,
+                r.effect,
+                r.ballots,
+                r.ranks.map(|n| n.to_string()).unwrap_or_default()
+            ));
+        }
+        if let (true, Some(margin)) = (self.exceeds_margin(), self.final_round_margin) {
+            lines.push(format!(
+                "rule impact exceeds winning margin ({} votes) — consider sensitivity analysis",
+                margin
+            ));
+        }
+        lines.join("\n")
+    }
+}
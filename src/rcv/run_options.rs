@@ -0,0 +1,91 @@
+// The options of a run that are not part of the configuration of the election: the additional
+// outputs, the snapshots of the validated ballots, the checks of the run and its logs. They come
+// from the command line, and are resolved once before the run (see run_election), so that a
+// wrong value fails before reading the ballots.
+
+use crate::rcv::analysis::Analysis;
+use crate::rcv::privacy::PrivacyGuard;
+use crate::rcv::*;
+
+/// The options of a run of the election, resolved from the command line.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// The warnings that fail the run (--fail-on).
+    pub fail_on: BTreeSet<WarningCode>,
+    /// Which ballots to tabulate when some are challenged (--with-challenged).
+    pub challenged_mode: ChallengedMode,
+    /// The analysis added to the summary (--analysis).
+    pub analysis: Option<Analysis>,
+    pub out_xlsx: Option<String>,
+    /// A template (see output_name), like the path of the summary.
+    pub out_flat_csv: Option<String>,
+    /// A template (see output_name), like the path of the summary.
+    pub out_rctab_csv: Option<String>,
+    pub out_fairvote_row: Option<String>,
+    pub out_metrics: Option<String>,
+    /// The directory of the rounds written during the tabulation (--out-partial).
+    pub out_partial: Option<String>,
+    pub keep_partials: bool,
+    pub save_validated: Option<String>,
+    pub load_validated: Option<String>,
+    pub allow_rules_mismatch: bool,
+    pub export_simple_csv: Option<String>,
+    pub export_preflib: Option<String>,
+    /// The guard of the exports with one row per ballot (--privacy-k...).
+    pub privacy: PrivacyGuard,
+    pub ledger: Option<String>,
+    pub allow_duplicate_ledger: bool,
+    pub skip_bad_sources: bool,
+    pub audit_first_choices: Option<String>,
+    pub audit_tolerance: u64,
+    pub emit_provenance: bool,
+    pub verify_output: bool,
+    pub reference_strict: bool,
+    /// The flag set by Ctrl-C, which interrupts the tabulation (set by the program, not an
+    /// argument).
+    pub interrupt: Option<Arc<AtomicBool>>,
+    pub log_top: Option<usize>,
+    pub debug_sample_rate: Option<SampleRate>,
+}
+
+impl RunOptions {
+    /// The options of the command line, or the defaults without one.
+    pub fn from_args(args_o: &Option<Args>) -> RcvResult<RunOptions> {
+        let text = |f: fn(&Args) -> &Option<String>| args_o.as_ref().and_then(|a| f(a).clone());
+        let flag = |f: fn(&Args) -> bool| args_o.as_ref().is_some_and(f);
+        Ok(RunOptions {
+            fail_on: fail_on_codes(args_o)?,
+            challenged_mode: ChallengedMode::parse(
+                args_o.as_ref().and_then(|a| a.with_challenged.as_deref()),
+            )?,
+            analysis: match args_o.as_ref().and_then(|a| a.analysis.as_deref()) {
+                Some(name) => Some(Analysis::parse(name)?),
+                None => None,
+            },
+            out_xlsx: text(|a| &a.out_xlsx),
+            out_flat_csv: text(|a| &a.out_flat_csv),
+            out_rctab_csv: text(|a| &a.out_rctab_csv),
+            out_fairvote_row: text(|a| &a.out_fairvote_row),
+            out_metrics: text(|a| &a.out_metrics),
+            out_partial: text(|a| &a.out_partial),
+            keep_partials: flag(|a| a.keep_partials),
+            save_validated: text(|a| &a.save_validated),
+            load_validated: text(|a| &a.load_validated),
+            allow_rules_mismatch: flag(|a| a.allow_rules_mismatch),
+            export_simple_csv: text(|a| &a.export_simple_csv),
+            export_preflib: text(|a| &a.export_preflib),
+            privacy: PrivacyGuard::from_args(args_o),
+            ledger: text(|a| &a.ledger),
+            allow_duplicate_ledger: flag(|a| a.allow_duplicate_ledger),
+            skip_bad_sources: flag(|a| a.skip_bad_sources),
+            audit_first_choices: text(|a| &a.audit_first_choices),
+            audit_tolerance: args_o.as_ref().map_or(0, |a| a.audit_tolerance),
+            emit_provenance: flag(|a| a.emit_provenance),
+            verify_output: flag(|a| a.verify_output),
+            reference_strict: flag(|a| a.reference_strict),
+            interrupt: None,
+            log_top: args_o.as_ref().and_then(|a| a.log_top),
+            debug_sample_rate: args_o.as_ref().and_then(|a| a.debug_sample_rate),
+        })
+    }
+}
@@ -0,0 +1,323 @@
+// Sanity checks on the parsed ballots, to catch misconfigured sources before tabulating.
+
+use std::borrow::Cow;
+
+use crate::rcv::*;
+
+/// The minimum fraction of the choices that must match a declared candidate.
+pub const MIN_CANDIDATE_HIT_RATE: f64 = 0.5;
+
+// The cells that validate_ballots interprets as something else than a candidate name.
+fn is_label(cell: &str, cfs: &FileSource) -> bool {
+    cell.is_empty()
+        || cell == "UWI"
+        || cfs.undervote_label.as_deref() == Some(cell)
+        || cfs.overvote_label.as_deref() == Some(cell)
+        || cfs
+            .overvote_delimiter
+            .as_deref()
+            .map(|delim| cell.contains(delim))
+            .unwrap_or(false)
+}
+
+/// Checks that most of the choices of a source are declared candidates, both overall and in the
+/// first rank. A low rate usually means that `firstVoteColumnIndex` points to the wrong column,
+/// which turns most choices into write-ins. The error lists the most common unknown choices and
+/// the columns that contain candidate names.
+pub fn check_candidate_hit_rate(
+    parsed_ballots: &[ParsedBallot],
+    candidates: &[RcvCandidate],
+    cfs: &FileSource,
+) -> RcvResult<()> {
+    // The names are compared in NFC, as when the ballots are matched with the candidates.
+    let names: HashSet<String> = candidates
+        .iter()
+        .map(|c| nfc(&c.name).into_owned())
+        .collect();
+    // For each rank position: the number of cells and of candidate names.
+    let mut positions: Vec<(u64, u64)> = Vec::new();
+    let mut unmatched: HashMap<&str, u64> = HashMap::new();
+    for pb in parsed_ballots.iter() {
+        for (idx, group) in pb.choices.iter().enumerate() {
+            let cell = match group.as_slice() {
+                [c] if !is_label(c.trim(), cfs) => c.trim(),
+                _ => continue,
+            };
+            if positions.len() <= idx {
+                positions.resize(idx + 1, (0, 0));
+            }
+            positions[idx].0 += 1;
+            if names.contains(nfc(cell).as_ref()) {
+                positions[idx].1 += 1;
+            } else {
+                *unmatched.entry(cell).or_insert(0) += 1;
+            }
+        }
+    }
+    let low_rate = |(n, h): (u64, u64)| n > 0 && (h as f64) < MIN_CANDIDATE_HIT_RATE * (n as f64);
+    let total: u64 = positions.iter().map(|(n, _)| n).sum();
+    let hits: u64 = positions.iter().map(|(_, h)| h).sum();
+    // The first rank is checked on its own: a column shifted by one to the left only adds one
+    // column of unknown strings, which may not be enough to lower the overall rate.
+    let first_rank = positions.first().cloned().unwrap_or((0, 0));
+    if !low_rate((total, hits)) && !low_rate(first_rank) {
+        return Ok(());
+    }
+
+    let mut common: Vec<(&str, u64)> = unmatched.into_iter().collect();
+    common.sort_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then(n1.cmp(n2)));
+    let first_col = cfs.first_vote_column_index()?;
+    let candidate_columns: Vec<String> = positions
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.0 > 0 && !low_rate(**p))
+        .map(|(idx, _)| (first_col + idx + 1).to_string())
+        .collect();
+    let suggestion = match candidate_columns.first() {
+        Some(col) => format!(
+            " The columns {} contain candidate names: did you mean \"firstVoteColumnIndex\": \"{}\"?",
+            candidate_columns.join(", "),
+            col
+        ),
+        None => " No column contains the names of the declared candidates.".to_string(),
+    };
+    let (scope, (n, h)) = if low_rate((total, hits)) {
+        ("choices", (total, hits))
+    } else {
+        ("first choices", first_rank)
+    };
+    Err(RcvError::CandidateHitRate {
+        scope: scope.to_string(),
+        percent: format!("{:.1}", (h as f64) * 100.0 / (n as f64)),
+        column: (first_col + 1).to_string(),
+        unmatched: common
+            .iter()
+            .take(3)
+            .map(|(n, c)| format!("'{}' ({})", n, c))
+            .collect::<Vec<_>>()
+            .join(", "),
+        suggestion,
+    })
+}
+
+/// The maximum number of edits between two names for them to be reported as a likely typo.
+pub const MAX_NEAR_MISS_EDITS: usize = 2;
+
+/// How two different names are close to each other.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum NearMiss {
+    /// The names differ by a few characters, which is likely a typo.
+    Typo,
+    /// The names only differ by their trailing parenthetical label, as the lines of the same
+    /// person for different parties ("Jane Doe (DEM)" and "Jane Doe (GOP)"). These are distinct
+    /// candidates.
+    PartyLabel,
+}
+
+// The number of characters to insert, delete or replace to change a into b.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// Splits the trailing parenthetical label of a name: "Jane Doe (DEM)" is ("Jane Doe", "DEM").
+fn split_label(name: &str) -> (&str, Option<&str>) {
+    let name = name.trim();
+    match name.strip_suffix(')').and_then(|s| s.rsplit_once('(')) {
+        Some((base, label)) if !base.trim().is_empty() => (base.trim(), Some(label.trim())),
+        _ => (name, None),
+    }
+}
+
+/// Compares two different names. The names with the same base and different parenthetical
+/// labels are party labels, unless the labels themselves differ by a single character.
+pub fn near_miss(n1: &str, n2: &str) -> Option<NearMiss> {
+    if let ((b1, Some(l1)), (b2, Some(l2))) = (split_label(n1), split_label(n2)) {
+        if b1.to_lowercase() == b2.to_lowercase() {
+            return match edit_distance(&l1.to_lowercase(), &l2.to_lowercase()) {
+                0 | 1 => Some(NearMiss::Typo),
+                _ => Some(NearMiss::PartyLabel),
+            };
+        }
+    }
+    let edits = edit_distance(n1, n2);
+    // A few edits are only a typo for long enough names: "A" and "B" are not close.
+    let min_len = n1.chars().count().min(n2.chars().count());
+    Some(NearMiss::Typo).filter(|_| edits <= MAX_NEAR_MISS_EDITS && edits * 4 <= min_len)
+}
+
+/// Reports the declared candidates that are close to each other. The party labels are only
+/// logged: they are expected in fusion voting.
+pub fn check_declared_near_misses(candidates: &[RcvCandidate]) -> Vec<(String, String, NearMiss)> {
+    let mut res: Vec<(String, String, NearMiss)> = Vec::new();
+    for (idx, c1) in candidates.iter().enumerate() {
+        for c2 in candidates.iter().skip(idx + 1) {
+            let m = match near_miss(&c1.name, &c2.name) {
+                Some(m) => m,
+                None => continue,
+            };
+            match m {
+                NearMiss::Typo => codes::warning(
+                    WarningCode::NearMissCandidate,
+                    format!(
+                        "the declared candidates '{}' and '{}' differ by only a few characters",
+                        c1.name, c2.name
+                    ),
+                ),
+                NearMiss::PartyLabel => info!(
+                    "the declared candidates '{}' and '{}' only differ by their party label: they are distinct candidates",
+                    c1.name, c2.name
+                ),
+            }
+            res.push((c1.name.clone(), c2.name.clone(), m));
+        }
+    }
+    res
+}
+
+/// Reports the names of the ballots that are not declared candidates but are close to one. They
+/// are counted as write-ins. A likely typo is a warning, with the number of cells of each name.
+/// A name that only differs by its party label is logged, since it may be a distinct line of
+/// the ballot that should be declared.
+pub fn check_undeclared_near_misses(
+    path: &str,
+    parsed_ballots: &[ParsedBallot],
+    candidates: &[RcvCandidate],
+    cfs: &FileSource,
+) -> Vec<(String, String, NearMiss)> {
+    // The names are compared in NFC, as in check_candidate_hit_rate.
+    let names: HashSet<String> = candidates
+        .iter()
+        .map(|c| nfc(&c.name).into_owned())
+        .collect();
+    let mut undeclared: BTreeMap<Cow<str>, u64> = BTreeMap::new();
+    for pb in parsed_ballots.iter() {
+        for group in pb.choices.iter() {
+            for cell in group.iter().map(|c| c.trim()) {
+                let name = nfc(cell);
+                if !is_label(cell, cfs) && !names.contains(name.as_ref()) {
+                    *undeclared.entry(name).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    let mut res: Vec<(String, String, NearMiss)> = Vec::new();
+    let mut typos: Vec<String> = Vec::new();
+    for (name, count) in undeclared.iter() {
+        let misses: Vec<(&str, NearMiss)> = candidates
+            .iter()
+            .filter_map(|c| near_miss(name, &nfc(&c.name)).map(|m| (c.name.as_str(), m)))
+            .collect();
+        // A typo of a candidate is reported even if the name also has the label of another one.
+        let closest = misses
+            .iter()
+            .find(|(_, m)| *m == NearMiss::Typo)
+            .or_else(|| misses.first());
+        match closest {
+            Some((candidate, NearMiss::Typo)) => {
+                typos.push(format!("'{}' ({} times) is close to '{}'", name, count, candidate))
+            }
+            Some((candidate, NearMiss::PartyLabel)) => info!(
+                "in {}, '{}' ({} times) only differs from the candidate '{}' by its party label: it is counted as a write-in, declare it as a candidate if it is a distinct line of the ballot",
+                path, name, count, candidate
+            ),
+            None => continue,
+        }
+        if let Some((candidate, m)) = closest {
+            res.push((name.to_string(), candidate.to_string(), *m));
+        }
+    }
+    if !typos.is_empty() {
+        codes::warning(
+            WarningCode::NearMissCandidate,
+            format!(
+                "in {}, some names are not declared candidates and are counted as write-ins: {}",
+                path,
+                typos.join(", ")
+            ),
+        );
+    }
+    res
+}
+
+/// The default maximum number of candidates inferred from a source (maxInferredCandidates).
+pub const DEFAULT_MAX_INFERRED_CANDIDATES: usize = 100;
+
+/// The maximum number of inferred candidates, as a fraction of the ballots of the source.
+pub const MAX_INFERRED_CANDIDATES_SHARE: f64 = 0.5;
+
+// A small election may have as many candidates as ballots: the share of the ballots is only
+// checked from this number of candidates.
+const MIN_INFERRED_CANDIDATES_CHECKED: usize = 20;
+
+/// Checks the number of candidates inferred from the ballots of a source without declared
+/// candidates. Hundreds of candidates, or more candidates than half of the ballots, usually
+/// means that the choice columns point to a column of free text. This is a warning with the
+/// ten most common names, or an error with strictInference (--strict).
+pub fn check_inferred_candidates(
+    path: &str,
+    parsed_ballots: &[ParsedBallot],
+    candidates: &[RcvCandidate],
+    cfs: &FileSource,
+) -> RcvResult<()> {
+    let max_candidates = cfs
+        .max_inferred_candidates
+        .unwrap_or(DEFAULT_MAX_INFERRED_CANDIDATES);
+    let num_candidates = candidates.len();
+    let votes: u64 = parsed_ballots.iter().map(|pb| pb.count.unwrap_or(1)).sum();
+    let too_many = num_candidates > max_candidates
+        || (num_candidates >= MIN_INFERRED_CANDIDATES_CHECKED
+            && (num_candidates as f64) > MAX_INFERRED_CANDIDATES_SHARE * (votes as f64));
+    if !too_many {
+        return Ok(());
+    }
+
+    let names: HashSet<String> = candidates
+        .iter()
+        .map(|c| nfc(&c.name).into_owned())
+        .collect();
+    let mut counts: HashMap<Cow<str>, u64> = HashMap::new();
+    for pb in parsed_ballots.iter() {
+        for group in pb.choices.iter() {
+            for cell in group.iter().map(|c| nfc(c.trim())) {
+                if names.contains(cell.as_ref()) {
+                    *counts.entry(cell).or_insert(0) += pb.count.unwrap_or(1);
+                }
+            }
+        }
+    }
+    let mut common: Vec<(Cow<str>, u64)> = counts.into_iter().collect();
+    common.sort_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then(n1.cmp(n2)));
+    let common = common
+        .iter()
+        .take(10)
+        .map(|(n, c)| format!("'{}' ({})", n, c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if cfs.strict_inference == Some(true) {
+        return Err(RcvError::TooManyCandidates {
+            path: path.to_string(),
+            num_candidates,
+            votes,
+            common,
+        });
+    }
+    codes::warning(
+        WarningCode::TooManyCandidates,
+        format!(
+            "{} candidates were inferred from the {} ballots of {}, which is suspiciously many. The most common are {}. Declare the candidates, or check the column indices (firstVoteColumnIndex)",
+            num_candidates, votes, path, common
+        ),
+    );
+    Ok(())
+}
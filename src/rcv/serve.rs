@@ -0,0 +1,233 @@
+// The server mode (serve --stdio), for the front-ends that drive timrcv as a subprocess.
+//
+// Each line of the standard input is a JSON-RPC 2.0 request, and each response is written on
+// one line of the standard output. The session keeps the configuration, the validated ballots
+// and the last tabulation between the requests, so that a change of the rules only runs the
+// tabulation again. The methods are, in the order of a session:
+// - loadConfig {"path": "config.json"}: reads the configuration, and forgets the ballots;
+// - parseSources {}: reads and validates the ballots of the file sources;
+// - tabulate {"rules": {"overvoteRule": "exhaustImmediately"}}: tabulates the ballots, with the
+//   rules of the configuration and the optional overrides (as with --rule);
+// - getRounds {}: the rounds of the last tabulation, as in the summary;
+// - shutdown {}: ends the session.
+//
+// A notification (a request without id) is handled, but gets no response.
+//
+// The errors of timrcv are JSON-RPC errors with the code -32000 - N for the stable error EN (for
+// example -32001 for E001), and the stable code and name in the data.
+
+use std::io::{BufRead, Write};
+
+use crate::rcv::*;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The state of the server between the requests.
+#[derive(Default)]
+pub struct Session {
+    config: Option<(RcvConfig, String)>,
+    ballots: Option<(Vec<Ballot>, Option<Vec<RcvCandidate>>)>,
+    summary: Option<JSValue>,
+    pub shutdown: bool,
+}
+
+// An error of a request, before it is written as a JSON-RPC error.
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<JSValue>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: &str) -> RpcError {
+        RpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+}
+
+impl From<RcvError> for RpcError {
+    fn from(e: RcvError) -> RpcError {
+        let code = e.code();
+        let number: i64 = code.code()[1..].parse().unwrap_or(0);
+        let message = match e.to_string() {
+            m if m.is_empty() => format!("{:?}", e),
+            m => m,
+        };
+        RpcError {
+            code: -32000 - number,
+            message,
+            data: Some(json!({"code": code.code(), "name": code.name()})),
+        }
+    }
+}
+
+impl Session {
+    /// Handles one line of the input, and returns the line of the response. The notifications
+    /// (requests without id) are handled like the other requests, but get no response, even
+    /// for an error.
+    pub fn handle_line(&mut self, line: &str) -> Option<String> {
+        let (id, res) = match serde_json::from_str::<JSValue>(line) {
+            Ok(request) => match request.get("id").cloned() {
+                Some(id) => (id, self.handle(&request)),
+                None => {
+                    if let Err(e) = self.handle(&request) {
+                        debug!("error of a notification: {}", e.message);
+                    }
+                    return None;
+                }
+            },
+            Err(e) => (
+                JSValue::Null,
+                Err(RpcError::new(PARSE_ERROR, &e.to_string())),
+            ),
+        };
+        let response = match res {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => {
+                let mut error = json!({"code": e.code, "message": e.message});
+                if let Some(data) = e.data {
+                    error["data"] = data;
+                }
+                json!({"jsonrpc": "2.0", "id": id, "error": error})
+            }
+        };
+        Some(response.to_string())
+    }
+
+    fn handle(&mut self, request: &JSValue) -> Result<JSValue, RpcError> {
+        let method = request["method"]
+            .as_str()
+            .ok_or_else(|| RpcError::new(INVALID_REQUEST, "the request has no method"))?;
+        let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+        codes::take_warnings();
+        let mut result = match method {
+            "loadConfig" => self.load_config(&params)?,
+            "parseSources" => self.parse_sources()?,
+            "tabulate" => self.tabulate(&params)?,
+            "getRounds" => self
+                .summary
+                .as_ref()
+                .map(|s| s["results"].clone())
+                .ok_or_else(|| RpcError::new(INVALID_REQUEST, "no tabulation yet"))?,
+            "shutdown" => {
+                self.shutdown = true;
+                JSValue::Null
+            }
+            m => {
+                return Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    &format!("unknown method {}", m),
+                ))
+            }
+        };
+        let warnings = codes::take_warnings();
+        if let (false, Some(obj)) = (warnings.is_empty(), result.as_object_mut()) {
+            obj.insert("warnings".to_string(), codes::warnings_to_json(&warnings));
+        }
+        Ok(result)
+    }
+
+    fn load_config(&mut self, params: &JSValue) -> Result<JSValue, RpcError> {
+        let path = params["path"]
+            .as_str()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "loadConfig needs a path"))?;
+        let config = resolve_config(&Some(path.to_string()), &None, &None)?;
+        validate_rules(&config.rules)?;
+        let result = json!({
+            "candidates": config.candidates.len(),
+            "sources": config.cvr_file_sources.len(),
+        });
+        self.config = Some((config, path.to_string()));
+        self.ballots = None;
+        self.summary = None;
+        Ok(result)
+    }
+
+    fn parse_sources(&mut self) -> Result<JSValue, RpcError> {
+        let (config, path) = self
+            .config
+            .as_ref()
+            .ok_or_else(|| RpcError::new(INVALID_REQUEST, "no configuration loaded"))?;
+        let mut category_totals = CategoryTotals::new();
+        let mut source_totals: Vec<SourceTotal> = Vec::new();
+        let (data, candidates_o) = read_election_data(
+            config,
+            &Some(path.clone()),
+            &mut category_totals,
+            &mut source_totals,
+            None,
+            None,
+        )?;
+        let result = json!({
+            "ballots": source_totals.iter().map(|t| t.ballots).sum::<u64>(),
+            "votes": data.iter().map(|b| b.count).sum::<u64>(),
+        });
+        self.ballots = Some((data, candidates_o));
+        self.summary = None;
+        Ok(result)
+    }
+
+    fn tabulate(&mut self, params: &JSValue) -> Result<JSValue, RpcError> {
+        let (config, _) = self
+            .config
+            .as_ref()
+            .ok_or_else(|| RpcError::new(INVALID_REQUEST, "no configuration loaded"))?;
+        let (data, candidates_o) = self
+            .ballots
+            .as_mut()
+            .ok_or_else(|| RpcError::new(INVALID_REQUEST, "the sources are not parsed"))?;
+        let mut rcv_rules = config.rules.clone();
+        for (key, value) in params["rules"].as_object().into_iter().flatten() {
+            let value = match value {
+                JSValue::String(s) => s.clone(),
+                v => v.to_string(),
+            };
+            rcv_rules.set_rule(key, &value)?;
+        }
+        let rules = validate_rules(&rcv_rules)?;
+        let result = tabulate(
+            &rules,
+            candidates_o,
+            data,
+            None,
+            TabulationOptions::default(),
+        )?;
+        let summary = Summary::new(config, &result)?.to_json()?;
+        let response = json!({
+            "status": summary["status"],
+            "reason": summary["reason"],
+            "winners": result.winners,
+            "rounds": result.round_stats.len(),
+        });
+        self.summary = Some(summary);
+        Ok(response)
+    }
+}
+
+/// Serves the requests of the standard input until the shutdown request or the end of the
+/// input.
+pub fn serve_stdio() -> RcvResult<()> {
+    let mut session = Session::default();
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line.context(ServeIoSnafu {})?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = session.handle_line(&line) {
+            writeln!(stdout, "{}", response).context(ServeIoSnafu {})?;
+            stdout.flush().context(ServeIoSnafu {})?;
+        }
+        if session.shutdown {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,459 @@
+// Binary snapshots of the validated ballots, so that several runs on the same election do not
+// read and validate the sources again.
+//
+// Layout: the magic bytes, a version byte, the payload and the SHA-256 digest (in hexadecimal)
+// of everything before it. The integers are little-endian, the strings are prefixed with their
+// length in bytes.
+
+use ranked_voting::verify::VerificationReport;
+
+use crate::rcv::*;
+
+const MAGIC: &[u8; 4] = b"TRCV";
+/// The version of the layout. Files written with another version are refused.
+pub const SNAPSHOT_VERSION: u8 = 3;
+const DIGEST_LEN: usize = 64;
+
+// The tags of the ballot choices.
+const TAG_CANDIDATE: u8 = 0;
+const TAG_UWI: u8 = 1;
+const TAG_OVERVOTE: u8 = 2;
+const TAG_UNDERVOTE: u8 = 3;
+const TAG_BLANK: u8 = 4;
+
+/// A fingerprint of the rules. A snapshot is only loaded with the rules that wrote it, unless
+/// `--allow-rules-mismatch` is passed. The digest is computed on a canonical text of the rules,
+/// one `name=value` line for each rule, so that it does not depend on how the rules are
+/// printed.
+pub fn rules_fingerprint(rules: &VoteRules) -> String {
+    let opt = |x: Option<u32>| x.map_or("none".to_string(), |x| x.to_string());
+    let lines = [
+        (
+            "tiebreakMode",
+            match rules.tiebreak_mode {
+                TieBreakMode::UseCandidateOrder => "useCandidateOrder".to_string(),
+                TieBreakMode::Random(seed) => format!("random({})", seed),
+            },
+        ),
+        (
+            "overvoteRule",
+            match rules.overvote_rule {
+                OverVoteRule::ExhaustImmediately => "exhaustImmediately",
+                OverVoteRule::AlwaysSkipToNextRank => "alwaysSkipToNextRank",
+            }
+            .to_string(),
+        ),
+        (
+            "overvoteScope",
+            match rules.overvote_scope {
+                OverVoteScope::UpToNextContinuing => "upToNextContinuing",
+                OverVoteScope::WholeBallot => "wholeBallot",
+            }
+            .to_string(),
+        ),
+        (
+            "winnerElectionMode",
+            match rules.winner_election_mode {
+                WinnerElectionMode::SingelWinnerMajority => "singleWinnerMajority",
+            }
+            .to_string(),
+        ),
+        (
+            "maxSkippedRanksAllowed",
+            match rules.max_skipped_rank_allowed {
+                MaxSkippedRank::Unlimited => "unlimited".to_string(),
+                MaxSkippedRank::ExhaustOnFirstOccurence => "exhaustOnFirstOccurence".to_string(),
+                MaxSkippedRank::MaxAllowed(n) => n.to_string(),
+            },
+        ),
+        (
+            "blankCountsAsSkipped",
+            rules.blank_counts_as_skipped.to_string(),
+        ),
+        ("maxRankingsAllowed", opt(rules.max_rankings_allowed)),
+        (
+            "eliminationAlgorithm",
+            match rules.elimination_algorithm {
+                EliminationAlgorithm::Batch => "batch",
+                EliminationAlgorithm::Single => "single",
+            }
+            .to_string(),
+        ),
+        (
+            "duplicateCandidateMode",
+            match rules.duplicate_candidate_mode {
+                DuplicateCandidateMode::Exhaust => "exhaust",
+                DuplicateCandidateMode::SkipDuplicate => "skipDuplicate",
+            }
+            .to_string(),
+        ),
+        ("exhaustRankingsCap", opt(rules.exhaust_rankings_cap)),
+        (
+            "continueToCompletion",
+            rules.continue_to_completion.to_string(),
+        ),
+        (
+            "legacyRandomTiebreak",
+            rules.legacy_random_tiebreak.to_string(),
+        ),
+        (
+            "notaCandidate",
+            rules
+                .nota_candidate
+                .as_ref()
+                .map_or("none".to_string(), |name| format!("{:?}", name)),
+        ),
+        ("protectNota", rules.protect_nota.to_string()),
+        (
+            "uwiRound1Reporting",
+            match rules.uwi_round1_reporting {
+                UwiRound1Reporting::Current => "current",
+                UwiRound1Reporting::Reference => "reference",
+            }
+            .to_string(),
+        ),
+        (
+            "nameNormalization",
+            match rules.name_normalization {
+                NameNormalization::Trim => "trim",
+                NameNormalization::Fold => "fold",
+            }
+            .to_string(),
+        ),
+        (
+            "eliminationStrategy",
+            match rules.elimination_strategy {
+                None => "none",
+                Some(_) => "custom",
+            }
+            .to_string(),
+        ),
+        ("trackRankOrigin", rules.track_rank_origin.to_string()),
+    ];
+    let canonical: String = lines
+        .iter()
+        .map(|(name, value)| format!("{}={}\n", name, value))
+        .collect();
+    sha256::digest(canonical)
+}
+
+/// Merges the identical ballots, in the order of their first appearance. This keeps the order
+/// in which the candidates first appear. The merged ballots have no id, as in the snapshots.
+pub fn aggregate_ballots(ballots: &[Ballot]) -> RcvResult<Vec<Ballot>> {
+    let mut positions: HashMap<(&[BallotChoice], bool), usize> = HashMap::new();
+    let mut res: Vec<Ballot> = Vec::new();
+    for b in ballots.iter() {
+        match positions.get(&(b.candidates.as_slice(), b.challenged)) {
+            Some(idx) => {
+                let total = &mut res[*idx].count;
+                *total = total.checked_add(b.count).context(CountOverflowSnafu {
+                    what: "the identical ballots",
+                })?;
+            }
+            None => {
+                positions.insert((b.candidates.as_slice(), b.challenged), res.len());
+                let mut merged = b.clone();
+                merged.id = None;
+                res.push(merged);
+            }
+        }
+    }
+    Ok(res)
+}
+
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn put_u8(&mut self, x: u8) {
+        self.buf.push(x);
+    }
+
+    fn put_u64(&mut self, x: u64) {
+        self.buf.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn put_str(&mut self, s: &str) {
+        self.put_u64(s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn put_opt_str(&mut self, s: &Option<String>) {
+        match s {
+            None => self.put_u8(0),
+            Some(s) => {
+                self.put_u8(1);
+                self.put_str(s);
+            }
+        }
+    }
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+        if self.buf.len() - self.pos < n {
+            return Err("truncated payload");
+        }
+        let res = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(res)
+    }
+
+    fn get_u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn get_u64(&mut self) -> Result<u64, &'static str> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // Lengths and numbers of items, which must fit in the rest of the payload.
+    fn get_len(&mut self) -> Result<usize, &'static str> {
+        let n = self.get_u64()?;
+        if n > (self.buf.len() - self.pos) as u64 {
+            return Err("truncated payload");
+        }
+        Ok(n as usize)
+    }
+
+    fn get_str(&mut self) -> Result<String, &'static str> {
+        let n = self.get_len()?;
+        String::from_utf8(self.take(n)?.to_vec()).map_err(|_| "invalid string")
+    }
+
+    fn get_opt_str(&mut self) -> Result<Option<String>, &'static str> {
+        match self.get_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.get_str()?)),
+            _ => Err("invalid optional string"),
+        }
+    }
+}
+
+/// The validated ballots of an election, aggregated, with its candidates and the fingerprint of
+/// its rules: the content of a snapshot.
+pub struct ValidatedElection {
+    pub ballots: Vec<Ballot>,
+    pub candidates: Option<Vec<RcvCandidate>>,
+    pub rules_fingerprint: String,
+}
+
+impl ValidatedElection {
+    /// Aggregates the validated ballots of an election.
+    pub fn new(
+        rules: &VoteRules,
+        candidates: Option<Vec<RcvCandidate>>,
+        ballots: &[Ballot],
+    ) -> RcvResult<ValidatedElection> {
+        Ok(ValidatedElection {
+            ballots: aggregate_ballots(ballots)?,
+            candidates,
+            rules_fingerprint: rules_fingerprint(rules),
+        })
+    }
+
+    /// Writes the snapshot (--save-validated).
+    pub fn save(&self, path: &str) -> RcvResult<()> {
+        let mut enc = Encoder::default();
+        enc.buf.extend_from_slice(MAGIC);
+        enc.put_u8(SNAPSHOT_VERSION);
+        enc.put_str(&self.rules_fingerprint);
+        match self.candidates.as_ref() {
+            None => enc.put_u8(0),
+            Some(candidates) => {
+                enc.put_u8(1);
+                enc.put_u64(candidates.len() as u64);
+                for c in candidates.iter() {
+                    enc.put_str(&c.name);
+                    enc.put_opt_str(&c.code);
+                    enc.put_u8(match c.excluded {
+                        None => 0,
+                        Some(false) => 1,
+                        Some(true) => 2,
+                    });
+                    enc.put_u8(c.inferred as u8);
+                }
+            }
+        }
+        enc.put_u64(self.ballots.len() as u64);
+        for b in self.ballots.iter() {
+            enc.put_u64(b.count);
+            enc.put_u8(b.challenged as u8);
+            enc.put_u64(b.candidates.len() as u64);
+            for choice in b.candidates.iter() {
+                match choice {
+                    BallotChoice::Candidate(name) => {
+                        enc.put_u8(TAG_CANDIDATE);
+                        enc.put_str(name);
+                    }
+                    BallotChoice::UndeclaredWriteIn => enc.put_u8(TAG_UWI),
+                    BallotChoice::Overvote => enc.put_u8(TAG_OVERVOTE),
+                    BallotChoice::Undervote => enc.put_u8(TAG_UNDERVOTE),
+                    BallotChoice::Blank => enc.put_u8(TAG_BLANK),
+                }
+            }
+        }
+        let digest = sha256::digest(&enc.buf);
+        enc.buf.extend_from_slice(digest.as_bytes());
+        fs::write(path, &enc.buf).context(SnapshotWriteSnafu { path })?;
+        info!(
+            "save: {} distinct ballots written to {}",
+            self.ballots.len(),
+            path
+        );
+        Ok(())
+    }
+
+    /// Reads a snapshot (--load-validated). The snapshot is refused if it is corrupted, or if it
+    /// was written by another version of the layout. See `check_rules` for its rules.
+    pub fn load(path: &str) -> RcvResult<ValidatedElection> {
+        let bytes = fs::read(path).context(SnapshotOpenSnafu { path })?;
+        let invalid = |reason: String| RcvError::InvalidSnapshot {
+            path: path.to_string(),
+            reason,
+        };
+        if bytes.len() < MAGIC.len() + 1 + DIGEST_LEN || !bytes.starts_with(MAGIC) {
+            return Err(invalid("not a timrcv snapshot".to_string()));
+        }
+        let version = bytes[MAGIC.len()];
+        if version != SNAPSHOT_VERSION {
+            return Err(invalid(format!(
+                "version {} is not supported, this program reads version {}",
+                version, SNAPSHOT_VERSION
+            )));
+        }
+        let (content, digest) = bytes.split_at(bytes.len() - DIGEST_LEN);
+        if sha256::digest(content).as_bytes() != digest {
+            return Err(invalid(
+                "the checksum does not match, the file is corrupted".to_string(),
+            ));
+        }
+        let mut dec = Decoder {
+            buf: content,
+            pos: MAGIC.len() + 1,
+        };
+        let (rules_fingerprint, candidates, ballots) =
+            decode_payload(&mut dec).map_err(|reason| invalid(reason.to_string()))?;
+        info!(
+            "load: {} distinct ballots read from {}",
+            ballots.len(),
+            path
+        );
+        Ok(ValidatedElection {
+            ballots,
+            candidates,
+            rules_fingerprint,
+        })
+    }
+
+    /// Tabulates the ballots with `rules` and compares each round with official round data.
+    ///
+    /// The official rounds may be partial, for example only the first round. The report
+    /// contains the first round whose tallies differ from the tabulation, with the tallies of
+    /// the candidates that differ. See `ranked_voting::verify::compare_rounds` for the details.
+    pub fn verify_round_sequence(
+        &self,
+        rules: &VoteRules,
+        official_rounds: &[RoundStats],
+    ) -> RcvResult<VerificationReport> {
+        let mut ballots = self.ballots.clone();
+        let builder = election_builder(rules, &self.candidates, &mut ballots)?;
+        let result = ranked_voting::run_election(&builder).context(RvVotingSnafu {})?;
+        Ok(ranked_voting::verify::compare_rounds(
+            &result.round_stats,
+            official_rounds,
+        ))
+    }
+
+    /// Fails if the snapshot of `path` was written with other rules, unless
+    /// `allow_rules_mismatch` is set (--allow-rules-mismatch).
+    pub fn check_rules(
+        &self,
+        path: &str,
+        rules: &VoteRules,
+        allow_rules_mismatch: bool,
+    ) -> RcvResult<()> {
+        if self.rules_fingerprint != rules_fingerprint(rules) {
+            ensure!(allow_rules_mismatch, SnapshotRulesMismatchSnafu { path });
+            codes::warning(
+                WarningCode::SnapshotRulesMismatch,
+                format!(
+                    "load: the snapshot {} was written with other rules (--allow-rules-mismatch)",
+                    path
+                ),
+            );
+        }
+        Ok(())
+    }
+}
+
+// The fingerprint of the rules, the candidates and the ballots.
+type Payload = (String, Option<Vec<RcvCandidate>>, Vec<Ballot>);
+
+fn decode_payload(dec: &mut Decoder) -> Result<Payload, &'static str> {
+    let fingerprint = dec.get_str()?;
+    let candidates_o = match dec.get_u8()? {
+        0 => None,
+        1 => {
+            let n = dec.get_len()?;
+            let mut candidates = Vec::with_capacity(n);
+            for _ in 0..n {
+                candidates.push(RcvCandidate {
+                    name: dec.get_str()?,
+                    code: dec.get_opt_str()?,
+                    excluded: match dec.get_u8()? {
+                        0 => None,
+                        1 => Some(false),
+                        2 => Some(true),
+                        _ => return Err("invalid candidate"),
+                    },
+                    inferred: match dec.get_u8()? {
+                        0 => false,
+                        1 => true,
+                        _ => return Err("invalid candidate"),
+                    },
+                });
+            }
+            Some(candidates)
+        }
+        _ => return Err("invalid candidate list"),
+    };
+    let n = dec.get_len()?;
+    let mut ballots = Vec::with_capacity(n);
+    for _ in 0..n {
+        let count = dec.get_u64()?;
+        let challenged = match dec.get_u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err("invalid ballot"),
+        };
+        let num_choices = dec.get_len()?;
+        let mut candidates = Vec::with_capacity(num_choices);
+        for _ in 0..num_choices {
+            candidates.push(match dec.get_u8()? {
+                TAG_CANDIDATE => BallotChoice::Candidate(dec.get_str()?),
+                TAG_UWI => BallotChoice::UndeclaredWriteIn,
+                TAG_OVERVOTE => BallotChoice::Overvote,
+                TAG_UNDERVOTE => BallotChoice::Undervote,
+                TAG_BLANK => BallotChoice::Blank,
+                _ => return Err("invalid ballot choice"),
+            });
+        }
+        let mut ballot = Ballot::new(candidates, count);
+        ballot.challenged = challenged;
+        ballots.push(ballot);
+    }
+    if dec.pos != dec.buf.len() {
+        return Err("unexpected data after the ballots");
+    }
+    Ok((fingerprint, candidates_o, ballots))
+}
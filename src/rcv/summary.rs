@@ -0,0 +1,716 @@
+// The JSON summary of an election, as typed structures that borrow from the result of the
+// tabulation.
+//
+// The summary is written to its file without building a JSValue tree first. The JSValue of the
+// summary (serde_json::to_value) is only built when it is compared with a reference. The fields
+// are declared in the order of their keys, so that both give the same output: the maps of
+// serde_json sort their keys.
+//
+// All the strings of the summary are in the Unicode normalization form C (NFC), in the file and
+// in the JSValue. The names of the candidates are already in NFC (see CandidateName), so that the
+// order of the keys does not change.
+
+use std::io::Write;
+
+use serde::ser::SerializeMap;
+use serde::Serializer;
+
+use crate::rcv::*;
+
+// A count, written as a string as in the summaries of the reference implementation.
+#[derive(Debug, Clone, Copy)]
+pub struct CountString(pub u64);
+
+impl Serialize for CountString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+// Counts by name, sorted by name as in the maps of serde_json.
+#[derive(Debug, Clone, Default)]
+pub struct CountMap<'a>(Vec<(&'a str, CountString)>);
+
+impl<'a> CountMap<'a> {
+    fn new(counts: impl Iterator<Item = (&'a str, u64)>) -> CountMap<'a> {
+        let mut counts: Vec<(&str, CountString)> = counts
+            .map(|(name, count)| (name, CountString(count)))
+            .collect();
+        // The last count of a name is kept, as when inserting in a map.
+        counts.reverse();
+        counts.sort_by_key(|(name, _)| *name);
+        counts.dedup_by_key(|(name, _)| *name);
+        CountMap(counts)
+    }
+}
+
+impl Serialize for CountMap<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(name, count)| (name, count)))
+    }
+}
+
+/// The inactive ballots by reason, with the keys of the RCTab summaries. The exhausted choices
+/// are the ballots that "did not rank any continuing candidates".
+#[derive(Debug, Clone, Serialize)]
+pub struct InactiveSummary {
+    #[serde(rename = "exhaustedChoices")]
+    pub exhausted_choices: CountString,
+    pub overvotes: CountString,
+    #[serde(rename = "repeatedRankings")]
+    pub repeated_rankings: CountString,
+    #[serde(rename = "skippedRankings")]
+    pub skipped_rankings: CountString,
+}
+
+impl InactiveSummary {
+    pub fn new(inactive: &InactiveBallots) -> InactiveSummary {
+        InactiveSummary {
+            exhausted_choices: CountString(inactive.exhausted_choices),
+            overvotes: CountString(inactive.overvotes),
+            repeated_rankings: CountString(inactive.repeated_rankings),
+            skipped_rankings: CountString(inactive.skipped_rankings),
+        }
+    }
+}
+
+/// An elected or an eliminated candidate of a round. The transfers include the exhausted
+/// votes.
+#[derive(Debug, Clone, Serialize)]
+pub struct TallyResultSummary<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elected: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eliminated: Option<&'a str>,
+    pub transfers: CountMap<'a>,
+}
+
+/// A note of a round, as an object whose type is in the key "type". The keys are written in
+/// sorted order.
+#[derive(Debug, Clone, Copy)]
+pub struct NoteSummary<'a>(pub &'a RoundNote);
+
+impl Serialize for NoteSummary<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        match self.0 {
+            RoundNote::Tiebreak {
+                candidates,
+                eliminated,
+            } => {
+                map.serialize_entry("candidates", candidates)?;
+                map.serialize_entry("eliminated", eliminated)?;
+                map.serialize_entry("type", "tiebreak")?;
+            }
+            RoundNote::BatchElimination { count } => {
+                map.serialize_entry("count", count)?;
+                map.serialize_entry("type", "batchElimination")?;
+            }
+            RoundNote::Forced { candidate } => {
+                map.serialize_entry("candidate", candidate)?;
+                map.serialize_entry("type", "forced")?;
+            }
+            RoundNote::UwiElimination => map.serialize_entry("type", "uwiElimination")?,
+            RoundNote::Informational => map.serialize_entry("type", "informational")?,
+            RoundNote::ThresholdPolicy {
+                reporting,
+                threshold,
+            } => {
+                let reporting = match reporting {
+                    UwiRound1Reporting::Current => "current",
+                    UwiRound1Reporting::Reference => "reference",
+                };
+                map.serialize_entry("reporting", reporting)?;
+                map.serialize_entry("threshold", &CountString(*threshold))?;
+                map.serialize_entry("type", "thresholdPolicy")?;
+            }
+        }
+        map.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundSummary<'a> {
+    #[serde(rename = "inactiveBallots", skip_serializing_if = "Option::is_none")]
+    pub inactive_ballots: Option<InactiveSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub informational: Option<bool>,
+    /// The notes of the round, only in the v2 schema and if the round has some.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<Vec<NoteSummary<'a>>>,
+    pub round: u32,
+    pub tally: CountMap<'a>,
+    /// The tally of the candidates that have a code, by code. Only in the v2 schema.
+    #[serde(rename = "tallyByCode", skip_serializing_if = "Option::is_none")]
+    pub tally_by_code: Option<BTreeMap<String, CountString>>,
+    #[serde(rename = "tallyResults")]
+    pub tally_results: Vec<TallyResultSummary<'a>>,
+}
+
+impl<'a> RoundSummary<'a> {
+    /// The eliminated candidates are not output for the last round.
+    pub fn new(round_stat: &'a RoundStats, is_last: bool, inactive: bool) -> RoundSummary<'a> {
+        let tally = CountMap::new(
+            round_stat
+                .tally
+                .iter()
+                .map(|(name, count)| (name.as_str(), *count)),
+        );
+
+        let mut tally_results: Vec<TallyResultSummary> = Vec::new();
+        if !is_last {
+            for elim_stats in round_stat.tally_result_eliminated.iter() {
+                // No UWI to account for in transfers for now
+                // TODO: check that this is the case
+                let exhausted =
+                    Some(("exhausted", elim_stats.exhausted)).filter(|(_, count)| *count > 0);
+                let transfers = CountMap::new(
+                    elim_stats
+                        .transfers
+                        .iter()
+                        .map(|(name, count)| (name.as_str(), *count))
+                        .chain(exhausted),
+                );
+                tally_results.push(TallyResultSummary {
+                    elected: None,
+                    eliminated: Some(&elim_stats.name),
+                    transfers,
+                });
+            }
+        }
+        for winner_name in round_stat.tally_results_elected.iter() {
+            tally_results.push(TallyResultSummary {
+                elected: Some(winner_name),
+                eliminated: None,
+                transfers: CountMap::default(),
+            });
+        }
+
+        RoundSummary {
+            inactive_ballots: Some(InactiveSummary::new(&round_stat.inactive_ballots))
+                .filter(|_| inactive),
+            informational: Some(true).filter(|_| round_stat.informational),
+            notes: None,
+            round: round_stat.round.0,
+            tally,
+            tally_by_code: None,
+            tally_results,
+        }
+    }
+}
+
+// The tally by code of the candidates that have a code (the codes are unique, see
+// check_candidate_keys), if some candidates have a code.
+fn set_tally_by_code<'s, 'a: 's>(
+    rounds: impl Iterator<Item = &'s mut RoundSummary<'a>>,
+    rv: &VotingResult,
+    config: &RcvConfig,
+) {
+    let codes: BTreeMap<String, &str> = config
+        .candidates
+        .iter()
+        .filter_map(|c| {
+            let code = c.code.as_deref().map(|code| code.trim())?;
+            let name = CandidateName::new(&c.name, NameNormalization::default());
+            Some((name.as_str().to_string(), code)).filter(|_| !code.is_empty())
+        })
+        .collect();
+    if codes.is_empty() {
+        return;
+    }
+    for round in rounds {
+        let round_stat = rv.round_stats.iter().find(|rs| rs.round.0 == round.round);
+        round.tally_by_code = round_stat.map(|rs| {
+            rs.tally
+                .iter()
+                .filter_map(|(name, count)| {
+                    codes
+                        .get(name.as_str())
+                        .map(|code| (code.to_string(), CountString(*count)))
+                })
+                .collect()
+        });
+    }
+}
+
+/// The rounds of the summary. The informational rounds and the notes are only part of the v2
+/// schema.
+pub fn round_summaries(
+    rv: &VotingResult,
+    schema: SummarySchema,
+    inactive: bool,
+) -> Vec<RoundSummary<'_>> {
+    let round_stats: Vec<&RoundStats> = rv
+        .round_stats
+        .iter()
+        .filter(|rs| schema == SummarySchema::V2 || !rs.informational)
+        .collect();
+    let num_rounds = round_stats.len();
+    round_stats
+        .into_iter()
+        .enumerate()
+        .map(|(idx, round_stat)| {
+            let mut summary = RoundSummary::new(round_stat, idx == num_rounds - 1, inactive);
+            if schema == SummarySchema::V2 && !round_stat.notes.is_empty() {
+                summary.notes = Some(round_stat.notes.iter().map(NoteSummary).collect());
+            }
+            summary
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RankPositionSummary {
+    #[serde(rename = "lastMark")]
+    pub last_mark: CountString,
+    pub position: u32,
+    #[serde(rename = "skipThenReturn")]
+    pub skip_then_return: CountString,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RankStatsSummary {
+    pub positions: Vec<RankPositionSummary>,
+    #[serde(rename = "skipThenReturnPercent")]
+    pub skip_then_return_percent: f64,
+    #[serde(rename = "skipThenReturnVotes")]
+    pub skip_then_return_votes: CountString,
+    #[serde(rename = "totalVotes")]
+    pub total_votes: CountString,
+}
+
+impl RankStatsSummary {
+    pub fn new(rs: &RankStatistics, rounding: PercentRounding) -> RankStatsSummary {
+        RankStatsSummary {
+            positions: rs
+                .positions
+                .iter()
+                .map(|p| RankPositionSummary {
+                    last_mark: CountString(p.last_mark),
+                    position: p.position,
+                    skip_then_return: CountString(p.skip_then_return),
+                })
+                .collect(),
+            skip_then_return_percent: rounding
+                .format(rs.skip_then_return_votes, rs.total_votes, 2)
+                .parse()
+                .unwrap_or(0.0),
+            skip_then_return_votes: CountString(rs.skip_then_return_votes),
+            total_votes: CountString(rs.total_votes),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompetitivenessSummary {
+    #[serde(rename = "comeFromBehind")]
+    pub come_from_behind: Option<bool>,
+    #[serde(rename = "effectiveCandidates")]
+    pub effective_candidates: Option<f64>,
+    #[serde(rename = "exhaustedPercent")]
+    pub exhausted_percent: Option<f64>,
+    #[serde(rename = "finalRoundMarginPercent")]
+    pub final_round_margin_percent: Option<f64>,
+    pub rounds: usize,
+}
+
+impl CompetitivenessSummary {
+    pub fn new(
+        report: &ranked_voting::metrics::CompetitivenessReport,
+        rounding: PercentRounding,
+    ) -> CompetitivenessSummary {
+        let percent = |count: Option<u64>, total: u64| -> Option<f64> {
+            count
+                .filter(|_| total > 0)
+                .and_then(|c| rounding.format(c, total, 2).parse().ok())
+        };
+        CompetitivenessSummary {
+            come_from_behind: report.come_from_behind,
+            effective_candidates: report
+                .effective_candidates
+                .and_then(|e| format!("{:.2}", e).parse().ok()),
+            exhausted_percent: percent(Some(report.exhausted_ballots), report.first_round_votes),
+            final_round_margin_percent: percent(
+                report.final_round_margin,
+                report.final_round_votes,
+            ),
+            rounds: report.rounds,
+        }
+    }
+}
+
+/// The ballots affected by one rule (see `rules_impact`).
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleImpactSummary {
+    pub ballots: CountString,
+    pub effect: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ranks: Option<CountString>,
+    pub rule: &'static str,
+    pub setting: String,
+}
+
+/// The impact of the rules on the ballots, in the v2 summary.
+#[derive(Debug, Clone, Serialize)]
+pub struct RulesImpactSummary {
+    #[serde(rename = "exceedsMargin")]
+    pub exceeds_margin: bool,
+    #[serde(rename = "finalRoundMargin")]
+    pub final_round_margin: Option<CountString>,
+    pub rules: Vec<RuleImpactSummary>,
+}
+
+impl RulesImpactSummary {
+    pub fn new(impact: &rules_impact::RulesImpact) -> RulesImpactSummary {
+        RulesImpactSummary {
+            exceeds_margin: impact.exceeds_margin(),
+            final_round_margin: impact.final_round_margin.map(CountString),
+            rules: impact
+                .rules
+                .iter()
+                .map(|r| RuleImpactSummary {
+                    ballots: CountString(r.ballots),
+                    effect: r.effect,
+                    ranks: r.ranks.map(CountString),
+                    rule: r.rule,
+                    setting: r.setting.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateSummary<'a> {
+    pub code: Option<&'a str>,
+    pub excluded: bool,
+    pub inferred: bool,
+    pub name: &'a str,
+}
+
+/// An excluded candidate, with the votes initially cast for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExcludedSummary<'a> {
+    pub name: &'a str,
+    pub votes: CountString,
+}
+
+/// The votes of a candidate in a round, by the rank they come from (trackRankOrigin).
+#[derive(Debug, Clone, Serialize)]
+pub struct RankOriginSummary<'a> {
+    pub candidate: &'a str,
+    pub round: u32,
+    #[serde(rename = "votesByRank")]
+    pub votes_by_rank: Vec<RankVotesSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RankVotesSummary {
+    pub rank: u32,
+    pub votes: CountString,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EliminationSummary<'a> {
+    pub elected: bool,
+    pub name: &'a str,
+    pub round: u32,
+    #[serde(rename = "sharePercent")]
+    pub share_percent: String,
+    pub votes: CountString,
+}
+
+/// The config section of the summary. With `--with-challenged both`, it only has the sources.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSummary {
+    pub output: Option<OutputConfig>,
+    /// The number of ballots of each file source (--emit-provenance).
+    pub sources: Option<JSValue>,
+}
+
+// The keys are written in sorted order, with the sources between the office and the threshold.
+impl Serialize for ConfigSummary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(c) = self.output.as_ref() {
+            map.serialize_entry("contest", &c.contest)?;
+            map.serialize_entry("date", &c.date)?;
+            map.serialize_entry("jurisdiction", &c.jurisdiction)?;
+            map.serialize_entry("office", &c.office)?;
+        }
+        if let Some(sources) = self.sources.as_ref() {
+            map.serialize_entry("sources", sources)?;
+        }
+        if let Some(c) = self.output.as_ref() {
+            map.serialize_entry("threshold", &c.threshold)?;
+        }
+        map.end()
+    }
+}
+
+/// The summary of an election. With `--with-challenged both`, the summary has the summaries of
+/// the two tabulations and their comparison instead of the results.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Summary<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<JSValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidates: Option<Vec<CandidateSummary<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub categories: Option<JSValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<JSValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub competitiveness: Option<CompetitivenessSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<ConfigSummary>,
+    #[serde(rename = "eliminationOrder", skip_serializing_if = "Option::is_none")]
+    pub elimination_order: Option<Vec<EliminationSummary<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excluded: Option<Box<Summary<'a>>>,
+    // With `--with-challenged both`, `excluded` is the summary without the challenged ballots:
+    // the excluded candidates are listed inside it and inside `included`, not at the top level.
+    #[serde(rename = "excludedCandidates", skip_serializing_if = "Option::is_none")]
+    pub excluded_candidates: Option<Vec<ExcludedSummary<'a>>>,
+    #[serde(rename = "failedSources", skip_serializing_if = "Option::is_none")]
+    pub failed_sources: Option<JSValue>,
+    #[serde(rename = "firstChoicePercent", skip_serializing_if = "Option::is_none")]
+    pub first_choice_percent: Option<BTreeMap<String, String>>,
+    #[serde(rename = "firstMajorityRound", skip_serializing_if = "Option::is_none")]
+    pub first_majority_round: Option<BTreeMap<&'a str, Option<u32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub included: Option<Box<Summary<'a>>>,
+    #[serde(rename = "rankOrigin", skip_serializing_if = "Option::is_none")]
+    pub rank_origin: Option<Vec<RankOriginSummary<'a>>>,
+    #[serde(rename = "rankStatistics", skip_serializing_if = "Option::is_none")]
+    pub rank_statistics: Option<RankStatsSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<Vec<RoundSummary<'a>>>,
+    #[serde(rename = "rulesImpact", skip_serializing_if = "Option::is_none")]
+    pub rules_impact: Option<RulesImpactSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<JSValue>,
+    #[serde(rename = "winnerRankedOn", skip_serializing_if = "Option::is_none")]
+    pub winner_ranked_on: Option<BTreeMap<&'a str, CountString>>,
+}
+
+impl<'a> Summary<'a> {
+    pub fn new(config: &RcvConfig, rv: &'a VotingResult) -> RcvResult<Summary<'a>> {
+        let schema = config.output_settings.summary_schema()?;
+        let inactive = config.output_settings.inactive_ballots_block()?;
+        let (status, reason) = summary_status(rv);
+        let mut summary = Summary {
+            config: Some(ConfigSummary {
+                output: Some(output_config(config, Some(rv.threshold.to_string()))),
+                sources: None,
+            }),
+            results: Some(round_summaries(rv, schema, inactive)),
+            status: Some(status.label()),
+            reason: Some(reason),
+            excluded_candidates: Some(
+                rv.excluded
+                    .iter()
+                    .map(|(name, votes)| ExcludedSummary {
+                        name,
+                        votes: CountString(*votes),
+                    })
+                    .collect(),
+            )
+            .filter(|e: &Vec<ExcludedSummary>| !e.is_empty()),
+            ..Default::default()
+        };
+        // The statistics on the rank positions and the candidates are specific to timrcv.
+        if schema == SummarySchema::V2 {
+            set_tally_by_code(summary.results.iter_mut().flatten(), rv, config);
+            let rounding = config.output_settings.percent_rounding()?;
+            summary.rank_statistics = Some(RankStatsSummary::new(&rv.rank_stats, rounding));
+            summary.competitiveness = Some(CompetitivenessSummary::new(
+                &ranked_voting::metrics::competitiveness(rv),
+                rounding,
+            ));
+            summary.candidates = Some(
+                rv.candidates
+                    .iter()
+                    .map(|c| CandidateSummary {
+                        code: c.code.as_deref(),
+                        excluded: c.excluded,
+                        inferred: c.inferred,
+                        name: &c.name,
+                    })
+                    .collect(),
+            );
+            summary.first_choice_percent = Some(
+                rv.first_choice_percent_rounded(rounding)
+                    .into_iter()
+                    .collect(),
+            );
+            summary.elimination_order = Some(
+                rv.elimination_order
+                    .iter()
+                    .map(|e| {
+                        // The share is rounded like the other percentages of the summary.
+                        let total: u64 = rv
+                            .round_stats
+                            .iter()
+                            .find(|rs| rs.round == e.round)
+                            .map(|rs| rs.tally.iter().map(|(_, count)| count).sum())
+                            .unwrap_or(0);
+                        EliminationSummary {
+                            elected: e.elected,
+                            name: &e.name,
+                            round: e.round.0,
+                            share_percent: rounding.format(e.votes, total, 1),
+                            votes: CountString(e.votes),
+                        }
+                    })
+                    .collect(),
+            );
+            summary.first_majority_round = Some(
+                rv.first_majority_round
+                    .iter()
+                    .map(|(name, round)| (name.as_str(), round.map(|r| r.0)))
+                    .collect(),
+            );
+            summary.winner_ranked_on = Some(
+                rv.winner_ranked_on
+                    .iter()
+                    .map(|(name, count)| (name.as_str(), CountString(*count)))
+                    .collect(),
+            );
+            summary.rank_origin = rv.rank_origin.as_ref().map(|origins| {
+                origins
+                    .iter()
+                    .map(|o| RankOriginSummary {
+                        candidate: &o.candidate,
+                        round: o.round.0,
+                        votes_by_rank: o
+                            .votes_by_rank
+                            .iter()
+                            .map(|(rank, votes)| RankVotesSummary {
+                                rank: *rank,
+                                votes: CountString(*votes),
+                            })
+                            .collect(),
+                    })
+                    .collect()
+            });
+        }
+        Ok(summary)
+    }
+
+    /// The summary written when the tabulation itself failed.
+    pub fn error(config: &RcvConfig, reason: String) -> Summary<'a> {
+        Summary {
+            config: Some(ConfigSummary {
+                output: Some(output_config(config, None)),
+                sources: None,
+            }),
+            results: Some(vec![]),
+            status: Some(SummaryStatus::Error.label()),
+            reason: Some(reason),
+            ..Default::default()
+        }
+    }
+
+    /// The summary of an interrupted tabulation, with the rounds completed so far. The
+    /// eliminations of the last round are written, as the tabulation did not end there.
+    pub fn interrupted(config: &RcvConfig, rounds: &'a [RoundStats]) -> RcvResult<Summary<'a>> {
+        let inactive = config.output_settings.inactive_ballots_block()?;
+        Ok(Summary {
+            config: Some(ConfigSummary {
+                output: Some(output_config(config, None)),
+                sources: None,
+            }),
+            results: Some(
+                rounds
+                    .iter()
+                    .map(|rs| RoundSummary::new(rs, false, inactive))
+                    .collect(),
+            ),
+            status: Some(SummaryStatus::Interrupted.label()),
+            reason: Some(format!(
+                "the tabulation was interrupted after {} rounds",
+                rounds.len()
+            )),
+            ..Default::default()
+        })
+    }
+
+    /// The config section, created if needed.
+    pub fn config_mut(&mut self) -> &mut ConfigSummary {
+        self.config.get_or_insert_with(ConfigSummary::default)
+    }
+
+    pub fn to_json(&self) -> RcvResult<JSValue> {
+        let js = serde_json::to_value(self).context(ParsingJsonSnafu {})?;
+        Ok(nfc_json(js))
+    }
+
+    /// Writes the summary in the pretty JSON format, the same as serde_json::to_string_pretty
+    /// of [Summary::to_json].
+    pub fn write_pretty(&self, writer: impl Write) -> Result<(), serde_json::Error> {
+        let mut writer = NfcWriter {
+            inner: writer,
+            pending: Vec::new(),
+        };
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.finish().map_err(serde_json::Error::io)
+    }
+}
+
+/// The JSON value with all its strings (keys and values) in NFC.
+pub fn nfc_json(js: JSValue) -> JSValue {
+    match js {
+        JSValue::String(s) => JSValue::String(nfc(&s).into_owned()),
+        JSValue::Array(values) => JSValue::Array(values.into_iter().map(nfc_json).collect()),
+        JSValue::Object(obj) => JSValue::Object(
+            obj.into_iter()
+                .map(|(k, v)| (nfc(&k).into_owned(), nfc_json(v)))
+                .collect(),
+        ),
+        js => js,
+    }
+}
+
+// Writes the JSON text in NFC. The text is normalized up to the last quote: a quote does not
+// combine with the characters around it, so that the strings are normalized as a whole, and the
+// text that is held back is short.
+struct NfcWriter<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> NfcWriter<W> {
+    fn write_normalized(&mut self, end: usize) -> std::io::Result<()> {
+        let rest = self.pending.split_off(end);
+        match std::str::from_utf8(&self.pending) {
+            Ok(s) => self.inner.write_all(nfc(s).as_bytes())?,
+            Err(_) => self.inner.write_all(&self.pending)?,
+        }
+        self.pending = rest;
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        self.write_normalized(self.pending.len())?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for NfcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if let Some(pos) = self.pending.iter().rposition(|b| *b == b'"') {
+            self.write_normalized(pos + 1)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
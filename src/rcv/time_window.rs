@@ -0,0 +1,302 @@
+// The filtering of the ballots by the time of their submission (timestampColumn, acceptFrom
+// and acceptUntil).
+//
+// The window is half-open: a ballot submitted exactly at acceptUntil is rejected, and a ballot
+// submitted exactly at acceptFrom is accepted. The times are kept as seconds since
+// 1970-01-01T00:00:00Z. A time without an offset is read as UTC. The ballots whose timestamp
+// cannot be read are kept, unless there are too many of them: the column is then most likely
+// the wrong one.
+
+use crate::rcv::*;
+
+// The largest share of the rows whose timestamp cannot be read.
+const MAX_UNPARSEABLE_SHARE: f64 = 0.05;
+
+// The number of days from 1970-01-01 to a civil date (H. Hinnant's algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// The civil date of a number of days since 1970-01-01 (H. Hinnant's algorithm).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Formats a number of seconds since 1970-01-01T00:00:00Z in the RFC 3339 format
+/// (2024-11-05T21:03:00Z).
+pub fn format_timestamp(secs: i64) -> String {
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+// A number of exactly `len` digits.
+fn digits(s: &str, len: usize) -> Option<i64> {
+    if s.len() == len && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+// The offset of a time zone, in seconds: Z, +hh:mm, +hhmm or +hh.
+fn parse_offset(s: &str) -> Option<i64> {
+    if s.is_empty() || s.eq_ignore_ascii_case("z") {
+        return Some(0);
+    }
+    let sign = match s.as_bytes()[0] {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let rest = s[1..].replace(':', "");
+    let (hours, minutes) = match rest.len() {
+        2 => (digits(&rest, 2)?, 0),
+        4 => (digits(&rest[..2], 2)?, digits(&rest[2..], 2)?),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parses a date or a time in the ISO 8601 format: `2024-11-05`, `2024-11-05T21:03`,
+/// `2024-11-05 21:03:00.123` or `2024-11-05T21:03:00-05:00`. The fractions of seconds are
+/// ignored, and a time without an offset is in UTC. Returns the number of seconds since
+/// 1970-01-01T00:00:00Z.
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (date, time) = match s.find(['T', 't', ' ']) {
+        Some(pos) => (&s[..pos], s[pos + 1..].trim_start()),
+        None => (s, ""),
+    };
+    let mut parts = date.split('-');
+    let year = digits(parts.next()?, 4)?;
+    let month = digits(parts.next()?, 2)?;
+    let day = digits(parts.next()?, 2)?;
+    if parts.next().is_some() || !(1..=12).contains(&month) || day < 1 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    // Rejects the days beyond the end of the month, such as 2023-02-29.
+    if civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+    if time.is_empty() {
+        return Some(days * 86400);
+    }
+    let offset_pos = time.find(['Z', 'z', '+', '-']).unwrap_or(time.len());
+    let offset = parse_offset(time[offset_pos..].trim())?;
+    let clock = time[..offset_pos].trim_end();
+    let clock = clock.split_once('.').map(|(c, _)| c).unwrap_or(clock);
+    let mut fields = clock.split(':');
+    let hours = digits(fields.next()?, 2)?;
+    let minutes = digits(fields.next()?, 2)?;
+    let seconds = match fields.next() {
+        Some(f) => digits(f, 2)?,
+        None => 0,
+    };
+    if fields.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    Some(days * 86400 + hours * 3600 + minutes * 60 + seconds - offset)
+}
+
+/// The time of a spreadsheet cell: a date cell, a number of days since 1899-12-30 as written
+/// by Excel, or a string in the ISO 8601 format.
+pub fn excel_timestamp(cell: &calamine::DataType) -> Option<i64> {
+    match cell {
+        calamine::DataType::DateTime(f) | calamine::DataType::Float(f) => {
+            Some(((f - 25569.0) * 86400.0).round() as i64)
+        }
+        calamine::DataType::Int(i) => Some((i - 25569) * 86400),
+        calamine::DataType::String(s) => parse_timestamp(s),
+        _ => None,
+    }
+}
+
+fn parse_bound(option: &str, value: &Option<String>) -> RcvResult<Option<i64>> {
+    match value.as_ref() {
+        None => Ok(None),
+        Some(s) => match parse_timestamp(s) {
+            Some(ts) => Ok(Some(ts)),
+            None => InvalidTimeWindowSnafu {
+                reason: format!(
+                    "{} {:?} is not a date or a time in the RFC 3339 format",
+                    option, s
+                ),
+            }
+            .fail(),
+        },
+    }
+}
+
+/// Drops the ballots that were not submitted within [acceptFrom, acceptUntil), and counts them
+/// for the warning of the file.
+pub struct TimeWindow {
+    column: usize,
+    from: Option<i64>,
+    until: Option<i64>,
+    num_rows: u64,
+    num_rejected: u64,
+    rejected_range: Option<(i64, i64)>,
+    num_unparseable: u64,
+    first_unparseable: Option<(usize, String)>,
+}
+
+impl TimeWindow {
+    /// The window of a file source, or None if it has no timestamp column. The column is the
+    /// index given by [FileSource::timestamp_column_index].
+    pub fn new(cfs: &FileSource, column_o: Option<usize>) -> RcvResult<Option<TimeWindow>> {
+        let from = parse_bound("acceptFrom", &cfs.accept_from)?;
+        let until = parse_bound("acceptUntil", &cfs.accept_until)?;
+        let column = match column_o {
+            Some(column) => column,
+            None => {
+                ensure!(
+                    from.is_none() && until.is_none(),
+                    InvalidTimeWindowSnafu {
+                        reason: "acceptFrom and acceptUntil require timestampColumn".to_string(),
+                    }
+                );
+                return Ok(None);
+            }
+        };
+        ensure!(
+            from.is_some() || until.is_some(),
+            InvalidTimeWindowSnafu {
+                reason: "timestampColumn requires acceptFrom or acceptUntil".to_string(),
+            }
+        );
+        if let (Some(f), Some(u)) = (from, until) {
+            ensure!(
+                f < u,
+                InvalidTimeWindowSnafu {
+                    reason: format!(
+                        "acceptFrom ({}) must be before acceptUntil ({})",
+                        format_timestamp(f),
+                        format_timestamp(u)
+                    ),
+                }
+            );
+        }
+        Ok(Some(TimeWindow {
+            column,
+            from,
+            until,
+            num_rows: 0,
+            num_rejected: 0,
+            rejected_range: None,
+            num_unparseable: 0,
+            first_unparseable: None,
+        }))
+    }
+
+    /// The 0-based index of the timestamp column.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// True if the ballot of a row is kept: its timestamp is within the window, or cannot be
+    /// read. `cell` is the content of the timestamp cell, for the error message.
+    pub fn accept(&mut self, lineno: usize, cell: &str, ts: Option<i64>) -> bool {
+        self.num_rows += 1;
+        let ts = match ts {
+            Some(ts) => ts,
+            None => {
+                self.num_unparseable += 1;
+                if self.first_unparseable.is_none() {
+                    self.first_unparseable = Some((lineno, cell.to_string()));
+                }
+                return true;
+            }
+        };
+        let inside = self.from.map(|f| ts >= f).unwrap_or(true)
+            && self.until.map(|u| ts < u).unwrap_or(true);
+        if !inside {
+            self.num_rejected += 1;
+            self.rejected_range = Some(match self.rejected_range {
+                Some((earliest, latest)) => (earliest.min(ts), latest.max(ts)),
+                None => (ts, ts),
+            });
+        }
+        inside
+    }
+
+    fn describe(&self) -> String {
+        match (self.from, self.until) {
+            (Some(f), Some(u)) => format!(
+                "the window [{}, {})",
+                format_timestamp(f),
+                format_timestamp(u)
+            ),
+            (Some(f), None) => format!("the window from {}", format_timestamp(f)),
+            (_, Some(u)) => format!("the window until {} (excluded)", format_timestamp(u)),
+            (None, None) => "the window".to_string(),
+        }
+    }
+
+    /// Reports the rejected ballots of the file with a warning, or fails if too many
+    /// timestamps cannot be read.
+    pub fn finish(&self, path: &str) -> RcvResult<()> {
+        if let Some((lineno, content)) = self.first_unparseable.as_ref() {
+            ensure!(
+                (self.num_unparseable as f64) <= MAX_UNPARSEABLE_SHARE * (self.num_rows as f64),
+                UnparseableTimestampsSnafu {
+                    path,
+                    num_unparseable: self.num_unparseable,
+                    num_rows: self.num_rows,
+                    lineno: *lineno,
+                    content: content.clone(),
+                }
+            );
+        }
+        let mut messages: Vec<String> = Vec::new();
+        if let Some((earliest, latest)) = self.rejected_range {
+            messages.push(format!(
+                "{} ballots were submitted outside of {} and are dropped (the earliest at {}, the latest at {})",
+                self.num_rejected,
+                self.describe(),
+                format_timestamp(earliest),
+                format_timestamp(latest)
+            ));
+        }
+        if self.num_unparseable > 0 {
+            messages.push(format!(
+                "{} ballots have a timestamp that cannot be read and are kept",
+                self.num_unparseable
+            ));
+        }
+        if !messages.is_empty() {
+            codes::warning(
+                WarningCode::OutsideTimeWindow,
+                format!("{}: {}", path, messages.join("; ")),
+            );
+        }
+        Ok(())
+    }
+}
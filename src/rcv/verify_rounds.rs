@@ -0,0 +1,124 @@
+// Comparison of official round tallies with the tabulation of the ballots.
+
+use ranked_voting::verify::VerificationReport;
+
+use crate::rcv::*;
+
+fn invalid(path: &str, reason: &str) -> RcvError {
+    RcvError::OfficialRounds {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+// Counts are numbers, or strings as in the summaries.
+fn read_count(js: &JSValue) -> Option<u64> {
+    match js {
+        JSValue::Number(n) => n.as_u64(),
+        JSValue::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Reads the official rounds, as a list of `{"round": 1, "tally": {"A": 10, ...}}` objects.
+///
+/// The `results` section of a summary has the same format, so a summary can also be used.
+pub fn read_official_rounds(path: &str) -> RcvResult<Vec<RoundStats>> {
+    let contents = fs::read_to_string(path).context(OpeningJsonSnafu { path })?;
+    let js: JSValue = serde_json::from_str(&contents).context(ParsingJsonSnafu {})?;
+    let rounds_js = match js.get("results") {
+        Some(results) => results,
+        None => &js,
+    }
+    .as_array()
+    .ok_or_else(|| invalid(path, "expected a list of rounds"))?;
+
+    let mut rounds: Vec<RoundStats> = Vec::new();
+    for round_js in rounds_js.iter() {
+        let round = round_js["round"]
+            .as_u64()
+            .filter(|r| *r > 0)
+            .ok_or_else(|| invalid(path, "each round needs a round number, starting at 1"))?;
+        let round = u32::try_from(round)
+            .map_err(|_| invalid(path, &format!("the round number {} is too large", round)))?;
+        let tally_js = round_js["tally"]
+            .as_object()
+            .ok_or_else(|| invalid(path, &format!("round {} has no tally", round)))?;
+        let mut tally: Vec<(String, u64)> = Vec::new();
+        for (name, count_js) in tally_js.iter() {
+            let count = read_count(count_js).ok_or_else(|| {
+                invalid(
+                    path,
+                    &format!("round {}: invalid count for candidate {}", round, name),
+                )
+            })?;
+            tally.push((name.clone(), count));
+        }
+        rounds.push(RoundStats::new(RoundId(round), tally));
+    }
+    Ok(rounds)
+}
+
+// A number of JSON, with the precision of a float beyond the range of i64.
+fn delta_to_json(delta: i128) -> JSValue {
+    i64::try_from(delta).map_or_else(|_| JSValue::from(delta as f64), JSValue::from)
+}
+
+pub fn report_to_json(report: &VerificationReport) -> JSValue {
+    let divergence = report.divergence.as_ref().map(|d| {
+        let deltas: Vec<JSValue> = d
+            .deltas
+            .iter()
+            .map(|cd| {
+                json!({
+                    "candidate": cd.name,
+                    "official": cd.official,
+                    "computed": cd.computed,
+                    "delta": delta_to_json(cd.delta()),
+                })
+            })
+            .collect();
+        json!({
+            "round": d.round.0,
+            "missingRound": d.missing_round,
+            "deltas": deltas,
+        })
+    });
+    json!({
+        "match": report.is_match(),
+        "matchingRounds": report.matching_rounds,
+        "computedRounds": report.computed_rounds,
+        "divergence": divergence,
+    })
+}
+
+pub fn report_to_text(report: &VerificationReport) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    match report.divergence.as_ref() {
+        None => lines.push(format!(
+            "The {} official rounds match the tabulation ({} rounds)",
+            report.matching_rounds, report.computed_rounds
+        )),
+        Some(d) if d.missing_round => lines.push(format!(
+            "Round {} is not in the tabulation, which has {} rounds",
+            d.round, report.computed_rounds
+        )),
+        Some(d) => {
+            lines.push(format!("First divergence in round {}", d.round));
+            lines.push(format!(
+                "{:>20} {:>10} {:>10} {:>10}",
+                "candidate", "official", "computed", "delta"
+            ));
+            for cd in d.deltas.iter() {
+                lines.push(format!(
+                    "{:>20} {:>10} {:>10} {:>+10}",
+                    cd.name,
+                    cd.official,
+                    cd.computed,
+                    cd.delta()
+                ));
+            }
+        }
+    }
+    lines.join("\n")
+}
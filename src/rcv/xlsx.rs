@@ -0,0 +1,336 @@
+// Export of the results to an Excel workbook: a summary sheet, a candidates sheet and one sheet
+// per round.
+//
+// The workbook is written directly as the XML parts of the Office Open XML format, in a zip
+// archive. The strings are inline strings, so that no shared string table is needed.
+
+use std::io::Write;
+
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use crate::rcv::*;
+
+/// The content of a cell. The counts are numeric cells, not strings.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    Empty,
+}
+
+fn text(s: &str) -> Cell {
+    Cell::Text(s.to_string())
+}
+
+fn number(x: u64) -> Cell {
+    Cell::Number(x as f64)
+}
+
+/// A worksheet, as a list of rows.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Sheet {
+    pub name: String,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+// The share in percent, with one decimal, like the first choice shares of the summary.
+fn share(count: u64, total: u64, rounding: PercentRounding) -> Cell {
+    if total == 0 {
+        Cell::Empty
+    } else {
+        Cell::Number(rounding.format(count, total, 1).parse().unwrap_or(0.0))
+    }
+}
+
+fn summary_sheet(config: &RcvConfig, result: &VotingResult, rounds: &[&RoundStats]) -> Sheet {
+    let settings = &config.output_settings;
+    let total = |rs: Option<&&RoundStats>| -> u64 {
+        rs.map(|rs| rs.tally.iter().map(|(_, c)| c).sum())
+            .unwrap_or(0)
+    };
+    let first_round_votes = total(rounds.first());
+    let final_round_votes = total(rounds.last());
+    let optional = |s: &Option<String>| s.as_deref().map(text).unwrap_or(Cell::Empty);
+    let winners = match (&result.winners, result.nota_prevailed) {
+        (Some(w), _) => text(&w.join(", ")),
+        (None, true) => text("none of the above"),
+        (None, false) => Cell::Empty,
+    };
+    let mut rows = vec![
+        vec![text("Contest"), text(&settings.contest_name)],
+        vec![text("Date"), optional(&settings.contest_date)],
+        vec![
+            text("Jurisdiction"),
+            optional(&settings.contest_juridiction),
+        ],
+        vec![text("Office"), optional(&settings.contest_office)],
+        vec![text("Winners"), winners],
+        vec![text("Threshold"), number(result.threshold)],
+        vec![text("Rounds"), number(rounds.len() as u64)],
+        vec![text("First round votes"), number(first_round_votes)],
+        vec![text("Final round votes"), number(final_round_votes)],
+        vec![
+            text("Exhausted ballots"),
+            number(
+                rounds
+                    .last()
+                    .map(|rs| rs.inactive_ballots.total())
+                    .unwrap_or(0),
+            ),
+        ],
+    ];
+    if let Some(path) = settings.rendered_from.as_ref() {
+        rows.push(vec![
+            text("Source"),
+            text(&format!("{}: {}", render::RENDERED_NOTE, path)),
+        ]);
+    }
+    Sheet {
+        name: "Summary".to_string(),
+        rows,
+    }
+}
+
+fn candidates_sheet(result: &VotingResult) -> Sheet {
+    let winners: Vec<&String> = result.winners.iter().flatten().collect();
+    let mut rows = vec![vec![
+        text("Candidate"),
+        text("Code"),
+        text("Status"),
+        text("Round"),
+    ]];
+    for c in result.candidates.iter() {
+        let eliminated = result
+            .elimination_order
+            .iter()
+            .find(|e| !e.elected && e.name == c.name)
+            .map(|e| e.round.0);
+        let (status, round) = if winners.contains(&&c.name) {
+            ("elected", Cell::Empty)
+        } else if c.excluded {
+            ("excluded", Cell::Empty)
+        } else if let Some(round) = eliminated {
+            ("eliminated", number(round as u64))
+        } else {
+            ("not elected", Cell::Empty)
+        };
+        rows.push(vec![
+            text(&c.name),
+            c.code.as_deref().map(text).unwrap_or(Cell::Empty),
+            text(status),
+            round,
+        ]);
+    }
+    Sheet {
+        name: "Candidates".to_string(),
+        rows,
+    }
+}
+
+fn round_sheet(rs: &RoundStats, order: &NameOrder, rounding: PercentRounding) -> Sheet {
+    let round_votes: u64 = rs.tally.iter().map(|(_, c)| c).sum();
+    let mut rows = vec![vec![
+        text("Candidate"),
+        text("Votes"),
+        text("Percent"),
+        text("Status"),
+    ]];
+    // The candidates with the most votes first.
+    let mut tally: Vec<&(String, u64)> = rs.tally.iter().collect();
+    tally.sort_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then_with(|| order.compare(n1, n2)));
+    for (name, count) in tally {
+        let status = if rs.tally_results_elected.contains(name) {
+            "elected"
+        } else if rs.tally_result_eliminated.iter().any(|es| es.name == *name) {
+            "eliminated"
+        } else {
+            "continuing"
+        };
+        rows.push(vec![
+            text(name),
+            number(*count),
+            share(*count, round_votes, rounding),
+            text(status),
+        ]);
+    }
+    // The ballots inactive at the start of the round, as in the summary.
+    rows.push(vec![text("Exhausted"), number(rs.inactive_ballots.total())]);
+    Sheet {
+        name: format!("Round {}", rs.round.0),
+        rows,
+    }
+}
+
+/// The sheets of the workbook: the summary, the candidates and one sheet per round, with the
+/// candidates sorted by their votes. The informational rounds (see `continueToCompletion`) are not included.
+pub fn workbook_sheets(
+    config: &RcvConfig,
+    result: &VotingResult,
+    order: &NameOrder,
+    rounding: PercentRounding,
+) -> Vec<Sheet> {
+    let rounds: Vec<&RoundStats> = result
+        .round_stats
+        .iter()
+        .filter(|rs| !rs.informational)
+        .collect();
+    let mut sheets = vec![
+        summary_sheet(config, result, &rounds),
+        candidates_sheet(result),
+    ];
+    sheets.extend(rounds.iter().map(|rs| round_sheet(rs, order, rounding)));
+    sheets
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// The name of a column: A to Z, then AA, AB, ...
+fn column_name(idx: usize) -> String {
+    let mut idx = idx + 1;
+    let mut name = Vec::new();
+    while idx > 0 {
+        let rem = (idx - 1) % 26;
+        name.push(b'A' + rem as u8);
+        idx = (idx - 1) / 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+fn sheet_xml(sheet: &Sheet) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    );
+    for (row_idx, row) in sheet.rows.iter().enumerate() {
+        xml.push_str(&format!(r#"<row r="{}">"#, row_idx + 1));
+        for (col_idx, cell) in row.iter().enumerate() {
+            let reference = format!("{}{}", column_name(col_idx), row_idx + 1);
+            match cell {
+                Cell::Text(s) => xml.push_str(&format!(
+                    r#"<c r="{}" t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#,
+                    reference,
+                    escape(s)
+                )),
+                Cell::Number(x) => {
+                    xml.push_str(&format!(r#"<c r="{}"><v>{}</v></c>"#, reference, x))
+                }
+                Cell::Empty => {}
+            }
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+    xml
+}
+
+fn content_types_xml(num_sheets: usize) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>"#,
+    );
+    for idx in 1..=num_sheets {
+        xml.push_str(&format!(
+            r#"<Override PartName="/xl/worksheets/sheet{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+            idx
+        ));
+    }
+    xml.push_str("</Types>");
+    xml
+}
+
+const ROOT_RELS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#;
+
+const STYLES_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><fonts count="1"><font><sz val="11"/><name val="Calibri"/></font></fonts><fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills><borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders><cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs><cellXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/></cellXfs></styleSheet>"#;
+
+fn workbook_xml(sheets: &[Sheet]) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>"#,
+    );
+    for (idx, sheet) in sheets.iter().enumerate() {
+        xml.push_str(&format!(
+            r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#,
+            escape(&sheet.name),
+            idx + 1,
+            idx + 1
+        ));
+    }
+    xml.push_str("</sheets></workbook>");
+    xml
+}
+
+fn workbook_rels_xml(num_sheets: usize) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    );
+    for idx in 1..=num_sheets {
+        xml.push_str(&format!(
+            r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{}.xml"/>"#,
+            idx, idx
+        ));
+    }
+    xml.push_str(&format!(
+        r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#,
+        num_sheets + 1
+    ));
+    xml
+}
+
+fn write_parts(path: &str, parts: &[(String, String)]) -> zip::result::ZipResult<()> {
+    let file = fs::File::create(path)?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, content) in parts.iter() {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Writes the results of the tabulation to an Excel workbook.
+pub fn write_workbook(
+    path: &str,
+    config: &RcvConfig,
+    result: &VotingResult,
+    order: &NameOrder,
+) -> RcvResult<()> {
+    let rounding = config.output_settings.percent_rounding()?;
+    write_sheets(path, &workbook_sheets(config, result, order, rounding))
+}
+
+/// Writes the sheets to an Excel workbook.
+pub fn write_sheets(path: &str, sheets: &[Sheet]) -> RcvResult<()> {
+    let mut parts: Vec<(String, String)> = vec![
+        (
+            "[Content_Types].xml".to_string(),
+            content_types_xml(sheets.len()),
+        ),
+        ("_rels/.rels".to_string(), ROOT_RELS_XML.to_string()),
+        ("xl/workbook.xml".to_string(), workbook_xml(sheets)),
+        (
+            "xl/_rels/workbook.xml.rels".to_string(),
+            workbook_rels_xml(sheets.len()),
+        ),
+        ("xl/styles.xml".to_string(), STYLES_XML.to_string()),
+    ];
+    for (idx, sheet) in sheets.iter().enumerate() {
+        parts.push((
+            format!("xl/worksheets/sheet{}.xml", idx + 1),
+            sheet_xml(sheet),
+        ));
+    }
+    write_parts(path, &parts).context(XlsxWriteSnafu { path })?;
+    info!("write_sheets: {} sheets written to {}", sheets.len(), path);
+    Ok(())
+}
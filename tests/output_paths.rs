@@ -0,0 +1,85 @@
+// The errors of the paths of the outputs, as printed by the binary: the flag that caused the
+// error and the suggested fix must be in the message.
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("timrcv_output_paths_{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/csv_simple_2/example.csv");
+    fs::copy(example, dir.join("example.csv")).unwrap();
+    dir
+}
+
+// Runs a command that must fail, and returns its log.
+fn run_failing(dir: &Path, args: &[&str]) -> String {
+    let output = Command::cargo_bin("timrcv")
+        .unwrap()
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        !output.status.success(),
+        "timrcv {:?} succeeded:\n{}",
+        args,
+        log
+    );
+    log
+}
+
+#[test]
+fn out_is_a_directory() {
+    let dir = work_dir("directory");
+    fs::create_dir_all(dir.join("results")).unwrap();
+    let log = run_failing(&dir, &["--input", "example.csv", "--out", "results/"]);
+    assert!(
+        log.contains(
+            "--out results/ is a directory, append a file name, for example results/summary.json"
+        ),
+        "{}",
+        log
+    );
+    assert!(log.contains("[E001]"), "{}", log);
+}
+
+#[test]
+fn out_xlsx_is_a_directory() {
+    let dir = work_dir("xlsx_directory");
+    fs::create_dir_all(dir.join("results")).unwrap();
+    let log = run_failing(&dir, &["--input", "example.csv", "--out-xlsx", "results"]);
+    assert!(
+        log.contains("--out-xlsx results is a directory, append a file name, for example results/results.xlsx"),
+        "{}",
+        log
+    );
+}
+
+#[test]
+fn out_flat_csv_missing_parent() {
+    let dir = work_dir("missing_parent");
+    let log = run_failing(
+        &dir,
+        &[
+            "--input",
+            "example.csv",
+            "--out-flat-csv",
+            "missing/rounds.csv",
+        ],
+    );
+    assert!(
+        log.contains("the directory missing of --out-flat-csv missing/rounds.csv does not exist, create it first (mkdir -p missing)"),
+        "{}",
+        log
+    );
+    // The check is done before the tabulation.
+    assert!(!log.contains("elected"), "{}", log);
+}
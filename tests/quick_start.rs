@@ -0,0 +1,91 @@
+// The commands of the README and of the quick start of the documentation, run with the built
+// binary on the files of the examples. The commands are copied as they are documented: a
+// renamed flag breaks these tests before it breaks the instructions of the users.
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The documented commands are run from a copy of the files of the example, so that their outputs
+// are not written into the tests directory.
+fn example_dir(name: &str, files: &[&str]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("timrcv_quick_start_{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    for file in files.iter() {
+        let src = root.join(file);
+        fs::copy(&src, dir.join(src.file_name().unwrap())).unwrap();
+    }
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = Command::cargo_bin("timrcv")
+        .unwrap()
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        output.status.success(),
+        "timrcv {:?} failed:\n{}",
+        args,
+        log
+    );
+    log
+}
+
+// The arguments of the command of the Google Forms example.
+const GOOGLE_FORMS_ARGS: [&str; 10] = [
+    "-i",
+    "test voting.xlsx",
+    "--input-type",
+    "msforms_likert_transpose",
+    "--choices",
+    "Who do you want to vote for? [First choice]",
+    "--choices",
+    "Who do you want to vote for? [Choice #2]",
+    "--choices",
+    "Who do you want to vote for? [Choice #3]",
+];
+
+#[test]
+fn readme_csv() {
+    // timrcv --input example.csv
+    let dir = example_dir("readme_csv", &["csv_simple_2/example.csv"]);
+    let log = run(&dir, &["--input", "example.csv"]);
+    assert!(log.contains("6 A -> elected"), "{}", log);
+    assert!(
+        log.contains("A won with 6 of the 6 active ballots"),
+        "{}",
+        log
+    );
+}
+
+#[test]
+fn quick_start_google_forms() {
+    let dir = example_dir("google_forms", &["quick_start/test voting.xlsx"]);
+    let log = run(&dir, &GOOGLE_FORMS_ARGS);
+    assert!(log.contains("Processing 3 votes"), "{}", log);
+    assert!(log.contains("2  66.7% Alice -> elected"), "{}", log);
+}
+
+#[test]
+fn quick_start_google_forms_out() {
+    let dir = example_dir("google_forms_out", &["quick_start/test voting.xlsx"]);
+    let mut args = GOOGLE_FORMS_ARGS.to_vec();
+    args.extend(["--out", "my_election_results.json"]);
+    run(&dir, &args);
+    let contents = fs::read_to_string(dir.join("my_election_results.json")).unwrap();
+    let summary: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    let results = summary["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["tally"]["Alice"], "2");
+    assert_eq!(results[0]["tallyResults"][0]["elected"], "Alice");
+}